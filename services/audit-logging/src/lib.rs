@@ -84,15 +84,17 @@ pub fn verify_audit_integrity(entries: &[AuditEntry]) -> bool {
         return true;
     }
     
-    for i in 1..entries.len() {
-        let prev_entry = &entries[i - 1];
+    for i in 0..entries.len() {
         let curr_entry = &entries[i];
-        
+
         // Verify hash chain
-        if curr_entry.previous_hash.as_ref() != Some(&prev_entry.current_hash) {
-            return false;
+        if i > 0 {
+            let prev_entry = &entries[i - 1];
+            if curr_entry.previous_hash.as_ref() != Some(&prev_entry.current_hash) {
+                return false;
+            }
         }
-        
+
         // Recompute hash to verify
         let entry_data = format!(
             "{}|{}|{}|{}|{}|{}",
@@ -103,12 +105,16 @@ pub fn verify_audit_integrity(entries: &[AuditEntry]) -> bool {
             curr_entry.resource,
             serde_json::to_string(&curr_entry.result).unwrap()
         );
-        
-        let hash_input = format!("{}|{}", prev_entry.current_hash, entry_data);
+
+        let hash_input = if let Some(prev_hash) = curr_entry.previous_hash.as_ref() {
+            format!("{}|{}", prev_hash, entry_data)
+        } else {
+            entry_data
+        };
         let mut hasher = Sha256::new();
         hasher.update(hash_input.as_bytes());
         let expected_hash = format!("{:x}", hasher.finalize());
-        
+
         if curr_entry.current_hash != expected_hash {
             return false;
         }
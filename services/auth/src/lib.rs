@@ -1,7 +1,10 @@
+// ForgeWorks Core - Authentication Module
+// OIDC, SAML, and Role-Based Access Control
+
 pub mod oidc;
 pub mod saml;
 pub mod roles;
 
-pub use oidc::*;
-pub use saml::*;
-pub use roles::*;
+pub use oidc::{Claims, Role, validate_token, has_permission, authorize_custodian_access};
+pub use saml::{SamlAssertion, validate_assertion, extract_roles};
+pub use roles::{UserContext, RolePermissions, check_permission};
@@ -2,7 +2,6 @@
 // Enterprise SSO Integration
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
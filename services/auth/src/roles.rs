@@ -2,7 +2,7 @@
 // Centralized role definitions and permissions
 
 use serde::{Deserialize, Serialize};
-use crate::auth::oidc::Role;
+use crate::oidc::Role;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserContext {
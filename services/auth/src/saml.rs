@@ -2,7 +2,7 @@
 // Enterprise SSO Integration (Alternative to OIDC)
 
 use serde::{Deserialize, Serialize};
-use crate::auth::oidc::{Role, has_permission};
+use crate::oidc::Role;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SamlAssertion {
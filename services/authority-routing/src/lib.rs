@@ -3,7 +3,6 @@
 
 use serde::{Deserialize, Serialize};
 use legal_classification::{LegalClassification, RouteTarget};
-use device_analysis::RiskLevel;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RoutingPath {
@@ -168,7 +167,6 @@ pub fn generate_routing_result(
 mod tests {
     use super::*;
     use legal_classification::{Jurisdiction, LegalStatus, AuthorizationType};
-    use device_analysis::RiskLevel;
 
     #[test]
     fn test_route_to_oem() {
@@ -0,0 +1,50 @@
+// BootForge USB - protocol detection example
+// Run with: cargo run --example detect_protocols
+// Classifies every enumerable device straight off the primary libusb
+// path, reporting how much each detection can be trusted.
+
+use std::collections::HashMap;
+
+use bootforge_usb::protocols::classify_device_info_protocols;
+
+fn main() {
+    let devices = match bootforge_usb::enumerate_libusb() {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("enumeration failed: {}", e);
+            return;
+        }
+    };
+    if devices.is_empty() {
+        eprintln!("no devices enumerable");
+        return;
+    }
+
+    // Identical units share a VID/PID; count them so duplicates get
+    // their port and serial printed for disambiguation.
+    let mut counts: HashMap<(u16, u16), usize> = HashMap::new();
+    for device in &devices {
+        *counts.entry((device.vendor_id, device.product_id)).or_default() += 1;
+    }
+
+    for device in &devices {
+        let classification = classify_device_info_protocols(device);
+        print!("{}: ", device);
+        if counts[&(device.vendor_id, device.product_id)] > 1 {
+            print!(
+                "[port {}, serial {}] ",
+                device.port_path.as_deref().unwrap_or("?"),
+                device.serial_number.as_deref().unwrap_or("?")
+            );
+        }
+        if classification.all().is_empty() {
+            println!("no known protocols");
+            continue;
+        }
+        let detections: Vec<String> = classification
+            .iter()
+            .map(|(protocol, confidence)| format!("{:?} ({:?})", protocol, confidence))
+            .collect();
+        println!("{}", detections.join(", "));
+    }
+}
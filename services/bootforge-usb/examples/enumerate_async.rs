@@ -0,0 +1,30 @@
+// BootForge USB - async enumeration example
+// Run with: cargo run --example enumerate_async --features tokio
+
+use bootforge_usb::enumeration::EnumerationOptions;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    // Runs on the blocking pool; the executor stays free for other work.
+    match bootforge_usb::asynchronous::enumerate_libusb_report_async(EnumerationOptions::default())
+        .await
+    {
+        Ok(report) => {
+            for device in &report.devices {
+                println!(
+                    "{:04x}:{:04x} {} {}",
+                    device.vendor_id,
+                    device.product_id,
+                    device.product.as_deref().unwrap_or("-"),
+                    device.port_path.as_deref().unwrap_or("-"),
+                );
+            }
+            eprintln!(
+                "{} devices, {} skipped",
+                report.devices.len(),
+                report.skipped.len()
+            );
+        }
+        Err(e) => eprintln!("enumeration failed: {}", e),
+    }
+}
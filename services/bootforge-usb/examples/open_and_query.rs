@@ -0,0 +1,46 @@
+// BootForge USB - open-and-query example
+// Run with: cargo run --example open_and_query
+// Opens the first enumerable device and reads its configuration
+// descriptor through the control-transfer layer.
+
+use std::time::Duration;
+
+use bootforge_usb::transfer::ControlTransfer;
+
+fn main() {
+    let devices = match bootforge_usb::enumerate_libusb() {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("enumeration failed: {}", e);
+            return;
+        }
+    };
+    let Some(device) = devices.first() else {
+        eprintln!("no devices enumerable");
+        return;
+    };
+    println!(
+        "opening {:04x}:{:04x} ({})",
+        device.vendor_id,
+        device.product_id,
+        device.product.as_deref().unwrap_or("-"),
+    );
+
+    let handle = match device.open() {
+        Ok(handle) => handle,
+        Err(e) => {
+            eprintln!("open failed: {}", e);
+            return;
+        }
+    };
+
+    let mut control = ControlTransfer::new(handle);
+    match control.read_config_descriptor(0, Duration::from_millis(500)) {
+        Ok(descriptor) => println!(
+            "configuration descriptor: {} bytes, bNumInterfaces={}",
+            descriptor.len(),
+            descriptor.get(4).copied().unwrap_or(0),
+        ),
+        Err(e) => eprintln!("descriptor read failed: {}", e),
+    }
+}
@@ -0,0 +1,361 @@
+// BootForge USB - Bus analysis
+// Offline estimates over a topology snapshot; nothing here touches the
+// bus.
+
+use crate::topology::{EndpointInfo, EndpointKind, Speed, TopologyNode};
+
+// Periodic bandwidth budgets in bytes per second. These are planning
+// numbers, not guarantees: 90% of the full-speed frame is reservable for
+// periodic traffic per USB 2.0 section 5.11.1, 80% of the high-speed
+// microframe, and for SuperSpeed we apply the same 80% to a nominal
+// 500 MB/s of post-encoding throughput.
+const FS_PERIODIC_BUDGET: f64 = 1_500_000.0 * 0.9;
+const HS_PERIODIC_BUDGET: f64 = 60_000_000.0 * 0.8;
+const SS_PERIODIC_BUDGET: f64 = 500_000_000.0 * 0.8;
+
+/**
+ * One device's share of the periodic reservation.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthConsumer {
+    pub name: String,
+    pub bytes_per_second: f64,
+}
+
+/**
+ * Estimated periodic bandwidth state of one controller.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthEstimate {
+    /// Reservable periodic budget for the controller, bytes per second.
+    pub budget_bytes_per_second: f64,
+    /// Sum of all isochronous/interrupt reservations in the subtree.
+    pub reserved_bytes_per_second: f64,
+    /// reserved / budget, as a percentage; can exceed 100.
+    pub utilization_percent: f64,
+    /// Devices sorted by reservation, largest first; zero-reservation
+    /// nodes (hubs, bulk-only devices) are omitted.
+    pub top_consumers: Vec<BandwidthConsumer>,
+}
+
+/// Reservation of one endpoint in bytes per second, per the speed's
+/// interval semantics.
+fn endpoint_bytes_per_second(speed: Speed, ep: &EndpointInfo) -> f64 {
+    if !matches!(ep.kind, EndpointKind::Isochronous | EndpointKind::Interrupt) {
+        return 0.0;
+    }
+    match speed {
+        Speed::Low | Speed::Full => {
+            // bInterval is in 1 ms frames; one wMaxPacketSize packet per
+            // service interval.
+            let interval_frames = ep.interval.max(1) as f64;
+            (ep.max_packet_size & 0x07ff) as f64 * 1000.0 / interval_frames
+        }
+        Speed::High => {
+            // bInterval is an exponent: period = 2^(n-1) microframes of
+            // 125 us. Bits 12:11 of wMaxPacketSize add up to two extra
+            // transactions per microframe for high-bandwidth endpoints.
+            let period_microframes = 1u32 << (ep.interval.clamp(1, 16) - 1);
+            let base = (ep.max_packet_size & 0x07ff) as f64;
+            let transactions = 1.0 + ((ep.max_packet_size >> 11) & 0x3) as f64;
+            base * transactions * 8000.0 / period_microframes as f64
+        }
+        Speed::Super | Speed::SuperPlus => {
+            // The SuperSpeed companion's wBytesPerInterval already folds
+            // in burst and mult; fall back to wMaxPacketSize when the
+            // snapshot lacks the companion.
+            let period_microframes = 1u32 << (ep.interval.clamp(1, 16) - 1);
+            let per_interval = ep
+                .ss_bytes_per_interval
+                .unwrap_or((ep.max_packet_size & 0x07ff) as u32);
+            per_interval as f64 * 8000.0 / period_microframes as f64
+        }
+    }
+}
+
+fn node_bytes_per_second(node: &TopologyNode) -> f64 {
+    node.endpoints
+        .iter()
+        .map(|ep| endpoint_bytes_per_second(node.speed, ep))
+        .sum()
+}
+
+/**
+ * Estimate the periodic (isochronous + interrupt) bandwidth reserved in
+ * the subtree under `controller`, against the controller's reservable
+ * budget.
+ *
+ * This is a planning estimate, not a schedulability proof: it ignores
+ * per-transaction protocol overhead, transaction translator placement
+ * behind high-speed hubs, and host controller scheduling granularity.
+ * Real controllers typically refuse reservations somewhat before the
+ * estimate reaches 100%.
+ */
+pub fn estimate_periodic_bandwidth(controller: &TopologyNode) -> BandwidthEstimate {
+    let budget = match controller.speed {
+        Speed::Low | Speed::Full => FS_PERIODIC_BUDGET,
+        Speed::High => HS_PERIODIC_BUDGET,
+        Speed::Super | Speed::SuperPlus => SS_PERIODIC_BUDGET,
+    };
+
+    let mut top_consumers: Vec<BandwidthConsumer> = controller
+        .iter()
+        .filter_map(|node| {
+            let bytes = node_bytes_per_second(node);
+            (bytes > 0.0).then(|| BandwidthConsumer {
+                name: node.name.clone(),
+                bytes_per_second: bytes,
+            })
+        })
+        .collect();
+    top_consumers.sort_by(|a, b| {
+        b.bytes_per_second
+            .partial_cmp(&a.bytes_per_second)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let reserved: f64 = top_consumers.iter().map(|c| c.bytes_per_second).sum();
+
+    BandwidthEstimate {
+        budget_bytes_per_second: budget,
+        reserved_bytes_per_second: reserved,
+        utilization_percent: reserved / budget * 100.0,
+        top_consumers,
+    }
+}
+
+/**
+ * The upstream link that caps a device below its claimed capability.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bottleneck {
+    /// Name of the limiting node, e.g. "hub 2109:2812".
+    pub limiting_node: String,
+    pub limiting_speed: Speed,
+}
+
+impl std::fmt::Display for Bottleneck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "limited to {} by {}", self.limiting_speed, self.limiting_node)
+    }
+}
+
+/**
+ * Per-device outcome of the bottleneck walk.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceBottleneck {
+    pub device: String,
+    pub negotiated: Speed,
+    /// None when the device runs at its capability, or when the slow
+    /// link is the device's own (bad cable rather than an upstream hub).
+    pub speed_bottleneck: Option<Bottleneck>,
+}
+
+/**
+ * Identify devices running below the capability their descriptor claims
+ * because of a slower upstream hop - the classic case being a USB3
+ * device behind a 2.0-only hub on a 3.x port.
+ *
+ * Reported for every node carrying a usb_version. The limiting node is
+ * the slowest hop strictly upstream of the device; when even the
+ * slowest hop is faster than the device's negotiated speed, the limit
+ * is the device's own link and no bottleneck is reported.
+ */
+pub fn speed_bottlenecks(root: &TopologyNode) -> Vec<DeviceBottleneck> {
+    fn walk<'a>(
+        node: &'a TopologyNode,
+        slowest_upstream: Option<(&'a str, Speed)>,
+        out: &mut Vec<DeviceBottleneck>,
+    ) {
+        if let Some(usb_version) = node.usb_version {
+            let capability = Speed::ceiling_for(usb_version);
+            let speed_bottleneck = slowest_upstream
+                .filter(|(_, speed)| *speed < capability && *speed <= node.speed)
+                .map(|(name, speed)| Bottleneck {
+                    limiting_node: name.to_string(),
+                    limiting_speed: speed,
+                });
+            out.push(DeviceBottleneck {
+                device: node.name.clone(),
+                negotiated: node.speed,
+                speed_bottleneck,
+            });
+        }
+
+        let next = match slowest_upstream {
+            Some((name, speed)) if speed <= node.speed => Some((name, speed)),
+            _ => Some((node.name.as_str(), node.speed)),
+        };
+        for child in &node.children {
+            walk(child, next, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(root, None, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::BcdVersion;
+
+    fn endpoint(kind: EndpointKind, mps: u16, interval: u8) -> EndpointInfo {
+        EndpointInfo {
+            address: 0x81,
+            kind,
+            max_packet_size: mps,
+            interval,
+            ss_bytes_per_interval: None,
+        }
+    }
+
+    #[test]
+    fn test_full_speed_interrupt_math() {
+        // Mouse: 8-byte interrupt endpoint every 10 ms = 800 B/s.
+        let mouse = TopologyNode::new("Mouse", Speed::Full)
+            .with_endpoints(vec![endpoint(EndpointKind::Interrupt, 8, 10)]);
+        let controller = TopologyNode::new("ohci bus 2", Speed::Full).with_children(vec![mouse]);
+
+        let estimate = estimate_periodic_bandwidth(&controller);
+        assert_eq!(estimate.reserved_bytes_per_second, 800.0);
+        assert_eq!(estimate.budget_bytes_per_second, 1_350_000.0);
+        assert!((estimate.utilization_percent - 800.0 / 1_350_000.0 * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_high_speed_high_bandwidth_iso_math() {
+        // Camera: wMaxPacketSize 0x1400 = 1024 bytes x 3 transactions per
+        // microframe, bInterval 1 => every microframe.
+        // 1024 * 3 * 8000 = 24_576_000 B/s against a 48 MB/s budget.
+        let camera = TopologyNode::new("Camera", Speed::High)
+            .with_endpoints(vec![endpoint(EndpointKind::Isochronous, 0x1400, 1)]);
+        let controller = TopologyNode::new("ehci bus 1", Speed::High).with_children(vec![camera]);
+
+        let estimate = estimate_periodic_bandwidth(&controller);
+        assert_eq!(estimate.reserved_bytes_per_second, 24_576_000.0);
+        assert!((estimate.utilization_percent - 51.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_high_speed_interval_exponent() {
+        // bInterval 4 => 2^3 = 8 microframes: 512 * 8000 / 8 = 512_000.
+        let dev = TopologyNode::new("HID", Speed::High)
+            .with_endpoints(vec![endpoint(EndpointKind::Interrupt, 512, 4)]);
+        let controller = TopologyNode::new("ehci bus 1", Speed::High).with_children(vec![dev]);
+
+        let estimate = estimate_periodic_bandwidth(&controller);
+        assert_eq!(estimate.reserved_bytes_per_second, 512_000.0);
+    }
+
+    #[test]
+    fn test_superspeed_uses_companion_bytes_per_interval() {
+        // 48 KiB per service interval, every microframe:
+        // 49152 * 8000 = 393_216_000 B/s => 98.304% of the SS budget.
+        let mut ep = endpoint(EndpointKind::Isochronous, 1024, 1);
+        ep.ss_bytes_per_interval = Some(49_152);
+        let camera = TopologyNode::new("SS Camera", Speed::Super).with_endpoints(vec![ep]);
+        let controller = TopologyNode::new("xhci bus 4", Speed::Super).with_children(vec![camera]);
+
+        let estimate = estimate_periodic_bandwidth(&controller);
+        assert_eq!(estimate.reserved_bytes_per_second, 393_216_000.0);
+        assert!((estimate.utilization_percent - 98.304).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bulk_and_control_do_not_reserve() {
+        let disk = TopologyNode::new("Disk", Speed::High).with_endpoints(vec![
+            endpoint(EndpointKind::Bulk, 512, 0),
+            endpoint(EndpointKind::Control, 64, 0),
+        ]);
+        let controller = TopologyNode::new("ehci bus 1", Speed::High).with_children(vec![disk]);
+
+        let estimate = estimate_periodic_bandwidth(&controller);
+        assert_eq!(estimate.reserved_bytes_per_second, 0.0);
+        assert!(estimate.top_consumers.is_empty());
+    }
+
+    #[test]
+    fn test_top_consumers_sorted_and_recursive() {
+        let camera = TopologyNode::new("Camera", Speed::High)
+            .with_endpoints(vec![endpoint(EndpointKind::Isochronous, 1024, 1)]);
+        let headset = TopologyNode::new("Headset", Speed::Full)
+            .with_endpoints(vec![endpoint(EndpointKind::Isochronous, 192, 1)]);
+        // Headset sits behind a hub; the hub itself reserves nothing.
+        let hub = TopologyNode::new("Hub", Speed::High).with_children(vec![headset]);
+        let controller =
+            TopologyNode::new("ehci bus 1", Speed::High).with_children(vec![camera, hub]);
+
+        let estimate = estimate_periodic_bandwidth(&controller);
+        let names: Vec<&str> = estimate
+            .top_consumers
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Camera", "Headset"]);
+        assert_eq!(
+            estimate.reserved_bytes_per_second,
+            1024.0 * 8000.0 + 192.0 * 1000.0
+        );
+    }
+
+    #[test]
+    fn test_usb3_device_behind_20_hub_reports_bottleneck() {
+        // A 3.2 SSD negotiating High Speed because the hub in between is
+        // 2.0-only, even though the root port is SuperSpeed.
+        let ssd = TopologyNode::new("SSD", Speed::High).with_usb_version(BcdVersion(0x0320));
+        let hub = TopologyNode::new("hub 2109:2812", Speed::High).with_children(vec![ssd]);
+        let root = TopologyNode::new("xhci bus 3", Speed::Super).with_children(vec![hub]);
+
+        let report = speed_bottlenecks(&root);
+        assert_eq!(report.len(), 1);
+        let entry = &report[0];
+        assert_eq!(entry.device, "SSD");
+        assert_eq!(entry.negotiated, Speed::High);
+        let bottleneck = entry.speed_bottleneck.as_ref().unwrap();
+        assert_eq!(bottleneck.limiting_node, "hub 2109:2812");
+        assert_eq!(bottleneck.limiting_speed, Speed::High);
+        assert_eq!(
+            bottleneck.to_string(),
+            "limited to 480 Mbps High Speed by hub 2109:2812"
+        );
+    }
+
+    #[test]
+    fn test_device_at_capability_has_no_bottleneck() {
+        let ssd = TopologyNode::new("SSD", Speed::Super).with_usb_version(BcdVersion(0x0300));
+        let root = TopologyNode::new("xhci bus 3", Speed::Super).with_children(vec![ssd]);
+
+        let report = speed_bottlenecks(&root);
+        assert_eq!(report[0].speed_bottleneck, None);
+    }
+
+    #[test]
+    fn test_device_slower_than_upstream_blames_nothing() {
+        // Bad cable: the device itself negotiated Full Speed while every
+        // upstream hop runs SuperSpeed. No upstream node is at fault.
+        let ssd = TopologyNode::new("SSD", Speed::Full).with_usb_version(BcdVersion(0x0320));
+        let hub = TopologyNode::new("SS hub", Speed::Super).with_children(vec![ssd]);
+        let root = TopologyNode::new("xhci bus 3", Speed::Super).with_children(vec![hub]);
+
+        let report = speed_bottlenecks(&root);
+        assert_eq!(report[0].speed_bottleneck, None);
+    }
+
+    #[test]
+    fn test_nested_hubs_pick_the_slowest_upstream_hop() {
+        // SS root -> 2.0 hub -> SS-capable hub (also dragged down to High)
+        // -> device. The 2.0 hub is the slowest hop and gets the blame.
+        let ssd = TopologyNode::new("SSD", Speed::High).with_usb_version(BcdVersion(0x0320));
+        let inner = TopologyNode::new("inner hub", Speed::High).with_children(vec![ssd]);
+        let outer = TopologyNode::new("hub 2109:2812", Speed::High).with_children(vec![inner]);
+        let root = TopologyNode::new("xhci bus 3", Speed::Super).with_children(vec![outer]);
+
+        let report = speed_bottlenecks(&root);
+        let bottleneck = report[0].speed_bottleneck.as_ref().unwrap();
+        assert_eq!(bottleneck.limiting_node, "hub 2109:2812");
+    }
+}
@@ -0,0 +1,106 @@
+// BootForge USB - Async adapters (feature "tokio")
+// Enumeration does blocking I/O - libusb transfers, sysfs reads, string
+// descriptor round-trips - and stalls an async executor when called
+// from a handler. These adapters run the pipeline on the runtime's
+// blocking pool and bridge event channels into tokio ones; the
+// underlying code stays synchronous.
+
+use crate::enumeration::{
+    enumerate_libusb_report_with, EnumerationOptions, EnumerationReport, UsbDeviceInfo,
+};
+use crate::error::UsbError;
+use crate::events::DeviceEvent;
+
+/**
+ * `enumerate_libusb` off the executor: the pass runs on tokio's
+ * blocking pool.
+ */
+pub async fn enumerate_libusb_async() -> Result<Vec<UsbDeviceInfo>, UsbError> {
+    Ok(enumerate_libusb_report_async(EnumerationOptions::default())
+        .await?
+        .devices)
+}
+
+/**
+ * `enumerate_libusb_report_with` off the executor. Options are taken by
+ * value because the blocking task outlives the caller's borrow.
+ */
+pub async fn enumerate_libusb_report_async(
+    options: EnumerationOptions,
+) -> Result<EnumerationReport, UsbError> {
+    tokio::task::spawn_blocking(move || enumerate_libusb_report_with(&options))
+        .await
+        .map_err(|e| UsbError::Internal(format!("enumeration task failed: {}", e)))?
+}
+
+/**
+ * Bridge a blocking event receiver into a tokio channel so watcher
+ * events can be consumed with `.recv().await`.
+ *
+ * A forwarding thread drains the std receiver; it exits when the
+ * producer hangs up or the returned receiver is dropped.
+ */
+pub fn bridge_events(
+    receiver: std::sync::mpsc::Receiver<DeviceEvent>,
+) -> tokio::sync::mpsc::UnboundedReceiver<DeviceEvent> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for event in receiver {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::DeviceIdentity;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_report_async_returns_without_unwinding() {
+        // Outcome is host-dependent (no USB access in CI); the contract
+        // is completion off the executor, not success.
+        let outcome = runtime().block_on(enumerate_libusb_report_async(
+            EnumerationOptions::default(),
+        ));
+        match outcome {
+            Ok(_) | Err(_) => {}
+        }
+    }
+
+    #[test]
+    fn test_bridge_forwards_and_closes() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut bridged = bridge_events(rx);
+
+        tx.send(DeviceEvent::Disconnected(DeviceIdentity(
+            "usb:18d1:4ee7:A".to_string(),
+        )))
+        .unwrap();
+        tx.send(DeviceEvent::Disconnected(DeviceIdentity(
+            "usb:18d1:4ee7:B".to_string(),
+        )))
+        .unwrap();
+        drop(tx);
+
+        runtime().block_on(async {
+            assert!(matches!(
+                bridged.recv().await,
+                Some(DeviceEvent::Disconnected(id)) if id.0.ends_with(":A")
+            ));
+            assert!(bridged.recv().await.is_some());
+            // Producer hung up: the bridge closes instead of hanging.
+            assert!(bridged.recv().await.is_none());
+        });
+    }
+}
@@ -0,0 +1,375 @@
+// BootForge USB - BOS descriptor parsing
+// Typed view of the Binary Object Store (USB 3.2 section 9.6.2): LPM
+// support, SuperSpeed capabilities, the Container ID that survives
+// re-enumeration, and Billboard data for USB-C dongle diagnostics.
+// Unknown and truncated capabilities are kept as raw blobs rather than
+// failing the whole parse.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::UsbError;
+use crate::topology::Speed;
+
+const DESCRIPTOR_TYPE_BOS: u8 = 0x0f;
+const DESCRIPTOR_TYPE_DEVICE_CAPABILITY: u8 = 0x10;
+
+const CAP_USB2_EXTENSION: u8 = 0x02;
+const CAP_SUPERSPEED: u8 = 0x03;
+const CAP_CONTAINER_ID: u8 = 0x04;
+const CAP_BILLBOARD: u8 = 0x0d;
+
+/**
+ * USB 2.0 Extension capability (type 0x02): link power management
+ * attributes.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Usb2Extension {
+    /// Raw bmAttributes.
+    pub attributes: u32,
+}
+
+impl Usb2Extension {
+    /// LPM supported (bit 1).
+    pub fn lpm_supported(&self) -> bool {
+        self.attributes & 0x02 != 0
+    }
+
+    /// BESL and alternate HIRD supported (bit 2).
+    pub fn besl_supported(&self) -> bool {
+        self.attributes & 0x04 != 0
+    }
+}
+
+/**
+ * SuperSpeed USB Device Capability (type 0x03): the speeds the device
+ * can operate at and its U1/U2 exit latencies.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SuperSpeedCapability {
+    /// LTM capable (bmAttributes bit 1).
+    pub ltm_capable: bool,
+    /// Speeds from the wSpeedsSupported bitmap, slowest first.
+    pub speeds: Vec<Speed>,
+    /// Lowest speed at which all functionality is available
+    /// (bFunctionalitySupport, as a wSpeedsSupported bit index).
+    pub functionality_support: u8,
+    /// bU1DevExitLat, microseconds.
+    pub u1_exit_latency_us: u8,
+    /// wU2DevExitLat, microseconds.
+    pub u2_exit_latency_us: u16,
+}
+
+/**
+ * Container ID capability (type 0x04): a UUID shared by every function
+ * and port of a physical device, stable across re-enumeration - the
+ * strongest identity a device can offer short of a serial number.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct ContainerId(pub [u8; 16]);
+
+impl fmt::Display for ContainerId {
+    /// Canonical 8-4-4-4-12 form over the bytes as transmitted.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7], b[8], b[9], b[10], b[11], b[12],
+            b[13], b[14], b[15]
+        )
+    }
+}
+
+/**
+ * One alternate mode advertised by a Billboard capability.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AlternateMode {
+    /// Standard or vendor ID of the mode (e.g. 0xff01 DisplayPort).
+    pub svid: u16,
+    /// Mode index within the SVID.
+    pub mode: u8,
+    /// iAlternateModeString descriptor index.
+    pub string_index: u8,
+}
+
+/**
+ * Billboard capability (type 0x0d), exposed by USB-C devices whose
+ * alternate-mode negotiation failed or is pending - the reason a dock
+ * enumerates but the display stays dark.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BillboardCapability {
+    /// bPreferredAlternateMode: index into `alternate_modes`.
+    pub preferred_alternate_mode: u8,
+    /// VCONN power needed, raw wVconnPower.
+    pub vconn_power: u16,
+    /// bcdVersion of the Billboard spec.
+    pub version: u16,
+    pub alternate_modes: Vec<AlternateMode>,
+}
+
+/**
+ * One device capability from the BOS, typed where the type is known.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BosCapability {
+    Usb2Extension(Usb2Extension),
+    SuperSpeed(SuperSpeedCapability),
+    ContainerId(ContainerId),
+    Billboard(BillboardCapability),
+    /// A capability type this parser does not know, or a known type too
+    /// truncated to interpret; the raw bytes are kept as-is.
+    Unknown { capability_type: u8, data: Vec<u8> },
+}
+
+/**
+ * Parsed Binary Object Store descriptor.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BosDescriptor {
+    /// wTotalLength as claimed by the header.
+    pub total_length: u16,
+    pub capabilities: Vec<BosCapability>,
+}
+
+impl BosDescriptor {
+    /// Parse the raw descriptor bytes (bLength, bDescriptorType first).
+    /// The header must be intact; individual capabilities degrade to
+    /// `Unknown` blobs when truncated or unrecognised.
+    pub fn parse(raw: &[u8]) -> Result<Self, UsbError> {
+        if raw.len() < 5 || usize::from(raw[0]) < 5 {
+            return Err(UsbError::Parse(format!(
+                "BOS descriptor too short: {} bytes",
+                raw.len()
+            )));
+        }
+        if raw[1] != DESCRIPTOR_TYPE_BOS {
+            return Err(UsbError::Parse(format!(
+                "not a BOS descriptor: type 0x{:02x}",
+                raw[1]
+            )));
+        }
+        let total_length = u16::from_le_bytes([raw[2], raw[3]]);
+
+        let mut capabilities = Vec::new();
+        let mut offset = usize::from(raw[0]);
+        while offset + 3 <= raw.len() {
+            let length = usize::from(raw[offset]);
+            if length < 3 {
+                // A zero/short bLength would loop forever; stop here.
+                break;
+            }
+            let end = (offset + length).min(raw.len());
+            let cap = &raw[offset..end];
+            if cap[1] == DESCRIPTOR_TYPE_DEVICE_CAPABILITY {
+                capabilities.push(parse_capability(cap[2], &cap[3..]));
+            }
+            offset = end;
+        }
+        Ok(BosDescriptor {
+            total_length,
+            capabilities,
+        })
+    }
+
+    /// The Container ID, when the device advertises one.
+    pub fn container_id(&self) -> Option<ContainerId> {
+        self.capabilities.iter().find_map(|cap| match cap {
+            BosCapability::ContainerId(id) => Some(*id),
+            _ => None,
+        })
+    }
+}
+
+/// `data` is the capability payload after bDevCapabilityType; a known
+/// type with a short payload falls back to an Unknown blob.
+fn parse_capability(capability_type: u8, data: &[u8]) -> BosCapability {
+    let unknown = || BosCapability::Unknown {
+        capability_type,
+        data: data.to_vec(),
+    };
+    match capability_type {
+        CAP_USB2_EXTENSION => {
+            if data.len() < 4 {
+                return unknown();
+            }
+            BosCapability::Usb2Extension(Usb2Extension {
+                attributes: u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            })
+        }
+        CAP_SUPERSPEED => {
+            if data.len() < 7 {
+                return unknown();
+            }
+            let speeds_supported = u16::from_le_bytes([data[1], data[2]]);
+            let speeds = [Speed::Low, Speed::Full, Speed::High, Speed::Super]
+                .into_iter()
+                .enumerate()
+                .filter(|(bit, _)| speeds_supported & (1 << bit) != 0)
+                .map(|(_, speed)| speed)
+                .collect();
+            BosCapability::SuperSpeed(SuperSpeedCapability {
+                ltm_capable: data[0] & 0x02 != 0,
+                speeds,
+                functionality_support: data[3],
+                u1_exit_latency_us: data[4],
+                u2_exit_latency_us: u16::from_le_bytes([data[5], data[6]]),
+            })
+        }
+        CAP_CONTAINER_ID => {
+            // bReserved, then the 16 UUID bytes.
+            if data.len() < 17 {
+                return unknown();
+            }
+            let mut uuid = [0u8; 16];
+            uuid.copy_from_slice(&data[1..17]);
+            BosCapability::ContainerId(ContainerId(uuid))
+        }
+        CAP_BILLBOARD => {
+            // iAdditionalInfoURL, bNumberOfAlternateModes,
+            // bPreferredAlternateMode, wVconnPower, bmConfigured[32],
+            // bcdVersion, bAdditionalFailureInfo, bReserved, then
+            // 4-byte alternate mode entries.
+            if data.len() < 41 {
+                return unknown();
+            }
+            let num_modes = usize::from(data[1]);
+            let mut alternate_modes = Vec::new();
+            for i in 0..num_modes {
+                let entry = 41 + i * 4;
+                let Some(bytes) = data.get(entry..entry + 4) else {
+                    break;
+                };
+                alternate_modes.push(AlternateMode {
+                    svid: u16::from_le_bytes([bytes[0], bytes[1]]),
+                    mode: bytes[2],
+                    string_index: bytes[3],
+                });
+            }
+            BosCapability::Billboard(BillboardCapability {
+                preferred_alternate_mode: data[2],
+                vconn_power: u16::from_le_bytes([data[3], data[4]]),
+                version: u16::from_le_bytes([data[37], data[38]]),
+                alternate_modes,
+            })
+        }
+        _ => unknown(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BOS of a USB 3.0 SSD: USB 2.0 Extension (LPM+BESL) and a
+    /// SuperSpeed capability (full/high/super, U1 10 us, U2 2047 us).
+    const SSD_BOS: &[u8] = &[
+        0x05, 0x0f, 0x16, 0x00, 0x02, // header, wTotalLength 22, 2 caps
+        0x07, 0x10, 0x02, 0x06, 0x00, 0x00, 0x00, // USB 2.0 ext, bmAttributes 0x06
+        0x0a, 0x10, 0x03, 0x00, 0x0e, 0x00, 0x01, 0x0a, 0xff, 0x07, // SuperSpeed
+    ];
+
+    #[test]
+    fn test_parse_usb3_ssd_blob() {
+        let bos = BosDescriptor::parse(SSD_BOS).unwrap();
+        assert_eq!(bos.total_length, 22);
+        assert_eq!(bos.capabilities.len(), 2);
+
+        let BosCapability::Usb2Extension(ext) = &bos.capabilities[0] else {
+            panic!("expected USB 2.0 extension, got {:?}", bos.capabilities[0]);
+        };
+        assert!(ext.lpm_supported());
+        assert!(ext.besl_supported());
+
+        let BosCapability::SuperSpeed(ss) = &bos.capabilities[1] else {
+            panic!("expected SuperSpeed capability");
+        };
+        assert!(!ss.ltm_capable);
+        assert_eq!(ss.speeds, vec![Speed::Full, Speed::High, Speed::Super]);
+        assert_eq!(ss.functionality_support, 1);
+        assert_eq!(ss.u1_exit_latency_us, 10);
+        assert_eq!(ss.u2_exit_latency_us, 2047);
+
+        assert_eq!(bos.container_id(), None);
+    }
+
+    #[test]
+    fn test_parse_usbc_dock_blob() {
+        // Container ID + Billboard with one DisplayPort alternate mode,
+        // plus an SSP capability (type 0x0a) this parser keeps raw.
+        let mut raw = vec![0x05, 0x0f, 0x00, 0x00, 0x03];
+        // Container ID: bLength 20.
+        raw.extend_from_slice(&[0x14, 0x10, 0x04, 0x00]);
+        raw.extend_from_slice(&[
+            0x41, 0x2b, 0x74, 0x8c, 0x9a, 0x01, 0x4d, 0x02, 0x8a, 0x10, 0x5f, 0x3e, 0x44, 0x01,
+            0x22, 0x7f,
+        ]);
+        // Billboard: 3 header bytes + 41 payload + 1 mode entry = 48.
+        let mut billboard = vec![0x30, 0x10, 0x0d];
+        billboard.extend_from_slice(&[
+            0x03, 0x01, 0x00, // iURL, 1 mode, preferred 0
+            0x90, 0x01, // wVconnPower
+        ]);
+        billboard.extend_from_slice(&[0x00; 32]); // bmConfigured
+        billboard.extend_from_slice(&[0x21, 0x01, 0x00, 0x00]); // bcdVersion 1.21, failure, reserved
+        billboard.extend_from_slice(&[0x01, 0xff, 0x01, 0x05]); // SVID ff01, mode 1, string 5
+        raw.extend_from_slice(&billboard);
+        // Unknown SSP capability.
+        raw.extend_from_slice(&[0x05, 0x10, 0x0a, 0xaa, 0xbb]);
+        let total = raw.len() as u16;
+        raw[2..4].copy_from_slice(&total.to_le_bytes());
+
+        let bos = BosDescriptor::parse(&raw).unwrap();
+        assert_eq!(bos.capabilities.len(), 3);
+        assert_eq!(
+            bos.container_id().unwrap().to_string(),
+            "412b748c-9a01-4d02-8a10-5f3e4401227f"
+        );
+
+        let BosCapability::Billboard(billboard) = &bos.capabilities[1] else {
+            panic!("expected billboard capability");
+        };
+        assert_eq!(billboard.vconn_power, 0x0190);
+        assert_eq!(billboard.version, 0x0121);
+        assert_eq!(
+            billboard.alternate_modes,
+            vec![AlternateMode {
+                svid: 0xff01,
+                mode: 1,
+                string_index: 5,
+            }]
+        );
+
+        assert_eq!(
+            bos.capabilities[2],
+            BosCapability::Unknown {
+                capability_type: 0x0a,
+                data: vec![0xaa, 0xbb],
+            }
+        );
+    }
+
+    #[test]
+    fn test_truncated_capability_degrades_to_blob() {
+        // SuperSpeed capability cut off after three payload bytes.
+        let raw = [0x05, 0x0f, 0x0b, 0x00, 0x01, 0x0a, 0x10, 0x03, 0x00, 0x0e, 0x00];
+        let bos = BosDescriptor::parse(&raw).unwrap();
+        assert_eq!(
+            bos.capabilities,
+            vec![BosCapability::Unknown {
+                capability_type: 0x03,
+                data: vec![0x00, 0x0e, 0x00],
+            }]
+        );
+
+        // A zero bLength stops iteration instead of hanging.
+        let raw = [0x05, 0x0f, 0x08, 0x00, 0x01, 0x00, 0x10, 0x03];
+        assert!(BosDescriptor::parse(&raw).unwrap().capabilities.is_empty());
+
+        // Header damage is still an error.
+        assert!(BosDescriptor::parse(&[0x05, 0x02, 0x00, 0x00, 0x00]).is_err());
+        assert!(BosDescriptor::parse(&[0x05, 0x0f]).is_err());
+    }
+}
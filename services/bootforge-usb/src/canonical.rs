@@ -0,0 +1,566 @@
+// BootForge USB - Canonical device identifier
+// One textual form for a device identity, used everywhere a device is
+// named in text: registry identities, journal entries, CLI selectors.
+//
+//     usb:1234:5678:SERIAL     serial present (percent-escaped)
+//     usb:1234:5678@3-1.4      no serial: hub port chain
+//     usb:1234:5678@bus3-6     no serial, no port info: bus/address slot
+//
+// Hex is lowercase, always four digits. Legacy identity strings
+// ("1234:5678/serial=X", "1234:5678/bus=1-4") stay accepted as input.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::enumeration::{UsbDeviceInfo, UsbDeviceRecord};
+use crate::error::UsbError;
+use crate::events::DeviceIdentity;
+
+/**
+ * Which discriminator an identity may use. `Auto` is the documented
+ * priority order - serial, then port chain, then bus slot - and always
+ * produces an identity; the forced strategies produce none when the
+ * device lacks that discriminator, so a device without a serial never
+ * spuriously matches under `SerialOnly`.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum IdentityStrategy {
+    #[default]
+    Auto,
+    /// Match on VID/PID plus serial number only.
+    SerialOnly,
+    /// Match on VID/PID plus hub port chain only; tells apart
+    /// same-model devices with duplicate (or missing) serials.
+    PortOnly,
+}
+
+/**
+ * The part of a canonical ID that tells same-model devices apart.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Discriminator {
+    /// Device serial number, verbatim (unescaped).
+    Serial(String),
+    /// Sysfs-style hub port chain, e.g. "3-1.4".
+    Port(String),
+    /// Bus/address slot; volatile across replug, last resort.
+    Slot { bus: u8, address: u8 },
+}
+
+/**
+ * Canonical textual identity of a device, with strict parsing.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct CanonicalId {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub discriminator: Discriminator,
+}
+
+/// Serial bytes that pass through unescaped.
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'~' | b'-')
+}
+
+/// Percent-escape a serial for embedding in a canonical ID.
+fn escape_serial(serial: &str) -> String {
+    let mut out = String::with_capacity(serial.len());
+    for &b in serial.as_bytes() {
+        if is_unreserved(b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+fn unescape_serial(escaped: &str) -> Result<String, UsbError> {
+    let invalid = || UsbError::Parse(format!("bad serial escape in {:?}", escaped));
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut iter = escaped.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hi = iter.next().ok_or_else(invalid)?;
+            let lo = iter.next().ok_or_else(invalid)?;
+            let hex = [hi, lo];
+            let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        } else if is_unreserved(b) {
+            bytes.push(b);
+        } else {
+            return Err(UsbError::Parse(format!(
+                "unescaped character {:?} in serial of {:?}",
+                b as char, escaped
+            )));
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+/// Strict "bus-port[.port...]" validation, e.g. "3-1.4".
+fn is_valid_port_path(port: &str) -> bool {
+    let Some((bus, chain)) = port.split_once('-') else {
+        return false;
+    };
+    !bus.is_empty()
+        && bus.bytes().all(|b| b.is_ascii_digit())
+        && !chain.is_empty()
+        && chain
+            .split('.')
+            .all(|seg| !seg.is_empty() && seg.bytes().all(|b| b.is_ascii_digit()))
+}
+
+impl CanonicalId {
+    /// Identity of an enumerated device: serial when present, then port
+    /// chain, then the bus/address slot.
+    pub fn of(info: &UsbDeviceInfo) -> Self {
+        let discriminator = match &info.serial_number {
+            Some(serial) if !serial.is_empty() => Discriminator::Serial(serial.clone()),
+            _ => match &info.port_path {
+                Some(port) => Discriminator::Port(port.clone()),
+                None => Discriminator::Slot {
+                    bus: info.bus_number,
+                    address: info.address,
+                },
+            },
+        };
+        CanonicalId {
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            discriminator,
+        }
+    }
+
+    /// Identity under a forced strategy; `None` when the device lacks
+    /// the required discriminator.
+    pub fn of_with(info: &UsbDeviceInfo, strategy: IdentityStrategy) -> Option<Self> {
+        let discriminator = match strategy {
+            IdentityStrategy::Auto => return Some(Self::of(info)),
+            IdentityStrategy::SerialOnly => match &info.serial_number {
+                Some(serial) if !serial.is_empty() => Discriminator::Serial(serial.clone()),
+                _ => return None,
+            },
+            IdentityStrategy::PortOnly => Discriminator::Port(info.port_path.clone()?),
+        };
+        Some(CanonicalId {
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            discriminator,
+        })
+    }
+
+    /// Identity of a fallback-enumerated device. The port chain is the
+    /// sysfs directory name ("3-1.4"); root hubs and other directories
+    /// that are not port chains fall through to the bus slot.
+    pub fn of_record(record: &UsbDeviceRecord) -> Self {
+        let discriminator = match &record.serial_number {
+            Some(serial) if !serial.is_empty() => Discriminator::Serial(serial.clone()),
+            _ => match record_port_path(record) {
+                Some(port) => Discriminator::Port(port),
+                None => Discriminator::Slot {
+                    bus: record.bus_number,
+                    address: record.device_number,
+                },
+            },
+        };
+        CanonicalId {
+            vendor_id: record.vendor_id,
+            product_id: record.product_id,
+            discriminator,
+        }
+    }
+
+    /// `of_with` for fallback-enumerated devices.
+    pub fn of_record_with(record: &UsbDeviceRecord, strategy: IdentityStrategy) -> Option<Self> {
+        let discriminator = match strategy {
+            IdentityStrategy::Auto => return Some(Self::of_record(record)),
+            IdentityStrategy::SerialOnly => match &record.serial_number {
+                Some(serial) if !serial.is_empty() => Discriminator::Serial(serial.clone()),
+                _ => return None,
+            },
+            IdentityStrategy::PortOnly => Discriminator::Port(record_port_path(record)?),
+        };
+        Some(CanonicalId {
+            vendor_id: record.vendor_id,
+            product_id: record.product_id,
+            discriminator,
+        })
+    }
+
+    pub fn to_identity(&self) -> DeviceIdentity {
+        DeviceIdentity(self.to_string())
+    }
+
+    /// Parse an identity string, canonical or legacy.
+    pub fn from_identity(identity: &DeviceIdentity) -> Result<Self, UsbError> {
+        identity.0.parse()
+    }
+
+    fn parse_legacy(s: &str) -> Result<Self, UsbError> {
+        let invalid = || UsbError::Parse(format!("unrecognized identity {:?}", s));
+        let (ids, rest) = s.split_at_checked(9).ok_or_else(invalid)?;
+        let (vid, pid) = ids.split_once(':').ok_or_else(invalid)?;
+        let vendor_id = u16::from_str_radix(vid, 16).map_err(|_| invalid())?;
+        let product_id = u16::from_str_radix(pid, 16).map_err(|_| invalid())?;
+
+        let discriminator = if let Some(serial) = rest.strip_prefix("/serial=") {
+            Discriminator::Serial(serial.to_string())
+        } else if let Some(slot) = rest.strip_prefix("/bus=") {
+            let (bus, address) = slot.split_once('-').ok_or_else(invalid)?;
+            Discriminator::Slot {
+                bus: bus.parse().map_err(|_| invalid())?,
+                address: address.parse().map_err(|_| invalid())?,
+            }
+        } else {
+            return Err(invalid());
+        };
+
+        Ok(CanonicalId {
+            vendor_id,
+            product_id,
+            discriminator,
+        })
+    }
+}
+
+/// Port chain of a sysfs record, from the device directory name.
+fn record_port_path(record: &UsbDeviceRecord) -> Option<String> {
+    let name = record.sysfs_path.rsplit('/').next()?;
+    is_valid_port_path(name).then(|| name.to_string())
+}
+
+impl UsbDeviceInfo {
+    /// Stable identity key, documented priority order.
+    pub fn identity(&self) -> DeviceIdentity {
+        CanonicalId::of(self).to_identity()
+    }
+
+    /// Whether two observations are the same device under `strategy`.
+    /// Forced strategies never match when either side lacks the
+    /// discriminator.
+    pub fn matches(&self, other: &UsbDeviceInfo, strategy: IdentityStrategy) -> bool {
+        match (
+            CanonicalId::of_with(self, strategy),
+            CanonicalId::of_with(other, strategy),
+        ) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl UsbDeviceRecord {
+    /// Stable identity key, documented priority order.
+    pub fn identity(&self) -> DeviceIdentity {
+        CanonicalId::of_record(self).to_identity()
+    }
+
+    /// Whether two observations are the same device under `strategy`.
+    pub fn matches(&self, other: &UsbDeviceRecord, strategy: IdentityStrategy) -> bool {
+        match (
+            CanonicalId::of_record_with(self, strategy),
+            CanonicalId::of_record_with(other, strategy),
+        ) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for CanonicalId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "usb:{:04x}:{:04x}", self.vendor_id, self.product_id)?;
+        match &self.discriminator {
+            Discriminator::Serial(serial) => write!(f, ":{}", escape_serial(serial)),
+            Discriminator::Port(port) => write!(f, "@{}", port),
+            Discriminator::Slot { bus, address } => write!(f, "@bus{}-{}", bus, address),
+        }
+    }
+}
+
+impl FromStr for CanonicalId {
+    type Err = UsbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(rest) = s.strip_prefix("usb:") else {
+            return Self::parse_legacy(s);
+        };
+        let invalid = || UsbError::Parse(format!("malformed canonical id {:?}", s));
+
+        let mut parts = rest.splitn(3, [':', '@']);
+        let vid = parts.next().ok_or_else(invalid)?;
+        let pid_and_rest_start = vid.len() + 1;
+        if vid.len() != 4 || !vid.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let vendor_id = u16::from_str_radix(vid, 16).map_err(|_| invalid())?;
+
+        let pid = parts.next().ok_or_else(invalid)?;
+        if pid.len() != 4 || !pid.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let product_id = u16::from_str_radix(pid, 16).map_err(|_| invalid())?;
+
+        // The delimiter before the discriminator decides its kind.
+        let delim_index = pid_and_rest_start + pid.len();
+        let delim = rest.as_bytes().get(delim_index).ok_or_else(invalid)?;
+        let tail = parts.next().ok_or_else(invalid)?;
+        if tail.is_empty() {
+            return Err(invalid());
+        }
+
+        let discriminator = match delim {
+            b':' => Discriminator::Serial(unescape_serial(tail)?),
+            b'@' => {
+                if let Some(slot) = tail.strip_prefix("bus") {
+                    let (bus, address) = slot.split_once('-').ok_or_else(invalid)?;
+                    Discriminator::Slot {
+                        bus: bus.parse().map_err(|_| invalid())?,
+                        address: address.parse().map_err(|_| invalid())?,
+                    }
+                } else if is_valid_port_path(tail) {
+                    Discriminator::Port(tail.to_string())
+                } else {
+                    return Err(invalid());
+                }
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(CanonicalId {
+            vendor_id,
+            product_id,
+            discriminator,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::version::BcdVersion;
+
+    fn info(serial: Option<&str>, port: Option<&str>) -> UsbDeviceInfo {
+        UsbDeviceInfo {
+            bus_number: 3,
+            address: 6,
+            vendor_id: 0x18d1,
+            product_id: 0x4ee7,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0210),
+                device_version: BcdVersion(0x0440),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: None,
+            product: None,
+            serial_number: serial.map(str::to_string),
+            port_path: port.map(str::to_string),
+            speed: None,
+            tags: Vec::new(),
+            active_config: None,
+            usb_ids: None,
+            container_id: None,
+        }
+    }
+
+    fn round_trip(id: &CanonicalId) {
+        let rendered = id.to_string();
+        let parsed: CanonicalId = rendered.parse().unwrap();
+        assert_eq!(&parsed, id, "round trip failed for {}", rendered);
+    }
+
+    #[test]
+    fn test_rendering_precedence() {
+        assert_eq!(
+            CanonicalId::of(&info(Some("ABC123"), Some("3-1.4"))).to_string(),
+            "usb:18d1:4ee7:ABC123"
+        );
+        assert_eq!(
+            CanonicalId::of(&info(None, Some("3-1.4"))).to_string(),
+            "usb:18d1:4ee7@3-1.4"
+        );
+        assert_eq!(
+            CanonicalId::of(&info(Some(""), None)).to_string(),
+            "usb:18d1:4ee7@bus3-6"
+        );
+    }
+
+    #[test]
+    fn test_serial_escaping() {
+        // ':' would be ambiguous with the field separator.
+        let id = CanonicalId {
+            vendor_id: 0x05ac,
+            product_id: 0x12a8,
+            discriminator: Discriminator::Serial("ab:cd/ef gh".to_string()),
+        };
+        assert_eq!(id.to_string(), "usb:05ac:12a8:ab%3Acd%2Fef%20gh");
+        round_trip(&id);
+
+        // Unicode serials escape per UTF-8 byte.
+        let id = CanonicalId {
+            vendor_id: 0x05ac,
+            product_id: 0x12a8,
+            discriminator: Discriminator::Serial("série№7".to_string()),
+        };
+        round_trip(&id);
+        match id.to_string().parse::<CanonicalId>().unwrap().discriminator {
+            Discriminator::Serial(s) => assert_eq!(s, "série№7"),
+            other => panic!("expected serial, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parser_strictness() {
+        for bad in [
+            "",
+            "usb:",
+            "usb:18d1",
+            "usb:18d1:4ee7",         // discriminator required
+            "usb:18d1:4ee7:",        // empty serial
+            "usb:18d1:4ee7@",        // empty port
+            "usb:18d:4ee7:S",        // short vid
+            "usb:18d1x:4ee7:S",      // bad hex
+            "usb:18d1:4ee7:a b",     // unescaped space
+            "usb:18d1:4ee7:a%zz",    // bad escape
+            "usb:18d1:4ee7@3-",      // truncated port
+            "usb:18d1:4ee7@bus3",    // slot without address
+            "usb:18d1:4ee7@x-1",     // non-numeric port
+            "scsi:18d1:4ee7:S",      // wrong scheme, not legacy either
+        ] {
+            assert!(bad.parse::<CanonicalId>().is_err(), "{:?} should fail", bad);
+        }
+    }
+
+    #[test]
+    fn test_port_and_slot_forms() {
+        let id: CanonicalId = "usb:2109:2812@3-1.4.2".parse().unwrap();
+        assert_eq!(id.discriminator, Discriminator::Port("3-1.4.2".to_string()));
+        round_trip(&id);
+
+        let id: CanonicalId = "usb:2109:2812@bus3-14".parse().unwrap();
+        assert_eq!(id.discriminator, Discriminator::Slot { bus: 3, address: 14 });
+        round_trip(&id);
+    }
+
+    #[test]
+    fn test_legacy_identity_strings_accepted() {
+        let id: CanonicalId = "18d1:4ee7/serial=29061FDH300EXZ".parse().unwrap();
+        assert_eq!(
+            id.discriminator,
+            Discriminator::Serial("29061FDH300EXZ".to_string())
+        );
+        // Legacy input re-renders canonically.
+        assert_eq!(id.to_string(), "usb:18d1:4ee7:29061FDH300EXZ");
+
+        let id: CanonicalId = "18d1:4ee7/bus=1-9".parse().unwrap();
+        assert_eq!(id.discriminator, Discriminator::Slot { bus: 1, address: 9 });
+        assert_eq!(id.to_string(), "usb:18d1:4ee7@bus1-9");
+    }
+
+    fn record(serial: Option<&str>, sysfs_path: &str) -> UsbDeviceRecord {
+        UsbDeviceRecord {
+            bus_number: 3,
+            device_number: 6,
+            vendor_id: 0x18d1,
+            product_id: 0x4ee7,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0210),
+                device_version: BcdVersion(0x0440),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: None,
+            product: None,
+            serial_number: serial.map(str::to_string),
+            sysfs_path: sysfs_path.to_string(),
+            speed: None,
+            interfaces: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_strategy_requires_discriminator() {
+        let no_serial = info(None, Some("3-1.4"));
+        assert!(CanonicalId::of_with(&no_serial, IdentityStrategy::SerialOnly).is_none());
+        // Auto falls through to the port chain instead.
+        assert_eq!(
+            CanonicalId::of_with(&no_serial, IdentityStrategy::Auto)
+                .unwrap()
+                .to_string(),
+            "usb:18d1:4ee7@3-1.4"
+        );
+        // A device without a serial never matches anything serial-only,
+        // itself included.
+        assert!(!no_serial.matches(&no_serial.clone(), IdentityStrategy::SerialOnly));
+
+        let no_port = info(Some("ABC123"), None);
+        assert!(CanonicalId::of_with(&no_port, IdentityStrategy::PortOnly).is_none());
+    }
+
+    #[test]
+    fn test_duplicate_serials_on_different_ports() {
+        // Two same-model sticks flashed with the same serial.
+        let mut a = info(Some("0000000000000001"), Some("3-1.4"));
+        let mut b = info(Some("0000000000000001"), Some("3-2"));
+        a.address = 7;
+        b.address = 9;
+
+        assert!(a.matches(&b, IdentityStrategy::Auto));
+        assert!(a.matches(&b, IdentityStrategy::SerialOnly));
+        assert!(!a.matches(&b, IdentityStrategy::PortOnly));
+    }
+
+    #[test]
+    fn test_serial_survives_bus_address_change() {
+        // Same device replugged: new address, new port, same serial.
+        let mut before = info(Some("29061FDH300EXZ"), Some("3-1.4"));
+        let mut after = info(Some("29061FDH300EXZ"), Some("1-2"));
+        before.bus_number = 3;
+        after.bus_number = 1;
+        after.address = 11;
+
+        assert!(before.matches(&after, IdentityStrategy::Auto));
+        assert_eq!(before.identity(), after.identity());
+        assert!(!before.matches(&after, IdentityStrategy::PortOnly));
+    }
+
+    #[test]
+    fn test_record_identity_uses_sysfs_port() {
+        let rec = record(None, "/sys/bus/usb/devices/3-1.4");
+        assert_eq!(rec.identity().0, "usb:18d1:4ee7@3-1.4");
+
+        // Root hubs ("usb3") are not port chains; slot fallback.
+        let root = record(None, "/sys/bus/usb/devices/usb3");
+        assert_eq!(root.identity().0, "usb:18d1:4ee7@bus3-6");
+
+        let with_serial = record(Some("ABC123"), "/sys/bus/usb/devices/3-1.4");
+        assert_eq!(with_serial.identity().0, "usb:18d1:4ee7:ABC123");
+        assert!(with_serial.matches(&with_serial.clone(), IdentityStrategy::PortOnly));
+        assert!(!root.matches(&with_serial, IdentityStrategy::PortOnly));
+    }
+
+    #[test]
+    fn test_identity_conversions() {
+        let device = info(Some("ABC123"), None);
+        let identity = DeviceIdentity::of(&device);
+        assert_eq!(identity.0, "usb:18d1:4ee7:ABC123");
+
+        let id = CanonicalId::from_identity(&identity).unwrap();
+        assert_eq!(id, CanonicalId::of(&device));
+        assert_eq!(id.to_identity(), identity);
+
+        // Pre-migration identities still convert.
+        let legacy = DeviceIdentity("18d1:4ee7/serial=ABC123".to_string());
+        assert_eq!(CanonicalId::from_identity(&legacy).unwrap(), id);
+    }
+}
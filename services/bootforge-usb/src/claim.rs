@@ -0,0 +1,317 @@
+// BootForge USB - Interface claiming with driver restoration
+// Claiming an interface with kernel-driver auto-detach leaves the device
+// broken for the rest of the desktop session unless the original driver
+// is re-attached; the guard here makes restoration the default.
+
+use crate::error::{classify_transfer_error, UsbError};
+
+/**
+ * The claim-related operations of a device handle, separated out so the
+ * guard's restore logic is testable without hardware.
+ */
+pub trait InterfaceHost {
+    fn kernel_driver_active(&mut self, interface: u8) -> Result<bool, rusb::Error>;
+    fn detach_kernel_driver(&mut self, interface: u8) -> Result<(), rusb::Error>;
+    fn attach_kernel_driver(&mut self, interface: u8) -> Result<(), rusb::Error>;
+    fn claim_interface(&mut self, interface: u8) -> Result<(), rusb::Error>;
+    fn release_interface(&mut self, interface: u8) -> Result<(), rusb::Error>;
+    fn set_alternate_setting(&mut self, interface: u8, setting: u8) -> Result<(), rusb::Error>;
+}
+
+impl<C: rusb::UsbContext> InterfaceHost for rusb::DeviceHandle<C> {
+    fn kernel_driver_active(&mut self, interface: u8) -> Result<bool, rusb::Error> {
+        rusb::DeviceHandle::kernel_driver_active(self, interface)
+    }
+
+    fn detach_kernel_driver(&mut self, interface: u8) -> Result<(), rusb::Error> {
+        rusb::DeviceHandle::detach_kernel_driver(self, interface)
+    }
+
+    fn attach_kernel_driver(&mut self, interface: u8) -> Result<(), rusb::Error> {
+        rusb::DeviceHandle::attach_kernel_driver(self, interface)
+    }
+
+    fn claim_interface(&mut self, interface: u8) -> Result<(), rusb::Error> {
+        rusb::DeviceHandle::claim_interface(self, interface)
+    }
+
+    fn release_interface(&mut self, interface: u8) -> Result<(), rusb::Error> {
+        rusb::DeviceHandle::release_interface(self, interface)
+    }
+
+    fn set_alternate_setting(&mut self, interface: u8, setting: u8) -> Result<(), rusb::Error> {
+        rusb::DeviceHandle::set_alternate_setting(self, interface, setting)
+    }
+}
+
+/**
+ * RAII guard over a claimed interface. Remembers whether a kernel
+ * driver was detached to make the claim and re-attaches it on release
+ * or drop - including the unwind path - so an MTP pull does not leave
+ * the phone's MTP dead for the rest of the session. Platforms without
+ * detach semantics (NotSupported) behave as if no driver was attached.
+ */
+pub struct ClaimedInterface<'a, H: InterfaceHost> {
+    host: &'a mut H,
+    interface: u8,
+    detached_driver: bool,
+    restore_driver: bool,
+    released: bool,
+}
+
+impl<'a, H: InterfaceHost> ClaimedInterface<'a, H> {
+    /**
+     * Claim `interface`, detaching an active kernel driver first. A
+     * failed claim re-attaches immediately rather than leaving the
+     * device driverless.
+     */
+    pub fn claim(host: &'a mut H, interface: u8) -> Result<Self, UsbError> {
+        let detached_driver = match host.kernel_driver_active(interface) {
+            Ok(true) => match host.detach_kernel_driver(interface) {
+                Ok(()) => true,
+                Err(rusb::Error::NotSupported) => false,
+                Err(e) => return Err(classify_transfer_error(e)),
+            },
+            Ok(false) | Err(rusb::Error::NotSupported) => false,
+            Err(e) => return Err(classify_transfer_error(e)),
+        };
+
+        if let Err(e) = host.claim_interface(interface) {
+            if detached_driver {
+                if let Err(attach_err) = host.attach_kernel_driver(interface) {
+                    log::warn!(
+                        "could not re-attach kernel driver to interface {} after failed claim: {}",
+                        interface,
+                        attach_err
+                    );
+                }
+            }
+            return Err(classify_transfer_error(e));
+        }
+
+        Ok(ClaimedInterface {
+            host,
+            interface,
+            detached_driver,
+            restore_driver: true,
+            released: false,
+        })
+    }
+
+    /// Skip driver re-attachment on release; for callers that want the
+    /// device to stay bound to usbfs.
+    pub fn with_restore_driver(mut self, restore_driver: bool) -> Self {
+        self.restore_driver = restore_driver;
+        self
+    }
+
+    pub fn interface(&self) -> u8 {
+        self.interface
+    }
+
+    /// Whether claiming displaced a kernel driver.
+    pub fn detached_kernel_driver(&self) -> bool {
+        self.detached_driver
+    }
+
+    /// Select an alternate setting on the claimed interface. Going
+    /// through the guard keeps alt-setting changes scoped to the claim,
+    /// where the kernel requires them to be.
+    pub fn set_alt_setting(&mut self, setting: u8) -> Result<(), UsbError> {
+        self.host
+            .set_alternate_setting(self.interface, setting)
+            .map_err(classify_transfer_error)
+    }
+
+    /**
+     * Release the claim and restore the kernel driver, surfacing errors
+     * that the Drop path can only log.
+     */
+    pub fn release_and_restore(mut self) -> Result<(), UsbError> {
+        self.released = true;
+        self.host
+            .release_interface(self.interface)
+            .map_err(classify_transfer_error)?;
+        if self.detached_driver && self.restore_driver {
+            self.detached_driver = false;
+            self.host
+                .attach_kernel_driver(self.interface)
+                .map_err(classify_transfer_error)?;
+        }
+        Ok(())
+    }
+}
+
+impl<H: InterfaceHost> Drop for ClaimedInterface<'_, H> {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        if let Err(e) = self.host.release_interface(self.interface) {
+            log::warn!("releasing interface {} failed: {}", self.interface, e);
+        }
+        if self.detached_driver && self.restore_driver {
+            self.detached_driver = false;
+            if let Err(e) = self.host.attach_kernel_driver(self.interface) {
+                log::warn!(
+                    "re-attaching kernel driver to interface {} failed: {}",
+                    self.interface,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Counts {
+        detach: usize,
+        attach: usize,
+        claim: usize,
+        release: usize,
+        alt_settings: Vec<(u8, u8)>,
+    }
+
+    struct MockHost {
+        driver_active: bool,
+        claim_result: Result<(), rusb::Error>,
+        detach_supported: bool,
+        counts: RefCell<Counts>,
+    }
+
+    impl MockHost {
+        fn new(driver_active: bool) -> Self {
+            MockHost {
+                driver_active,
+                claim_result: Ok(()),
+                detach_supported: true,
+                counts: RefCell::new(Counts::default()),
+            }
+        }
+    }
+
+    impl InterfaceHost for MockHost {
+        fn kernel_driver_active(&mut self, _interface: u8) -> Result<bool, rusb::Error> {
+            if !self.detach_supported {
+                return Err(rusb::Error::NotSupported);
+            }
+            Ok(self.driver_active)
+        }
+
+        fn detach_kernel_driver(&mut self, _interface: u8) -> Result<(), rusb::Error> {
+            self.counts.borrow_mut().detach += 1;
+            Ok(())
+        }
+
+        fn attach_kernel_driver(&mut self, _interface: u8) -> Result<(), rusb::Error> {
+            self.counts.borrow_mut().attach += 1;
+            Ok(())
+        }
+
+        fn claim_interface(&mut self, _interface: u8) -> Result<(), rusb::Error> {
+            self.counts.borrow_mut().claim += 1;
+            self.claim_result
+        }
+
+        fn release_interface(&mut self, _interface: u8) -> Result<(), rusb::Error> {
+            self.counts.borrow_mut().release += 1;
+            Ok(())
+        }
+
+        fn set_alternate_setting(&mut self, interface: u8, setting: u8) -> Result<(), rusb::Error> {
+            self.counts.borrow_mut().alt_settings.push((interface, setting));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_release_and_restore_reattaches_once() {
+        let mut host = MockHost::new(true);
+        let guard = ClaimedInterface::claim(&mut host, 0).unwrap();
+        assert!(guard.detached_kernel_driver());
+        guard.release_and_restore().unwrap();
+
+        let counts = host.counts.borrow();
+        assert_eq!((counts.detach, counts.attach), (1, 1));
+        assert_eq!((counts.claim, counts.release), (1, 1));
+    }
+
+    #[test]
+    fn test_drop_restores_exactly_once() {
+        let mut host = MockHost::new(true);
+        {
+            let _guard = ClaimedInterface::claim(&mut host, 0).unwrap();
+        }
+        let counts = host.counts.borrow();
+        assert_eq!((counts.attach, counts.release), (1, 1));
+    }
+
+    #[test]
+    fn test_unwind_path_restores_exactly_once() {
+        let mut host = MockHost::new(true);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = ClaimedInterface::claim(&mut host, 0).unwrap();
+            panic!("mid-operation failure");
+        }));
+        assert!(result.is_err());
+
+        let counts = host.counts.borrow();
+        assert_eq!((counts.attach, counts.release), (1, 1));
+    }
+
+    #[test]
+    fn test_no_driver_means_no_attach() {
+        let mut host = MockHost::new(false);
+        ClaimedInterface::claim(&mut host, 0)
+            .unwrap()
+            .release_and_restore()
+            .unwrap();
+        let counts = host.counts.borrow();
+        assert_eq!((counts.detach, counts.attach), (0, 0));
+    }
+
+    #[test]
+    fn test_restore_can_be_disabled() {
+        let mut host = MockHost::new(true);
+        {
+            let _guard = ClaimedInterface::claim(&mut host, 0)
+                .unwrap()
+                .with_restore_driver(false);
+        }
+        assert_eq!(host.counts.borrow().attach, 0);
+    }
+
+    #[test]
+    fn test_platforms_without_detach_are_a_noop() {
+        let mut host = MockHost::new(false);
+        host.detach_supported = false;
+        let guard = ClaimedInterface::claim(&mut host, 0).unwrap();
+        assert!(!guard.detached_kernel_driver());
+        guard.release_and_restore().unwrap();
+        assert_eq!(host.counts.borrow().attach, 0);
+    }
+
+    #[test]
+    fn test_alt_setting_goes_through_the_claim() {
+        let mut host = MockHost::new(false);
+        let mut guard = ClaimedInterface::claim(&mut host, 2).unwrap();
+        guard.set_alt_setting(1).unwrap();
+        guard.release_and_restore().unwrap();
+        assert_eq!(host.counts.borrow().alt_settings, vec![(2, 1)]);
+    }
+
+    #[test]
+    fn test_failed_claim_reattaches_immediately() {
+        let mut host = MockHost::new(true);
+        host.claim_result = Err(rusb::Error::Busy);
+        assert!(ClaimedInterface::claim(&mut host, 0).is_err());
+        let counts = host.counts.borrow();
+        assert_eq!((counts.detach, counts.attach), (1, 1));
+        assert_eq!(counts.release, 0);
+    }
+}
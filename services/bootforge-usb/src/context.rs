@@ -0,0 +1,163 @@
+// BootForge USB - Shared libusb context
+// enumerate_libusb() runs on rusb's global context, which cannot be
+// configured and aborts the process when libusb fails to initialize
+// (hence guard_panics in enumeration). SharedContext is the caller-owned
+// alternative: configured up front, initialized lazily on first use, and
+// cheap to clone into every subsystem that talks to the bus, so the
+// process holds exactly one libusb context.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::error::UsbError;
+
+/// Construction seam: tests swap in a counting initializer, since a
+/// working libusb backend cannot be assumed on CI hosts.
+type ContextInit = dyn Fn(&ContextOptions) -> Result<rusb::Context, rusb::Error> + Send + Sync;
+
+/// The one initialization slot shared by every clone of a SharedContext.
+type ContextSlot = Arc<Mutex<Option<Result<rusb::Context, rusb::Error>>>>;
+
+/**
+ * libusb construction options. Applied when the context is first used;
+ * changing options after `shared()` has no effect on that context.
+ */
+#[derive(Clone, Copy, Default)]
+pub struct ContextOptions {
+    log_level: Option<rusb::LogLevel>,
+    use_usbdk: bool,
+}
+
+impl ContextOptions {
+    /// libusb's own log verbosity for this context.
+    pub fn with_log_level(mut self, level: rusb::LogLevel) -> Self {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Route traffic through the UsbDk backend. Windows-only; elsewhere
+    /// the context fails to initialize with `NotSupported`.
+    pub fn with_usbdk(mut self, use_usbdk: bool) -> Self {
+        self.use_usbdk = use_usbdk;
+        self
+    }
+
+    /// A lazily-initialized context carrying these options.
+    pub fn shared(self) -> SharedContext {
+        SharedContext::with_init(self, Arc::new(init_context))
+    }
+}
+
+#[cfg(windows)]
+fn backend_options(options: &ContextOptions) -> Result<Vec<rusb::UsbOption>, rusb::Error> {
+    Ok(if options.use_usbdk {
+        vec![rusb::UsbOption::use_usbdk()]
+    } else {
+        Vec::new()
+    })
+}
+
+#[cfg(not(windows))]
+fn backend_options(options: &ContextOptions) -> Result<Vec<rusb::UsbOption>, rusb::Error> {
+    if options.use_usbdk {
+        return Err(rusb::Error::NotSupported);
+    }
+    Ok(Vec::new())
+}
+
+fn init_context(options: &ContextOptions) -> Result<rusb::Context, rusb::Error> {
+    let mut context = rusb::Context::with_options(&backend_options(options)?)?;
+    if let Some(level) = options.log_level {
+        rusb::UsbContext::set_log_level(&mut context, level);
+    }
+    Ok(context)
+}
+
+/**
+ * A caller-owned libusb context, shared across the crate and with any
+ * other rusb-using code in the process.
+ *
+ * Clones share one underlying context: the first operation through any
+ * clone initializes libusb, every later operation reuses it, and device
+ * handles opened from it stay valid for as long as any clone lives. The
+ * initialization outcome is cached - a backend that failed to come up
+ * yields the same error on every call instead of one init attempt per
+ * call; build a fresh `SharedContext` to retry.
+ */
+#[derive(Clone)]
+pub struct SharedContext {
+    options: ContextOptions,
+    init: Arc<ContextInit>,
+    slot: ContextSlot,
+}
+
+impl Default for SharedContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SharedContext {
+    /// Default options; libusb is not touched until first use.
+    pub fn new() -> Self {
+        ContextOptions::default().shared()
+    }
+
+    pub(crate) fn with_init(options: ContextOptions, init: Arc<ContextInit>) -> Self {
+        SharedContext {
+            options,
+            init,
+            slot: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /**
+     * Run `f` against the live context, initializing libusb first if
+     * this is the first use across all clones.
+     */
+    pub fn with_context<R>(&self, f: impl FnOnce(&rusb::Context) -> R) -> Result<R, UsbError> {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+        match slot.get_or_insert_with(|| (self.init)(&self.options)) {
+            Ok(context) => Ok(f(context)),
+            Err(e) => Err(UsbError::Libusb(*e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// An initializer that counts attempts and always fails; a real
+    /// libusb backend cannot be assumed on CI hosts, and the caching
+    /// under test does not depend on the outcome.
+    fn counting_init(counter: Arc<AtomicUsize>) -> Arc<ContextInit> {
+        Arc::new(move |_options| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err(rusb::Error::Other)
+        })
+    }
+
+    #[test]
+    fn test_clones_share_one_initialization() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let context = SharedContext::with_init(ContextOptions::default(), counting_init(count.clone()));
+        let clone = context.clone();
+
+        for ctx in [&context, &clone, &context] {
+            let err = ctx.with_context(|_| ()).unwrap_err();
+            assert!(matches!(err, UsbError::Libusb(rusb::Error::Other)));
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1, "re-initialized");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_usbdk_rejected_off_windows() {
+        // Goes through the real initializer, which rejects the option
+        // before touching libusb.
+        let context = ContextOptions::default().with_usbdk(true).shared();
+        let err = context.with_context(|_| ()).unwrap_err();
+        assert!(matches!(err, UsbError::Libusb(rusb::Error::NotSupported)));
+    }
+}
@@ -0,0 +1,215 @@
+// BootForge USB - Endpoint discovery
+// Parses configuration descriptors and answers "which endpoints does
+// this protocol talk to" so protocol clients need not hard-code
+// addresses.
+
+use crate::enumeration::{ConfigInfo, InterfaceInfo};
+use crate::error::UsbError;
+use crate::topology::{EndpointInfo, EndpointKind};
+
+// Descriptor types in a configuration blob.
+const DESC_CONFIGURATION: u8 = 0x02;
+const DESC_INTERFACE: u8 = 0x04;
+const DESC_ENDPOINT: u8 = 0x05;
+const DESC_SS_COMPANION: u8 = 0x30;
+
+/**
+ * Parse a raw configuration descriptor blob (as returned by
+ * GET_DESCRIPTOR) into the crate's ConfigInfo model, including every
+ * alternate setting. Unknown descriptor types are skipped, matching
+ * how the kernel walks the same bytes.
+ */
+pub fn parse_config_descriptor(bytes: &[u8]) -> Result<ConfigInfo, UsbError> {
+    if bytes.len() < 9 || bytes[1] != DESC_CONFIGURATION {
+        return Err(UsbError::Parse(
+            "not a configuration descriptor".to_string(),
+        ));
+    }
+    let total_length = usize::from(u16::from_le_bytes([bytes[2], bytes[3]]));
+    if total_length > bytes.len() {
+        return Err(UsbError::Parse(format!(
+            "wTotalLength {} exceeds the {} bytes provided",
+            total_length,
+            bytes.len()
+        )));
+    }
+    let attributes = bytes[7];
+    let mut config = ConfigInfo {
+        configuration_value: bytes[5],
+        max_power_ma: u16::from(bytes[8]) * 2,
+        self_powered: attributes & 0x40 != 0,
+        remote_wakeup: attributes & 0x20 != 0,
+        // Raw descriptor parsing has no handle to read strings with.
+        description: None,
+        interfaces: Vec::new(),
+    };
+
+    let mut at = usize::from(bytes[0]);
+    while at + 2 <= total_length {
+        let length = usize::from(bytes[at]);
+        if length < 2 || at + length > total_length {
+            return Err(UsbError::Parse(format!(
+                "descriptor at offset {} overruns the configuration",
+                at
+            )));
+        }
+        let descriptor = &bytes[at..at + length];
+        match descriptor[1] {
+            DESC_INTERFACE if length >= 9 => {
+                config.interfaces.push(InterfaceInfo {
+                    number: descriptor[2],
+                    alternate_setting: descriptor[3],
+                    class: descriptor[5],
+                    subclass: descriptor[6],
+                    protocol: descriptor[7],
+                    description: None,
+                    endpoints: Vec::new(),
+                });
+            }
+            DESC_ENDPOINT if length >= 7 => {
+                let interface = config.interfaces.last_mut().ok_or_else(|| {
+                    UsbError::Parse("endpoint descriptor before any interface".to_string())
+                })?;
+                interface.endpoints.push(EndpointInfo {
+                    address: descriptor[2],
+                    kind: match descriptor[3] & 0x03 {
+                        0 => EndpointKind::Control,
+                        1 => EndpointKind::Isochronous,
+                        2 => EndpointKind::Bulk,
+                        _ => EndpointKind::Interrupt,
+                    },
+                    max_packet_size: u16::from_le_bytes([descriptor[4], descriptor[5]]),
+                    interval: descriptor[6],
+                    ss_bytes_per_interval: None,
+                });
+            }
+            DESC_SS_COMPANION if length >= 6 => {
+                if let Some(endpoint) = config
+                    .interfaces
+                    .last_mut()
+                    .and_then(|i| i.endpoints.last_mut())
+                {
+                    endpoint.ss_bytes_per_interval =
+                        Some(u32::from(u16::from_le_bytes([descriptor[4], descriptor[5]])));
+                }
+            }
+            _ => {}
+        }
+        at += length;
+    }
+    Ok(config)
+}
+
+/**
+ * Every endpoint of a configuration, optionally narrowed to one
+ * interface number (all of its alternate settings).
+ */
+pub fn find_endpoints(config: &ConfigInfo, interface: Option<u8>) -> Vec<&EndpointInfo> {
+    config
+        .interfaces
+        .iter()
+        .filter(|i| interface.is_none_or(|n| i.number == n))
+        .flat_map(|i| i.endpoints.iter())
+        .collect()
+}
+
+/// The (IN, OUT) bulk endpoint addresses of an interface, when it has
+/// both.
+pub fn find_bulk_pair(interface: &InterfaceInfo) -> Option<(u8, u8)> {
+    let ep_in = interface
+        .endpoints
+        .iter()
+        .find(|e| e.kind == EndpointKind::Bulk && e.address & 0x80 != 0)?;
+    let ep_out = interface
+        .endpoints
+        .iter()
+        .find(|e| e.kind == EndpointKind::Bulk && e.address & 0x80 == 0)?;
+    Some((ep_in.address, ep_out.address))
+}
+
+/// The interrupt IN endpoint address of an interface, when present.
+pub fn find_interrupt_in(interface: &InterfaceInfo) -> Option<u8> {
+    interface
+        .endpoints
+        .iter()
+        .find(|e| e.kind == EndpointKind::Interrupt && e.address & 0x80 != 0)
+        .map(|e| e.address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A canned configuration: an MTP-ish interface (bulk pair plus an
+    /// interrupt IN) and a vendor interface with two alt settings.
+    fn canned_config() -> Vec<u8> {
+        let mut blob = Vec::new();
+        // Configuration header; wTotalLength patched at the end.
+        blob.extend_from_slice(&[9, 0x02, 0, 0, 2, 1, 0, 0xa0, 50]);
+        // Interface 0 alt 0: still image class, three endpoints.
+        blob.extend_from_slice(&[9, 0x04, 0, 0, 3, 0x06, 0x01, 0x01, 0]);
+        blob.extend_from_slice(&[7, 0x05, 0x81, 0x02, 0x00, 0x02, 0]); // bulk IN 512
+        blob.extend_from_slice(&[7, 0x05, 0x01, 0x02, 0x00, 0x02, 0]); // bulk OUT 512
+        blob.extend_from_slice(&[7, 0x05, 0x82, 0x03, 0x1c, 0x00, 6]); // intr IN 28
+        // Interface 1 alt 0: no endpoints.
+        blob.extend_from_slice(&[9, 0x04, 1, 0, 0, 0xff, 0, 0, 0]);
+        // Interface 1 alt 1: one bulk IN.
+        blob.extend_from_slice(&[9, 0x04, 1, 1, 1, 0xff, 0, 0, 0]);
+        blob.extend_from_slice(&[7, 0x05, 0x83, 0x02, 0x00, 0x02, 0]);
+        let total = blob.len() as u16;
+        blob[2..4].copy_from_slice(&total.to_le_bytes());
+        blob
+    }
+
+    #[test]
+    fn test_parse_config_with_alt_settings() {
+        let config = parse_config_descriptor(&canned_config()).unwrap();
+        assert_eq!(config.configuration_value, 1);
+        assert_eq!(config.max_power_ma, 100);
+        assert!(config.remote_wakeup && !config.self_powered);
+        // Three interface descriptors: alt settings listed separately.
+        assert_eq!(config.interfaces.len(), 3);
+        assert_eq!(config.interfaces[0].endpoints.len(), 3);
+        assert_eq!(config.interfaces[1].endpoints.len(), 0);
+        assert_eq!(config.interfaces[2].alternate_setting, 1);
+        assert_eq!(config.interfaces[2].endpoints[0].address, 0x83);
+        assert_eq!(
+            config.interfaces[0].endpoints[2].kind,
+            EndpointKind::Interrupt
+        );
+        assert_eq!(config.interfaces[0].endpoints[0].max_packet_size, 512);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_blobs() {
+        assert!(parse_config_descriptor(&[9, 0x04, 0, 0, 0, 0, 0, 0, 0]).is_err());
+
+        // wTotalLength beyond the provided bytes.
+        let mut short = canned_config();
+        short[2] = 0xff;
+        short[3] = 0x00;
+        assert!(parse_config_descriptor(&short).is_err());
+
+        // Endpoint before any interface.
+        let mut orphan = vec![9u8, 0x02, 0, 0, 2, 1, 0, 0x80, 50];
+        orphan.extend_from_slice(&[7, 0x05, 0x81, 0x02, 0x00, 0x02, 0]);
+        let total = orphan.len() as u16;
+        orphan[2..4].copy_from_slice(&total.to_le_bytes());
+        assert!(parse_config_descriptor(&orphan).is_err());
+    }
+
+    #[test]
+    fn test_endpoint_finders() {
+        let config = parse_config_descriptor(&canned_config()).unwrap();
+
+        assert_eq!(find_endpoints(&config, None).len(), 4);
+        assert_eq!(find_endpoints(&config, Some(0)).len(), 3);
+        assert_eq!(find_endpoints(&config, Some(1)).len(), 1);
+
+        assert_eq!(find_bulk_pair(&config.interfaces[0]), Some((0x81, 0x01)));
+        assert_eq!(find_interrupt_in(&config.interfaces[0]), Some(0x82));
+        // Alt 1 of interface 1 has no OUT endpoint, so no pair.
+        assert_eq!(find_bulk_pair(&config.interfaces[2]), None);
+        assert_eq!(find_interrupt_in(&config.interfaces[1]), None);
+    }
+}
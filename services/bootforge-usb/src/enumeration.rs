@@ -0,0 +1,3148 @@
+// BootForge USB - Device enumeration
+// Primary path goes through libusb; FallbackEnumerator reads sysfs for
+// hosts where libusb is unavailable or the device cannot be opened.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::context::SharedContext;
+use crate::error::UsbError;
+use crate::string_cache::{CacheKey, CachedStrings, StringDescriptorCache};
+use crate::strings::{get_string_descriptor_limited, MALFORMED_STRINGS_TAG};
+use crate::topology::{EndpointInfo, EndpointKind, Speed};
+use crate::transfer::{DescriptorLimits, UsbTransport, OVERSIZED_DESCRIPTOR_TAG};
+use crate::usb_ids::{self, UsbIds};
+use crate::version::BcdVersion;
+
+/// Timeout for best-effort string descriptor reads during enumeration.
+const STRING_READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/**
+ * Summary of the device descriptor shared by both enumeration paths.
+ *
+ * `usb_version` and `device_version` carry the structured BCD value; the
+ * canonical string form is derived on demand via `usb_version_string`.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct UsbDescriptorSummary {
+    pub usb_version: BcdVersion,
+    pub device_version: BcdVersion,
+    pub device_class: u8,
+    pub device_subclass: u8,
+    pub device_protocol: u8,
+    pub max_packet_size_0: u8,
+    pub num_configurations: u8,
+}
+
+impl UsbDescriptorSummary {
+    /// Canonical rendering of bcdUSB ("2.10", "3.20"), matching lsusb.
+    pub fn usb_version_string(&self) -> String {
+        self.usb_version.to_string()
+    }
+}
+
+// Manual because BcdVersion carries no Default of its own; zeroed BCD
+// values are the established "not captured" convention in snapshots.
+impl Default for UsbDescriptorSummary {
+    fn default() -> Self {
+        UsbDescriptorSummary {
+            usb_version: BcdVersion(0),
+            device_version: BcdVersion(0),
+            device_class: 0,
+            device_subclass: 0,
+            device_protocol: 0,
+            max_packet_size_0: 0,
+            num_configurations: 0,
+        }
+    }
+}
+
+/**
+ * Device enumerated through libusb.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct UsbDeviceInfo {
+    pub bus_number: u8,
+    pub address: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub descriptor: UsbDescriptorSummary,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    /// Hub port chain in sysfs style, e.g. "3-1.4" (bus 3, hub port 1,
+    /// downstream port 4). None when the platform does not report it.
+    pub port_path: Option<String>,
+    /// Negotiated link speed as reported by the host stack; None when
+    /// the platform does not report it (notification-driven partial
+    /// records, older snapshots).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<Speed>,
+    /// Advisory quality flags, e.g. "descriptor:malformed-strings".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The configuration the device is currently in, when it could be
+    /// read. Absent for devices we could not probe and in snapshots
+    /// written before the field existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_config: Option<ConfigInfo>,
+    /// Names from the system usb.ids database, filled even when string
+    /// descriptors could not be read; None when the database has no
+    /// entry (or is not installed).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usb_ids: Option<UsbIds>,
+    /// Container ID from the BOS descriptor in canonical UUID text,
+    /// when the device advertises one and it could be read. Stable
+    /// across ports and re-enumeration, so it outranks bus position
+    /// for identity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+}
+
+impl UsbDeviceInfo {
+    /// Interfaces of the active configuration; empty when the
+    /// configuration descriptor could not be read.
+    pub fn interfaces(&self) -> &[InterfaceInfo] {
+        self.active_config
+            .as_ref()
+            .map_or(&[], |config| config.interfaces.as_slice())
+    }
+
+    /// Start a record with this VID/PID and everything else unset -
+    /// the supported way to construct one outside the crate now that
+    /// the struct is `#[non_exhaustive]`.
+    pub fn builder(vendor_id: u16, product_id: u16) -> UsbDeviceInfoBuilder {
+        UsbDeviceInfoBuilder {
+            info: UsbDeviceInfo {
+                bus_number: 0,
+                address: 0,
+                vendor_id,
+                product_id,
+                descriptor: UsbDescriptorSummary::default(),
+                manufacturer: None,
+                product: None,
+                serial_number: None,
+                port_path: None,
+                speed: None,
+                tags: Vec::new(),
+                active_config: None,
+                usb_ids: None,
+                container_id: None,
+            },
+        }
+    }
+}
+
+/**
+ * Builds a `UsbDeviceInfo` field by field, for tests and mocks in this
+ * crate and downstream. `build` normalises rather than rejects: string
+ * fields are trimmed (and dropped entirely when blank, matching how
+ * enumeration treats unreadable descriptors) and duplicate tags are
+ * collapsed.
+ */
+#[derive(Debug, Clone)]
+pub struct UsbDeviceInfoBuilder {
+    info: UsbDeviceInfo,
+}
+
+impl UsbDeviceInfoBuilder {
+    pub fn bus_number(mut self, bus_number: u8) -> Self {
+        self.info.bus_number = bus_number;
+        self
+    }
+
+    pub fn address(mut self, address: u8) -> Self {
+        self.info.address = address;
+        self
+    }
+
+    /// Replace the whole descriptor summary; the `class`/`subclass`/
+    /// `protocol`/`usb_version`/`device_version` setters tweak fields
+    /// within it.
+    pub fn descriptor(mut self, descriptor: UsbDescriptorSummary) -> Self {
+        self.info.descriptor = descriptor;
+        self
+    }
+
+    pub fn usb_version(mut self, version: BcdVersion) -> Self {
+        self.info.descriptor.usb_version = version;
+        self
+    }
+
+    pub fn device_version(mut self, version: BcdVersion) -> Self {
+        self.info.descriptor.device_version = version;
+        self
+    }
+
+    pub fn class(mut self, class: u8) -> Self {
+        self.info.descriptor.device_class = class;
+        self
+    }
+
+    pub fn subclass(mut self, subclass: u8) -> Self {
+        self.info.descriptor.device_subclass = subclass;
+        self
+    }
+
+    pub fn protocol(mut self, protocol: u8) -> Self {
+        self.info.descriptor.device_protocol = protocol;
+        self
+    }
+
+    pub fn max_packet_size_0(mut self, size: u8) -> Self {
+        self.info.descriptor.max_packet_size_0 = size;
+        self
+    }
+
+    pub fn num_configurations(mut self, count: u8) -> Self {
+        self.info.descriptor.num_configurations = count;
+        self
+    }
+
+    pub fn manufacturer(mut self, manufacturer: impl Into<String>) -> Self {
+        self.info.manufacturer = Some(manufacturer.into());
+        self
+    }
+
+    pub fn product(mut self, product: impl Into<String>) -> Self {
+        self.info.product = Some(product.into());
+        self
+    }
+
+    pub fn serial(mut self, serial: impl Into<String>) -> Self {
+        self.info.serial_number = Some(serial.into());
+        self
+    }
+
+    pub fn port_path(mut self, port_path: impl Into<String>) -> Self {
+        self.info.port_path = Some(port_path.into());
+        self
+    }
+
+    pub fn speed(mut self, speed: Speed) -> Self {
+        self.info.speed = Some(speed);
+        self
+    }
+
+    /// Add one advisory tag; duplicates are collapsed in `build`.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.info.tags.push(tag.into());
+        self
+    }
+
+    pub fn active_config(mut self, config: ConfigInfo) -> Self {
+        self.info.active_config = Some(config);
+        self
+    }
+
+    pub fn container_id(mut self, container_id: impl Into<String>) -> Self {
+        self.info.container_id = Some(container_id.into());
+        self
+    }
+
+    pub fn build(mut self) -> UsbDeviceInfo {
+        normalise_string(&mut self.info.manufacturer);
+        normalise_string(&mut self.info.product);
+        normalise_string(&mut self.info.serial_number);
+        normalise_string(&mut self.info.container_id);
+        dedup_tags(&mut self.info.tags);
+        self.info
+    }
+}
+
+/// Trim a builder-supplied string, dropping it when nothing remains -
+/// the same treatment enumeration gives unreadable descriptors.
+fn normalise_string(field: &mut Option<String>) {
+    if let Some(value) = field {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            *field = None;
+        } else if trimmed.len() != value.len() {
+            *field = Some(trimmed.to_string());
+        }
+    }
+}
+
+/// Drop repeated tags, keeping first-occurrence order.
+fn dedup_tags(tags: &mut Vec<String>) {
+    let mut seen = Vec::with_capacity(tags.len());
+    tags.retain(|tag| {
+        if seen.contains(tag) {
+            false
+        } else {
+            seen.push(tag.clone());
+            true
+        }
+    });
+}
+
+impl UsbDeviceInfo {
+    /**
+     * A human-readable product name, falling through a documented
+     * ladder until a rung holds: the product string descriptor, the
+     * usb.ids product name carried from enumeration, the device class
+     * name ("Mass Storage device"), and finally "USB device vid:pid".
+     */
+    pub fn display_name(&self) -> String {
+        self.named_product().unwrap_or_else(|| {
+            format!("USB device {:04x}:{:04x}", self.vendor_id, self.product_id)
+        })
+    }
+
+    /**
+     * As `display_name` for the vendor side: the manufacturer string,
+     * then the usb.ids vendor name, then "USB vendor vid".
+     */
+    pub fn vendor_display_name(&self) -> String {
+        self.manufacturer
+            .clone()
+            .or_else(|| {
+                self.usb_ids
+                    .as_ref()
+                    .and_then(|ids| ids.vendor_name.clone())
+            })
+            .unwrap_or_else(|| format!("USB vendor {:04x}", self.vendor_id))
+    }
+
+    /// The rungs of the ladder that actually name something; `Display`
+    /// stops here rather than repeat the VID:PID it already printed.
+    fn named_product(&self) -> Option<String> {
+        self.product
+            .clone()
+            .or_else(|| {
+                self.usb_ids
+                    .as_ref()
+                    .and_then(|ids| ids.product_name.clone())
+            })
+            .or_else(|| {
+                usb_ids::class_code_name(self.descriptor.device_class)
+                    .map(|name| format!("{} device", name))
+            })
+    }
+}
+
+impl fmt::Display for UsbDeviceInfo {
+    /// Lsusb-style one-liner: bus position, VID:PID, the best
+    /// available name (see `display_name`) and link speed when known.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Bus {:03} Device {:03}: {:04x}:{:04x}",
+            self.bus_number, self.address, self.vendor_id, self.product_id
+        )?;
+        if let Some(name) = self.named_product() {
+            write!(f, " {}", name)?;
+        }
+        if let Some(speed) = self.speed {
+            write!(f, " ({})", speed)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * The negotiated (active) configuration of an enumerated device:
+ * bConfigurationValue plus every interface alternate setting and its
+ * endpoints. Captured from the active configuration descriptor during
+ * enumeration; read-only, no configuration change is ever issued.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConfigInfo {
+    /// bConfigurationValue of the active configuration.
+    pub configuration_value: u8,
+    /// Maximum bus power draw in milliamps (bMaxPower scaled).
+    pub max_power_ma: u16,
+    pub self_powered: bool,
+    pub remote_wakeup: bool,
+    /// iConfiguration string; read only when
+    /// `EnumerationOptions::read_interface_strings` is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub interfaces: Vec<InterfaceInfo>,
+}
+
+/**
+ * One interface alternate setting within the active configuration.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    /// bInterfaceNumber.
+    pub number: u8,
+    /// bAlternateSetting; settings beyond 0 are listed but not selected.
+    pub alternate_setting: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    /// iInterface string; read only when
+    /// `EnumerationOptions::read_interface_strings` is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub endpoints: Vec<EndpointInfo>,
+}
+
+/**
+ * Device enumerated through the sysfs fallback path.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct UsbDeviceRecord {
+    pub bus_number: u8,
+    pub device_number: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub descriptor: UsbDescriptorSummary,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    pub sysfs_path: String,
+    /// Negotiated link speed from the sysfs `speed` attribute; None
+    /// when the attribute is absent or reports a rate we do not know.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<Speed>,
+    /// Interfaces of the active configuration; empty when the kernel
+    /// did not export interface directories for the device.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interfaces: Vec<InterfaceSummary>,
+}
+
+impl UsbDeviceRecord {
+    /// Start a record with this VID/PID and everything else unset; the
+    /// literal-construction counterpart for `#[non_exhaustive]`, as on
+    /// `UsbDeviceInfo`.
+    pub fn builder(vendor_id: u16, product_id: u16) -> UsbDeviceRecordBuilder {
+        UsbDeviceRecordBuilder {
+            record: UsbDeviceRecord {
+                bus_number: 0,
+                device_number: 0,
+                vendor_id,
+                product_id,
+                descriptor: UsbDescriptorSummary::default(),
+                manufacturer: None,
+                product: None,
+                serial_number: None,
+                sysfs_path: String::new(),
+                speed: None,
+                interfaces: Vec::new(),
+            },
+        }
+    }
+
+    /**
+     * As `UsbDeviceInfo::display_name`. Records carry no resolved
+     * usb.ids names, so that rung consults the system database
+     * directly; the rest of the ladder is identical.
+     */
+    pub fn display_name(&self) -> String {
+        self.product
+            .clone()
+            .or_else(|| {
+                usb_ids::lookup_product(self.vendor_id, self.product_id).map(str::to_string)
+            })
+            .or_else(|| {
+                usb_ids::class_code_name(self.descriptor.device_class)
+                    .map(|name| format!("{} device", name))
+            })
+            .unwrap_or_else(|| {
+                format!("USB device {:04x}:{:04x}", self.vendor_id, self.product_id)
+            })
+    }
+
+    /**
+     * As `UsbDeviceInfo::vendor_display_name`, against the system
+     * usb.ids database.
+     */
+    pub fn vendor_display_name(&self) -> String {
+        self.manufacturer
+            .clone()
+            .or_else(|| usb_ids::lookup_vendor(self.vendor_id).map(str::to_string))
+            .unwrap_or_else(|| format!("USB vendor {:04x}", self.vendor_id))
+    }
+}
+
+/**
+ * Builds a `UsbDeviceRecord` with the same normalisation rules as
+ * `UsbDeviceInfoBuilder`.
+ */
+#[derive(Debug, Clone)]
+pub struct UsbDeviceRecordBuilder {
+    record: UsbDeviceRecord,
+}
+
+impl UsbDeviceRecordBuilder {
+    pub fn bus_number(mut self, bus_number: u8) -> Self {
+        self.record.bus_number = bus_number;
+        self
+    }
+
+    pub fn device_number(mut self, device_number: u8) -> Self {
+        self.record.device_number = device_number;
+        self
+    }
+
+    pub fn descriptor(mut self, descriptor: UsbDescriptorSummary) -> Self {
+        self.record.descriptor = descriptor;
+        self
+    }
+
+    pub fn usb_version(mut self, version: BcdVersion) -> Self {
+        self.record.descriptor.usb_version = version;
+        self
+    }
+
+    pub fn device_version(mut self, version: BcdVersion) -> Self {
+        self.record.descriptor.device_version = version;
+        self
+    }
+
+    pub fn class(mut self, class: u8) -> Self {
+        self.record.descriptor.device_class = class;
+        self
+    }
+
+    pub fn subclass(mut self, subclass: u8) -> Self {
+        self.record.descriptor.device_subclass = subclass;
+        self
+    }
+
+    pub fn protocol(mut self, protocol: u8) -> Self {
+        self.record.descriptor.device_protocol = protocol;
+        self
+    }
+
+    pub fn max_packet_size_0(mut self, size: u8) -> Self {
+        self.record.descriptor.max_packet_size_0 = size;
+        self
+    }
+
+    pub fn num_configurations(mut self, count: u8) -> Self {
+        self.record.descriptor.num_configurations = count;
+        self
+    }
+
+    pub fn manufacturer(mut self, manufacturer: impl Into<String>) -> Self {
+        self.record.manufacturer = Some(manufacturer.into());
+        self
+    }
+
+    pub fn product(mut self, product: impl Into<String>) -> Self {
+        self.record.product = Some(product.into());
+        self
+    }
+
+    pub fn serial(mut self, serial: impl Into<String>) -> Self {
+        self.record.serial_number = Some(serial.into());
+        self
+    }
+
+    pub fn sysfs_path(mut self, sysfs_path: impl Into<String>) -> Self {
+        self.record.sysfs_path = sysfs_path.into();
+        self
+    }
+
+    pub fn speed(mut self, speed: Speed) -> Self {
+        self.record.speed = Some(speed);
+        self
+    }
+
+    /// Append one interface summary by its class triple.
+    pub fn interface(mut self, class: u8, subclass: u8, protocol: u8) -> Self {
+        self.record.interfaces.push(InterfaceSummary {
+            class,
+            subclass,
+            protocol,
+            description: None,
+        });
+        self
+    }
+
+    pub fn build(mut self) -> UsbDeviceRecord {
+        normalise_string(&mut self.record.manufacturer);
+        normalise_string(&mut self.record.product);
+        normalise_string(&mut self.record.serial_number);
+        self.record
+    }
+}
+
+/**
+ * Interface-level summary on the sysfs fallback path: the class triple
+ * classification wants, plus the iInterface string when exported.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InterfaceSummary {
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub description: Option<String>,
+}
+
+/**
+ * Predicate over enumerated devices, shared by enumeration consumers
+ * and the event journal. Unset fields match everything.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeviceFilter {
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub serial_number: Option<String>,
+    /// Matches devices carrying this tag, e.g. "descriptor:malformed-strings".
+    /// Sysfs records carry no tags, so a set tag fails every record.
+    pub tag: Option<String>,
+    /// Additional accepted vendor ids; a device matches `vendor_id` or
+    /// any entry here.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vendor_ids: Vec<u16>,
+    /// Inclusive product id ranges, alongside `product_id`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub product_id_ranges: Vec<(u16, u16)>,
+    /// Accepted device classes; empty accepts every class.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<u8>,
+    /// Accepted interface classes: a device matches when any interface
+    /// of its active configuration carries one. A device whose
+    /// interfaces are unknown fails the filter rather than passing
+    /// unvetted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interface_classes: Vec<u8>,
+    /// Case-sensitive substring of the serial, alongside the exact
+    /// match in `serial_number`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_substring: Option<String>,
+    /// Require (or forbid, with false) a serial number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_serial: Option<bool>,
+}
+
+impl DeviceFilter {
+    /// Matches every device.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    pub fn with_vendor_id(mut self, vendor_id: u16) -> Self {
+        if self.vendor_id.is_some() {
+            // Additional vendors accumulate rather than replace.
+            self.vendor_ids.push(vendor_id);
+        } else {
+            self.vendor_id = Some(vendor_id);
+        }
+        self
+    }
+
+    pub fn with_product_id(mut self, product_id: u16) -> Self {
+        self.product_id = Some(product_id);
+        self
+    }
+
+    /// Accept product ids in `lo..=hi`, alongside any exact id.
+    pub fn with_product_id_range(mut self, lo: u16, hi: u16) -> Self {
+        self.product_id_ranges.push((lo.min(hi), lo.max(hi)));
+        self
+    }
+
+    pub fn with_class(mut self, class: u8) -> Self {
+        self.classes.push(class);
+        self
+    }
+
+    pub fn with_interface_class(mut self, class: u8) -> Self {
+        self.interface_classes.push(class);
+        self
+    }
+
+    pub fn with_serial_number(mut self, serial: impl Into<String>) -> Self {
+        self.serial_number = Some(serial.into());
+        self
+    }
+
+    /// Accept serials containing this substring - fleets often share a
+    /// batch prefix worth matching on.
+    pub fn with_serial_substring(mut self, substring: impl Into<String>) -> Self {
+        self.serial_substring = Some(substring.into());
+        self
+    }
+
+    /// Require that the device reports (or omits) a serial number.
+    pub fn with_has_serial(mut self, has_serial: bool) -> Self {
+        self.has_serial = Some(has_serial);
+        self
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /**
+     * The part of the filter decidable from the device descriptor
+     * alone, used before a device is opened for string reads.
+     */
+    pub fn matches_descriptor(&self, vendor_id: u16, product_id: u16, device_class: u8) -> bool {
+        let vendor_ok = (self.vendor_id.is_none() && self.vendor_ids.is_empty())
+            || self.vendor_id == Some(vendor_id)
+            || self.vendor_ids.contains(&vendor_id);
+        let product_ok = (self.product_id.is_none() && self.product_id_ranges.is_empty())
+            || self.product_id == Some(product_id)
+            || self
+                .product_id_ranges
+                .iter()
+                .any(|&(lo, hi)| (lo..=hi).contains(&product_id));
+        let class_ok = self.classes.is_empty() || self.classes.contains(&device_class);
+        vendor_ok && product_ok && class_ok
+    }
+
+    pub fn matches(&self, info: &UsbDeviceInfo) -> bool {
+        self.matches_descriptor(info.vendor_id, info.product_id, info.descriptor.device_class)
+            && (self.interface_classes.is_empty()
+                || info.active_config.as_ref().is_some_and(|config| {
+                    config
+                        .interfaces
+                        .iter()
+                        .any(|i| self.interface_classes.contains(&i.class))
+                }))
+            && self
+                .serial_number
+                .as_deref()
+                .is_none_or(|s| info.serial_number.as_deref() == Some(s))
+            && self.serial_matches_substring(info.serial_number.as_deref())
+            && self
+                .has_serial
+                .is_none_or(|want| info.serial_number.is_some() == want)
+            && self
+                .tag
+                .as_deref()
+                .is_none_or(|t| info.tags.iter().any(|tag| tag == t))
+    }
+
+    /// `matches` for the sysfs fallback path's records.
+    pub fn matches_record(&self, record: &UsbDeviceRecord) -> bool {
+        self.matches_descriptor(
+            record.vendor_id,
+            record.product_id,
+            record.descriptor.device_class,
+        ) && (self.interface_classes.is_empty()
+            || record
+                .interfaces
+                .iter()
+                .any(|i| self.interface_classes.contains(&i.class)))
+            && self
+                .serial_number
+                .as_deref()
+                .is_none_or(|s| record.serial_number.as_deref() == Some(s))
+            && self.serial_matches_substring(record.serial_number.as_deref())
+            && self
+                .has_serial
+                .is_none_or(|want| record.serial_number.is_some() == want)
+            // Records carry no tags; fail closed rather than matching
+            // every record when a tag is asked for, as with unknown
+            // interfaces above.
+            && self.tag.is_none()
+    }
+
+    fn serial_matches_substring(&self, serial: Option<&str>) -> bool {
+        self.serial_substring
+            .as_deref()
+            .is_none_or(|sub| serial.is_some_and(|serial| serial.contains(sub)))
+    }
+}
+
+/**
+ * Built-in skip controls for application-facing device lists. Skips are
+ * decided from the device descriptor alone, before any open, so skipped
+ * devices cost nothing and never wake from selective suspend.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnumerationOptions {
+    /// Skip hubs (device class 0x09): root hubs, internal hubs.
+    pub skip_hubs: bool,
+    /// Skip devices with these device classes, e.g. 0x11 for billboards.
+    pub skip_classes: Vec<u8>,
+    pub skip_vendors: Vec<u16>,
+    /// When non-empty, only devices matching one of these VID (and
+    /// optionally PID) entries are scanned; everything else is skipped
+    /// before any open. The inverse of `skip_vendors`, for callers that
+    /// know exactly which hardware they care about.
+    #[serde(default)]
+    pub allow_devices: Vec<(u16, Option<u16>)>,
+    /// Bounds on descriptor reads; see `transfer::DescriptorLimits`.
+    #[serde(default)]
+    pub limits: DescriptorLimits,
+    /// Timeout for each language and string descriptor read.
+    #[serde(default = "default_string_timeout")]
+    pub string_timeout: Duration,
+    /// Worker threads for the string-probe phase; 1 probes sequentially.
+    /// A device that stalls on string reads costs its own timeouts but
+    /// only ties up one worker.
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+    /// When false, devices are never opened and the string fields stay
+    /// unset: a descriptor-only scan with no per-device timeouts at all.
+    #[serde(default = "default_read_strings")]
+    pub read_strings: bool,
+    /// Also read iConfiguration and iInterface strings into the active
+    /// configuration. Off by default: it multiplies the control
+    /// transfers per device, and most devices leave the indices unset.
+    #[serde(default)]
+    pub read_interface_strings: bool,
+    /// Opt-in cache of string-probe results, consulted before a device
+    /// is opened; see `string_cache::StringDescriptorCache`.
+    #[serde(skip)]
+    pub string_cache: Option<Arc<StringDescriptorCache>>,
+}
+
+// Manual so the cache - runtime state, not configuration - compares by
+// handle identity instead of demanding PartialEq of its contents.
+impl PartialEq for EnumerationOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.skip_hubs == other.skip_hubs
+            && self.skip_classes == other.skip_classes
+            && self.skip_vendors == other.skip_vendors
+            && self.allow_devices == other.allow_devices
+            && self.limits == other.limits
+            && self.string_timeout == other.string_timeout
+            && self.parallelism == other.parallelism
+            && self.read_strings == other.read_strings
+            && self.read_interface_strings == other.read_interface_strings
+            && match (&self.string_cache, &other.string_cache) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+impl Eq for EnumerationOptions {}
+
+/// Device class of hubs.
+const HUB_CLASS: u8 = 0x09;
+
+fn default_string_timeout() -> Duration {
+    STRING_READ_TIMEOUT
+}
+
+fn default_parallelism() -> usize {
+    4
+}
+
+fn default_read_strings() -> bool {
+    true
+}
+
+impl Default for EnumerationOptions {
+    fn default() -> Self {
+        EnumerationOptions {
+            skip_hubs: false,
+            skip_classes: Vec::new(),
+            skip_vendors: Vec::new(),
+            allow_devices: Vec::new(),
+            limits: DescriptorLimits::default(),
+            string_timeout: default_string_timeout(),
+            parallelism: default_parallelism(),
+            read_strings: default_read_strings(),
+            read_interface_strings: false,
+            string_cache: None,
+        }
+    }
+}
+
+impl EnumerationOptions {
+    pub fn with_skip_hubs(mut self, skip_hubs: bool) -> Self {
+        self.skip_hubs = skip_hubs;
+        self
+    }
+
+    pub fn with_skip_class(mut self, class: u8) -> Self {
+        self.skip_classes.push(class);
+        self
+    }
+
+    pub fn with_skip_vendor(mut self, vendor_id: u16) -> Self {
+        self.skip_vendors.push(vendor_id);
+        self
+    }
+
+    /// Restrict the scan to this vendor, or to one of its products when
+    /// `product_id` is given. Repeatable; the entries form an allowlist.
+    pub fn with_allowed_device(mut self, vendor_id: u16, product_id: Option<u16>) -> Self {
+        self.allow_devices.push((vendor_id, product_id));
+        self
+    }
+
+    pub fn with_limits(mut self, limits: DescriptorLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_string_timeout(mut self, timeout: Duration) -> Self {
+        self.string_timeout = timeout;
+        self
+    }
+
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    pub fn with_read_strings(mut self, read_strings: bool) -> Self {
+        self.read_strings = read_strings;
+        self
+    }
+
+    pub fn with_read_interface_strings(mut self, read_interface_strings: bool) -> Self {
+        self.read_interface_strings = read_interface_strings;
+        self
+    }
+
+    pub fn with_string_cache(mut self, cache: Arc<StringDescriptorCache>) -> Self {
+        self.string_cache = Some(cache);
+        self
+    }
+
+    /**
+     * Whether a device with this class/vendor pair should be skipped.
+     * Shared by enumeration and the watcher-side consumers so lists and
+     * events agree about what exists.
+     */
+    pub fn should_skip(&self, device_class: u8, vendor_id: u16) -> bool {
+        (self.skip_hubs && device_class == HUB_CLASS)
+            || self.skip_classes.contains(&device_class)
+            || self.skip_vendors.contains(&vendor_id)
+    }
+
+    /**
+     * Whether a device passes the allowlist. Always true when the
+     * allowlist is empty; the skip controls in `should_skip` still
+     * apply either way.
+     */
+    pub fn allows(&self, vendor_id: u16, product_id: u16) -> bool {
+        self.allow_devices.is_empty()
+            || self
+                .allow_devices
+                .iter()
+                .any(|(vid, pid)| *vid == vendor_id && pid.is_none_or(|p| p == product_id))
+    }
+
+    /// Which filtered-count bucket a skipped device falls into.
+    fn count(&self, counts: &mut FilteredCounts, device_class: u8) {
+        if self.skip_hubs && device_class == HUB_CLASS {
+            counts.hubs += 1;
+        } else if self.skip_classes.contains(&device_class) {
+            counts.by_class += 1;
+        } else {
+            counts.by_vendor += 1;
+        }
+    }
+}
+
+/**
+ * How many listed devices the skip controls filtered out, kept in the
+ * report so "my device is missing" can be distinguished from "my device
+ * was filtered".
+ */
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FilteredCounts {
+    pub hubs: usize,
+    pub by_class: usize,
+    pub by_vendor: usize,
+    /// Devices outside a non-empty allowlist.
+    #[serde(default)]
+    pub not_allowed: usize,
+}
+
+impl FilteredCounts {
+    pub fn total(&self) -> usize {
+        self.hubs + self.by_class + self.by_vendor + self.not_allowed
+    }
+}
+
+/// Stage of enumeration a device was lost at.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ProbeStage {
+    /// Reading the device descriptor after listing.
+    Probe,
+    /// Reading string descriptors.
+    Strings,
+}
+
+/// Why a listed device did not make it into the result.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Device vanished between listing and probing.
+    Disconnected,
+    Other(String),
+}
+
+impl SkipReason {
+    fn from_rusb(e: rusb::Error) -> Self {
+        match e {
+            rusb::Error::NoDevice => SkipReason::Disconnected,
+            other => SkipReason::Other(other.to_string()),
+        }
+    }
+}
+
+/**
+ * A device that was present in the device list but could not be probed.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SkippedDevice {
+    pub bus_number: u8,
+    pub address: u8,
+    pub stage: ProbeStage,
+    pub error: SkipReason,
+}
+
+/**
+ * Full outcome of an enumeration pass: the devices that probed
+ * successfully plus first-class records of the ones that vanished
+ * mid-probe.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct EnumerationReport {
+    pub devices: Vec<UsbDeviceInfo>,
+    pub skipped: Vec<SkippedDevice>,
+    /// Devices filtered out by the skip controls, by bucket.
+    #[serde(default)]
+    pub filtered: FilteredCounts,
+}
+
+/**
+ * Enumerate devices through libusb.
+ *
+ * Convenience adapter over `enumerate_libusb_report` for callers that
+ * only want the successfully-probed devices.
+ */
+pub fn enumerate_libusb() -> Result<Vec<UsbDeviceInfo>, UsbError> {
+    Ok(enumerate_libusb_report()?.devices)
+}
+
+/**
+ * Enumerate devices through libusb, reporting probe failures instead of
+ * silently dropping them.
+ *
+ * String descriptors are read best-effort: devices we cannot open still
+ * appear in the result with the string fields unset.
+ */
+pub fn enumerate_libusb_report() -> Result<EnumerationReport, UsbError> {
+    enumerate_libusb_report_with(&EnumerationOptions::default())
+}
+
+/**
+ * Enumerate devices through libusb with skip controls applied before
+ * any device is opened.
+ */
+pub fn enumerate_libusb_report_with(
+    options: &EnumerationOptions,
+) -> Result<EnumerationReport, UsbError> {
+    // libusb can abort the process on some platforms when USB access is
+    // denied outright; honour the crate's no-panic guarantee by turning
+    // any unwind into UsbError::Internal.
+    guard_panics("libusb enumeration", || {
+        enumerate_libusb_report_inner(&rusb::GlobalContext::default(), options, None)
+    })
+}
+
+/**
+ * Enumerate devices through libusb, keeping only those matching
+ * `filter`.
+ *
+ * The VID/PID/class part of the filter is applied before a device is
+ * opened, so devices the filter rejects are never probed for string
+ * descriptors; serial and tag conditions apply afterwards.
+ */
+pub fn enumerate_filtered(filter: &DeviceFilter) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+    enumerate_filtered_where(filter, |_| true)
+}
+
+/**
+ * As `enumerate_filtered`, with an additional caller predicate for
+ * conditions the declarative filter cannot express.
+ */
+pub fn enumerate_filtered_where(
+    filter: &DeviceFilter,
+    predicate: impl Fn(&UsbDeviceInfo) -> bool,
+) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+    let report = guard_panics("libusb enumeration", || {
+        enumerate_libusb_report_inner(
+            &rusb::GlobalContext::default(),
+            &EnumerationOptions::default(),
+            Some(filter),
+        )
+    })?;
+    Ok(report
+        .devices
+        .into_iter()
+        .filter(|info| filter.matches(info) && predicate(info))
+        .collect())
+}
+
+/**
+ * Is a device with this VID/PID present right now?
+ *
+ * Walks device descriptors only - no open, no string reads, no
+ * enrichment - so it answers the presence question without the
+ * per-device cost of a full `enumerate_libusb` pass, which on some
+ * hosts runs to hundreds of milliseconds.
+ */
+pub fn probe_libusb(vendor_id: u16, product_id: u16) -> Result<bool, UsbError> {
+    guard_panics("libusb probe", || {
+        use rusb::UsbContext;
+        for device in rusb::GlobalContext::default().devices()?.iter() {
+            if let Ok(descriptor) = device.device_descriptor() {
+                if descriptor.vendor_id() == vendor_id && descriptor.product_id() == product_id {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    })
+}
+
+/**
+ * The first device matching VID/PID, and `serial` when given.
+ *
+ * Non-matching devices are rejected on descriptor data before any open;
+ * the serial condition needs string reads, so passing one costs a probe
+ * of each VID/PID match - still far less than a full pass. None means
+ * no such device, not a bus failure.
+ */
+pub fn get_device(
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<&str>,
+) -> Result<Option<UsbDeviceInfo>, UsbError> {
+    let mut filter = DeviceFilter::any()
+        .with_vendor_id(vendor_id)
+        .with_product_id(product_id);
+    if let Some(serial) = serial {
+        filter = filter.with_serial_number(serial);
+    }
+    Ok(enumerate_filtered(&filter)?.into_iter().next())
+}
+
+/**
+ * As `enumerate_libusb_report_with`, but on a caller-owned context
+ * instead of rusb's global one, so repeated passes reuse a single
+ * libusb initialization (see `context::SharedContext`).
+ */
+pub fn enumerate_libusb_report_in(
+    context: &SharedContext,
+    options: &EnumerationOptions,
+) -> Result<EnumerationReport, UsbError> {
+    context.with_context(|ctx| {
+        let ctx = std::panic::AssertUnwindSafe(ctx);
+        guard_panics("libusb enumeration", move || {
+            enumerate_libusb_report_inner(*ctx, options, None)
+        })
+    })?
+}
+
+/// Convert a panic in `f` into UsbError::Internal; public enumeration
+/// APIs must return Err, never unwind (see the crate docs).
+pub(crate) fn guard_panics<T>(
+    what: &str,
+    f: impl FnOnce() -> Result<T, UsbError> + std::panic::UnwindSafe,
+) -> Result<T, UsbError> {
+    std::panic::catch_unwind(f).unwrap_or_else(|payload| {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        Err(UsbError::Internal(format!("{} panicked: {}", what, msg)))
+    })
+}
+
+/// An entered stage span that records its elapsed time on drop, so
+/// `tracing-subscriber` users get a per-stage breakdown for free.
+#[cfg(feature = "tracing")]
+struct StageTimer {
+    span: tracing::span::EnteredSpan,
+    started: std::time::Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl StageTimer {
+    fn new(span: tracing::Span) -> Self {
+        StageTimer {
+            span: span.entered(),
+            started: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for StageTimer {
+    fn drop(&mut self) {
+        self.span
+            .record("elapsed_ms", self.started.elapsed().as_millis() as u64);
+    }
+}
+
+fn enumerate_libusb_report_inner<C: rusb::UsbContext>(
+    context: &C,
+    options: &EnumerationOptions,
+    filter: Option<&DeviceFilter>,
+) -> Result<EnumerationReport, UsbError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("enumerate").entered();
+
+    let mut report = EnumerationReport::default();
+    let mut probes: Vec<(rusb::Device<C>, rusb::DeviceDescriptor)> = Vec::new();
+
+    #[cfg(feature = "tracing")]
+    let scan_stage = StageTimer::new(tracing::info_span!(
+        "scan_devices",
+        elapsed_ms = tracing::field::Empty
+    ));
+    for device in context.devices()?.iter() {
+        let descriptor = match device.device_descriptor() {
+            Ok(d) => d,
+            Err(e) => {
+                log::debug!(
+                    "skipping device on bus {} addr {}: {}",
+                    device.bus_number(),
+                    device.address(),
+                    e
+                );
+                report.skipped.push(SkippedDevice {
+                    bus_number: device.bus_number(),
+                    address: device.address(),
+                    stage: ProbeStage::Probe,
+                    error: SkipReason::from_rusb(e),
+                });
+                continue;
+            }
+        };
+
+        // Skip controls run on descriptor data alone, before any open.
+        if options.should_skip(descriptor.class_code(), descriptor.vendor_id()) {
+            options.count(&mut report.filtered, descriptor.class_code());
+            continue;
+        }
+        if !options.allows(descriptor.vendor_id(), descriptor.product_id()) {
+            report.filtered.not_allowed += 1;
+            continue;
+        }
+
+        // Likewise the descriptor-decidable part of a caller filter, so
+        // non-matching devices are never opened for string reads.
+        if let Some(filter) = filter {
+            if !filter.matches_descriptor(
+                descriptor.vendor_id(),
+                descriptor.product_id(),
+                descriptor.class_code(),
+            ) {
+                continue;
+            }
+        }
+
+        let summary = UsbDescriptorSummary {
+            usb_version: BcdVersion::from(descriptor.usb_version()),
+            device_version: BcdVersion::from(descriptor.device_version()),
+            device_class: descriptor.class_code(),
+            device_subclass: descriptor.sub_class_code(),
+            device_protocol: descriptor.protocol_code(),
+            max_packet_size_0: descriptor.max_packet_size(),
+            num_configurations: descriptor.num_configurations(),
+        };
+
+        let speed = link_speed(device.speed());
+        let info = UsbDeviceInfo {
+            bus_number: device.bus_number(),
+            address: device.address(),
+            vendor_id: descriptor.vendor_id(),
+            product_id: descriptor.product_id(),
+            descriptor: summary,
+            manufacturer: None,
+            product: None,
+            serial_number: None,
+            port_path: port_path(&device),
+            speed,
+            tags: Vec::new(),
+            active_config: device
+                .active_config_descriptor()
+                .ok()
+                .map(|config| config_info(config, speed)),
+            usb_ids: usb_ids::system_db().and_then(|db| {
+                db.resolve_device(
+                    descriptor.vendor_id(),
+                    descriptor.product_id(),
+                    descriptor.class_code(),
+                    descriptor.sub_class_code(),
+                    descriptor.protocol_code(),
+                )
+            }),
+            container_id: None,
+        };
+
+        #[cfg(feature = "tracing")]
+        if info.usb_ids.is_none() {
+            tracing::debug!(
+                vid = info.vendor_id,
+                pid = info.product_id,
+                "no usb.ids entry for device"
+            );
+        }
+
+        probes.push((device, descriptor));
+        report.devices.push(info);
+    }
+    #[cfg(feature = "tracing")]
+    drop(scan_stage);
+
+    if options.read_strings {
+        #[cfg(feature = "tracing")]
+        let _stage = StageTimer::new(tracing::info_span!(
+            "probe_strings",
+            elapsed_ms = tracing::field::Empty
+        ));
+        probe_strings_all(&probes, &mut report.devices, options);
+    }
+
+    #[cfg(feature = "tracing")]
+    let classify_stage = StageTimer::new(tracing::info_span!(
+        "classify",
+        elapsed_ms = tracing::field::Empty
+    ));
+    // Mode tags want the product string, so they go on last.
+    for info in &mut report.devices {
+        crate::protocols::apple::tag_apple_mode(info);
+        #[cfg(feature = "tracing")]
+        if !info.tags.is_empty() {
+            tracing::debug!(
+                vid = info.vendor_id,
+                pid = info.product_id,
+                tags = ?info.tags,
+                "device classified"
+            );
+        }
+    }
+
+    // Some host stacks (Intel xHCI controllers on Windows, notably)
+    // hand libusb the same physical device through two controller
+    // views, with slightly different address data.
+    report.devices = dedup_device_info(std::mem::take(&mut report.devices));
+    #[cfg(feature = "tracing")]
+    drop(classify_stage);
+
+    Ok(report)
+}
+
+/**
+ * Merge two records believed to describe the same physical device.
+ *
+ * Precedence: `a` is the libusb-scanned record and wins for bus
+ * position and descriptor numerics; `b` is the platform-layer record
+ * and wins for `port_path`. Everything optional falls back to whichever
+ * side has a value (`a` first), and tags are unioned in
+ * first-occurrence order.
+ */
+pub fn merge_device_info(a: UsbDeviceInfo, b: UsbDeviceInfo) -> UsbDeviceInfo {
+    let mut merged = a;
+    merged.port_path = b.port_path.or(merged.port_path);
+    merged.manufacturer = merged.manufacturer.or(b.manufacturer);
+    merged.product = merged.product.or(b.product);
+    merged.serial_number = merged.serial_number.or(b.serial_number);
+    merged.speed = merged.speed.or(b.speed);
+    merged.active_config = merged.active_config.or(b.active_config);
+    merged.usb_ids = merged.usb_ids.or(b.usb_ids);
+    merged.container_id = merged.container_id.or(b.container_id);
+    for tag in b.tags {
+        if !merged.tags.contains(&tag) {
+            merged.tags.push(tag);
+        }
+    }
+    merged
+}
+
+/**
+ * Collapse duplicate records of one physical device, merging with
+ * `merge_device_info`. Records are matched on (bus, address) first,
+ * then on (VID, PID, serial) for platform-minted records whose bus
+ * position disagrees or is unknown. The zeroed position of a
+ * notification-driven partial record never matches on position alone.
+ */
+pub fn dedup_device_info(devices: Vec<UsbDeviceInfo>) -> Vec<UsbDeviceInfo> {
+    let mut out: Vec<UsbDeviceInfo> = Vec::with_capacity(devices.len());
+    let mut by_position: HashMap<(u8, u8), usize> = HashMap::new();
+    let mut by_identity: HashMap<(u16, u16, String), usize> = HashMap::new();
+
+    for device in devices {
+        let position =
+            (device.bus_number != 0 || device.address != 0).then_some((device.bus_number, device.address));
+        let identity = device
+            .serial_number
+            .clone()
+            .map(|serial| (device.vendor_id, device.product_id, serial));
+
+        let existing = position
+            .and_then(|p| by_position.get(&p).copied())
+            .or_else(|| identity.as_ref().and_then(|k| by_identity.get(k).copied()));
+
+        match existing {
+            Some(index) => {
+                let merged = merge_device_info(std::mem::replace(&mut out[index], placeholder()), device);
+                out[index] = merged;
+            }
+            None => {
+                let index = out.len();
+                if let Some(position) = position {
+                    by_position.insert(position, index);
+                }
+                if let Some(identity) = identity {
+                    by_identity.insert(identity, index);
+                }
+                out.push(device);
+            }
+        }
+    }
+    out
+}
+
+/// Throwaway value for the swap in `dedup_device_info`.
+fn placeholder() -> UsbDeviceInfo {
+    UsbDeviceInfo::builder(0, 0).build()
+}
+
+/// String descriptors of one device: the open + language read + three
+/// string reads that used to run inline in the enumeration loop.
+#[derive(Default)]
+struct StringProbe {
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial_number: Option<String>,
+    container_id: Option<String>,
+    config_description: Option<String>,
+    /// iInterface strings keyed by (number, alternate_setting).
+    interface_descriptions: Vec<((u8, u8), String)>,
+    malformed: bool,
+    oversized: bool,
+}
+
+impl StringProbe {
+    fn from_cached(strings: CachedStrings) -> Self {
+        StringProbe {
+            manufacturer: strings.manufacturer,
+            product: strings.product,
+            serial_number: strings.serial_number,
+            container_id: strings.container_id,
+            config_description: strings.config_description,
+            interface_descriptions: strings.interface_descriptions,
+            malformed: strings.malformed,
+            oversized: strings.oversized,
+        }
+    }
+
+    fn to_cached(&self) -> CachedStrings {
+        CachedStrings {
+            manufacturer: self.manufacturer.clone(),
+            product: self.product.clone(),
+            serial_number: self.serial_number.clone(),
+            container_id: self.container_id.clone(),
+            config_description: self.config_description.clone(),
+            interface_descriptions: self.interface_descriptions.clone(),
+            malformed: self.malformed,
+            oversized: self.oversized,
+        }
+    }
+
+    fn apply(self, info: &mut UsbDeviceInfo) {
+        info.manufacturer = self.manufacturer;
+        info.product = self.product;
+        info.serial_number = self.serial_number;
+        info.container_id = self.container_id;
+        if let Some(config) = &mut info.active_config {
+            if self.config_description.is_some() {
+                config.description = self.config_description;
+            }
+            for ((number, alternate_setting), text) in self.interface_descriptions {
+                if let Some(interface) = config
+                    .interfaces
+                    .iter_mut()
+                    .find(|i| i.number == number && i.alternate_setting == alternate_setting)
+                {
+                    interface.description = Some(text);
+                }
+            }
+        }
+        if self.malformed {
+            info.tags.push(MALFORMED_STRINGS_TAG.to_string());
+        }
+        if self.oversized {
+            info.tags.push(OVERSIZED_DESCRIPTOR_TAG.to_string());
+        }
+    }
+}
+
+fn probe_strings_one<C: rusb::UsbContext>(
+    device: &rusb::Device<C>,
+    descriptor: &rusb::DeviceDescriptor,
+    options: &EnumerationOptions,
+) -> StringProbe {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!(
+        "probe_device",
+        vid = descriptor.vendor_id(),
+        pid = descriptor.product_id(),
+        bus = device.bus_number(),
+        address = device.address()
+    )
+    .entered();
+
+    let key = CacheKey::new(
+        descriptor.vendor_id(),
+        descriptor.product_id(),
+        device.bus_number(),
+        device.address(),
+    );
+    if let Some(cache) = &options.string_cache {
+        if let Some(strings) = cache.get(&key) {
+            return StringProbe::from_cached(strings);
+        }
+    }
+
+    let mut probe = StringProbe::default();
+    let mut opened = false;
+    if let Ok(mut handle) = device.open() {
+        opened = true;
+        if let Ok(Some(language)) = handle
+            .read_languages(options.string_timeout)
+            .map(|l| l.first().copied())
+        {
+            let lang_id = language.lang_id();
+            probe.manufacturer = read_string(
+                &mut handle,
+                descriptor.manufacturer_string_index(),
+                lang_id,
+                options,
+                &mut probe.malformed,
+                &mut probe.oversized,
+            );
+            probe.product = read_string(
+                &mut handle,
+                descriptor.product_string_index(),
+                lang_id,
+                options,
+                &mut probe.malformed,
+                &mut probe.oversized,
+            );
+            probe.serial_number = read_string(
+                &mut handle,
+                descriptor.serial_number_string_index(),
+                lang_id,
+                options,
+                &mut probe.malformed,
+                &mut probe.oversized,
+            );
+            if options.read_interface_strings {
+                if let Ok(config) = device.active_config_descriptor() {
+                    probe.config_description = read_string(
+                        &mut handle,
+                        config.description_string_index(),
+                        lang_id,
+                        options,
+                        &mut probe.malformed,
+                        &mut probe.oversized,
+                    );
+                    for alt in config
+                        .interfaces()
+                        .flat_map(|interface| interface.descriptors())
+                    {
+                        if let Some(text) = read_string(
+                            &mut handle,
+                            alt.description_string_index(),
+                            lang_id,
+                            options,
+                            &mut probe.malformed,
+                            &mut probe.oversized,
+                        ) {
+                            probe.interface_descriptions.push((
+                                (alt.interface_number(), alt.setting_number()),
+                                text,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        // The BOS (and with it a Container ID) only exists from USB
+        // 2.01 on; skipping older devices avoids a guaranteed stall.
+        if BcdVersion::from(descriptor.usb_version()) >= BcdVersion(0x0201) {
+            probe.container_id = read_container_id(&handle, options);
+        }
+    }
+    #[cfg(feature = "tracing")]
+    if !opened {
+        tracing::debug!("string descriptors unread: device open failed");
+    }
+    #[cfg(feature = "tracing")]
+    if probe.malformed {
+        tracing::debug!("device returned malformed string descriptors");
+    }
+
+    // Only successful opens are cached; a device we could not open is
+    // retried on the next pass instead of pinning empty strings.
+    if opened {
+        if let Some(cache) = &options.string_cache {
+            cache.insert(key, probe.to_cached());
+        }
+    }
+    probe
+}
+
+/// Best-effort Container ID from the BOS descriptor, in canonical UUID
+/// text. Any failure - stall, truncation, no such capability - is None.
+fn read_container_id<C: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<C>,
+    options: &EnumerationOptions,
+) -> Option<String> {
+    const REQUEST_TYPE_STANDARD_IN: u8 = 0x80;
+    const REQ_GET_DESCRIPTOR: u8 = 0x06;
+    const DESCRIPTOR_TYPE_BOS: u16 = 0x0f;
+
+    let mut header = [0u8; 5];
+    let n = handle
+        .read_control(
+            REQUEST_TYPE_STANDARD_IN,
+            REQ_GET_DESCRIPTOR,
+            DESCRIPTOR_TYPE_BOS << 8,
+            0,
+            &mut header,
+            options.string_timeout,
+        )
+        .ok()?;
+    if n < 5 {
+        return None;
+    }
+    let total = usize::from(u16::from_le_bytes([header[2], header[3]]));
+    if total < 5 || total > options.limits.max_bos_len {
+        return None;
+    }
+    let mut raw = vec![0u8; total];
+    let n = handle
+        .read_control(
+            REQUEST_TYPE_STANDARD_IN,
+            REQ_GET_DESCRIPTOR,
+            DESCRIPTOR_TYPE_BOS << 8,
+            0,
+            &mut raw,
+            options.string_timeout,
+        )
+        .ok()?;
+    raw.truncate(n);
+    crate::bos::BosDescriptor::parse(&raw)
+        .ok()?
+        .container_id()
+        .map(|id| id.to_string())
+}
+
+/// Probe string descriptors across a small worker pool. Workers claim
+/// devices through a shared cursor, so one slow device delays only its
+/// own worker; results are applied by index, keeping the device order
+/// of the sequential pass.
+fn probe_strings_all<C: rusb::UsbContext>(
+    probes: &[(rusb::Device<C>, rusb::DeviceDescriptor)],
+    devices: &mut [UsbDeviceInfo],
+    options: &EnumerationOptions,
+) {
+    run_indexed_parallel(
+        probes,
+        options.parallelism,
+        |(device, descriptor)| probe_strings_one(device, descriptor, options),
+        |at, probe| probe.apply(&mut devices[at]),
+    );
+}
+
+/**
+ * Run `work` over `items` across at most `workers` scoped threads.
+ * Workers claim items through a shared cursor, so one slow item delays
+ * only its own worker; results reach `apply` in index order regardless
+ * of completion order. A panicking worker loses only the items it
+ * claimed - everything else is still applied.
+ */
+fn run_indexed_parallel<T: Sync, R: Send>(
+    items: &[T],
+    workers: usize,
+    work: impl Fn(&T) -> R + Sync,
+    mut apply: impl FnMut(usize, R),
+) {
+    let workers = workers.clamp(1, items.len().max(1));
+    if workers <= 1 {
+        for (at, item) in items.iter().enumerate() {
+            apply(at, work(item));
+        }
+        return;
+    }
+
+    let cursor = std::sync::atomic::AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut out = Vec::new();
+                    loop {
+                        let at = cursor.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        let Some(item) = items.get(at) else {
+                            break;
+                        };
+                        out.push((at, work(item)));
+                    }
+                    out
+                })
+            })
+            .collect();
+        let mut results: Vec<(usize, R)> = Vec::with_capacity(items.len());
+        for handle in handles {
+            results.extend(handle.join().unwrap_or_default());
+        }
+        results.sort_by_key(|(at, _)| *at);
+        for (at, result) in results {
+            apply(at, result);
+        }
+    });
+}
+
+/// Best-effort string descriptor read under the repair policy in
+/// `strings` and the configured size limits; records whether a repair
+/// was needed or a limit was hit.
+fn read_string<T: UsbTransport>(
+    transport: &mut T,
+    index: Option<u8>,
+    language: u16,
+    options: &EnumerationOptions,
+    malformed: &mut bool,
+    oversized: &mut bool,
+) -> Option<String> {
+    match get_string_descriptor_limited(
+        transport,
+        index?,
+        language,
+        options.string_timeout,
+        options.limits.max_string_len,
+    ) {
+        Ok(decoded) => {
+            *malformed |= decoded.malformed;
+            decoded.text
+        }
+        Err(e) if DescriptorLimits::is_limit_error(&e) => {
+            *oversized = true;
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Map libusb's reported link speed onto the crate enum; unknown (and
+/// any future variants) collapse to None.
+fn link_speed(speed: rusb::Speed) -> Option<Speed> {
+    match speed {
+        rusb::Speed::Low => Some(Speed::Low),
+        rusb::Speed::Full => Some(Speed::Full),
+        rusb::Speed::High => Some(Speed::High),
+        rusb::Speed::Super => Some(Speed::Super),
+        rusb::Speed::SuperPlus => Some(Speed::SuperPlus),
+        _ => None,
+    }
+}
+
+/// bMaxPower is in 2 mA units up to high speed and 8 mA units on
+/// SuperSpeed links (USB 3.2 section 9.6.3); an unknown speed gets the
+/// conservative 2 mA reading.
+fn max_power_ma(b_max_power: u8, speed: Option<Speed>) -> u16 {
+    let unit = match speed {
+        Some(Speed::Super) | Some(Speed::SuperPlus) => 8,
+        _ => 2,
+    };
+    u16::from(b_max_power) * unit
+}
+
+/// Capture the active configuration descriptor, alternate settings and
+/// endpoints included. All data comes from the cached descriptor; no
+/// transfer is issued.
+fn config_info(config: rusb::ConfigDescriptor, speed: Option<Speed>) -> ConfigInfo {
+    ConfigInfo {
+        configuration_value: config.number(),
+        // rusb pre-scales bMaxPower by the USB 2.0 unit; recover the raw
+        // byte so SuperSpeed devices get the 8 mA unit.
+        max_power_ma: max_power_ma((config.max_power() / 2) as u8, speed),
+        self_powered: config.self_powered(),
+        remote_wakeup: config.remote_wakeup(),
+        // String descriptors need an open handle; the probe phase fills
+        // them in when read_interface_strings is enabled.
+        description: None,
+        interfaces: config
+            .interfaces()
+            .flat_map(|interface| interface.descriptors())
+            .map(|alt| InterfaceInfo {
+                number: alt.interface_number(),
+                alternate_setting: alt.setting_number(),
+                class: alt.class_code(),
+                subclass: alt.sub_class_code(),
+                protocol: alt.protocol_code(),
+                description: None,
+                endpoints: alt
+                    .endpoint_descriptors()
+                    .map(|ep| EndpointInfo {
+                        address: ep.address(),
+                        kind: endpoint_kind(ep.transfer_type()),
+                        max_packet_size: ep.max_packet_size(),
+                        interval: ep.interval(),
+                        // Companions are not exposed through the cached
+                        // config descriptor; see topology for BOS-based
+                        // SuperSpeed parsing.
+                        ss_bytes_per_interval: None,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+fn endpoint_kind(transfer_type: rusb::TransferType) -> EndpointKind {
+    match transfer_type {
+        rusb::TransferType::Control => EndpointKind::Control,
+        rusb::TransferType::Isochronous => EndpointKind::Isochronous,
+        rusb::TransferType::Bulk => EndpointKind::Bulk,
+        rusb::TransferType::Interrupt => EndpointKind::Interrupt,
+    }
+}
+
+/// Sysfs-style port chain for a libusb device: "{bus}-{p1}.{p2}...".
+pub(crate) fn port_path<C: rusb::UsbContext>(device: &rusb::Device<C>) -> Option<String> {
+    let ports = device.port_numbers().ok()?;
+    if ports.is_empty() {
+        return None;
+    }
+    let chain: Vec<String> = ports.iter().map(|p| p.to_string()).collect();
+    Some(format!("{}-{}", device.bus_number(), chain.join(".")))
+}
+
+/**
+ * Sysfs-based enumerator used when libusb is unavailable.
+ *
+ * Reads /sys/bus/usb/devices on Linux. The root is injectable so tests
+ * can point it at a fixture tree.
+ */
+pub struct FallbackEnumerator {
+    sysfs_root: PathBuf,
+}
+
+impl Default for FallbackEnumerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FallbackEnumerator {
+    pub fn new() -> Self {
+        FallbackEnumerator {
+            sysfs_root: PathBuf::from("/sys/bus/usb/devices"),
+        }
+    }
+
+    /// Use an alternate sysfs root (fixture trees in tests).
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        FallbackEnumerator {
+            sysfs_root: root.into(),
+        }
+    }
+
+    /// Enumerate and keep only records matching `filter`; sysfs exposes
+    /// descriptors without opening devices, so there is no pre-open
+    /// stage here.
+    pub fn enumerate_where(&self, filter: &DeviceFilter) -> Result<Vec<UsbDeviceRecord>, UsbError> {
+        Ok(self
+            .enumerate()?
+            .into_iter()
+            .filter(|r| filter.matches_record(r))
+            .collect())
+    }
+
+    /**
+     * `enumerate_where` with the descriptor-decidable part of the
+     * filter applied first: a directory failing on VID/PID/class costs
+     * three attribute reads, not a full record parse.
+     */
+    pub fn find_devices(&self, filter: &DeviceFilter) -> Result<Vec<UsbDeviceRecord>, UsbError> {
+        if !self.sysfs_root.exists() {
+            return Err(UsbError::Unsupported(format!(
+                "sysfs root not present: {}",
+                self.sysfs_root.display()
+            )));
+        }
+
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.sysfs_root)? {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.join("idVendor").exists() {
+                continue;
+            }
+            let cheap = (|| -> Result<bool, UsbError> {
+                Ok(filter.matches_descriptor(
+                    read_hex_u16(&path, "idVendor")?,
+                    read_hex_u16(&path, "idProduct")?,
+                    read_hex_u8(&path, "bDeviceClass")?,
+                ))
+            })();
+            // An unreadable attribute falls through to the full parse,
+            // which logs the reason.
+            if !cheap.unwrap_or(true) {
+                continue;
+            }
+            match self.read_device(&path) {
+                Ok(record) if filter.matches_record(&record) => records.push(record),
+                Ok(_) => {}
+                Err(e) => log::debug!("skipping {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(records)
+    }
+
+    pub fn enumerate(&self) -> Result<Vec<UsbDeviceRecord>, UsbError> {
+        if !self.sysfs_root.exists() {
+            return Err(UsbError::Unsupported(format!(
+                "sysfs root not present: {}",
+                self.sysfs_root.display()
+            )));
+        }
+
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.sysfs_root)? {
+            let entry = entry?;
+            let path = entry.path();
+            // Device directories carry idVendor; interface directories do not.
+            if !path.join("idVendor").exists() {
+                continue;
+            }
+            match self.read_device(&path) {
+                Ok(record) => records.push(record),
+                Err(e) => log::debug!("skipping {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// The sysfs counterpart of `probe_libusb`: present-or-not from the
+    /// idVendor/idProduct attributes alone, without parsing the rest of
+    /// the device directory.
+    pub fn probe(&self, vendor_id: u16, product_id: u16) -> Result<bool, UsbError> {
+        if !self.sysfs_root.exists() {
+            return Err(UsbError::Unsupported(format!(
+                "sysfs root not present: {}",
+                self.sysfs_root.display()
+            )));
+        }
+        for entry in fs::read_dir(&self.sysfs_root)? {
+            let path = entry?.path();
+            if read_hex_u16(&path, "idVendor").ok() == Some(vendor_id)
+                && read_hex_u16(&path, "idProduct").ok() == Some(product_id)
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The first record matching VID/PID, and `serial` when given.
+    pub fn get_device(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+        serial: Option<&str>,
+    ) -> Result<Option<UsbDeviceRecord>, UsbError> {
+        let mut filter = DeviceFilter::any()
+            .with_vendor_id(vendor_id)
+            .with_product_id(product_id);
+        if let Some(serial) = serial {
+            filter = filter.with_serial_number(serial);
+        }
+        Ok(self.enumerate_where(&filter)?.into_iter().next())
+    }
+
+    /**
+     * Every record matching VID/PID, in sysfs order. The multi-device
+     * counterpart of `get_device`: a fleet of identical units all
+     * share one VID/PID, so "first match" is the wrong primitive there
+     * - take the list and pick by serial or port.
+     */
+    pub fn get_devices(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+    ) -> Result<Vec<UsbDeviceRecord>, UsbError> {
+        self.enumerate_where(
+            &DeviceFilter::any()
+                .with_vendor_id(vendor_id)
+                .with_product_id(product_id),
+        )
+    }
+
+    /**
+     * The record whose serial number matches exactly. An empty serial
+     * never matches: serial-less devices report no serial at all, and
+     * treating "" as a wildcard is how the wrong unit of sixteen gets
+     * picked.
+     */
+    pub fn get_device_by_serial(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+        serial: &str,
+    ) -> Result<Option<UsbDeviceRecord>, UsbError> {
+        if serial.is_empty() {
+            return Ok(None);
+        }
+        self.get_device(vendor_id, product_id, Some(serial))
+    }
+
+    /**
+     * The record at a sysfs port chain ("1-4.2"), regardless of
+     * VID/PID. Port position is the one stable identity left when
+     * identical units ship without serial numbers.
+     */
+    pub fn get_device_at_port(&self, port_path: &str) -> Result<Option<UsbDeviceRecord>, UsbError> {
+        Ok(self.enumerate()?.into_iter().find(|record| {
+            Path::new(&record.sysfs_path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                == Some(port_path)
+        }))
+    }
+
+    /**
+     * Presence check, optionally narrowed to one unit by serial.
+     * Without a serial this is `probe` - descriptors alone, no string
+     * reads; with one it costs a filtered enumeration pass.
+     */
+    pub fn is_connected(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+        serial: Option<&str>,
+    ) -> Result<bool, UsbError> {
+        match serial {
+            None => self.probe(vendor_id, product_id),
+            Some(serial) => Ok(self
+                .get_device_by_serial(vendor_id, product_id, serial)?
+                .is_some()),
+        }
+    }
+
+    fn read_device(&self, path: &Path) -> Result<UsbDeviceRecord, UsbError> {
+        let usb_version: BcdVersion = read_attr(path, "version")?
+            .parse()
+            .map_err(UsbError::Parse)?;
+
+        let descriptor = UsbDescriptorSummary {
+            usb_version,
+            device_version: BcdVersion(read_hex_u16(path, "bcdDevice").unwrap_or(0)),
+            device_class: read_hex_u8(path, "bDeviceClass")?,
+            device_subclass: read_hex_u8(path, "bDeviceSubClass")?,
+            device_protocol: read_hex_u8(path, "bDeviceProtocol")?,
+            max_packet_size_0: read_dec_u8(path, "bMaxPacketSize0").unwrap_or(0),
+            num_configurations: read_dec_u8(path, "bNumConfigurations").unwrap_or(0),
+        };
+
+        Ok(UsbDeviceRecord {
+            bus_number: read_dec_u8(path, "busnum")?,
+            device_number: read_dec_u8(path, "devnum")?,
+            vendor_id: read_hex_u16(path, "idVendor")?,
+            product_id: read_hex_u16(path, "idProduct")?,
+            descriptor,
+            manufacturer: read_attr(path, "manufacturer").ok(),
+            product: read_attr(path, "product").ok(),
+            serial_number: read_attr(path, "serial").ok(),
+            sysfs_path: path.display().to_string(),
+            speed: read_attr(path, "speed").ok().and_then(|v| Speed::from_sysfs(&v)),
+            interfaces: read_interfaces(path),
+        })
+    }
+}
+
+/// Interface directories the kernel nests under the device ("1-4/1-4:1.0").
+/// Unreadable interfaces are skipped; a device that refuses the read just
+/// gets an empty list, never an enumeration failure.
+fn read_interfaces(path: &Path) -> Vec<InterfaceSummary> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}:", name);
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(&prefix))
+                && p.join("bInterfaceClass").exists()
+        })
+        .collect();
+    dirs.sort();
+
+    dirs.iter()
+        .filter_map(|dir| {
+            let parsed = (|| -> Result<InterfaceSummary, UsbError> {
+                Ok(InterfaceSummary {
+                    class: read_hex_u8(dir, "bInterfaceClass")?,
+                    subclass: read_hex_u8(dir, "bInterfaceSubClass")?,
+                    protocol: read_hex_u8(dir, "bInterfaceProtocol")?,
+                    description: read_attr(dir, "interface").ok(),
+                })
+            })();
+            match parsed {
+                Ok(interface) => Some(interface),
+                Err(e) => {
+                    log::debug!("skipping interface {}: {}", dir.display(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn read_attr(path: &Path, name: &str) -> Result<String, UsbError> {
+    Ok(fs::read_to_string(path.join(name))?.trim().to_string())
+}
+
+fn read_hex_u16(path: &Path, name: &str) -> Result<u16, UsbError> {
+    let raw = read_attr(path, name)?;
+    u16::from_str_radix(&raw, 16)
+        .map_err(|_| UsbError::Parse(format!("bad hex attribute {}: {:?}", name, raw)))
+}
+
+fn read_hex_u8(path: &Path, name: &str) -> Result<u8, UsbError> {
+    let raw = read_attr(path, name)?;
+    u8::from_str_radix(&raw, 16)
+        .map_err(|_| UsbError::Parse(format!("bad hex attribute {}: {:?}", name, raw)))
+}
+
+fn read_dec_u8(path: &Path, name: &str) -> Result<u8, UsbError> {
+    let raw = read_attr(path, name)?;
+    raw.parse()
+        .map_err(|_| UsbError::Parse(format!("bad attribute {}: {:?}", name, raw)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_fixture_device(root: &Path, name: &str, attrs: &[(&str, &str)]) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (attr, value) in attrs {
+            fs::write(dir.join(attr), format!("{}\n", value)).unwrap();
+        }
+    }
+
+    fn fixture_root(test: &str) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join(test)
+            .join(format!("{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_fallback_parses_usb21_device() {
+        let root = fixture_root("fallback_usb21");
+        write_fixture_device(
+            &root,
+            "1-4",
+            &[
+                ("idVendor", "18d1"),
+                ("idProduct", "4ee7"),
+                ("busnum", "1"),
+                ("devnum", "9"),
+                // sysfs renders bcdUSB 0x0210 as " 2.10"
+                ("version", " 2.10"),
+                ("bcdDevice", "0440"),
+                ("bDeviceClass", "00"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "00"),
+                ("bMaxPacketSize0", "64"),
+                ("bNumConfigurations", "1"),
+                ("manufacturer", "Google Inc."),
+                ("product", "Pixel 7"),
+                ("speed", "480"),
+            ],
+        );
+
+        let records = FallbackEnumerator::with_root(&root).enumerate().unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.vendor_id, 0x18d1);
+        assert_eq!(record.descriptor.usb_version, BcdVersion(0x0210));
+        // The old formatter dropped the sub-minor digit and printed "2.1"
+        assert_eq!(record.descriptor.usb_version_string(), "2.10");
+        assert_eq!(record.manufacturer.as_deref(), Some("Google Inc."));
+        assert_eq!(record.serial_number, None);
+        assert_eq!(record.speed, Some(Speed::High));
+    }
+
+    #[test]
+    fn test_fallback_probe_and_get_device() {
+        let root = fixture_root("fallback_probe");
+        for (name, serial) in [("1-4", "29061FDH300EXZ"), ("1-5", "1A2B3C4D")] {
+            write_fixture_device(
+                &root,
+                name,
+                &[
+                    ("idVendor", "18d1"),
+                    ("idProduct", "4ee7"),
+                    ("busnum", "1"),
+                    ("devnum", "9"),
+                    ("version", " 2.10"),
+                    ("bDeviceClass", "00"),
+                    ("bDeviceSubClass", "00"),
+                    ("bDeviceProtocol", "00"),
+                    ("serial", serial),
+                ],
+            );
+        }
+
+        let enumerator = FallbackEnumerator::with_root(&root);
+        assert!(enumerator.probe(0x18d1, 0x4ee7).unwrap());
+        assert!(!enumerator.probe(0x18d1, 0x4ee6).unwrap());
+
+        // Without a serial either device is acceptable; with one, only
+        // the matching device is.
+        assert!(enumerator.get_device(0x18d1, 0x4ee7, None).unwrap().is_some());
+        let picked = enumerator
+            .get_device(0x18d1, 0x4ee7, Some("1A2B3C4D"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(picked.serial_number.as_deref(), Some("1A2B3C4D"));
+        assert_eq!(
+            enumerator.get_device(0x18d1, 0x4ee7, Some("nope")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fallback_fleet_of_identical_devices() {
+        // Three identical units on one hub; one has no serial burned in.
+        let root = fixture_root("fallback_fleet");
+        for (name, serial) in [
+            ("1-4.1", Some("FLEET-A")),
+            ("1-4.2", Some("FLEET-B")),
+            ("1-4.3", None),
+        ] {
+            let mut attrs = vec![
+                ("idVendor", "18d1"),
+                ("idProduct", "4ee7"),
+                ("busnum", "1"),
+                ("devnum", "9"),
+                ("version", " 2.10"),
+                ("bDeviceClass", "00"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "00"),
+            ];
+            if let Some(serial) = serial {
+                attrs.push(("serial", serial));
+            }
+            write_fixture_device(&root, name, &attrs);
+        }
+        let enumerator = FallbackEnumerator::with_root(&root);
+
+        assert_eq!(enumerator.get_devices(0x18d1, 0x4ee7).unwrap().len(), 3);
+        assert_eq!(enumerator.get_devices(0x18d1, 0x4ee6).unwrap().len(), 0);
+
+        let picked = enumerator
+            .get_device_by_serial(0x18d1, 0x4ee7, "FLEET-B")
+            .unwrap()
+            .unwrap();
+        assert_eq!(picked.serial_number.as_deref(), Some("FLEET-B"));
+
+        // An empty serial is not a wildcard for the serial-less unit.
+        assert_eq!(
+            enumerator.get_device_by_serial(0x18d1, 0x4ee7, "").unwrap(),
+            None
+        );
+
+        let at_port = enumerator.get_device_at_port("1-4.3").unwrap().unwrap();
+        assert_eq!(at_port.serial_number, None);
+        assert!(at_port.sysfs_path.ends_with("1-4.3"));
+        assert_eq!(enumerator.get_device_at_port("1-9").unwrap(), None);
+
+        assert!(enumerator.is_connected(0x18d1, 0x4ee7, None).unwrap());
+        assert!(enumerator
+            .is_connected(0x18d1, 0x4ee7, Some("FLEET-A"))
+            .unwrap());
+        assert!(!enumerator
+            .is_connected(0x18d1, 0x4ee7, Some("FLEET-Z"))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_probe_is_cheaper_than_enumerate() {
+        // Needs real devices; on hosts without a usable libusb backend
+        // (or with an empty bus) there is nothing to compare.
+        let Ok(devices) = enumerate_libusb() else {
+            return;
+        };
+        let Some(first) = devices.first() else {
+            return;
+        };
+
+        let start = std::time::Instant::now();
+        let _ = probe_libusb(first.vendor_id, first.product_id);
+        let probe_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let _ = enumerate_libusb();
+        let enumerate_time = start.elapsed();
+
+        assert!(
+            probe_time <= enumerate_time,
+            "probe ({:?}) should not cost more than a full pass ({:?})",
+            probe_time,
+            enumerate_time
+        );
+    }
+
+    #[test]
+    fn test_fallback_reads_nested_interfaces() {
+        let root = fixture_root("fallback_interfaces");
+        write_fixture_device(
+            &root,
+            "1-4",
+            &[
+                ("idVendor", "18d1"),
+                ("idProduct", "4ee7"),
+                ("busnum", "1"),
+                ("devnum", "9"),
+                ("version", " 2.10"),
+                ("bcdDevice", "0440"),
+                ("bDeviceClass", "00"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "00"),
+                ("bMaxPacketSize0", "64"),
+                ("bNumConfigurations", "1"),
+            ],
+        );
+        // Interface directories nest under the device, ":{config}.{n}".
+        write_fixture_device(
+            &root,
+            "1-4/1-4:1.0",
+            &[
+                ("bInterfaceClass", "ff"),
+                ("bInterfaceSubClass", "42"),
+                ("bInterfaceProtocol", "01"),
+                ("interface", "ADB Interface"),
+            ],
+        );
+        write_fixture_device(
+            &root,
+            "1-4/1-4:1.1",
+            &[
+                ("bInterfaceClass", "06"),
+                ("bInterfaceSubClass", "01"),
+                ("bInterfaceProtocol", "01"),
+            ],
+        );
+        // Malformed interface: skipped, not fatal.
+        write_fixture_device(&root, "1-4/1-4:1.2", &[("bInterfaceClass", "zz")]);
+
+        let records = FallbackEnumerator::with_root(&root).enumerate().unwrap();
+        assert_eq!(records.len(), 1);
+        let interfaces = &records[0].interfaces;
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(
+            (interfaces[0].class, interfaces[0].subclass, interfaces[0].protocol),
+            (0xff, 0x42, 0x01)
+        );
+        assert_eq!(interfaces[0].description.as_deref(), Some("ADB Interface"));
+        assert_eq!(interfaces[1].class, 0x06);
+        assert_eq!(interfaces[1].description, None);
+    }
+
+    #[test]
+    fn test_fallback_skips_interface_dirs() {
+        let root = fixture_root("fallback_skips_ifaces");
+        write_fixture_device(&root, "1-4:1.0", &[("bInterfaceClass", "ff")]);
+        let records = FallbackEnumerator::with_root(&root).enumerate().unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn test_fallback_survives_malformed_attributes() {
+        let root = fixture_root("fallback_malformed");
+        // Bad hex in a required attribute.
+        write_fixture_device(&root, "1-1", &[("idVendor", "zzzz"), ("idProduct", "0001")]);
+        // Required attributes missing entirely.
+        write_fixture_device(&root, "1-2", &[("idVendor", "0781")]);
+        // Attribute file holding invalid UTF-8.
+        write_fixture_device(
+            &root,
+            "1-3",
+            &[
+                ("idVendor", "0781"),
+                ("idProduct", "5583"),
+                ("busnum", "1"),
+                ("devnum", "3"),
+                ("bcdDevice", "0100"),
+                ("bDeviceClass", "00"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "00"),
+                ("bMaxPacketSize0", "64"),
+                ("bNumConfigurations", "1"),
+            ],
+        );
+        fs::write(root.join("1-3/version"), [0xff, 0xfe, 0x00]).unwrap();
+
+        // Malformed devices are skipped, not panicked over.
+        let records = FallbackEnumerator::with_root(&root).enumerate().unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn test_enumeration_apis_never_unwind() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        static PANICKED: AtomicBool = AtomicBool::new(false);
+
+        // A hook fires even for panics that something downstream catches,
+        // so this fails if our own enumeration code unwinds at all - even
+        // transiently - on malformed input.
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| PANICKED.store(true, Ordering::SeqCst)));
+
+        let fallback_outcome =
+            FallbackEnumerator::with_root(fixture_root("panic_free_empty")).enumerate();
+        let _ = crate::strings::decode_string_descriptor(&[0x09, 0x03, 0xd8]);
+
+        std::panic::set_hook(previous);
+
+        assert!(!PANICKED.load(Ordering::SeqCst), "enumeration panicked");
+        drop(fallback_outcome);
+
+        // The libusb path may legitimately hit rusb's internal panic when
+        // USB access is denied (this is the bug the guard exists for);
+        // the guarantee is that the call returns instead of unwinding.
+        match enumerate_libusb_report() {
+            Ok(_) | Err(_) => {}
+        }
+
+        // And verify the guard's translation itself.
+        let err = guard_panics::<()>("test probe", || panic!("libusb aborted")).unwrap_err();
+        match err {
+            UsbError::Internal(msg) => {
+                assert!(msg.contains("test probe"));
+                assert!(msg.contains("libusb aborted"));
+            }
+            other => panic!("expected Internal, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_enumerate_in_shared_context_surfaces_init_failure() {
+        // A context whose backend never comes up: enumeration reports
+        // the init error instead of unwinding or touching libusb again.
+        let context = SharedContext::with_init(
+            crate::context::ContextOptions::default(),
+            std::sync::Arc::new(|_| Err(rusb::Error::Other)),
+        );
+        for _ in 0..2 {
+            let err = enumerate_libusb_report_in(&context, &EnumerationOptions::default())
+                .unwrap_err();
+            assert!(matches!(err, UsbError::Libusb(rusb::Error::Other)));
+        }
+    }
+
+    #[test]
+    fn test_skip_controls_and_filtered_buckets() {
+        let options = EnumerationOptions::default()
+            .with_skip_hubs(true)
+            .with_skip_class(0x11)
+            .with_skip_vendor(0x1d6b);
+        assert!(options.should_skip(0x09, 0x2109));
+        assert!(options.should_skip(0x11, 0x0000));
+        assert!(options.should_skip(0x00, 0x1d6b));
+        assert!(!options.should_skip(0x00, 0x18d1));
+
+        let mut counts = FilteredCounts::default();
+        options.count(&mut counts, 0x09);
+        options.count(&mut counts, 0x11);
+        options.count(&mut counts, 0x00);
+        assert_eq!((counts.hubs, counts.by_class, counts.by_vendor), (1, 1, 1));
+        assert_eq!(counts.total(), 3);
+    }
+
+    /// A synthetic device for filter-matching tests.
+    fn synthetic_device(
+        vendor_id: u16,
+        product_id: u16,
+        device_class: u8,
+        serial: Option<&str>,
+    ) -> UsbDeviceInfo {
+        let mut info = crate::watch::partial_info(
+            vendor_id,
+            product_id,
+            serial.map(str::to_string),
+            None,
+            "test:synthetic".to_string(),
+        );
+        info.descriptor.device_class = device_class;
+        info
+    }
+
+    #[test]
+    fn test_max_power_unit_depends_on_speed() {
+        // The same raw bMaxPower byte 0xFA (250) reads as 500 mA under
+        // the USB 2.0 2 mA unit and 2000 mA under the SuperSpeed 8 mA
+        // unit.
+        assert_eq!(max_power_ma(0xfa, Some(Speed::High)), 500);
+        assert_eq!(max_power_ma(0xfa, Some(Speed::Full)), 500);
+        assert_eq!(max_power_ma(0xfa, None), 500);
+        assert_eq!(max_power_ma(0xfa, Some(Speed::Super)), 2000);
+        assert_eq!(max_power_ma(0x70, Some(Speed::SuperPlus)), 896);
+        // A self-powered device's zero draw is zero at any speed.
+        assert_eq!(max_power_ma(0x00, Some(Speed::Super)), 0);
+    }
+
+    #[test]
+    fn test_display_includes_speed_when_known() {
+        let mut info = synthetic_device(0x18d1, 0x4ee7, 0x00, Some("A1"));
+        info.bus_number = 3;
+        info.address = 7;
+        info.product = Some("Pixel 7".to_string());
+        assert_eq!(info.to_string(), "Bus 003 Device 007: 18d1:4ee7 Pixel 7");
+
+        info.speed = Some(Speed::High);
+        assert_eq!(
+            info.to_string(),
+            "Bus 003 Device 007: 18d1:4ee7 Pixel 7 (480 Mbps High Speed)"
+        );
+
+        // Partial records have neither product nor speed.
+        info.product = None;
+        info.speed = None;
+        assert_eq!(info.to_string(), "Bus 003 Device 007: 18d1:4ee7");
+    }
+
+    #[test]
+    fn test_filter_matches_multiple_vendors_and_pid_ranges() {
+        let fleet = [
+            synthetic_device(0x18d1, 0x4ee7, 0x00, Some("A1")),
+            synthetic_device(0x05c6, 0x9008, 0xff, None),
+            synthetic_device(0x2109, 0x0817, 0x09, None),
+            synthetic_device(0x18d1, 0xd00d, 0x00, Some("B2")),
+        ];
+
+        // Two accepted vendors; everything else is out.
+        let vendors = DeviceFilter::any()
+            .with_vendor_id(0x18d1)
+            .with_vendor_id(0x05c6);
+        let matched: Vec<_> = fleet.iter().filter(|d| vendors.matches(d)).collect();
+        assert_eq!(matched.len(), 3);
+        assert!(matched.iter().all(|d| d.vendor_id != 0x2109));
+
+        // A product range alongside an exact id.
+        let pids = DeviceFilter::any()
+            .with_product_id(0x9008)
+            .with_product_id_range(0x4ee0, 0x4eef);
+        assert!(pids.matches(&fleet[0]));
+        assert!(pids.matches(&fleet[1]));
+        assert!(!pids.matches(&fleet[3]));
+        // Reversed bounds normalize.
+        let reversed = DeviceFilter::any().with_product_id_range(0x4eef, 0x4ee0);
+        assert!(reversed.matches(&fleet[0]));
+    }
+
+    #[test]
+    fn test_filter_matches_classes_and_serial_presence() {
+        let with_serial = synthetic_device(0x18d1, 0x4ee7, 0xff, Some("A1"));
+        let without = synthetic_device(0x18d1, 0x4ee8, 0xff, None);
+        let hub = synthetic_device(0x2109, 0x0817, 0x09, None);
+
+        let classes = DeviceFilter::any().with_class(0xff).with_class(0x00);
+        assert!(classes.matches(&with_serial));
+        assert!(!classes.matches(&hub));
+
+        let need_serial = DeviceFilter::any().with_has_serial(true);
+        assert!(need_serial.matches(&with_serial));
+        assert!(!need_serial.matches(&without));
+        assert!(DeviceFilter::any().with_has_serial(false).matches(&without));
+
+        // Combined builder chain, and the descriptor-only subset used
+        // before devices are opened: serial conditions do not apply yet.
+        let combined = DeviceFilter::any()
+            .with_vendor_id(0x18d1)
+            .with_class(0xff)
+            .with_has_serial(true);
+        assert!(combined.matches(&with_serial));
+        assert!(!combined.matches(&without));
+        assert!(combined.matches_descriptor(0x18d1, 0x4ee8, 0xff));
+        assert!(!combined.matches_descriptor(0x2109, 0x0817, 0x09));
+    }
+
+    #[test]
+    fn test_fallback_enumerate_where_filters_records() {
+        let root = fixture_root("filter_where");
+        write_fixture_device(
+            &root,
+            "1-1",
+            &[
+                ("busnum", "1"),
+                ("devnum", "4"),
+                ("idVendor", "18d1"),
+                ("idProduct", "4ee7"),
+                ("version", " 2.10"),
+                ("bcdDevice", "0100"),
+                ("bDeviceClass", "00"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "00"),
+                ("bMaxPacketSize0", "64"),
+                ("bNumConfigurations", "1"),
+                ("serial", "FLEET1"),
+            ],
+        );
+        write_fixture_device(
+            &root,
+            "1-2",
+            &[
+                ("busnum", "1"),
+                ("devnum", "5"),
+                ("idVendor", "2109"),
+                ("idProduct", "0817"),
+                ("version", " 2.10"),
+                ("bcdDevice", "0100"),
+                ("bDeviceClass", "09"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "01"),
+                ("bMaxPacketSize0", "64"),
+                ("bNumConfigurations", "1"),
+            ],
+        );
+
+        let enumerator = FallbackEnumerator::with_root(&root);
+        let all = enumerator.enumerate().unwrap();
+        assert_eq!(all.len(), 2);
+
+        let google = enumerator
+            .enumerate_where(&DeviceFilter::any().with_vendor_id(0x18d1))
+            .unwrap();
+        assert_eq!(google.len(), 1);
+        assert_eq!(google[0].serial_number.as_deref(), Some("FLEET1"));
+
+        let no_hubs = enumerator
+            .enumerate_where(&DeviceFilter::any().with_class(0x00).with_class(0xff))
+            .unwrap();
+        assert_eq!(no_hubs.len(), 1);
+        assert_eq!(no_hubs[0].vendor_id, 0x18d1);
+    }
+
+    #[test]
+    fn test_filter_interface_class_and_serial_substring() {
+        let mut webcam = synthetic_device(0x046d, 0x085e, 0xef, Some("FLEET-CAM-07"));
+        webcam.active_config = Some(ConfigInfo {
+            configuration_value: 1,
+            max_power_ma: 500,
+            self_powered: false,
+            remote_wakeup: false,
+            description: None,
+            interfaces: vec![InterfaceInfo {
+                number: 0,
+                alternate_setting: 0,
+                class: 0x0e,
+                subclass: 0x01,
+                protocol: 0x00,
+                description: None,
+                endpoints: Vec::new(),
+            }],
+        });
+        let unconfigured = synthetic_device(0x046d, 0x085e, 0xef, Some("FLEET-CAM-08"));
+
+        let video = DeviceFilter::any().with_interface_class(0x0e);
+        assert!(video.matches(&webcam));
+        // Unknown interfaces fail a non-empty interface filter rather
+        // than passing unvetted.
+        assert!(!video.matches(&unconfigured));
+        assert!(!DeviceFilter::any().with_interface_class(0x08).matches(&webcam));
+
+        let fleet = DeviceFilter::any().with_serial_substring("FLEET-CAM");
+        assert!(fleet.matches(&webcam));
+        assert!(!fleet.matches(&synthetic_device(0x046d, 0x085e, 0xef, Some("LAB-CAM-01"))));
+        assert!(!fleet.matches(&synthetic_device(0x046d, 0x085e, 0xef, None)));
+
+        // Record-side counterparts, with interfaces on the record itself.
+        let storage = UsbDeviceRecord::builder(0x0781, 0x5583)
+            .serial("FLEET-KEY-22")
+            .interface(0x08, 0x06, 0x50)
+            .build();
+        assert!(DeviceFilter::any()
+            .with_interface_class(0x08)
+            .matches_record(&storage));
+        assert!(!DeviceFilter::any()
+            .with_interface_class(0x0e)
+            .matches_record(&storage));
+        assert!(DeviceFilter::any()
+            .with_serial_substring("FLEET-")
+            .matches_record(&storage));
+        assert!(!DeviceFilter::any()
+            .with_serial_substring("LAB-")
+            .matches_record(&storage));
+        // Records carry no tags, so a tag filter fails closed on them.
+        assert!(!DeviceFilter::any()
+            .with_tag("health:reset-loop")
+            .matches_record(&storage));
+    }
+
+    #[test]
+    fn test_fallback_find_devices_prefilters_on_descriptor() {
+        let root = fixture_root("find_devices");
+        write_fixture_device(
+            &root,
+            "1-1",
+            &[
+                ("busnum", "1"),
+                ("devnum", "4"),
+                ("idVendor", "18d1"),
+                ("idProduct", "4ee7"),
+                ("version", " 2.10"),
+                ("bcdDevice", "0100"),
+                ("bDeviceClass", "00"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "00"),
+                ("bMaxPacketSize0", "64"),
+                ("bNumConfigurations", "1"),
+                ("serial", "FLEET1"),
+            ],
+        );
+        write_fixture_device(
+            &root,
+            "1-2",
+            &[
+                ("busnum", "1"),
+                ("devnum", "5"),
+                ("idVendor", "2109"),
+                ("idProduct", "0817"),
+                ("version", " 2.10"),
+                ("bcdDevice", "0100"),
+                ("bDeviceClass", "09"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "01"),
+                ("bMaxPacketSize0", "64"),
+                ("bNumConfigurations", "1"),
+            ],
+        );
+        // A device directory whose record would fail to parse; the
+        // descriptor pre-filter drops it before read_device runs, so it
+        // must not surface as an error.
+        write_fixture_device(
+            &root,
+            "1-3",
+            &[("idVendor", "05ac"), ("idProduct", "12a8"), ("bDeviceClass", "00")],
+        );
+
+        let enumerator = FallbackEnumerator::with_root(&root);
+        let google = enumerator
+            .find_devices(&DeviceFilter::any().with_vendor_id(0x18d1))
+            .unwrap();
+        assert_eq!(google.len(), 1);
+        assert_eq!(google[0].serial_number.as_deref(), Some("FLEET1"));
+
+        // Serial conditions only apply after the full record parse.
+        let fleet = enumerator
+            .find_devices(&DeviceFilter::any().with_serial_substring("FLEET"))
+            .unwrap();
+        assert_eq!(fleet.len(), 1);
+        assert_eq!(fleet[0].vendor_id, 0x18d1);
+
+        let none = enumerator
+            .find_devices(&DeviceFilter::any().with_vendor_id(0x1234))
+            .unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_options_allowlist() {
+        // Empty allowlist: no restriction.
+        let open = EnumerationOptions::default();
+        assert!(open.allows(0x18d1, 0x4ee7));
+
+        let options = EnumerationOptions::default()
+            .with_allowed_device(0x18d1, None)
+            .with_allowed_device(0x05ac, Some(0x12a8));
+        // Vendor-wide entry admits any of its products.
+        assert!(options.allows(0x18d1, 0x4ee7));
+        assert!(options.allows(0x18d1, 0x0000));
+        // Product-specific entry admits only that product.
+        assert!(options.allows(0x05ac, 0x12a8));
+        assert!(!options.allows(0x05ac, 0x12ab));
+        assert!(!options.allows(0x1d6b, 0x0003));
+
+        // Options serialized before the allowlist existed.
+        let old: EnumerationOptions =
+            serde_json::from_str(r#"{"skip_hubs":false,"skip_classes":[],"skip_vendors":[]}"#)
+                .unwrap();
+        assert!(old.allow_devices.is_empty());
+    }
+
+    #[test]
+    fn test_report_without_filtered_field_deserializes() {
+        // Reports serialized before the skip controls existed.
+        let report: EnumerationReport =
+            serde_json::from_str(r#"{"devices":[],"skipped":[]}"#).unwrap();
+        assert_eq!(report.filtered.total(), 0);
+    }
+
+    #[test]
+    fn test_options_probe_defaults_and_builders() {
+        let options = EnumerationOptions::default();
+        assert_eq!(options.string_timeout, Duration::from_millis(100));
+        assert_eq!(options.parallelism, 4);
+        assert!(options.read_strings);
+
+        let options = options
+            .with_string_timeout(Duration::from_millis(250))
+            .with_parallelism(0)
+            .with_read_strings(false);
+        assert_eq!(options.string_timeout, Duration::from_millis(250));
+        // Zero workers would stall the probe phase; clamped to 1.
+        assert_eq!(options.parallelism, 1);
+        assert!(!options.read_strings);
+
+        // Options serialized before the probe controls existed.
+        let old: EnumerationOptions =
+            serde_json::from_str(r#"{"skip_hubs":true,"skip_classes":[],"skip_vendors":[]}"#)
+                .unwrap();
+        assert_eq!(old.string_timeout, Duration::from_millis(100));
+        assert_eq!(old.parallelism, 4);
+        assert!(old.read_strings);
+    }
+
+    #[test]
+    fn test_run_indexed_parallel_overlaps_synthetic_delays() {
+        // Eight 60ms jobs over four workers: two rounds, not eight.
+        let items: Vec<usize> = (0..8).collect();
+        let started = std::time::Instant::now();
+        let mut applied = Vec::new();
+        run_indexed_parallel(
+            &items,
+            4,
+            |item| {
+                std::thread::sleep(Duration::from_millis(60));
+                item * 2
+            },
+            |at, result| applied.push((at, result)),
+        );
+        let elapsed = started.elapsed();
+
+        // Results arrive in index order regardless of completion order.
+        let expected: Vec<(usize, usize)> = (0..8).map(|i| (i, i * 2)).collect();
+        assert_eq!(applied, expected);
+        // The sequential pass would take 480ms; leave slack for a slow
+        // scheduler but require real overlap.
+        assert!(elapsed < Duration::from_millis(360), "took {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_run_indexed_parallel_panicking_item_loses_only_its_worker() {
+        let items: Vec<usize> = (0..8).collect();
+        let mut applied = Vec::new();
+        run_indexed_parallel(
+            &items,
+            4,
+            |item| {
+                if *item == 5 {
+                    panic!("synthetic probe failure");
+                }
+                *item
+            },
+            |at, result| applied.push((at, result)),
+        );
+
+        // The panicking item never lands; the call itself returns and
+        // whatever the surviving workers claimed is still applied in
+        // index order.
+        assert!(applied.iter().all(|(at, _)| *at != 5));
+        assert!(applied.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn test_device_builders_normalise_input() {
+        let info = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .bus_number(2)
+            .address(7)
+            .class(0xff)
+            .manufacturer("  Google Inc.  ")
+            .product("   ")
+            .serial("29061FDH300EXZ")
+            .tag("protocol:adb")
+            .tag("protocol:adb")
+            .tag("blocked")
+            .build();
+        assert_eq!(info.bus_number, 2);
+        assert_eq!(info.address, 7);
+        assert_eq!(info.descriptor.device_class, 0xff);
+        // Strings are trimmed; blank ones drop to None like unreadable
+        // descriptors do on the enumeration path.
+        assert_eq!(info.manufacturer.as_deref(), Some("Google Inc."));
+        assert_eq!(info.product, None);
+        assert_eq!(info.serial_number.as_deref(), Some("29061FDH300EXZ"));
+        assert_eq!(info.tags, vec!["protocol:adb", "blocked"]);
+
+        let record = UsbDeviceRecord::builder(0x0781, 0x5583)
+            .bus_number(2)
+            .device_number(14)
+            .sysfs_path("/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1")
+            .interface(0x08, 0x06, 0x50)
+            .serial("  4C531001331122111213 ")
+            .build();
+        assert_eq!(record.interfaces.len(), 1);
+        assert_eq!(record.interfaces[0].class, 0x08);
+        assert_eq!(
+            record.serial_number.as_deref(),
+            Some("4C531001331122111213")
+        );
+    }
+
+    #[test]
+    fn test_display_name_fallback_ladder() {
+        // 0xdead is not a registered vendor, so the usb.ids rungs stay
+        // empty unless the test fills them in.
+
+        // Rung 1: the product string descriptor.
+        let named = UsbDeviceInfo::builder(0xdead, 0xbeef)
+            .product("Widget")
+            .build();
+        assert_eq!(named.display_name(), "Widget");
+
+        // Rung 2: the usb.ids names carried from enumeration.
+        let mut from_db = UsbDeviceInfo::builder(0xdead, 0xbeef).build();
+        from_db.usb_ids = Some(UsbIds {
+            vendor_name: Some("Acme".to_string()),
+            product_name: Some("Widget Pro".to_string()),
+            class_name: None,
+        });
+        assert_eq!(from_db.display_name(), "Widget Pro");
+        assert_eq!(from_db.vendor_display_name(), "Acme");
+
+        // Rung 3: the built-in class table.
+        let by_class = UsbDeviceInfo::builder(0xdead, 0xbeef).class(0x08).build();
+        assert_eq!(by_class.display_name(), "Mass Storage device");
+
+        // Rung 4: nothing known beyond the ids themselves.
+        let bare = UsbDeviceInfo::builder(0xdead, 0xbeef).build();
+        assert_eq!(bare.display_name(), "USB device dead:beef");
+        assert_eq!(bare.vendor_display_name(), "USB vendor dead");
+
+        // Display appends the naming rungs, but never the last-resort
+        // form - the line already starts with the VID:PID.
+        assert!(by_class
+            .to_string()
+            .ends_with("dead:beef Mass Storage device"));
+        assert!(bare.to_string().ends_with("dead:beef"));
+    }
+
+    #[test]
+    fn test_record_display_name_ladder() {
+        let named = UsbDeviceRecord::builder(0xdead, 0xbeef)
+            .product("Widget")
+            .build();
+        assert_eq!(named.display_name(), "Widget");
+
+        let by_class = UsbDeviceRecord::builder(0xdead, 0xbeef).class(0x09).build();
+        assert_eq!(by_class.display_name(), "Hub device");
+
+        let bare = UsbDeviceRecord::builder(0xdead, 0xbeef).build();
+        assert_eq!(bare.display_name(), "USB device dead:beef");
+        assert_eq!(bare.vendor_display_name(), "USB vendor dead");
+    }
+
+    #[test]
+    fn test_merge_device_info_precedence() {
+        let libusb = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .bus_number(2)
+            .address(7)
+            .class(0xff)
+            .manufacturer("Google Inc.")
+            .tag("protocol:adb")
+            .build();
+        let platform = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .bus_number(2)
+            .address(9)
+            .manufacturer("Google")
+            .serial("29061FDH300EXZ")
+            .port_path("2-1.4")
+            .tag("blocked")
+            .tag("protocol:adb")
+            .build();
+
+        let merged = merge_device_info(libusb, platform);
+        // Libusb wins bus position and descriptor numerics; the
+        // platform record wins the port path.
+        assert_eq!((merged.bus_number, merged.address), (2, 7));
+        assert_eq!(merged.descriptor.device_class, 0xff);
+        assert_eq!(merged.port_path.as_deref(), Some("2-1.4"));
+        // Conflicting strings keep the libusb side; absent ones fill in.
+        assert_eq!(merged.manufacturer.as_deref(), Some("Google Inc."));
+        assert_eq!(merged.serial_number.as_deref(), Some("29061FDH300EXZ"));
+        // Tags union without duplicates.
+        assert_eq!(merged.tags, vec!["protocol:adb", "blocked"]);
+    }
+
+    #[test]
+    fn test_dedup_matches_position_then_serial() {
+        let scanned = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .bus_number(2)
+            .address(7)
+            .serial("29061FDH300EXZ")
+            .build();
+        // Same position, no serial: the position key collapses it.
+        let rescanned = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .bus_number(2)
+            .address(7)
+            .port_path("2-1.4")
+            .build();
+        // Different address data but the same serial: the identity
+        // fallback collapses it.
+        let platform = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .bus_number(2)
+            .address(9)
+            .serial("29061FDH300EXZ")
+            .manufacturer("Google Inc.")
+            .build();
+        // A genuinely different stick survives.
+        let other = UsbDeviceInfo::builder(0x0781, 0x5583)
+            .bus_number(2)
+            .address(3)
+            .serial("4C531001331122111213")
+            .build();
+
+        let devices = dedup_device_info(vec![scanned, rescanned, platform, other]);
+        assert_eq!(devices.len(), 2);
+        assert_eq!((devices[0].bus_number, devices[0].address), (2, 7));
+        assert_eq!(devices[0].port_path.as_deref(), Some("2-1.4"));
+        assert_eq!(devices[0].manufacturer.as_deref(), Some("Google Inc."));
+        assert_eq!(devices[1].vendor_id, 0x0781);
+    }
+
+    #[test]
+    fn test_dedup_keeps_distinct_partial_records() {
+        // Notification-driven partial records are all at bus 0 addr 0;
+        // without serials they must not merge into one.
+        let a = UsbDeviceInfo::builder(0x18d1, 0x4ee7).build();
+        let b = UsbDeviceInfo::builder(0x0781, 0x5583).build();
+        assert_eq!(dedup_device_info(vec![a, b]).len(), 2);
+    }
+
+    #[test]
+    fn test_descriptor_only_scan_skips_device_opens() {
+        // read_strings: false never opens a device, so the scan cost is
+        // the device list alone. Like the other libusb-path tests this
+        // only asserts the call returns; the sandbox has no USB access.
+        let options = EnumerationOptions::default().with_read_strings(false);
+        if let Ok(report) = enumerate_libusb_report_with(&options) {
+            assert!(report
+                .devices
+                .iter()
+                .all(|d| d.manufacturer.is_none() && d.serial_number.is_none()));
+        }
+    }
+
+    #[test]
+    fn test_old_snapshot_round_trips() {
+        // Snapshot written before BcdVersion carried lossy strings
+        let old = r#"{
+            "usb_version": "2.1",
+            "device_version": "4.40",
+            "device_class": 0,
+            "device_subclass": 0,
+            "device_protocol": 0,
+            "max_packet_size_0": 64,
+            "num_configurations": 1
+        }"#;
+        let summary: UsbDescriptorSummary = serde_json::from_str(old).unwrap();
+        assert_eq!(summary.usb_version, BcdVersion(0x0210));
+        assert_eq!(summary.usb_version_string(), "2.10");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans_nest_per_stage() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        /// Minimal capturing subscriber: records each new span as
+        /// (name, contextual parent name).
+        #[derive(Default)]
+        struct Capture {
+            next_id: AtomicU64,
+            names: Mutex<HashMap<u64, &'static str>>,
+            stack: Mutex<Vec<u64>>,
+            seen: Mutex<Vec<(&'static str, Option<&'static str>)>>,
+        }
+
+        impl tracing::Subscriber for Capture {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, attrs: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+                let name = attrs.metadata().name();
+                let parent = self
+                    .stack
+                    .lock()
+                    .unwrap()
+                    .last()
+                    .and_then(|current| self.names.lock().unwrap().get(current).copied());
+                self.names.lock().unwrap().insert(id, name);
+                self.seen.lock().unwrap().push((name, parent));
+                tracing::span::Id::from_u64(id)
+            }
+
+            fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+            fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+            fn event(&self, _: &tracing::Event<'_>) {}
+
+            fn enter(&self, id: &tracing::span::Id) {
+                self.stack.lock().unwrap().push(id.into_u64());
+            }
+
+            fn exit(&self, _: &tracing::span::Id) {
+                self.stack.lock().unwrap().pop();
+            }
+        }
+
+        let capture = Arc::new(Capture::default());
+        let dispatch = tracing::Dispatch::new(Arc::clone(&capture));
+        tracing::dispatcher::with_default(&dispatch, || {
+            // The result does not matter: even a host with no USB
+            // stack opens the pipeline spans before failing.
+            let _ = enumerate_libusb_report();
+        });
+
+        let seen = capture.seen.lock().unwrap();
+        assert!(
+            seen.contains(&("enumerate", None)),
+            "missing root span in {:?}",
+            *seen
+        );
+        assert!(
+            seen.contains(&("scan_devices", Some("enumerate"))),
+            "scan stage not nested in {:?}",
+            *seen
+        );
+        // The later stages only run when the scan succeeded at all.
+        if seen.iter().any(|(name, _)| *name == "classify") {
+            assert!(seen.contains(&("classify", Some("enumerate"))));
+        }
+    }
+}
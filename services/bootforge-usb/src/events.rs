@@ -0,0 +1,151 @@
+// BootForge USB - Device events and descriptor diffing
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::enumeration::UsbDeviceInfo;
+
+/**
+ * Identity a device keeps across re-enumerations, in the canonical
+ * textual form defined in `canonical` (VID/PID plus serial when
+ * present, then port chain, then the bus/address slot).
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct DeviceIdentity(pub String);
+
+impl DeviceIdentity {
+    pub fn of(info: &UsbDeviceInfo) -> Self {
+        crate::canonical::CanonicalId::of(info).to_identity()
+    }
+}
+
+impl fmt::Display for DeviceIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/**
+ * One field that differs between two observations of a device.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/**
+ * Differences between two descriptor observations of the same identity.
+ * Only identity-relevant fields participate; volatile fields such as
+ * bus number and address are ignored.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct DescriptorDiff {
+    pub changed_fields: Vec<FieldDiff>,
+}
+
+impl DescriptorDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed_fields.is_empty()
+    }
+}
+
+/**
+ * Compare the identity-relevant descriptor fields of two observations.
+ */
+pub fn diff_descriptors(before: &UsbDeviceInfo, after: &UsbDeviceInfo) -> DescriptorDiff {
+    let mut diff = DescriptorDiff::default();
+    let mut push = |field: &str, b: String, a: String| {
+        if b != a {
+            diff.changed_fields.push(FieldDiff {
+                field: field.to_string(),
+                before: b,
+                after: a,
+            });
+        }
+    };
+
+    push(
+        "vendor_id",
+        format!("{:04x}", before.vendor_id),
+        format!("{:04x}", after.vendor_id),
+    );
+    push(
+        "product_id",
+        format!("{:04x}", before.product_id),
+        format!("{:04x}", after.product_id),
+    );
+    push(
+        "usb_version",
+        before.descriptor.usb_version.to_string(),
+        after.descriptor.usb_version.to_string(),
+    );
+    push(
+        "device_version",
+        before.descriptor.device_version.to_string(),
+        after.descriptor.device_version.to_string(),
+    );
+    push(
+        "device_class",
+        before.descriptor.device_class.to_string(),
+        after.descriptor.device_class.to_string(),
+    );
+    push(
+        "device_subclass",
+        before.descriptor.device_subclass.to_string(),
+        after.descriptor.device_subclass.to_string(),
+    );
+    push(
+        "device_protocol",
+        before.descriptor.device_protocol.to_string(),
+        after.descriptor.device_protocol.to_string(),
+    );
+    push(
+        "num_configurations",
+        before.descriptor.num_configurations.to_string(),
+        after.descriptor.num_configurations.to_string(),
+    );
+    push(
+        "manufacturer",
+        format!("{:?}", before.manufacturer),
+        format!("{:?}", after.manufacturer),
+    );
+    push(
+        "product",
+        format!("{:?}", before.product),
+        format!("{:?}", after.product),
+    );
+    push(
+        "serial_number",
+        format!("{:?}", before.serial_number),
+        format!("{:?}", after.serial_number),
+    );
+
+    diff
+}
+
+/**
+ * State transitions observed for tracked devices.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+// Connected stays unboxed: it is the variant every consumer matches
+// and moves from, and events are not stored in bulk.
+#[allow(clippy::large_enum_variant)]
+pub enum DeviceEvent {
+    Connected(UsbDeviceInfo),
+    Disconnected(DeviceIdentity),
+    Changed {
+        identity: DeviceIdentity,
+        before: Box<UsbDeviceInfo>,
+        after: Box<UsbDeviceInfo>,
+    },
+    /// A device's descriptors changed in place, without a disconnect.
+    /// BadUSB-style reconfiguration shows up here.
+    DescriptorChanged {
+        identity: DeviceIdentity,
+        before: Box<UsbDeviceInfo>,
+        after: Box<UsbDeviceInfo>,
+        diff: DescriptorDiff,
+    },
+}
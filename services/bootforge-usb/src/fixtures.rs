@@ -0,0 +1,298 @@
+// BootForge USB - Golden descriptor fixture corpus
+// Curated, anonymized real-device data for parser and downstream tests:
+// device records, sysfs attribute trees, and raw descriptor blobs. The
+// validation tests at the bottom keep the corpus honest - every fixture
+// must round-trip through the crate's own parsers.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::enumeration::{UsbDescriptorSummary, UsbDeviceRecord};
+use crate::version::BcdVersion;
+
+#[allow(clippy::too_many_arguments)]
+fn record(
+    bus_number: u8,
+    device_number: u8,
+    vendor_id: u16,
+    product_id: u16,
+    usb_version: u16,
+    device_version: u16,
+    class_triple: (u8, u8, u8),
+    max_packet_size_0: u8,
+    manufacturer: &str,
+    product: &str,
+    serial_number: Option<&str>,
+) -> UsbDeviceRecord {
+    UsbDeviceRecord {
+        bus_number,
+        device_number,
+        vendor_id,
+        product_id,
+        descriptor: UsbDescriptorSummary {
+            usb_version: BcdVersion(usb_version),
+            device_version: BcdVersion(device_version),
+            device_class: class_triple.0,
+            device_subclass: class_triple.1,
+            device_protocol: class_triple.2,
+            max_packet_size_0,
+            num_configurations: 1,
+        },
+        manufacturer: Some(manufacturer.to_string()),
+        product: Some(product.to_string()),
+        serial_number: serial_number.map(str::to_string),
+        sysfs_path: String::new(),
+        speed: None,
+        interfaces: Vec::new(),
+    }
+}
+
+/// Pixel phone booted to Android with USB debugging on.
+pub fn pixel_adb() -> UsbDeviceRecord {
+    record(
+        1, 9, 0x18d1, 0x4ee7, 0x0210, 0x0440, (0, 0, 0), 64,
+        "Google Inc.", "Pixel 7", Some("FIXTURE1A2B3C4D"),
+    )
+}
+
+/// Same phone with file transfer selected (MTP interface exposed).
+pub fn pixel_mtp() -> UsbDeviceRecord {
+    record(
+        1, 10, 0x18d1, 0x4ee1, 0x0210, 0x0440, (0, 0, 0), 64,
+        "Google Inc.", "Pixel 7 (MTP)", Some("FIXTURE1A2B3C4D"),
+    )
+}
+
+/// Same phone rebooted to the bootloader.
+pub fn pixel_fastboot() -> UsbDeviceRecord {
+    record(
+        1, 11, 0x18d1, 0x4ee0, 0x0200, 0x0100, (0, 0, 0), 64,
+        "Google Inc.", "Android Bootloader Interface", Some("FIXTURE1A2B3C4D"),
+    )
+}
+
+/// iPhone booted normally.
+pub fn iphone_normal() -> UsbDeviceRecord {
+    record(
+        2, 4, 0x05ac, 0x12a8, 0x0200, 0x1208, (0, 0, 0), 64,
+        "Apple Inc.", "iPhone", Some("FIXTURE00008110ABCD"),
+    )
+}
+
+/// iPhone in recovery mode (iBoot).
+pub fn iphone_recovery() -> UsbDeviceRecord {
+    record(
+        2, 5, 0x05ac, 0x1281, 0x0200, 0x0000, (0, 0, 0), 64,
+        "Apple Inc.", "Apple Mobile Device (Recovery Mode)", None,
+    )
+}
+
+/// STM32 microcontroller in its ROM DFU bootloader.
+pub fn stm32_dfu() -> UsbDeviceRecord {
+    record(
+        3, 6, 0x0483, 0xdf11, 0x0100, 0x2200, (0, 0, 0), 64,
+        "STMicroelectronics", "STM32 BOOTLOADER", Some("FIXTURE-STM32-0001"),
+    )
+}
+
+/// FTDI USB-serial bridge.
+pub fn ftdi_serial() -> UsbDeviceRecord {
+    record(
+        3, 7, 0x0403, 0x6001, 0x0200, 0x0600, (0, 0, 0), 8,
+        "FTDI", "FT232R USB UART", Some("FIXTUREA1B2C3"),
+    )
+}
+
+/// External SSD running UAS behind a SuperSpeed link.
+pub fn usb3_ssd() -> UsbDeviceRecord {
+    record(
+        4, 2, 0x0bc2, 0xab38, 0x0320, 0x0100, (0x08, 0x06, 0x62), 9,
+        "Seagate", "Expansion SSD", Some("FIXTURENACB1234"),
+    )
+}
+
+/// 7-port USB 3.x hub.
+pub fn hub_7port() -> UsbDeviceRecord {
+    record(
+        4, 3, 0x2109, 0x0817, 0x0320, 0x9011, (0x09, 0, 3), 9,
+        "VIA Labs, Inc.", "USB3.0 Hub", None,
+    )
+}
+
+/// UVC webcam with an isochronous video interface.
+pub fn webcam() -> UsbDeviceRecord {
+    record(
+        1, 12, 0x046d, 0x085e, 0x0200, 0x0016, (0xef, 0x02, 0x01), 64,
+        "Logitech", "BRIO Ultra HD Webcam", Some("FIXTURECAM001"),
+    )
+}
+
+/// The whole corpus, named, for table-driven tests.
+pub fn all() -> Vec<(&'static str, UsbDeviceRecord)> {
+    vec![
+        ("pixel_adb", pixel_adb()),
+        ("pixel_mtp", pixel_mtp()),
+        ("pixel_fastboot", pixel_fastboot()),
+        ("iphone_normal", iphone_normal()),
+        ("iphone_recovery", iphone_recovery()),
+        ("stm32_dfu", stm32_dfu()),
+        ("ftdi_serial", ftdi_serial()),
+        ("usb3_ssd", usb3_ssd()),
+        ("hub_7port", hub_7port()),
+        ("webcam", webcam()),
+    ]
+}
+
+/**
+ * Raw configuration descriptor of the STM32 DFU bootloader: config,
+ * one DFU interface (FE/01/02), and the DFU functional descriptor
+ * (type 0x21, bmAttributes 0x0b = download+upload+manifestation,
+ * wTransferSize 1024, DFU 1.1a).
+ */
+pub const STM32_DFU_CONFIG: &[u8] = &[
+    0x09, 0x02, 0x1b, 0x00, 0x01, 0x01, 0x00, 0xc0, 0x32, // config
+    0x09, 0x04, 0x00, 0x00, 0x00, 0xfe, 0x01, 0x02, 0x04, // interface
+    0x09, 0x21, 0x0b, 0xff, 0x00, 0x00, 0x04, 0x1a, 0x01, // DFU functional
+];
+
+/**
+ * Bulk-IN endpoint of the USB3 SSD with its SuperSpeed companion:
+ * wMaxPacketSize 1024, bMaxBurst 15, MaxStreams 2^4 = 16 (UAS).
+ */
+pub const USB3_SSD_BULK_IN_ENDPOINT: &[u8] = &[
+    0x07, 0x05, 0x81, 0x02, 0x00, 0x04, 0x00, // endpoint
+    0x06, 0x30, 0x0f, 0x04, 0x00, 0x00, // SS companion
+];
+
+/// The webcam's product string descriptor as read off the wire.
+pub const WEBCAM_PRODUCT_STRING: &[u8] = &[
+    0x2a, 0x03, 0x42, 0x00, 0x52, 0x00, 0x49, 0x00, 0x4f, 0x00, 0x20, 0x00, 0x55, 0x00, 0x6c,
+    0x00, 0x74, 0x00, 0x72, 0x00, 0x61, 0x00, 0x20, 0x00, 0x48, 0x00, 0x44, 0x00, 0x20, 0x00,
+    0x57, 0x00, 0x65, 0x00, 0x62, 0x00, 0x63, 0x00, 0x61, 0x00, 0x6d, 0x00,
+];
+
+/**
+ * SetupAPI-style device property map for the Pixel in ADB mode, as a
+ * Windows-side consumer would see it.
+ */
+pub fn pixel_adb_setupapi_properties() -> BTreeMap<&'static str, &'static str> {
+    BTreeMap::from([
+        ("DEVPKEY_Device_InstanceId", "USB\\VID_18D1&PID_4EE7\\FIXTURE1A2B3C4D"),
+        ("DEVPKEY_Device_HardwareIds", "USB\\VID_18D1&PID_4EE7&REV_0440"),
+        ("DEVPKEY_Device_CompatibleIds", "USB\\Class_FF&SubClass_42&Prot_01"),
+        ("DEVPKEY_Device_BusReportedDeviceDesc", "Pixel 7"),
+        ("DEVPKEY_Device_Manufacturer", "Google Inc."),
+    ])
+}
+
+/// Sysfs attribute files for a fixture, as (name, contents) pairs in the
+/// format the kernel uses (e.g. "version" is " 2.10").
+pub fn sysfs_attributes(record: &UsbDeviceRecord) -> Vec<(&'static str, String)> {
+    let mut attrs = vec![
+        ("busnum", record.bus_number.to_string()),
+        ("devnum", record.device_number.to_string()),
+        ("idVendor", format!("{:04x}", record.vendor_id)),
+        ("idProduct", format!("{:04x}", record.product_id)),
+        ("version", format!(" {}", record.descriptor.usb_version)),
+        ("bcdDevice", format!("{:04x}", record.descriptor.device_version.0)),
+        ("bDeviceClass", format!("{:02x}", record.descriptor.device_class)),
+        ("bDeviceSubClass", format!("{:02x}", record.descriptor.device_subclass)),
+        ("bDeviceProtocol", format!("{:02x}", record.descriptor.device_protocol)),
+        ("bMaxPacketSize0", record.descriptor.max_packet_size_0.to_string()),
+        ("bNumConfigurations", record.descriptor.num_configurations.to_string()),
+    ];
+    if let Some(manufacturer) = &record.manufacturer {
+        attrs.push(("manufacturer", manufacturer.clone()));
+    }
+    if let Some(product) = &record.product {
+        attrs.push(("product", product.clone()));
+    }
+    if let Some(serial) = &record.serial_number {
+        attrs.push(("serial", serial.clone()));
+    }
+    attrs
+}
+
+/// Write a fixture's sysfs tree under `root/name` for tests that drive
+/// the fallback enumerator against it.
+pub fn write_sysfs_tree(root: &Path, name: &str, record: &UsbDeviceRecord) -> std::io::Result<()> {
+    let dir = root.join(name);
+    fs::create_dir_all(&dir)?;
+    for (attr, value) in sysfs_attributes(record) {
+        fs::write(dir.join(attr), format!("{}\n", value))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::FallbackEnumerator;
+    use crate::protocols::classify::{classify_device_protocols_set, Protocol};
+    use crate::protocols::dfu::DfuCapabilities;
+    use crate::strings::decode_string_descriptor;
+    use crate::topology::EndpointCompanion;
+
+    #[test]
+    fn test_every_fixture_round_trips_through_the_fallback_parser() {
+        let root = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join("fixture_corpus")
+            .join(format!("{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        for (name, record) in all() {
+            write_sysfs_tree(&root, name, &record).unwrap();
+        }
+
+        let mut parsed = FallbackEnumerator::with_root(&root).enumerate().unwrap();
+        assert_eq!(parsed.len(), all().len());
+        for record in &mut parsed {
+            record.sysfs_path = String::new();
+        }
+        for (name, record) in all() {
+            assert!(
+                parsed.contains(&record),
+                "fixture {} did not survive the parser round-trip",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_fixtures_classify_as_expected() {
+        let set = classify_device_protocols_set(&pixel_fastboot());
+        assert!(set.contains(Protocol::Fastboot));
+        assert!(classify_device_protocols_set(&pixel_mtp()).contains(Protocol::Mtp));
+        assert!(classify_device_protocols_set(&iphone_normal()).contains(Protocol::Apple));
+        assert!(classify_device_protocols_set(&usb3_ssd()).contains(Protocol::Uasp));
+    }
+
+    #[test]
+    fn test_raw_blobs_parse_with_crate_parsers() {
+        // STM32 DFU functional descriptor: attributes at offset 18 + 2.
+        assert_eq!(STM32_DFU_CONFIG[19], 0x21);
+        let caps = DfuCapabilities::from_attributes(STM32_DFU_CONFIG[20]);
+        assert!(caps.can_download && caps.can_upload);
+
+        let companion = EndpointCompanion::parse(&USB3_SSD_BULK_IN_ENDPOINT[7..]).unwrap();
+        assert_eq!(companion.max_burst, 15);
+        assert_eq!(companion.max_streams(), 16);
+
+        let decoded = decode_string_descriptor(WEBCAM_PRODUCT_STRING);
+        assert_eq!(decoded.text.as_deref(), Some("BRIO Ultra HD Webcam"));
+        assert!(!decoded.malformed);
+    }
+
+    #[test]
+    fn test_udev_mapping_accepts_fixture_records() {
+        let props = crate::linux::to_udev_properties(&pixel_adb());
+        assert_eq!(props.get("ID_VENDOR_ID").map(String::as_str), Some("18d1"));
+        assert_eq!(
+            props.get("ID_SERIAL_SHORT").map(String::as_str),
+            Some("FIXTURE1A2B3C4D")
+        );
+    }
+}
@@ -0,0 +1,143 @@
+// BootForge USB - Gadget configfs writer
+// Sets up a USB gadget through /sys/kernel/config/usb_gadget, primarily
+// so the integration harness can fake plug/unplug against dummy_hcd.
+// Requires libcomposite (and dummy_hcd for a software UDC) plus root.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::UsbError;
+
+const CONFIGFS_ROOT: &str = "/sys/kernel/config/usb_gadget";
+const UDC_CLASS: &str = "/sys/class/udc";
+
+/**
+ * Identity strings written into the gadget's 0x409 string directory.
+ */
+#[derive(Debug, Clone)]
+pub struct GadgetStrings {
+    pub manufacturer: String,
+    pub product: String,
+    pub serial_number: String,
+}
+
+/**
+ * A gadget directory under configfs. Dropping the handle tears the
+ * gadget down (unbind, unlink functions, remove directories),
+ * best-effort.
+ */
+pub struct ConfigfsGadget {
+    root: PathBuf,
+    bound: bool,
+}
+
+impl ConfigfsGadget {
+    /**
+     * Create a gadget named `name` with one configuration and one ACM
+     * function, ready to bind. Fails with Unsupported when configfs is
+     * absent and with Io when permissions are lacking.
+     */
+    pub fn create(
+        name: &str,
+        vendor_id: u16,
+        product_id: u16,
+        strings: &GadgetStrings,
+    ) -> Result<Self, UsbError> {
+        Self::create_under(Path::new(CONFIGFS_ROOT), name, vendor_id, product_id, strings)
+    }
+
+    /// Same as `create` with an injectable configfs root for tests.
+    pub fn create_under(
+        configfs_root: &Path,
+        name: &str,
+        vendor_id: u16,
+        product_id: u16,
+        strings: &GadgetStrings,
+    ) -> Result<Self, UsbError> {
+        if !configfs_root.exists() {
+            return Err(UsbError::Unsupported(format!(
+                "configfs gadget root not present: {} (modprobe libcomposite?)",
+                configfs_root.display()
+            )));
+        }
+
+        let root = configfs_root.join(name);
+        fs::create_dir(&root)?;
+        let gadget = ConfigfsGadget { root, bound: false };
+
+        gadget.write("idVendor", &format!("0x{:04x}", vendor_id))?;
+        gadget.write("idProduct", &format!("0x{:04x}", product_id))?;
+        gadget.write("bcdDevice", "0x0100")?;
+        gadget.write("bcdUSB", "0x0200")?;
+
+        let lang = gadget.root.join("strings/0x409");
+        fs::create_dir_all(&lang)?;
+        fs::write(lang.join("manufacturer"), &strings.manufacturer)?;
+        fs::write(lang.join("product"), &strings.product)?;
+        fs::write(lang.join("serialnumber"), &strings.serial_number)?;
+
+        let config = gadget.root.join("configs/c.1");
+        fs::create_dir_all(&config)?;
+        fs::write(config.join("MaxPower"), "250")?;
+
+        let function = gadget.root.join("functions/acm.usb0");
+        fs::create_dir_all(&function)?;
+        std::os::unix::fs::symlink(&function, config.join("acm.usb0"))?;
+
+        Ok(gadget)
+    }
+
+    fn write(&self, attr: &str, value: &str) -> Result<(), UsbError> {
+        fs::write(self.root.join(attr), value)?;
+        Ok(())
+    }
+
+    /// Bind the gadget to a UDC, making it appear on the host side.
+    pub fn bind(&mut self, udc: &str) -> Result<(), UsbError> {
+        self.write("UDC", udc)?;
+        self.bound = true;
+        Ok(())
+    }
+
+    /// Unbind from the UDC, simulating an unplug.
+    pub fn unbind(&mut self) -> Result<(), UsbError> {
+        self.write("UDC", "\n")?;
+        self.bound = false;
+        Ok(())
+    }
+
+    /// Remove the gadget directories; called by Drop, best-effort.
+    pub fn teardown(&mut self) {
+        if self.bound {
+            let _ = self.unbind();
+        }
+        let _ = fs::remove_file(self.root.join("configs/c.1/acm.usb0"));
+        let _ = fs::remove_dir(self.root.join("configs/c.1"));
+        let _ = fs::remove_dir(self.root.join("functions/acm.usb0"));
+        let _ = fs::remove_dir(self.root.join("strings/0x409"));
+        let _ = fs::remove_dir(&self.root);
+    }
+}
+
+impl Drop for ConfigfsGadget {
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
+/**
+ * First dummy_hcd UDC on the system, e.g. "dummy_udc.0". None when the
+ * module is not loaded.
+ */
+pub fn dummy_udc() -> Option<String> {
+    let entries = fs::read_dir(UDC_CLASS).ok()?;
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .find(|name| name.starts_with("dummy_udc"))
+}
+
+/// Whether the kernel exposes the configfs gadget interface at all.
+pub fn configfs_available() -> bool {
+    Path::new(CONFIGFS_ROOT).exists()
+}
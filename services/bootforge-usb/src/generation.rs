@@ -0,0 +1,108 @@
+// BootForge USB - Bus generation counter
+// Cheap "did anything change since I last looked" signal for consumers
+// that do not want to pay for enumeration or run a watcher.
+
+use crate::enumeration::FallbackEnumerator;
+
+/**
+ * A cheap, monotonically-increasing-in-practice counter over the USB
+ * bus. Semantics: a topology change implies the value changes; a
+ * changed value does not guarantee a meaningful USB change (the Linux
+ * source counts all kernel uevents, and the fallback is a set hash that
+ * moves whenever the (bus, address, vid, pid) set moves).
+ *
+ * Sources, in order: the kernel's uevent sequence number on Linux, then
+ * a hash of the sysfs device set, then zero when nothing is readable.
+ * Compare values for equality only; never interpret the magnitude.
+ */
+pub fn bus_generation() -> u64 {
+    #[cfg(target_os = "linux")]
+    if let Some(seq) = uevent_seqnum() {
+        return seq;
+    }
+    fallback_generation()
+}
+
+#[cfg(target_os = "linux")]
+fn uevent_seqnum() -> Option<u64> {
+    std::fs::read_to_string("/sys/kernel/uevent_seqnum")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Hash of the present device set; used when no kernel counter exists.
+fn fallback_generation() -> u64 {
+    match FallbackEnumerator::new().enumerate() {
+        Ok(records) => generation_of_set(
+            records
+                .iter()
+                .map(|r| (r.bus_number, r.device_number, r.vendor_id, r.product_id)),
+        ),
+        Err(_) => 0,
+    }
+}
+
+/**
+ * Order-independent hash of a (bus, address, vid, pid) set. Exposed so
+ * platform watchers can derive a generation from whatever set they
+ * already hold.
+ */
+pub fn generation_of_set(devices: impl Iterator<Item = (u8, u8, u16, u16)>) -> u64 {
+    devices
+        .map(|(bus, address, vendor_id, product_id)| {
+            // FNV-1a over the tuple bytes, then a splitmix64 finalizer so
+            // near-identical tuples don't produce near-identical hashes.
+            let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+            for byte in [
+                bus,
+                address,
+                (vendor_id >> 8) as u8,
+                vendor_id as u8,
+                (product_id >> 8) as u8,
+                product_id as u8,
+            ] {
+                h ^= u64::from(byte);
+                h = h.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+            h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            h ^ (h >> 31)
+        })
+        .fold(0, |acc, h| acc ^ h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_hash_is_order_independent() {
+        let a = [(1, 4, 0x18d1, 0x4ee7), (2, 7, 0x0781, 0x5583)];
+        let b = [(2, 7, 0x0781, 0x5583), (1, 4, 0x18d1, 0x4ee7)];
+        assert_eq!(
+            generation_of_set(a.into_iter()),
+            generation_of_set(b.into_iter())
+        );
+    }
+
+    #[test]
+    fn test_set_hash_moves_on_any_membership_change() {
+        let base = [(1, 4, 0x18d1, 0x4ee7), (2, 7, 0x0781, 0x5583)];
+        let removed = [(1, 4, 0x18d1, 0x4ee7)];
+        let readdressed = [(1, 5, 0x18d1, 0x4ee7), (2, 7, 0x0781, 0x5583)];
+
+        let base = generation_of_set(base.into_iter());
+        assert_ne!(base, generation_of_set(removed.into_iter()));
+        assert_ne!(base, generation_of_set(readdressed.into_iter()));
+        assert_ne!(base, generation_of_set(std::iter::empty()));
+    }
+
+    #[test]
+    fn test_bus_generation_is_callable_everywhere() {
+        // Whatever source is available, the call must return without
+        // opening devices or erroring.
+        let _ = bus_generation();
+    }
+}
@@ -0,0 +1,449 @@
+// BootForge USB - Link health heuristics
+// Detects the two failure shapes support keeps seeing: tight reset
+// loops (bad cable, brownout, bootloader crash cycles) and slower but
+// persistent flapping. Pure bookkeeping over observed events; every
+// entry point takes an explicit timestamp so the heuristics are
+// deterministic under test, with now()-based convenience wrappers.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::enumeration::UsbDeviceInfo;
+use crate::events::{DeviceEvent, DeviceIdentity};
+
+/**
+ * Assessment of a device's connection stability.
+ */
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LinkHealth {
+    #[default]
+    Good,
+    /// Flapping: repeated disconnects or transfer errors, slower than a
+    /// reset loop but persistent.
+    Unstable,
+    /// Cycling faster than any legitimate replug.
+    ResetLoop,
+    /// The device requests more bus power than its upstream hub can
+    /// legally supply; see `assess_power`.
+    PowerIssueHint,
+}
+
+impl LinkHealth {
+    /// The tag watchers attach to emitted records; None for Good.
+    pub fn tag(&self) -> Option<&'static str> {
+        match self {
+            LinkHealth::Good => None,
+            LinkHealth::Unstable => Some("health:unstable"),
+            LinkHealth::ResetLoop => Some("health:reset-loop"),
+            LinkHealth::PowerIssueHint => Some("health:power-issue"),
+        }
+    }
+}
+
+/// Bus power a high-draw configuration requests; a single port on a
+/// bus-powered hub can legally supply far less.
+const POWER_HINT_THRESHOLD_MA: u16 = 500;
+
+/**
+ * Flag configurations that over-draw a bus-powered hub: a device whose
+ * active configuration requests `POWER_HINT_THRESHOLD_MA` or more while
+ * sitting behind a hub that is itself bus powered. Such setups work
+ * until the device actually draws the power, then brown out - a common
+ * root cause of the reset loops the tracker detects after the fact.
+ *
+ * Returns the hint and a human-readable reason, or None when the
+ * configuration is unknown, self powered, or within budget.
+ */
+pub fn assess_power(
+    info: &UsbDeviceInfo,
+    hub_self_powered: bool,
+) -> Option<(LinkHealth, String)> {
+    let config = info.active_config.as_ref()?;
+    if config.self_powered || hub_self_powered || config.max_power_ma < POWER_HINT_THRESHOLD_MA {
+        return None;
+    }
+    Some((
+        LinkHealth::PowerIssueHint,
+        format!(
+            "device {:04x}:{:04x} requests {} mA from a bus-powered hub",
+            info.vendor_id, info.product_id, config.max_power_ma
+        ),
+    ))
+}
+
+/**
+ * When a cycle count becomes a verdict. The defaults classify five
+ * disconnects inside ten seconds as a reset loop and three disconnects
+ * or transfer errors inside two minutes as unstable.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthThresholds {
+    pub reset_loop_count: usize,
+    pub reset_loop_window: Duration,
+    pub unstable_count: usize,
+    pub unstable_window: Duration,
+    /// Caller-reported transfer errors counted toward Unstable.
+    pub error_count: usize,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        HealthThresholds {
+            reset_loop_count: 5,
+            reset_loop_window: Duration::from_secs(10),
+            unstable_count: 3,
+            unstable_window: Duration::from_secs(120),
+            error_count: 3,
+        }
+    }
+}
+
+impl HealthThresholds {
+    pub fn with_reset_loop(mut self, count: usize, window: Duration) -> Self {
+        self.reset_loop_count = count;
+        self.reset_loop_window = window;
+        self
+    }
+
+    pub fn with_unstable(mut self, count: usize, window: Duration) -> Self {
+        self.unstable_count = count;
+        self.unstable_window = window;
+        self
+    }
+
+    pub fn with_error_count(mut self, count: usize) -> Self {
+        self.error_count = count;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct DeviceState {
+    disconnects: VecDeque<Instant>,
+    errors: VecDeque<Instant>,
+    last_seen: Option<Instant>,
+}
+
+/**
+ * Per-identity connection statistics over a sliding window.
+ *
+ * Feed it the watcher's event stream (and transfer failures via
+ * `record_error`), then ask `assess` for the verdict. Samples older
+ * than the widest window are dropped as new ones arrive, so memory is
+ * bounded by actual flapping, not uptime.
+ */
+#[derive(Debug, Default)]
+pub struct LinkHealthTracker {
+    thresholds: HealthThresholds,
+    state: HashMap<String, DeviceState>,
+}
+
+impl LinkHealthTracker {
+    pub fn new(thresholds: HealthThresholds) -> Self {
+        LinkHealthTracker {
+            thresholds,
+            state: HashMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, event: &DeviceEvent) {
+        self.observe_at(event, Instant::now());
+    }
+
+    /// As `observe`, with an explicit timestamp.
+    pub fn observe_at(&mut self, event: &DeviceEvent, at: Instant) {
+        let identity = match event {
+            DeviceEvent::Connected(info) => DeviceIdentity::of(info),
+            DeviceEvent::Disconnected(identity) => identity.clone(),
+            DeviceEvent::Changed { identity, .. }
+            | DeviceEvent::DescriptorChanged { identity, .. } => identity.clone(),
+        };
+        let window = self.widest_window();
+        let state = self.state.entry(identity.0).or_default();
+        match event {
+            DeviceEvent::Disconnected(_) => state.disconnects.push_back(at),
+            _ => state.last_seen = Some(at),
+        }
+        prune(&mut state.disconnects, at, window);
+        prune(&mut state.errors, at, window);
+    }
+
+    pub fn record_error(&mut self, identity: &DeviceIdentity) {
+        self.record_error_at(identity, Instant::now());
+    }
+
+    /// Count a failed transfer against an identity.
+    pub fn record_error_at(&mut self, identity: &DeviceIdentity, at: Instant) {
+        let window = self.widest_window();
+        let state = self.state.entry(identity.0.clone()).or_default();
+        state.errors.push_back(at);
+        prune(&mut state.errors, at, window);
+    }
+
+    pub fn assess(&self, identity: &DeviceIdentity) -> LinkHealth {
+        self.assess_at(identity, Instant::now())
+    }
+
+    /// The verdict for an identity as of `at`.
+    pub fn assess_at(&self, identity: &DeviceIdentity, at: Instant) -> LinkHealth {
+        let Some(state) = self.state.get(&identity.0) else {
+            return LinkHealth::Good;
+        };
+        let t = &self.thresholds;
+        if count_within(&state.disconnects, at, t.reset_loop_window) >= t.reset_loop_count {
+            return LinkHealth::ResetLoop;
+        }
+        if count_within(&state.disconnects, at, t.unstable_window) >= t.unstable_count
+            || count_within(&state.errors, at, t.unstable_window) >= t.error_count
+        {
+            return LinkHealth::Unstable;
+        }
+        LinkHealth::Good
+    }
+
+    /**
+     * Re-assess a device in place: the current verdict replaces any
+     * previous `health:` tag on the info. The hook for callers that
+     * want to re-check after an operation fails - record the error,
+     * refresh, and the record reflects the new verdict.
+     */
+    pub fn refresh_link_health(&self, info: &mut UsbDeviceInfo) -> LinkHealth {
+        self.refresh_link_health_at(info, Instant::now())
+    }
+
+    /// As `refresh_link_health`, with an explicit timestamp.
+    pub fn refresh_link_health_at(&self, info: &mut UsbDeviceInfo, at: Instant) -> LinkHealth {
+        let verdict = self.assess_at(&DeviceIdentity::of(info), at);
+        info.tags.retain(|tag| !tag.starts_with("health:"));
+        if let Some(tag) = verdict.tag() {
+            info.tags.push(tag.to_string());
+        }
+        verdict
+    }
+
+    /// Time since the identity last produced a non-disconnect event.
+    pub fn time_since_last_seen(&self, identity: &DeviceIdentity, at: Instant) -> Option<Duration> {
+        self.state
+            .get(&identity.0)
+            .and_then(|s| s.last_seen)
+            .map(|seen| at.saturating_duration_since(seen))
+    }
+
+    fn widest_window(&self) -> Duration {
+        self.thresholds
+            .reset_loop_window
+            .max(self.thresholds.unstable_window)
+    }
+}
+
+fn prune(samples: &mut VecDeque<Instant>, at: Instant, window: Duration) {
+    while let Some(&front) = samples.front() {
+        if at.saturating_duration_since(front) > window {
+            samples.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+fn count_within(samples: &VecDeque<Instant>, at: Instant, window: Duration) -> usize {
+    samples
+        .iter()
+        .filter(|&&s| at.saturating_duration_since(s) <= window)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> DeviceIdentity {
+        DeviceIdentity("usb:18d1:4ee7:serial:A".to_string())
+    }
+
+    fn cycle(tracker: &mut LinkHealthTracker, at: Instant) {
+        let info = crate::watch::partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("A".to_string()),
+            None,
+            "test:health".to_string(),
+        );
+        // Identity comes from the canonical rules, not our literal.
+        let identity = DeviceIdentity::of(&info);
+        tracker.observe_at(&DeviceEvent::Connected(info), at);
+        tracker.observe_at(&DeviceEvent::Disconnected(identity), at);
+    }
+
+    fn tracked_identity() -> DeviceIdentity {
+        DeviceIdentity::of(&crate::watch::partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("A".to_string()),
+            None,
+            "test:health".to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_reset_loop_detection() {
+        let base = Instant::now();
+        let mut tracker = LinkHealthTracker::new(
+            HealthThresholds::default().with_reset_loop(5, Duration::from_secs(10)),
+        );
+        // Five cycles in eight seconds: a loop.
+        for i in 0..5u64 {
+            cycle(&mut tracker, base + Duration::from_secs(i * 2));
+        }
+        let id = tracked_identity();
+        assert_eq!(
+            tracker.assess_at(&id, base + Duration::from_secs(8)),
+            LinkHealth::ResetLoop
+        );
+        // The same five cycles viewed much later have aged out of the
+        // reset window but still read as slow flapping.
+        assert_eq!(
+            tracker.assess_at(&id, base + Duration::from_secs(60)),
+            LinkHealth::Unstable
+        );
+    }
+
+    #[test]
+    fn test_slow_flapping_is_unstable_not_reset_loop() {
+        let base = Instant::now();
+        let mut tracker = LinkHealthTracker::new(HealthThresholds::default());
+        // One cycle every 30 seconds: persistent but not a loop.
+        for i in 0..4u64 {
+            cycle(&mut tracker, base + Duration::from_secs(i * 30));
+        }
+        let id = tracked_identity();
+        assert_eq!(
+            tracker.assess_at(&id, base + Duration::from_secs(95)),
+            LinkHealth::Unstable
+        );
+        // Two quiet hours later everything has aged out.
+        assert_eq!(
+            tracker.assess_at(&id, base + Duration::from_secs(7200)),
+            LinkHealth::Good
+        );
+    }
+
+    #[test]
+    fn test_refresh_link_health_replaces_the_tag() {
+        let base = Instant::now();
+        let mut tracker = LinkHealthTracker::new(
+            HealthThresholds::default().with_reset_loop(5, Duration::from_secs(10)),
+        );
+        for i in 0..5u64 {
+            cycle(&mut tracker, base + Duration::from_secs(i));
+        }
+
+        let mut info = crate::watch::partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("A".to_string()),
+            None,
+            "test:health".to_string(),
+        );
+        assert_eq!(
+            tracker.refresh_link_health_at(&mut info, base + Duration::from_secs(5)),
+            LinkHealth::ResetLoop
+        );
+        assert!(info.tags.iter().any(|t| t == "health:reset-loop"));
+
+        // A later re-check downgrades the verdict and swaps the tag
+        // rather than stacking a second one.
+        assert_eq!(
+            tracker.refresh_link_health_at(&mut info, base + Duration::from_secs(60)),
+            LinkHealth::Unstable
+        );
+        assert!(info.tags.iter().any(|t| t == "health:unstable"));
+        assert!(!info.tags.iter().any(|t| t == "health:reset-loop"));
+
+        // Everything aged out: no health tag at all, other tags intact.
+        assert_eq!(
+            tracker.refresh_link_health_at(&mut info, base + Duration::from_secs(7200)),
+            LinkHealth::Good
+        );
+        assert!(!info.tags.iter().any(|t| t.starts_with("health:")));
+        assert!(info.tags.iter().any(|t| t == "test:health"));
+    }
+
+    #[test]
+    fn test_transfer_errors_feed_unstable() {
+        let base = Instant::now();
+        let mut tracker =
+            LinkHealthTracker::new(HealthThresholds::default().with_error_count(3));
+        let id = identity();
+        assert_eq!(tracker.assess_at(&id, base), LinkHealth::Good);
+        for i in 0..3u64 {
+            tracker.record_error_at(&id, base + Duration::from_secs(i));
+        }
+        assert_eq!(
+            tracker.assess_at(&id, base + Duration::from_secs(3)),
+            LinkHealth::Unstable
+        );
+        // Errors count per identity; a neighbour stays Good.
+        assert_eq!(
+            tracker.assess_at(
+                &DeviceIdentity("usb:0781:5583:port:1-2".to_string()),
+                base + Duration::from_secs(3)
+            ),
+            LinkHealth::Good
+        );
+    }
+
+    #[test]
+    fn test_power_hint_only_behind_bus_powered_hubs() {
+        let mut info = crate::watch::partial_info(
+            0x0bda,
+            0x8153,
+            None,
+            Some("1-2.3".to_string()),
+            "test:health".to_string(),
+        );
+        // No config read: nothing to assess.
+        assert_eq!(assess_power(&info, false), None);
+
+        info.active_config = Some(crate::enumeration::ConfigInfo {
+            configuration_value: 1,
+            max_power_ma: 500,
+            self_powered: false,
+            remote_wakeup: false,
+            description: None,
+            interfaces: Vec::new(),
+        });
+        let (health, reason) = assess_power(&info, false).unwrap();
+        assert_eq!(health, LinkHealth::PowerIssueHint);
+        assert_eq!(health.tag(), Some("health:power-issue"));
+        assert!(reason.contains("0bda:8153"), "reason: {}", reason);
+        assert!(reason.contains("500 mA"), "reason: {}", reason);
+
+        // A self-powered hub can supply the draw; a self-powered device
+        // does not take it from the bus at all.
+        assert_eq!(assess_power(&info, true), None);
+        info.active_config.as_mut().unwrap().self_powered = true;
+        assert_eq!(assess_power(&info, false), None);
+
+        // Modest draws stay below the threshold.
+        let config = info.active_config.as_mut().unwrap();
+        config.self_powered = false;
+        config.max_power_ma = 100;
+        assert_eq!(assess_power(&info, false), None);
+    }
+
+    #[test]
+    fn test_last_seen_tracking() {
+        let base = Instant::now();
+        let mut tracker = LinkHealthTracker::new(HealthThresholds::default());
+        cycle(&mut tracker, base);
+        let id = tracked_identity();
+        assert_eq!(
+            tracker.time_since_last_seen(&id, base + Duration::from_secs(5)),
+            Some(Duration::from_secs(5))
+        );
+    }
+}
@@ -0,0 +1,523 @@
+// BootForge USB - Device event journal
+// Append-only JSONL changelog of device state transitions, rotated by
+// size and age, with time-range queries for auditor-style questions
+// ("what storage devices were attached between 2pm and 4pm").
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::enumeration::DeviceFilter;
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+
+const ACTIVE_FILE: &str = "journal.jsonl";
+
+/// Kind of state transition a journal entry records.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+    Connected,
+    Disconnected,
+    Changed,
+    DescriptorChanged,
+}
+
+/**
+ * One journaled state transition.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JournalEntry {
+    pub timestamp: DateTime<Utc>,
+    pub kind: TransitionKind,
+    pub identity: DeviceIdentity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_id: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl JournalEntry {
+    fn from_event(event: &DeviceEvent, timestamp: DateTime<Utc>) -> Self {
+        let (kind, identity, info) = match event {
+            DeviceEvent::Connected(info) => (
+                TransitionKind::Connected,
+                DeviceIdentity::of(info),
+                Some(info),
+            ),
+            DeviceEvent::Disconnected(identity) => {
+                (TransitionKind::Disconnected, identity.clone(), None)
+            }
+            DeviceEvent::Changed {
+                identity, after, ..
+            } => (TransitionKind::Changed, identity.clone(), Some(&**after)),
+            DeviceEvent::DescriptorChanged {
+                identity, after, ..
+            } => (
+                TransitionKind::DescriptorChanged,
+                identity.clone(),
+                Some(&**after),
+            ),
+        };
+        JournalEntry {
+            timestamp,
+            kind,
+            identity,
+            vendor_id: info.map(|i| i.vendor_id),
+            product_id: info.map(|i| i.product_id),
+            serial_number: info.and_then(|i| i.serial_number.clone()),
+            port_path: info.and_then(|i| i.port_path.clone()),
+            tags: info.map(|i| i.tags.clone()).unwrap_or_default(),
+        }
+    }
+
+    fn matches(&self, filter: &DeviceFilter) -> bool {
+        filter.vendor_id.is_none_or(|v| self.vendor_id == Some(v))
+            && filter.product_id.is_none_or(|p| self.product_id == Some(p))
+            && filter
+                .serial_number
+                .as_deref()
+                .is_none_or(|s| self.serial_number.as_deref() == Some(s))
+            && filter
+                .tag
+                .as_deref()
+                .is_none_or(|t| self.tags.iter().any(|tag| tag == t))
+    }
+}
+
+/**
+ * Result of a journal query; corrupted lines (crash mid-write, manual
+ * edits) are skipped and counted rather than aborting the query.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub entries: Vec<JournalEntry>,
+    pub corrupted_lines: usize,
+}
+
+/**
+ * Append-only JSONL event journal with size/age rotation. Rotated files
+ * stay in the journal directory and remain queryable.
+ */
+pub struct EventJournal {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    max_file_age: Duration,
+    /// Timestamp of the first entry in the active file, for age rotation.
+    active_since: Option<DateTime<Utc>>,
+}
+
+impl EventJournal {
+    /// Open (or create) a journal in `dir` with default rotation limits
+    /// of 16 MiB and 24 hours per file.
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self, UsbError> {
+        Self::open_with_limits(dir, 16 * 1024 * 1024, Duration::from_secs(24 * 60 * 60))
+    }
+
+    pub fn open_with_limits<P: Into<PathBuf>>(
+        dir: P,
+        max_file_bytes: u64,
+        max_file_age: Duration,
+    ) -> Result<Self, UsbError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut journal = EventJournal {
+            dir,
+            max_file_bytes,
+            max_file_age,
+            active_since: None,
+        };
+        journal.active_since = journal.first_active_timestamp();
+        Ok(journal)
+    }
+
+    fn active_path(&self) -> PathBuf {
+        self.dir.join(ACTIVE_FILE)
+    }
+
+    /// Timestamp of the first parseable entry in the active file.
+    fn first_active_timestamp(&self) -> Option<DateTime<Utc>> {
+        let contents = fs::read_to_string(self.active_path()).ok()?;
+        contents
+            .lines()
+            .find_map(|line| serde_json::from_str::<JournalEntry>(line).ok())
+            .map(|entry| entry.timestamp)
+    }
+
+    /// Record `event` with the current time.
+    pub fn record(&mut self, event: &DeviceEvent) -> Result<(), UsbError> {
+        self.record_at(event, Utc::now())
+    }
+
+    /// Record `event` with an explicit timestamp (deterministic tests,
+    /// replayed histories).
+    pub fn record_at(
+        &mut self,
+        event: &DeviceEvent,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), UsbError> {
+        self.rotate_if_needed(timestamp)?;
+
+        let entry = JournalEntry::from_event(event, timestamp);
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| UsbError::Parse(format!("journal serialization failed: {}", e)))?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(self.active_path())?;
+        // A crash mid-write can leave a torn line without a newline;
+        // terminate it so the new entry does not get glued onto it.
+        let len = file.metadata()?.len();
+        if len > 0 {
+            use std::io::{Read, Seek, SeekFrom};
+            file.seek(SeekFrom::End(-1))?;
+            let mut last = [0u8; 1];
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                writeln!(file)?;
+            }
+        }
+        writeln!(file, "{}", line)?;
+
+        if self.active_since.is_none() {
+            self.active_since = Some(timestamp);
+        }
+        Ok(())
+    }
+
+    fn rotate_if_needed(&mut self, now: DateTime<Utc>) -> Result<(), UsbError> {
+        let path = self.active_path();
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let over_size = size >= self.max_file_bytes;
+        let over_age = self.active_since.is_some_and(|since| {
+            (now - since).to_std().unwrap_or(Duration::ZERO) >= self.max_file_age
+        });
+        if size == 0 || (!over_size && !over_age) {
+            return Ok(());
+        }
+
+        // Rotated name carries the rotation instant; a counter suffix
+        // keeps same-second rotations from colliding.
+        let base = now.format("journal-%Y%m%dT%H%M%S");
+        let mut target = self.dir.join(format!("{}.jsonl", base));
+        let mut counter = 1;
+        while target.exists() {
+            target = self.dir.join(format!("{}-{}.jsonl", base, counter));
+            counter += 1;
+        }
+        fs::rename(&path, target)?;
+        self.active_since = None;
+        Ok(())
+    }
+
+    /**
+     * All entries within `time_range` matching `filter`, across the
+     * active and rotated files, oldest first. Unparseable lines are
+     * counted in the result, not fatal.
+     */
+    pub fn query(
+        &self,
+        time_range: Range<DateTime<Utc>>,
+        filter: &DeviceFilter,
+    ) -> Result<QueryResult, UsbError> {
+        let mut result = QueryResult::default();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_journal = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("journal") && n.ends_with(".jsonl"));
+            if !is_journal {
+                continue;
+            }
+            for line in fs::read_to_string(&path)?.lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<JournalEntry>(line) {
+                    Ok(entry) => {
+                        if time_range.contains(&entry.timestamp) && entry.matches(filter) {
+                            result.entries.push(entry);
+                        }
+                    }
+                    Err(_) => result.corrupted_lines += 1,
+                }
+            }
+        }
+
+        result.entries.sort_by_key(|e| e.timestamp);
+        Ok(result)
+    }
+}
+
+/// Minimal CSV field quoting: wrap when the value contains a comma,
+/// quote, or newline, doubling embedded quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/**
+ * Export entries as CSV with a header row, for handing query results to
+ * auditors.
+ */
+pub fn export_csv<W: Write>(entries: &[JournalEntry], out: &mut W) -> Result<(), UsbError> {
+    writeln!(
+        out,
+        "timestamp,kind,identity,vendor_id,product_id,serial_number,port_path,tags"
+    )?;
+    for entry in entries {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            entry.timestamp.to_rfc3339(),
+            serde_json::to_value(entry.kind)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            csv_field(&entry.identity.0),
+            entry.vendor_id.map(|v| format!("{:04x}", v)).unwrap_or_default(),
+            entry.product_id.map(|p| format!("{:04x}", p)).unwrap_or_default(),
+            csv_field(entry.serial_number.as_deref().unwrap_or("")),
+            csv_field(entry.port_path.as_deref().unwrap_or("")),
+            csv_field(&entry.tags.join(";")),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::{UsbDescriptorSummary, UsbDeviceInfo};
+    use crate::version::BcdVersion;
+    use chrono::TimeZone;
+
+    fn fixture_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join(test)
+            .join(format!("{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn device(vid: u16, serial: &str) -> UsbDeviceInfo {
+        UsbDeviceInfo {
+            bus_number: 1,
+            address: 4,
+            vendor_id: vid,
+            product_id: 0x5583,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0210),
+                device_version: BcdVersion(0x0100),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: None,
+            product: Some("Ultra Fit".to_string()),
+            serial_number: Some(serial.to_string()),
+            port_path: Some("1-4".to_string()),
+            speed: None,
+            tags: vec!["class:storage".to_string()],
+            active_config: None,
+            usb_ids: None,
+            container_id: None,
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 8, 18, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_time_range_and_filter_query() {
+        let dir = fixture_dir("journal_query");
+        let mut journal = EventJournal::open(&dir).unwrap();
+
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "A")), at(13, 0))
+            .unwrap();
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x18d1, "B")), at(14, 30))
+            .unwrap();
+        journal
+            .record_at(
+                &DeviceEvent::Disconnected(DeviceIdentity::of(&device(0x0781, "A"))),
+                at(15, 0),
+            )
+            .unwrap();
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "C")), at(17, 0))
+            .unwrap();
+
+        // "Between 2pm and 4pm"
+        let result = journal
+            .query(at(14, 0)..at(16, 0), &DeviceFilter::any())
+            .unwrap();
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.corrupted_lines, 0);
+        assert_eq!(result.entries[0].kind, TransitionKind::Connected);
+        assert_eq!(result.entries[1].kind, TransitionKind::Disconnected);
+
+        // Filter on classification tag, full day.
+        let result = journal
+            .query(
+                at(0, 0)..at(23, 59),
+                &DeviceFilter::any().with_tag("class:storage"),
+            )
+            .unwrap();
+        // The Disconnected entry carries no tags, so three Connected hits.
+        assert_eq!(result.entries.len(), 3);
+
+        let result = journal
+            .query(at(0, 0)..at(23, 59), &DeviceFilter::any().with_vendor_id(0x18d1))
+            .unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].serial_number.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn test_size_rotation_keeps_old_entries_queryable() {
+        let dir = fixture_dir("journal_size_rotation");
+        let mut journal =
+            EventJournal::open_with_limits(&dir, 256, Duration::from_secs(3600)).unwrap();
+
+        for i in 0..20 {
+            journal
+                .record_at(
+                    &DeviceEvent::Connected(device(0x0781, &format!("S{}", i))),
+                    at(10, i),
+                )
+                .unwrap();
+        }
+
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().filter_map(|e| e.ok()).collect();
+        assert!(
+            files.len() > 1,
+            "expected rotation to produce multiple files, got {}",
+            files.len()
+        );
+
+        // Every entry is still visible across the rotation boundary.
+        let result = journal
+            .query(at(0, 0)..at(23, 0), &DeviceFilter::any())
+            .unwrap();
+        assert_eq!(result.entries.len(), 20);
+        let serials: Vec<_> = result
+            .entries
+            .iter()
+            .map(|e| e.serial_number.clone().unwrap())
+            .collect();
+        assert_eq!(serials[0], "S0");
+        assert_eq!(serials[19], "S19");
+    }
+
+    #[test]
+    fn test_age_rotation() {
+        let dir = fixture_dir("journal_age_rotation");
+        let mut journal =
+            EventJournal::open_with_limits(&dir, u64::MAX, Duration::from_secs(3600)).unwrap();
+
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "A")), at(10, 0))
+            .unwrap();
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "B")), at(10, 30))
+            .unwrap();
+        // Crosses the one-hour age limit: rotates before writing.
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "C")), at(11, 30))
+            .unwrap();
+
+        let active = fs::read_to_string(dir.join(ACTIVE_FILE)).unwrap();
+        assert_eq!(active.lines().count(), 1, "active file should hold only C");
+        let result = journal
+            .query(at(0, 0)..at(23, 0), &DeviceFilter::any())
+            .unwrap();
+        assert_eq!(result.entries.len(), 3);
+    }
+
+    #[test]
+    fn test_corrupted_tail_skipped_and_counted() {
+        let dir = fixture_dir("journal_corrupted");
+        let mut journal = EventJournal::open(&dir).unwrap();
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "A")), at(10, 0))
+            .unwrap();
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "B")), at(10, 5))
+            .unwrap();
+
+        // Simulate a crash mid-write: truncated JSON on the last line.
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(ACTIVE_FILE))
+            .unwrap();
+        write!(file, "{{\"timestamp\":\"2026-08-18T10:0").unwrap();
+        drop(file);
+
+        let result = journal
+            .query(at(0, 0)..at(23, 0), &DeviceFilter::any())
+            .unwrap();
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.corrupted_lines, 1);
+
+        // The journal stays appendable after the torn write.
+        journal
+            .record_at(&DeviceEvent::Connected(device(0x0781, "C")), at(10, 10))
+            .unwrap();
+        let result = journal
+            .query(at(0, 0)..at(23, 0), &DeviceFilter::any())
+            .unwrap();
+        assert_eq!(result.entries.len(), 3);
+        assert_eq!(result.corrupted_lines, 1);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let entries = vec![JournalEntry {
+            timestamp: at(14, 30),
+            kind: TransitionKind::Connected,
+            identity: DeviceIdentity("0781:5583/serial=A,B".to_string()),
+            vendor_id: Some(0x0781),
+            product_id: Some(0x5583),
+            serial_number: Some("A,B".to_string()),
+            port_path: Some("1-4".to_string()),
+            tags: vec!["class:storage".to_string(), "trusted".to_string()],
+        }];
+
+        let mut out = Vec::new();
+        export_csv(&entries, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "timestamp,kind,identity,vendor_id,product_id,serial_number,port_path,tags"
+        );
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("2026-08-18T14:30:00+00:00,connected,"));
+        // Comma-bearing fields are quoted.
+        assert!(row.contains("\"0781:5583/serial=A,B\""));
+        assert!(row.contains("\"A,B\""));
+        assert!(row.ends_with("1-4,class:storage;trusted"));
+    }
+}
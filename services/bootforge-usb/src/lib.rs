@@ -0,0 +1,123 @@
+// BootForge USB - Device enumeration and identification
+// COMPLIANCE-FIRST: Read-only device identification by default
+//
+// No-panic guarantee: public enumeration APIs return Err - never unwind -
+// for permission failures, absent devices, and malformed descriptor
+// data, so the crate is safe to embed in long-running services. Enforced
+// by the panic-hook tests in enumeration.rs.
+
+pub mod analysis;
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+pub mod bos;
+pub mod canonical;
+pub mod claim;
+pub mod context;
+pub mod endpoints;
+pub mod enumeration;
+pub mod error;
+pub mod events;
+#[cfg(feature = "test-fixtures")]
+pub mod fixtures;
+#[cfg(target_os = "linux")]
+pub mod gadget;
+pub mod generation;
+pub mod health;
+pub mod journal;
+pub mod linux;
+pub mod macos;
+pub mod manager;
+pub mod open;
+#[cfg(feature = "picker")]
+pub mod picker;
+pub mod ports;
+pub mod protocols;
+pub mod reacquire;
+pub mod registry;
+pub mod report;
+pub mod reset;
+pub mod snapshot;
+pub mod storage_map;
+pub mod string_cache;
+pub mod strings;
+pub mod topology;
+pub mod transfer;
+pub mod typec;
+pub mod usb_ids;
+pub mod version;
+pub mod wait_for;
+pub mod watch;
+#[cfg(windows)]
+pub mod windows;
+
+pub use analysis::{
+    estimate_periodic_bandwidth, speed_bottlenecks, BandwidthEstimate, Bottleneck,
+    DeviceBottleneck,
+};
+#[cfg(feature = "tokio")]
+pub use asynchronous::{bridge_events, enumerate_libusb_async, enumerate_libusb_report_async};
+pub use bos::{
+    AlternateMode, BillboardCapability, BosCapability, BosDescriptor, ContainerId,
+    SuperSpeedCapability, Usb2Extension,
+};
+pub use canonical::{CanonicalId, IdentityStrategy};
+pub use claim::{ClaimedInterface, InterfaceHost};
+pub use context::{ContextOptions, SharedContext};
+pub use endpoints::{find_bulk_pair, find_endpoints, find_interrupt_in, parse_config_descriptor};
+pub use enumeration::{
+    dedup_device_info, enumerate_filtered, enumerate_filtered_where, enumerate_libusb,
+    enumerate_libusb_report, enumerate_libusb_report_in, enumerate_libusb_report_with, get_device,
+    merge_device_info, probe_libusb, ConfigInfo, DeviceFilter, EnumerationOptions,
+    EnumerationReport, FallbackEnumerator, FilteredCounts, InterfaceInfo, InterfaceSummary,
+    SkippedDevice, UsbDescriptorSummary, UsbDeviceInfo, UsbDeviceInfoBuilder, UsbDeviceRecord,
+    UsbDeviceRecordBuilder,
+};
+pub use error::UsbError;
+pub use events::{DeviceEvent, DeviceIdentity};
+pub use generation::{bus_generation, generation_of_set};
+pub use health::{assess_power, HealthThresholds, LinkHealth, LinkHealthTracker};
+pub use journal::{EventJournal, JournalEntry, QueryResult};
+pub use manager::{
+    DeviceSource, HandlingLevel, InterestToken, ManagedRecord, ManagerState, RecoveredEvent,
+    SharedDeviceManager,
+};
+pub use open::OpenOptions;
+#[cfg(target_os = "linux")]
+pub use ports::power_cycle;
+pub use ports::{
+    assemble_topology, build_topology, enumerate_hubs, enumerate_hubs_in, power_cycle_in,
+    read_hub_descriptor, AttachedDevice, HubDescriptor, PortPath, PowerSwitching, TopologyHub,
+    UsbHub, UsbPort, UsbTopology, DEFAULT_HUB_PORTS, POWER_CYCLE_OFF_TIME,
+};
+pub use reacquire::{DeviceReopener, ReacquirePolicy, ReacquiringHandle};
+pub use registry::{DeviceRegistry, PhantomDeviceTracker};
+pub use report::{capture_snapshot, UsbSnapshot, REPORT_SCHEMA_VERSION};
+pub use reset::{reset_and_reopen, reset_and_reopen_with, reset_device, wait_for_return};
+pub use snapshot::{snapshot_schema, Snapshot, SNAPSHOT_SCHEMA_VERSION};
+pub use storage_map::{block_devices, BlockDeviceInfo};
+pub use string_cache::{CacheKey, CachedEnumerator, CachedStrings, StringDescriptorCache};
+pub use strings::{
+    decode_string_descriptor, get_string_descriptor, get_string_descriptor_limited, DecodedString,
+};
+pub use topology::{EndpointCompanion, EndpointInfo, EndpointKind, Speed, TopologyNode};
+pub use transfer::{
+    alloc_streams, free_streams, BulkTransfer, CancellationToken, ControlTransfer, DescriptorLimits,
+    InterruptPoller, InterruptTransfer, ProgressSink, ProgressTracker, RetryPolicy,
+    ThrottledSink, TransferProgress, TransferStats, UsbTransport, OVERSIZED_DESCRIPTOR_TAG,
+};
+#[cfg(feature = "test-fixtures")]
+pub use transfer::mock::{ControlRequest, MockTransport};
+pub use usb_ids::{class_code_name, resolve_usb_ids, UsbIds, UsbIdsDb};
+pub use version::BcdVersion;
+pub use wait_for::{
+    wait_for_protocol, wait_for_protocol_on, wait_for_removal, wait_for_removal_on,
+};
+pub use watch::{
+    default_enrichment, info_from_interface_path, parse_interface_path, DebouncedWatcher,
+    DeviceWatcher, EnrichedWatcher, Enricher, LibusbHotplugWatcher, PollingWatcher, RecordedEvent,
+    RecordingWatcher, ReplayWatcher, ReplugPolicy, SnapshotSource,
+};
+#[cfg(target_os = "macos")]
+pub use watch::MacOSDeviceWatcher;
+#[cfg(windows)]
+pub use watch::WindowsDeviceWatcher;
@@ -0,0 +1,814 @@
+// BootForge USB - Linux platform mapping
+// Renders a device record as the property environment udev presents to
+// rules and parses one back (shared key constants so the mapping cannot
+// drift apart), plus sysfs enrichment: driver bindings, the authorized
+// flag, and the devnode. Kept un-gated - the udev half is pure string
+// work and the sysfs half reads through an injectable root - so test
+// suites can run on any host.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::enumeration::{UsbDescriptorSummary, UsbDeviceInfo, UsbDeviceRecord};
+use crate::error::UsbError;
+use crate::topology::Speed;
+use crate::version::BcdVersion;
+
+// Property keys, as emitted by `udevadm info` for a usb_device.
+pub const KEY_BUSNUM: &str = "BUSNUM";
+pub const KEY_DEVNUM: &str = "DEVNUM";
+pub const KEY_DEVPATH: &str = "DEVPATH";
+pub const KEY_PRODUCT: &str = "PRODUCT";
+pub const KEY_ID_VENDOR_ID: &str = "ID_VENDOR_ID";
+pub const KEY_ID_MODEL_ID: &str = "ID_MODEL_ID";
+pub const KEY_ID_REVISION: &str = "ID_REVISION";
+pub const KEY_ID_VENDOR: &str = "ID_VENDOR";
+pub const KEY_ID_MODEL: &str = "ID_MODEL";
+pub const KEY_ID_SERIAL: &str = "ID_SERIAL";
+pub const KEY_ID_SERIAL_SHORT: &str = "ID_SERIAL_SHORT";
+
+/**
+ * Encode a descriptor string the way udev's usb_id does for ID_VENDOR
+ * and ID_MODEL: whitespace runs become a single underscore and anything
+ * outside udev's safe set is replaced with an underscore. Lossy by
+ * design, exactly like the original.
+ */
+pub fn encode_udev_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_whitespace = false;
+    for c in s.trim().chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                out.push('_');
+            }
+            in_whitespace = true;
+        } else {
+            in_whitespace = false;
+            if c.is_ascii_alphanumeric() || "#+-.:=@_".contains(c) {
+                out.push(c);
+            } else {
+                out.push('_');
+            }
+        }
+    }
+    out
+}
+
+/**
+ * Render `record` as the udev property map a rule would see, following
+ * udev's naming and value conventions (zero-padded BUSNUM/DEVNUM, the
+ * unpadded PRODUCT triple, the ID_SERIAL vendor_model_serial
+ * composition with hex IDs standing in for missing strings).
+ */
+pub fn to_udev_properties(record: &UsbDeviceRecord) -> BTreeMap<String, String> {
+    let mut props = BTreeMap::new();
+
+    props.insert(KEY_BUSNUM.to_string(), format!("{:03}", record.bus_number));
+    props.insert(
+        KEY_DEVNUM.to_string(),
+        format!("{:03}", record.device_number),
+    );
+    props.insert(
+        KEY_DEVPATH.to_string(),
+        record
+            .sysfs_path
+            .strip_prefix("/sys")
+            .unwrap_or(&record.sysfs_path)
+            .to_string(),
+    );
+    props.insert(
+        KEY_PRODUCT.to_string(),
+        format!(
+            "{:x}/{:x}/{:x}",
+            record.vendor_id, record.product_id, record.descriptor.device_version.0
+        ),
+    );
+    props.insert(
+        KEY_ID_VENDOR_ID.to_string(),
+        format!("{:04x}", record.vendor_id),
+    );
+    props.insert(
+        KEY_ID_MODEL_ID.to_string(),
+        format!("{:04x}", record.product_id),
+    );
+    props.insert(
+        KEY_ID_REVISION.to_string(),
+        format!("{:04x}", record.descriptor.device_version.0),
+    );
+
+    let vendor = record
+        .manufacturer
+        .as_deref()
+        .map(encode_udev_string)
+        .unwrap_or_else(|| format!("{:04x}", record.vendor_id));
+    let model = record
+        .product
+        .as_deref()
+        .map(encode_udev_string)
+        .unwrap_or_else(|| format!("{:04x}", record.product_id));
+
+    let mut serial = format!("{}_{}", vendor, model);
+    if let Some(short) = &record.serial_number {
+        serial.push('_');
+        serial.push_str(short);
+        props.insert(KEY_ID_SERIAL_SHORT.to_string(), short.clone());
+    }
+    props.insert(KEY_ID_VENDOR.to_string(), vendor);
+    props.insert(KEY_ID_MODEL.to_string(), model);
+    props.insert(KEY_ID_SERIAL.to_string(), serial);
+
+    props
+}
+
+fn require<'a>(
+    props: &'a BTreeMap<String, String>,
+    key: &str,
+) -> Result<&'a str, UsbError> {
+    props
+        .get(key)
+        .map(String::as_str)
+        .ok_or_else(|| UsbError::Parse(format!("udev properties missing {}", key)))
+}
+
+fn parse_hex_u16(key: &str, value: &str) -> Result<u16, UsbError> {
+    u16::from_str_radix(value, 16)
+        .map_err(|_| UsbError::Parse(format!("bad hex value for {}: {:?}", key, value)))
+}
+
+/**
+ * Rebuild a record from a udev property map, as the watcher does for
+ * events it did not enumerate itself.
+ *
+ * The reconstruction is partial: udev does not carry bcdUSB, the class
+ * triple, or endpoint-zero sizing, so those descriptor fields come back
+ * zeroed, and ID_VENDOR/ID_MODEL are udev-encoded (lossy) forms of the
+ * original strings.
+ */
+pub fn from_udev_properties(
+    props: &BTreeMap<String, String>,
+) -> Result<UsbDeviceRecord, UsbError> {
+    let bus_number: u8 = require(props, KEY_BUSNUM)?
+        .parse()
+        .map_err(|_| UsbError::Parse("bad BUSNUM".to_string()))?;
+    let device_number: u8 = require(props, KEY_DEVNUM)?
+        .parse()
+        .map_err(|_| UsbError::Parse("bad DEVNUM".to_string()))?;
+    let vendor_id = parse_hex_u16(KEY_ID_VENDOR_ID, require(props, KEY_ID_VENDOR_ID)?)?;
+    let product_id = parse_hex_u16(KEY_ID_MODEL_ID, require(props, KEY_ID_MODEL_ID)?)?;
+    let device_version = props
+        .get(KEY_ID_REVISION)
+        .map(|v| parse_hex_u16(KEY_ID_REVISION, v))
+        .transpose()?
+        .unwrap_or(0);
+
+    Ok(UsbDeviceRecord {
+        bus_number,
+        device_number,
+        vendor_id,
+        product_id,
+        descriptor: UsbDescriptorSummary {
+            usb_version: BcdVersion(0),
+            device_version: BcdVersion(device_version),
+            device_class: 0,
+            device_subclass: 0,
+            device_protocol: 0,
+            max_packet_size_0: 0,
+            num_configurations: 0,
+        },
+        manufacturer: props.get(KEY_ID_VENDOR).cloned(),
+        product: props.get(KEY_ID_MODEL).cloned(),
+        serial_number: props.get(KEY_ID_SERIAL_SHORT).cloned(),
+        sysfs_path: props
+            .get(KEY_DEVPATH)
+            .map(|p| format!("/sys{}", p))
+            .unwrap_or_default(),
+        // udev events carry no speed property worth trusting.
+        speed: None,
+        interfaces: Vec::new(),
+    })
+}
+
+/**
+ * How the kernel currently holds a device, from the driver symlinks of
+ * its interface directories.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DriverStatus {
+    /// No interface directories were exported; nothing to read.
+    #[default]
+    Unknown,
+    /// Interfaces exist but none has a driver bound.
+    Missing,
+    /// Every bound interface uses this one driver.
+    Bound(String),
+    /// Interfaces are split across these drivers, e.g. a composite
+    /// device half-claimed by usb-storage.
+    Multiple(Vec<String>),
+    /// The host is refusing to use the device - authorization withheld
+    /// or the devnode unopenable - so any driver state is moot until
+    /// that changes. The reason says which.
+    Blocked { reason: String },
+}
+
+/**
+ * Linux-side facts about an enumerated device that libusb does not
+ * surface: driver bindings, the sysfs authorized flag, and the devnode
+ * a transfer would open.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PlatformHint {
+    pub driver: DriverStatus,
+    /// The sysfs `authorized` attribute; None when not exported.
+    pub authorized: Option<bool>,
+    /// `/dev/bus/usb/BBB/DDD`, when the node exists.
+    pub devnode: Option<PathBuf>,
+    /// The sysfs directory name, e.g. "2-1.4".
+    pub port_path: Option<String>,
+    /// The sysfs device directory the other fields were read from, so
+    /// follow-up attribute access does not re-derive the path.
+    pub sysfs_path: Option<PathBuf>,
+    /// Negotiated link speed per the sysfs `speed` attribute - the
+    /// kernel's view, for cross-checking what libusb reported.
+    pub speed: Option<Speed>,
+}
+
+/**
+ * Reads platform hints from sysfs and the devfs tree. Both roots are
+ * injectable so tests can point it at fixture trees, mirroring
+ * `FallbackEnumerator`.
+ */
+pub struct LinuxEnricher {
+    sysfs_root: PathBuf,
+    dev_root: PathBuf,
+}
+
+impl Default for LinuxEnricher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinuxEnricher {
+    pub fn new() -> Self {
+        LinuxEnricher {
+            sysfs_root: PathBuf::from("/sys/bus/usb/devices"),
+            dev_root: PathBuf::from("/dev/bus/usb"),
+        }
+    }
+
+    /// Use alternate sysfs and devfs roots (fixture trees in tests).
+    pub fn with_roots<S: Into<PathBuf>, D: Into<PathBuf>>(sysfs: S, dev: D) -> Self {
+        LinuxEnricher {
+            sysfs_root: sysfs.into(),
+            dev_root: dev.into(),
+        }
+    }
+
+    /**
+     * The platform hint for the device at this bus and device number,
+     * located by matching the busnum/devnum attributes rather than
+     * trusting any path the caller derived.
+     */
+    pub fn enrich(&self, bus_number: u8, device_number: u8) -> Result<PlatformHint, UsbError> {
+        let device_dir = self.find_device_dir(bus_number, device_number)?;
+        let mut hint = PlatformHint {
+            port_path: device_dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned()),
+            ..PlatformHint::default()
+        };
+
+        hint.authorized = read_attr(&device_dir, "authorized").map(|v| v != "0");
+        hint.driver = interface_driver_status(&device_dir);
+        hint.speed = read_attr(&device_dir, "speed").and_then(|v| Speed::from_sysfs(&v));
+
+        let devnode = self
+            .dev_root
+            .join(format!("{:03}", bus_number))
+            .join(format!("{:03}", device_number));
+        match fs::OpenOptions::new().read(true).write(true).open(&devnode) {
+            Ok(_) => hint.devnode = Some(devnode),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(_) => {
+                // Node exists but we cannot open it the way a transfer
+                // would; report that over whatever the driver links say.
+                hint.devnode = Some(devnode);
+                hint.driver = DriverStatus::Blocked {
+                    reason: "devnode not openable".to_string(),
+                };
+            }
+        }
+
+        // Withheld authorization (usbguard and friends) outranks both:
+        // the kernel will not bind drivers or service the node while
+        // authorized is 0, whatever else sysfs says.
+        if hint.authorized == Some(false) {
+            hint.driver = DriverStatus::Blocked {
+                reason: "usb authorization disabled".to_string(),
+            };
+        }
+
+        hint.sysfs_path = Some(device_dir);
+        Ok(hint)
+    }
+
+    /**
+     * Write the sysfs `authorized` attribute so the kernel accepts
+     * (`true`) or refuses (`false`) the device. Needs root on stock
+     * systems; a refusal comes back as `PermissionDenied` so callers
+     * can say "fix your privileges" rather than retrying.
+     */
+    pub fn set_authorized(&self, info: &UsbDeviceInfo, allow: bool) -> Result<(), UsbError> {
+        let hint = self.enrich(info.bus_number, info.address)?;
+        let device_dir = hint.sysfs_path.ok_or_else(|| {
+            UsbError::NotFound(format!(
+                "no sysfs path for usb:{:04x}:{:04x}",
+                info.vendor_id, info.product_id
+            ))
+        })?;
+        fs::write(
+            device_dir.join("authorized"),
+            if allow { "1" } else { "0" },
+        )
+        .map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => UsbError::PermissionDenied(format!(
+                "writing {} requires elevated privileges",
+                device_dir.join("authorized").display()
+            )),
+            _ => UsbError::Io(e),
+        })
+    }
+
+    /**
+     * The per-interface `authorized` attributes, keyed by interface
+     * directory name (e.g. "2-1.4:1.0"). Interfaces that do not export
+     * the attribute are omitted; kernels without per-interface
+     * authorization return an empty map.
+     */
+    pub fn get_interface_authorization(
+        &self,
+        info: &UsbDeviceInfo,
+    ) -> Result<BTreeMap<String, bool>, UsbError> {
+        let hint = self.enrich(info.bus_number, info.address)?;
+        let device_dir = hint.sysfs_path.ok_or_else(|| {
+            UsbError::NotFound(format!(
+                "no sysfs path for usb:{:04x}:{:04x}",
+                info.vendor_id, info.product_id
+            ))
+        })?;
+
+        let mut authorization = BTreeMap::new();
+        for entry in fs::read_dir(&device_dir)?.flatten() {
+            let path = entry.path();
+            if !path.is_dir()
+                || !path.file_name().is_some_and(|n| n.to_string_lossy().contains(':'))
+            {
+                continue;
+            }
+            if let Some(value) = read_attr(&path, "authorized") {
+                authorization.insert(
+                    path.file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    value != "0",
+                );
+            }
+        }
+        Ok(authorization)
+    }
+
+    fn find_device_dir(&self, bus_number: u8, device_number: u8) -> Result<PathBuf, UsbError> {
+        let entries = fs::read_dir(&self.sysfs_root)?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // Interface directories carry ':'; devices never do.
+            if path.file_name().is_some_and(|n| n.to_string_lossy().contains(':')) {
+                continue;
+            }
+            let busnum = read_attr(&path, "busnum").and_then(|v| v.parse::<u8>().ok());
+            let devnum = read_attr(&path, "devnum").and_then(|v| v.parse::<u8>().ok());
+            if busnum == Some(bus_number) && devnum == Some(device_number) {
+                return Ok(path);
+            }
+        }
+        Err(UsbError::NotFound(format!(
+            "no sysfs device with busnum {} devnum {}",
+            bus_number, device_number
+        )))
+    }
+}
+
+/**
+ * Enrich one enumerated device from the standard sysfs and devfs roots.
+ */
+pub fn enrich_linux(info: &UsbDeviceInfo) -> Result<PlatformHint, UsbError> {
+    LinuxEnricher::new().enrich(info.bus_number, info.address)
+}
+
+/**
+ * Authorize or deauthorize one device through the standard sysfs root.
+ */
+pub fn set_authorized(info: &UsbDeviceInfo, allow: bool) -> Result<(), UsbError> {
+    LinuxEnricher::new().set_authorized(info, allow)
+}
+
+/**
+ * Per-interface authorization for one device through the standard
+ * sysfs root.
+ */
+pub fn get_interface_authorization(
+    info: &UsbDeviceInfo,
+) -> Result<BTreeMap<String, bool>, UsbError> {
+    LinuxEnricher::new().get_interface_authorization(info)
+}
+
+/// One sysfs attribute, trimmed; None when absent or unreadable.
+fn read_attr(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|v| v.trim().to_string())
+}
+
+/// Walk the device's nested interface directories and classify their
+/// driver symlinks.
+fn interface_driver_status(device_dir: &Path) -> DriverStatus {
+    let mut interfaces = 0usize;
+    let mut drivers: Vec<String> = Vec::new();
+    let Ok(entries) = fs::read_dir(device_dir) else {
+        return DriverStatus::Unknown;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir()
+            || !path.file_name().is_some_and(|n| n.to_string_lossy().contains(':'))
+        {
+            continue;
+        }
+        interfaces += 1;
+        if let Ok(target) = fs::read_link(path.join("driver")) {
+            if let Some(name) = target.file_name() {
+                let name = name.to_string_lossy().into_owned();
+                if !drivers.contains(&name) {
+                    drivers.push(name);
+                }
+            }
+        }
+    }
+    match (interfaces, drivers.len()) {
+        (0, _) => DriverStatus::Unknown,
+        (_, 0) => DriverStatus::Missing,
+        (_, 1) => DriverStatus::Bound(drivers.pop().expect("one driver")),
+        _ => {
+            drivers.sort();
+            DriverStatus::Multiple(drivers)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel() -> UsbDeviceRecord {
+        UsbDeviceRecord::builder(0x18d1, 0x4ee7)
+            .bus_number(1)
+            .device_number(9)
+            .usb_version(BcdVersion(0x0210))
+            .device_version(BcdVersion(0x0440))
+            .max_packet_size_0(64)
+            .num_configurations(1)
+            .manufacturer("Google Inc.")
+            .product("Pixel 7")
+            .serial("29061FDH300EXZ")
+            .sysfs_path("/sys/devices/pci0000:00/0000:00:14.0/usb1/1-4")
+            .build()
+    }
+
+    #[test]
+    fn test_to_udev_properties_conventions() {
+        // Shape cross-checked against `udevadm info` for the same phone.
+        let props = to_udev_properties(&pixel());
+        assert_eq!(props["BUSNUM"], "001");
+        assert_eq!(props["DEVNUM"], "009");
+        assert_eq!(props["DEVPATH"], "/devices/pci0000:00/0000:00:14.0/usb1/1-4");
+        assert_eq!(props["PRODUCT"], "18d1/4ee7/440");
+        assert_eq!(props["ID_VENDOR_ID"], "18d1");
+        assert_eq!(props["ID_MODEL_ID"], "4ee7");
+        assert_eq!(props["ID_REVISION"], "0440");
+        assert_eq!(props["ID_VENDOR"], "Google_Inc.");
+        assert_eq!(props["ID_MODEL"], "Pixel_7");
+        assert_eq!(props["ID_SERIAL"], "Google_Inc._Pixel_7_29061FDH300EXZ");
+        assert_eq!(props["ID_SERIAL_SHORT"], "29061FDH300EXZ");
+    }
+
+    #[test]
+    fn test_missing_strings_fall_back_to_hex_ids() {
+        let mut record = pixel();
+        record.manufacturer = None;
+        record.product = None;
+        record.serial_number = None;
+
+        let props = to_udev_properties(&record);
+        assert_eq!(props["ID_VENDOR"], "18d1");
+        assert_eq!(props["ID_MODEL"], "4ee7");
+        assert_eq!(props["ID_SERIAL"], "18d1_4ee7");
+        assert!(!props.contains_key("ID_SERIAL_SHORT"));
+    }
+
+    #[test]
+    fn test_encode_udev_string() {
+        assert_eq!(encode_udev_string("Google Inc."), "Google_Inc.");
+        assert_eq!(encode_udev_string("  SanDisk   Ultra  "), "SanDisk_Ultra");
+        assert_eq!(encode_udev_string("Café/USB"), "Caf__USB");
+        assert_eq!(encode_udev_string("a#b+c-d.e:f=g@h_i"), "a#b+c-d.e:f=g@h_i");
+    }
+
+    #[test]
+    fn test_round_trip_through_properties() {
+        let record = pixel();
+        let rebuilt = from_udev_properties(&to_udev_properties(&record)).unwrap();
+
+        assert_eq!(rebuilt.bus_number, record.bus_number);
+        assert_eq!(rebuilt.device_number, record.device_number);
+        assert_eq!(rebuilt.vendor_id, record.vendor_id);
+        assert_eq!(rebuilt.product_id, record.product_id);
+        assert_eq!(rebuilt.descriptor.device_version, record.descriptor.device_version);
+        assert_eq!(rebuilt.serial_number, record.serial_number);
+        assert_eq!(rebuilt.sysfs_path, record.sysfs_path);
+        // String fields come back in their lossy udev encoding.
+        assert_eq!(rebuilt.manufacturer.as_deref(), Some("Google_Inc."));
+        // And a second render must be byte-identical.
+        assert_eq!(to_udev_properties(&rebuilt)[KEY_ID_SERIAL], "Google_Inc._Pixel_7_29061FDH300EXZ");
+    }
+
+    #[test]
+    fn test_from_properties_dump() {
+        // Subset of a `udevadm info` dump for a SanDisk stick.
+        let props: BTreeMap<String, String> = [
+            ("BUSNUM", "002"),
+            ("DEVNUM", "014"),
+            ("DEVPATH", "/devices/pci0000:00/0000:00:14.0/usb2/2-1"),
+            ("ID_VENDOR_ID", "0781"),
+            ("ID_MODEL_ID", "5583"),
+            ("ID_REVISION", "0100"),
+            ("ID_VENDOR", "SanDisk"),
+            ("ID_MODEL", "Ultra_Fit"),
+            ("ID_SERIAL", "SanDisk_Ultra_Fit_4C531001331122111213"),
+            ("ID_SERIAL_SHORT", "4C531001331122111213"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let record = from_udev_properties(&props).unwrap();
+        assert_eq!(record.bus_number, 2);
+        assert_eq!(record.device_number, 14);
+        assert_eq!(record.vendor_id, 0x0781);
+        assert_eq!(record.serial_number.as_deref(), Some("4C531001331122111213"));
+        assert_eq!(record.sysfs_path, "/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1");
+
+        // Shared mapping table: rendering the rebuilt record reproduces
+        // every property the dump carried.
+        let rendered = to_udev_properties(&record);
+        for (key, value) in &props {
+            assert_eq!(rendered.get(key), Some(value), "mismatch for {}", key);
+        }
+    }
+
+    fn fixture_root(test: &str) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join(test)
+            .join(format!("{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    /// A sysfs device directory with busnum/devnum and the given extra
+    /// attributes; returns its path so interfaces can be nested.
+    fn write_sysfs_device(
+        sysfs: &Path,
+        name: &str,
+        bus: u8,
+        dev: u8,
+        attrs: &[(&str, &str)],
+    ) -> PathBuf {
+        let dir = sysfs.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("busnum"), format!("{}\n", bus)).unwrap();
+        fs::write(dir.join("devnum"), format!("{}\n", dev)).unwrap();
+        for (key, value) in attrs {
+            fs::write(dir.join(key), format!("{}\n", value)).unwrap();
+        }
+        dir
+    }
+
+    /// An interface directory under `device_dir`, optionally bound to a
+    /// driver via the symlink the kernel would create.
+    fn write_interface(device_dir: &Path, name: &str, driver: Option<&str>) {
+        let dir = device_dir.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        if let Some(driver) = driver {
+            let target = device_dir.join("drivers").join(driver);
+            fs::create_dir_all(&target).unwrap();
+            std::os::unix::fs::symlink(target, dir.join("driver")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_enrich_reads_driver_authorized_and_devnode() {
+        let root = fixture_root("enrich_full");
+        let sysfs = root.join("sys");
+        let dev = root.join("dev");
+        fs::create_dir_all(&sysfs).unwrap();
+
+        let device =
+            write_sysfs_device(&sysfs, "2-1.4", 2, 7, &[("authorized", "1"), ("speed", "480")]);
+        write_interface(&device, "2-1.4:1.0", Some("cdc_acm"));
+        write_interface(&device, "2-1.4:1.1", Some("cdc_acm"));
+        fs::create_dir_all(dev.join("002")).unwrap();
+        fs::write(dev.join("002").join("007"), b"").unwrap();
+
+        let hint = LinuxEnricher::with_roots(&sysfs, &dev).enrich(2, 7).unwrap();
+        assert_eq!(hint.port_path.as_deref(), Some("2-1.4"));
+        assert_eq!(hint.authorized, Some(true));
+        assert_eq!(hint.driver, DriverStatus::Bound("cdc_acm".to_string()));
+        assert_eq!(hint.devnode, Some(dev.join("002").join("007")));
+        assert_eq!(hint.speed, Some(Speed::High));
+    }
+
+    #[test]
+    fn test_enrich_driver_status_variants() {
+        let root = fixture_root("enrich_variants");
+        let sysfs = root.join("sys");
+        let dev = root.join("dev");
+        fs::create_dir_all(&sysfs).unwrap();
+
+        // Interfaces with no driver symlinks at all.
+        let bare = write_sysfs_device(&sysfs, "1-2", 1, 3, &[("authorized", "1")]);
+        write_interface(&bare, "1-2:1.0", None);
+        // Composite device split across two drivers.
+        let split = write_sysfs_device(&sysfs, "1-3", 1, 4, &[]);
+        write_interface(&split, "1-3:1.0", Some("usb-storage"));
+        write_interface(&split, "1-3:1.1", Some("usbfs"));
+        // No interface directories exported.
+        write_sysfs_device(&sysfs, "1-4", 1, 5, &[]);
+
+        let enricher = LinuxEnricher::with_roots(&sysfs, &dev);
+        let bare_hint = enricher.enrich(1, 3).unwrap();
+        assert_eq!(bare_hint.driver, DriverStatus::Missing);
+        assert_eq!(bare_hint.authorized, Some(true));
+        // No devnode was created for any of these.
+        assert_eq!(bare_hint.devnode, None);
+
+        assert_eq!(
+            enricher.enrich(1, 4).unwrap().driver,
+            DriverStatus::Multiple(vec!["usb-storage".to_string(), "usbfs".to_string()])
+        );
+        let missing = enricher.enrich(1, 5).unwrap();
+        assert_eq!(missing.driver, DriverStatus::Unknown);
+        assert_eq!(missing.authorized, None);
+
+        assert!(matches!(
+            enricher.enrich(9, 9),
+            Err(UsbError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_enrich_reports_blocked_devnode() {
+        let root = fixture_root("enrich_blocked");
+        let sysfs = root.join("sys");
+        let dev = root.join("dev");
+        fs::create_dir_all(&sysfs).unwrap();
+
+        let device = write_sysfs_device(&sysfs, "3-1", 3, 2, &[]);
+        write_interface(&device, "3-1:1.0", Some("usbfs"));
+        // A node that exists but cannot be opened read-write the way a
+        // transfer would open it.
+        fs::create_dir_all(dev.join("003").join("002")).unwrap();
+
+        let hint = LinuxEnricher::with_roots(&sysfs, &dev).enrich(3, 2).unwrap();
+        assert_eq!(
+            hint.driver,
+            DriverStatus::Blocked {
+                reason: "devnode not openable".to_string()
+            }
+        );
+        assert_eq!(hint.devnode, Some(dev.join("003").join("002")));
+    }
+
+    /// The record `set_authorized` and friends locate a fixture device
+    /// by: only the bus position matters.
+    fn fixture_info(bus: u8, dev: u8) -> UsbDeviceInfo {
+        UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .bus_number(bus)
+            .address(dev)
+            .build()
+    }
+
+    #[test]
+    fn test_enrich_unauthorized_is_blocked() {
+        let root = fixture_root("enrich_unauthorized");
+        let sysfs = root.join("sys");
+        let dev = root.join("dev");
+        fs::create_dir_all(&sysfs).unwrap();
+
+        // usbguard-style refusal: device present, authorized withheld,
+        // a driver link left over from before the policy kicked in.
+        let device = write_sysfs_device(&sysfs, "2-3", 2, 5, &[("authorized", "0")]);
+        write_interface(&device, "2-3:1.0", Some("usb-storage"));
+
+        let hint = LinuxEnricher::with_roots(&sysfs, &dev).enrich(2, 5).unwrap();
+        assert_eq!(hint.authorized, Some(false));
+        assert_eq!(
+            hint.driver,
+            DriverStatus::Blocked {
+                reason: "usb authorization disabled".to_string()
+            }
+        );
+        assert_eq!(hint.sysfs_path, Some(device));
+    }
+
+    #[test]
+    fn test_enrich_misses_are_not_found_and_skip_interface_dirs() {
+        let root = fixture_root("enrich_miss");
+        let sysfs = root.join("sys");
+        let dev = root.join("dev");
+        fs::create_dir_all(&sysfs).unwrap();
+
+        // A device on another slot, plus an interface directory whose
+        // busnum/devnum must never be consulted (':' names are not
+        // devices, whatever attributes they carry).
+        write_sysfs_device(&sysfs, "2-1", 2, 7, &[]);
+        write_sysfs_device(&sysfs, "2-1:1.0", 2, 9, &[]);
+
+        let enricher = LinuxEnricher::with_roots(&sysfs, &dev);
+        assert!(matches!(
+            enricher.enrich(2, 9),
+            Err(UsbError::NotFound(_))
+        ));
+        assert!(enricher.enrich(2, 7).is_ok());
+    }
+
+    #[test]
+    fn test_set_authorized_writes_through_the_hinted_path() {
+        let root = fixture_root("set_authorized");
+        let sysfs = root.join("sys");
+        let dev = root.join("dev");
+        fs::create_dir_all(&sysfs).unwrap();
+
+        let device = write_sysfs_device(&sysfs, "2-1", 2, 9, &[("authorized", "1")]);
+        let enricher = LinuxEnricher::with_roots(&sysfs, &dev);
+
+        enricher.set_authorized(&fixture_info(2, 9), false).unwrap();
+        assert_eq!(read_attr(&device, "authorized").as_deref(), Some("0"));
+
+        enricher.set_authorized(&fixture_info(2, 9), true).unwrap();
+        assert_eq!(read_attr(&device, "authorized").as_deref(), Some("1"));
+
+        assert!(matches!(
+            enricher.set_authorized(&fixture_info(9, 9), false),
+            Err(UsbError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_interface_authorization() {
+        let root = fixture_root("interface_authorization");
+        let sysfs = root.join("sys");
+        let dev = root.join("dev");
+        fs::create_dir_all(&sysfs).unwrap();
+
+        let device = write_sysfs_device(&sysfs, "4-2", 4, 6, &[("authorized", "1")]);
+        write_interface(&device, "4-2:1.0", Some("usbhid"));
+        write_interface(&device, "4-2:1.1", None);
+        fs::write(device.join("4-2:1.0").join("authorized"), "1\n").unwrap();
+        fs::write(device.join("4-2:1.1").join("authorized"), "0\n").unwrap();
+        // An interface that does not export the attribute is omitted.
+        write_interface(&device, "4-2:1.2", None);
+
+        let map = LinuxEnricher::with_roots(&sysfs, &dev)
+            .get_interface_authorization(&fixture_info(4, 6))
+            .unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("4-2:1.0"), Some(&true));
+        assert_eq!(map.get("4-2:1.1"), Some(&false));
+    }
+
+    #[test]
+    fn test_missing_required_key_is_an_error() {
+        let mut props = to_udev_properties(&pixel());
+        props.remove(KEY_ID_VENDOR_ID);
+        assert!(matches!(
+            from_udev_properties(&props),
+            Err(UsbError::Parse(_))
+        ));
+    }
+}
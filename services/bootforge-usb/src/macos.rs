@@ -0,0 +1,465 @@
+// BootForge USB - macOS platform mapping
+// Matches IORegistry USB device entries to enumerated devices and fills
+// in the facts libusb does not surface there: the locationID, the
+// IORegistry path, and the bound driver (kext or dext) name. The
+// locationID-to-port-path conversion is pure bit work and stays
+// un-gated so it is tested on every host; only the IOKit walk is
+// restricted to macOS.
+
+use serde::{Deserialize, Serialize};
+
+use crate::enumeration::UsbDeviceInfo;
+
+/// Driver binding state of an IORegistry entry, from its attached
+/// IOService clients.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DriverStatus {
+    #[default]
+    Unknown,
+    /// The entry has no client attached; nothing claimed the device.
+    Missing,
+    /// Claimed by this IOClass, e.g. "AppleUSBHostCompositeDevice".
+    Bound(String),
+}
+
+/**
+ * macOS-side facts about an enumerated device, filled in by
+ * `enrich_macos`.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MacPlatformHint {
+    /// The IOKit locationID, e.g. 0x14230000.
+    pub location_id: Option<u32>,
+    /// Full IOService-plane path of the matched entry.
+    pub ioregistry_path: Option<String>,
+    /// IOClass of the attached client, when any.
+    pub driver_name: Option<String>,
+    pub driver: DriverStatus,
+    /// Synthetic Linux-style port path derived from the locationID.
+    pub port_path: Option<String>,
+}
+
+/**
+ * One IOUSBDevice registry entry reduced to the fields the correlation
+ * cares about - plain data, so the matching logic tests without IOKit.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegistryEntry {
+    pub location_id: Option<u32>,
+    /// The "USB Address" property, when the entry exports it.
+    pub usb_address: Option<u8>,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial_number: Option<String>,
+    pub ioregistry_path: Option<String>,
+    /// IOClass of the attached client, when any.
+    pub driver_name: Option<String>,
+}
+
+/**
+ * The registry entry for an enumerated device, if any.
+ *
+ * Primary key: the locationID-derived bus plus USB address - rusb
+ * derives the macOS bus number from the locationID top byte, so the
+ * pair pins the entry - guarded by VID/PID agreement. A device that
+ * renumbered between the two walks falls back to VID/PID plus serial,
+ * accepted only when exactly one entry agrees; two units of the same
+ * model without serials stay unmatched rather than guessed at.
+ */
+pub fn match_registry_entry<'a>(
+    entries: &'a [RegistryEntry],
+    info: &UsbDeviceInfo,
+) -> Option<&'a RegistryEntry> {
+    if let Some(found) = entries.iter().find(|entry| {
+        entry
+            .location_id
+            .is_some_and(|id| (id >> 24) as u8 == info.bus_number)
+            && entry.usb_address == Some(info.address)
+            && entry.vendor_id == info.vendor_id
+            && entry.product_id == info.product_id
+    }) {
+        return Some(found);
+    }
+
+    let serial = info.serial_number.as_deref()?;
+    let mut candidates = entries.iter().filter(|entry| {
+        entry.vendor_id == info.vendor_id
+            && entry.product_id == info.product_id
+            && entry.serial_number.as_deref() == Some(serial)
+    });
+    let found = candidates.next()?;
+    candidates.next().is_none().then_some(found)
+}
+
+/// The platform hint a matched registry entry implies.
+pub fn hint_from_entry(entry: &RegistryEntry) -> MacPlatformHint {
+    MacPlatformHint {
+        location_id: entry.location_id,
+        ioregistry_path: entry.ioregistry_path.clone(),
+        driver: match &entry.driver_name {
+            Some(name) => DriverStatus::Bound(name.clone()),
+            None => DriverStatus::Missing,
+        },
+        driver_name: entry.driver_name.clone(),
+        port_path: entry.location_id.and_then(location_id_to_port_path),
+    }
+}
+
+/**
+ * Derive a Linux-style port path from an IOKit locationID.
+ *
+ * The top byte is the bus; the remaining nibbles are the port chain,
+ * most significant first, terminated by the first zero nibble. So
+ * 0x14230000 on bus 0x14 is ports 2 then 3: "20-2.3", comparable to
+ * the sysfs directory names Linux produces. Root devices with an empty
+ * chain have no port path, matching `enumeration::port_path`.
+ */
+pub fn location_id_to_port_path(location_id: u32) -> Option<String> {
+    let bus = location_id >> 24;
+    let mut chain = Vec::new();
+    let mut shift = 20i32;
+    while shift >= 0 {
+        let nibble = (location_id >> shift) & 0xf;
+        if nibble == 0 {
+            break;
+        }
+        chain.push(nibble.to_string());
+        shift -= 4;
+    }
+    if chain.is_empty() {
+        return None;
+    }
+    Some(format!("{}-{}", bus, chain.join(".")))
+}
+
+#[cfg(target_os = "macos")]
+mod ioreg {
+    // As with the watchers, the IOKit bindings are declared by hand:
+    // the surface is small and the crate otherwise has no
+    // Apple-framework dependency.
+
+    use std::ffi::{c_char, c_void, CString};
+
+    use crate::enumeration::UsbDeviceInfo;
+    use crate::error::UsbError;
+
+    use super::{MacPlatformHint, RegistryEntry};
+
+    type IoObject = u32;
+    type IoIterator = u32;
+    type KernReturn = i32;
+    type CfRef = *const c_void;
+    type CfMutableRef = *mut c_void;
+
+    const KERN_SUCCESS: KernReturn = 0;
+    const K_IO_MASTER_PORT_DEFAULT: u32 = 0;
+    const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+    const K_CF_NUMBER_SINT32_TYPE: isize = 3;
+
+    const IO_USB_DEVICE_CLASS_NAME: &str = "IOUSBDevice";
+    const IO_SERVICE_PLANE: &str = "IOService";
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOServiceMatching(name: *const c_char) -> CfMutableRef;
+        fn IOServiceGetMatchingServices(
+            master_port: u32,
+            matching: CfMutableRef,
+            existing: *mut IoIterator,
+        ) -> KernReturn;
+        fn IOIteratorNext(iterator: IoIterator) -> IoObject;
+        fn IOObjectRelease(object: IoObject) -> KernReturn;
+        fn IOObjectGetClass(object: IoObject, class_name: *mut c_char) -> KernReturn;
+        fn IORegistryEntryGetPath(
+            entry: IoObject,
+            plane: *const c_char,
+            path: *mut c_char,
+        ) -> KernReturn;
+        fn IORegistryEntryGetChildIterator(
+            entry: IoObject,
+            plane: *const c_char,
+            iterator: *mut IoIterator,
+        ) -> KernReturn;
+        fn IORegistryEntryCreateCFProperty(
+            entry: IoObject,
+            key: CfRef,
+            allocator: CfRef,
+            options: u32,
+        ) -> CfRef;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFRelease(object: CfRef);
+        fn CFStringCreateWithCString(
+            allocator: CfRef,
+            string: *const c_char,
+            encoding: u32,
+        ) -> CfRef;
+        fn CFNumberGetValue(number: CfRef, number_type: isize, value_ptr: *mut c_void) -> bool;
+        fn CFStringGetCString(
+            string: CfRef,
+            buffer: *mut c_char,
+            size: isize,
+            encoding: u32,
+        ) -> bool;
+    }
+
+    /**
+     * Enrich one enumerated device by walking the IOUSBDevice entries
+     * and correlating through `match_registry_entry`. `NotFound` when
+     * no entry matches.
+     */
+    pub fn enrich_macos(info: &UsbDeviceInfo) -> Result<MacPlatformHint, UsbError> {
+        let entries = read_registry_entries()?;
+        super::match_registry_entry(&entries, info)
+            .map(super::hint_from_entry)
+            .ok_or_else(|| {
+                UsbError::NotFound(format!(
+                    "no IORegistry entry matches bus {} address {}",
+                    info.bus_number, info.address
+                ))
+            })
+    }
+
+    /// Snapshot every IOUSBDevice entry into plain data.
+    fn read_registry_entries() -> Result<Vec<RegistryEntry>, UsbError> {
+        unsafe {
+            let class_name = CString::new(IO_USB_DEVICE_CLASS_NAME).unwrap();
+            let matching = IOServiceMatching(class_name.as_ptr());
+            let mut iterator: IoIterator = 0;
+            // IOServiceGetMatchingServices consumes the dictionary.
+            let rc =
+                IOServiceGetMatchingServices(K_IO_MASTER_PORT_DEFAULT, matching, &mut iterator);
+            if rc != KERN_SUCCESS {
+                return Err(UsbError::Internal(format!(
+                    "IOServiceGetMatchingServices failed: {:#x}",
+                    rc
+                )));
+            }
+
+            let mut entries = Vec::new();
+            loop {
+                let entry = IOIteratorNext(iterator);
+                if entry == 0 {
+                    break;
+                }
+                entries.push(entry_of(entry));
+                IOObjectRelease(entry);
+            }
+            IOObjectRelease(iterator);
+            Ok(entries)
+        }
+    }
+
+    unsafe fn entry_of(entry: IoObject) -> RegistryEntry {
+        RegistryEntry {
+            location_id: prop_u32(entry, "locationID"),
+            usb_address: prop_u32(entry, "USB Address").map(|a| a as u8),
+            vendor_id: prop_u32(entry, "idVendor").unwrap_or(0) as u16,
+            product_id: prop_u32(entry, "idProduct").unwrap_or(0) as u16,
+            serial_number: prop_string(entry, "USB Serial Number"),
+            ioregistry_path: registry_path(entry),
+            driver_name: client_class(entry),
+        }
+    }
+
+    /// IOClass of the first IOService-plane child: the client that
+    /// claimed the device, kext or dext alike.
+    unsafe fn client_class(entry: IoObject) -> Option<String> {
+        let plane = CString::new(IO_SERVICE_PLANE).unwrap();
+        let mut iterator: IoIterator = 0;
+        if IORegistryEntryGetChildIterator(entry, plane.as_ptr(), &mut iterator) != KERN_SUCCESS {
+            return None;
+        }
+        let mut class = None;
+        loop {
+            let child = IOIteratorNext(iterator);
+            if child == 0 {
+                break;
+            }
+            let mut name = [0 as c_char; 128];
+            if IOObjectGetClass(child, name.as_mut_ptr()) == KERN_SUCCESS {
+                class = c_chars_to_string(&name);
+            }
+            IOObjectRelease(child);
+            if class.is_some() {
+                break;
+            }
+        }
+        IOObjectRelease(iterator);
+        class
+    }
+
+    unsafe fn registry_path(entry: IoObject) -> Option<String> {
+        let plane = CString::new(IO_SERVICE_PLANE).unwrap();
+        // io_string_t is 512 bytes.
+        let mut path = [0 as c_char; 512];
+        (IORegistryEntryGetPath(entry, plane.as_ptr(), path.as_mut_ptr()) == KERN_SUCCESS)
+            .then(|| c_chars_to_string(&path))
+            .flatten()
+    }
+
+    fn c_chars_to_string(buf: &[c_char]) -> Option<String> {
+        let bytes: Vec<u8> = buf
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        (!bytes.is_empty()).then(|| String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// The CF value of a registry property; the caller releases it.
+    unsafe fn cf_prop(entry: IoObject, key: &str) -> Option<CfRef> {
+        let key = CString::new(key).ok()?;
+        let key =
+            CFStringCreateWithCString(std::ptr::null(), key.as_ptr(), K_CF_STRING_ENCODING_UTF8);
+        if key.is_null() {
+            return None;
+        }
+        let value = IORegistryEntryCreateCFProperty(entry, key, std::ptr::null(), 0);
+        CFRelease(key);
+        (!value.is_null()).then_some(value)
+    }
+
+    unsafe fn prop_string(entry: IoObject, key: &str) -> Option<String> {
+        let value = cf_prop(entry, key)?;
+        let mut buf = [0 as c_char; 256];
+        let ok = CFStringGetCString(
+            value,
+            buf.as_mut_ptr(),
+            buf.len() as isize,
+            K_CF_STRING_ENCODING_UTF8,
+        );
+        CFRelease(value);
+        if !ok {
+            return None;
+        }
+        c_chars_to_string(&buf)
+    }
+
+    unsafe fn prop_u32(entry: IoObject, key: &str) -> Option<u32> {
+        let value = cf_prop(entry, key)?;
+        let mut out: i32 = 0;
+        let ok = CFNumberGetValue(
+            value,
+            K_CF_NUMBER_SINT32_TYPE,
+            &mut out as *mut i32 as *mut c_void,
+        );
+        CFRelease(value);
+        ok.then_some(out as u32)
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use ioreg::enrich_macos;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(bus: u8, address: u8, vid: u16, pid: u16, serial: Option<&str>) -> RegistryEntry {
+        RegistryEntry {
+            location_id: Some(u32::from(bus) << 24 | 0x0010_0000),
+            usb_address: Some(address),
+            vendor_id: vid,
+            product_id: pid,
+            serial_number: serial.map(str::to_string),
+            ioregistry_path: Some(format!(
+                "IOService:/AppleUSBHostController@{:02x}/device@{}",
+                bus, address
+            )),
+            driver_name: Some("AppleUSBHostCompositeDevice".to_string()),
+        }
+    }
+
+    fn info(bus: u8, address: u8, vid: u16, pid: u16, serial: Option<&str>) -> UsbDeviceInfo {
+        let mut builder = UsbDeviceInfo::builder(vid, pid).bus_number(bus).address(address);
+        if let Some(serial) = serial {
+            builder = builder.serial(serial);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_match_on_location_bus_and_address() {
+        let entries = vec![
+            entry(0x14, 3, 0x05ac, 0x12a8, Some("F2LX")),
+            entry(0x14, 7, 0x18d1, 0x4ee7, Some("29061FDH300EXZ")),
+        ];
+        let found = match_registry_entry(&entries, &info(0x14, 7, 0x18d1, 0x4ee7, None)).unwrap();
+        assert_eq!(found.usb_address, Some(7));
+
+        // VID/PID disagreement at the same slot: the device renumbered
+        // and something else took its address.
+        assert!(match_registry_entry(&entries, &info(0x14, 7, 0x05ac, 0x12a8, None)).is_none());
+    }
+
+    #[test]
+    fn test_renumbered_device_falls_back_to_serial() {
+        // The registry still shows the pre-renumber address.
+        let entries = vec![
+            entry(0x14, 3, 0x18d1, 0x4ee7, Some("UNIT-A")),
+            entry(0x14, 4, 0x18d1, 0x4ee7, Some("UNIT-B")),
+        ];
+        let found =
+            match_registry_entry(&entries, &info(0x14, 9, 0x18d1, 0x4ee7, Some("UNIT-B"))).unwrap();
+        assert_eq!(found.serial_number.as_deref(), Some("UNIT-B"));
+
+        // Without a serial the fallback never guesses between units.
+        assert!(match_registry_entry(&entries, &info(0x14, 9, 0x18d1, 0x4ee7, None)).is_none());
+
+        // Nor when two entries claim the same serial.
+        let twins = vec![
+            entry(0x14, 3, 0x18d1, 0x4ee7, Some("CLONED")),
+            entry(0x14, 4, 0x18d1, 0x4ee7, Some("CLONED")),
+        ];
+        assert!(match_registry_entry(&twins, &info(0x14, 9, 0x18d1, 0x4ee7, Some("CLONED"))).is_none());
+    }
+
+    #[test]
+    fn test_hint_from_entry_fills_every_field() {
+        let hint = hint_from_entry(&entry(0x14, 7, 0x18d1, 0x4ee7, Some("29061FDH300EXZ")));
+        assert_eq!(hint.location_id, Some(0x1410_0000));
+        assert_eq!(hint.port_path.as_deref(), Some("20-1"));
+        assert_eq!(
+            hint.driver,
+            DriverStatus::Bound("AppleUSBHostCompositeDevice".to_string())
+        );
+        assert_eq!(hint.driver_name.as_deref(), Some("AppleUSBHostCompositeDevice"));
+        assert!(hint.ioregistry_path.is_some());
+
+        // No client attached reads as Missing, not Unknown.
+        let mut unbound = entry(0x14, 7, 0x18d1, 0x4ee7, None);
+        unbound.driver_name = None;
+        assert_eq!(hint_from_entry(&unbound).driver, DriverStatus::Missing);
+    }
+
+    #[test]
+    fn test_location_id_to_port_path() {
+        // Bus 0x14, ports 2 then 3: the example from an M1 hub chain.
+        assert_eq!(
+            location_id_to_port_path(0x14230000).as_deref(),
+            Some("20-2.3")
+        );
+        // Single port directly on the bus.
+        assert_eq!(
+            location_id_to_port_path(0x01100000).as_deref(),
+            Some("1-1")
+        );
+        // Five-deep chain uses every remaining nibble.
+        assert_eq!(
+            location_id_to_port_path(0x02123450).as_deref(),
+            Some("2-1.2.3.4.5")
+        );
+        // The chain stops at the first zero nibble even with trailing
+        // garbage nibbles after it.
+        assert_eq!(
+            location_id_to_port_path(0x14200300).as_deref(),
+            Some("20-2")
+        );
+        // Root entry: bus byte only, no ports, no path.
+        assert_eq!(location_id_to_port_path(0x14000000), None);
+        assert_eq!(location_id_to_port_path(0), None);
+    }
+}
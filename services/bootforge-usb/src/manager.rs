@@ -0,0 +1,555 @@
+// BootForge USB - Shared device manager
+// Long-lived tracking layer shared by several consumers: owns a registry,
+// runs incremental updates, and only pays for expensive per-device work
+// (opens, string reads) on devices some consumer has declared interest in.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::enumeration::{DeviceFilter, EnumerationOptions, UsbDeviceInfo};
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+use crate::registry::DeviceRegistry;
+
+/**
+ * How much work the manager spent on a device. Exposed so UIs can show
+ * why a record's data is sparse.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandlingLevel {
+    /// Descriptor-level data only: no opens, no string reads, no
+    /// classification. Keeps uninteresting devices in selective suspend.
+    Lightweight,
+    /// Opened and fully populated.
+    Full,
+}
+
+/**
+ * One device as the manager tracks it, with the handling level its data
+ * reflects.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagedRecord {
+    pub info: UsbDeviceInfo,
+    pub handling: HandlingLevel,
+}
+
+/// Handle returned by `register_interest`, used to withdraw it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterestToken(u64);
+
+/**
+ * Serializable manager state for restart recovery: the registered
+ * interests (channels and observers are re-established by consumers)
+ * plus the tracked-device snapshot and its sequence number. Feed it to
+ * `import_state` and then `resume` on the replacement manager.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManagerState {
+    /// Update counter at export time; resumes continue from here so
+    /// consumers can order snapshots across the restart.
+    pub sequence: u64,
+    next_token: u64,
+    interests: Vec<InterestState>,
+    devices: Vec<UsbDeviceInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct InterestState {
+    token: u64,
+    filter: DeviceFilter,
+}
+
+/**
+ * An event together with whether it was synthesized while catching up
+ * after a restart (`recovered`) rather than observed live.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredEvent {
+    pub event: DeviceEvent,
+    pub recovered: bool,
+}
+
+/**
+ * Where the manager gets its device data. The snapshot path must stay
+ * descriptor-level (sysfs reads only); `enrich` is the expensive path
+ * that opens the device - mock implementations count its calls to prove
+ * uninteresting devices are never opened.
+ */
+pub trait DeviceSource {
+    /// Descriptor-level snapshot of the bus; must not open any device.
+    fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError>;
+
+    /// Open one device and fill in the expensive fields (strings,
+    /// anything requiring a handle).
+    fn enrich(&mut self, info: &UsbDeviceInfo) -> Result<UsbDeviceInfo, UsbError>;
+
+    /// Cheap change hint for `update_if_changed`, e.g. the platform's
+    /// bus generation counter. None means no cheap source exists and
+    /// every update pays for a snapshot.
+    fn generation(&mut self) -> Option<u64> {
+        None
+    }
+}
+
+/**
+ * Shared manager with interest-gated handling. Consumers register
+ * `DeviceFilter` interests; devices matching no interest get lightweight
+ * handling on every update, and registering a new interest upgrades any
+ * previously-lightweight matches in place through the single-device
+ * refresh path.
+ */
+pub struct SharedDeviceManager<S: DeviceSource> {
+    source: S,
+    registry: DeviceRegistry,
+    records: HashMap<DeviceIdentity, ManagedRecord>,
+    interests: HashMap<u64, DeviceFilter>,
+    options: EnumerationOptions,
+    next_token: u64,
+    sequence: u64,
+    last_generation: Option<u64>,
+}
+
+impl<S: DeviceSource> SharedDeviceManager<S> {
+    pub fn new(source: S) -> Self {
+        SharedDeviceManager {
+            source,
+            registry: DeviceRegistry::new(),
+            records: HashMap::new(),
+            interests: HashMap::new(),
+            options: EnumerationOptions::default(),
+            next_token: 0,
+            sequence: 0,
+            last_generation: None,
+        }
+    }
+
+    /// Apply the same skip controls as enumeration, so the manager's
+    /// events agree with list-style consumers about what exists.
+    pub fn with_enumeration_options(mut self, options: EnumerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Enable descriptor-fingerprint tamper detection on the inner
+    /// registry.
+    pub fn with_tamper_detection(mut self, enabled: bool) -> Self {
+        self.registry = DeviceRegistry::new().with_tamper_detection(enabled);
+        self
+    }
+
+    /**
+     * Register a consumer's interest. Tracked devices that match and
+     * were previously lightweight are upgraded immediately; the token
+     * withdraws the interest via `clear_interest`.
+     */
+    pub fn register_interest(&mut self, filter: DeviceFilter) -> InterestToken {
+        let token = self.next_token;
+        self.next_token += 1;
+        self.interests.insert(token, filter);
+
+        let to_upgrade: Vec<DeviceIdentity> = self
+            .records
+            .iter()
+            .filter(|(_, record)| {
+                record.handling == HandlingLevel::Lightweight && self.is_interesting(&record.info)
+            })
+            .map(|(identity, _)| identity.clone())
+            .collect();
+        for identity in to_upgrade {
+            if let Err(err) = self.refresh_device(&identity) {
+                log::warn!("interest upgrade of {} failed: {}", identity, err);
+            }
+        }
+
+        InterestToken(token)
+    }
+
+    /// Withdraw an interest. Existing records keep their data; the
+    /// device simply gets lightweight handling from the next update on.
+    pub fn clear_interest(&mut self, token: InterestToken) {
+        self.interests.remove(&token.0);
+    }
+
+    fn is_interesting(&self, info: &UsbDeviceInfo) -> bool {
+        self.interests.values().any(|filter| filter.matches(info))
+    }
+
+    /**
+     * Run one incremental update: take a descriptor-level snapshot,
+     * enrich only the devices some interest covers, and reconcile the
+     * result against the registry. Returns the implied events.
+     */
+    pub fn update(&mut self) -> Result<Vec<DeviceEvent>, UsbError> {
+        let snapshot = self.source.snapshot()?;
+        let mut effective = Vec::with_capacity(snapshot.len());
+        let mut handling = Vec::with_capacity(snapshot.len());
+
+        for info in snapshot {
+            if self
+                .options
+                .should_skip(info.descriptor.device_class, info.vendor_id)
+                || !self.options.allows(info.vendor_id, info.product_id)
+            {
+                continue;
+            }
+            if self.is_interesting(&info) {
+                let enriched = self.source.enrich(&info)?;
+                effective.push(enriched);
+                handling.push(HandlingLevel::Full);
+            } else {
+                effective.push(info);
+                handling.push(HandlingLevel::Lightweight);
+            }
+        }
+
+        let events = self.registry.observe_snapshot(&effective);
+        self.sequence += 1;
+
+        self.records.clear();
+        for (info, handling) in effective.into_iter().zip(handling) {
+            self.records
+                .insert(DeviceIdentity::of(&info), ManagedRecord { info, handling });
+        }
+
+        Ok(events)
+    }
+
+    /**
+     * Run an update only if the source's generation counter has moved
+     * since the last one. None means the update was skipped because
+     * nothing changed. Sources without a generation always update.
+     */
+    pub fn update_if_changed(&mut self) -> Result<Option<Vec<DeviceEvent>>, UsbError> {
+        let current = self.source.generation();
+        if current.is_some() && current == self.last_generation {
+            return Ok(None);
+        }
+        let events = self.update()?;
+        self.last_generation = current;
+        Ok(Some(events))
+    }
+
+    /**
+     * Refresh a single tracked device through the expensive path,
+     * upgrading it to full handling regardless of interests. Returns
+     * the refreshed record, or None if the identity is not tracked.
+     */
+    pub fn refresh_device(
+        &mut self,
+        identity: &DeviceIdentity,
+    ) -> Result<Option<&ManagedRecord>, UsbError> {
+        let Some(record) = self.records.get(identity) else {
+            return Ok(None);
+        };
+        let enriched = self.source.enrich(&record.info)?;
+        let record = self.records.get_mut(identity).expect("checked above");
+        record.info = enriched;
+        record.handling = HandlingLevel::Full;
+        Ok(Some(&*record))
+    }
+
+    pub fn record(&self, identity: &DeviceIdentity) -> Option<&ManagedRecord> {
+        self.records.get(identity)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &ManagedRecord> {
+        self.records.values()
+    }
+
+    /// Update counter; bumps once per `update` (and once for `resume`).
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /**
+     * Snapshot the restorable parts of the manager for a restart:
+     * interests, tokens, tracked devices, and the sequence number.
+     */
+    pub fn export_state(&self) -> ManagerState {
+        let mut interests: Vec<InterestState> = self
+            .interests
+            .iter()
+            .map(|(token, filter)| InterestState {
+                token: *token,
+                filter: filter.clone(),
+            })
+            .collect();
+        interests.sort_by_key(|i| i.token);
+        ManagerState {
+            sequence: self.sequence,
+            next_token: self.next_token,
+            interests,
+            devices: self.records.values().map(|r| r.info.clone()).collect(),
+        }
+    }
+
+    /**
+     * Restore exported state into a fresh manager. Tokens stay valid,
+     * and the imported snapshot primes the registry so the next update
+     * emits only what changed during the gap - call `resume` for that
+     * first catch-up pass.
+     */
+    pub fn import_state(&mut self, state: ManagerState) {
+        self.sequence = state.sequence;
+        self.next_token = state.next_token;
+        self.interests = state
+            .interests
+            .into_iter()
+            .map(|i| (i.token, i.filter))
+            .collect();
+
+        // Prime the registry; the implied Connected events describe
+        // devices that were already tracked before the restart.
+        let _ = self.registry.observe_snapshot(&state.devices);
+        self.records = state
+            .devices
+            .into_iter()
+            .map(|info| {
+                // Imported data may be stale; records are re-leveled by
+                // the next update.
+                (
+                    DeviceIdentity::of(&info),
+                    ManagedRecord {
+                        info,
+                        handling: HandlingLevel::Lightweight,
+                    },
+                )
+            })
+            .collect();
+    }
+
+    /**
+     * First update after an import: diffs the imported snapshot against
+     * a fresh enumeration and returns the gap's delta as standard
+     * events flagged `recovered`.
+     */
+    pub fn resume(&mut self) -> Result<Vec<RecoveredEvent>, UsbError> {
+        Ok(self
+            .update()?
+            .into_iter()
+            .map(|event| RecoveredEvent {
+                event,
+                recovered: true,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::version::BcdVersion;
+
+    fn device(vendor_id: u16, product_id: u16, serial: &str) -> UsbDeviceInfo {
+        UsbDeviceInfo {
+            bus_number: 1,
+            address: 4,
+            vendor_id,
+            product_id,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0200),
+                device_version: BcdVersion(0x0100),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: None,
+            product: None,
+            serial_number: Some(serial.to_string()),
+            port_path: None,
+            speed: None,
+            tags: Vec::new(),
+            active_config: None,
+            usb_ids: None,
+            container_id: None,
+        }
+    }
+
+    /// Counts enrich calls so tests can prove which devices got opened.
+    struct CountingSource {
+        devices: Vec<UsbDeviceInfo>,
+        enrich_calls: Vec<String>,
+        snapshot_calls: usize,
+        generation: Option<u64>,
+    }
+
+    impl CountingSource {
+        fn new(devices: Vec<UsbDeviceInfo>) -> Self {
+            CountingSource {
+                devices,
+                enrich_calls: Vec::new(),
+                snapshot_calls: 0,
+                generation: None,
+            }
+        }
+    }
+
+    impl DeviceSource for CountingSource {
+        fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+            self.snapshot_calls += 1;
+            Ok(self.devices.clone())
+        }
+
+        fn enrich(&mut self, info: &UsbDeviceInfo) -> Result<UsbDeviceInfo, UsbError> {
+            self.enrich_calls
+                .push(info.serial_number.clone().unwrap_or_default());
+            let mut enriched = info.clone();
+            enriched.product = Some("Enriched".to_string());
+            Ok(enriched)
+        }
+
+        fn generation(&mut self) -> Option<u64> {
+            self.generation
+        }
+    }
+
+    #[test]
+    fn test_uninteresting_devices_are_never_opened() {
+        let source = CountingSource::new(vec![
+            device(0x18d1, 0x4ee7, "PHONE"),
+            device(0x2109, 0x2812, "HUB"),
+        ]);
+        let mut manager = SharedDeviceManager::new(source);
+        manager.register_interest(DeviceFilter::any().with_vendor_id(0x18d1));
+
+        manager.update().unwrap();
+        assert_eq!(manager.source.enrich_calls, vec!["PHONE"]);
+
+        let phone = manager
+            .record(&DeviceIdentity("usb:18d1:4ee7:PHONE".to_string()))
+            .unwrap();
+        assert_eq!(phone.handling, HandlingLevel::Full);
+        assert_eq!(phone.info.product.as_deref(), Some("Enriched"));
+
+        let hub = manager
+            .record(&DeviceIdentity("usb:2109:2812:HUB".to_string()))
+            .unwrap();
+        assert_eq!(hub.handling, HandlingLevel::Lightweight);
+        assert_eq!(hub.info.product, None);
+    }
+
+    #[test]
+    fn test_skip_controls_drop_devices_before_any_open() {
+        let mut hub = device(0x2109, 0x2812, "HUB");
+        hub.descriptor.device_class = 0x09;
+        let source = CountingSource::new(vec![hub, device(0x18d1, 0x4ee7, "PHONE")]);
+        let mut manager = SharedDeviceManager::new(source)
+            .with_enumeration_options(EnumerationOptions::default().with_skip_hubs(true));
+        manager.register_interest(DeviceFilter::any());
+
+        let events = manager.update().unwrap();
+        assert_eq!(events.len(), 1, "only the phone connects: {:?}", events);
+        assert_eq!(manager.source.enrich_calls, vec!["PHONE"]);
+        assert!(manager
+            .record(&DeviceIdentity("usb:2109:2812:HUB".to_string()))
+            .is_none());
+    }
+
+    #[test]
+    fn test_new_interest_upgrades_lightweight_records_in_place() {
+        let source = CountingSource::new(vec![device(0x2109, 0x2812, "HUB")]);
+        let mut manager = SharedDeviceManager::new(source);
+
+        manager.update().unwrap();
+        assert!(manager.source.enrich_calls.is_empty());
+
+        manager.register_interest(DeviceFilter::any().with_vendor_id(0x2109));
+        assert_eq!(manager.source.enrich_calls, vec!["HUB"]);
+        let hub = manager
+            .record(&DeviceIdentity("usb:2109:2812:HUB".to_string()))
+            .unwrap();
+        assert_eq!(hub.handling, HandlingLevel::Full);
+    }
+
+    #[test]
+    fn test_unmoved_generation_skips_enumeration() {
+        let mut source = CountingSource::new(vec![device(0x18d1, 0x4ee7, "PHONE")]);
+        source.generation = Some(7);
+        let mut manager = SharedDeviceManager::new(source);
+
+        assert!(manager.update_if_changed().unwrap().is_some());
+        assert!(manager.update_if_changed().unwrap().is_none());
+        assert_eq!(manager.source.snapshot_calls, 1);
+
+        manager.source.generation = Some(8);
+        assert!(manager.update_if_changed().unwrap().is_some());
+        assert_eq!(manager.source.snapshot_calls, 2);
+    }
+
+    #[test]
+    fn test_sources_without_generation_always_update() {
+        let source = CountingSource::new(Vec::new());
+        let mut manager = SharedDeviceManager::new(source);
+        assert!(manager.update_if_changed().unwrap().is_some());
+        assert!(manager.update_if_changed().unwrap().is_some());
+        assert_eq!(manager.source.snapshot_calls, 2);
+    }
+
+    #[test]
+    fn test_restart_recovery_emits_exactly_the_gap_delta() {
+        // Before the restart: X and Y present, interest in everything.
+        let source = CountingSource::new(vec![
+            device(0x18d1, 0x4ee7, "X"),
+            device(0x0781, 0x5583, "Y"),
+        ]);
+        let mut manager = SharedDeviceManager::new(source);
+        manager.register_interest(DeviceFilter::any());
+        manager.update().unwrap();
+
+        let state = manager.export_state();
+        // State must survive a trip through serialization.
+        let json = serde_json::to_string(&state).unwrap();
+        let state: ManagerState = serde_json::from_str(&json).unwrap();
+
+        // During the gap X was unplugged and Z plugged in.
+        let source = CountingSource::new(vec![
+            device(0x0781, 0x5583, "Y"),
+            device(0x2e8a, 0x0003, "Z"),
+        ]);
+        let mut manager = SharedDeviceManager::new(source);
+        manager.import_state(state);
+
+        let events = manager.resume().unwrap();
+        assert!(events.iter().all(|e| e.recovered));
+        let mut kinds: Vec<String> = events
+            .iter()
+            .map(|e| match &e.event {
+                DeviceEvent::Connected(info) => {
+                    format!("connected:{}", info.serial_number.as_deref().unwrap())
+                }
+                DeviceEvent::Disconnected(identity) => format!("disconnected:{}", identity),
+                other => format!("other:{:?}", other),
+            })
+            .collect();
+        kinds.sort();
+        assert_eq!(kinds, vec!["connected:Z", "disconnected:usb:18d1:4ee7:X"]);
+
+        // The imported interest still applies: Y and Z were enriched.
+        let mut enriched = manager.source.enrich_calls.clone();
+        enriched.sort();
+        assert_eq!(enriched, vec!["Y", "Z"]);
+        assert_eq!(manager.sequence(), 2);
+    }
+
+    #[test]
+    fn test_cleared_interest_returns_device_to_lightweight_handling() {
+        let source = CountingSource::new(vec![device(0x18d1, 0x4ee7, "PHONE")]);
+        let mut manager = SharedDeviceManager::new(source);
+        let token = manager.register_interest(DeviceFilter::any().with_vendor_id(0x18d1));
+
+        manager.update().unwrap();
+        assert_eq!(manager.source.enrich_calls.len(), 1);
+
+        manager.clear_interest(token);
+        manager.update().unwrap();
+        assert_eq!(manager.source.enrich_calls.len(), 1, "no further opens");
+        let phone = manager
+            .record(&DeviceIdentity("usb:18d1:4ee7:PHONE".to_string()))
+            .unwrap();
+        assert_eq!(phone.handling, HandlingLevel::Lightweight);
+    }
+}
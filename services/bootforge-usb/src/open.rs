@@ -0,0 +1,261 @@
+// BootForge USB - Opening enumerated devices
+// The path from an enumerated record to an open handle: relocate the
+// device in a live libusb context, open it, and hand back the
+// `rusb::DeviceHandle` that the transfer and claim layers already
+// accept as a `UsbTransport`. Opening is the first write-capable step,
+// so it is explicit and never part of enumeration.
+
+use crate::canonical::CanonicalId;
+use crate::context::SharedContext;
+use crate::enumeration::{guard_panics, UsbDeviceInfo, UsbDeviceRecord};
+use crate::error::UsbError;
+
+/**
+ * How to open a device.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenOptions {
+    /// Ask libusb to detach the kernel driver around interface claims
+    /// (Linux; silently unsupported elsewhere).
+    pub detach_kernel_driver: bool,
+}
+
+impl OpenOptions {
+    pub fn with_detach_kernel_driver(mut self, detach: bool) -> Self {
+        self.detach_kernel_driver = detach;
+        self
+    }
+}
+
+/// The fields that relocate a previously-enumerated device.
+struct Target {
+    bus_number: u8,
+    address: u8,
+    vendor_id: u16,
+    product_id: u16,
+    serial_number: Option<String>,
+    id: CanonicalId,
+}
+
+impl UsbDeviceInfo {
+    /**
+     * Open this device for communication. The device is matched by bus
+     * and address first; when that slot is stale (the device was
+     * replugged since enumeration) it falls back to VID/PID, using the
+     * serial number to pick between same-model devices.
+     */
+    pub fn open(&self) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+        self.open_with(&OpenOptions::default())
+    }
+
+    pub fn open_with(
+        &self,
+        options: &OpenOptions,
+    ) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+        open_target(self.target(), options)
+    }
+
+    /// `open_with` against a caller-owned shared context.
+    pub fn open_in(
+        &self,
+        context: &SharedContext,
+        options: &OpenOptions,
+    ) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+        let target = self.target();
+        context.with_context(|ctx| locate_and_open(ctx, &target, options))?
+    }
+
+    fn target(&self) -> Target {
+        Target {
+            bus_number: self.bus_number,
+            address: self.address,
+            vendor_id: self.vendor_id,
+            product_id: self.product_id,
+            serial_number: self.serial_number.clone(),
+            id: CanonicalId::of(self),
+        }
+    }
+}
+
+impl UsbDeviceRecord {
+    /**
+     * Open this fallback-enumerated device. Matching works as for
+     * `UsbDeviceInfo::open`; the sysfs device number is the libusb
+     * address.
+     */
+    pub fn open(&self) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+        self.open_with(&OpenOptions::default())
+    }
+
+    pub fn open_with(
+        &self,
+        options: &OpenOptions,
+    ) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+        open_target(
+            Target {
+                bus_number: self.bus_number,
+                address: self.device_number,
+                vendor_id: self.vendor_id,
+                product_id: self.product_id,
+                serial_number: self.serial_number.clone(),
+                id: CanonicalId::of_record(self),
+            },
+            options,
+        )
+    }
+}
+
+fn open_target(
+    target: Target,
+    options: &OpenOptions,
+) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+    guard_panics(
+        "device open",
+        std::panic::AssertUnwindSafe(|| {
+            let context = rusb::Context::new()?;
+            locate_and_open(&context, &target, options)
+        }),
+    )
+}
+
+fn locate_and_open(
+    context: &rusb::Context,
+    target: &Target,
+    options: &OpenOptions,
+) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+    use rusb::UsbContext;
+
+    let devices = context.devices().map_err(|e| map_open_error(e, target))?;
+
+    // Exact slot first: cheapest, and unambiguous while the device has
+    // not been replugged.
+    for device in devices.iter() {
+        if device.bus_number() == target.bus_number && device.address() == target.address {
+            let descriptor = device.device_descriptor().map_err(|e| map_open_error(e, target))?;
+            if descriptor.vendor_id() == target.vendor_id
+                && descriptor.product_id() == target.product_id
+            {
+                return open_handle(&device, options).map_err(|e| map_open_error(e, target));
+            }
+            // Another device moved into the slot; fall through to the
+            // VID/PID search.
+            break;
+        }
+    }
+
+    // Stale address: search by VID/PID, disambiguating by serial. Probe
+    // failures on unrelated candidates are skipped, not fatal.
+    let mut fallback = None;
+    let mut denied = false;
+    for device in devices.iter() {
+        let Ok(descriptor) = device.device_descriptor() else {
+            continue;
+        };
+        if descriptor.vendor_id() != target.vendor_id
+            || descriptor.product_id() != target.product_id
+        {
+            continue;
+        }
+        match &target.serial_number {
+            None => return open_handle(&device, options).map_err(|e| map_open_error(e, target)),
+            Some(serial) => match open_handle(&device, options) {
+                Ok(handle) => {
+                    let matches = handle
+                        .read_serial_number_string_ascii(&descriptor)
+                        .is_ok_and(|s| &s == serial);
+                    if matches {
+                        return Ok(handle);
+                    }
+                    // Keep one same-model handle in case no serial
+                    // matches exactly (e.g. the string read needs a
+                    // different language id).
+                    fallback.get_or_insert(handle);
+                }
+                Err(rusb::Error::Access) => denied = true,
+                Err(_) => {}
+            },
+        }
+    }
+
+    if denied {
+        return Err(UsbError::PermissionDenied(target.id.to_string()));
+    }
+    match fallback {
+        Some(handle) => Ok(handle),
+        None => Err(UsbError::NotFound(target.id.to_string())),
+    }
+}
+
+fn open_handle(
+    device: &rusb::Device<rusb::Context>,
+    options: &OpenOptions,
+) -> Result<rusb::DeviceHandle<rusb::Context>, rusb::Error> {
+    let handle = device.open()?;
+    if options.detach_kernel_driver {
+        match handle.set_auto_detach_kernel_driver(true) {
+            Ok(()) | Err(rusb::Error::NotSupported) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(handle)
+}
+
+/// Fold libusb's open-path errors into the crate's named ones; anything
+/// else stays a libusb error.
+fn map_open_error(error: rusb::Error, target: &Target) -> UsbError {
+    match error {
+        rusb::Error::Access => UsbError::PermissionDenied(target.id.to_string()),
+        rusb::Error::NoDevice | rusb::Error::NotFound => {
+            UsbError::NotFound(target.id.to_string())
+        }
+        other => UsbError::Libusb(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watch::partial_info;
+
+    fn absent_device() -> UsbDeviceInfo {
+        let mut info = partial_info(
+            0xdead,
+            0xbeef,
+            Some("NO-SUCH-DEVICE".to_string()),
+            None,
+            "test".to_string(),
+        );
+        info.bus_number = 250;
+        info.address = 125;
+        info
+    }
+
+    #[test]
+    fn test_open_error_mapping() {
+        let info = absent_device();
+        let target = info.target();
+        assert!(matches!(
+            map_open_error(rusb::Error::Access, &target),
+            UsbError::PermissionDenied(id) if id == "usb:dead:beef:NO-SUCH-DEVICE"
+        ));
+        assert!(matches!(
+            map_open_error(rusb::Error::NoDevice, &target),
+            UsbError::NotFound(_)
+        ));
+        assert!(matches!(
+            map_open_error(rusb::Error::Pipe, &target),
+            UsbError::Libusb(rusb::Error::Pipe)
+        ));
+    }
+
+    #[test]
+    fn test_open_absent_device_errs_without_unwinding() {
+        // No hardware in CI; the contract is a clean error, with
+        // NotFound once a context is available at all.
+        match absent_device().open() {
+            Err(UsbError::NotFound(id)) => assert!(id.starts_with("usb:dead:beef")),
+            Err(_) => {}
+            Ok(_) => panic!("absent device must not open"),
+        }
+    }
+}
@@ -0,0 +1,431 @@
+// BootForge USB - Interactive device picker for CLI tools
+// Plain stdin/stdout; no TUI dependency. Gated behind the "picker"
+// feature so library-only users do not pay for it.
+
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use thiserror::Error;
+
+use crate::canonical::CanonicalId;
+use crate::enumeration::UsbDeviceInfo;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PickError {
+    #[error("no devices connected")]
+    NoDevices,
+
+    #[error("no device matches the selector")]
+    NoMatch,
+
+    #[error("{0} devices match; refine the selector or pick interactively")]
+    Ambiguous(usize),
+
+    #[error("invalid selector: {0}")]
+    InvalidSelector(String),
+
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("selection cancelled")]
+    Cancelled,
+}
+
+/**
+ * Non-interactive device selector, as passed to `--device`.
+ *
+ * Supported forms:
+ *   "usb:18d1:4ee7:SER"  - canonical ID (see the canonical module)
+ *   "18d1:4ee7"          - VID:PID in hex
+ *   "serial=ABC123"      - exact serial number
+ *   "port=3-1.4"         - sysfs-style port path
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSelector {
+    VidPid { vendor_id: u16, product_id: u16 },
+    Serial(String),
+    Port(String),
+    /// Full canonical ID; matches one exact device.
+    Canonical(CanonicalId),
+}
+
+impl DeviceSelector {
+    pub fn parse(s: &str) -> Result<DeviceSelector, PickError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(PickError::InvalidSelector("empty selector".to_string()));
+        }
+
+        if s.starts_with("usb:") {
+            return s
+                .parse::<CanonicalId>()
+                .map(DeviceSelector::Canonical)
+                .map_err(|e| PickError::InvalidSelector(e.to_string()));
+        }
+
+        if let Some(serial) = s.strip_prefix("serial=") {
+            if serial.is_empty() {
+                return Err(PickError::InvalidSelector(
+                    "serial= requires a value".to_string(),
+                ));
+            }
+            return Ok(DeviceSelector::Serial(serial.to_string()));
+        }
+
+        if let Some(port) = s.strip_prefix("port=") {
+            return parse_port_selector(port);
+        }
+
+        if let Some((vid, pid)) = s.split_once(':') {
+            let vendor_id = u16::from_str_radix(vid, 16)
+                .map_err(|_| PickError::InvalidSelector(format!("bad VID in {:?}", s)))?;
+            let product_id = u16::from_str_radix(pid, 16)
+                .map_err(|_| PickError::InvalidSelector(format!("bad PID in {:?}", s)))?;
+            return Ok(DeviceSelector::VidPid {
+                vendor_id,
+                product_id,
+            });
+        }
+
+        Err(PickError::InvalidSelector(format!(
+            "unrecognized selector {:?}; expected vid:pid, serial=<s> or port=<p>",
+            s
+        )))
+    }
+
+    pub fn matches(&self, info: &UsbDeviceInfo) -> bool {
+        match self {
+            DeviceSelector::VidPid {
+                vendor_id,
+                product_id,
+            } => info.vendor_id == *vendor_id && info.product_id == *product_id,
+            DeviceSelector::Serial(serial) => info.serial_number.as_deref() == Some(serial),
+            DeviceSelector::Port(port) => info.port_path.as_deref() == Some(port),
+            DeviceSelector::Canonical(id) => CanonicalId::of(info) == *id,
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSelector::VidPid {
+                vendor_id,
+                product_id,
+            } => write!(f, "{:04x}:{:04x}", vendor_id, product_id),
+            DeviceSelector::Serial(s) => write!(f, "serial={}", s),
+            DeviceSelector::Port(p) => write!(f, "port={}", p),
+            DeviceSelector::Canonical(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+/// Validate "bus-port[.port...]" syntax, e.g. "3-1.4".
+fn parse_port_selector(port: &str) -> Result<DeviceSelector, PickError> {
+    let invalid = || PickError::InvalidSelector(format!("bad port path {:?}", port));
+    let (bus, chain) = port.split_once('-').ok_or_else(invalid)?;
+    if bus.is_empty() || !bus.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    if chain.is_empty()
+        || !chain
+            .split('.')
+            .all(|seg| !seg.is_empty() && seg.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(invalid());
+    }
+    Ok(DeviceSelector::Port(port.to_string()))
+}
+
+/// One row of the numbered device table.
+fn format_device_row(index: usize, info: &UsbDeviceInfo) -> String {
+    format!(
+        "[{}] {:04x}:{:04x}  {:<12}  {}",
+        index,
+        info.vendor_id,
+        info.product_id,
+        info.serial_number.as_deref().unwrap_or("-"),
+        info.product.as_deref().unwrap_or("(unnamed device)"),
+    )
+}
+
+/**
+ * Pick a device, preferring non-interactive resolution.
+ *
+ * With a selector: exactly one match is returned, zero is NoMatch and
+ * several are Ambiguous. Without one: a single connected device is
+ * auto-selected, otherwise the user picks from a numbered table.
+ */
+pub fn pick_device<'a>(
+    devices: &'a [UsbDeviceInfo],
+    prompt: &str,
+    filter: Option<&DeviceSelector>,
+) -> Result<&'a UsbDeviceInfo, PickError> {
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    pick_device_with_io(devices, prompt, filter, &mut stdin.lock(), &mut stdout)
+}
+
+/// Same as `pick_device` with injectable streams for tests.
+pub fn pick_device_with_io<'a, R: BufRead, W: Write>(
+    devices: &'a [UsbDeviceInfo],
+    prompt: &str,
+    filter: Option<&DeviceSelector>,
+    input: &mut R,
+    output: &mut W,
+) -> Result<&'a UsbDeviceInfo, PickError> {
+    if devices.is_empty() {
+        return Err(PickError::NoDevices);
+    }
+
+    let candidates: Vec<&UsbDeviceInfo> = match filter {
+        Some(selector) => devices.iter().filter(|d| selector.matches(d)).collect(),
+        None => devices.iter().collect(),
+    };
+
+    match (candidates.len(), filter.is_some()) {
+        (0, _) => return Err(PickError::NoMatch),
+        (1, _) => return Ok(candidates[0]),
+        (n, true) => return Err(PickError::Ambiguous(n)),
+        _ => {}
+    }
+
+    let _ = writeln!(output, "{}", prompt);
+    for (i, info) in candidates.iter().enumerate() {
+        let _ = writeln!(output, "{}", format_device_row(i + 1, info));
+    }
+    let _ = write!(output, "Select [1-{}]: ", candidates.len());
+    let _ = output.flush();
+
+    let mut line = String::new();
+    if input.read_line(&mut line).is_err() || line.is_empty() {
+        return Err(PickError::Cancelled);
+    }
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("q") {
+        return Err(PickError::Cancelled);
+    }
+
+    let choice: usize = trimmed
+        .parse()
+        .map_err(|_| PickError::InvalidInput(trimmed.to_string()))?;
+    if choice == 0 || choice > candidates.len() {
+        return Err(PickError::InvalidInput(trimmed.to_string()));
+    }
+
+    Ok(candidates[choice - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::version::BcdVersion;
+
+    fn device(vid: u16, pid: u16, serial: Option<&str>, port: Option<&str>) -> UsbDeviceInfo {
+        UsbDeviceInfo {
+            bus_number: 3,
+            address: 6,
+            vendor_id: vid,
+            product_id: pid,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0200),
+                device_version: BcdVersion(0x0100),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: None,
+            product: Some("Test Device".to_string()),
+            serial_number: serial.map(str::to_string),
+            port_path: port.map(str::to_string),
+            speed: None,
+            tags: Vec::new(),
+            active_config: None,
+            usb_ids: None,
+            container_id: None,
+        }
+    }
+
+    #[test]
+    fn test_selector_parse_vid_pid() {
+        assert_eq!(
+            DeviceSelector::parse("18d1:4ee7").unwrap(),
+            DeviceSelector::VidPid {
+                vendor_id: 0x18d1,
+                product_id: 0x4ee7
+            }
+        );
+        // short hex is fine
+        assert_eq!(
+            DeviceSelector::parse("5ac:12a8").unwrap(),
+            DeviceSelector::VidPid {
+                vendor_id: 0x05ac,
+                product_id: 0x12a8
+            }
+        );
+        assert!(DeviceSelector::parse("18d1:zzzz").is_err());
+        assert!(DeviceSelector::parse("fffff:0001").is_err());
+    }
+
+    #[test]
+    fn test_selector_parse_serial_and_port() {
+        assert_eq!(
+            DeviceSelector::parse("serial=ABC123").unwrap(),
+            DeviceSelector::Serial("ABC123".to_string())
+        );
+        assert!(DeviceSelector::parse("serial=").is_err());
+
+        assert_eq!(
+            DeviceSelector::parse("port=3-1.4").unwrap(),
+            DeviceSelector::Port("3-1.4".to_string())
+        );
+        assert_eq!(
+            DeviceSelector::parse("port=1-2").unwrap(),
+            DeviceSelector::Port("1-2".to_string())
+        );
+        for bad in ["port=", "port=3", "port=3-", "port=-1", "port=3-1..4", "port=a-b"] {
+            assert!(DeviceSelector::parse(bad).is_err(), "{} should fail", bad);
+        }
+    }
+
+    #[test]
+    fn test_selector_parse_canonical() {
+        let selector = DeviceSelector::parse("usb:18d1:4ee7:ABC123").unwrap();
+        let d = device(0x18d1, 0x4ee7, Some("ABC123"), Some("3-1.4"));
+        assert!(selector.matches(&d));
+        assert!(!selector.matches(&device(0x18d1, 0x4ee7, Some("XYZ"), None)));
+
+        // Port-form canonical IDs match serial-less devices only, since
+        // CanonicalId::of prefers the serial discriminator.
+        let selector = DeviceSelector::parse("usb:18d1:4ee7@3-1.4").unwrap();
+        assert!(selector.matches(&device(0x18d1, 0x4ee7, None, Some("3-1.4"))));
+        assert!(!selector.matches(&d));
+
+        assert!(DeviceSelector::parse("usb:18d1:4ee7").is_err());
+        assert!(DeviceSelector::parse("usb:18d1:4ee7:a b").is_err());
+    }
+
+    #[test]
+    fn test_selector_parse_garbage() {
+        assert!(DeviceSelector::parse("").is_err());
+        assert!(DeviceSelector::parse("whatever").is_err());
+    }
+
+    #[test]
+    fn test_selector_matching() {
+        let d = device(0x18d1, 0x4ee7, Some("ABC123"), Some("3-1.4"));
+        assert!(DeviceSelector::parse("18d1:4ee7").unwrap().matches(&d));
+        assert!(DeviceSelector::parse("serial=ABC123").unwrap().matches(&d));
+        assert!(DeviceSelector::parse("port=3-1.4").unwrap().matches(&d));
+        assert!(!DeviceSelector::parse("serial=XYZ").unwrap().matches(&d));
+        assert!(!DeviceSelector::parse("04e8:4ee7").unwrap().matches(&d));
+    }
+
+    #[test]
+    fn test_auto_select_single_filter_match() {
+        let devices = vec![
+            device(0x18d1, 0x4ee7, Some("A"), None),
+            device(0x04e8, 0x6860, Some("B"), None),
+        ];
+        let selector = DeviceSelector::parse("serial=B").unwrap();
+        let mut input = std::io::empty();
+        let mut output = Vec::new();
+        let picked = pick_device_with_io(
+            &devices,
+            "pick:",
+            Some(&selector),
+            &mut std::io::BufReader::new(&mut input),
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(picked.serial_number.as_deref(), Some("B"));
+        // fully non-interactive: nothing printed
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_filter_none_and_ambiguous() {
+        let devices = vec![
+            device(0x18d1, 0x4ee7, Some("A"), None),
+            device(0x18d1, 0x4ee7, Some("B"), None),
+        ];
+        let selector = DeviceSelector::parse("18d1:4ee7").unwrap();
+        let mut output = Vec::new();
+        let err = pick_device_with_io(
+            &devices,
+            "pick:",
+            Some(&selector),
+            &mut std::io::BufReader::new(std::io::empty()),
+            &mut output,
+        )
+        .unwrap_err();
+        assert_eq!(err, PickError::Ambiguous(2));
+
+        let none = DeviceSelector::parse("serial=missing").unwrap();
+        let err = pick_device_with_io(
+            &devices,
+            "pick:",
+            Some(&none),
+            &mut std::io::BufReader::new(std::io::empty()),
+            &mut output,
+        )
+        .unwrap_err();
+        assert_eq!(err, PickError::NoMatch);
+    }
+
+    #[test]
+    fn test_interactive_numbered_selection() {
+        let devices = vec![
+            device(0x18d1, 0x4ee7, Some("A"), None),
+            device(0x04e8, 0x6860, Some("B"), None),
+        ];
+        let mut input = std::io::BufReader::new(&b"2\n"[..]);
+        let mut output = Vec::new();
+        let picked = pick_device_with_io(&devices, "pick:", None, &mut input, &mut output).unwrap();
+        assert_eq!(picked.serial_number.as_deref(), Some("B"));
+
+        let printed = String::from_utf8(output).unwrap();
+        assert!(printed.contains("[1] 18d1:4ee7"));
+        assert!(printed.contains("[2] 04e8:6860"));
+    }
+
+    #[test]
+    fn test_interactive_invalid_and_cancel() {
+        let devices = vec![
+            device(0x18d1, 0x4ee7, Some("A"), None),
+            device(0x04e8, 0x6860, Some("B"), None),
+        ];
+        let mut output = Vec::new();
+
+        let mut input = std::io::BufReader::new(&b"7\n"[..]);
+        assert_eq!(
+            pick_device_with_io(&devices, "p", None, &mut input, &mut output).unwrap_err(),
+            PickError::InvalidInput("7".to_string())
+        );
+
+        let mut input = std::io::BufReader::new(&b"q\n"[..]);
+        assert_eq!(
+            pick_device_with_io(&devices, "p", None, &mut input, &mut output).unwrap_err(),
+            PickError::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_no_devices() {
+        let mut output = Vec::new();
+        assert_eq!(
+            pick_device_with_io(
+                &[],
+                "p",
+                None,
+                &mut std::io::BufReader::new(std::io::empty()),
+                &mut output
+            )
+            .unwrap_err(),
+            PickError::NoDevices
+        );
+    }
+}
@@ -0,0 +1,920 @@
+// BootForge USB - Hub and port enumeration
+// Hubs are enumerated like any device, then asked for their class
+// descriptor (type 0x29 on USB 2.0, 0x2a on USB 3.x) so the topology
+// carries real port counts instead of a guess. Hubs we cannot open -
+// commonly a permissions problem on root hubs - keep the default count.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::context::SharedContext;
+use crate::enumeration::{enumerate_libusb, UsbDeviceInfo};
+use crate::error::UsbError;
+use crate::transfer::UsbTransport;
+use crate::version::BcdVersion;
+
+/// Port count assumed for hubs whose descriptor could not be read.
+pub const DEFAULT_HUB_PORTS: u8 = 4;
+
+/// Device class of hubs.
+const HUB_CLASS: u8 = 0x09;
+/// Hub class descriptor types: USB 2.0 and USB 3.x (enhanced SuperSpeed).
+const HUB_DESCRIPTOR_TYPE_USB2: u8 = 0x29;
+const HUB_DESCRIPTOR_TYPE_USB3: u8 = 0x2a;
+/// GET_DESCRIPTOR, class, device-to-host.
+const REQ_GET_DESCRIPTOR: u8 = 0x06;
+const REQUEST_TYPE_CLASS_IN: u8 = 0xa0;
+
+const HUB_READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long a power-cycled port stays off before power is restored.
+pub const POWER_CYCLE_OFF_TIME: Duration = Duration::from_millis(500);
+
+/**
+ * Downstream-facing power switching mode from wHubCharacteristics.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PowerSwitching {
+    /// All ports power on and off together.
+    Ganged,
+    /// Each port is switched individually.
+    PerPort,
+    /// No switching; ports are powered whenever the hub is (USB 1.x).
+    NoSwitching,
+}
+
+/**
+ * One downstream port of an enumerated hub.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UsbPort {
+    /// Port number, 1-based as on the wire.
+    pub number: u8,
+    /// Sysfs-style path a device plugged here would carry, extending
+    /// the hub's own chain: "3-1.4" for port 4 of hub "3-1".
+    pub path: String,
+}
+
+/**
+ * A parsed sysfs-style port path. Accepts the three forms udev and
+ * sysfs hand back:
+ *
+ * - `B-P.P.P` - a device at the end of a port chain ("3-1.4");
+ * - `B-P.P.P:C.I` - one interface of that device, with the
+ *   configuration and interface numbers ("3-1.4:1.0");
+ * - `usbB` - a root hub, which sits on no port (empty chain).
+ *
+ * `Display` regenerates the canonical string, so parse and format
+ * round-trip.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct PortPath {
+    pub bus: u8,
+    /// Hub port chain from the controller down, 1-based as on the wire;
+    /// empty for a root hub.
+    pub ports: Vec<u8>,
+    /// (bConfigurationValue, bInterfaceNumber) when the path named one
+    /// interface rather than the device.
+    pub config_interface: Option<(u8, u8)>,
+}
+
+impl PortPath {
+    /// Parse any of the accepted forms; malformed input reports the
+    /// offending component, not just "bad path".
+    pub fn parse(raw: &str) -> Result<Self, UsbError> {
+        let bad = |component: &str| {
+            UsbError::Parse(format!(
+                "port path {:?}: bad component {:?}",
+                raw, component
+            ))
+        };
+        if let Some(bus) = raw.strip_prefix("usb") {
+            return Ok(PortPath {
+                bus: bus.parse().map_err(|_| bad(bus))?,
+                ports: Vec::new(),
+                config_interface: None,
+            });
+        }
+        let (chain, suffix) = match raw.split_once(':') {
+            Some((chain, suffix)) => (chain, Some(suffix)),
+            None => (raw, None),
+        };
+        let (bus, ports) = chain.split_once('-').ok_or_else(|| bad(chain))?;
+        let bus = bus.parse().map_err(|_| bad(bus))?;
+        let ports = ports
+            .split('.')
+            .map(|p| p.parse().map_err(|_| bad(p)))
+            .collect::<Result<Vec<u8>, _>>()?;
+        let config_interface = match suffix {
+            Some(suffix) => {
+                let (config, interface) = suffix.split_once('.').ok_or_else(|| bad(suffix))?;
+                Some((
+                    config.parse().map_err(|_| bad(config))?,
+                    interface.parse().map_err(|_| bad(interface))?,
+                ))
+            }
+            None => None,
+        };
+        Ok(PortPath {
+            bus,
+            ports,
+            config_interface,
+        })
+    }
+
+    /// A root hub: the controller itself, on no downstream port.
+    pub fn is_root_hub(&self) -> bool {
+        self.ports.is_empty()
+    }
+}
+
+impl fmt::Display for PortPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root_hub() && self.config_interface.is_none() {
+            return write!(f, "usb{}", self.bus);
+        }
+        let chain: Vec<String> = self.ports.iter().map(u8::to_string).collect();
+        write!(f, "{}-{}", self.bus, chain.join("."))?;
+        if let Some((config, interface)) = self.config_interface {
+            write!(f, ":{}.{}", config, interface)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * A hub with its class-descriptor detail. The descriptor fields are
+ * None when the hub could not be opened (typically permissions) and the
+ * port count fell back to `DEFAULT_HUB_PORTS`.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UsbHub {
+    pub bus_number: u8,
+    pub address: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub num_ports: u8,
+    pub ports: Vec<UsbPort>,
+    pub power_switching: Option<PowerSwitching>,
+    /// Hub is part of a compound device (bit 2 of wHubCharacteristics).
+    pub compound: Option<bool>,
+}
+
+/**
+ * The parsed hub class descriptor, common prefix of the 2.0 and 3.x
+ * layouts: bNbrPorts and wHubCharacteristics.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HubDescriptor {
+    pub num_ports: u8,
+    pub power_switching: PowerSwitching,
+    pub compound: bool,
+}
+
+impl HubDescriptor {
+    /// Parse the raw descriptor bytes (bDescLength, bDescriptorType first).
+    pub fn parse(raw: &[u8]) -> Result<Self, UsbError> {
+        if raw.len() < 5 || usize::from(raw[0]) < 5 {
+            return Err(UsbError::Parse(format!(
+                "hub descriptor too short: {} bytes",
+                raw.len()
+            )));
+        }
+        if raw[1] != HUB_DESCRIPTOR_TYPE_USB2 && raw[1] != HUB_DESCRIPTOR_TYPE_USB3 {
+            return Err(UsbError::Parse(format!(
+                "not a hub descriptor: type 0x{:02x}",
+                raw[1]
+            )));
+        }
+        let characteristics = u16::from_le_bytes([raw[3], raw[4]]);
+        Ok(HubDescriptor {
+            num_ports: raw[2],
+            power_switching: match characteristics & 0x0003 {
+                0 => PowerSwitching::Ganged,
+                1 => PowerSwitching::PerPort,
+                _ => PowerSwitching::NoSwitching,
+            },
+            compound: characteristics & 0x0004 != 0,
+        })
+    }
+}
+
+/**
+ * Read and parse the hub class descriptor over `transport`. `usb3`
+ * selects the enhanced SuperSpeed descriptor type.
+ */
+pub fn read_hub_descriptor<T: UsbTransport>(
+    transport: &mut T,
+    usb3: bool,
+) -> Result<HubDescriptor, UsbError> {
+    let descriptor_type = if usb3 {
+        HUB_DESCRIPTOR_TYPE_USB3
+    } else {
+        HUB_DESCRIPTOR_TYPE_USB2
+    };
+    let mut buf = [0u8; 71];
+    let n = transport
+        .read_control(
+            REQUEST_TYPE_CLASS_IN,
+            REQ_GET_DESCRIPTOR,
+            u16::from(descriptor_type) << 8,
+            0,
+            &mut buf,
+            HUB_READ_TIMEOUT,
+        )
+        .map_err(crate::error::classify_transfer_error)?;
+    HubDescriptor::parse(&buf[..n])
+}
+
+/**
+ * Enumerate hubs with their real port counts where readable.
+ */
+pub fn enumerate_hubs() -> Result<Vec<UsbHub>, UsbError> {
+    enumerate_hubs_inner(&rusb::GlobalContext::default())
+}
+
+/**
+ * As `enumerate_hubs`, but on a caller-owned context.
+ */
+pub fn enumerate_hubs_in(context: &SharedContext) -> Result<Vec<UsbHub>, UsbError> {
+    context.with_context(enumerate_hubs_inner)?
+}
+
+fn enumerate_hubs_inner<C: rusb::UsbContext>(context: &C) -> Result<Vec<UsbHub>, UsbError> {
+    let mut hubs = Vec::new();
+    for device in context.devices()?.iter() {
+        let descriptor = match device.device_descriptor() {
+            Ok(d) if d.class_code() == HUB_CLASS => d,
+            _ => continue,
+        };
+
+        let usb3 = BcdVersion::from(descriptor.usb_version()) >= BcdVersion(0x0300);
+        let detail = device
+            .open()
+            .map_err(UsbError::from)
+            .and_then(|mut handle| read_hub_descriptor(&mut handle, usb3));
+        if let Err(e) = &detail {
+            log::debug!(
+                "hub {:04x}:{:04x}: descriptor unreadable, assuming {} ports: {}",
+                descriptor.vendor_id(),
+                descriptor.product_id(),
+                DEFAULT_HUB_PORTS,
+                e
+            );
+        }
+        let detail = detail.ok();
+
+        let num_ports = detail.map_or(DEFAULT_HUB_PORTS, |d| d.num_ports);
+        hubs.push(UsbHub {
+            bus_number: device.bus_number(),
+            address: device.address(),
+            vendor_id: descriptor.vendor_id(),
+            product_id: descriptor.product_id(),
+            num_ports,
+            ports: downstream_ports(hub_chain(&device), num_ports),
+            power_switching: detail.map(|d| d.power_switching),
+            compound: detail.map(|d| d.compound),
+        });
+    }
+    Ok(hubs)
+}
+
+/// The path prefix downstream devices extend: the hub's own port chain,
+/// or "{bus}" for root hubs.
+fn hub_chain<C: rusb::UsbContext>(device: &rusb::Device<C>) -> String {
+    crate::enumeration::port_path(device)
+        .unwrap_or_else(|| device.bus_number().to_string())
+}
+
+fn downstream_ports(chain: String, num_ports: u8) -> Vec<UsbPort> {
+    // Root hub children are "3-1"; deeper levels join with '.'.
+    let separator = if chain.contains('-') { '.' } else { '-' };
+    (1..=num_ports)
+        .map(|number| UsbPort {
+            number,
+            path: format!("{}{}{}", chain, separator, number),
+        })
+        .collect()
+}
+
+/**
+ * Cut and restore power on one downstream port of `hub` (Linux).
+ *
+ * Drives the kernel's per-port power control: writing the port device's
+ * `disable` attribute under sysfs, which clears and re-sets PORT_POWER
+ * on hubs that switch per port. The port stays off for
+ * `POWER_CYCLE_OFF_TIME` so the device sees a real power gap. Requires
+ * root; a refusal comes back as `PermissionDenied`, and kernels without
+ * the attribute (pre-5.9) as `Unsupported`.
+ */
+#[cfg(target_os = "linux")]
+pub fn power_cycle(hub: &UsbHub, port: u8) -> Result<(), UsbError> {
+    power_cycle_in(
+        Path::new("/sys/bus/usb/devices"),
+        hub,
+        port,
+        POWER_CYCLE_OFF_TIME,
+    )
+}
+
+/**
+ * As `power_cycle`, against an explicit sysfs devices root and off
+ * time. Un-gated - like the `linux` module's enrichment helpers - so
+ * the suite can exercise it on any host.
+ */
+pub fn power_cycle_in(
+    sysfs_root: &Path,
+    hub: &UsbHub,
+    port: u8,
+    off_time: Duration,
+) -> Result<(), UsbError> {
+    let chain = hub_chain_of(hub);
+    // Root hubs are named "usbB" in sysfs; their port devices extend
+    // that name ("usb3-port1"), external hubs their chain ("3-1-port4").
+    let device_name = if chain.contains('-') {
+        chain
+    } else {
+        format!("usb{}", chain)
+    };
+    let device_dir = sysfs_root.join(&device_name);
+    if !device_dir.is_dir() {
+        return Err(UsbError::NotFound(format!(
+            "hub {} not in sysfs",
+            device_name
+        )));
+    }
+
+    // The port device sits under the hub's interface directory; scan
+    // for it rather than hardcode ":1.0" so alternate configurations
+    // still resolve.
+    let port_name = format!("{}-port{}", device_name, port);
+    let port_dir = fs::read_dir(&device_dir)?
+        .flatten()
+        .map(|entry| entry.path().join(&port_name))
+        .find(|candidate| candidate.is_dir())
+        .ok_or_else(|| {
+            UsbError::NotFound(format!("hub {} has no port {}", device_name, port))
+        })?;
+
+    let disable = port_dir.join("disable");
+    if !disable.is_file() {
+        return Err(UsbError::Unsupported(format!(
+            "kernel exposes no per-port power control at {}",
+            disable.display()
+        )));
+    }
+
+    write_port_attr(&disable, "1")?;
+    std::thread::sleep(off_time);
+    write_port_attr(&disable, "0")
+}
+
+fn write_port_attr(path: &Path, value: &str) -> Result<(), UsbError> {
+    fs::write(path, value).map_err(|e| match e.kind() {
+        ErrorKind::PermissionDenied => UsbError::PermissionDenied(format!(
+            "writing {} requires elevated privileges",
+            path.display()
+        )),
+        _ => UsbError::Io(e),
+    })
+}
+
+/**
+ * A leaf device in the assembled topology, with the downstream port it
+ * occupies on its parent hub.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttachedDevice {
+    pub port: UsbPort,
+    pub info: UsbDeviceInfo,
+}
+
+/**
+ * A hub in the assembled topology: the hub itself, the leaf devices on
+ * its ports, and any hubs cascaded below it.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TopologyHub {
+    pub hub: UsbHub,
+    pub devices: Vec<AttachedDevice>,
+    pub children: Vec<TopologyHub>,
+}
+
+/**
+ * The bus tree joining `enumerate_libusb` and `enumerate_hubs`: root
+ * hubs at the top (one per controller), cascaded hubs below them, leaf
+ * devices on the ports they occupy. Devices whose port chain matched no
+ * enumerated hub - notification-driven partial records, hubs we could
+ * not see - land in `orphans` rather than being dropped.
+ *
+ * The `Display` impl renders an lsusb -t style tree.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsbTopology {
+    pub roots: Vec<TopologyHub>,
+    pub orphans: Vec<UsbDeviceInfo>,
+}
+
+impl UsbTopology {
+    /// The device at a bus position, wherever it sits in the tree
+    /// (orphans included).
+    pub fn find_device(&self, bus: u8, address: u8) -> Option<&UsbDeviceInfo> {
+        fn walk(node: &TopologyHub, bus: u8, address: u8) -> Option<&UsbDeviceInfo> {
+            node.devices
+                .iter()
+                .map(|attached| &attached.info)
+                .find(|info| info.bus_number == bus && info.address == address)
+                .or_else(|| node.children.iter().find_map(|c| walk(c, bus, address)))
+        }
+        self.roots
+            .iter()
+            .find_map(|root| walk(root, bus, address))
+            .or_else(|| {
+                self.orphans
+                    .iter()
+                    .find(|info| info.bus_number == bus && info.address == address)
+            })
+    }
+
+    /// The hubs between the controller and `device`, root hub first.
+    /// Empty when the device is not in the tree (or is an orphan).
+    pub fn path_to(&self, device: &UsbDeviceInfo) -> Vec<&UsbHub> {
+        fn locate<'a>(
+            node: &'a TopologyHub,
+            bus: u8,
+            address: u8,
+            trail: &mut Vec<&'a UsbHub>,
+        ) -> bool {
+            trail.push(&node.hub);
+            if node
+                .devices
+                .iter()
+                .any(|a| a.info.bus_number == bus && a.info.address == address)
+            {
+                return true;
+            }
+            for child in &node.children {
+                if locate(child, bus, address, trail) {
+                    return true;
+                }
+            }
+            trail.pop();
+            false
+        }
+        let mut trail = Vec::new();
+        for root in &self.roots {
+            if locate(root, device.bus_number, device.address, &mut trail) {
+                return trail;
+            }
+        }
+        Vec::new()
+    }
+}
+
+impl fmt::Display for UsbTopology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_hub(f: &mut fmt::Formatter<'_>, node: &TopologyHub, depth: usize) -> fmt::Result {
+            let indent = "    ".repeat(depth);
+            if depth == 0 {
+                writeln!(
+                    f,
+                    "/:  Bus {:02}: Dev {}, {:04x}:{:04x} hub, {} ports",
+                    node.hub.bus_number,
+                    node.hub.address,
+                    node.hub.vendor_id,
+                    node.hub.product_id,
+                    node.hub.num_ports
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "{}|__ Port {}: Dev {}, {:04x}:{:04x} hub, {} ports",
+                    indent,
+                    port_number_of(&hub_chain_of(&node.hub)),
+                    node.hub.address,
+                    node.hub.vendor_id,
+                    node.hub.product_id,
+                    node.hub.num_ports
+                )?;
+            }
+            let indent = "    ".repeat(depth + 1);
+            for attached in &node.devices {
+                write!(
+                    f,
+                    "{}|__ Port {}: Dev {}, {:04x}:{:04x}",
+                    indent,
+                    attached.port.number,
+                    attached.info.address,
+                    attached.info.vendor_id,
+                    attached.info.product_id
+                )?;
+                if let Some(product) = &attached.info.product {
+                    write!(f, " {}", product)?;
+                }
+                if let Some(speed) = attached.info.speed {
+                    write!(f, ", {}", speed)?;
+                }
+                writeln!(f)?;
+            }
+            for child in &node.children {
+                write_hub(f, child, depth + 1)?;
+            }
+            Ok(())
+        }
+
+        for root in &self.roots {
+            write_hub(f, root, 0)?;
+        }
+        for orphan in &self.orphans {
+            writeln!(f, "orphan: {}", orphan)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Enumerate devices and hubs, then join them into a tree. Best effort
+ * like `capture_snapshot`: a device behind a hub we could not enumerate
+ * is reported as an orphan, not an error.
+ */
+pub fn build_topology() -> Result<UsbTopology, UsbError> {
+    Ok(assemble_topology(enumerate_libusb()?, enumerate_hubs()?))
+}
+
+/**
+ * Join already-enumerated device and hub lists into a tree. Matching
+ * runs entirely on the sysfs-style port chains both enumerations carry,
+ * so synthetic lists assemble the same way live ones do.
+ */
+pub fn assemble_topology(devices: Vec<UsbDeviceInfo>, hubs: Vec<UsbHub>) -> UsbTopology {
+    let mut nodes: BTreeMap<String, TopologyHub> = BTreeMap::new();
+    for hub in hubs {
+        nodes.insert(
+            hub_chain_of(&hub),
+            TopologyHub {
+                hub,
+                devices: Vec::new(),
+                children: Vec::new(),
+            },
+        );
+    }
+    let hub_positions: HashSet<(u8, u8)> = nodes
+        .values()
+        .map(|n| (n.hub.bus_number, n.hub.address))
+        .collect();
+
+    let mut orphans = Vec::new();
+    for info in devices {
+        // Hubs appear in both enumerations; the hub entry is
+        // authoritative here.
+        if hub_positions.contains(&(info.bus_number, info.address)) {
+            continue;
+        }
+        let parent = info.port_path.as_deref().and_then(parent_chain);
+        let Some(node) = parent.and_then(|p| nodes.get_mut(&p)) else {
+            orphans.push(info);
+            continue;
+        };
+        let path = info.port_path.clone().unwrap_or_default();
+        // Ports past a defaulted count are not in the hub's list; mint
+        // the entry so the device still hangs off the right hub.
+        let port = node
+            .hub
+            .ports
+            .iter()
+            .find(|p| p.path == path)
+            .cloned()
+            .unwrap_or_else(|| UsbPort {
+                number: port_number_of(&path),
+                path,
+            });
+        node.devices.push(AttachedDevice { port, info });
+    }
+
+    // Deepest chains first, so every hub is complete before it moves
+    // under its parent. A cascaded hub whose parent was not enumerated
+    // surfaces as a root rather than disappearing.
+    let mut chains: Vec<String> = nodes.keys().cloned().collect();
+    chains.sort_by_key(|c| std::cmp::Reverse(chain_depth(c)));
+    let mut roots = Vec::new();
+    for chain in chains {
+        let Some(mut node) = nodes.remove(&chain) else {
+            continue;
+        };
+        node.devices.sort_by_key(|a| a.port.number);
+        node.children.sort_by_key(|c| c.hub.address);
+        match parent_chain(&chain).and_then(|p| nodes.get_mut(&p)) {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+    roots.sort_by_key(|n| (n.hub.bus_number, n.hub.address));
+    UsbTopology { roots, orphans }
+}
+
+/// The port chain a hub's downstream paths extend, recovered from its
+/// first port ("3-1.4" -> "3-1"); root hubs reduce to the bus number.
+fn hub_chain_of(hub: &UsbHub) -> String {
+    hub.ports
+        .first()
+        .and_then(|p| parent_chain(&p.path))
+        .unwrap_or_else(|| hub.bus_number.to_string())
+}
+
+/// The chain of the hub one level up: "3-1.4" -> "3-1", "3-1" -> "3",
+/// "3" -> None (a root hub has no parent).
+fn parent_chain(path: &str) -> Option<String> {
+    if let Some((head, _)) = path.rsplit_once('.') {
+        Some(head.to_string())
+    } else {
+        path.split_once('-').map(|(bus, _)| bus.to_string())
+    }
+}
+
+/// Number of separators, i.e. how many hops below the controller.
+fn chain_depth(chain: &str) -> usize {
+    chain.matches(['-', '.']).count()
+}
+
+/// The final port number of a chain; 0 when the tail is not numeric.
+fn port_number_of(path: &str) -> u8 {
+    path.rsplit(['-', '.'])
+        .next()
+        .and_then(|tail| tail.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    // 7-port USB 2.0 hub: wHubCharacteristics 0x00e5 = per-port power
+    // switching (bits 1:0 = 01), compound device (bit 2).
+    const SEVEN_PORT_HUB: &[u8] = &[9, 0x29, 7, 0xe5, 0x00, 50, 100, 0x00, 0xff];
+
+    #[test]
+    fn test_parse_canned_descriptor() {
+        let parsed = HubDescriptor::parse(SEVEN_PORT_HUB).unwrap();
+        assert_eq!(parsed.num_ports, 7);
+        assert_eq!(parsed.power_switching, PowerSwitching::PerPort);
+        assert!(parsed.compound);
+    }
+
+    #[test]
+    fn test_parse_usb3_and_ganged() {
+        // USB 3.x layout shares the prefix; ganged switching, not compound.
+        let parsed = HubDescriptor::parse(&[12, 0x2a, 4, 0x00, 0x00, 10, 0, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(parsed.num_ports, 4);
+        assert_eq!(parsed.power_switching, PowerSwitching::Ganged);
+        assert!(!parsed.compound);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_hub_descriptors() {
+        assert!(HubDescriptor::parse(&[9, 0x02, 7, 0xe5, 0x00]).is_err());
+        assert!(HubDescriptor::parse(&[2, 0x29]).is_err());
+    }
+
+    #[test]
+    fn test_read_request_shape() {
+        let mut transport = MockTransport::new();
+        transport
+            .control_read_results
+            .push_back(Ok(SEVEN_PORT_HUB.to_vec()));
+        let parsed = read_hub_descriptor(&mut transport, false).unwrap();
+        assert_eq!(parsed.num_ports, 7);
+
+        let req = &transport.control_requests[0];
+        assert_eq!(req.request_type, 0xa0);
+        assert_eq!(req.request, 0x06);
+        assert_eq!(req.value, 0x2900);
+        assert_eq!(req.index, 0);
+    }
+
+    #[test]
+    fn test_downstream_port_paths() {
+        let ports = downstream_ports("3-1".to_string(), 2);
+        assert_eq!(ports[1].path, "3-1.2");
+        let root = downstream_ports("3".to_string(), 2);
+        assert_eq!(root[0].path, "3-1");
+    }
+
+    #[test]
+    fn test_port_path_round_trips() {
+        // Real-world shapes: root hubs, the udev interface-suffix form,
+        // and chains deep enough to hit the USB tier limit.
+        let table = [
+            "usb1",
+            "usb3",
+            "1-1",
+            "3-1.4",
+            "3-0:1.0",
+            "2-1.2:1.0",
+            "1-1:2.0",
+            "5-2.1.3.4.2",
+            "2-1.2.3.4.5.6",
+            "4-4.4.4.4.4:1.1",
+            "9-1.10.11",
+            "255-1.2",
+        ];
+        for raw in table {
+            let parsed = PortPath::parse(raw).unwrap_or_else(|e| panic!("{}: {}", raw, e));
+            assert_eq!(parsed.to_string(), raw, "round trip of {:?}", raw);
+        }
+
+        let deep = PortPath::parse("2-1.2.3.4.5.6").unwrap();
+        assert_eq!((deep.bus, deep.ports.len()), (2, 6));
+        assert!(!deep.is_root_hub());
+        let root = PortPath::parse("usb3").unwrap();
+        assert!(root.is_root_hub() && root.ports.is_empty());
+        let iface = PortPath::parse("3-1.4:1.0").unwrap();
+        assert_eq!(iface.config_interface, Some((1, 0)));
+    }
+
+    #[test]
+    fn test_port_path_rejects_garbage_with_component() {
+        for raw in ["", "abc", "3-", "3-1..2", "3-x.4", "usb", "usbx", "3-1:9", "3-1:1.y", "300-1"] {
+            let err = PortPath::parse(raw).unwrap_err();
+            assert!(
+                matches!(&err, UsbError::Parse(msg) if msg.contains(raw)),
+                "{:?} -> {}",
+                raw,
+                err
+            );
+        }
+        // The message names the component that failed, not just the path.
+        let err = PortPath::parse("3-x.4").unwrap_err();
+        assert!(err.to_string().contains("\"x\""), "{}", err);
+    }
+
+    /// A synthetic hub whose downstream paths extend `chain`.
+    fn hub(bus: u8, address: u8, chain: &str, num_ports: u8) -> UsbHub {
+        UsbHub {
+            bus_number: bus,
+            address,
+            vendor_id: 0x2109,
+            product_id: 0x0817,
+            num_ports,
+            ports: downstream_ports(chain.to_string(), num_ports),
+            power_switching: None,
+            compound: None,
+        }
+    }
+
+    fn device(bus: u8, address: u8, port_path: Option<&str>, product: &str) -> UsbDeviceInfo {
+        let mut info = crate::watch::partial_info(
+            0x18d1,
+            0x4ee7,
+            None,
+            port_path.map(str::to_string),
+            "test:topology".to_string(),
+        );
+        info.bus_number = bus;
+        info.address = address;
+        info.product = Some(product.to_string());
+        info
+    }
+
+    #[test]
+    fn test_assemble_nested_tree() {
+        let devices = vec![
+            // The child hub also shows up in the device list; the hub
+            // entry must win.
+            device(3, 2, Some("3-1"), "USB2.0 Hub"),
+            device(3, 5, Some("3-1.4"), "Pixel 7"),
+            device(3, 6, Some("3-2"), "Ultra Fit"),
+            device(9, 3, Some("9-1"), "Behind an unseen hub"),
+            device(0, 0, None, "Partial record"),
+        ];
+        let hubs = vec![hub(3, 1, "3", 4), hub(3, 2, "3-1", 4)];
+        let topology = assemble_topology(devices, hubs);
+
+        assert_eq!(topology.roots.len(), 1);
+        let root = &topology.roots[0];
+        assert_eq!(root.hub.address, 1);
+        assert_eq!(root.devices.len(), 1);
+        assert_eq!(root.devices[0].port.number, 2);
+        assert_eq!(root.children.len(), 1);
+        let child = &root.children[0];
+        assert_eq!(child.hub.address, 2);
+        assert_eq!(child.devices[0].port.path, "3-1.4");
+        assert_eq!(topology.orphans.len(), 2);
+
+        let pixel = topology.find_device(3, 5).unwrap();
+        assert_eq!(pixel.product.as_deref(), Some("Pixel 7"));
+        let trail: Vec<u8> = topology.path_to(pixel).iter().map(|h| h.address).collect();
+        assert_eq!(trail, vec![1, 2]);
+
+        // Orphans are findable but have no hub path.
+        let orphan = topology.find_device(9, 3).unwrap();
+        assert!(topology.path_to(orphan).is_empty());
+    }
+
+    #[test]
+    fn test_assemble_mints_ports_and_promotes_parentless_hubs() {
+        // The child hub's descriptor was unreadable, so its port list
+        // stops at the default; a device on port 6 still attaches.
+        let devices = vec![device(3, 5, Some("3-1.6"), "Keyboard")];
+        let topology = assemble_topology(devices, vec![hub(3, 2, "3-1", DEFAULT_HUB_PORTS)]);
+
+        // No root hub was enumerated: the cascaded hub surfaces as a root.
+        assert_eq!(topology.roots.len(), 1);
+        let node = &topology.roots[0];
+        assert_eq!(node.devices[0].port, UsbPort {
+            number: 6,
+            path: "3-1.6".to_string(),
+        });
+        assert!(topology.orphans.is_empty());
+    }
+
+    #[test]
+    fn test_topology_display_renders_tree() {
+        let mut pixel = device(3, 5, Some("3-1.4"), "Pixel 7");
+        pixel.speed = Some(crate::topology::Speed::High);
+        let topology = assemble_topology(
+            vec![pixel, device(3, 6, Some("3-2"), "Ultra Fit")],
+            vec![hub(3, 1, "3", 4), hub(3, 2, "3-1", 4)],
+        );
+
+        let rendered = topology.to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "/:  Bus 03: Dev 1, 2109:0817 hub, 4 ports");
+        assert_eq!(lines[1], "    |__ Port 2: Dev 6, 18d1:4ee7 Ultra Fit");
+        assert_eq!(lines[2], "    |__ Port 1: Dev 2, 2109:0817 hub, 4 ports");
+        assert_eq!(
+            lines[3],
+            "        |__ Port 4: Dev 5, 18d1:4ee7 Pixel 7, 480 Mbps High Speed"
+        );
+    }
+
+    /// A throwaway sysfs devices root with one hub interface directory.
+    fn sysfs_with_port(test: &str, device_name: &str, port_dir: Option<&str>) -> std::path::PathBuf {
+        let root = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join(test)
+            .join(format!("{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let interface = root.join(device_name).join(format!(
+            "{}:1.0",
+            match device_name.strip_prefix("usb") {
+                Some(bus) => format!("{}-0", bus),
+                None => device_name.to_string(),
+            }
+        ));
+        std::fs::create_dir_all(&interface).unwrap();
+        if let Some(port_dir) = port_dir {
+            std::fs::create_dir_all(interface.join(port_dir)).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_power_cycle_writes_disable_attr() {
+        let root = sysfs_with_port("power_cycle", "3-1", Some("3-1-port4"));
+        let disable = root.join("3-1/3-1:1.0/3-1-port4/disable");
+        std::fs::write(&disable, "0\n").unwrap();
+
+        power_cycle_in(&root, &hub(3, 2, "3-1", 4), 4, Duration::ZERO).unwrap();
+        // The port ends the cycle re-enabled.
+        assert_eq!(std::fs::read_to_string(&disable).unwrap(), "0");
+    }
+
+    #[test]
+    fn test_power_cycle_resolves_root_hub_naming() {
+        let root = sysfs_with_port("power_cycle_root", "usb3", Some("usb3-port1"));
+        let disable = root.join("usb3/3-0:1.0/usb3-port1/disable");
+        std::fs::write(&disable, "0\n").unwrap();
+
+        power_cycle_in(&root, &hub(3, 1, "3", 4), 1, Duration::ZERO).unwrap();
+        assert_eq!(std::fs::read_to_string(&disable).unwrap(), "0");
+    }
+
+    #[test]
+    fn test_power_cycle_without_attr_is_unsupported() {
+        // Port device present but no `disable` attribute: old kernel.
+        let root = sysfs_with_port("power_cycle_old", "3-1", Some("3-1-port4"));
+        assert!(matches!(
+            power_cycle_in(&root, &hub(3, 2, "3-1", 4), 4, Duration::ZERO),
+            Err(UsbError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_power_cycle_unknown_hub_or_port_is_not_found() {
+        let root = sysfs_with_port("power_cycle_absent", "3-1", None);
+        assert!(matches!(
+            power_cycle_in(&root, &hub(3, 2, "3-1", 4), 4, Duration::ZERO),
+            Err(UsbError::NotFound(message)) if message.contains("port 4")
+        ));
+        assert!(matches!(
+            power_cycle_in(&root, &hub(2, 2, "2-3", 4), 1, Duration::ZERO),
+            Err(UsbError::NotFound(message)) if message.contains("2-3")
+        ));
+    }
+}
@@ -0,0 +1,647 @@
+// BootForge USB - ADB transport layer
+// The ADB wire protocol over bulk endpoints: 24-byte message headers,
+// the CNXN/AUTH handshake, and OPEN/OKAY/WRTE/CLSE stream multiplexing.
+// Authentication is delegated to the caller through a signer callback -
+// this crate does not hold keys.
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::error::UsbError;
+use crate::transfer::{BulkTransfer, UsbTransport};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Protocol version and our maximum payload, sent in CNXN.
+const ADB_VERSION: u32 = 0x0100_0001;
+const MAX_DATA: u32 = 0x0010_0000;
+
+/// AUTH subtypes (arg0).
+const AUTH_TOKEN: u32 = 1;
+const AUTH_SIGNATURE: u32 = 2;
+const AUTH_RSAPUBLICKEY: u32 = 3;
+
+/// Give up after this many AUTH round trips; a device that keeps
+/// issuing tokens has rejected every credential we can offer.
+const MAX_AUTH_ATTEMPTS: u32 = 3;
+
+const HEADER_LEN: usize = 24;
+
+#[derive(Debug, Error)]
+pub enum AdbError {
+    #[error("malformed message: {0}")]
+    Malformed(String),
+
+    #[error("payload checksum mismatch (header {expected:08x}, computed {actual:08x})")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error("device rejected authentication")]
+    AuthRejected,
+
+    #[error("device closed stream to {destination:?}")]
+    StreamRefused { destination: String },
+
+    #[error("unexpected {got:?} while waiting for {expected:?}")]
+    UnexpectedCommand { expected: AdbCommand, got: AdbCommand },
+
+    #[error(transparent)]
+    Usb(#[from] UsbError),
+}
+
+/**
+ * The six message types of the transport layer.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdbCommand {
+    Sync,
+    Connect,
+    Auth,
+    Open,
+    Okay,
+    Write,
+    Close,
+}
+
+impl AdbCommand {
+    pub fn as_u32(self) -> u32 {
+        // The command word is the ASCII name read as a little-endian
+        // u32: "CNXN" on the wire is 0x4e584e43.
+        match self {
+            AdbCommand::Sync => 0x434e_5953,
+            AdbCommand::Connect => 0x4e58_4e43,
+            AdbCommand::Auth => 0x4854_5541,
+            AdbCommand::Open => 0x4e45_504f,
+            AdbCommand::Okay => 0x5941_4b4f,
+            AdbCommand::Write => 0x4554_5257,
+            AdbCommand::Close => 0x4553_4c43,
+        }
+    }
+
+    pub fn from_u32(raw: u32) -> Option<Self> {
+        [
+            AdbCommand::Sync,
+            AdbCommand::Connect,
+            AdbCommand::Auth,
+            AdbCommand::Open,
+            AdbCommand::Okay,
+            AdbCommand::Write,
+            AdbCommand::Close,
+        ]
+        .into_iter()
+        .find(|c| c.as_u32() == raw)
+    }
+}
+
+/**
+ * One transport-layer message. The header carries the command, two
+ * arguments, and the payload length, checksum, and magic.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdbMessage {
+    pub command: AdbCommand,
+    pub arg0: u32,
+    pub arg1: u32,
+    pub payload: Vec<u8>,
+}
+
+impl AdbMessage {
+    pub fn new(command: AdbCommand, arg0: u32, arg1: u32, payload: Vec<u8>) -> Self {
+        AdbMessage {
+            command,
+            arg0,
+            arg1,
+            payload,
+        }
+    }
+
+    /// Encode the 24-byte header. Despite the field's traditional name
+    /// (data_crc32), the checksum is a plain byte sum.
+    pub fn encode_header(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        let command = self.command.as_u32();
+        header[0..4].copy_from_slice(&command.to_le_bytes());
+        header[4..8].copy_from_slice(&self.arg0.to_le_bytes());
+        header[8..12].copy_from_slice(&self.arg1.to_le_bytes());
+        header[12..16].copy_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        header[16..20].copy_from_slice(&checksum(&self.payload).to_le_bytes());
+        header[20..24].copy_from_slice(&(command ^ 0xffff_ffff).to_le_bytes());
+        header
+    }
+
+    /// Decode a header, returning the message (payload still to be
+    /// read) and its declared payload length.
+    pub fn decode_header(header: &[u8]) -> Result<(AdbMessage, usize, u32), AdbError> {
+        if header.len() < HEADER_LEN {
+            return Err(AdbError::Malformed(format!(
+                "header is {} bytes, need {}",
+                header.len(),
+                HEADER_LEN
+            )));
+        }
+        let word = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().expect("4 bytes"));
+        let raw_command = word(0);
+        if word(20) != raw_command ^ 0xffff_ffff {
+            return Err(AdbError::Malformed(format!(
+                "magic {:08x} does not invert command {:08x}",
+                word(20),
+                raw_command
+            )));
+        }
+        let command = AdbCommand::from_u32(raw_command)
+            .ok_or_else(|| AdbError::Malformed(format!("unknown command {:08x}", raw_command)))?;
+        Ok((
+            AdbMessage::new(command, word(4), word(8), Vec::new()),
+            word(12) as usize,
+            word(16),
+        ))
+    }
+}
+
+/// The transport checksum: a wrapping byte sum of the payload.
+pub fn checksum(payload: &[u8]) -> u32 {
+    payload.iter().fold(0u32, |sum, &b| sum.wrapping_add(u32::from(b)))
+}
+
+/**
+ * The caller's answer to an AUTH token.
+ */
+pub enum AuthResponse {
+    /// Token signed with a key the device already trusts.
+    Signature(Vec<u8>),
+    /// Public key to offer for user confirmation on the device.
+    PublicKey(Vec<u8>),
+}
+
+/**
+ * The device's CNXN banner: "<state>::<prop>=<value>;...".
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceBanner {
+    /// "device", "recovery", "sideload", ...
+    pub state: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl DeviceBanner {
+    pub fn parse(raw: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(raw);
+        let text = text.trim_end_matches('\0');
+        let (state, rest) = text.split_once(':').unwrap_or((text, ""));
+        let properties = rest
+            .trim_start_matches(':')
+            .split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        DeviceBanner {
+            state: state.to_string(),
+            properties,
+        }
+    }
+}
+
+/**
+ * An authenticated ADB transport connection over bulk endpoints.
+ * Streams are opened one at a time; each borrows the connection while
+ * it is in use.
+ */
+pub struct AdbConnection<T: UsbTransport> {
+    bulk: BulkTransfer<T>,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    max_data: u32,
+    next_local_id: u32,
+}
+
+impl<T: UsbTransport> AdbConnection<T> {
+    pub fn new(transport: T, endpoint_in: u8, endpoint_out: u8) -> Self {
+        AdbConnection {
+            bulk: BulkTransfer::new(transport),
+            endpoint_in,
+            endpoint_out,
+            max_data: MAX_DATA,
+            next_local_id: 1,
+        }
+    }
+
+    /// Device's negotiated maximum payload size.
+    pub fn max_data(&self) -> u32 {
+        self.max_data
+    }
+
+    /**
+     * CNXN handshake. `system_identity` is our banner ("host::" for a
+     * plain host). On an AUTH challenge the signer is consulted: a
+     * `Signature` answer is sent as AUTH(SIGNATURE), a `PublicKey` as
+     * AUTH(RSAPUBLICKEY). Returns the device's banner.
+     */
+    pub fn connect(
+        &mut self,
+        system_identity: &str,
+        mut signer: impl FnMut(&[u8]) -> AuthResponse,
+    ) -> Result<DeviceBanner, AdbError> {
+        self.send(&AdbMessage::new(
+            AdbCommand::Connect,
+            ADB_VERSION,
+            MAX_DATA,
+            system_identity.as_bytes().to_vec(),
+        ))?;
+
+        for _ in 0..MAX_AUTH_ATTEMPTS {
+            let message = self.receive()?;
+            match message.command {
+                AdbCommand::Connect => {
+                    self.max_data = message.arg1.min(MAX_DATA);
+                    return Ok(DeviceBanner::parse(&message.payload));
+                }
+                AdbCommand::Auth if message.arg0 == AUTH_TOKEN => {
+                    let (auth_type, payload) = match signer(&message.payload) {
+                        AuthResponse::Signature(sig) => (AUTH_SIGNATURE, sig),
+                        AuthResponse::PublicKey(key) => (AUTH_RSAPUBLICKEY, key),
+                    };
+                    self.send(&AdbMessage::new(AdbCommand::Auth, auth_type, 0, payload))?;
+                }
+                other => {
+                    return Err(AdbError::UnexpectedCommand {
+                        expected: AdbCommand::Connect,
+                        got: other,
+                    })
+                }
+            }
+        }
+        Err(AdbError::AuthRejected)
+    }
+
+    /**
+     * Open a stream to a service destination such as `shell:` or
+     * `sync:`. The destination is NUL-terminated on the wire.
+     */
+    pub fn open_stream(&mut self, destination: &str) -> Result<AdbStream<'_, T>, AdbError> {
+        let local_id = self.next_local_id;
+        self.next_local_id += 1;
+
+        let mut payload = destination.as_bytes().to_vec();
+        payload.push(0);
+        self.send(&AdbMessage::new(AdbCommand::Open, local_id, 0, payload))?;
+
+        let reply = self.receive()?;
+        match reply.command {
+            AdbCommand::Okay if reply.arg1 == local_id => Ok(AdbStream {
+                connection: self,
+                local_id,
+                remote_id: reply.arg0,
+                open: true,
+            }),
+            AdbCommand::Close => Err(AdbError::StreamRefused {
+                destination: destination.to_string(),
+            }),
+            other => Err(AdbError::UnexpectedCommand {
+                expected: AdbCommand::Okay,
+                got: other,
+            }),
+        }
+    }
+
+    fn send(&mut self, message: &AdbMessage) -> Result<(), AdbError> {
+        self.bulk
+            .write(self.endpoint_out, &message.encode_header(), IO_TIMEOUT)?;
+        if !message.payload.is_empty() {
+            self.bulk
+                .write(self.endpoint_out, &message.payload, IO_TIMEOUT)?;
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<AdbMessage, AdbError> {
+        let mut header = [0u8; HEADER_LEN];
+        let n = self.bulk.read(self.endpoint_in, &mut header, IO_TIMEOUT)?;
+        let (mut message, payload_len, declared_sum) = AdbMessage::decode_header(&header[..n])?;
+        if payload_len > 0 {
+            let mut payload = vec![0u8; payload_len];
+            let mut offset = 0;
+            while offset < payload_len {
+                let n = self
+                    .bulk
+                    .read(self.endpoint_in, &mut payload[offset..], IO_TIMEOUT)?;
+                if n == 0 {
+                    return Err(AdbError::Malformed(format!(
+                        "payload truncated at {} of {} bytes",
+                        offset, payload_len
+                    )));
+                }
+                offset += n;
+            }
+            let actual = checksum(&payload);
+            if actual != declared_sum {
+                return Err(AdbError::ChecksumMismatch {
+                    expected: declared_sum,
+                    actual,
+                });
+            }
+            message.payload = payload;
+        }
+        Ok(message)
+    }
+}
+
+/**
+ * One open stream. Writes wait for the peer's OKAY; reads acknowledge
+ * each WRTE. Dropping without `close` leaves the remote end to time
+ * out, so close explicitly when the stream is done.
+ */
+pub struct AdbStream<'a, T: UsbTransport> {
+    connection: &'a mut AdbConnection<T>,
+    local_id: u32,
+    remote_id: u32,
+    open: bool,
+}
+
+impl<T: UsbTransport> AdbStream<'_, T> {
+    pub fn local_id(&self) -> u32 {
+        self.local_id
+    }
+
+    pub fn remote_id(&self) -> u32 {
+        self.remote_id
+    }
+
+    /// Send one WRTE and wait for the acknowledgement.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), AdbError> {
+        self.ensure_open()?;
+        self.connection.send(&AdbMessage::new(
+            AdbCommand::Write,
+            self.local_id,
+            self.remote_id,
+            data.to_vec(),
+        ))?;
+        let reply = self.connection.receive()?;
+        match reply.command {
+            AdbCommand::Okay => Ok(()),
+            AdbCommand::Close => {
+                self.open = false;
+                Err(AdbError::UnexpectedCommand {
+                    expected: AdbCommand::Okay,
+                    got: AdbCommand::Close,
+                })
+            }
+            other => Err(AdbError::UnexpectedCommand {
+                expected: AdbCommand::Okay,
+                got: other,
+            }),
+        }
+    }
+
+    /// Receive one WRTE payload, acknowledging it. `None` when the
+    /// remote closed the stream.
+    pub fn read(&mut self) -> Result<Option<Vec<u8>>, AdbError> {
+        self.ensure_open()?;
+        let message = self.connection.receive()?;
+        match message.command {
+            AdbCommand::Write => {
+                self.connection.send(&AdbMessage::new(
+                    AdbCommand::Okay,
+                    self.local_id,
+                    self.remote_id,
+                    Vec::new(),
+                ))?;
+                Ok(Some(message.payload))
+            }
+            AdbCommand::Close => {
+                self.open = false;
+                Ok(None)
+            }
+            other => Err(AdbError::UnexpectedCommand {
+                expected: AdbCommand::Write,
+                got: other,
+            }),
+        }
+    }
+
+    /// Close the stream from our side.
+    pub fn close(mut self) -> Result<(), AdbError> {
+        if self.open {
+            self.open = false;
+            self.connection.send(&AdbMessage::new(
+                AdbCommand::Close,
+                self.local_id,
+                self.remote_id,
+                Vec::new(),
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn ensure_open(&self) -> Result<(), AdbError> {
+        if self.open {
+            Ok(())
+        } else {
+            Err(AdbError::UnexpectedCommand {
+                expected: AdbCommand::Okay,
+                got: AdbCommand::Close,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    /// Known-good CNXN header for payload "host::\0", checked byte by
+    /// byte against the protocol document.
+    const CNXN_HEADER: [u8; HEADER_LEN] = [
+        0x43, 0x4e, 0x58, 0x4e, // "CNXN" as LE u32 0x4e584e43
+        0x01, 0x00, 0x00, 0x01, // version 0x01000001
+        0x00, 0x00, 0x10, 0x00, // maxdata 0x00100000
+        0x07, 0x00, 0x00, 0x00, // data length 7
+        0x32, 0x02, 0x00, 0x00, // byte sum of "host::\0" = 0x232
+        0xbc, 0xb1, 0xa7, 0xb1, // magic = command ^ 0xffffffff
+    ];
+
+    fn cnxn(payload: &[u8]) -> AdbMessage {
+        AdbMessage::new(AdbCommand::Connect, ADB_VERSION, MAX_DATA, payload.to_vec())
+    }
+
+    /// Queue a full message (header, then payload) on the mock.
+    fn script(transport: &mut MockTransport, message: &AdbMessage) {
+        transport
+            .read_results
+            .push_back(Ok(message.encode_header().to_vec()));
+        if !message.payload.is_empty() {
+            transport.read_results.push_back(Ok(message.payload.clone()));
+        }
+    }
+
+    fn connection(mut transport: MockTransport) -> AdbConnection<MockTransport> {
+        // Generous write budget; headers and payloads pop one each.
+        for _ in 0..32 {
+            transport.write_results.push_back(Ok(4096));
+        }
+        AdbConnection::new(transport, 0x81, 0x01)
+    }
+
+    #[test]
+    fn test_header_encoding_matches_capture() {
+        assert_eq!(cnxn(b"host::\0").encode_header(), CNXN_HEADER);
+    }
+
+    #[test]
+    fn test_header_decoding_matches_capture() {
+        let (message, payload_len, sum) = AdbMessage::decode_header(&CNXN_HEADER).unwrap();
+        assert_eq!(message.command, AdbCommand::Connect);
+        assert_eq!(message.arg0, ADB_VERSION);
+        assert_eq!(message.arg1, MAX_DATA);
+        assert_eq!(payload_len, 7);
+        assert_eq!(sum, 0x232);
+    }
+
+    #[test]
+    fn test_header_round_trip_all_commands() {
+        for command in [
+            AdbCommand::Sync,
+            AdbCommand::Connect,
+            AdbCommand::Auth,
+            AdbCommand::Open,
+            AdbCommand::Okay,
+            AdbCommand::Write,
+            AdbCommand::Close,
+        ] {
+            let message = AdbMessage::new(command, 7, 9, b"xyz".to_vec());
+            let (decoded, len, sum) = AdbMessage::decode_header(&message.encode_header()).unwrap();
+            assert_eq!(decoded.command, command);
+            assert_eq!((decoded.arg0, decoded.arg1), (7, 9));
+            assert_eq!(len, 3);
+            assert_eq!(sum, checksum(b"xyz"));
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_corruption() {
+        let mut bad_magic = CNXN_HEADER;
+        bad_magic[20] ^= 0x01;
+        assert!(matches!(
+            AdbMessage::decode_header(&bad_magic),
+            Err(AdbError::Malformed(_))
+        ));
+
+        let mut unknown_command = CNXN_HEADER;
+        unknown_command[0] = 0x00;
+        unknown_command[20] = 0xff; // keep the magic consistent
+        assert!(AdbMessage::decode_header(&unknown_command).is_err());
+
+        assert!(AdbMessage::decode_header(&CNXN_HEADER[..20]).is_err());
+    }
+
+    #[test]
+    fn test_checksum_is_byte_sum() {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"host::\0"), 0x232);
+        assert_eq!(checksum(&[0xff; 4]), 0x3fc);
+    }
+
+    #[test]
+    fn test_connect_without_auth() {
+        let mut transport = MockTransport::new();
+        script(
+            &mut transport,
+            &cnxn(b"device::ro.product.name=walleye;ro.product.model=Pixel 2;\0"),
+        );
+        let mut conn = connection(transport);
+
+        let banner = conn
+            .connect("host::", |_| panic!("no auth expected"))
+            .unwrap();
+        assert_eq!(banner.state, "device");
+        assert_eq!(
+            banner.properties[0],
+            ("ro.product.name".to_string(), "walleye".to_string())
+        );
+    }
+
+    #[test]
+    fn test_connect_signs_auth_token() {
+        let mut transport = MockTransport::new();
+        script(
+            &mut transport,
+            &AdbMessage::new(AdbCommand::Auth, AUTH_TOKEN, 0, vec![0xaa; 20]),
+        );
+        script(&mut transport, &cnxn(b"device::\0"));
+        let mut conn = connection(transport);
+
+        let mut tokens = Vec::new();
+        conn.connect("host::", |token| {
+            tokens.push(token.to_vec());
+            AuthResponse::Signature(vec![0x51; 256])
+        })
+        .unwrap();
+        assert_eq!(tokens, vec![vec![0xaa; 20]]);
+    }
+
+    #[test]
+    fn test_repeated_tokens_mean_rejection() {
+        let mut transport = MockTransport::new();
+        for _ in 0..MAX_AUTH_ATTEMPTS {
+            script(
+                &mut transport,
+                &AdbMessage::new(AdbCommand::Auth, AUTH_TOKEN, 0, vec![0xbb; 20]),
+            );
+        }
+        let mut conn = connection(transport);
+
+        let err = conn
+            .connect("host::", |_| AuthResponse::PublicKey(b"key".to_vec()))
+            .unwrap_err();
+        assert!(matches!(err, AdbError::AuthRejected));
+    }
+
+    #[test]
+    fn test_stream_open_write_read_close() {
+        let mut transport = MockTransport::new();
+        // OPEN -> OKAY(remote=5, local=1); WRTE ack; inbound WRTE; our
+        // CLSE needs no reply.
+        script(&mut transport, &AdbMessage::new(AdbCommand::Okay, 5, 1, Vec::new()));
+        script(&mut transport, &AdbMessage::new(AdbCommand::Okay, 5, 1, Vec::new()));
+        script(
+            &mut transport,
+            &AdbMessage::new(AdbCommand::Write, 5, 1, b"uid=2000(shell)\n".to_vec()),
+        );
+        let mut conn = connection(transport);
+
+        let mut stream = conn.open_stream("shell:id").unwrap();
+        assert_eq!((stream.local_id(), stream.remote_id()), (1, 5));
+        stream.write(b"id\n").unwrap();
+        assert_eq!(stream.read().unwrap().unwrap(), b"uid=2000(shell)\n");
+        stream.close().unwrap();
+    }
+
+    #[test]
+    fn test_stream_refused_with_clse() {
+        let mut transport = MockTransport::new();
+        script(&mut transport, &AdbMessage::new(AdbCommand::Close, 0, 1, Vec::new()));
+        let mut conn = connection(transport);
+
+        let err = conn.open_stream("shell:").err().expect("refused");
+        assert!(matches!(
+            err,
+            AdbError::StreamRefused { destination } if destination == "shell:"
+        ));
+    }
+
+    #[test]
+    fn test_corrupted_payload_checksum_detected() {
+        let mut transport = MockTransport::new();
+        let mut message = cnxn(b"device::\0");
+        let header = message.encode_header();
+        message.payload[0] ^= 0xff; // corrupt after the header was built
+        transport.read_results.push_back(Ok(header.to_vec()));
+        transport.read_results.push_back(Ok(message.payload.clone()));
+        let mut conn = connection(transport);
+
+        assert!(matches!(
+            conn.connect("host::", |_| panic!()),
+            Err(AdbError::ChecksumMismatch { .. })
+        ));
+    }
+}
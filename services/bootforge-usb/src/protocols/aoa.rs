@@ -0,0 +1,246 @@
+// BootForge USB - Android Open Accessory (AOA) mode
+// Handshake helpers for flipping a phone into accessory mode and
+// talking to the accessory bulk interface afterwards.
+
+use std::time::{Duration, Instant};
+
+use crate::enumeration::{enumerate_libusb, UsbDeviceInfo};
+use crate::error::UsbError;
+use crate::transfer::{BulkTransfer, UsbTransport};
+
+const GOOGLE_VID: u16 = 0x18d1;
+/// Product IDs a device re-enumerates with after the accessory switch.
+const ACCESSORY_PID_RANGE: std::ops::RangeInclusive<u16> = 0x2d00..=0x2d05;
+
+// Vendor control requests (AOA protocol)
+const REQ_GET_PROTOCOL: u8 = 51;
+const REQ_SEND_STRING: u8 = 52;
+const REQ_START: u8 = 53;
+
+const REQUEST_TYPE_VENDOR_IN: u8 = 0xc0;
+const REQUEST_TYPE_VENDOR_OUT: u8 = 0x40;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_millis(500);
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+// String indices for REQ_SEND_STRING, in the order the handshake sends them.
+const STR_MANUFACTURER: u16 = 0;
+const STR_MODEL: u16 = 1;
+const STR_DESCRIPTION: u16 = 2;
+const STR_VERSION: u16 = 3;
+const STR_URI: u16 = 4;
+const STR_SERIAL: u16 = 5;
+
+/**
+ * The accessory's self-identification sent during the handshake.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessoryIdentity {
+    pub manufacturer: String,
+    pub model: String,
+    pub description: String,
+    pub version: String,
+    pub uri: String,
+    pub serial: String,
+}
+
+/**
+ * Query the AOA protocol version (vendor request 51). None when the
+ * device does not speak AOA.
+ */
+pub fn probe_aoa_version<T: UsbTransport>(transport: &mut T) -> Option<u16> {
+    let mut buf = [0u8; 2];
+    match transport.read_control(
+        REQUEST_TYPE_VENDOR_IN,
+        REQ_GET_PROTOCOL,
+        0,
+        0,
+        &mut buf,
+        CONTROL_TIMEOUT,
+    ) {
+        Ok(2) => {
+            let version = u16::from_le_bytes(buf);
+            if version > 0 {
+                Some(version)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/**
+ * Send the identity strings (request 52, UTF-8 null-terminated, in index
+ * order) followed by the start request (53). The device drops off the
+ * bus and re-enumerates as 18d1:2d00-2d05.
+ */
+pub fn start_accessory<T: UsbTransport>(
+    transport: &mut T,
+    identity: &AccessoryIdentity,
+) -> Result<(), UsbError> {
+    let strings: [(u16, &str); 6] = [
+        (STR_MANUFACTURER, &identity.manufacturer),
+        (STR_MODEL, &identity.model),
+        (STR_DESCRIPTION, &identity.description),
+        (STR_VERSION, &identity.version),
+        (STR_URI, &identity.uri),
+        (STR_SERIAL, &identity.serial),
+    ];
+
+    for (index, value) in strings {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        transport
+            .write_control(
+                REQUEST_TYPE_VENDOR_OUT,
+                REQ_SEND_STRING,
+                0,
+                index,
+                &data,
+                CONTROL_TIMEOUT,
+            )
+            .map_err(crate::error::classify_transfer_error)?;
+    }
+
+    transport
+        .write_control(
+            REQUEST_TYPE_VENDOR_OUT,
+            REQ_START,
+            0,
+            0,
+            &[],
+            CONTROL_TIMEOUT,
+        )
+        .map_err(crate::error::classify_transfer_error)?;
+    Ok(())
+}
+
+/// Whether a VID/PID pair is a post-switch AOA accessory identity.
+pub fn is_accessory_mode(vendor_id: u16, product_id: u16) -> bool {
+    vendor_id == GOOGLE_VID && ACCESSORY_PID_RANGE.contains(&product_id)
+}
+
+/**
+ * Poll enumeration until a device in accessory mode appears.
+ */
+pub fn wait_for_accessory_device(timeout: Duration) -> Result<UsbDeviceInfo, UsbError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(info) = enumerate_libusb()?
+            .into_iter()
+            .find(|d| is_accessory_mode(d.vendor_id, d.product_id))
+        {
+            return Ok(info);
+        }
+        if Instant::now() >= deadline {
+            return Err(UsbError::Timeout);
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+/**
+ * Full switch: handshake, then wait for the accessory-mode device to
+ * re-enumerate and return its info.
+ */
+pub fn enter_accessory_mode<T: UsbTransport>(
+    transport: &mut T,
+    identity: &AccessoryIdentity,
+    timeout: Duration,
+) -> Result<UsbDeviceInfo, UsbError> {
+    start_accessory(transport, identity)?;
+    wait_for_accessory_device(timeout)
+}
+
+/**
+ * Bulk read/write helpers for the accessory data interface.
+ */
+pub struct AccessoryStream<T: UsbTransport> {
+    bulk: BulkTransfer<T>,
+    endpoint_in: u8,
+    endpoint_out: u8,
+}
+
+impl<T: UsbTransport> AccessoryStream<T> {
+    pub fn new(transport: T, endpoint_in: u8, endpoint_out: u8) -> Self {
+        AccessoryStream {
+            bulk: BulkTransfer::new(transport),
+            endpoint_in,
+            endpoint_out,
+        }
+    }
+
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, UsbError> {
+        self.bulk.read(self.endpoint_in, buf, IO_TIMEOUT)
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, UsbError> {
+        self.bulk.write(self.endpoint_out, data, IO_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    fn identity() -> AccessoryIdentity {
+        AccessoryIdentity {
+            manufacturer: "BootForge".to_string(),
+            model: "Workbench".to_string(),
+            description: "Diagnostics accessory".to_string(),
+            version: "1.0".to_string(),
+            uri: "https://example.com/bootforge".to_string(),
+            serial: "BF-0001".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_probe_version() {
+        let mut transport = MockTransport::new();
+        transport.control_read_results.push_back(Ok(vec![2, 0]));
+        assert_eq!(probe_aoa_version(&mut transport), Some(2));
+
+        let req = &transport.control_requests[0];
+        assert_eq!(req.request_type, 0xc0);
+        assert_eq!(req.request, 51);
+
+        let mut transport = MockTransport::new();
+        transport
+            .control_read_results
+            .push_back(Err(rusb::Error::Pipe));
+        assert_eq!(probe_aoa_version(&mut transport), None);
+    }
+
+    #[test]
+    fn test_start_accessory_request_ordering_and_encoding() {
+        let mut transport = MockTransport::new();
+        start_accessory(&mut transport, &identity()).unwrap();
+
+        assert_eq!(transport.control_requests.len(), 7);
+        // Strings 0..=5 in index order, then the start request.
+        for (i, req) in transport.control_requests[..6].iter().enumerate() {
+            assert_eq!(req.request_type, 0x40);
+            assert_eq!(req.request, 52);
+            assert_eq!(req.index, i as u16);
+            // UTF-8, null-terminated
+            assert_eq!(req.data.last(), Some(&0u8));
+        }
+        assert_eq!(
+            &transport.control_requests[0].data,
+            b"BootForge\0"
+        );
+        let start = &transport.control_requests[6];
+        assert_eq!(start.request, 53);
+        assert!(start.data.is_empty());
+    }
+
+    #[test]
+    fn test_accessory_mode_pid_range() {
+        assert!(is_accessory_mode(0x18d1, 0x2d00));
+        assert!(is_accessory_mode(0x18d1, 0x2d05));
+        assert!(!is_accessory_mode(0x18d1, 0x2d06));
+        assert!(!is_accessory_mode(0x05ac, 0x2d00));
+    }
+}
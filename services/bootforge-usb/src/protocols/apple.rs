@@ -0,0 +1,190 @@
+// BootForge USB - Apple device modes
+// Distinguishes the modes an Apple device enumerates in - normal,
+// recovery (iBoot), DFU (SecureROM), and the old WTF loader - since
+// restore tooling must pick a completely different path for each.
+// Identification only; no usbmuxd or restore protocol here.
+
+use serde::{Deserialize, Serialize};
+
+use crate::enumeration::{UsbDeviceInfo, UsbDeviceRecord};
+use crate::protocols::classify::contains_ignore_ascii_case;
+
+pub const APPLE_VID: u16 = 0x05ac;
+
+/**
+ * The mode an Apple device is currently enumerated in. `Unknown` means
+ * an Apple device whose PID and product string match no known mode -
+ * accessories, or a PID newer than the table.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum AppleDeviceMode {
+    /// Booted into the OS; usbmuxd services available.
+    Normal,
+    /// Recovery mode (iBoot), PID 0x1281 and neighbours.
+    Recovery,
+    /// DFU mode (SecureROM), PID 0x1227 and predecessors.
+    Dfu,
+    /// The pre-DFU "WTF" loader on very old devices (PID 0x1240).
+    Wtf,
+    Unknown,
+}
+
+impl AppleDeviceMode {
+    /// The advisory tag classification attaches to enumerated records.
+    pub fn tag(&self) -> Option<&'static str> {
+        match self {
+            AppleDeviceMode::Normal => Some("apple:normal"),
+            AppleDeviceMode::Recovery => Some("apple:recovery"),
+            AppleDeviceMode::Dfu => Some("apple:dfu"),
+            AppleDeviceMode::Wtf => Some("apple:wtf"),
+            AppleDeviceMode::Unknown => None,
+        }
+    }
+
+    /// The PID table. Ranges, so new devices that keep Apple's
+    /// allocation pattern classify without an update.
+    fn from_product_id(product_id: u16) -> AppleDeviceMode {
+        match product_id {
+            0x1222 | 0x1226 | 0x1227 => AppleDeviceMode::Dfu,
+            0x1240 => AppleDeviceMode::Wtf,
+            0x1280..=0x1283 => AppleDeviceMode::Recovery,
+            0x1290..=0x12af => AppleDeviceMode::Normal,
+            _ => AppleDeviceMode::Unknown,
+        }
+    }
+}
+
+/// Mode from PID first, product string second ("Apple Mobile Device
+/// (Recovery Mode)" and friends). None for non-Apple vendors.
+pub fn detect_mode(
+    vendor_id: u16,
+    product_id: u16,
+    product: Option<&str>,
+) -> Option<AppleDeviceMode> {
+    if vendor_id != APPLE_VID {
+        return None;
+    }
+    let mut mode = AppleDeviceMode::from_product_id(product_id);
+    if mode == AppleDeviceMode::Unknown {
+        if let Some(product) = product {
+            if contains_ignore_ascii_case(product, "recovery") {
+                mode = AppleDeviceMode::Recovery;
+            } else if contains_ignore_ascii_case(product, "dfu") {
+                mode = AppleDeviceMode::Dfu;
+            } else if contains_ignore_ascii_case(product, "wtf") {
+                mode = AppleDeviceMode::Wtf;
+            }
+        }
+    }
+    Some(mode)
+}
+
+/**
+ * Mode of a sysfs-enumerated device; None for non-Apple vendors.
+ */
+pub fn detect_apple_mode(record: &UsbDeviceRecord) -> Option<AppleDeviceMode> {
+    detect_mode(record.vendor_id, record.product_id, record.product.as_deref())
+}
+
+/// Attach the mode tag to an enumerated device; enumeration calls this
+/// after string descriptors are read so the product-string fallback
+/// has something to look at.
+pub(crate) fn tag_apple_mode(info: &mut UsbDeviceInfo) {
+    let tag = detect_mode(info.vendor_id, info.product_id, info.product.as_deref())
+        .and_then(|mode| mode.tag());
+    if let Some(tag) = tag {
+        if !info.tags.iter().any(|t| t == tag) {
+            info.tags.push(tag.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::version::BcdVersion;
+
+    fn record(vendor_id: u16, product_id: u16, product: Option<&str>) -> UsbDeviceRecord {
+        UsbDeviceRecord {
+            bus_number: 1,
+            device_number: 5,
+            vendor_id,
+            product_id,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0200),
+                device_version: BcdVersion(0x0100),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: Some("Apple Inc.".to_string()),
+            product: product.map(str::to_string),
+            serial_number: None,
+            sysfs_path: String::new(),
+            speed: None,
+            interfaces: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_mode_per_pid() {
+        let cases = [
+            (0x12a8, Some("iPhone"), AppleDeviceMode::Normal),
+            (0x12ab, Some("iPad"), AppleDeviceMode::Normal),
+            (0x1281, Some("Apple Mobile Device (Recovery Mode)"), AppleDeviceMode::Recovery),
+            (0x1227, Some("Apple Mobile Device (DFU Mode)"), AppleDeviceMode::Dfu),
+            (0x1222, None, AppleDeviceMode::Dfu),
+            (0x1240, None, AppleDeviceMode::Wtf),
+            (0x024f, Some("Magic Keyboard"), AppleDeviceMode::Unknown),
+        ];
+        for (pid, product, expected) in cases {
+            assert_eq!(
+                detect_apple_mode(&record(APPLE_VID, pid, product)),
+                Some(expected),
+                "pid {:04x}",
+                pid
+            );
+        }
+    }
+
+    #[test]
+    fn test_product_string_fallback_and_foreign_vendor() {
+        // Off-table PID, but the string says recovery.
+        let r = record(APPLE_VID, 0x1999, Some("Apple Mobile Device (Recovery Mode)"));
+        assert_eq!(detect_apple_mode(&r), Some(AppleDeviceMode::Recovery));
+        let r = record(APPLE_VID, 0x1999, Some("something DFU something"));
+        assert_eq!(detect_apple_mode(&r), Some(AppleDeviceMode::Dfu));
+
+        // Non-Apple vendors never classify, strings notwithstanding.
+        assert_eq!(
+            detect_apple_mode(&record(0x18d1, 0x1281, Some("Recovery"))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_tagging_is_idempotent() {
+        let mut info = crate::watch::partial_info(
+            APPLE_VID,
+            0x1227,
+            None,
+            None,
+            "test:apple".to_string(),
+        );
+        tag_apple_mode(&mut info);
+        tag_apple_mode(&mut info);
+        assert_eq!(
+            info.tags.iter().filter(|t| *t == "apple:dfu").count(),
+            1
+        );
+
+        // Unknown modes add nothing.
+        let mut accessory =
+            crate::watch::partial_info(APPLE_VID, 0x024f, None, None, "test:apple".to_string());
+        tag_apple_mode(&mut accessory);
+        assert_eq!(accessory.tags.len(), 1);
+    }
+}
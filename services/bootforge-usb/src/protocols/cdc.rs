@@ -0,0 +1,572 @@
+// BootForge USB - CDC-ACM serial client
+// Line coding, modem control lines, and buffered reads over the bulk
+// data endpoints of a communications-class device.
+
+use std::time::Duration;
+
+use crate::enumeration::ConfigInfo;
+use crate::error::UsbError;
+use crate::topology::EndpointKind;
+use crate::transfer::{BulkTransfer, InterruptPoller, UsbTransport};
+
+// Class / subclass codes
+pub const CDC_CLASS_COMM: u8 = 0x02;
+pub const CDC_SUBCLASS_ACM: u8 = 0x02;
+pub const CDC_CLASS_DATA: u8 = 0x0a;
+
+// Class requests (CDC PSTN 6.3)
+const REQ_SET_LINE_CODING: u8 = 0x20;
+const REQ_GET_LINE_CODING: u8 = 0x21;
+const REQ_SET_CONTROL_LINE_STATE: u8 = 0x22;
+
+// Class-specific interface requests, host-to-device and device-to-host.
+const REQT_CLASS_INTERFACE_OUT: u8 = 0x21;
+const REQT_CLASS_INTERFACE_IN: u8 = 0xa1;
+
+/// SERIAL_STATE notification code (CDC PSTN 6.5.4).
+const NOTIFY_SERIAL_STATE: u8 = 0x20;
+
+const LINE_CODING_LEN: usize = 7;
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StopBits {
+    #[default]
+    One,
+    OnePointFive,
+    Two,
+}
+
+impl StopBits {
+    fn as_byte(self) -> u8 {
+        match self {
+            StopBits::One => 0,
+            StopBits::OnePointFive => 1,
+            StopBits::Two => 2,
+        }
+    }
+
+    fn from_byte(raw: u8) -> Result<Self, UsbError> {
+        match raw {
+            0 => Ok(StopBits::One),
+            1 => Ok(StopBits::OnePointFive),
+            2 => Ok(StopBits::Two),
+            other => Err(UsbError::Parse(format!("bCharFormat {} out of range", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+    Mark,
+    Space,
+}
+
+impl Parity {
+    fn as_byte(self) -> u8 {
+        match self {
+            Parity::None => 0,
+            Parity::Odd => 1,
+            Parity::Even => 2,
+            Parity::Mark => 3,
+            Parity::Space => 4,
+        }
+    }
+
+    fn from_byte(raw: u8) -> Result<Self, UsbError> {
+        match raw {
+            0 => Ok(Parity::None),
+            1 => Ok(Parity::Odd),
+            2 => Ok(Parity::Even),
+            3 => Ok(Parity::Mark),
+            4 => Ok(Parity::Space),
+            other => Err(UsbError::Parse(format!("bParityType {} out of range", other))),
+        }
+    }
+}
+
+/**
+ * The 7-byte line coding structure from SET/GET_LINE_CODING.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCoding {
+    pub baud: u32,
+    pub stop_bits: StopBits,
+    pub parity: Parity,
+    pub data_bits: u8,
+}
+
+impl Default for LineCoding {
+    /// The ubiquitous 115200 8N1.
+    fn default() -> Self {
+        LineCoding {
+            baud: 115_200,
+            stop_bits: StopBits::One,
+            parity: Parity::None,
+            data_bits: 8,
+        }
+    }
+}
+
+impl LineCoding {
+    pub fn encode(&self) -> [u8; LINE_CODING_LEN] {
+        let mut out = [0u8; LINE_CODING_LEN];
+        out[0..4].copy_from_slice(&self.baud.to_le_bytes());
+        out[4] = self.stop_bits.as_byte();
+        out[5] = self.parity.as_byte();
+        out[6] = self.data_bits;
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, UsbError> {
+        if bytes.len() < LINE_CODING_LEN {
+            return Err(UsbError::Parse(format!(
+                "line coding is {} bytes, need {}",
+                bytes.len(),
+                LINE_CODING_LEN
+            )));
+        }
+        Ok(LineCoding {
+            baud: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            stop_bits: StopBits::from_byte(bytes[4])?,
+            parity: Parity::from_byte(bytes[5])?,
+            data_bits: bytes[6],
+        })
+    }
+}
+
+/**
+ * The interface and endpoint addresses of one ACM function, located in
+ * an enumerated configuration.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CdcAcmLayout {
+    /// Communication interface carrying the class requests.
+    pub comm_interface: u8,
+    /// Data interface carrying the bulk endpoints.
+    pub data_interface: u8,
+    pub endpoint_in: u8,
+    pub endpoint_out: u8,
+    /// Interrupt IN endpoint for SERIAL_STATE notifications, when the
+    /// communication interface has one.
+    pub endpoint_notify: Option<u8>,
+}
+
+impl CdcAcmLayout {
+    /**
+     * Locate the first ACM communication interface and the data
+     * interface that follows it. Returns None when the configuration
+     * has no complete ACM function.
+     */
+    pub fn find(config: &ConfigInfo) -> Option<CdcAcmLayout> {
+        let comm = config
+            .interfaces
+            .iter()
+            .find(|i| i.class == CDC_CLASS_COMM && i.subclass == CDC_SUBCLASS_ACM)?;
+        let data = config
+            .interfaces
+            .iter()
+            .find(|i| i.class == CDC_CLASS_DATA && i.number != comm.number)?;
+
+        let endpoint_in = data
+            .endpoints
+            .iter()
+            .find(|e| e.kind == EndpointKind::Bulk && e.address & 0x80 != 0)?
+            .address;
+        let endpoint_out = data
+            .endpoints
+            .iter()
+            .find(|e| e.kind == EndpointKind::Bulk && e.address & 0x80 == 0)?
+            .address;
+        let endpoint_notify = comm
+            .endpoints
+            .iter()
+            .find(|e| e.kind == EndpointKind::Interrupt && e.address & 0x80 != 0)
+            .map(|e| e.address);
+
+        Some(CdcAcmLayout {
+            comm_interface: comm.number,
+            data_interface: data.number,
+            endpoint_in,
+            endpoint_out,
+            endpoint_notify,
+        })
+    }
+}
+
+/**
+ * Decoded SERIAL_STATE notification bitmap.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialState {
+    bitmap: u16,
+}
+
+impl SerialState {
+    /**
+     * Parse a notification from the interrupt endpoint: an 8-byte
+     * request-style header followed by the 2-byte state bitmap.
+     * Ok(None) for other notification codes.
+     */
+    pub fn parse(bytes: &[u8]) -> Result<Option<SerialState>, UsbError> {
+        if bytes.len() < 8 {
+            return Err(UsbError::Parse(format!(
+                "notification is {} bytes, need at least 8",
+                bytes.len()
+            )));
+        }
+        if bytes[1] != NOTIFY_SERIAL_STATE {
+            return Ok(None);
+        }
+        if bytes.len() < 10 {
+            return Err(UsbError::Parse(
+                "SERIAL_STATE notification missing its bitmap".to_string(),
+            ));
+        }
+        Ok(Some(SerialState {
+            bitmap: u16::from_le_bytes(bytes[8..10].try_into().unwrap()),
+        }))
+    }
+
+    /// Carrier detect (bRxCarrier).
+    pub fn dcd(&self) -> bool {
+        self.bitmap & 0x01 != 0
+    }
+
+    /// Data set ready (bTxCarrier).
+    pub fn dsr(&self) -> bool {
+        self.bitmap & 0x02 != 0
+    }
+
+    pub fn break_received(&self) -> bool {
+        self.bitmap & 0x04 != 0
+    }
+
+    pub fn ring(&self) -> bool {
+        self.bitmap & 0x08 != 0
+    }
+
+    pub fn framing_error(&self) -> bool {
+        self.bitmap & 0x10 != 0
+    }
+
+    pub fn parity_error(&self) -> bool {
+        self.bitmap & 0x20 != 0
+    }
+
+    pub fn overrun(&self) -> bool {
+        self.bitmap & 0x40 != 0
+    }
+}
+
+/**
+ * Blocking CDC-ACM serial client. The caller claims both interfaces
+ * (see `claim`) before handing the transport over.
+ */
+pub struct CdcAcmClient<T: UsbTransport> {
+    bulk: BulkTransfer<T>,
+    layout: CdcAcmLayout,
+    /// Bytes read past the last returned line.
+    pending: Vec<u8>,
+}
+
+impl<T: UsbTransport> CdcAcmClient<T> {
+    pub fn new(transport: T, layout: CdcAcmLayout) -> Self {
+        CdcAcmClient {
+            bulk: BulkTransfer::new(transport),
+            layout,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn layout(&self) -> &CdcAcmLayout {
+        &self.layout
+    }
+
+    pub fn set_line_coding(&mut self, coding: &LineCoding) -> Result<(), UsbError> {
+        let index = u16::from(self.layout.comm_interface);
+        self.bulk
+            .transport_mut()
+            .write_control(
+                REQT_CLASS_INTERFACE_OUT,
+                REQ_SET_LINE_CODING,
+                0,
+                index,
+                &coding.encode(),
+                CONTROL_TIMEOUT,
+            )
+            .map_err(UsbError::from)?;
+        Ok(())
+    }
+
+    pub fn get_line_coding(&mut self) -> Result<LineCoding, UsbError> {
+        let index = u16::from(self.layout.comm_interface);
+        let mut buf = [0u8; LINE_CODING_LEN];
+        let n = self
+            .bulk
+            .transport_mut()
+            .read_control(
+                REQT_CLASS_INTERFACE_IN,
+                REQ_GET_LINE_CODING,
+                0,
+                index,
+                &mut buf,
+                CONTROL_TIMEOUT,
+            )
+            .map_err(UsbError::from)?;
+        LineCoding::decode(&buf[..n])
+    }
+
+    /// Assert or drop DTR (bit 0) and RTS (bit 1).
+    pub fn set_control_line_state(&mut self, dtr: bool, rts: bool) -> Result<(), UsbError> {
+        let value = u16::from(dtr) | (u16::from(rts) << 1);
+        let index = u16::from(self.layout.comm_interface);
+        self.bulk
+            .transport_mut()
+            .write_control(
+                REQT_CLASS_INTERFACE_OUT,
+                REQ_SET_CONTROL_LINE_STATE,
+                value,
+                index,
+                &[],
+                CONTROL_TIMEOUT,
+            )
+            .map_err(UsbError::from)?;
+        Ok(())
+    }
+
+    /**
+     * Typical open sequence: apply the line coding, then raise DTR and
+     * RTS so the device starts talking.
+     */
+    pub fn open(&mut self, coding: &LineCoding) -> Result<(), UsbError> {
+        self.set_line_coding(coding)?;
+        self.set_control_line_state(true, true)
+    }
+
+    pub fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, UsbError> {
+        self.bulk.read(self.layout.endpoint_in, buf, timeout)
+    }
+
+    pub fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize, UsbError> {
+        self.bulk.write(self.layout.endpoint_out, data, timeout)
+    }
+
+    /**
+     * Read until a newline arrives, buffering any bytes past it for
+     * the next call. The returned line has its trailing CR/LF removed.
+     */
+    pub fn read_line(&mut self, timeout: Duration) -> Result<String, UsbError> {
+        loop {
+            if let Some(at) = self.pending.iter().position(|&b| b == b'\n') {
+                let rest = self.pending.split_off(at + 1);
+                let mut line = std::mem::replace(&mut self.pending, rest);
+                while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(String::from_utf8_lossy(&line).into_owned());
+            }
+            let mut buf = [0u8; 512];
+            let n = self.bulk.read(self.layout.endpoint_in, &mut buf, timeout)?;
+            self.pending.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    /**
+     * One read of the notification endpoint. Ok(None) when nothing
+     * arrived in time or the notification was not SERIAL_STATE.
+     */
+    pub fn poll_serial_state(&mut self, timeout: Duration) -> Result<Option<SerialState>, UsbError> {
+        let endpoint = self.layout.endpoint_notify.ok_or_else(|| {
+            UsbError::Unsupported("no notification endpoint in this layout".to_string())
+        })?;
+        let mut poller = InterruptPoller::new(self.bulk.transport_mut(), endpoint, timeout);
+        let mut buf = [0u8; 16];
+        match poller.poll_once(&mut buf)? {
+            Some(n) => SerialState::parse(&buf[..n]),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::InterfaceInfo;
+    use crate::topology::EndpointInfo;
+    use crate::transfer::mock::MockTransport;
+
+    fn endpoint(address: u8, kind: EndpointKind) -> EndpointInfo {
+        EndpointInfo {
+            address,
+            kind,
+            max_packet_size: 64,
+            interval: 0,
+            ss_bytes_per_interval: None,
+        }
+    }
+
+    fn acm_config() -> ConfigInfo {
+        ConfigInfo {
+            configuration_value: 1,
+            max_power_ma: 100,
+            self_powered: false,
+            remote_wakeup: false,
+            description: None,
+            interfaces: vec![
+                InterfaceInfo {
+                    number: 0,
+                    alternate_setting: 0,
+                    class: CDC_CLASS_COMM,
+                    subclass: CDC_SUBCLASS_ACM,
+                    protocol: 1,
+                    description: None,
+                    endpoints: vec![endpoint(0x83, EndpointKind::Interrupt)],
+                },
+                InterfaceInfo {
+                    number: 1,
+                    alternate_setting: 0,
+                    class: CDC_CLASS_DATA,
+                    subclass: 0,
+                    protocol: 0,
+                    description: None,
+                    endpoints: vec![
+                        endpoint(0x81, EndpointKind::Bulk),
+                        endpoint(0x02, EndpointKind::Bulk),
+                    ],
+                },
+            ],
+        }
+    }
+
+    fn layout() -> CdcAcmLayout {
+        CdcAcmLayout::find(&acm_config()).unwrap()
+    }
+
+    #[test]
+    fn test_line_coding_round_trip() {
+        let codings = [
+            LineCoding::default(),
+            LineCoding {
+                baud: 9600,
+                stop_bits: StopBits::Two,
+                parity: Parity::Even,
+                data_bits: 7,
+            },
+            LineCoding {
+                baud: 1_000_000,
+                stop_bits: StopBits::OnePointFive,
+                parity: Parity::Space,
+                data_bits: 5,
+            },
+        ];
+        for coding in codings {
+            assert_eq!(LineCoding::decode(&coding.encode()).unwrap(), coding);
+        }
+    }
+
+    #[test]
+    fn test_line_coding_decode_rejects_bad_bytes() {
+        assert!(LineCoding::decode(&[0; 6]).is_err());
+
+        let mut bad_stop = LineCoding::default().encode();
+        bad_stop[4] = 3;
+        assert!(LineCoding::decode(&bad_stop).is_err());
+
+        let mut bad_parity = LineCoding::default().encode();
+        bad_parity[5] = 5;
+        assert!(LineCoding::decode(&bad_parity).is_err());
+    }
+
+    #[test]
+    fn test_layout_finds_acm_function() {
+        let layout = layout();
+        assert_eq!(layout.comm_interface, 0);
+        assert_eq!(layout.data_interface, 1);
+        assert_eq!(layout.endpoint_in, 0x81);
+        assert_eq!(layout.endpoint_out, 0x02);
+        assert_eq!(layout.endpoint_notify, Some(0x83));
+
+        // A config without the data interface is not a usable function.
+        let mut comm_only = acm_config();
+        comm_only.interfaces.truncate(1);
+        assert!(CdcAcmLayout::find(&comm_only).is_none());
+    }
+
+    #[test]
+    fn test_open_sequence_issues_class_requests() {
+        let mut client = CdcAcmClient::new(MockTransport::new(), layout());
+        let coding = LineCoding {
+            baud: 9600,
+            ..LineCoding::default()
+        };
+        client.open(&coding).unwrap();
+
+        let requests = &client.bulk.transport_mut().control_requests;
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].request_type, REQT_CLASS_INTERFACE_OUT);
+        assert_eq!(requests[0].request, REQ_SET_LINE_CODING);
+        assert_eq!(requests[0].index, 0); // comm interface
+        assert_eq!(requests[0].data, coding.encode());
+        assert_eq!(requests[1].request, REQ_SET_CONTROL_LINE_STATE);
+        assert_eq!(requests[1].value, 0b11); // DTR | RTS
+    }
+
+    #[test]
+    fn test_get_line_coding_decodes_reply() {
+        let mut transport = MockTransport::new();
+        let expected = LineCoding {
+            baud: 57_600,
+            stop_bits: StopBits::One,
+            parity: Parity::Odd,
+            data_bits: 8,
+        };
+        transport
+            .control_read_results
+            .push_back(Ok(expected.encode().to_vec()));
+
+        let mut client = CdcAcmClient::new(transport, layout());
+        assert_eq!(client.get_line_coding().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_read_line_buffers_across_reads() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(b"ok\r\npar".to_vec()));
+        transport.read_results.push_back(Ok(b"tial\n".to_vec()));
+
+        let mut client = CdcAcmClient::new(transport, layout());
+        let timeout = Duration::from_millis(10);
+        assert_eq!(client.read_line(timeout).unwrap(), "ok");
+        assert_eq!(client.read_line(timeout).unwrap(), "partial");
+    }
+
+    #[test]
+    fn test_serial_state_notification() {
+        // bmRequestType, SERIAL_STATE, wValue, wIndex, wLength=2, bitmap
+        let mut notification = vec![0xa1, NOTIFY_SERIAL_STATE, 0, 0, 0, 0, 2, 0];
+        notification.extend_from_slice(&0b0000_0111u16.to_le_bytes());
+
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(notification));
+
+        let mut client = CdcAcmClient::new(transport, layout());
+        let state = client
+            .poll_serial_state(Duration::from_millis(10))
+            .unwrap()
+            .unwrap();
+        assert!(state.dcd());
+        assert!(state.dsr());
+        assert!(state.break_received());
+        assert!(!state.ring());
+
+        // Other notification codes are skipped, not errors.
+        let other = [0xa1, 0x29, 0, 0, 0, 0, 0, 0];
+        assert_eq!(SerialState::parse(&other).unwrap(), None);
+        assert!(SerialState::parse(&[0xa1, NOTIFY_SERIAL_STATE]).is_err());
+    }
+}
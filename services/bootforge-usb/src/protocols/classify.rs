@@ -0,0 +1,856 @@
+// BootForge USB - Protocol classification
+// Heuristic detection of the protocols a device speaks, with an
+// allocation-free set-based fast path for watcher-driven pipelines.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::enumeration::{UsbDescriptorSummary, UsbDeviceInfo, UsbDeviceRecord};
+use crate::protocols::apple::APPLE_VID;
+
+/**
+ * A protocol a device is believed to speak.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Protocol {
+    Adb,
+    Fastboot,
+    Mtp,
+    Ptp,
+    Apple,
+    /// Bulk-only (BOT) mass storage.
+    MassStorage,
+    /// Phone re-enumerated in AOA accessory mode (18d1:2d00-2d05).
+    AndroidAccessory,
+    /// UAS mass storage (class 08, protocol 0x62), which needs USB3
+    /// bulk streams; distinct from BOT so stream diagnostics can tell
+    /// the two apart.
+    Uasp,
+    /// Qualcomm Emergency Download mode (05c6:9008 and friends), which
+    /// speaks Sahara/Firehose - not fastboot.
+    QualcommEdl,
+    /// Samsung bootloader download mode (Odin/LOKE over CDC-ACM).
+    SamsungDownload,
+}
+
+impl Protocol {
+    const ALL: [Protocol; 10] = [
+        Protocol::Adb,
+        Protocol::Fastboot,
+        Protocol::Mtp,
+        Protocol::Ptp,
+        Protocol::Apple,
+        Protocol::MassStorage,
+        Protocol::AndroidAccessory,
+        Protocol::Uasp,
+        Protocol::QualcommEdl,
+        Protocol::SamsungDownload,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << (self as u32)
+    }
+
+    /// The lowercase dashed name used in registry configs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Protocol::Adb => "adb",
+            Protocol::Fastboot => "fastboot",
+            Protocol::Mtp => "mtp",
+            Protocol::Ptp => "ptp",
+            Protocol::Apple => "apple",
+            Protocol::MassStorage => "mass-storage",
+            Protocol::AndroidAccessory => "android-accessory",
+            Protocol::Uasp => "uasp",
+            Protocol::QualcommEdl => "qualcomm-edl",
+            Protocol::SamsungDownload => "samsung-download",
+        }
+    }
+
+    /// Inverse of `name`, case-insensitive; None for unknown names.
+    pub fn by_name(name: &str) -> Option<Protocol> {
+        Protocol::ALL
+            .into_iter()
+            .find(|p| p.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/**
+ * Allocation-free set of detected protocols.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ProtocolSet(u32);
+
+impl ProtocolSet {
+    pub const EMPTY: ProtocolSet = ProtocolSet(0);
+
+    pub fn insert(&mut self, protocol: Protocol) {
+        self.0 |= protocol.bit();
+    }
+
+    pub fn remove(&mut self, protocol: Protocol) {
+        self.0 &= !protocol.bit();
+    }
+
+    pub fn contains(&self, protocol: Protocol) -> bool {
+        self.0 & protocol.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Protocol> + '_ {
+        Protocol::ALL.into_iter().filter(|p| self.contains(*p))
+    }
+}
+
+/**
+ * How much to trust a detection. Descriptor facts - class triples,
+ * known VID/PID tables - identify a protocol by construction; substring
+ * matches on vendor-supplied strings merely suggest one.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Confidence {
+    /// Attested by descriptors or a VID/PID table hit.
+    Definite,
+    /// Inferred from product/manufacturer strings.
+    Heuristic,
+}
+
+/**
+ * Classification result split by confidence, so callers can act on
+ * descriptor-backed detections and merely log the string-derived ones.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtocolClassification {
+    pub definite: ProtocolSet,
+    pub heuristic: ProtocolSet,
+}
+
+impl ProtocolClassification {
+    /// Everything detected, at any confidence.
+    pub fn all(&self) -> ProtocolSet {
+        ProtocolSet(self.definite.0 | self.heuristic.0)
+    }
+
+    /// The confidence a protocol was detected at; None if not detected.
+    pub fn confidence(&self, protocol: Protocol) -> Option<Confidence> {
+        if self.definite.contains(protocol) {
+            Some(Confidence::Definite)
+        } else if self.heuristic.contains(protocol) {
+            Some(Confidence::Heuristic)
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Protocol, Confidence)> + '_ {
+        Protocol::ALL
+            .into_iter()
+            .filter_map(|p| self.confidence(p).map(|c| (p, c)))
+    }
+}
+
+impl FromIterator<Protocol> for ProtocolSet {
+    fn from_iter<I: IntoIterator<Item = Protocol>>(iter: I) -> Self {
+        let mut set = ProtocolSet::EMPTY;
+        for p in iter {
+            set.insert(p);
+        }
+        set
+    }
+}
+
+impl fmt::Display for ProtocolSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for p in self.iter() {
+            if !first {
+                f.write_str("+")?;
+            }
+            write!(f, "{:?}", p)?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+/// Case-insensitive ASCII substring search without allocating.
+pub(crate) fn contains_ignore_ascii_case(haystack: &str, needle: &str) -> bool {
+    if needle.is_empty() {
+        return true;
+    }
+    let haystack = haystack.as_bytes();
+    let needle = needle.as_bytes();
+    haystack
+        .windows(needle.len())
+        .any(|w| w.eq_ignore_ascii_case(needle))
+}
+
+pub(crate) fn field_contains(field: &Option<String>, needle: &str) -> bool {
+    field
+        .as_deref()
+        .is_some_and(|s| contains_ignore_ascii_case(s, needle))
+}
+
+/// Facts read off the device identity alone - vendor id, AOA product id
+/// range, device-level class - reliable regardless of how the device
+/// was enumerated.
+fn classify_identity(vendor_id: u16, product_id: u16, descriptor: &UsbDescriptorSummary) -> ProtocolSet {
+    let mut set = ProtocolSet::EMPTY;
+
+    if vendor_id == APPLE_VID {
+        set.insert(Protocol::Apple);
+    }
+
+    match descriptor.device_class {
+        0x06 => set.insert(Protocol::Ptp),
+        0x08 => match descriptor.device_protocol {
+            0x62 => set.insert(Protocol::Uasp),
+            _ => set.insert(Protocol::MassStorage),
+        },
+        _ => {}
+    }
+
+    if crate::protocols::aoa::is_accessory_mode(vendor_id, product_id) {
+        set.insert(Protocol::AndroidAccessory);
+    }
+
+    if crate::protocols::edl::is_edl_mode(vendor_id, product_id) {
+        set.insert(Protocol::QualcommEdl);
+    }
+    if crate::protocols::odin::is_download_mode(vendor_id, product_id) {
+        set.insert(Protocol::SamsungDownload);
+    }
+
+    set
+}
+
+/// Product/manufacturer substring heuristics - the fallback of last
+/// resort, used only when no interface descriptors are available. Many
+/// vendors ship generic product strings, so these under-detect.
+fn classify_strings(set: &mut ProtocolSet, manufacturer: &Option<String>, product: &Option<String>) {
+    if field_contains(product, "fastboot") || field_contains(product, "bootloader") {
+        set.insert(Protocol::Fastboot);
+    }
+    if field_contains(product, "adb") || field_contains(manufacturer, "android") {
+        set.insert(Protocol::Adb);
+    }
+    if field_contains(product, "mtp") {
+        set.insert(Protocol::Mtp);
+    }
+}
+
+/// Interface class triple pass, shared by both enumeration paths. A
+/// still-image interface (06/01/01) next to an Android vendor interface
+/// is reported as MTP - Android serves MTP through the PTP class - and
+/// as PTP on its own.
+fn classify_triples(set: &mut ProtocolSet, triples: impl Iterator<Item = (u8, u8, u8)>) {
+    let mut still_image = false;
+    let mut android_vendor = false;
+    for (class, subclass, protocol) in triples {
+        if (class, subclass, protocol) == (0x06, 0x01, 0x01) {
+            still_image = true;
+        } else if let Some(p) = classify_interface(class, subclass, protocol) {
+            set.insert(p);
+        }
+        if (class, subclass) == (0xff, 0x42) {
+            android_vendor = true;
+        }
+    }
+    if still_image {
+        set.insert(if android_vendor {
+            Protocol::Mtp
+        } else {
+            Protocol::Ptp
+        });
+    }
+}
+
+/**
+ * Zero-allocation classification of the protocols a device speaks.
+ *
+ * Interface descriptors are authoritative when the record carries them;
+ * the product-string heuristics only run for records without interface
+ * data, since substring matching misclassifies vendors that ship
+ * generic strings.
+ */
+pub fn classify_device_protocols_set(record: &UsbDeviceRecord) -> ProtocolSet {
+    classify_device_record_protocols(record).all()
+}
+
+/**
+ * As `classify_device_protocols_set`, keeping descriptor-backed and
+ * string-derived detections apart.
+ */
+pub fn classify_device_record_protocols(record: &UsbDeviceRecord) -> ProtocolClassification {
+    let mut classification = ProtocolClassification {
+        definite: classify_identity(record.vendor_id, record.product_id, &record.descriptor),
+        heuristic: ProtocolSet::EMPTY,
+    };
+    if record.interfaces.is_empty() {
+        classify_strings(
+            &mut classification.heuristic,
+            &record.manufacturer,
+            &record.product,
+        );
+        classify_download_strings(&mut classification.heuristic, record.vendor_id, &record.product);
+    } else {
+        classify_triples(
+            &mut classification.definite,
+            record
+                .interfaces
+                .iter()
+                .map(|i| (i.class, i.subclass, i.protocol)),
+        );
+        classify_download_triples(
+            &mut classification.heuristic,
+            record.vendor_id,
+            record
+                .interfaces
+                .iter()
+                .map(|i| (i.class, i.subclass, i.protocol)),
+        );
+        classify_interface_strings(
+            &mut classification.heuristic,
+            record.vendor_id,
+            record.interfaces.iter().map(|i| &i.description),
+        );
+    }
+    suppress_fastboot_for_download_modes(&mut classification);
+    classification
+}
+
+/// Vendor-gated string heuristics for the dedicated download modes.
+fn classify_download_strings(set: &mut ProtocolSet, vendor_id: u16, product: &Option<String>) {
+    if vendor_id == crate::protocols::edl::QUALCOMM_VID
+        && crate::protocols::edl::strings_suggest_edl(product)
+    {
+        set.insert(Protocol::QualcommEdl);
+    }
+    if vendor_id == crate::protocols::odin::SAMSUNG_VID
+        && crate::protocols::odin::strings_suggest_download(product)
+    {
+        set.insert(Protocol::SamsungDownload);
+    }
+}
+
+/// Vendor-gated interface heuristics for the dedicated download modes.
+fn classify_download_triples(
+    set: &mut ProtocolSet,
+    vendor_id: u16,
+    mut triples: impl Iterator<Item = (u8, u8, u8)>,
+) {
+    if vendor_id == crate::protocols::edl::QUALCOMM_VID
+        && triples
+            .by_ref()
+            .any(|(c, s, p)| crate::protocols::edl::interface_suggests_edl(c, s, p))
+    {
+        set.insert(Protocol::QualcommEdl);
+    }
+    if vendor_id == crate::protocols::odin::SAMSUNG_VID
+        && triples.any(|(c, s, p)| crate::protocols::odin::interface_suggests_download(c, s, p))
+    {
+        set.insert(Protocol::SamsungDownload);
+    }
+}
+
+/// A device sitting in EDL or Odin mode is not a fastboot device,
+/// whatever its strings claim; drop the string-derived detection.
+fn suppress_fastboot_for_download_modes(classification: &mut ProtocolClassification) {
+    let all = classification.all();
+    if all.contains(Protocol::QualcommEdl) || all.contains(Protocol::SamsungDownload) {
+        classification.heuristic.remove(Protocol::Fastboot);
+    }
+}
+
+/**
+ * Classify a libusb-enumerated device under the same policy as
+ * `classify_device_protocols_set`: interface class triples from the
+ * active configuration when captured, string heuristics otherwise.
+ */
+pub fn classify_device_info_set(info: &UsbDeviceInfo) -> ProtocolSet {
+    classify_device_info_protocols(info).all()
+}
+
+/**
+ * As `classify_device_info_set`, keeping descriptor-backed and
+ * string-derived detections apart so callers can decide how much to
+ * trust each protocol.
+ */
+pub fn classify_device_info_protocols(info: &UsbDeviceInfo) -> ProtocolClassification {
+    let mut classification = ProtocolClassification {
+        definite: classify_identity(info.vendor_id, info.product_id, &info.descriptor),
+        heuristic: ProtocolSet::EMPTY,
+    };
+    if info.interfaces().is_empty() {
+        classify_strings(&mut classification.heuristic, &info.manufacturer, &info.product);
+        classify_download_strings(&mut classification.heuristic, info.vendor_id, &info.product);
+    } else {
+        classify_triples(
+            &mut classification.definite,
+            info.interfaces()
+                .iter()
+                .map(|i| (i.class, i.subclass, i.protocol)),
+        );
+        classify_download_triples(
+            &mut classification.heuristic,
+            info.vendor_id,
+            info.interfaces()
+                .iter()
+                .map(|i| (i.class, i.subclass, i.protocol)),
+        );
+        // iInterface strings name the function more precisely than a
+        // device-level product string ("ADB Interface" on an otherwise
+        // generic vendor-specific interface), so they get the string
+        // heuristics even though the product string does not here.
+        classify_interface_strings(
+            &mut classification.heuristic,
+            info.vendor_id,
+            info.interfaces().iter().map(|i| &i.description),
+        );
+    }
+    suppress_fastboot_for_download_modes(&mut classification);
+    classification
+}
+
+/// String heuristics over iInterface strings, where captured.
+fn classify_interface_strings<'a>(
+    set: &mut ProtocolSet,
+    vendor_id: u16,
+    descriptions: impl Iterator<Item = &'a Option<String>>,
+) {
+    for description in descriptions {
+        if description.is_some() {
+            classify_strings(set, &None, description);
+            classify_download_strings(set, vendor_id, description);
+        }
+    }
+}
+
+/**
+ * Classify one interface by its class triple; more precise than the
+ * device-level heuristics once interface descriptors are available.
+ * Most devices advertise storage transport per-interface, not in the
+ * device descriptor.
+ */
+pub fn classify_interface(class: u8, subclass: u8, protocol: u8) -> Option<Protocol> {
+    match (class, subclass, protocol) {
+        // Mass storage: 0x62 is UAS, 0x50 (and legacy CBI) is BOT.
+        (0x08, _, 0x62) => Some(Protocol::Uasp),
+        (0x08, _, _) => Some(Protocol::MassStorage),
+        (0x06, _, _) => Some(Protocol::Ptp),
+        // Android's vendor interface: ADB and fastboot.
+        (0xff, 0x42, 0x01) => Some(Protocol::Adb),
+        (0xff, 0x42, 0x03) => Some(Protocol::Fastboot),
+        // Apple's vendor interface as used by usbmuxd.
+        (0xff, 0xfe, 0x02) => Some(Protocol::Apple),
+        _ => None,
+    }
+}
+
+/**
+ * Vec-returning adapter kept for existing callers: the default
+ * registry, which is the built-in probes with nothing registered.
+ */
+pub fn classify_device_protocols(record: &UsbDeviceRecord) -> Vec<Protocol> {
+    crate::protocols::registry::ProtocolRegistry::default()
+        .classify(record)
+        .iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::version::BcdVersion;
+
+    fn record(
+        vendor_id: u16,
+        device_class: u8,
+        manufacturer: Option<&str>,
+        product: Option<&str>,
+    ) -> UsbDeviceRecord {
+        UsbDeviceRecord {
+            bus_number: 1,
+            device_number: 2,
+            vendor_id,
+            product_id: 0x0001,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0200),
+                device_version: BcdVersion(0x0100),
+                device_class,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: manufacturer.map(str::to_string),
+            product: product.map(str::to_string),
+            serial_number: None,
+            sysfs_path: String::new(),
+            speed: None,
+            interfaces: Vec::new(),
+        }
+    }
+
+    /// Composite device with generic strings: only the interface
+    /// descriptors say what it speaks.
+    fn composite(vendor_id: u16, product: &str, triples: &[(u8, u8, u8)]) -> UsbDeviceRecord {
+        use crate::enumeration::InterfaceSummary;
+        let mut r = record(vendor_id, 0x00, None, Some(product));
+        r.interfaces = triples
+            .iter()
+            .map(|&(class, subclass, protocol)| InterfaceSummary {
+                class,
+                subclass,
+                protocol,
+                description: None,
+            })
+            .collect();
+        r
+    }
+
+    #[test]
+    fn test_interface_data_beats_string_heuristics() {
+        // Five vendors whose product strings say nothing useful.
+        let cases: [(&str, UsbDeviceRecord, &[Protocol]); 5] = [
+            (
+                "OnePlus ADB+MTP",
+                composite(0x2a70, "SM8250-MTP", &[(0x06, 0x01, 0x01), (0xff, 0x42, 0x01)]),
+                &[Protocol::Adb, Protocol::Mtp],
+            ),
+            (
+                "Xiaomi fastboot",
+                composite(0x2717, "Merlin", &[(0xff, 0x42, 0x03)]),
+                &[Protocol::Fastboot],
+            ),
+            (
+                "Google ADB only",
+                composite(0x18d1, "Pixel 7", &[(0xff, 0x42, 0x01)]),
+                &[Protocol::Adb],
+            ),
+            (
+                "Apple usbmuxd",
+                composite(0x05ac, "iPhone", &[(0xff, 0xfe, 0x02), (0x06, 0x01, 0x01)]),
+                &[Protocol::Apple, Protocol::Ptp],
+            ),
+            (
+                "Samsung storage+ADB",
+                composite(0x04e8, "Galaxy", &[(0x08, 0x06, 0x50), (0xff, 0x42, 0x01)]),
+                &[Protocol::Adb, Protocol::MassStorage],
+            ),
+        ];
+
+        for (name, record, expected) in cases {
+            let set = classify_device_protocols_set(&record);
+            assert_eq!(
+                set,
+                expected.iter().copied().collect(),
+                "{}: got {}",
+                name,
+                set
+            );
+        }
+    }
+
+    #[test]
+    fn test_strings_ignored_when_interfaces_present() {
+        // Product string claims fastboot; the interfaces say ADB. The
+        // string heuristic must not fire.
+        let r = composite(0x2717, "Android Fastboot Gadget", &[(0xff, 0x42, 0x01)]);
+        let set = classify_device_protocols_set(&r);
+        assert!(set.contains(Protocol::Adb));
+        assert!(!set.contains(Protocol::Fastboot));
+    }
+
+    #[test]
+    fn test_classification_heuristics() {
+        let fastboot = record(0x18d1, 0, Some("Google"), Some("Android FASTBOOT Gadget"));
+        let set = classify_device_protocols_set(&fastboot);
+        assert!(set.contains(Protocol::Fastboot));
+        assert!(!set.contains(Protocol::Apple));
+
+        let apple = record(APPLE_VID, 0, Some("Apple Inc."), Some("iPhone"));
+        assert!(classify_device_protocols_set(&apple).contains(Protocol::Apple));
+
+        let msc = record(0x0781, 0x08, None, None);
+        assert!(classify_device_protocols_set(&msc).contains(Protocol::MassStorage));
+    }
+
+    #[test]
+    fn test_uasp_distinct_from_bot() {
+        let mut uas = record(0x0bc2, 0x08, None, Some("Expansion"));
+        uas.descriptor.device_protocol = 0x62;
+        let set = classify_device_protocols_set(&uas);
+        assert!(set.contains(Protocol::Uasp));
+        assert!(!set.contains(Protocol::MassStorage));
+
+        let bot = record(0x0781, 0x08, None, None);
+        let set = classify_device_protocols_set(&bot);
+        assert!(set.contains(Protocol::MassStorage));
+        assert!(!set.contains(Protocol::Uasp));
+    }
+
+    #[test]
+    fn test_interface_classification() {
+        assert_eq!(classify_interface(0x08, 0x06, 0x62), Some(Protocol::Uasp));
+        assert_eq!(
+            classify_interface(0x08, 0x06, 0x50),
+            Some(Protocol::MassStorage)
+        );
+        assert_eq!(classify_interface(0x06, 0x01, 0x01), Some(Protocol::Ptp));
+        assert_eq!(classify_interface(0xff, 0x42, 0x01), Some(Protocol::Adb));
+        assert_eq!(classify_interface(0xff, 0x42, 0x03), Some(Protocol::Fastboot));
+        assert_eq!(classify_interface(0x03, 0x01, 0x01), None);
+    }
+
+    fn info_with_interfaces(triples: &[(u8, u8, u8)]) -> UsbDeviceInfo {
+        use crate::enumeration::{ConfigInfo, InterfaceInfo};
+        UsbDeviceInfo {
+            bus_number: 1,
+            address: 4,
+            vendor_id: 0x18d1,
+            product_id: 0x4ee7,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0210),
+                device_version: BcdVersion(0x0440),
+                // Composite: nothing at the device level.
+                device_class: 0x00,
+                device_subclass: 0x00,
+                device_protocol: 0x00,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: Some("Google Inc.".to_string()),
+            product: Some("Pixel 7".to_string()),
+            serial_number: None,
+            port_path: None,
+            speed: None,
+            tags: Vec::new(),
+            active_config: Some(ConfigInfo {
+                configuration_value: 1,
+                max_power_ma: 500,
+                self_powered: false,
+                remote_wakeup: false,
+                description: None,
+                interfaces: triples
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(class, subclass, protocol))| InterfaceInfo {
+                        number: i as u8,
+                        alternate_setting: 0,
+                        class,
+                        subclass,
+                        protocol,
+                        description: None,
+                        endpoints: Vec::new(),
+                    })
+                    .collect(),
+            }),
+            usb_ids: None,
+            container_id: None,
+        }
+    }
+
+    #[test]
+    fn test_interface_string_outranks_generic_product() {
+        // A vendor-specific interface whose triple says nothing, but
+        // whose iInterface string names the function; the product
+        // string is a generic model name.
+        let mut phone = info_with_interfaces(&[(0xff, 0x00, 0x00)]);
+        phone.active_config.as_mut().unwrap().interfaces[0].description =
+            Some("ADB Interface".to_string());
+
+        let classification = classify_device_info_protocols(&phone);
+        assert!(classification.heuristic.contains(Protocol::Adb));
+        assert!(!classification.definite.contains(Protocol::Adb));
+
+        // Without the interface string the same device classifies as
+        // nothing: product strings are not consulted once interface
+        // descriptors exist.
+        let bare = info_with_interfaces(&[(0xff, 0x00, 0x00)]);
+        assert!(classify_device_info_set(&bare).is_empty());
+    }
+
+    #[test]
+    fn test_composite_phone_reports_both_interfaces() {
+        // MTP + ADB composite: device-level heuristics see neither.
+        let phone = info_with_interfaces(&[(0x06, 0x01, 0x01), (0xff, 0x42, 0x01)]);
+        let set = classify_device_info_set(&phone);
+        assert!(set.contains(Protocol::Adb));
+        assert!(set.contains(Protocol::Mtp));
+        assert!(!set.contains(Protocol::Ptp));
+    }
+
+    #[test]
+    fn test_lone_still_image_interface_is_ptp() {
+        let camera = info_with_interfaces(&[(0x06, 0x01, 0x01)]);
+        let set = classify_device_info_set(&camera);
+        assert!(set.contains(Protocol::Ptp));
+        assert!(!set.contains(Protocol::Mtp));
+    }
+
+    #[test]
+    fn test_info_without_config_falls_back_to_device_level() {
+        let mut phone = info_with_interfaces(&[]);
+        phone.active_config = None;
+        phone.product = Some("Android ADB Interface".to_string());
+        assert!(classify_device_info_set(&phone).contains(Protocol::Adb));
+    }
+
+    #[test]
+    fn test_edl_and_odin_are_not_fastboot() {
+        // 05c6:9008 with the ROM's product string: EDL, and no fastboot
+        // even though "bootloader"-ish strings are around.
+        let mut edl = record(0x05c6, 0x00, None, Some("QUSB_BULK_CID:0402_SN:B7C45925"));
+        edl.product_id = 0x9008;
+        let set = classify_device_protocols_set(&edl);
+        assert!(set.contains(Protocol::QualcommEdl));
+        assert!(!set.contains(Protocol::Fastboot));
+
+        // Odin mode advertising itself in the product string.
+        let mut odin = record(0x04e8, 0x02, None, Some("Samsung Bootloader Download"));
+        odin.product_id = 0x685d;
+        let set = classify_device_protocols_set(&odin);
+        assert!(set.contains(Protocol::SamsungDownload));
+        assert!(!set.contains(Protocol::Fastboot));
+
+        // The same strings on a non-download PID keep the fastboot
+        // heuristic alive.
+        let other = record(0x2717, 0x00, None, Some("Android Bootloader Interface"));
+        assert!(classify_device_protocols_set(&other).contains(Protocol::Fastboot));
+    }
+
+    #[test]
+    fn test_download_mode_interface_heuristics() {
+        // Qualcomm VID with the all-0xff loader interface, off-table PID.
+        let mut edl = composite(0x05c6, "unnamed", &[(0xff, 0xff, 0xff)]);
+        edl.product_id = 0x9091;
+        let classification = classify_device_record_protocols(&edl);
+        assert_eq!(
+            classification.confidence(Protocol::QualcommEdl),
+            Some(Confidence::Heuristic)
+        );
+
+        // Same triple on another vendor says nothing.
+        let vendor = composite(0x2717, "unnamed", &[(0xff, 0xff, 0xff)]);
+        assert!(!classify_device_protocols_set(&vendor).contains(Protocol::QualcommEdl));
+
+        // Table hits are definite regardless of strings.
+        let mut odin = record(0x04e8, 0x02, None, None);
+        odin.product_id = 0x685d;
+        let classification = classify_device_record_protocols(&odin);
+        assert_eq!(
+            classification.confidence(Protocol::SamsungDownload),
+            Some(Confidence::Definite)
+        );
+    }
+
+    #[test]
+    fn test_confidence_splits_descriptor_and_string_detections() {
+        // Interface triples: everything is descriptor-backed.
+        let phone = info_with_interfaces(&[(0x06, 0x01, 0x01), (0xff, 0x42, 0x01)]);
+        let classification = classify_device_info_protocols(&phone);
+        assert_eq!(classification.confidence(Protocol::Adb), Some(Confidence::Definite));
+        assert_eq!(classification.confidence(Protocol::Mtp), Some(Confidence::Definite));
+        assert!(classification.heuristic.is_empty());
+        assert_eq!(classification.all(), classify_device_info_set(&phone));
+
+        // No config captured: string matches are only heuristics, but a
+        // VID-table hit stays definite.
+        let mut bare = info_with_interfaces(&[]);
+        bare.active_config = None;
+        bare.vendor_id = APPLE_VID;
+        bare.product = Some("Android Fastboot Gadget".to_string());
+        let classification = classify_device_info_protocols(&bare);
+        assert_eq!(
+            classification.confidence(Protocol::Fastboot),
+            Some(Confidence::Heuristic)
+        );
+        assert_eq!(
+            classification.confidence(Protocol::Apple),
+            Some(Confidence::Definite)
+        );
+        assert_eq!(classification.confidence(Protocol::Uasp), None);
+
+        let pairs: Vec<(Protocol, Confidence)> = classification.iter().collect();
+        assert!(pairs.contains(&(Protocol::Fastboot, Confidence::Heuristic)));
+
+        // The record path applies the same policy.
+        let r = record(0x18d1, 0, Some("Google"), Some("Android FASTBOOT Gadget"));
+        let classification = classify_device_record_protocols(&r);
+        assert_eq!(
+            classification.confidence(Protocol::Fastboot),
+            Some(Confidence::Heuristic)
+        );
+        assert_eq!(classification.all(), classify_device_protocols_set(&r));
+    }
+
+    #[test]
+    fn test_set_and_vec_paths_agree() {
+        // Exercise every combination of the classification inputs and
+        // assert the Vec adapter always mirrors the set.
+        let vids = [0x05ac, 0x18d1, 0x0781];
+        let classes = [0x00, 0x06, 0x08];
+        let manufacturers = [None, Some("Android"), Some("Apple Inc.")];
+        let products = [None, Some("fastboot d"), Some("MTP Device"), Some("adb if")];
+
+        for vid in vids {
+            for class in classes {
+                for m in manufacturers {
+                    for p in products {
+                        let r = record(vid, class, m, p);
+                        let set = classify_device_protocols_set(&r);
+                        let vec = classify_device_protocols(&r);
+                        assert_eq!(vec.len(), set.len());
+                        for protocol in &vec {
+                            assert!(set.contains(*protocol));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_contains_ignore_ascii_case() {
+        assert!(contains_ignore_ascii_case("Android FASTBOOT Gadget", "fastboot"));
+        assert!(contains_ignore_ascii_case("mtp", "MTP"));
+        assert!(!contains_ignore_ascii_case("fastboo", "fastboot"));
+        assert!(contains_ignore_ascii_case("anything", ""));
+    }
+
+    // Micro-benchmark; run with `cargo test -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_set_vs_vec_classification() {
+        let r = record(0x18d1, 0x08, Some("Android"), Some("MTP fastboot adb"));
+        let iterations = 1_000_000;
+
+        let start = std::time::Instant::now();
+        let mut acc = 0usize;
+        for _ in 0..iterations {
+            acc += classify_device_protocols_set(&r).len();
+        }
+        let set_elapsed = start.elapsed();
+
+        let start = std::time::Instant::now();
+        for _ in 0..iterations {
+            acc += classify_device_protocols(&r).len();
+        }
+        let vec_elapsed = start.elapsed();
+
+        println!(
+            "set path: {:?}, vec path: {:?} ({} iterations, checksum {})",
+            set_elapsed, vec_elapsed, iterations, acc
+        );
+    }
+}
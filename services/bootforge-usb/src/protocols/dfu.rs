@@ -0,0 +1,1384 @@
+// BootForge USB - DFU protocol client
+// USB Device Firmware Upgrade (DFU 1.1) over control transfers, with
+// optional readback verification because "flashed successfully" from a
+// corrupted transfer is worse than a clean failure.
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::error::{classify_transfer_error, UsbError};
+use crate::protocols::verify::{crc32, Crc32, Verification, VerifyMode};
+use crate::transfer::{CancellationToken, ProgressSink, ProgressTracker, UsbTransport};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Class requests from DFU 1.1 section 3.
+const DFU_DETACH: u8 = 0;
+const DFU_DNLOAD: u8 = 1;
+const DFU_UPLOAD: u8 = 2;
+const DFU_GETSTATUS: u8 = 3;
+
+/// bDescriptorType of the DFU functional descriptor.
+const DFU_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+
+/// Application-specific class / DFU subclass marking a DFU interface.
+const DFU_INTERFACE_CLASS: u8 = 0xfe;
+const DFU_INTERFACE_SUBCLASS: u8 = 0x01;
+
+/// bmRequestType for class requests to the DFU interface.
+const REQUEST_OUT: u8 = 0x21;
+const REQUEST_IN: u8 = 0xa1;
+
+const STATUS_OK: u8 = 0;
+
+/// Bound on busy/manifest re-polls per block, against devices that
+/// never leave dfuDNBUSY.
+const MAX_STATUS_POLLS: u32 = 100;
+
+/**
+ * DFU 1.1 device states (section 4.1.2, bState).
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DfuState {
+    AppIdle,
+    AppDetach,
+    DfuIdle,
+    DnloadSync,
+    DnBusy,
+    DnloadIdle,
+    ManifestSync,
+    Manifest,
+    ManifestWaitReset,
+    UploadIdle,
+    Error,
+    /// A bState value outside the spec's table.
+    Unknown(u8),
+}
+
+impl DfuState {
+    pub fn from_byte(state: u8) -> Self {
+        match state {
+            0 => DfuState::AppIdle,
+            1 => DfuState::AppDetach,
+            2 => DfuState::DfuIdle,
+            3 => DfuState::DnloadSync,
+            4 => DfuState::DnBusy,
+            5 => DfuState::DnloadIdle,
+            6 => DfuState::ManifestSync,
+            7 => DfuState::Manifest,
+            8 => DfuState::ManifestWaitReset,
+            9 => DfuState::UploadIdle,
+            10 => DfuState::Error,
+            other => DfuState::Unknown(other),
+        }
+    }
+
+    fn into_byte(self) -> u8 {
+        match self {
+            DfuState::AppIdle => 0,
+            DfuState::AppDetach => 1,
+            DfuState::DfuIdle => 2,
+            DfuState::DnloadSync => 3,
+            DfuState::DnBusy => 4,
+            DfuState::DnloadIdle => 5,
+            DfuState::ManifestSync => 6,
+            DfuState::Manifest => 7,
+            DfuState::ManifestWaitReset => 8,
+            DfuState::UploadIdle => 9,
+            DfuState::Error => 10,
+            DfuState::Unknown(other) => other,
+        }
+    }
+
+    /// States the host answers with another GETSTATUS after waiting
+    /// bwPollTimeout, rather than the next request.
+    fn needs_repoll(self) -> bool {
+        matches!(
+            self,
+            DfuState::DnloadSync | DfuState::DnBusy | DfuState::ManifestSync | DfuState::Manifest
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DfuError {
+    #[error("device reported DFU status 0x{status:02x} in state 0x{state:02x}")]
+    Status { status: u8, state: u8 },
+
+    #[error("bad DFU file: {0}")]
+    BadFile(String),
+
+    #[error("file CRC mismatch: suffix says {expected:08x}, computed {actual:08x}")]
+    SuffixCrc { expected: u32, actual: u32 },
+
+    #[error(
+        "file targets {file_vendor:04x}:{file_product:04x}, device is \
+         {device_vendor:04x}:{device_product:04x}"
+    )]
+    TargetMismatch {
+        file_vendor: u16,
+        file_product: u16,
+        device_vendor: u16,
+        device_product: u16,
+    },
+
+    #[error("readback mismatch at offset {offset}")]
+    VerifyMismatch { offset: usize },
+
+    #[error("CRC mismatch: wrote {expected:08x}, read back {actual:08x}")]
+    CrcMismatch { expected: u32, actual: u32 },
+
+    #[error("device cannot upload (bmAttributes); readback verification unavailable")]
+    VerifyUnsupported,
+
+    #[error("DFU-mode device has no DFU functional descriptor")]
+    NoFunctionalDescriptor,
+
+    #[error(transparent)]
+    Usb(#[from] UsbError),
+}
+
+/// Firmware file containers: the 16-byte DFU suffix every .dfu file
+/// carries, and ST's DfuSe prefix with its target/element layout.
+pub mod file {
+    use super::DfuError;
+    use crate::protocols::verify::crc32;
+
+    const SUFFIX_LEN: usize = 16;
+    /// ucDfuSignature bytes, "DFU" stored reversed.
+    const SUFFIX_SIGNATURE: [u8; 3] = [0x55, 0x46, 0x44];
+    const DFUSE_PREFIX_LEN: usize = 11;
+    const DFUSE_TARGET_PREFIX_LEN: usize = 274;
+    /// bcdDFU value DfuSe files declare.
+    pub const BCD_DFU_DFUSE: u16 = 0x011a;
+
+    /// Wildcard vendor/product id in a suffix: matches any device.
+    pub const ANY_ID: u16 = 0xffff;
+
+    /// The suffix CRC: CRC-32 without the final inversion.
+    fn suffix_crc(bytes: &[u8]) -> u32 {
+        !crc32(bytes)
+    }
+
+    /**
+     * The DFU 1.1 file suffix (section 6.2), read from the last 16
+     * bytes of a firmware file.
+     */
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct DfuSuffix {
+        pub bcd_device: u16,
+        pub product_id: u16,
+        pub vendor_id: u16,
+        pub bcd_dfu: u16,
+        /// bLength; at least 16, larger values reserve room before the
+        /// signature.
+        pub length: u8,
+        pub crc: u32,
+    }
+
+    impl DfuSuffix {
+        pub fn parse(file: &[u8]) -> Result<DfuSuffix, DfuError> {
+            if file.len() < SUFFIX_LEN {
+                return Err(DfuError::BadFile(format!(
+                    "{} bytes is too short for a DFU suffix",
+                    file.len()
+                )));
+            }
+            let s = &file[file.len() - SUFFIX_LEN..];
+            if s[8..11] != SUFFIX_SIGNATURE {
+                return Err(DfuError::BadFile("missing DFU suffix signature".to_string()));
+            }
+            let length = s[11];
+            if usize::from(length) < SUFFIX_LEN || usize::from(length) > file.len() {
+                return Err(DfuError::BadFile(format!(
+                    "suffix length {} inconsistent with {}-byte file",
+                    length,
+                    file.len()
+                )));
+            }
+            Ok(DfuSuffix {
+                bcd_device: u16::from_le_bytes([s[0], s[1]]),
+                product_id: u16::from_le_bytes([s[2], s[3]]),
+                vendor_id: u16::from_le_bytes([s[4], s[5]]),
+                bcd_dfu: u16::from_le_bytes([s[6], s[7]]),
+                length,
+                crc: u32::from_le_bytes(s[12..16].try_into().unwrap()),
+            })
+        }
+
+        /// Check dwCRC against the file (everything but the CRC bytes).
+        pub fn verify_crc(&self, file: &[u8]) -> Result<(), DfuError> {
+            let actual = suffix_crc(&file[..file.len() - 4]);
+            if actual != self.crc {
+                return Err(DfuError::SuffixCrc {
+                    expected: self.crc,
+                    actual,
+                });
+            }
+            Ok(())
+        }
+
+        pub fn matches_device(&self, vendor_id: u16, product_id: u16) -> bool {
+            (self.vendor_id == ANY_ID || self.vendor_id == vendor_id)
+                && (self.product_id == ANY_ID || self.product_id == product_id)
+        }
+
+        pub fn is_dfuse(&self) -> bool {
+            self.bcd_dfu == BCD_DFU_DFUSE
+        }
+    }
+
+    /**
+     * A plain DFU file: raw firmware payload plus its validated suffix.
+     */
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DfuFile<'a> {
+        pub payload: &'a [u8],
+        pub suffix: DfuSuffix,
+    }
+
+    impl<'a> DfuFile<'a> {
+        /**
+         * Parse and validate: suffix present, CRC good, and the
+         * target ids matching the device unless `allow_mismatch`.
+         * Wildcard (0xffff) ids in the suffix always match.
+         */
+        pub fn from_bytes(
+            bytes: &'a [u8],
+            device: (u16, u16),
+            allow_mismatch: bool,
+        ) -> Result<DfuFile<'a>, DfuError> {
+            let suffix = DfuSuffix::parse(bytes)?;
+            suffix.verify_crc(bytes)?;
+            if !allow_mismatch && !suffix.matches_device(device.0, device.1) {
+                return Err(DfuError::TargetMismatch {
+                    file_vendor: suffix.vendor_id,
+                    file_product: suffix.product_id,
+                    device_vendor: device.0,
+                    device_product: device.1,
+                });
+            }
+            Ok(DfuFile {
+                payload: &bytes[..bytes.len() - usize::from(suffix.length)],
+                suffix,
+            })
+        }
+    }
+
+    /**
+     * One contiguous flash region inside a DfuSe target.
+     */
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DfuseElement<'a> {
+        pub address: u32,
+        pub data: &'a [u8],
+    }
+
+    /**
+     * One DfuSe target image: an alternate setting plus its elements.
+     */
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DfuseTarget<'a> {
+        pub alternate_setting: u8,
+        pub name: Option<String>,
+        pub elements: Vec<DfuseElement<'a>>,
+    }
+
+    /**
+     * ST's DfuSe container: a "DfuSe" prefix, per-target images, and
+     * the standard suffix at the end.
+     */
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DfuseFile<'a> {
+        pub targets: Vec<DfuseTarget<'a>>,
+        pub suffix: DfuSuffix,
+    }
+
+    impl<'a> DfuseFile<'a> {
+        pub fn parse(bytes: &'a [u8]) -> Result<DfuseFile<'a>, DfuError> {
+            let suffix = DfuSuffix::parse(bytes)?;
+            suffix.verify_crc(bytes)?;
+            if !suffix.is_dfuse() {
+                return Err(DfuError::BadFile(format!(
+                    "bcdDFU {:04x} is not a DfuSe file",
+                    suffix.bcd_dfu
+                )));
+            }
+            let body = &bytes[..bytes.len() - usize::from(suffix.length)];
+
+            if body.len() < DFUSE_PREFIX_LEN || &body[0..5] != b"DfuSe" {
+                return Err(DfuError::BadFile("missing DfuSe prefix".to_string()));
+            }
+            if body[5] != 1 {
+                return Err(DfuError::BadFile(format!(
+                    "unsupported DfuSe version {}",
+                    body[5]
+                )));
+            }
+            let target_count = body[10];
+
+            let mut targets = Vec::with_capacity(usize::from(target_count));
+            let mut at = DFUSE_PREFIX_LEN;
+            for _ in 0..target_count {
+                let (target, next) = Self::parse_target(body, at)?;
+                targets.push(target);
+                at = next;
+            }
+            Ok(DfuseFile { targets, suffix })
+        }
+
+        fn parse_target(body: &'a [u8], at: usize) -> Result<(DfuseTarget<'a>, usize), DfuError> {
+            let truncated = || DfuError::BadFile("truncated DfuSe target".to_string());
+            let prefix = body
+                .get(at..at + DFUSE_TARGET_PREFIX_LEN)
+                .ok_or_else(truncated)?;
+            if &prefix[0..6] != b"Target" {
+                return Err(DfuError::BadFile(
+                    "missing DfuSe target signature".to_string(),
+                ));
+            }
+            let alternate_setting = prefix[6];
+            let named = u32::from_le_bytes(prefix[7..11].try_into().unwrap()) != 0;
+            let name = if named {
+                let raw = &prefix[11..266];
+                let end = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+                Some(String::from_utf8_lossy(&raw[..end]).into_owned())
+            } else {
+                None
+            };
+            let element_count = u32::from_le_bytes(prefix[270..274].try_into().unwrap());
+
+            let mut elements = Vec::with_capacity(element_count as usize);
+            let mut cursor = at + DFUSE_TARGET_PREFIX_LEN;
+            for _ in 0..element_count {
+                let header = body.get(cursor..cursor + 8).ok_or_else(truncated)?;
+                let address = u32::from_le_bytes(header[0..4].try_into().unwrap());
+                let size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+                let data = body.get(cursor + 8..cursor + 8 + size).ok_or_else(truncated)?;
+                elements.push(DfuseElement { address, data });
+                cursor += 8 + size;
+            }
+            Ok((
+                DfuseTarget {
+                    alternate_setting,
+                    name,
+                    elements,
+                },
+                cursor,
+            ))
+        }
+    }
+}
+
+/**
+ * Capability bits from the DFU functional descriptor's bmAttributes.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfuCapabilities {
+    pub can_download: bool,
+    pub can_upload: bool,
+}
+
+impl DfuCapabilities {
+    /// Parse bmAttributes (bit 0 bitCanDnload, bit 1 bitCanUpload).
+    pub fn from_attributes(bits: u8) -> Self {
+        DfuCapabilities {
+            can_download: bits & 0x01 != 0,
+            can_upload: bits & 0x02 != 0,
+        }
+    }
+}
+
+/**
+ * The DFU functional descriptor (DFU 1.1 section 4.1.3), appended to
+ * the DFU interface's descriptors.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfuFunctionalDescriptor {
+    pub capabilities: DfuCapabilities,
+    /// bit 2: the device keeps talking after manifestation.
+    pub manifestation_tolerant: bool,
+    /// bit 3: the device detaches itself after DFU_DETACH, no reset
+    /// needed.
+    pub will_detach: bool,
+    pub detach_timeout_ms: u16,
+    pub transfer_size: u16,
+    /// bcdDFUVersion; absent in the older 7-byte form.
+    pub bcd_dfu_version: Option<u16>,
+}
+
+impl DfuFunctionalDescriptor {
+    /// Parse one descriptor starting at its bLength byte.
+    pub fn parse(bytes: &[u8]) -> Result<DfuFunctionalDescriptor, DfuError> {
+        if bytes.len() < 7 || bytes[1] != DFU_FUNCTIONAL_DESCRIPTOR {
+            return Err(DfuError::NoFunctionalDescriptor);
+        }
+        let length = usize::from(bytes[0]);
+        if length < 7 || length > bytes.len() {
+            return Err(DfuError::NoFunctionalDescriptor);
+        }
+        let attributes = bytes[2];
+        Ok(DfuFunctionalDescriptor {
+            capabilities: DfuCapabilities::from_attributes(attributes),
+            manifestation_tolerant: attributes & 0x04 != 0,
+            will_detach: attributes & 0x08 != 0,
+            detach_timeout_ms: u16::from_le_bytes([bytes[3], bytes[4]]),
+            transfer_size: u16::from_le_bytes([bytes[5], bytes[6]]),
+            bcd_dfu_version: if length >= 9 {
+                Some(u16::from_le_bytes([bytes[7], bytes[8]]))
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Walk an interface's class-specific descriptor bytes for the
+    /// functional descriptor.
+    pub fn find_in_extra(extra: &[u8]) -> Option<DfuFunctionalDescriptor> {
+        let mut at = 0;
+        while at + 2 <= extra.len() {
+            let length = usize::from(extra[at]);
+            if length < 2 || at + length > extra.len() {
+                return None;
+            }
+            if extra[at + 1] == DFU_FUNCTIONAL_DESCRIPTOR {
+                return Self::parse(&extra[at..at + length]).ok();
+            }
+            at += length;
+        }
+        None
+    }
+}
+
+/**
+ * Options for a DFU download, including how to verify it afterwards.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct DfuDownloadOptions {
+    /// Block size for the data phase; wTransferSize from the functional
+    /// descriptor.
+    pub transfer_size: u16,
+    pub verify: VerifyMode,
+}
+
+impl Default for DfuDownloadOptions {
+    fn default() -> Self {
+        DfuDownloadOptions {
+            transfer_size: 1024,
+            verify: VerifyMode::None,
+        }
+    }
+}
+
+impl DfuDownloadOptions {
+    pub fn with_transfer_size(mut self, transfer_size: u16) -> Self {
+        self.transfer_size = transfer_size.max(1);
+        self
+    }
+
+    pub fn with_verify(mut self, verify: VerifyMode) -> Self {
+        self.verify = verify;
+        self
+    }
+}
+
+/**
+ * What a download did, including whether the data on the device was
+ * verified against the source.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfuReport {
+    pub bytes_written: usize,
+    pub verification: Verification,
+}
+
+/**
+ * Blocking DFU client over the control endpoint of one interface.
+ */
+pub struct DfuClient<T: UsbTransport> {
+    transport: T,
+    interface: u16,
+    capabilities: DfuCapabilities,
+    last_state: DfuState,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<T: UsbTransport> DfuClient<T> {
+    pub fn new(transport: T, interface: u16, capabilities: DfuCapabilities) -> Self {
+        DfuClient {
+            transport,
+            interface,
+            capabilities,
+            last_state: DfuState::DfuIdle,
+            cancellation: None,
+        }
+    }
+
+    /// Observe `token` between blocks and between status polls, so a
+    /// multi-minute download can be aborted from another thread.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    fn check_cancelled(&self) -> Result<(), DfuError> {
+        if let Some(token) = &self.cancellation {
+            token.check()?;
+        }
+        Ok(())
+    }
+
+    /// State from the most recent GETSTATUS response.
+    pub fn last_state(&self) -> DfuState {
+        self.last_state
+    }
+
+    /**
+     * Download a firmware image: blocked DFU_DNLOAD writes with a
+     * GETSTATUS poll after each block, a zero-length block to enter
+     * manifestation, then verification per the options. Verification
+     * failures return Err - the report only ever describes a download
+     * whose contents were confirmed (or deliberately not checked).
+     */
+    pub fn download(
+        &mut self,
+        image: &[u8],
+        options: &DfuDownloadOptions,
+    ) -> Result<DfuReport, DfuError> {
+        self.download_with_progress(image, options, None)
+    }
+
+    /// `download` with per-block progress reporting.
+    pub fn download_with_progress(
+        &mut self,
+        image: &[u8],
+        options: &DfuDownloadOptions,
+        mut progress: Option<&mut dyn ProgressSink>,
+    ) -> Result<DfuReport, DfuError> {
+        if options.verify != VerifyMode::None && !self.capabilities.can_upload {
+            return Err(DfuError::VerifyUnsupported);
+        }
+
+        let tracker = ProgressTracker::new(image.len());
+        let mut written = 0;
+        let mut block: u16 = 0;
+        for chunk in image.chunks(usize::from(options.transfer_size)) {
+            self.check_cancelled()?;
+            self.dnload_block(block, chunk)?;
+            written += chunk.len();
+            tracker.report(written, &mut progress);
+            block = block.wrapping_add(1);
+        }
+        // Zero-length block signals end of transfer; the status poll
+        // drives manifestation.
+        self.dnload_block(block, &[])?;
+
+        let verification = match options.verify {
+            VerifyMode::None => Verification::Skipped,
+            mode => {
+                self.verify_against(image, options.transfer_size, mode)?;
+                Verification::Passed(mode)
+            }
+        };
+
+        Ok(DfuReport {
+            bytes_written: image.len(),
+            verification,
+        })
+    }
+
+    /**
+     * DFU_DETACH: ask the device to enter DFU mode within
+     * `timeout_ms`. Devices without bitWillDetach additionally need a
+     * bus reset; see `detach_and_wait`.
+     */
+    pub fn detach(&mut self, timeout_ms: u16) -> Result<(), DfuError> {
+        self.transport
+            .write_control(
+                REQUEST_OUT,
+                DFU_DETACH,
+                timeout_ms,
+                self.interface,
+                &[],
+                IO_TIMEOUT,
+            )
+            .map_err(classify_transfer_error)?;
+        Ok(())
+    }
+
+    /**
+     * Download a validated .dfu file. The file was CRC- and
+     * target-checked at parse time; this re-checks the target ids
+     * against the given device in case the file was parsed with
+     * `allow_mismatch`.
+     */
+    pub fn download_file(
+        &mut self,
+        file: &file::DfuFile<'_>,
+        device: (u16, u16),
+        options: &DfuDownloadOptions,
+    ) -> Result<DfuReport, DfuError> {
+        if !file.suffix.matches_device(device.0, device.1) {
+            return Err(DfuError::TargetMismatch {
+                file_vendor: file.suffix.vendor_id,
+                file_product: file.suffix.product_id,
+                device_vendor: device.0,
+                device_product: device.1,
+            });
+        }
+        self.download(file.payload, options)
+    }
+
+    /**
+     * Download a DfuSe file: each element is placed with the
+     * SET_ADDRESS_POINTER vendor command before its data blocks
+     * (which start at wBlockNum 2 in DfuSe mode), then a zero-length
+     * block manifests. Readback verification is not available on this
+     * path - DfuSe readback would need its own address bookkeeping.
+     */
+    pub fn download_dfuse(
+        &mut self,
+        file: &file::DfuseFile<'_>,
+        options: &DfuDownloadOptions,
+    ) -> Result<DfuReport, DfuError> {
+        if options.verify != VerifyMode::None {
+            return Err(DfuError::VerifyUnsupported);
+        }
+
+        let mut bytes_written = 0;
+        for target in &file.targets {
+            for element in &target.elements {
+                self.set_address_pointer(element.address)?;
+                let mut block: u16 = 2;
+                for chunk in element.data.chunks(usize::from(options.transfer_size.max(1))) {
+                    self.dnload_block(block, chunk)?;
+                    block = block.wrapping_add(1);
+                }
+                bytes_written += element.data.len();
+            }
+        }
+        self.dnload_block(0, &[])?;
+
+        Ok(DfuReport {
+            bytes_written,
+            verification: Verification::Skipped,
+        })
+    }
+
+    /// DfuSe SET_ADDRESS_POINTER: command 0x21 in a block-0 download.
+    fn set_address_pointer(&mut self, address: u32) -> Result<(), DfuError> {
+        let mut command = vec![0x21];
+        command.extend_from_slice(&address.to_le_bytes());
+        self.dnload_block(0, &command)
+    }
+
+    /**
+     * Upload `length` bytes from the device. Public because hosts also
+     * use it to pull firmware images off boards that allow it.
+     */
+    pub fn upload(&mut self, length: usize, transfer_size: u16) -> Result<Vec<u8>, DfuError> {
+        self.upload_with_progress(length, transfer_size, None)
+    }
+
+    /// `upload` with per-block progress reporting.
+    pub fn upload_with_progress(
+        &mut self,
+        length: usize,
+        transfer_size: u16,
+        mut progress: Option<&mut dyn ProgressSink>,
+    ) -> Result<Vec<u8>, DfuError> {
+        if !self.capabilities.can_upload {
+            return Err(DfuError::VerifyUnsupported);
+        }
+        let tracker = ProgressTracker::new(length);
+        let mut out = Vec::with_capacity(length);
+        self.upload_blocks(length, transfer_size, |chunk, offset| {
+            out.extend_from_slice(chunk);
+            tracker.report(offset + chunk.len(), &mut progress);
+            Ok(())
+        })?;
+        Ok(out)
+    }
+
+    /// Read back the image's length and compare against the source,
+    /// either byte-for-byte or by streaming CRC32.
+    fn verify_against(
+        &mut self,
+        source: &[u8],
+        transfer_size: u16,
+        mode: VerifyMode,
+    ) -> Result<(), DfuError> {
+        match mode {
+            VerifyMode::None => Ok(()),
+            VerifyMode::Readback => self.upload_blocks(source.len(), transfer_size, |chunk, offset| {
+                for (i, (read, written)) in chunk.iter().zip(&source[offset..]).enumerate() {
+                    if read != written {
+                        return Err(DfuError::VerifyMismatch { offset: offset + i });
+                    }
+                }
+                Ok(())
+            }),
+            VerifyMode::Crc => {
+                let mut readback = Crc32::new();
+                self.upload_blocks(source.len(), transfer_size, |chunk, _| {
+                    readback.update(chunk);
+                    Ok(())
+                })?;
+                let expected = crc32(source);
+                let actual = readback.finalize();
+                if expected != actual {
+                    return Err(DfuError::CrcMismatch { expected, actual });
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Blocked DFU_UPLOAD of exactly `length` bytes, handing each chunk
+    /// and its offset to the sink.
+    fn upload_blocks(
+        &mut self,
+        length: usize,
+        transfer_size: u16,
+        mut sink: impl FnMut(&[u8], usize) -> Result<(), DfuError>,
+    ) -> Result<(), DfuError> {
+        let mut buf = vec![0u8; usize::from(transfer_size.max(1))];
+        let mut block: u16 = 0;
+        let mut offset = 0;
+        while offset < length {
+            self.check_cancelled()?;
+            let want = buf.len().min(length - offset);
+            let n = self
+                .transport
+                .read_control(
+                    REQUEST_IN,
+                    DFU_UPLOAD,
+                    block,
+                    self.interface,
+                    &mut buf[..want],
+                    IO_TIMEOUT,
+                )
+                .map_err(classify_transfer_error)?;
+            if n == 0 {
+                // Device ran out of data before the written length.
+                return Err(DfuError::VerifyMismatch { offset });
+            }
+            sink(&buf[..n], offset)?;
+            offset += n;
+            block = block.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    fn dnload_block(&mut self, block: u16, data: &[u8]) -> Result<(), DfuError> {
+        self.transport
+            .write_control(
+                REQUEST_OUT,
+                DFU_DNLOAD,
+                block,
+                self.interface,
+                data,
+                IO_TIMEOUT,
+            )
+            .map_err(classify_transfer_error)?;
+        // DFU 1.1 section 6.1.2: the device answers GETSTATUS from
+        // dfuDNLOAD-SYNC with dfuDNBUSY until the block is programmed;
+        // the host waits bwPollTimeout and polls again. The same loop
+        // drives manifestation after the zero-length block.
+        for _ in 0..MAX_STATUS_POLLS {
+            self.check_cancelled()?;
+            if !self.get_status()?.needs_repoll() {
+                return Ok(());
+            }
+        }
+        Err(DfuError::Status {
+            status: STATUS_OK,
+            state: self.last_state.into_byte(),
+        })
+    }
+
+    /// DFU_GETSTATUS; errors if the device reports anything but OK.
+    /// Honours bwPollTimeout before the next request and returns the
+    /// reported state.
+    fn get_status(&mut self) -> Result<DfuState, DfuError> {
+        let mut buf = [0u8; 6];
+        let n = self
+            .transport
+            .read_control(REQUEST_IN, DFU_GETSTATUS, 0, self.interface, &mut buf, IO_TIMEOUT)
+            .map_err(classify_transfer_error)?;
+        if n < 6 {
+            return Err(DfuError::Status {
+                status: 0xff,
+                state: 0xff,
+            });
+        }
+        self.last_state = DfuState::from_byte(buf[4]);
+        if buf[0] != STATUS_OK {
+            return Err(DfuError::Status {
+                status: buf[0],
+                state: buf[4],
+            });
+        }
+        let poll_ms = u32::from_le_bytes([buf[1], buf[2], buf[3], 0]);
+        if poll_ms > 0 {
+            std::thread::sleep(Duration::from_millis(u64::from(poll_ms.min(5000))));
+        }
+        Ok(self.last_state)
+    }
+}
+
+/**
+ * How to recognise the device once it reappears in DFU mode. Many
+ * devices change product id when they detach, so the serial number
+ * and an explicit DFU-mode id pair are both accepted.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct DfuModeTarget {
+    pub serial_number: Option<String>,
+    /// (vendor_id, product_id) of the DFU-mode identity.
+    pub ids: Option<(u16, u16)>,
+}
+
+impl DfuModeTarget {
+    fn matches(&self, info: &crate::enumeration::UsbDeviceInfo) -> bool {
+        if let Some((vendor, product)) = self.ids {
+            if info.vendor_id == vendor && info.product_id == product {
+                return true;
+            }
+        }
+        match (&self.serial_number, &info.serial_number) {
+            (Some(want), Some(have)) => want == have,
+            _ => false,
+        }
+    }
+}
+
+/// How often `detach_and_wait` re-enumerates while waiting.
+const REENUMERATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/**
+ * Send DFU_DETACH on the runtime interface, drop the device off the
+ * bus (an explicit reset unless it advertises bitWillDetach), then
+ * poll enumeration until the DFU-mode identity shows up. Returns the
+ * freshly opened handle and the functional descriptor of its DFU
+ * interface.
+ */
+pub fn detach_and_wait(
+    handle: rusb::DeviceHandle<rusb::Context>,
+    interface: u16,
+    will_detach: bool,
+    target: &DfuModeTarget,
+    timeout: Duration,
+) -> Result<(rusb::DeviceHandle<rusb::Context>, DfuFunctionalDescriptor), DfuError> {
+    if target.serial_number.is_none() && target.ids.is_none() {
+        return Err(UsbError::Parse(
+            "DfuModeTarget needs a serial number or id pair".to_string(),
+        )
+        .into());
+    }
+
+    handle
+        .write_control(
+            REQUEST_OUT,
+            DFU_DETACH,
+            timeout.as_millis().min(u128::from(u16::MAX)) as u16,
+            interface,
+            &[],
+            IO_TIMEOUT,
+        )
+        .map_err(classify_transfer_error)?;
+    if !will_detach {
+        // NotFound/NoDevice here just means the device already left.
+        match handle.reset() {
+            Ok(()) | Err(rusb::Error::NotFound) | Err(rusb::Error::NoDevice) => {}
+            Err(e) => return Err(UsbError::from(e).into()),
+        }
+    }
+    drop(handle);
+
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        if let Ok(devices) = crate::enumeration::enumerate_libusb() {
+            if let Some(info) = devices.iter().find(|info| target.matches(info)) {
+                let handle = info.open()?;
+                let descriptor = functional_descriptor_of(&handle)?;
+                return Ok((handle, descriptor));
+            }
+        }
+        if std::time::Instant::now() >= deadline {
+            return Err(UsbError::NotFound(
+                "device did not reappear in DFU mode".to_string(),
+            )
+            .into());
+        }
+        std::thread::sleep(REENUMERATE_INTERVAL);
+    }
+}
+
+/// Find the DFU interface in the active configuration and parse its
+/// functional descriptor.
+fn functional_descriptor_of(
+    handle: &rusb::DeviceHandle<rusb::Context>,
+) -> Result<DfuFunctionalDescriptor, DfuError> {
+    let config = handle
+        .device()
+        .active_config_descriptor()
+        .map_err(UsbError::from)?;
+    for interface in config.interfaces() {
+        for descriptor in interface.descriptors() {
+            if descriptor.class_code() != DFU_INTERFACE_CLASS
+                || descriptor.sub_class_code() != DFU_INTERFACE_SUBCLASS
+            {
+                continue;
+            }
+            if let Some(parsed) = DfuFunctionalDescriptor::find_in_extra(descriptor.extra()) {
+                return Ok(parsed);
+            }
+        }
+    }
+    Err(DfuError::NoFunctionalDescriptor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    const STATUS_OK_FRAME: &[u8] = &[0, 0, 0, 0, 5, 0];
+
+    fn client(capabilities: DfuCapabilities) -> DfuClient<MockTransport> {
+        DfuClient::new(MockTransport::new(), 0, capabilities)
+    }
+
+    fn both() -> DfuCapabilities {
+        DfuCapabilities {
+            can_download: true,
+            can_upload: true,
+        }
+    }
+
+    #[test]
+    fn test_capabilities_from_attributes() {
+        let caps = DfuCapabilities::from_attributes(0x03);
+        assert!(caps.can_download && caps.can_upload);
+        let caps = DfuCapabilities::from_attributes(0x01);
+        assert!(caps.can_download && !caps.can_upload);
+    }
+
+    #[test]
+    fn test_download_with_readback_verification() {
+        let mut client = client(both());
+        // One data block + ZLP, each followed by GETSTATUS, then the
+        // readback upload.
+        client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        client.transport.control_read_results.push_back(Ok(vec![1, 2, 3, 4]));
+
+        let options = DfuDownloadOptions::default()
+            .with_transfer_size(4)
+            .with_verify(VerifyMode::Readback);
+        let report = client.download(&[1, 2, 3, 4], &options).unwrap();
+        assert_eq!(report.bytes_written, 4);
+        assert_eq!(report.verification, Verification::Passed(VerifyMode::Readback));
+    }
+
+    #[test]
+    fn test_cancel_stops_download_within_one_block() {
+        let mut client = client(both());
+        // Enough status frames for both blocks, were the download to
+        // run to completion.
+        for _ in 0..3 {
+            client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        }
+
+        let token = CancellationToken::new();
+        let cancel_handle = token.clone();
+        client = client.with_cancellation(token);
+
+        let options = DfuDownloadOptions::default().with_transfer_size(4);
+        let mut sink =
+            move |_: &crate::transfer::TransferProgress| cancel_handle.cancel();
+        let err = client
+            .download_with_progress(&[0u8; 8], &options, Some(&mut sink))
+            .unwrap_err();
+        assert!(matches!(err, DfuError::Usb(UsbError::Cancelled)));
+
+        // Only the first block went out; neither the second block nor
+        // the terminating zero-length block was sent.
+        let dnloads = client
+            .transport
+            .control_requests
+            .iter()
+            .filter(|r| r.request == DFU_DNLOAD)
+            .count();
+        assert_eq!(dnloads, 1);
+    }
+
+    #[test]
+    fn test_readback_mismatch_detected() {
+        let mut client = client(both());
+        client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        // Third byte flipped on the device.
+        client.transport.control_read_results.push_back(Ok(vec![1, 2, 0xff, 4]));
+
+        let options = DfuDownloadOptions::default()
+            .with_transfer_size(4)
+            .with_verify(VerifyMode::Readback);
+        let err = client.download(&[1, 2, 3, 4], &options).unwrap_err();
+        assert!(matches!(err, DfuError::VerifyMismatch { offset: 2 }));
+    }
+
+    #[test]
+    fn test_crc_mismatch_detected() {
+        let mut client = client(both());
+        client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        client.transport.control_read_results.push_back(Ok(vec![9, 9, 9, 9]));
+
+        let options = DfuDownloadOptions::default()
+            .with_transfer_size(4)
+            .with_verify(VerifyMode::Crc);
+        assert!(matches!(
+            client.download(&[1, 2, 3, 4], &options).unwrap_err(),
+            DfuError::CrcMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_verify_requires_can_upload() {
+        let mut client = client(DfuCapabilities {
+            can_download: true,
+            can_upload: false,
+        });
+        let options = DfuDownloadOptions::default().with_verify(VerifyMode::Readback);
+        assert!(matches!(
+            client.download(&[1, 2, 3], &options).unwrap_err(),
+            DfuError::VerifyUnsupported
+        ));
+        // Nothing was written before the refusal.
+        assert!(client.transport.control_requests.is_empty());
+    }
+
+    fn frame(state: u8) -> Vec<u8> {
+        vec![0, 0, 0, 0, state, 0]
+    }
+
+    #[test]
+    fn test_download_walks_the_dnload_state_machine() {
+        let mut client = client(both());
+        // One data block: dfuDNLOAD-SYNC answers dfuDNBUSY, then the
+        // re-poll lands in dfuDNLOAD-IDLE.
+        client.transport.control_read_results.push_back(Ok(frame(4)));
+        client.transport.control_read_results.push_back(Ok(frame(5)));
+        // ZLP: manifestation, polled through dfuMANIFEST back to
+        // dfuIDLE.
+        client.transport.control_read_results.push_back(Ok(frame(7)));
+        client.transport.control_read_results.push_back(Ok(frame(2)));
+
+        let options = DfuDownloadOptions::default().with_transfer_size(4);
+        client.download(&[1, 2, 3, 4], &options).unwrap();
+
+        // Every scripted poll was consumed and the device came to rest.
+        assert!(client.transport.control_read_results.is_empty());
+        assert_eq!(client.last_state(), DfuState::DfuIdle);
+    }
+
+    #[test]
+    fn test_stuck_busy_device_errors_out() {
+        let mut client = client(both());
+        for _ in 0..=MAX_STATUS_POLLS {
+            client.transport.control_read_results.push_back(Ok(frame(4)));
+        }
+        let options = DfuDownloadOptions::default().with_transfer_size(4);
+        assert!(matches!(
+            client.download(&[1, 2], &options).unwrap_err(),
+            DfuError::Status { status: 0, state: 4 }
+        ));
+    }
+
+    #[test]
+    fn test_download_reports_progress_per_block() {
+        let mut client = client(both());
+        // Two data blocks plus the manifest ZLP, one status poll each.
+        for _ in 0..3 {
+            client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        }
+
+        let mut seen = Vec::new();
+        {
+            let mut sink =
+                |p: &crate::transfer::TransferProgress| seen.push((p.bytes_done, p.bytes_total));
+            let options = DfuDownloadOptions::default().with_transfer_size(2);
+            client
+                .download_with_progress(&[1, 2, 3, 4], &options, Some(&mut sink))
+                .unwrap();
+        }
+        assert_eq!(seen, vec![(2, 4), (4, 4)]);
+    }
+
+    #[test]
+    fn test_detach_request_encoding() {
+        let mut client = client(both());
+        client.detach(1000).unwrap();
+        let request = &client.transport.control_requests[0];
+        assert_eq!(request.request_type, REQUEST_OUT);
+        assert_eq!(request.request, DFU_DETACH);
+        assert_eq!(request.value, 1000);
+        assert!(request.data.is_empty());
+    }
+
+    #[test]
+    fn test_functional_descriptor_parsing() {
+        // 9-byte form: bLength, type, bmAttributes (willDetach |
+        // manifestation tolerant | upload | dnload), wDetachTimeOut,
+        // wTransferSize, bcdDFUVersion.
+        let full = [9, 0x21, 0x0f, 0xe8, 0x03, 0x00, 0x04, 0x10, 0x01];
+        let parsed = DfuFunctionalDescriptor::parse(&full).unwrap();
+        assert!(parsed.capabilities.can_download && parsed.capabilities.can_upload);
+        assert!(parsed.manifestation_tolerant && parsed.will_detach);
+        assert_eq!(parsed.detach_timeout_ms, 1000);
+        assert_eq!(parsed.transfer_size, 1024);
+        assert_eq!(parsed.bcd_dfu_version, Some(0x0110));
+
+        // Older 7-byte form has no bcdDFUVersion.
+        let short = [7, 0x21, 0x01, 0xff, 0x00, 0x00, 0x08];
+        let parsed = DfuFunctionalDescriptor::parse(&short).unwrap();
+        assert_eq!(parsed.bcd_dfu_version, None);
+        assert!(!parsed.will_detach);
+
+        assert!(DfuFunctionalDescriptor::parse(&[9, 0x20, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_functional_descriptor_found_among_extras() {
+        // An endpoint-ish descriptor, then the functional descriptor.
+        let extra = [
+            5, 0x24, 0, 0, 0, // some other class-specific descriptor
+            9, 0x21, 0x0b, 0xff, 0x00, 0x00, 0x04, 0x10, 0x01,
+        ];
+        let found = DfuFunctionalDescriptor::find_in_extra(&extra).unwrap();
+        assert_eq!(found.transfer_size, 1024);
+        assert!(found.will_detach);
+
+        assert!(DfuFunctionalDescriptor::find_in_extra(&[5, 0x24, 0, 0, 0]).is_none());
+        assert!(DfuFunctionalDescriptor::find_in_extra(&[]).is_none());
+    }
+
+    #[test]
+    fn test_dfu_mode_target_matching() {
+        let info = crate::watch::partial_info(
+            0x0483,
+            0xdf11,
+            Some("SER123".to_string()),
+            None,
+            "test".to_string(),
+        );
+
+        let by_ids = DfuModeTarget {
+            ids: Some((0x0483, 0xdf11)),
+            ..DfuModeTarget::default()
+        };
+        assert!(by_ids.matches(&info));
+
+        let by_serial = DfuModeTarget {
+            serial_number: Some("SER123".to_string()),
+            ..DfuModeTarget::default()
+        };
+        assert!(by_serial.matches(&info));
+
+        let wrong = DfuModeTarget {
+            ids: Some((0x1234, 0x5678)),
+            serial_number: Some("OTHER".to_string()),
+        };
+        assert!(!wrong.matches(&info));
+
+        // An empty target matches nothing rather than everything.
+        assert!(!DfuModeTarget::default().matches(&info));
+    }
+
+    /// Append a DFU suffix (with a correct CRC) to a payload.
+    fn with_suffix(payload: &[u8], vendor: u16, product: u16, bcd_dfu: u16) -> Vec<u8> {
+        let mut out = payload.to_vec();
+        out.extend_from_slice(&0x0100u16.to_le_bytes()); // bcdDevice
+        out.extend_from_slice(&product.to_le_bytes());
+        out.extend_from_slice(&vendor.to_le_bytes());
+        out.extend_from_slice(&bcd_dfu.to_le_bytes());
+        out.extend_from_slice(&[0x55, 0x46, 0x44]); // "DFU" reversed
+        out.push(16); // bLength
+        let crc = !crc32(&out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// A small DfuSe file: one named target with two elements.
+    fn dfuse_fixture() -> Vec<u8> {
+        let mut target = b"Target".to_vec();
+        target.push(0); // bAlternateSetting
+        target.extend_from_slice(&1u32.to_le_bytes()); // bTargetNamed
+        let mut name = [0u8; 255];
+        name[..8].copy_from_slice(b"Internal");
+        target.extend_from_slice(&name);
+        target.extend_from_slice(&22u32.to_le_bytes()); // dwTargetSize
+        target.extend_from_slice(&2u32.to_le_bytes()); // dwNbElements
+        target.extend_from_slice(&0x0800_0000u32.to_le_bytes());
+        target.extend_from_slice(&4u32.to_le_bytes());
+        target.extend_from_slice(&[1, 2, 3, 4]);
+        target.extend_from_slice(&0x0800_1000u32.to_le_bytes());
+        target.extend_from_slice(&2u32.to_le_bytes());
+        target.extend_from_slice(&[9, 9]);
+
+        let mut body = b"DfuSe".to_vec();
+        body.push(1); // bVersion
+        body.extend_from_slice(&0u32.to_le_bytes()); // DFUImageSize, patched below
+        body.push(1); // bTargets
+        body.extend_from_slice(&target);
+        let size = body.len() as u32;
+        body[6..10].copy_from_slice(&size.to_le_bytes());
+
+        with_suffix(&body, 0x0483, 0xdf11, file::BCD_DFU_DFUSE)
+    }
+
+    #[test]
+    fn test_suffix_parse_and_crc() {
+        let bytes = with_suffix(&[1, 2, 3], 0x0483, 0xdf11, 0x0100);
+        let suffix = file::DfuSuffix::parse(&bytes).unwrap();
+        assert_eq!(suffix.vendor_id, 0x0483);
+        assert_eq!(suffix.product_id, 0xdf11);
+        assert_eq!(suffix.bcd_dfu, 0x0100);
+        assert_eq!(suffix.length, 16);
+        suffix.verify_crc(&bytes).unwrap();
+
+        // A flipped payload byte no longer matches dwCRC.
+        let mut corrupted = bytes.clone();
+        corrupted[0] ^= 0xff;
+        let suffix = file::DfuSuffix::parse(&corrupted).unwrap();
+        assert!(matches!(
+            suffix.verify_crc(&corrupted),
+            Err(DfuError::SuffixCrc { .. })
+        ));
+
+        // No signature at all.
+        assert!(matches!(
+            file::DfuSuffix::parse(&[0u8; 16]),
+            Err(DfuError::BadFile(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_checks_target_ids() {
+        let bytes = with_suffix(&[1, 2, 3], 0x0483, 0xdf11, 0x0100);
+
+        let file = file::DfuFile::from_bytes(&bytes, (0x0483, 0xdf11), false).unwrap();
+        assert_eq!(file.payload, &[1, 2, 3]);
+
+        assert!(matches!(
+            file::DfuFile::from_bytes(&bytes, (0x1234, 0x5678), false),
+            Err(DfuError::TargetMismatch { .. })
+        ));
+        assert!(file::DfuFile::from_bytes(&bytes, (0x1234, 0x5678), true).is_ok());
+
+        // Wildcard ids in the suffix match any device.
+        let wild = with_suffix(&[1, 2, 3], file::ANY_ID, file::ANY_ID, 0x0100);
+        assert!(file::DfuFile::from_bytes(&wild, (0x1234, 0x5678), false).is_ok());
+    }
+
+    #[test]
+    fn test_dfuse_multi_element_parse() {
+        let bytes = dfuse_fixture();
+        let parsed = file::DfuseFile::parse(&bytes).unwrap();
+        assert_eq!(parsed.targets.len(), 1);
+        let target = &parsed.targets[0];
+        assert_eq!(target.alternate_setting, 0);
+        assert_eq!(target.name.as_deref(), Some("Internal"));
+        assert_eq!(target.elements.len(), 2);
+        assert_eq!(target.elements[0].address, 0x0800_0000);
+        assert_eq!(target.elements[0].data, &[1, 2, 3, 4]);
+        assert_eq!(target.elements[1].address, 0x0800_1000);
+        assert_eq!(target.elements[1].data, &[9, 9]);
+
+        // A plain suffix without the DfuSe prefix is rejected.
+        let plain = with_suffix(&[1, 2, 3], 0x0483, 0xdf11, 0x0100);
+        assert!(matches!(
+            file::DfuseFile::parse(&plain),
+            Err(DfuError::BadFile(_))
+        ));
+    }
+
+    #[test]
+    fn test_download_file_rechecks_device() {
+        let bytes = with_suffix(&[1, 2, 3], 0x0483, 0xdf11, 0x0100);
+        let file = file::DfuFile::from_bytes(&bytes, (0x1111, 0x2222), true).unwrap();
+
+        let mut client = client(both());
+        let options = DfuDownloadOptions::default();
+        assert!(matches!(
+            client.download_file(&file, (0x1111, 0x2222), &options),
+            Err(DfuError::TargetMismatch { .. })
+        ));
+        // Refused before anything reached the device.
+        assert!(client.transport.control_requests.is_empty());
+    }
+
+    #[test]
+    fn test_dfuse_download_sets_address_pointers() {
+        let bytes = dfuse_fixture();
+        let file = file::DfuseFile::parse(&bytes).unwrap();
+
+        let mut client = client(both());
+        // Each download block polls status once: two SET_ADDRESS_POINTER
+        // commands, two data blocks, one manifest ZLP.
+        for _ in 0..5 {
+            client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        }
+
+        let options = DfuDownloadOptions::default().with_transfer_size(4);
+        let report = client.download_dfuse(&file, &options).unwrap();
+        assert_eq!(report.bytes_written, 6);
+
+        let dnloads: Vec<_> = client
+            .transport
+            .control_requests
+            .iter()
+            .filter(|r| r.request == DFU_DNLOAD)
+            .collect();
+        // SET_ADDRESS_POINTER for the first element, then its data at
+        // wBlockNum 2.
+        assert_eq!(dnloads[0].value, 0);
+        assert_eq!(dnloads[0].data, vec![0x21, 0x00, 0x00, 0x00, 0x08]);
+        assert_eq!(dnloads[1].value, 2);
+        assert_eq!(dnloads[1].data, vec![1, 2, 3, 4]);
+        // Second element re-targets before its data.
+        assert_eq!(dnloads[2].data, vec![0x21, 0x00, 0x10, 0x00, 0x08]);
+        assert_eq!(dnloads[3].value, 2);
+        assert_eq!(dnloads[3].data, vec![9, 9]);
+        // Manifest ZLP closes the transfer.
+        assert!(dnloads[4].data.is_empty());
+    }
+
+    #[test]
+    fn test_dnload_encoding_and_status_failure() {
+        let mut client = client(both());
+        client.transport.control_read_results.push_back(Ok(STATUS_OK_FRAME.to_vec()));
+        // Second status poll reports errVERIFY (0x07) in dfuERROR (0x0a).
+        client.transport.control_read_results.push_back(Ok(vec![0x07, 0, 0, 0, 0x0a, 0]));
+
+        let options = DfuDownloadOptions::default().with_transfer_size(2);
+        let err = client.download(&[1, 2, 3], &options).unwrap_err();
+        assert!(matches!(
+            err,
+            DfuError::Status {
+                status: 0x07,
+                state: 0x0a
+            }
+        ));
+
+        // First request was DNLOAD block 0 with the first chunk.
+        let first = &client.transport.control_requests[0];
+        assert_eq!(first.request_type, REQUEST_OUT);
+        assert_eq!(first.request, DFU_DNLOAD);
+        assert_eq!(first.value, 0);
+        assert_eq!(first.data, vec![1, 2]);
+    }
+}
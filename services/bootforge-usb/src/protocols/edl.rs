@@ -0,0 +1,57 @@
+// BootForge USB - Qualcomm Emergency Download (EDL) mode
+// Detection of the 9008 loader so it is not mistaken for fastboot: an
+// EDL device speaks Sahara/Firehose, and sending it fastboot commands
+// gets a stall at best. Identification only; no Sahara client here.
+
+use crate::protocols::classify::field_contains;
+
+pub const QUALCOMM_VID: u16 = 0x05c6;
+
+/// 9008 is the Sahara/Firehose loader the SoC ROM exposes; 900e is the
+/// diagnostics PID some firmwares fall back to with the same stack.
+const EDL_PIDS: [u16; 2] = [0x9008, 0x900e];
+
+/// Table hit: the device has re-enumerated as the Qualcomm loader.
+pub fn is_edl_mode(vendor_id: u16, product_id: u16) -> bool {
+    vendor_id == QUALCOMM_VID && EDL_PIDS.contains(&product_id)
+}
+
+/// The loader enumerates a single all-0xff vendor interface; only a
+/// suggestion, since plenty of vendor gadgets use the same triple.
+pub fn interface_suggests_edl(class: u8, subclass: u8, protocol: u8) -> bool {
+    (class, subclass, protocol) == (0xff, 0xff, 0xff)
+}
+
+/// Product strings the loader ships: "QUSB_BULK_CID:xxxx_SN:xxxx" from
+/// the SoC ROM, "QDLoader" from the driver-assigned name, "Sahara" on
+/// some engineering builds.
+pub fn strings_suggest_edl(product: &Option<String>) -> bool {
+    field_contains(product, "qusb_bulk")
+        || field_contains(product, "qdloader")
+        || field_contains(product, "sahara")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edl_pid_table() {
+        assert!(is_edl_mode(0x05c6, 0x9008));
+        assert!(is_edl_mode(0x05c6, 0x900e));
+        assert!(!is_edl_mode(0x05c6, 0x9091));
+        assert!(!is_edl_mode(0x18d1, 0x9008));
+    }
+
+    #[test]
+    fn test_edl_heuristics() {
+        assert!(interface_suggests_edl(0xff, 0xff, 0xff));
+        assert!(!interface_suggests_edl(0xff, 0x42, 0x01));
+        assert!(strings_suggest_edl(&Some(
+            "QUSB_BULK_CID:0402_SN:B7C45925".to_string()
+        )));
+        assert!(strings_suggest_edl(&Some("Qualcomm QDLoader 9008".to_string())));
+        assert!(!strings_suggest_edl(&Some("Pixel 7".to_string())));
+        assert!(!strings_suggest_edl(&None));
+    }
+}
@@ -0,0 +1,763 @@
+// BootForge USB - Fastboot protocol client
+// Text-command protocol over bulk endpoints; used for tethered boot and
+// flash operations on devices the caller is authorized to service.
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::error::UsbError;
+use crate::protocols::verify::{crc32, Verification, VerifyMode};
+use crate::transfer::{BulkTransfer, UsbTransport};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Data-phase writes are chunked to the fastboot-typical 1 MiB.
+const DOWNLOAD_CHUNK: usize = 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum FastbootError {
+    #[error("`{command}` requires {variable}={expected} (device reported {actual:?})")]
+    MissingCapability {
+        command: &'static str,
+        variable: &'static str,
+        expected: &'static str,
+        actual: Option<String>,
+    },
+
+    #[error("device replied FAIL: {0}")]
+    Failed(String),
+
+    #[error("unexpected fastboot response: {0:?}")]
+    UnexpectedResponse(String),
+
+    #[error("verification of {partition} failed: {detail}")]
+    VerificationFailed { partition: String, detail: String },
+
+    #[error(transparent)]
+    Usb(#[from] UsbError),
+}
+
+/// Precondition a command checks through getvar before running.
+#[derive(Debug, Clone, Copy)]
+struct Requirement {
+    variable: &'static str,
+    expected: &'static str,
+}
+
+/// Command strings and their preconditions, table-driven so tests can
+/// cover them without re-scripting the transport per command.
+#[derive(Debug, Clone, Copy)]
+struct CommandSpec {
+    command: &'static str,
+    requires: Option<Requirement>,
+}
+
+const CMD_BOOT: CommandSpec = CommandSpec {
+    command: "boot",
+    requires: None,
+};
+const CMD_FLASH_RAW: CommandSpec = CommandSpec {
+    command: "flash",
+    requires: None,
+};
+const CMD_SET_ACTIVE: CommandSpec = CommandSpec {
+    command: "set_active",
+    requires: Some(Requirement {
+        variable: "has-slot:a",
+        expected: "yes",
+    }),
+};
+const CMD_SNAPSHOT_UPDATE: CommandSpec = CommandSpec {
+    command: "snapshot-update",
+    requires: Some(Requirement {
+        variable: "is-userspace",
+        expected: "yes",
+    }),
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotUpdateAction {
+    Cancel,
+    Merge,
+}
+
+impl SnapshotUpdateAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            SnapshotUpdateAction::Cancel => "cancel",
+            SnapshotUpdateAction::Merge => "merge",
+        }
+    }
+}
+
+/**
+ * Options for verified flash operations.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlashOptions {
+    pub verify: VerifyMode,
+}
+
+impl FlashOptions {
+    pub fn with_verify(mut self, verify: VerifyMode) -> Self {
+        self.verify = verify;
+        self
+    }
+}
+
+/**
+ * What a verified flash did.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashReport {
+    pub bytes_flashed: usize,
+    pub verification: Verification,
+}
+
+/**
+ * Progress reported during `flash`: the data phase as it is written,
+ * and the device's INFO lines during the write to flash itself.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum FlashProgress<'a> {
+    Downloaded { bytes: usize, total: usize },
+    Info(&'a str),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Response {
+    Okay(String),
+    Info(String),
+    Data(u32),
+    Fail(String),
+}
+
+/**
+ * Blocking fastboot client over a pair of bulk endpoints.
+ */
+pub struct FastbootClient<T: UsbTransport> {
+    bulk: BulkTransfer<T>,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    /// Cached `max-download-size`; queried once, `Some(None)` when the
+    /// device does not report one.
+    max_download: Option<Option<usize>>,
+}
+
+impl<T: UsbTransport> FastbootClient<T> {
+    pub fn new(transport: T, endpoint_in: u8, endpoint_out: u8) -> Self {
+        FastbootClient {
+            bulk: BulkTransfer::new(transport),
+            endpoint_in,
+            endpoint_out,
+            max_download: None,
+        }
+    }
+
+    /**
+     * Discover the interface's bulk pair from the enumerated
+     * configuration instead of taking addresses by hand.
+     */
+    pub fn for_interface(
+        transport: T,
+        config: &crate::enumeration::ConfigInfo,
+        interface: u8,
+    ) -> Result<Self, UsbError> {
+        let (endpoint_in, endpoint_out) = config
+            .interfaces
+            .iter()
+            .filter(|i| i.number == interface)
+            .find_map(crate::endpoints::find_bulk_pair)
+            .ok_or_else(|| {
+                UsbError::NotFound(format!("no bulk endpoint pair on interface {}", interface))
+            })?;
+        Ok(FastbootClient::new(transport, endpoint_in, endpoint_out))
+    }
+
+    pub fn getvar(&mut self, variable: &str) -> Result<String, FastbootError> {
+        self.command(&format!("getvar:{}", variable))
+    }
+
+    /**
+     * `getvar:all`: the device streams one INFO line per variable,
+     * "name:value", terminated by an OKAY.
+     */
+    pub fn getvar_all(&mut self) -> Result<Vec<(String, String)>, FastbootError> {
+        self.send("getvar:all")?;
+        let mut variables = Vec::new();
+        loop {
+            match self.read_response()? {
+                Response::Info(line) => {
+                    if let Some((name, value)) = line.split_once(':') {
+                        variables.push((name.to_string(), value.to_string()));
+                    }
+                }
+                Response::Okay(_) => return Ok(variables),
+                Response::Fail(msg) => return Err(FastbootError::Failed(msg)),
+                other => return Err(FastbootError::UnexpectedResponse(format!("{:?}", other))),
+            }
+        }
+    }
+
+    /// The device's `max-download-size`, queried once and cached.
+    /// `None` when the device does not report one.
+    pub fn max_download_size(&mut self) -> Option<usize> {
+        if self.max_download.is_none() {
+            let size = self
+                .getvar("max-download-size")
+                .ok()
+                .and_then(|v| parse_size(&v));
+            self.max_download = Some(size);
+        }
+        self.max_download.unwrap_or_default()
+    }
+
+    /**
+     * Tethered boot: download the image and ask the bootloader to run it
+     * without flashing anything.
+     */
+    pub fn boot(&mut self, image: &[u8]) -> Result<(), FastbootError> {
+        self.download(image)?;
+        self.command(CMD_BOOT.command)?;
+        Ok(())
+    }
+
+    /**
+     * Raw (non-sparse) flash of a partition. Bypasses sparse handling for
+     * callers that know their image is already raw.
+     */
+    pub fn flash_raw(&mut self, partition: &str, data: &[u8]) -> Result<(), FastbootError> {
+        self.download(data)?;
+        self.command(&format!("{}:{}", CMD_FLASH_RAW.command, partition))?;
+        Ok(())
+    }
+
+    /**
+     * Flash a raw image and verify it per the options. CRC mode asks
+     * the bootloader through the `has-crc` getvar / `oem crc` vendor
+     * hooks; when the device has neither, it falls back to reading the
+     * partition back over `fetch:` (generally fastbootd-only). A
+     * mismatch is an error; the report records how - or whether - the
+     * flash was confirmed.
+     */
+    pub fn flash_raw_verified(
+        &mut self,
+        partition: &str,
+        data: &[u8],
+        options: &FlashOptions,
+    ) -> Result<FlashReport, FastbootError> {
+        self.flash_raw(partition, data)?;
+        let verification = match options.verify {
+            VerifyMode::None => Verification::Skipped,
+            VerifyMode::Crc => self.verify_crc(partition, data)?,
+            VerifyMode::Readback => self.verify_fetch(partition, data)?,
+        };
+        Ok(FlashReport {
+            bytes_flashed: data.len(),
+            verification,
+        })
+    }
+
+    /// CRC verification through the vendor hook, falling back to a
+    /// fetch readback when the device reports no CRC support.
+    fn verify_crc(&mut self, partition: &str, data: &[u8]) -> Result<Verification, FastbootError> {
+        if self.getvar("has-crc").ok().as_deref() != Some("yes") {
+            return self.verify_fetch(partition, data);
+        }
+        let reply = self.command(&format!("oem crc:{}", partition))?;
+        let reply = reply.trim().trim_start_matches("0x");
+        let actual = u32::from_str_radix(reply, 16).map_err(|_| {
+            FastbootError::UnexpectedResponse(format!("oem crc returned {:?}", reply))
+        })?;
+        let expected = crc32(data);
+        if actual != expected {
+            return Err(FastbootError::VerificationFailed {
+                partition: partition.to_string(),
+                detail: format!("wrote crc {:08x}, device reports {:08x}", expected, actual),
+            });
+        }
+        Ok(Verification::Passed(VerifyMode::Crc))
+    }
+
+    /// Read the partition back via `fetch:` and compare against the
+    /// source image.
+    fn verify_fetch(&mut self, partition: &str, data: &[u8]) -> Result<Verification, FastbootError> {
+        self.send(&format!("fetch:{}", partition))?;
+        let size = match self.read_response()? {
+            Response::Data(size) => size as usize,
+            Response::Fail(msg) => {
+                return Err(FastbootError::VerificationFailed {
+                    partition: partition.to_string(),
+                    detail: format!("device cannot fetch: {}", msg),
+                })
+            }
+            other => return Err(FastbootError::UnexpectedResponse(format!("{:?}", other))),
+        };
+        if size < data.len() {
+            return Err(FastbootError::VerificationFailed {
+                partition: partition.to_string(),
+                detail: format!("fetch offers {} of {} bytes", size, data.len()),
+            });
+        }
+
+        // Partitions are usually larger than the image; compare only the
+        // written prefix, but drain everything the device sends.
+        let mut offset = 0;
+        let mut buf = vec![0u8; DOWNLOAD_CHUNK.min(size.max(1))];
+        while offset < size {
+            let n = self.bulk.read(self.endpoint_in, &mut buf, IO_TIMEOUT)?;
+            if n == 0 {
+                break;
+            }
+            let chunk = &buf[..n];
+            if offset < data.len() {
+                let want = (data.len() - offset).min(n);
+                if chunk[..want] != data[offset..offset + want] {
+                    return Err(FastbootError::VerificationFailed {
+                        partition: partition.to_string(),
+                        detail: format!("readback differs within bytes {}..{}", offset, offset + want),
+                    });
+                }
+            }
+            offset += n;
+        }
+        match self.read_response()? {
+            Response::Okay(_) => Ok(Verification::Passed(VerifyMode::Readback)),
+            Response::Fail(msg) => Err(FastbootError::Failed(msg)),
+            other => Err(FastbootError::UnexpectedResponse(format!("{:?}", other))),
+        }
+    }
+
+    /**
+     * Flash a partition with progress reporting: the data phase is
+     * reported chunk by chunk, and the bootloader's INFO lines during
+     * the write land in the callback rather than the log. Images larger
+     * than the device's `max-download-size` are refused up front - this
+     * client does not sparse-split.
+     */
+    pub fn flash(
+        &mut self,
+        partition: &str,
+        data: &[u8],
+        mut progress: impl FnMut(FlashProgress<'_>),
+    ) -> Result<(), FastbootError> {
+        if let Some(max) = self.max_download_size() {
+            if data.len() > max {
+                return Err(FastbootError::Failed(format!(
+                    "image is {} bytes but max-download-size is {}",
+                    data.len(),
+                    max
+                )));
+            }
+        }
+
+        self.send(&format!("download:{:08x}", data.len()))?;
+        match self.read_response()? {
+            Response::Data(accepted) if accepted as usize >= data.len() => {}
+            Response::Data(accepted) => {
+                return Err(FastbootError::Failed(format!(
+                    "device accepts only {} of {} bytes",
+                    accepted,
+                    data.len()
+                )))
+            }
+            Response::Fail(msg) => return Err(FastbootError::Failed(msg)),
+            other => return Err(FastbootError::UnexpectedResponse(format!("{:?}", other))),
+        }
+
+        let mut written = 0;
+        for chunk in data.chunks(DOWNLOAD_CHUNK) {
+            self.bulk.write(self.endpoint_out, chunk, IO_TIMEOUT)?;
+            written += chunk.len();
+            progress(FlashProgress::Downloaded {
+                bytes: written,
+                total: data.len(),
+            });
+        }
+        self.wait_okay_with_info(&mut progress)?;
+
+        self.send(&format!("{}:{}", CMD_FLASH_RAW.command, partition))?;
+        self.wait_okay_with_info(&mut progress)
+    }
+
+    /// Erase a partition.
+    pub fn erase(&mut self, partition: &str) -> Result<(), FastbootError> {
+        self.command(&format!("erase:{}", partition))?;
+        Ok(())
+    }
+
+    /// Reboot into the OS. The device acknowledges before it goes away.
+    pub fn reboot(&mut self) -> Result<(), FastbootError> {
+        self.command("reboot")?;
+        Ok(())
+    }
+
+    /// Reboot back into the bootloader.
+    pub fn reboot_bootloader(&mut self) -> Result<(), FastbootError> {
+        self.command("reboot-bootloader")?;
+        Ok(())
+    }
+
+    /// Vendor escape hatch; returns the OKAY payload.
+    pub fn oem(&mut self, command: &str) -> Result<String, FastbootError> {
+        self.command(&format!("oem {}", command))
+    }
+
+    /// Wait for OKAY, routing INFO lines to the progress callback.
+    fn wait_okay_with_info(
+        &mut self,
+        progress: &mut impl FnMut(FlashProgress<'_>),
+    ) -> Result<(), FastbootError> {
+        loop {
+            match self.read_response()? {
+                Response::Okay(_) => return Ok(()),
+                Response::Info(line) => progress(FlashProgress::Info(&line)),
+                Response::Fail(msg) => return Err(FastbootError::Failed(msg)),
+                other => return Err(FastbootError::UnexpectedResponse(format!("{:?}", other))),
+            }
+        }
+    }
+
+    /// Select the active A/B slot ("a" or "b").
+    pub fn set_active(&mut self, slot: &str) -> Result<(), FastbootError> {
+        self.check_requirement(&CMD_SET_ACTIVE)?;
+        self.command(&format!("{}:{}", CMD_SET_ACTIVE.command, slot))?;
+        Ok(())
+    }
+
+    /// Cancel or merge a Virtual A/B snapshot; fastbootd only.
+    pub fn snapshot_update(&mut self, action: SnapshotUpdateAction) -> Result<(), FastbootError> {
+        self.check_requirement(&CMD_SNAPSHOT_UPDATE)?;
+        self.command(&format!(
+            "{}:{}",
+            CMD_SNAPSHOT_UPDATE.command,
+            action.as_str()
+        ))?;
+        Ok(())
+    }
+
+    fn check_requirement(&mut self, spec: &CommandSpec) -> Result<(), FastbootError> {
+        let Some(req) = spec.requires else {
+            return Ok(());
+        };
+        let actual = self.getvar(req.variable).ok();
+        if actual.as_deref() != Some(req.expected) {
+            return Err(FastbootError::MissingCapability {
+                command: spec.command,
+                variable: req.variable,
+                expected: req.expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Data phase: `download:<size>` handshake followed by the payload.
+    fn download(&mut self, data: &[u8]) -> Result<(), FastbootError> {
+        self.send(&format!("download:{:08x}", data.len()))?;
+        match self.read_response()? {
+            Response::Data(accepted) if accepted as usize >= data.len() => {}
+            Response::Data(accepted) => {
+                return Err(FastbootError::Failed(format!(
+                    "device accepts only {} of {} bytes",
+                    accepted,
+                    data.len()
+                )))
+            }
+            Response::Fail(msg) => return Err(FastbootError::Failed(msg)),
+            other => return Err(FastbootError::UnexpectedResponse(format!("{:?}", other))),
+        }
+
+        for chunk in data.chunks(DOWNLOAD_CHUNK) {
+            self.bulk.write(self.endpoint_out, chunk, IO_TIMEOUT)?;
+        }
+
+        match self.read_response()? {
+            Response::Okay(_) => Ok(()),
+            Response::Fail(msg) => Err(FastbootError::Failed(msg)),
+            other => Err(FastbootError::UnexpectedResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Send a command and wait for OKAY, collecting INFO lines.
+    fn command(&mut self, command: &str) -> Result<String, FastbootError> {
+        self.send(command)?;
+        loop {
+            match self.read_response()? {
+                Response::Okay(payload) => return Ok(payload),
+                Response::Info(line) => log::debug!("fastboot INFO: {}", line),
+                Response::Fail(msg) => return Err(FastbootError::Failed(msg)),
+                Response::Data(n) => {
+                    return Err(FastbootError::UnexpectedResponse(format!("DATA{:08x}", n)))
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), FastbootError> {
+        self.bulk
+            .write(self.endpoint_out, command.as_bytes(), IO_TIMEOUT)?;
+        Ok(())
+    }
+
+    fn read_response(&mut self) -> Result<Response, FastbootError> {
+        let mut buf = [0u8; 256];
+        let n = self.bulk.read(self.endpoint_in, &mut buf, IO_TIMEOUT)?;
+        parse_response(&buf[..n])
+    }
+}
+
+/// Parse a getvar size value; devices report hex ("0x20000000") or
+/// decimal.
+fn parse_size(value: &str) -> Option<usize> {
+    let value = value.trim();
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response, FastbootError> {
+    let text = String::from_utf8_lossy(raw);
+    if text.len() < 4 {
+        return Err(FastbootError::UnexpectedResponse(text.into_owned()));
+    }
+    let (prefix, payload) = text.split_at(4);
+    match prefix {
+        "OKAY" => Ok(Response::Okay(payload.to_string())),
+        "INFO" => Ok(Response::Info(payload.to_string())),
+        "FAIL" => Ok(Response::Fail(payload.to_string())),
+        "DATA" => u32::from_str_radix(payload.trim(), 16)
+            .map(Response::Data)
+            .map_err(|_| FastbootError::UnexpectedResponse(text.into_owned())),
+        _ => Err(FastbootError::UnexpectedResponse(text.into_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    fn client_with(reads: &[&[u8]], writes: usize) -> FastbootClient<MockTransport> {
+        let mut transport = MockTransport::new();
+        for r in reads {
+            transport.read_results.push_back(Ok(r.to_vec()));
+        }
+        for _ in 0..writes {
+            transport.write_results.push_back(Ok(4096));
+        }
+        FastbootClient::new(transport, 0x81, 0x01)
+    }
+
+    #[test]
+    fn test_parse_response_table() {
+        // Response prefix -> expected parse, kept table-driven like the
+        // command specs.
+        let cases: &[(&[u8], Response)] = &[
+            (b"OKAY0.4", Response::Okay("0.4".to_string())),
+            (b"INFOhello", Response::Info("hello".to_string())),
+            (b"FAILunknown command", Response::Fail("unknown command".to_string())),
+            (b"DATA00001000", Response::Data(0x1000)),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(&parse_response(raw).unwrap(), expected);
+        }
+        assert!(parse_response(b"BOGUS").is_err());
+        assert!(parse_response(b"OK").is_err());
+    }
+
+    #[test]
+    fn test_getvar() {
+        let mut client = client_with(&[b"OKAYfastboot"], 1);
+        assert_eq!(client.getvar("version").unwrap(), "fastboot");
+    }
+
+    #[test]
+    fn test_boot_downloads_then_boots() {
+        let mut client = client_with(&[b"DATA00000004", b"OKAY", b"OKAY"], 3);
+        client.boot(&[1, 2, 3, 4]).unwrap();
+    }
+
+    #[test]
+    fn test_flash_raw_uses_flash_command() {
+        let mut client = client_with(&[b"DATA00000002", b"OKAY", b"OKAY"], 3);
+        client.flash_raw("boot_a", &[0xaa, 0xbb]).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_update_requires_userspace() {
+        // getvar is-userspace -> "no": command must not be sent.
+        let mut client = client_with(&[b"OKAYno"], 1);
+        let err = client
+            .snapshot_update(SnapshotUpdateAction::Merge)
+            .unwrap_err();
+        match err {
+            FastbootError::MissingCapability {
+                command,
+                variable,
+                expected,
+                actual,
+            } => {
+                assert_eq!(command, "snapshot-update");
+                assert_eq!(variable, "is-userspace");
+                assert_eq!(expected, "yes");
+                assert_eq!(actual.as_deref(), Some("no"));
+            }
+            other => panic!("expected MissingCapability, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_set_active_requires_slots() {
+        let mut client = client_with(&[b"OKAYyes", b"OKAY"], 2);
+        client.set_active("b").unwrap();
+
+        let mut client = client_with(&[b"FAILunknown variable"], 1);
+        assert!(matches!(
+            client.set_active("b").unwrap_err(),
+            FastbootError::MissingCapability { actual: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_flash_crc_verification_passes_and_fails() {
+        // flash (DATA + 2x OKAY), has-crc yes, oem crc -> matching value.
+        let data = b"123456789";
+        let crc_reply = format!("OKAY{:08x}", crate::protocols::verify::crc32(data));
+        let mut client = client_with(
+            &[b"DATA00000009", b"OKAY", b"OKAY", b"OKAYyes", crc_reply.as_bytes()],
+            5,
+        );
+        let options = FlashOptions::default().with_verify(VerifyMode::Crc);
+        let report = client.flash_raw_verified("boot_a", data, &options).unwrap();
+        assert_eq!(report.bytes_flashed, 9);
+        assert_eq!(report.verification, Verification::Passed(VerifyMode::Crc));
+
+        // Same flow, but the device reports a different CRC.
+        let mut client = client_with(
+            &[b"DATA00000009", b"OKAY", b"OKAY", b"OKAYyes", b"OKAYdeadbeef"],
+            5,
+        );
+        assert!(matches!(
+            client.flash_raw_verified("boot_a", data, &options).unwrap_err(),
+            FastbootError::VerificationFailed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_flash_readback_fallback_detects_mismatch() {
+        // No CRC support -> fetch readback; the device returns corrupted
+        // contents (the fetch data phase shares the scripted read queue).
+        let mut client = client_with(
+            &[
+                b"DATA00000002",
+                b"OKAY",
+                b"OKAY",
+                b"OKAYno",
+                b"DATA00000002",
+                &[0xaa, 0xcc],
+            ],
+            5,
+        );
+        let options = FlashOptions::default().with_verify(VerifyMode::Crc);
+        let err = client
+            .flash_raw_verified("boot_a", &[0xaa, 0xbb], &options)
+            .unwrap_err();
+        assert!(matches!(err, FastbootError::VerificationFailed { .. }));
+    }
+
+    #[test]
+    fn test_getvar_all_collects_info_lines() {
+        let mut client = client_with(
+            &[b"INFOversion:0.4", b"INFOproduct:walleye", b"OKAY"],
+            1,
+        );
+        assert_eq!(
+            client.getvar_all().unwrap(),
+            vec![
+                ("version".to_string(), "0.4".to_string()),
+                ("product".to_string(), "walleye".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flash_reports_progress_and_info() {
+        // getvar max-download-size, download handshake, data-phase OKAY,
+        // INFO during the write, final OKAY.
+        let mut client = client_with(
+            &[
+                b"OKAY0x100000",
+                b"DATA00000004",
+                b"OKAY",
+                b"INFOwriting 'boot_a'",
+                b"OKAY",
+            ],
+            4,
+        );
+
+        let mut seen = Vec::new();
+        client
+            .flash("boot_a", &[1, 2, 3, 4], |p| {
+                seen.push(format!("{:?}", p));
+            })
+            .unwrap();
+        assert_eq!(
+            seen,
+            vec![
+                "Downloaded { bytes: 4, total: 4 }".to_string(),
+                "Info(\"writing 'boot_a'\")".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flash_surfaces_mid_stream_failure() {
+        // The data phase succeeds; the write to flash itself FAILs.
+        let mut client = client_with(
+            &[
+                b"OKAY0x100000",
+                b"DATA00000002",
+                b"OKAY",
+                b"FAILflash write failure",
+            ],
+            4,
+        );
+        let err = client.flash("boot_a", &[1, 2], |_| {}).unwrap_err();
+        assert!(matches!(err, FastbootError::Failed(msg) if msg == "flash write failure"));
+    }
+
+    #[test]
+    fn test_flash_refuses_image_over_max_download() {
+        let mut client = client_with(&[b"OKAY0x2"], 1);
+        let err = client.flash("boot_a", &[1, 2, 3, 4], |_| {}).unwrap_err();
+        assert!(matches!(err, FastbootError::Failed(msg) if msg.contains("max-download-size")));
+        // Only the getvar command went out; the download never started.
+        assert_eq!(client.bulk.stats().attempts, 2); // one write, one read
+
+    }
+
+    #[test]
+    fn test_erase_reboot_and_oem() {
+        let mut client = client_with(&[b"OKAY", b"OKAY", b"OKAY", b"OKAYok"], 4);
+        client.erase("userdata").unwrap();
+        client.reboot_bootloader().unwrap();
+        client.reboot().unwrap();
+        assert_eq!(client.oem("device-info").unwrap(), "ok");
+    }
+
+    #[test]
+    fn test_parse_size_forms() {
+        assert_eq!(parse_size("0x100000"), Some(0x100000));
+        assert_eq!(parse_size("268435456"), Some(268435456));
+        assert_eq!(parse_size("lots"), None);
+    }
+
+    #[test]
+    fn test_download_rejected_when_too_large() {
+        let mut client = client_with(&[b"DATA00000002"], 1);
+        let err = client.boot(&[1, 2, 3, 4]).unwrap_err();
+        assert!(matches!(err, FastbootError::Failed(_)));
+    }
+}
@@ -0,0 +1,405 @@
+// BootForge USB - HID class client
+// GET/SET_REPORT over the control pipe for the occasional poke, plus
+// the interrupt IN pipeline real input devices (scanners, keypads)
+// actually stream on: one-shot reads through HidDevice, report-ID
+// routed dispatch through HidInputStream.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::endpoints::find_interrupt_in;
+use crate::enumeration::InterfaceInfo;
+use crate::error::{classify_transfer_error, UsbError};
+use crate::transfer::{InterruptPoller, UsbTransport};
+
+/// Device class of HID interfaces.
+pub const HID_CLASS: u8 = 0x03;
+
+// Class requests (HID 1.11, 7.2)
+const REQ_GET_REPORT: u8 = 0x01;
+const REQ_SET_REPORT: u8 = 0x09;
+
+// Class-specific interface requests, device-to-host and host-to-device.
+const REQT_CLASS_INTERFACE_IN: u8 = 0xa1;
+const REQT_CLASS_INTERFACE_OUT: u8 = 0x21;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/**
+ * Report type, the high byte of wValue in GET/SET_REPORT.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportType {
+    Input,
+    Output,
+    Feature,
+}
+
+impl ReportType {
+    fn as_byte(self) -> u8 {
+        match self {
+            ReportType::Input => 1,
+            ReportType::Output => 2,
+            ReportType::Feature => 3,
+        }
+    }
+}
+
+/**
+ * Blocking HID client over a claimed interface. Control-pipe report
+ * access always works; `read_input_report` needs the interrupt IN
+ * endpoint, located from the interface by `for_interface` or supplied
+ * with `with_endpoint`.
+ */
+pub struct HidDevice<T: UsbTransport> {
+    transport: T,
+    interface: u8,
+    endpoint_in: Option<u8>,
+}
+
+impl<T: UsbTransport> HidDevice<T> {
+    pub fn new(transport: T, interface: u8) -> Self {
+        HidDevice {
+            transport,
+            interface,
+            endpoint_in: None,
+        }
+    }
+
+    /// As `new`, locating the interrupt IN endpoint from the
+    /// enumerated interface when it has one.
+    pub fn for_interface(transport: T, interface: &InterfaceInfo) -> Self {
+        HidDevice {
+            transport,
+            interface: interface.number,
+            endpoint_in: find_interrupt_in(interface),
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint_in: u8) -> Self {
+        self.endpoint_in = Some(endpoint_in);
+        self
+    }
+
+    /**
+     * GET_REPORT over the control pipe. Returns the bytes read; pass
+     * report id 0 for un-numbered reports.
+     */
+    pub fn get_report(
+        &mut self,
+        report_type: ReportType,
+        report_id: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, UsbError> {
+        let value = (u16::from(report_type.as_byte()) << 8) | u16::from(report_id);
+        self.transport
+            .read_control(
+                REQT_CLASS_INTERFACE_IN,
+                REQ_GET_REPORT,
+                value,
+                u16::from(self.interface),
+                buf,
+                CONTROL_TIMEOUT,
+            )
+            .map_err(classify_transfer_error)
+    }
+
+    /**
+     * SET_REPORT over the control pipe. `data` carries the report
+     * without its id byte; the id travels in wValue.
+     */
+    pub fn set_report(
+        &mut self,
+        report_type: ReportType,
+        report_id: u8,
+        data: &[u8],
+    ) -> Result<(), UsbError> {
+        let value = (u16::from(report_type.as_byte()) << 8) | u16::from(report_id);
+        self.transport
+            .write_control(
+                REQT_CLASS_INTERFACE_OUT,
+                REQ_SET_REPORT,
+                value,
+                u16::from(self.interface),
+                data,
+                CONTROL_TIMEOUT,
+            )
+            .map_err(classify_transfer_error)?;
+        Ok(())
+    }
+
+    /**
+     * One input report from the interrupt IN endpoint. Returns the
+     * actual report length: interrupt transfers complete on the short
+     * packet, so reports smaller than the endpoint's max packet size
+     * come back immediately rather than blocking for a full buffer.
+     */
+    pub fn read_input_report(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, UsbError> {
+        let endpoint = self.endpoint_in.ok_or_else(|| UsbError::Protocol {
+            protocol: "hid",
+            message: "interface has no interrupt IN endpoint".to_string(),
+        })?;
+        self.transport
+            .read_interrupt(endpoint, buf, timeout)
+            .map_err(classify_transfer_error)
+    }
+
+    /**
+     * Consume this device into a report-routing stream over its
+     * interrupt endpoint.
+     */
+    pub fn into_input_stream(self, read_timeout: Duration) -> Result<HidInputStream<T>, UsbError> {
+        let endpoint = self.endpoint_in.ok_or_else(|| UsbError::Protocol {
+            protocol: "hid",
+            message: "interface has no interrupt IN endpoint".to_string(),
+        })?;
+        Ok(HidInputStream::new(self.transport, endpoint, read_timeout))
+    }
+}
+
+/// Callback invoked with one report's payload (id byte stripped).
+pub type ReportHandler = Box<dyn FnMut(&[u8])>;
+
+/// Catch-all callback: report id plus payload.
+pub type UnroutedHandler = Box<dyn FnMut(u8, &[u8])>;
+
+/**
+ * Interrupt-driven input stream with per-report-ID dispatch.
+ *
+ * When the device's report descriptor declares report ids, the first
+ * byte of every interrupt packet is the id and the rest the payload -
+ * enable that framing with `with_numbered_reports`. Without it the
+ * whole packet is one un-numbered report, routed as id 0. (The crate
+ * has no report-descriptor parser; whether ids are in use comes from
+ * the caller, who has read the descriptor.)
+ *
+ * Packets for ids without a handler go to the `on_unrouted` callback
+ * when one is set, and are logged and dropped otherwise.
+ */
+pub struct HidInputStream<T: UsbTransport> {
+    poller: InterruptPoller<T>,
+    numbered_reports: bool,
+    handlers: BTreeMap<u8, ReportHandler>,
+    unrouted: Option<UnroutedHandler>,
+}
+
+impl<T: UsbTransport> HidInputStream<T> {
+    pub fn new(transport: T, endpoint_in: u8, read_timeout: Duration) -> Self {
+        HidInputStream {
+            poller: InterruptPoller::new(transport, endpoint_in, read_timeout),
+            numbered_reports: false,
+            handlers: BTreeMap::new(),
+            unrouted: None,
+        }
+    }
+
+    /// Treat the first byte of every packet as the report id.
+    pub fn with_numbered_reports(mut self, numbered: bool) -> Self {
+        self.numbered_reports = numbered;
+        self
+    }
+
+    /// Route reports with this id to `handler`. Id 0 is the whole
+    /// packet when numbered reports are off.
+    pub fn on_report(mut self, report_id: u8, handler: impl FnMut(&[u8]) + 'static) -> Self {
+        self.handlers.insert(report_id, Box::new(handler));
+        self
+    }
+
+    /// Catch-all for ids no `on_report` route claims.
+    pub fn on_unrouted(mut self, handler: impl FnMut(u8, &[u8]) + 'static) -> Self {
+        self.unrouted = Some(Box::new(handler));
+        self
+    }
+
+    /**
+     * One read attempt; dispatches at most one report. Ok(false) when
+     * the endpoint timed out (no report pending).
+     */
+    pub fn poll_once(&mut self, buf: &mut [u8]) -> Result<bool, UsbError> {
+        match self.poller.poll_once(buf)? {
+            Some(n) => {
+                route(
+                    self.numbered_reports,
+                    &mut self.handlers,
+                    &mut self.unrouted,
+                    &buf[..n],
+                );
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /**
+     * Poll and dispatch until `keep_going` returns false or the
+     * endpoint fails. Timeouts keep polling, so a quiet device does
+     * not end the stream.
+     */
+    pub fn run(
+        &mut self,
+        buf: &mut [u8],
+        mut keep_going: impl FnMut() -> bool,
+    ) -> Result<(), UsbError> {
+        while keep_going() {
+            self.poll_once(buf)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split one interrupt packet into (id, payload) and hand it to its
+/// route. Zero-length packets carry nothing and are dropped.
+fn route(
+    numbered: bool,
+    handlers: &mut BTreeMap<u8, ReportHandler>,
+    unrouted: &mut Option<UnroutedHandler>,
+    packet: &[u8],
+) {
+    let (report_id, payload) = if numbered {
+        match packet.split_first() {
+            Some((id, payload)) => (*id, payload),
+            None => return,
+        }
+    } else {
+        (0u8, packet)
+    };
+
+    if let Some(handler) = handlers.get_mut(&report_id) {
+        handler(payload);
+    } else if let Some(handler) = unrouted {
+        handler(report_id, payload);
+    } else {
+        log::debug!(
+            "dropping unrouted HID report id {} ({} bytes)",
+            report_id,
+            payload.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::transfer::mock::MockTransport;
+
+    const TIMEOUT: Duration = Duration::from_millis(5);
+
+    #[test]
+    fn test_get_report_request_shape() {
+        let mut transport = MockTransport::new();
+        transport
+            .control_read_results
+            .push_back(Ok(vec![0x01, 0xff]));
+
+        let mut device = HidDevice::new(transport, 2);
+        let mut buf = [0u8; 8];
+        let n = device
+            .get_report(ReportType::Feature, 0x05, &mut buf)
+            .unwrap();
+        assert_eq!((n, &buf[..n]), (2, &[0x01, 0xff][..]));
+
+        let request = &device.transport.control_requests[0];
+        assert_eq!(request.request_type, REQT_CLASS_INTERFACE_IN);
+        assert_eq!(request.request, REQ_GET_REPORT);
+        // wValue: report type Feature (3) in the high byte, id low.
+        assert_eq!(request.value, 0x0305);
+        assert_eq!(request.index, 2);
+    }
+
+    #[test]
+    fn test_read_input_report_returns_short_reports() {
+        let mut transport = MockTransport::new();
+        // A 3-byte report on an endpoint with a 64-byte max packet.
+        transport.read_results.push_back(Ok(vec![0x01, 0x02, 0x03]));
+
+        let mut device = HidDevice::new(transport, 0).with_endpoint(0x81);
+        let mut buf = [0u8; 64];
+        let n = device.read_input_report(&mut buf, TIMEOUT).unwrap();
+        assert_eq!(&buf[..n], &[0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn test_read_input_report_without_endpoint_is_protocol_error() {
+        let mut device = HidDevice::new(MockTransport::new(), 0);
+        let mut buf = [0u8; 8];
+        assert!(matches!(
+            device.read_input_report(&mut buf, TIMEOUT),
+            Err(UsbError::Protocol { protocol: "hid", .. })
+        ));
+    }
+
+    #[test]
+    fn test_stream_routes_numbered_reports_by_id() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(vec![0x01, 0xaa, 0xbb]));
+        transport.read_results.push_back(Ok(vec![0x02, 0xcc]));
+        transport.read_results.push_back(Ok(vec![0x01, 0xdd]));
+
+        let ones = Rc::new(RefCell::new(Vec::new()));
+        let twos = Rc::new(RefCell::new(Vec::new()));
+        let ones_sink = Rc::clone(&ones);
+        let twos_sink = Rc::clone(&twos);
+
+        let mut stream = HidInputStream::new(transport, 0x81, TIMEOUT)
+            .with_numbered_reports(true)
+            .on_report(0x01, move |payload| {
+                ones_sink.borrow_mut().push(payload.to_vec())
+            })
+            .on_report(0x02, move |payload| {
+                twos_sink.borrow_mut().push(payload.to_vec())
+            });
+
+        let mut buf = [0u8; 64];
+        for _ in 0..3 {
+            assert!(stream.poll_once(&mut buf).unwrap());
+        }
+        // Queue exhausted: the mock times out, which is "nothing yet".
+        assert!(!stream.poll_once(&mut buf).unwrap());
+
+        assert_eq!(*ones.borrow(), vec![vec![0xaa, 0xbb], vec![0xdd]]);
+        assert_eq!(*twos.borrow(), vec![vec![0xcc]]);
+    }
+
+    #[test]
+    fn test_stream_unnumbered_packets_route_as_id_zero() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(vec![0x09, 0x08]));
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&seen);
+        let mut stream = HidInputStream::new(transport, 0x81, TIMEOUT)
+            .on_report(0, move |payload| sink.borrow_mut().push(payload.to_vec()));
+
+        let mut buf = [0u8; 64];
+        assert!(stream.poll_once(&mut buf).unwrap());
+        // The first byte is data, not an id, without the flag.
+        assert_eq!(*seen.borrow(), vec![vec![0x09, 0x08]]);
+    }
+
+    #[test]
+    fn test_stream_unrouted_ids_hit_the_catch_all() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(vec![0x07, 0x01]));
+        transport.read_results.push_back(Ok(vec![]));
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&seen);
+        let mut stream = HidInputStream::new(transport, 0x81, TIMEOUT)
+            .with_numbered_reports(true)
+            .on_unrouted(move |id, payload| sink.borrow_mut().push((id, payload.to_vec())));
+
+        let mut buf = [0u8; 64];
+        assert!(stream.poll_once(&mut buf).unwrap());
+        // The zero-length packet is read but carries no report.
+        assert!(stream.poll_once(&mut buf).unwrap());
+        assert_eq!(*seen.borrow(), vec![(0x07, vec![0x01])]);
+    }
+}
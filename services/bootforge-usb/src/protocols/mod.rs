@@ -0,0 +1,30 @@
+// BootForge USB - Device protocol clients
+// Read-oriented protocol support for devices we enumerate.
+
+pub mod adb;
+pub mod aoa;
+pub mod apple;
+pub mod cdc;
+pub mod classify;
+pub mod dfu;
+pub mod edl;
+pub mod fastboot;
+pub mod hid;
+pub mod msc;
+pub mod mtp;
+pub mod odin;
+pub mod registry;
+pub mod session;
+pub mod verify;
+
+pub use apple::{detect_apple_mode, AppleDeviceMode};
+pub use hid::{HidDevice, HidInputStream, ReportHandler, ReportType, UnroutedHandler};
+pub use classify::{
+    classify_device_info_protocols, classify_device_info_set, classify_device_protocols,
+    classify_device_protocols_set, classify_device_record_protocols, Confidence, Protocol,
+    ProtocolClassification, ProtocolSet,
+};
+pub use msc::{InquiryData, MscClient, MscError};
+pub use registry::{Matcher, ProtocolRegistry};
+pub use session::{DeviceSession, Mode, SessionError, SessionPort, TransitionTimeouts};
+pub use verify::{Verification, VerifyMode};
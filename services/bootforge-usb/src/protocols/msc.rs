@@ -0,0 +1,388 @@
+// BootForge USB - Mass storage (BOT) client
+// Just enough Bulk-Only Transport to identify the disk behind a USB
+// bridge: SCSI INQUIRY and READ CAPACITY(10). Read-only by design - no
+// write or mode-select commands are implemented.
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::error::UsbError;
+use crate::transfer::{BulkTransfer, UsbTransport};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// "USBC", little-endian, leading every Command Block Wrapper.
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+/// "USBS", little-endian, leading every Command Status Wrapper.
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+/// Fixed wire sizes from the BOT specification.
+const CBW_LEN: usize = 31;
+const CSW_LEN: usize = 13;
+
+/// Standard INQUIRY response length the client requests.
+const INQUIRY_LEN: usize = 36;
+
+#[derive(Debug, Error)]
+pub enum MscError {
+    #[error("malformed CSW: {0}")]
+    MalformedCsw(String),
+
+    #[error("CSW tag mismatch: sent {sent:#010x}, got {got:#010x}")]
+    TagMismatch { sent: u32, got: u32 },
+
+    /// The device reported the command itself failed (CSW status 01).
+    #[error("command failed (CSW status 01)")]
+    CommandFailed,
+
+    /// CSW status 02: the device lost phase sync; the host is expected
+    /// to reset the bridge before reusing it.
+    #[error("phase error (CSW status 02)")]
+    PhaseError,
+
+    #[error("short {what} response: {actual} of {expected} bytes")]
+    ShortResponse {
+        what: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error(transparent)]
+    Usb(#[from] UsbError),
+}
+
+/// Command Block Wrapper: the 31-byte header framing every command.
+/// Every command this read-only client implements has an IN data
+/// phase, so the direction flag is baked into `to_bytes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cbw {
+    tag: u32,
+    data_transfer_length: u32,
+    lun: u8,
+    command: Vec<u8>,
+}
+
+impl Cbw {
+    fn to_bytes(&self) -> [u8; CBW_LEN] {
+        let mut bytes = [0u8; CBW_LEN];
+        bytes[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.tag.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.data_transfer_length.to_le_bytes());
+        bytes[12] = 0x80;
+        bytes[13] = self.lun;
+        bytes[14] = self.command.len() as u8;
+        bytes[15..15 + self.command.len()].copy_from_slice(&self.command);
+        bytes
+    }
+}
+
+/// Command Status Wrapper: the 13-byte status the device returns after
+/// the data phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Csw {
+    tag: u32,
+    data_residue: u32,
+    status: u8,
+}
+
+impl Csw {
+    fn parse(bytes: &[u8]) -> Result<Csw, MscError> {
+        if bytes.len() < CSW_LEN {
+            return Err(MscError::MalformedCsw(format!(
+                "{} of {} bytes",
+                bytes.len(),
+                CSW_LEN
+            )));
+        }
+        let signature = u32::from_le_bytes(bytes[0..4].try_into().expect("sized slice"));
+        if signature != CSW_SIGNATURE {
+            return Err(MscError::MalformedCsw(format!(
+                "bad signature {:#010x}",
+                signature
+            )));
+        }
+        Ok(Csw {
+            tag: u32::from_le_bytes(bytes[4..8].try_into().expect("sized slice")),
+            data_residue: u32::from_le_bytes(bytes[8..12].try_into().expect("sized slice")),
+            status: bytes[12],
+        })
+    }
+}
+
+/**
+ * The identification fields of a standard INQUIRY response, trimmed of
+ * the space padding the SCSI spec mandates.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InquiryData {
+    pub vendor: String,
+    pub product: String,
+    pub revision: String,
+}
+
+impl InquiryData {
+    fn parse(bytes: &[u8]) -> Result<InquiryData, MscError> {
+        if bytes.len() < INQUIRY_LEN {
+            return Err(MscError::ShortResponse {
+                what: "INQUIRY",
+                expected: INQUIRY_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let field = |range: std::ops::Range<usize>| {
+            String::from_utf8_lossy(&bytes[range]).trim().to_string()
+        };
+        Ok(InquiryData {
+            vendor: field(8..16),
+            product: field(16..32),
+            revision: field(32..36),
+        })
+    }
+}
+
+/**
+ * Blocking Bulk-Only Transport client over a pair of bulk endpoints,
+ * mirroring `FastbootClient`'s construction. Tags increment per command
+ * and every CSW is checked against the tag its CBW carried.
+ */
+pub struct MscClient<T: UsbTransport> {
+    bulk: BulkTransfer<T>,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    lun: u8,
+    next_tag: u32,
+}
+
+impl<T: UsbTransport> MscClient<T> {
+    pub fn new(transport: T, endpoint_in: u8, endpoint_out: u8) -> Self {
+        MscClient {
+            bulk: BulkTransfer::new(transport),
+            endpoint_in,
+            endpoint_out,
+            lun: 0,
+            next_tag: 1,
+        }
+    }
+
+    /**
+     * Discover the interface's bulk pair from the enumerated
+     * configuration instead of taking addresses by hand.
+     */
+    pub fn for_interface(
+        transport: T,
+        config: &crate::enumeration::ConfigInfo,
+        interface: u8,
+    ) -> Result<Self, UsbError> {
+        let (endpoint_in, endpoint_out) = config
+            .interfaces
+            .iter()
+            .filter(|i| i.number == interface)
+            .find_map(crate::endpoints::find_bulk_pair)
+            .ok_or_else(|| {
+                UsbError::NotFound(format!("no bulk endpoint pair on interface {}", interface))
+            })?;
+        Ok(MscClient::new(transport, endpoint_in, endpoint_out))
+    }
+
+    /// Address a logical unit other than 0 (multi-slot card readers).
+    pub fn with_lun(mut self, lun: u8) -> Self {
+        self.lun = lun;
+        self
+    }
+
+    /**
+     * Standard SCSI INQUIRY: the vendor, product, and revision strings
+     * of the disk behind the bridge.
+     */
+    pub fn inquiry(&mut self) -> Result<InquiryData, MscError> {
+        let command = vec![0x12, 0x00, 0x00, 0x00, INQUIRY_LEN as u8, 0x00];
+        let data = self.command_in(command, INQUIRY_LEN)?;
+        InquiryData::parse(&data)
+    }
+
+    /**
+     * READ CAPACITY(10): the block count and block size of the medium.
+     * Devices above 2 TiB report u32::MAX blocks here; this client does
+     * not implement the 16-byte variant needed past that.
+     */
+    pub fn capacity(&mut self) -> Result<(u64, u32), MscError> {
+        let command = vec![0x25, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let data = self.command_in(command, 8)?;
+        if data.len() < 8 {
+            return Err(MscError::ShortResponse {
+                what: "READ CAPACITY",
+                expected: 8,
+                actual: data.len(),
+            });
+        }
+        let last_lba = u32::from_be_bytes(data[0..4].try_into().expect("sized slice"));
+        let block_size = u32::from_be_bytes(data[4..8].try_into().expect("sized slice"));
+        Ok((u64::from(last_lba) + 1, block_size))
+    }
+
+    /// One command with an IN data phase: CBW out, data in, CSW in,
+    /// tag and status checked.
+    fn command_in(&mut self, command: Vec<u8>, length: usize) -> Result<Vec<u8>, MscError> {
+        let tag = self.next_tag;
+        self.next_tag = self.next_tag.wrapping_add(1);
+
+        let cbw = Cbw {
+            tag,
+            data_transfer_length: length as u32,
+            lun: self.lun,
+            command,
+        };
+        self.bulk
+            .write(self.endpoint_out, &cbw.to_bytes(), IO_TIMEOUT)?;
+
+        let mut data = vec![0u8; length];
+        let n = self.bulk.read(self.endpoint_in, &mut data, IO_TIMEOUT)?;
+        data.truncate(n);
+
+        let mut csw = [0u8; CSW_LEN];
+        let n = self.bulk.read(self.endpoint_in, &mut csw, IO_TIMEOUT)?;
+        let csw = Csw::parse(&csw[..n])?;
+        if csw.tag != tag {
+            return Err(MscError::TagMismatch {
+                sent: tag,
+                got: csw.tag,
+            });
+        }
+        match csw.status {
+            0x00 => Ok(data),
+            0x01 => Err(MscError::CommandFailed),
+            _ => Err(MscError::PhaseError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    /// A success CSW echoing `tag`.
+    fn csw(tag: u32, residue: u32, status: u8) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CSW_LEN);
+        bytes.extend_from_slice(&CSW_SIGNATURE.to_le_bytes());
+        bytes.extend_from_slice(&tag.to_le_bytes());
+        bytes.extend_from_slice(&residue.to_le_bytes());
+        bytes.push(status);
+        bytes
+    }
+
+    /// A standard INQUIRY response for a SanDisk stick, space-padded
+    /// the way the SCSI spec requires.
+    fn inquiry_response() -> Vec<u8> {
+        let mut bytes = vec![0u8; INQUIRY_LEN];
+        bytes[8..16].copy_from_slice(b"SanDisk ");
+        bytes[16..32].copy_from_slice(b"Ultra Fit       ");
+        bytes[32..36].copy_from_slice(b"1.00");
+        bytes
+    }
+
+    fn client_with(reads: &[Vec<u8>]) -> MscClient<MockTransport> {
+        let mut transport = MockTransport::new();
+        for r in reads {
+            transport.read_results.push_back(Ok(r.clone()));
+        }
+        transport.write_results.push_back(Ok(CBW_LEN));
+        transport.write_results.push_back(Ok(CBW_LEN));
+        MscClient::new(transport, 0x81, 0x02)
+    }
+
+    #[test]
+    fn test_cbw_wire_layout() {
+        let cbw = Cbw {
+            tag: 0x0102_0304,
+            data_transfer_length: 36,
+            lun: 1,
+            command: vec![0x12, 0x00, 0x00, 0x00, 0x24, 0x00],
+        };
+        let bytes = cbw.to_bytes();
+        assert_eq!(&bytes[0..4], b"USBC");
+        assert_eq!(bytes[4..8], [0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(bytes[8..12], [36, 0, 0, 0]);
+        assert_eq!(bytes[12], 0x80);
+        assert_eq!(bytes[13], 1);
+        assert_eq!(bytes[14], 6);
+        assert_eq!(bytes[15], 0x12);
+        // The command block is zero-padded to the fixed 31 bytes.
+        assert_eq!(bytes[21..], [0u8; 10]);
+    }
+
+    #[test]
+    fn test_csw_parse_and_rejects() {
+        let parsed = Csw::parse(&csw(7, 4, 0)).unwrap();
+        assert_eq!(parsed.tag, 7);
+        assert_eq!(parsed.data_residue, 4);
+        assert_eq!(parsed.status, 0);
+
+        assert!(matches!(
+            Csw::parse(&csw(7, 0, 0)[..12]),
+            Err(MscError::MalformedCsw(_))
+        ));
+        let mut bad = csw(7, 0, 0);
+        bad[0] = b'X';
+        assert!(matches!(Csw::parse(&bad), Err(MscError::MalformedCsw(_))));
+    }
+
+    #[test]
+    fn test_inquiry_parses_trimmed_strings() {
+        let mut client = client_with(&[inquiry_response(), csw(1, 0, 0)]);
+        let data = client.inquiry().unwrap();
+        assert_eq!(data.vendor, "SanDisk");
+        assert_eq!(data.product, "Ultra Fit");
+        assert_eq!(data.revision, "1.00");
+    }
+
+    #[test]
+    fn test_capacity_decodes_big_endian() {
+        // Last LBA 0x01dcf32f, 512-byte blocks: a 16 GB stick.
+        let mut response = Vec::new();
+        response.extend_from_slice(&0x01dc_f32fu32.to_be_bytes());
+        response.extend_from_slice(&512u32.to_be_bytes());
+
+        let mut client = client_with(&[response, csw(1, 0, 0)]);
+        let (blocks, block_size) = client.capacity().unwrap();
+        assert_eq!(blocks, 0x01dc_f330);
+        assert_eq!(block_size, 512);
+    }
+
+    #[test]
+    fn test_csw_tag_mismatch_is_an_error() {
+        let mut client = client_with(&[inquiry_response(), csw(99, 0, 0)]);
+        assert!(matches!(
+            client.inquiry(),
+            Err(MscError::TagMismatch { sent: 1, got: 99 })
+        ));
+    }
+
+    #[test]
+    fn test_failed_and_phase_statuses() {
+        let mut client = client_with(&[inquiry_response(), csw(1, 36, 1)]);
+        assert!(matches!(client.inquiry(), Err(MscError::CommandFailed)));
+
+        let mut client = client_with(&[inquiry_response(), csw(1, 0, 2)]);
+        assert!(matches!(client.inquiry(), Err(MscError::PhaseError)));
+    }
+
+    #[test]
+    fn test_tags_increment_per_command() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(inquiry_response()));
+        transport.read_results.push_back(Ok(csw(1, 0, 0)));
+        let mut response = Vec::new();
+        response.extend_from_slice(&0u32.to_be_bytes());
+        response.extend_from_slice(&512u32.to_be_bytes());
+        transport.read_results.push_back(Ok(response));
+        transport.read_results.push_back(Ok(csw(2, 0, 0)));
+        for _ in 0..2 {
+            transport.write_results.push_back(Ok(CBW_LEN));
+        }
+
+        let mut client = MscClient::new(transport, 0x81, 0x02);
+        client.inquiry().unwrap();
+        client.capacity().unwrap();
+    }
+}
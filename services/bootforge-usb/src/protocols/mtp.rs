@@ -0,0 +1,1495 @@
+// BootForge USB - MTP/PTP session client
+// Container framing plus chunked, resumable object downloads.
+
+use std::io::Write;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::UsbError;
+use crate::transfer::{BulkTransfer, InterruptPoller, UsbTransport};
+
+// PTP/MTP operation codes
+pub const OP_GET_DEVICE_INFO: u16 = 0x1001;
+pub const OP_OPEN_SESSION: u16 = 0x1002;
+pub const OP_GET_STORAGE_IDS: u16 = 0x1004;
+pub const OP_GET_STORAGE_INFO: u16 = 0x1005;
+pub const OP_GET_OBJECT_HANDLES: u16 = 0x1007;
+pub const OP_GET_OBJECT_INFO: u16 = 0x1008;
+pub const OP_GET_OBJECT: u16 = 0x1009;
+pub const OP_SEND_OBJECT: u16 = 0x100D;
+pub const OP_GET_PARTIAL_OBJECT: u16 = 0x101B;
+/// Android extension: 64-bit offset variant of GetPartialObject.
+pub const OP_ANDROID_GET_PARTIAL_OBJECT_64: u16 = 0x95C1;
+
+// Response codes
+pub const RSP_OK: u16 = 0x2001;
+
+// Event codes
+pub const EVT_OBJECT_ADDED: u16 = 0x4002;
+pub const EVT_OBJECT_REMOVED: u16 = 0x4003;
+pub const EVT_STORE_ADDED: u16 = 0x4004;
+pub const EVT_STORE_REMOVED: u16 = 0x4005;
+
+// Container types
+const CONTAINER_COMMAND: u16 = 1;
+const CONTAINER_DATA: u16 = 2;
+const CONTAINER_RESPONSE: u16 = 3;
+const CONTAINER_EVENT: u16 = 4;
+
+const CONTAINER_HEADER_LEN: usize = 12;
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default chunk size for partial-object downloads (1 MiB).
+pub const DEFAULT_CHUNK_SIZE: u32 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PtpContainer {
+    pub kind: u16,
+    pub code: u16,
+    pub transaction_id: u32,
+    pub payload: Vec<u8>,
+}
+
+impl PtpContainer {
+    pub fn encode(&self) -> Vec<u8> {
+        let len = (CONTAINER_HEADER_LEN + self.payload.len()) as u32;
+        let mut out = Vec::with_capacity(len as usize);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&self.kind.to_le_bytes());
+        out.extend_from_slice(&self.code.to_le_bytes());
+        out.extend_from_slice(&self.transaction_id.to_le_bytes());
+        out.extend_from_slice(&self.payload);
+        out
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<PtpContainer, UsbError> {
+        if bytes.len() < CONTAINER_HEADER_LEN {
+            return Err(UsbError::Parse(format!(
+                "PTP container too short: {} bytes",
+                bytes.len()
+            )));
+        }
+        let len = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        if len < CONTAINER_HEADER_LEN || len > bytes.len() {
+            return Err(UsbError::Parse(format!(
+                "PTP container length {} inconsistent with {} bytes read",
+                len,
+                bytes.len()
+            )));
+        }
+        Ok(PtpContainer {
+            kind: u16::from_le_bytes(bytes[4..6].try_into().unwrap()),
+            code: u16::from_le_bytes(bytes[6..8].try_into().unwrap()),
+            transaction_id: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            payload: bytes[CONTAINER_HEADER_LEN..len].to_vec(),
+        })
+    }
+}
+
+/// Cursor-based decoding of PTP datasets: little-endian scalars,
+/// counted UCS-2 strings, and counted u16/u32 arrays.
+pub mod dataset {
+    use crate::error::UsbError;
+
+    pub struct Reader<'a> {
+        data: &'a [u8],
+        cursor: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        pub fn new(data: &'a [u8]) -> Self {
+            Reader { data, cursor: 0 }
+        }
+
+        pub fn remaining(&self) -> usize {
+            self.data.len() - self.cursor
+        }
+
+        fn take(&mut self, n: usize) -> Result<&'a [u8], UsbError> {
+            if self.remaining() < n {
+                return Err(UsbError::Parse(format!(
+                    "dataset truncated: wanted {} bytes at offset {}, have {}",
+                    n,
+                    self.cursor,
+                    self.remaining()
+                )));
+            }
+            let slice = &self.data[self.cursor..self.cursor + n];
+            self.cursor += n;
+            Ok(slice)
+        }
+
+        pub fn read_u8(&mut self) -> Result<u8, UsbError> {
+            Ok(self.take(1)?[0])
+        }
+
+        pub fn read_u16(&mut self) -> Result<u16, UsbError> {
+            Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+        }
+
+        pub fn read_u32(&mut self) -> Result<u32, UsbError> {
+            Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+        }
+
+        pub fn read_u64(&mut self) -> Result<u64, UsbError> {
+            Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+        }
+
+        /**
+         * A PTP string: u8 character count (including the NUL
+         * terminator), then that many UCS-2 LE code units. A count of
+         * zero is the empty string with no terminator.
+         */
+        pub fn read_string(&mut self) -> Result<String, UsbError> {
+            let chars = self.read_u8()? as usize;
+            let bytes = self.take(chars * 2)?;
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes(pair.try_into().unwrap()))
+                .collect();
+            Ok(String::from_utf16_lossy(&units)
+                .trim_end_matches('\0')
+                .to_string())
+        }
+
+        /// AUINT16: u32 element count, then the elements.
+        pub fn read_array_u16(&mut self) -> Result<Vec<u16>, UsbError> {
+            let count = self.read_u32()? as usize;
+            let bytes = self.take(count * 2)?;
+            Ok(bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes(pair.try_into().unwrap()))
+                .collect())
+        }
+
+        /// AUINT32: u32 element count, then the elements.
+        pub fn read_array_u32(&mut self) -> Result<Vec<u32>, UsbError> {
+            let count = self.read_u32()? as usize;
+            let bytes = self.take(count * 4)?;
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|quad| u32::from_le_bytes(quad.try_into().unwrap()))
+                .collect())
+        }
+    }
+}
+
+/**
+ * The DeviceInfo dataset (PTP 5.1.1), in declaration order.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtpDeviceInfo {
+    pub standard_version: u16,
+    pub vendor_extension_id: u32,
+    pub vendor_extension_version: u16,
+    pub vendor_extension_desc: String,
+    pub functional_mode: u16,
+    pub operations_supported: Vec<u16>,
+    pub events_supported: Vec<u16>,
+    pub device_properties_supported: Vec<u16>,
+    pub capture_formats: Vec<u16>,
+    pub playback_formats: Vec<u16>,
+    pub manufacturer: String,
+    pub model: String,
+    pub device_version: String,
+    pub serial_number: String,
+}
+
+impl PtpDeviceInfo {
+    pub fn parse(data: &[u8]) -> Result<Self, UsbError> {
+        let mut r = dataset::Reader::new(data);
+        Ok(PtpDeviceInfo {
+            standard_version: r.read_u16()?,
+            vendor_extension_id: r.read_u32()?,
+            vendor_extension_version: r.read_u16()?,
+            vendor_extension_desc: r.read_string()?,
+            functional_mode: r.read_u16()?,
+            operations_supported: r.read_array_u16()?,
+            events_supported: r.read_array_u16()?,
+            device_properties_supported: r.read_array_u16()?,
+            capture_formats: r.read_array_u16()?,
+            playback_formats: r.read_array_u16()?,
+            manufacturer: r.read_string()?,
+            model: r.read_string()?,
+            device_version: r.read_string()?,
+            serial_number: r.read_string()?,
+        })
+    }
+}
+
+/**
+ * The StorageInfo dataset (PTP 5.2.2).
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtpStorageInfo {
+    pub storage_type: u16,
+    pub filesystem_type: u16,
+    pub access_capability: u16,
+    pub max_capacity: u64,
+    pub free_space_bytes: u64,
+    pub free_space_objects: u32,
+    pub description: String,
+    pub volume_identifier: String,
+}
+
+impl PtpStorageInfo {
+    pub fn parse(data: &[u8]) -> Result<Self, UsbError> {
+        let mut r = dataset::Reader::new(data);
+        Ok(PtpStorageInfo {
+            storage_type: r.read_u16()?,
+            filesystem_type: r.read_u16()?,
+            access_capability: r.read_u16()?,
+            max_capacity: r.read_u64()?,
+            free_space_bytes: r.read_u64()?,
+            free_space_objects: r.read_u32()?,
+            description: r.read_string()?,
+            volume_identifier: r.read_string()?,
+        })
+    }
+}
+
+/**
+ * The ObjectInfo dataset (PTP 5.3.1).
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtpObjectInfo {
+    pub storage_id: u32,
+    pub object_format: u16,
+    pub protection_status: u16,
+    pub compressed_size: u32,
+    pub thumb_format: u16,
+    pub thumb_compressed_size: u32,
+    pub thumb_width: u32,
+    pub thumb_height: u32,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub image_bit_depth: u32,
+    pub parent_object: u32,
+    pub association_type: u16,
+    pub association_desc: u32,
+    pub sequence_number: u32,
+    pub filename: String,
+    pub capture_date: String,
+    pub modification_date: String,
+    pub keywords: String,
+}
+
+impl PtpObjectInfo {
+    pub fn parse(data: &[u8]) -> Result<Self, UsbError> {
+        let mut r = dataset::Reader::new(data);
+        Ok(PtpObjectInfo {
+            storage_id: r.read_u32()?,
+            object_format: r.read_u16()?,
+            protection_status: r.read_u16()?,
+            compressed_size: r.read_u32()?,
+            thumb_format: r.read_u16()?,
+            thumb_compressed_size: r.read_u32()?,
+            thumb_width: r.read_u32()?,
+            thumb_height: r.read_u32()?,
+            image_width: r.read_u32()?,
+            image_height: r.read_u32()?,
+            image_bit_depth: r.read_u32()?,
+            parent_object: r.read_u32()?,
+            association_type: r.read_u16()?,
+            association_desc: r.read_u32()?,
+            sequence_number: r.read_u32()?,
+            filename: r.read_string()?,
+            capture_date: r.read_string()?,
+            modification_date: r.read_string()?,
+            keywords: r.read_string()?,
+        })
+    }
+}
+
+/**
+ * One event from the interrupt endpoint: an event container with up to
+ * three u32 parameters (PTP 12.2).
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtpEvent {
+    pub code: u16,
+    pub transaction_id: u32,
+    pub params: Vec<u32>,
+}
+
+impl PtpEvent {
+    pub fn parse(bytes: &[u8]) -> Result<PtpEvent, UsbError> {
+        let container = PtpContainer::parse(bytes)?;
+        if container.kind != CONTAINER_EVENT {
+            return Err(UsbError::Parse(format!(
+                "expected event container, got type {}",
+                container.kind
+            )));
+        }
+        if container.payload.len() % 4 != 0 || container.payload.len() > 12 {
+            return Err(UsbError::Parse(format!(
+                "event payload of {} bytes is not 0-3 u32 parameters",
+                container.payload.len()
+            )));
+        }
+        Ok(PtpEvent {
+            code: container.code,
+            transaction_id: container.transaction_id,
+            params: container
+                .payload
+                .chunks_exact(4)
+                .map(|quad| u32::from_le_bytes(quad.try_into().unwrap()))
+                .collect(),
+        })
+    }
+
+    /// The new object's handle, for ObjectAdded events.
+    pub fn object_handle(&self) -> Option<u32> {
+        if self.code == EVT_OBJECT_ADDED {
+            self.params.first().copied()
+        } else {
+            None
+        }
+    }
+}
+
+/**
+ * Progress of a resumable download, durable across sessions. Object
+ * handles are NOT stable across MTP sessions - a reconnect renumbers
+ * them - so the state records the object's identity (storage, parent
+ * folder, filename, size) instead of its handle, and
+ * `MtpClient::resolve_object` turns that identity back into the
+ * current session's handle before the download continues. Serialize
+ * this next to the partial file to survive a process restart too.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResumeState {
+    pub storage_id: u32,
+    /// Parent folder handle as captured; used only to narrow the
+    /// re-resolution listing, not as a stable identifier.
+    pub parent_object: u32,
+    pub filename: String,
+    pub object_size: u64,
+    /// First byte not yet confirmed written to the sink.
+    pub next_offset: u64,
+    #[serde(default = "default_resume_chunk_size")]
+    pub chunk_size: u32,
+}
+
+fn default_resume_chunk_size() -> u32 {
+    DEFAULT_CHUNK_SIZE
+}
+
+impl ResumeState {
+    /// Fresh state for an object described by its ObjectInfo dataset.
+    pub fn for_object(info: &PtpObjectInfo) -> Self {
+        ResumeState {
+            storage_id: info.storage_id,
+            parent_object: info.parent_object,
+            filename: info.filename.clone(),
+            object_size: u64::from(info.compressed_size),
+            next_offset: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.next_offset >= self.object_size
+    }
+}
+
+/**
+ * Outcome of a (possibly resumed) object download.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadOutcome {
+    /// Offset the download started from (non-zero when resumed).
+    pub resumed_from: u64,
+    /// Total bytes written to the sink by this call.
+    pub bytes_transferred: u64,
+    /// Object offset after the download (resume point if interrupted).
+    pub final_offset: u64,
+    pub complete: bool,
+}
+
+/**
+ * Blocking MTP session client over a pair of bulk endpoints.
+ */
+pub struct MtpClient<T: UsbTransport> {
+    bulk: BulkTransfer<T>,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    /// Interrupt IN endpoint carrying event containers, when the
+    /// interface has one.
+    endpoint_event: Option<u8>,
+    transaction_id: u32,
+    operations_supported: Vec<u16>,
+}
+
+impl<T: UsbTransport> MtpClient<T> {
+    pub fn new(transport: T, endpoint_in: u8, endpoint_out: u8) -> Self {
+        MtpClient {
+            bulk: BulkTransfer::new(transport),
+            endpoint_in,
+            endpoint_out,
+            endpoint_event: None,
+            transaction_id: 0,
+            operations_supported: Vec::new(),
+        }
+    }
+
+    pub fn with_event_endpoint(mut self, endpoint: u8) -> Self {
+        self.endpoint_event = Some(endpoint);
+        self
+    }
+
+    /**
+     * Discover the bulk pair (and event endpoint, when present) of an
+     * interface from the enumerated configuration instead of taking
+     * addresses by hand.
+     */
+    pub fn for_interface(
+        transport: T,
+        config: &crate::enumeration::ConfigInfo,
+        interface: u8,
+    ) -> Result<Self, UsbError> {
+        let (iface, (endpoint_in, endpoint_out)) = config
+            .interfaces
+            .iter()
+            .filter(|i| i.number == interface)
+            .find_map(|i| crate::endpoints::find_bulk_pair(i).map(|pair| (i, pair)))
+            .ok_or_else(|| {
+                UsbError::NotFound(format!("no bulk endpoint pair on interface {}", interface))
+            })?;
+        let mut client = MtpClient::new(transport, endpoint_in, endpoint_out);
+        if let Some(event) = crate::endpoints::find_interrupt_in(iface) {
+            client = client.with_event_endpoint(event);
+        }
+        Ok(client)
+    }
+
+    /// Operations advertised by the last GetDeviceInfo.
+    pub fn operations_supported(&self) -> &[u16] {
+        &self.operations_supported
+    }
+
+    pub fn supports_operation(&self, code: u16) -> bool {
+        self.operations_supported.contains(&code)
+    }
+
+    pub fn open_session(&mut self, session_id: u32) -> Result<(), UsbError> {
+        self.send_command(OP_OPEN_SESSION, &[session_id])?;
+        self.expect_ok()
+    }
+
+    /**
+     * Fetch DeviceInfo and record the advertised operation list, which
+     * decides whether partial-object downloads are available.
+     */
+    pub fn refresh_device_info(&mut self) -> Result<(), UsbError> {
+        self.send_command(OP_GET_DEVICE_INFO, &[])?;
+        let data = self.read_data()?;
+        self.operations_supported = parse_device_info_operations(&data)?;
+        self.expect_ok()
+    }
+
+    /**
+     * Fetch and parse the full DeviceInfo dataset, also recording the
+     * advertised operation list like `refresh_device_info`.
+     */
+    pub fn get_device_info(&mut self) -> Result<PtpDeviceInfo, UsbError> {
+        self.send_command(OP_GET_DEVICE_INFO, &[])?;
+        let data = self.read_data()?;
+        let info = PtpDeviceInfo::parse(&data)?;
+        self.operations_supported = info.operations_supported.clone();
+        self.expect_ok()?;
+        Ok(info)
+    }
+
+    pub fn get_storage_ids(&mut self) -> Result<Vec<u32>, UsbError> {
+        self.send_command(OP_GET_STORAGE_IDS, &[])?;
+        let data = self.read_data()?;
+        let ids = dataset::Reader::new(&data).read_array_u32()?;
+        self.expect_ok()?;
+        Ok(ids)
+    }
+
+    pub fn get_storage_info(&mut self, storage_id: u32) -> Result<PtpStorageInfo, UsbError> {
+        self.send_command(OP_GET_STORAGE_INFO, &[storage_id])?;
+        let data = self.read_data()?;
+        let info = PtpStorageInfo::parse(&data)?;
+        self.expect_ok()?;
+        Ok(info)
+    }
+
+    /**
+     * Object handles on a storage, optionally narrowed to one format
+     * code and one parent folder (`None` asks for every object).
+     */
+    pub fn get_object_handles(
+        &mut self,
+        storage_id: u32,
+        format: Option<u16>,
+        parent: Option<u32>,
+    ) -> Result<Vec<u32>, UsbError> {
+        self.send_command(
+            OP_GET_OBJECT_HANDLES,
+            &[
+                storage_id,
+                format.map(u32::from).unwrap_or(0),
+                parent.unwrap_or(0),
+            ],
+        )?;
+        let data = self.read_data()?;
+        let handles = dataset::Reader::new(&data).read_array_u32()?;
+        self.expect_ok()?;
+        Ok(handles)
+    }
+
+    pub fn get_object_info(&mut self, object_handle: u32) -> Result<PtpObjectInfo, UsbError> {
+        self.send_command(OP_GET_OBJECT_INFO, &[object_handle])?;
+        let data = self.read_data()?;
+        let info = PtpObjectInfo::parse(&data)?;
+        self.expect_ok()?;
+        Ok(info)
+    }
+
+    /**
+     * Download a whole object in one transaction, writing it to the
+     * sink. Prefer `download_object` for anything large enough that a
+     * resume point matters.
+     */
+    pub fn get_object(&mut self, object_handle: u32, sink: &mut dyn Write) -> Result<u64, UsbError> {
+        self.send_command(OP_GET_OBJECT, &[object_handle])?;
+        let data = self.read_data()?;
+        sink.write_all(&data)?;
+        self.expect_ok()?;
+        Ok(data.len() as u64)
+    }
+
+    /**
+     * Upload object bytes for the slot reserved by the preceding
+     * SendObjectInfo exchange.
+     */
+    pub fn send_object(&mut self, data: &[u8]) -> Result<(), UsbError> {
+        self.send_command(OP_SEND_OBJECT, &[])?;
+        self.send_data(OP_SEND_OBJECT, data)?;
+        self.expect_ok()
+    }
+
+    /**
+     * One read of the event endpoint; Ok(None) when no event arrived
+     * within the timeout.
+     */
+    pub fn poll_event(&mut self, timeout: Duration) -> Result<Option<PtpEvent>, UsbError> {
+        let endpoint = self.endpoint_event.ok_or_else(|| {
+            UsbError::Unsupported("no interrupt event endpoint configured".to_string())
+        })?;
+        let mut poller = InterruptPoller::new(self.bulk.transport_mut(), endpoint, timeout);
+        let mut buf = [0u8; 64];
+        match poller.poll_once(&mut buf)? {
+            Some(n) => PtpEvent::parse(&buf[..n]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /**
+     * Pump events to the callback until it returns false or the
+     * endpoint fails; timeouts keep polling.
+     */
+    pub fn event_stream(
+        &mut self,
+        timeout: Duration,
+        mut on_event: impl FnMut(PtpEvent) -> bool,
+    ) -> Result<(), UsbError> {
+        loop {
+            if let Some(event) = self.poll_event(timeout)? {
+                if !on_event(event) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /**
+     * Download an object in chunks, resuming from `start_offset`.
+     *
+     * Uses the Android 64-bit GetPartialObject extension when advertised,
+     * falling back to standard GetPartialObject (32-bit offsets). Errors
+     * mid-transfer leave the resume point recoverable from the sink size.
+     */
+    pub fn download_object(
+        &mut self,
+        object_handle: u32,
+        object_size: u64,
+        start_offset: u64,
+        chunk_size: u32,
+        sink: &mut dyn Write,
+    ) -> Result<DownloadOutcome, UsbError> {
+        let use_64 = self.partial_object_variant(object_size)?;
+
+        let chunk_size = chunk_size.max(1);
+        let mut offset = start_offset;
+        let mut transferred: u64 = 0;
+
+        while offset < object_size {
+            let want = chunk_size.min((object_size - offset).min(u32::MAX as u64) as u32);
+            let data = self.read_partial(object_handle, offset, want, use_64, sink)?;
+            offset += data;
+            transferred += data;
+
+            if data < want as u64 {
+                // Device returned short: treat as end of object.
+                break;
+            }
+        }
+
+        Ok(DownloadOutcome {
+            resumed_from: start_offset,
+            bytes_transferred: transferred,
+            final_offset: offset,
+            complete: offset >= object_size,
+        })
+    }
+
+    /**
+     * Read `length` bytes of an object starting at `offset` into the
+     * sink, in chunks of at most `DEFAULT_CHUNK_SIZE`. Returns the
+     * bytes written, which is short of `length` past the end of the
+     * object.
+     */
+    pub fn get_object_range(
+        &mut self,
+        object_handle: u32,
+        offset: u64,
+        length: u64,
+        sink: &mut dyn Write,
+    ) -> Result<u64, UsbError> {
+        let use_64 = self.partial_object_variant(offset.saturating_add(length))?;
+
+        let mut cursor = offset;
+        let end = offset.saturating_add(length);
+        while cursor < end {
+            let want = u64::from(DEFAULT_CHUNK_SIZE).min(end - cursor) as u32;
+            let got = self.read_partial(object_handle, cursor, want, use_64, sink)?;
+            cursor += got;
+            if got < u64::from(want) {
+                break;
+            }
+        }
+        Ok(cursor - offset)
+    }
+
+    /**
+     * Turn a `ResumeState`'s object identity back into this session's
+     * handle. Handles are assigned per session, so after a reconnect
+     * the handle a download started under is meaningless; the object
+     * is found again by listing its parent folder on the recorded
+     * storage and matching filename and size.
+     */
+    pub fn resolve_object(&mut self, state: &ResumeState) -> Result<u32, UsbError> {
+        let handles =
+            self.get_object_handles(state.storage_id, None, Some(state.parent_object))?;
+        for handle in handles {
+            let info = self.get_object_info(handle)?;
+            if info.filename == state.filename
+                && u64::from(info.compressed_size) == state.object_size
+            {
+                return Ok(handle);
+            }
+        }
+        Err(UsbError::NotFound(format!(
+            "object {} ({} bytes) no longer on storage 0x{:08x}",
+            state.filename, state.object_size, state.storage_id
+        )))
+    }
+
+    /**
+     * Continue a download from `state.next_offset`, confirming
+     * progress into the state after every chunk so an interruption
+     * loses at most one chunk of work. The handle must belong to the
+     * current session - after a reconnect, obtain it from
+     * `resolve_object` first.
+     */
+    pub fn download_object_resumable(
+        &mut self,
+        object_handle: u32,
+        sink: &mut dyn Write,
+        state: &mut ResumeState,
+    ) -> Result<DownloadOutcome, UsbError> {
+        let use_64 = self.partial_object_variant(state.object_size)?;
+
+        let resumed_from = state.next_offset;
+        let chunk_size = state.chunk_size.max(1);
+        let mut transferred: u64 = 0;
+
+        while state.next_offset < state.object_size {
+            let want =
+                chunk_size.min((state.object_size - state.next_offset).min(u32::MAX as u64) as u32);
+            let data = self.read_partial(object_handle, state.next_offset, want, use_64, sink)?;
+            state.next_offset += data;
+            transferred += data;
+
+            if data < u64::from(want) {
+                break;
+            }
+        }
+
+        Ok(DownloadOutcome {
+            resumed_from,
+            bytes_transferred: transferred,
+            final_offset: state.next_offset,
+            complete: state.is_complete(),
+        })
+    }
+
+    /// Which partial-object operation to use for an object of this
+    /// size: Ok(true) for the Android 64-bit extension, Ok(false) for
+    /// standard GetPartialObject.
+    fn partial_object_variant(&self, object_size: u64) -> Result<bool, UsbError> {
+        let use_64 = self.supports_operation(OP_ANDROID_GET_PARTIAL_OBJECT_64);
+        if !use_64 && !self.supports_operation(OP_GET_PARTIAL_OBJECT) {
+            return Err(UsbError::Unsupported(
+                "device does not advertise GetPartialObject".to_string(),
+            ));
+        }
+        if !use_64 && object_size > u32::MAX as u64 {
+            return Err(UsbError::Unsupported(
+                "object larger than 4GiB requires the 64-bit partial-object extension".to_string(),
+            ));
+        }
+        Ok(use_64)
+    }
+
+    /// One partial-object transaction: command, data into the sink,
+    /// response. Returns the bytes the device produced.
+    fn read_partial(
+        &mut self,
+        object_handle: u32,
+        offset: u64,
+        want: u32,
+        use_64: bool,
+        sink: &mut dyn Write,
+    ) -> Result<u64, UsbError> {
+        if use_64 {
+            self.send_command(
+                OP_ANDROID_GET_PARTIAL_OBJECT_64,
+                &[
+                    object_handle,
+                    (offset & 0xffff_ffff) as u32,
+                    (offset >> 32) as u32,
+                    want,
+                ],
+            )?;
+        } else {
+            self.send_command(OP_GET_PARTIAL_OBJECT, &[object_handle, offset as u32, want])?;
+        }
+        let data = self.read_data()?;
+        sink.write_all(&data)?;
+        self.expect_ok()?;
+        Ok(data.len() as u64)
+    }
+
+    fn next_transaction_id(&mut self) -> u32 {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        self.transaction_id
+    }
+
+    fn send_command(&mut self, code: u16, params: &[u32]) -> Result<(), UsbError> {
+        let tid = self.next_transaction_id();
+        let mut payload = Vec::with_capacity(params.len() * 4);
+        for p in params {
+            payload.extend_from_slice(&p.to_le_bytes());
+        }
+        let container = PtpContainer {
+            kind: CONTAINER_COMMAND,
+            code,
+            transaction_id: tid,
+            payload,
+        };
+        self.bulk
+            .write(self.endpoint_out, &container.encode(), IO_TIMEOUT)?;
+        Ok(())
+    }
+
+    /// Send a data container for the in-flight transaction.
+    fn send_data(&mut self, code: u16, payload: &[u8]) -> Result<(), UsbError> {
+        let container = PtpContainer {
+            kind: CONTAINER_DATA,
+            code,
+            transaction_id: self.transaction_id,
+            payload: payload.to_vec(),
+        };
+        self.bulk
+            .write(self.endpoint_out, &container.encode(), IO_TIMEOUT)?;
+        Ok(())
+    }
+
+    /// Read a data container, following up until the declared length arrives.
+    fn read_data(&mut self) -> Result<Vec<u8>, UsbError> {
+        let container = self.read_container()?;
+        if container.kind != CONTAINER_DATA {
+            return Err(UsbError::Parse(format!(
+                "expected data container, got type {}",
+                container.kind
+            )));
+        }
+        Ok(container.payload)
+    }
+
+    fn expect_ok(&mut self) -> Result<(), UsbError> {
+        let container = self.read_container()?;
+        if container.kind != CONTAINER_RESPONSE {
+            return Err(UsbError::Parse(format!(
+                "expected response container, got type {}",
+                container.kind
+            )));
+        }
+        if container.code != RSP_OK {
+            return Err(UsbError::Parse(format!(
+                "MTP response 0x{:04x}",
+                container.code
+            )));
+        }
+        Ok(())
+    }
+
+    fn read_container(&mut self) -> Result<PtpContainer, UsbError> {
+        let mut buf = vec![0u8; 512 * 1024];
+        let n = self.bulk.read(self.endpoint_in, &mut buf, IO_TIMEOUT)?;
+        let mut bytes = buf[..n].to_vec();
+
+        if bytes.len() >= 4 {
+            let declared = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+            while bytes.len() < declared {
+                let n = self.bulk.read(self.endpoint_in, &mut buf, IO_TIMEOUT)?;
+                if n == 0 {
+                    break;
+                }
+                bytes.extend_from_slice(&buf[..n]);
+            }
+        }
+
+        PtpContainer::parse(&bytes)
+    }
+}
+
+/**
+ * Pull the OperationsSupported array out of a DeviceInfo dataset.
+ */
+fn parse_device_info_operations(data: &[u8]) -> Result<Vec<u16>, UsbError> {
+    let mut r = dataset::Reader::new(data);
+    // StandardVersion, VendorExtensionID, VendorExtensionVersion
+    r.read_u16()?;
+    r.read_u32()?;
+    r.read_u16()?;
+    r.read_string()?; // VendorExtensionDesc
+    r.read_u16()?; // FunctionalMode
+    r.read_array_u16()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    fn data_container(tid: u32, payload: &[u8]) -> Vec<u8> {
+        PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_PARTIAL_OBJECT,
+            transaction_id: tid,
+            payload: payload.to_vec(),
+        }
+        .encode()
+    }
+
+    fn ok_response(tid: u32) -> Vec<u8> {
+        PtpContainer {
+            kind: CONTAINER_RESPONSE,
+            code: RSP_OK,
+            transaction_id: tid,
+            payload: Vec::new(),
+        }
+        .encode()
+    }
+
+    fn device_info_with_ops(ops: &[u16]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&100u16.to_le_bytes()); // StandardVersion
+        data.extend_from_slice(&6u32.to_le_bytes()); // VendorExtensionID
+        data.extend_from_slice(&100u16.to_le_bytes()); // VendorExtensionVersion
+        data.push(0); // empty VendorExtensionDesc
+        data.extend_from_slice(&0u16.to_le_bytes()); // FunctionalMode
+        data.extend_from_slice(&(ops.len() as u32).to_le_bytes());
+        for op in ops {
+            data.extend_from_slice(&op.to_le_bytes());
+        }
+        data
+    }
+
+    /// Encode a PTP counted string (with NUL terminator) like a device
+    /// would.
+    fn ptp_string(s: &str) -> Vec<u8> {
+        if s.is_empty() {
+            return vec![0];
+        }
+        let units: Vec<u16> = s.encode_utf16().chain(std::iter::once(0)).collect();
+        let mut out = vec![units.len() as u8];
+        for unit in units {
+            out.extend_from_slice(&unit.to_le_bytes());
+        }
+        out
+    }
+
+    fn array_u16(values: &[u16]) -> Vec<u8> {
+        let mut out = (values.len() as u32).to_le_bytes().to_vec();
+        for v in values {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+
+    fn array_u32(values: &[u32]) -> Vec<u8> {
+        let mut out = (values.len() as u32).to_le_bytes().to_vec();
+        for v in values {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+
+    /// A full DeviceInfo dataset shaped like the capture from a Pixel
+    /// in MTP mode (fields in PTP 5.1.1 order).
+    fn full_device_info() -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&100u16.to_le_bytes()); // StandardVersion
+        data.extend_from_slice(&6u32.to_le_bytes()); // VendorExtensionID (MTP)
+        data.extend_from_slice(&100u16.to_le_bytes()); // VendorExtensionVersion
+        data.extend_from_slice(&ptp_string("microsoft.com: 1.0; android.com: 1.0;"));
+        data.extend_from_slice(&0u16.to_le_bytes()); // FunctionalMode
+        data.extend_from_slice(&array_u16(&[
+            OP_GET_DEVICE_INFO,
+            OP_OPEN_SESSION,
+            OP_GET_STORAGE_IDS,
+            OP_GET_STORAGE_INFO,
+            OP_GET_OBJECT_HANDLES,
+            OP_GET_OBJECT_INFO,
+            OP_GET_OBJECT,
+            OP_SEND_OBJECT,
+            OP_ANDROID_GET_PARTIAL_OBJECT_64,
+        ]));
+        data.extend_from_slice(&array_u16(&[0x4002, 0x4003])); // events
+        data.extend_from_slice(&array_u16(&[0xd401, 0xd402])); // device props
+        data.extend_from_slice(&array_u16(&[])); // capture formats
+        data.extend_from_slice(&array_u16(&[0x3000, 0x3001, 0x3801])); // playback
+        data.extend_from_slice(&ptp_string("Google"));
+        data.extend_from_slice(&ptp_string("Pixel 3"));
+        data.extend_from_slice(&ptp_string("1.0"));
+        data.extend_from_slice(&ptp_string("8AAY0PXXX"));
+        data
+    }
+
+    #[test]
+    fn test_dataset_string_decoding() {
+        // Counted UCS-2 with NUL terminator: 4 chars for "abc\0".
+        let bytes = [4u8, b'a', 0, b'b', 0, b'c', 0, 0, 0];
+        let mut r = dataset::Reader::new(&bytes);
+        assert_eq!(r.read_string().unwrap(), "abc");
+        assert_eq!(r.remaining(), 0);
+
+        let mut empty = dataset::Reader::new(&[0u8]);
+        assert_eq!(empty.read_string().unwrap(), "");
+
+        // Declared longer than the buffer.
+        let mut short = dataset::Reader::new(&[5u8, b'a', 0]);
+        assert!(matches!(short.read_string(), Err(UsbError::Parse(_))));
+    }
+
+    #[test]
+    fn test_dataset_array_decoding() {
+        let encoded = array_u16(&[0x1001, 0x1002]);
+        let mut r = dataset::Reader::new(&encoded);
+        assert_eq!(r.read_array_u16().unwrap(), vec![0x1001, 0x1002]);
+
+        let mut bytes = 2u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&0x0001_0001u32.to_le_bytes());
+        bytes.extend_from_slice(&0x0002_0001u32.to_le_bytes());
+        let mut r = dataset::Reader::new(&bytes);
+        assert_eq!(r.read_array_u32().unwrap(), vec![0x0001_0001, 0x0002_0001]);
+
+        let bare_count = 9u32.to_le_bytes();
+        let mut truncated = dataset::Reader::new(&bare_count);
+        assert!(matches!(
+            truncated.read_array_u16(),
+            Err(UsbError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_full_device_info() {
+        let info = PtpDeviceInfo::parse(&full_device_info()).unwrap();
+        assert_eq!(info.standard_version, 100);
+        assert_eq!(info.vendor_extension_id, 6);
+        assert_eq!(
+            info.vendor_extension_desc,
+            "microsoft.com: 1.0; android.com: 1.0;"
+        );
+        assert!(info
+            .operations_supported
+            .contains(&OP_ANDROID_GET_PARTIAL_OBJECT_64));
+        assert_eq!(info.events_supported, vec![0x4002, 0x4003]);
+        assert!(info.capture_formats.is_empty());
+        assert_eq!(info.manufacturer, "Google");
+        assert_eq!(info.model, "Pixel 3");
+        assert_eq!(info.serial_number, "8AAY0PXXX");
+    }
+
+    #[test]
+    fn test_get_device_info_records_operations() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_DEVICE_INFO,
+            transaction_id: 1,
+            payload: full_device_info(),
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        let info = client.get_device_info().unwrap();
+        assert_eq!(info.model, "Pixel 3");
+        assert!(client.supports_operation(OP_ANDROID_GET_PARTIAL_OBJECT_64));
+    }
+
+    #[test]
+    fn test_storage_listing() {
+        let mut storage_info = Vec::new();
+        storage_info.extend_from_slice(&0x0004u16.to_le_bytes()); // removable RAM
+        storage_info.extend_from_slice(&0x0002u16.to_le_bytes()); // generic hierarchical
+        storage_info.extend_from_slice(&0x0000u16.to_le_bytes()); // read-write
+        storage_info.extend_from_slice(&(64u64 * 1024 * 1024 * 1024).to_le_bytes());
+        storage_info.extend_from_slice(&(21u64 * 1024 * 1024 * 1024).to_le_bytes());
+        storage_info.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+        storage_info.extend_from_slice(&ptp_string("Internal shared storage"));
+        storage_info.extend_from_slice(&ptp_string(""));
+
+        let mut ids_payload = 1u32.to_le_bytes().to_vec();
+        ids_payload.extend_from_slice(&0x0001_0001u32.to_le_bytes());
+
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_STORAGE_IDS,
+            transaction_id: 1,
+            payload: ids_payload,
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_STORAGE_INFO,
+            transaction_id: 2,
+            payload: storage_info,
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(2)));
+        transport.write_results.push_back(Ok(0));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        let ids = client.get_storage_ids().unwrap();
+        assert_eq!(ids, vec![0x0001_0001]);
+        let info = client.get_storage_info(ids[0]).unwrap();
+        assert_eq!(info.description, "Internal shared storage");
+        assert_eq!(info.max_capacity, 64 * 1024 * 1024 * 1024);
+        assert_eq!(info.volume_identifier, "");
+    }
+
+    #[test]
+    fn test_object_info_and_download() {
+        let mut object_info = Vec::new();
+        object_info.extend_from_slice(&0x0001_0001u32.to_le_bytes()); // storage
+        object_info.extend_from_slice(&0x3801u16.to_le_bytes()); // EXIF/JPEG
+        object_info.extend_from_slice(&0u16.to_le_bytes()); // no protection
+        object_info.extend_from_slice(&5u32.to_le_bytes()); // compressed size
+        object_info.extend_from_slice(&[0u8; 2 + 4 + 4 + 4]); // thumb fields
+        object_info.extend_from_slice(&[0u8; 4 + 4 + 4]); // image fields
+        object_info.extend_from_slice(&0u32.to_le_bytes()); // parent (root)
+        object_info.extend_from_slice(&0u16.to_le_bytes()); // association type
+        object_info.extend_from_slice(&0u32.to_le_bytes()); // association desc
+        object_info.extend_from_slice(&0u32.to_le_bytes()); // sequence number
+        object_info.extend_from_slice(&ptp_string("IMG_0001.JPG"));
+        object_info.extend_from_slice(&ptp_string("20260826T120000"));
+        object_info.extend_from_slice(&ptp_string("20260826T120000"));
+        object_info.extend_from_slice(&ptp_string(""));
+
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_OBJECT_INFO,
+            transaction_id: 1,
+            payload: object_info,
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_OBJECT,
+            transaction_id: 2,
+            payload: b"hello".to_vec(),
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(2)));
+        transport.write_results.push_back(Ok(0));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        let info = client.get_object_info(7).unwrap();
+        assert_eq!(info.filename, "IMG_0001.JPG");
+        assert_eq!(info.compressed_size, 5);
+
+        let mut sink = Vec::new();
+        assert_eq!(client.get_object(7, &mut sink).unwrap(), 5);
+        assert_eq!(sink, b"hello");
+    }
+
+    fn event_container(code: u16, tid: u32, params: &[u32]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        for p in params {
+            payload.extend_from_slice(&p.to_le_bytes());
+        }
+        PtpContainer {
+            kind: CONTAINER_EVENT,
+            code,
+            transaction_id: tid,
+            payload,
+        }
+        .encode()
+    }
+
+    #[test]
+    fn test_event_parsing() {
+        let event = PtpEvent::parse(&event_container(EVT_OBJECT_ADDED, 9, &[0x42])).unwrap();
+        assert_eq!(event.code, EVT_OBJECT_ADDED);
+        assert_eq!(event.transaction_id, 9);
+        assert_eq!(event.object_handle(), Some(0x42));
+
+        // Handle accessor is specific to ObjectAdded.
+        let removed = PtpEvent::parse(&event_container(EVT_OBJECT_REMOVED, 10, &[0x42])).unwrap();
+        assert_eq!(removed.object_handle(), None);
+
+        let bare = PtpEvent::parse(&event_container(EVT_STORE_ADDED, 11, &[])).unwrap();
+        assert!(bare.params.is_empty());
+    }
+
+    #[test]
+    fn test_event_parsing_rejects_malformed() {
+        // Too short for a container header.
+        assert!(PtpEvent::parse(&[4, 0, 0]).is_err());
+
+        // Wrong container type.
+        assert!(PtpEvent::parse(&ok_response(1)).is_err());
+
+        // Payload not a whole number of u32 parameters.
+        let mut ragged = event_container(EVT_OBJECT_ADDED, 1, &[0x42]);
+        ragged.push(0xff);
+        ragged[0] = (ragged.len() as u32).to_le_bytes()[0];
+        assert!(PtpEvent::parse(&ragged).is_err());
+
+        // More than three parameters.
+        assert!(PtpEvent::parse(&event_container(EVT_OBJECT_ADDED, 1, &[1, 2, 3, 4])).is_err());
+    }
+
+    #[test]
+    fn test_poll_event_requires_configured_endpoint() {
+        let mut client = MtpClient::new(MockTransport::new(), 0x81, 0x01);
+        assert!(matches!(
+            client.poll_event(Duration::from_millis(10)),
+            Err(UsbError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_event_stream_delivers_until_stopped() {
+        let mut transport = MockTransport::new();
+        transport
+            .read_results
+            .push_back(Ok(event_container(EVT_OBJECT_ADDED, 1, &[7])));
+        transport
+            .read_results
+            .push_back(Ok(event_container(EVT_OBJECT_ADDED, 2, &[8])));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01).with_event_endpoint(0x82);
+        let mut handles = Vec::new();
+        client
+            .event_stream(Duration::from_millis(10), |event| {
+                handles.extend(event.object_handle());
+                handles.len() < 2
+            })
+            .unwrap();
+        assert_eq!(handles, vec![7, 8]);
+    }
+
+    #[test]
+    fn test_send_object() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.write_results.push_back(Ok(0)); // command
+        transport.write_results.push_back(Ok(0)); // data
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        client.send_object(b"payload").unwrap();
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let container = PtpContainer {
+            kind: CONTAINER_COMMAND,
+            code: OP_OPEN_SESSION,
+            transaction_id: 42,
+            payload: vec![1, 0, 0, 0],
+        };
+        assert_eq!(PtpContainer::parse(&container.encode()).unwrap(), container);
+    }
+
+    #[test]
+    fn test_parse_device_info_operations() {
+        let data = device_info_with_ops(&[OP_GET_DEVICE_INFO, OP_GET_PARTIAL_OBJECT]);
+        let ops = parse_device_info_operations(&data).unwrap();
+        assert_eq!(ops, vec![OP_GET_DEVICE_INFO, OP_GET_PARTIAL_OBJECT]);
+    }
+
+    #[test]
+    fn test_download_requires_partial_object_support() {
+        let client_transport = MockTransport::new();
+        let mut client = MtpClient::new(client_transport, 0x81, 0x01);
+        let mut sink = Vec::new();
+        let err = client
+            .download_object(1, 10, 0, DEFAULT_CHUNK_SIZE, &mut sink)
+            .unwrap_err();
+        assert!(matches!(err, UsbError::Unsupported(_)));
+    }
+
+    #[test]
+    fn test_chunked_download_resumes_from_offset() {
+        let mut transport = MockTransport::new();
+        // refresh_device_info: data + OK
+        transport
+            .read_results
+            .push_back(Ok(PtpContainer {
+                kind: CONTAINER_DATA,
+                code: OP_GET_DEVICE_INFO,
+                transaction_id: 1,
+                payload: device_info_with_ops(&[OP_GET_PARTIAL_OBJECT]),
+            }
+            .encode()));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        // two chunks of 4 bytes each, resumed from offset 4 of a 12-byte object
+        transport.read_results.push_back(Ok(data_container(2, &[4, 5, 6, 7])));
+        transport.read_results.push_back(Ok(ok_response(2)));
+        transport.read_results.push_back(Ok(data_container(3, &[8, 9, 10, 11])));
+        transport.read_results.push_back(Ok(ok_response(3)));
+        transport.write_results.push_back(Ok(0));
+        transport.write_results.push_back(Ok(0));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        client.refresh_device_info().unwrap();
+
+        let mut sink = Vec::new();
+        let outcome = client.download_object(7, 12, 4, 4, &mut sink).unwrap();
+        assert_eq!(sink, vec![4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(outcome.resumed_from, 4);
+        assert_eq!(outcome.bytes_transferred, 8);
+        assert_eq!(outcome.final_offset, 12);
+        assert!(outcome.complete);
+    }
+
+    /// An ObjectInfo dataset for a root-level object with this
+    /// filename and size.
+    fn object_info_dataset(filename: &str, size: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x0001_0001u32.to_le_bytes()); // storage
+        data.extend_from_slice(&0x3000u16.to_le_bytes()); // undefined format
+        data.extend_from_slice(&0u16.to_le_bytes()); // no protection
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&[0u8; 2 + 4 + 4 + 4]); // thumb fields
+        data.extend_from_slice(&[0u8; 4 + 4 + 4]); // image fields
+        data.extend_from_slice(&0u32.to_le_bytes()); // parent (root)
+        data.extend_from_slice(&0u16.to_le_bytes()); // association type
+        data.extend_from_slice(&0u32.to_le_bytes()); // association desc
+        data.extend_from_slice(&0u32.to_le_bytes()); // sequence number
+        data.extend_from_slice(&ptp_string(filename));
+        data.extend_from_slice(&ptp_string("20260826T120000"));
+        data.extend_from_slice(&ptp_string("20260826T120000"));
+        data.extend_from_slice(&ptp_string(""));
+        data
+    }
+
+    #[test]
+    fn test_get_object_range_reads_only_the_window() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(data_container(1, &[4, 5, 6, 7])));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        client.operations_supported = vec![OP_GET_PARTIAL_OBJECT];
+
+        let mut sink = Vec::new();
+        let got = client.get_object_range(7, 4, 4, &mut sink).unwrap();
+        assert_eq!(got, 4);
+        assert_eq!(sink, vec![4, 5, 6, 7]);
+
+        // A range past the end of the object comes back short.
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(data_container(1, &[10, 11])));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.write_results.push_back(Ok(0));
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        client.operations_supported = vec![OP_GET_PARTIAL_OBJECT];
+        let mut tail = Vec::new();
+        assert_eq!(client.get_object_range(7, 10, 8, &mut tail).unwrap(), 2);
+        assert_eq!(tail, vec![10, 11]);
+    }
+
+    #[test]
+    fn test_resumable_download_survives_reconnect_with_new_handle() {
+        let payload = b"ABCDEFGH";
+        let mut state = ResumeState::for_object(
+            &PtpObjectInfo::parse(&object_info_dataset("CLIP.MP4", payload.len() as u32)).unwrap(),
+        )
+        .with_chunk_size(4);
+        let mut sink = Vec::new();
+
+        // Session one: the first chunk lands, then the device drops off
+        // the bus mid-transfer.
+        let mut transport = MockTransport::new();
+        transport
+            .read_results
+            .push_back(Ok(data_container(1, &payload[..4])));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.read_results.push_back(Err(rusb::Error::NoDevice));
+        transport.write_results.push_back(Ok(0));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        client.operations_supported = vec![OP_GET_PARTIAL_OBJECT];
+        let err = client
+            .download_object_resumable(7, &mut sink, &mut state)
+            .unwrap_err();
+        assert!(matches!(err, UsbError::Disconnected));
+        assert_eq!(state.next_offset, 4);
+        assert!(!state.is_complete());
+
+        // The state survives serialization across the process restart.
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state: ResumeState = serde_json::from_str(&json).unwrap();
+
+        // Session two: the device renumbered its handles; the object is
+        // re-resolved by identity, skipping a stranger with the same
+        // parent, and the download continues from the confirmed byte.
+        let mut handles_payload = 2u32.to_le_bytes().to_vec();
+        handles_payload.extend_from_slice(&0x12u32.to_le_bytes());
+        handles_payload.extend_from_slice(&0x99u32.to_le_bytes());
+
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_OBJECT_HANDLES,
+            transaction_id: 1,
+            payload: handles_payload,
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_OBJECT_INFO,
+            transaction_id: 2,
+            payload: object_info_dataset("OTHER.MP4", 8),
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(2)));
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_OBJECT_INFO,
+            transaction_id: 3,
+            payload: object_info_dataset("CLIP.MP4", 8),
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(3)));
+        transport
+            .read_results
+            .push_back(Ok(data_container(4, &payload[4..])));
+        transport.read_results.push_back(Ok(ok_response(4)));
+        for _ in 0..4 {
+            transport.write_results.push_back(Ok(0));
+        }
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        client.operations_supported = vec![OP_GET_PARTIAL_OBJECT];
+        let handle = client.resolve_object(&state).unwrap();
+        assert_eq!(handle, 0x99);
+
+        let outcome = client
+            .download_object_resumable(handle, &mut sink, &mut state)
+            .unwrap();
+        assert_eq!(outcome.resumed_from, 4);
+        assert_eq!(outcome.final_offset, 8);
+        assert!(outcome.complete);
+        assert!(state.is_complete());
+        assert_eq!(sink, payload);
+    }
+
+    #[test]
+    fn test_resolve_object_reports_missing_object() {
+        let state = ResumeState {
+            storage_id: 0x0001_0001,
+            parent_object: 0,
+            filename: "GONE.MP4".to_string(),
+            object_size: 8,
+            next_offset: 4,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        };
+
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(PtpContainer {
+            kind: CONTAINER_DATA,
+            code: OP_GET_OBJECT_HANDLES,
+            transaction_id: 1,
+            payload: array_u32(&[]),
+        }
+        .encode()));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        let err = client.resolve_object(&state).unwrap_err();
+        assert!(matches!(err, UsbError::NotFound(_)));
+    }
+
+    #[test]
+    fn test_interrupted_download_reports_resume_point() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(data_container(1, &[0, 1, 2, 3])));
+        transport.read_results.push_back(Ok(ok_response(1)));
+        transport
+            .read_results
+            .push_back(Err(rusb::Error::NoDevice));
+        transport.write_results.push_back(Ok(0));
+        transport.write_results.push_back(Ok(0));
+
+        let mut client = MtpClient::new(transport, 0x81, 0x01);
+        client.operations_supported = vec![OP_GET_PARTIAL_OBJECT];
+
+        let mut sink = Vec::new();
+        let err = client
+            .download_object(7, 12, 0, 4, &mut sink)
+            .unwrap_err();
+        assert!(matches!(err, UsbError::Disconnected));
+        // Four bytes landed; a retry passes sink.len() as start_offset.
+        assert_eq!(sink.len(), 4);
+    }
+}
@@ -0,0 +1,54 @@
+// BootForge USB - Samsung download (Odin) mode
+// Detection of Samsung's bootloader download mode, which speaks the
+// Odin/LOKE protocol over what looks like a CDC-ACM serial port and is
+// unrelated to fastboot. Identification only; no LOKE client here.
+
+use crate::protocols::classify::field_contains;
+
+pub const SAMSUNG_VID: u16 = 0x04e8;
+
+/// 685d is modern download mode; 6601 is the pre-Kies loader still
+/// seen on older devices.
+const DOWNLOAD_PIDS: [u16; 2] = [0x685d, 0x6601];
+
+/// Table hit: the device has re-enumerated in download mode.
+pub fn is_download_mode(vendor_id: u16, product_id: u16) -> bool {
+    vendor_id == SAMSUNG_VID && DOWNLOAD_PIDS.contains(&product_id)
+}
+
+/// Download mode fronts a CDC-ACM control interface (02/02/01); only a
+/// suggestion, since Samsung modems expose the same triple when booted.
+pub fn interface_suggests_download(class: u8, subclass: u8, protocol: u8) -> bool {
+    (class, subclass, protocol) == (0x02, 0x02, 0x01)
+}
+
+/// Product strings seen in download mode: "Gadget Serial" on most
+/// builds, "Odin"/"Download" on engineering ones.
+pub fn strings_suggest_download(product: &Option<String>) -> bool {
+    field_contains(product, "odin")
+        || field_contains(product, "download")
+        || field_contains(product, "gadget serial")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_pid_table() {
+        assert!(is_download_mode(0x04e8, 0x685d));
+        assert!(is_download_mode(0x04e8, 0x6601));
+        assert!(!is_download_mode(0x04e8, 0x6860));
+        assert!(!is_download_mode(0x05c6, 0x685d));
+    }
+
+    #[test]
+    fn test_download_heuristics() {
+        assert!(interface_suggests_download(0x02, 0x02, 0x01));
+        assert!(!interface_suggests_download(0x0a, 0x00, 0x00));
+        assert!(strings_suggest_download(&Some("Gadget Serial".to_string())));
+        assert!(strings_suggest_download(&Some("ODIN  mode".to_string())));
+        assert!(!strings_suggest_download(&Some("Galaxy S23".to_string())));
+        assert!(!strings_suggest_download(&None));
+    }
+}
@@ -0,0 +1,241 @@
+// BootForge USB - Extensible protocol registry
+// The built-in classifier covers hardware the wider world ships;
+// deployments with custom VIDs extend it here - programmatically, or
+// from a JSON mapping file - instead of forking the probe modules.
+
+use std::fmt;
+use std::io::Read;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::enumeration::UsbDeviceRecord;
+use crate::error::UsbError;
+use crate::protocols::classify::{classify_device_protocols_set, Protocol, ProtocolSet};
+
+/**
+ * One way of recognising a device. The closure form sees the whole
+ * record, for matchers that need strings or interface data.
+ */
+pub enum Matcher {
+    Vid(u16),
+    VidPid(u16, u16),
+    Predicate(Box<dyn Fn(&UsbDeviceRecord) -> bool + Send + Sync>),
+}
+
+impl Matcher {
+    fn matches(&self, record: &UsbDeviceRecord) -> bool {
+        match self {
+            Matcher::Vid(vid) => record.vendor_id == *vid,
+            Matcher::VidPid(vid, pid) => {
+                record.vendor_id == *vid && record.product_id == *pid
+            }
+            Matcher::Predicate(predicate) => predicate(record),
+        }
+    }
+}
+
+impl fmt::Debug for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Matcher::Vid(vid) => write!(f, "Vid({:04x})", vid),
+            Matcher::VidPid(vid, pid) => write!(f, "VidPid({:04x}:{:04x})", vid, pid),
+            Matcher::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+/**
+ * The built-in probes plus user-registered matchers, applied in
+ * registration order. Results are a union: a record matching several
+ * matchers (or a matcher and a built-in probe) reports every protocol,
+ * same as a composite device does.
+ */
+#[derive(Debug, Default)]
+pub struct ProtocolRegistry {
+    entries: Vec<(Protocol, Matcher)>,
+}
+
+impl ProtocolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a matcher under a protocol name ("fastboot",
+    /// "qualcomm-edl", ...); see `Protocol::by_name` for the list.
+    pub fn register(&mut self, protocol: &str, matcher: Matcher) -> Result<(), UsbError> {
+        let protocol = Protocol::by_name(protocol)
+            .ok_or_else(|| UsbError::Parse(format!("unknown protocol name {:?}", protocol)))?;
+        self.register_protocol(protocol, matcher);
+        Ok(())
+    }
+
+    pub fn register_protocol(&mut self, protocol: Protocol, matcher: Matcher) {
+        self.entries.push((protocol, matcher));
+    }
+
+    /**
+     * Load extra VID/PID mappings from a JSON document of the shape
+     *
+     * ```json
+     * { "mappings": [
+     *   { "vendor_id": "0x2b4c", "product_id": "0x1001", "protocol": "fastboot" },
+     *   { "vendor_id": 1452, "protocol": "apple" }
+     * ] }
+     * ```
+     *
+     * Numbers may be plain integers or "0x"-prefixed strings; omitting
+     * `product_id` matches the whole vendor.
+     */
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, UsbError> {
+        let config: MappingConfig = serde_json::from_reader(reader)
+            .map_err(|e| UsbError::Parse(format!("protocol mapping config: {}", e)))?;
+        let mut registry = ProtocolRegistry::new();
+        for mapping in config.mappings {
+            let matcher = match mapping.product_id {
+                Some(pid) => Matcher::VidPid(mapping.vendor_id, pid),
+                None => Matcher::Vid(mapping.vendor_id),
+            };
+            registry.register(&mapping.protocol, matcher)?;
+        }
+        Ok(registry)
+    }
+
+    /// Built-in probes first, then the registered matchers in
+    /// registration order; the union of everything that matched.
+    pub fn classify(&self, record: &UsbDeviceRecord) -> ProtocolSet {
+        let mut set = classify_device_protocols_set(record);
+        for (protocol, matcher) in &self.entries {
+            if matcher.matches(record) {
+                set.insert(*protocol);
+            }
+        }
+        set
+    }
+}
+
+#[derive(Deserialize)]
+struct MappingConfig {
+    mappings: Vec<Mapping>,
+}
+
+#[derive(Deserialize)]
+struct Mapping {
+    #[serde(deserialize_with = "hex_or_int")]
+    vendor_id: u16,
+    #[serde(default, deserialize_with = "opt_hex_or_int")]
+    product_id: Option<u16>,
+    protocol: String,
+}
+
+/// Accept 4660 or "0x1234"; configs written by humans use hex.
+fn hex_or_int<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Int(u16),
+        Text(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Int(v) => Ok(v),
+        Raw::Text(s) => {
+            let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(&s);
+            u16::from_str_radix(digits, 16)
+                .map_err(|_| serde::de::Error::custom(format!("bad id {:?}", s)))
+        }
+    }
+}
+
+fn opt_hex_or_int<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    hex_or_int(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::version::BcdVersion;
+
+    fn record(vendor_id: u16, product_id: u16, product: Option<&str>) -> UsbDeviceRecord {
+        UsbDeviceRecord {
+            bus_number: 1,
+            device_number: 9,
+            vendor_id,
+            product_id,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0200),
+                device_version: BcdVersion(0x0100),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: None,
+            product: product.map(str::to_string),
+            serial_number: None,
+            sysfs_path: String::new(),
+            speed: None,
+            interfaces: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_vid_pid_and_closure_matchers() {
+        let mut registry = ProtocolRegistry::new();
+        registry
+            .register("fastboot", Matcher::VidPid(0x2b4c, 0x1001))
+            .unwrap();
+        registry.register_protocol(
+            Protocol::Adb,
+            Matcher::Predicate(Box::new(|r| {
+                r.product.as_deref() == Some("Internal Bridge")
+            })),
+        );
+
+        let custom = record(0x2b4c, 0x1001, Some("Internal Bridge"));
+        let set = registry.classify(&custom);
+        assert!(set.contains(Protocol::Fastboot));
+        assert!(set.contains(Protocol::Adb));
+
+        // The built-ins see nothing in this device.
+        assert!(classify_device_protocols_set(&custom).is_empty());
+        // And the matchers don't leak onto other hardware.
+        assert!(registry.classify(&record(0x2b4c, 0x1002, None)).is_empty());
+
+        assert!(registry.register("warp-drive", Matcher::Vid(0x2b4c)).is_err());
+    }
+
+    #[test]
+    fn test_from_reader_config() {
+        let config = r#"{ "mappings": [
+            { "vendor_id": "0x2b4c", "product_id": "0x1001", "protocol": "fastboot" },
+            { "vendor_id": 1256, "protocol": "samsung-download" }
+        ] }"#;
+        let registry = ProtocolRegistry::from_reader(config.as_bytes()).unwrap();
+
+        assert!(registry
+            .classify(&record(0x2b4c, 0x1001, None))
+            .contains(Protocol::Fastboot));
+        // Vendor-wide mapping (1256 = 0x04e8).
+        assert!(registry
+            .classify(&record(0x04e8, 0x1234, None))
+            .contains(Protocol::SamsungDownload));
+
+        let bad = r#"{ "mappings": [ { "vendor_id": "0xZZ", "protocol": "adb" } ] }"#;
+        assert!(ProtocolRegistry::from_reader(bad.as_bytes()).is_err());
+        let unknown = r#"{ "mappings": [ { "vendor_id": 1, "protocol": "nope" } ] }"#;
+        assert!(ProtocolRegistry::from_reader(unknown.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_union_with_builtins_preserves_multi_protocol() {
+        // An Apple phone the deployment also talks ADB to: both stay.
+        let mut registry = ProtocolRegistry::new();
+        registry
+            .register("adb", Matcher::VidPid(0x05ac, 0x12a8))
+            .unwrap();
+        let set = registry.classify(&record(0x05ac, 0x12a8, Some("iPhone")));
+        assert!(set.contains(Protocol::Apple));
+        assert!(set.contains(Protocol::Adb));
+    }
+}
@@ -0,0 +1,322 @@
+// BootForge USB - Device session across protocol-mode transitions
+// Holds onto one physical device (by serial) while it reboots between
+// system, recovery, and bootloader modes, re-acquiring it after each
+// transition so provisioning flows don't hand-roll enumeration waits.
+
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::enumeration::UsbDeviceRecord;
+use crate::error::UsbError;
+use crate::protocols::classify::{classify_device_protocols_set, Protocol};
+
+/**
+ * The protocol mode a device is currently running in.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Booted Android talking ADB.
+    System,
+    /// Recovery environment, which also speaks ADB.
+    Recovery,
+    /// Bootloader speaking fastboot.
+    Bootloader,
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Mode::System => "system",
+            Mode::Recovery => "recovery",
+            Mode::Bootloader => "bootloader",
+        })
+    }
+}
+
+/**
+ * Best-effort mode detection from a device record via classification.
+ *
+ * Fastboot maps to Bootloader; ADB maps to System. Recovery also speaks
+ * ADB and is indistinguishable from the descriptors alone - a
+ * `SessionPort` that can ask the device (`adb get-state`) should refine
+ * this.
+ */
+pub fn mode_of(record: &UsbDeviceRecord) -> Option<Mode> {
+    let set = classify_device_protocols_set(record);
+    if set.contains(Protocol::Fastboot) {
+        Some(Mode::Bootloader)
+    } else if set.contains(Protocol::Adb) {
+        Some(Mode::System)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("device {serial} did not reach {target} within {waited:?}")]
+    Timeout {
+        serial: String,
+        target: Mode,
+        waited: Duration,
+    },
+
+    #[error("no client can request {current} -> {target}")]
+    NoApplicableClient { current: Mode, target: Mode },
+
+    #[error("device {0} is not present in any known mode")]
+    NotPresent(String),
+
+    #[error(transparent)]
+    Usb(#[from] UsbError),
+}
+
+/**
+ * How long `reboot_to` waits for the device to reappear in each target
+ * mode. Bootloaders come up in seconds; a full system boot can take
+ * minutes.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct TransitionTimeouts {
+    pub to_system: Duration,
+    pub to_recovery: Duration,
+    pub to_bootloader: Duration,
+    /// How long to wait for the old mode to drop off the bus before
+    /// treating the reboot request as ignored.
+    pub disappear: Duration,
+}
+
+impl Default for TransitionTimeouts {
+    fn default() -> Self {
+        TransitionTimeouts {
+            to_system: Duration::from_secs(120),
+            to_recovery: Duration::from_secs(60),
+            to_bootloader: Duration::from_secs(30),
+            disappear: Duration::from_secs(10),
+        }
+    }
+}
+
+impl TransitionTimeouts {
+    fn appear_for(&self, target: Mode) -> Duration {
+        match target {
+            Mode::System => self.to_system,
+            Mode::Recovery => self.to_recovery,
+            Mode::Bootloader => self.to_bootloader,
+        }
+    }
+}
+
+/**
+ * The environment a session drives: finding the device and issuing the
+ * mode-appropriate reboot command. Implementations delegate to whichever
+ * protocol client applies to the current mode (fastboot `reboot-*` in
+ * the bootloader, `adb reboot ...` elsewhere), which keeps the
+ * state-machine logic here testable against a scripted port.
+ */
+pub trait SessionPort {
+    /// Locate the device by serial and report the mode it is in, or
+    /// None while it is off the bus mid-transition.
+    fn probe(&mut self, serial: &str) -> Result<Option<Mode>, UsbError>;
+
+    /// Ask the device, currently in `current`, to reboot into `target`.
+    fn request_reboot(&mut self, serial: &str, current: Mode, target: Mode)
+        -> Result<(), SessionError>;
+}
+
+/**
+ * A handle on one physical device that survives reboots between
+ * protocol modes.
+ */
+pub struct DeviceSession<P: SessionPort> {
+    port: P,
+    serial: String,
+    timeouts: TransitionTimeouts,
+    poll_interval: Duration,
+}
+
+impl<P: SessionPort> DeviceSession<P> {
+    pub fn new(port: P, serial: impl Into<String>) -> Self {
+        DeviceSession {
+            port,
+            serial: serial.into(),
+            timeouts: TransitionTimeouts::default(),
+            poll_interval: Duration::from_millis(250),
+        }
+    }
+
+    pub fn with_timeouts(mut self, timeouts: TransitionTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// The mode the device is in right now, or NotPresent.
+    pub fn current_mode(&mut self) -> Result<Mode, SessionError> {
+        self.port
+            .probe(&self.serial)?
+            .ok_or_else(|| SessionError::NotPresent(self.serial.clone()))
+    }
+
+    /**
+     * Reboot into `target` and block until the device has re-enumerated
+     * there: request the reboot through the currently applicable client,
+     * wait for the device to drop off the bus, then wait for it to
+     * reappear classified as `target`. A no-op when already there.
+     */
+    pub fn reboot_to(&mut self, target: Mode) -> Result<Mode, SessionError> {
+        let current = self.current_mode()?;
+        if current == target {
+            return Ok(current);
+        }
+
+        self.port.request_reboot(&self.serial, current, target)?;
+        log::info!(
+            "session {}: rebooting {} -> {}",
+            self.serial,
+            current,
+            target
+        );
+
+        // The old mode dropping off the bus confirms the request took;
+        // some devices re-enumerate fast enough that we only ever see
+        // the target mode, so absence is not required - seeing the
+        // target is.
+        let deadline = Instant::now() + self.timeouts.disappear + self.timeouts.appear_for(target);
+        let mut seen_gone = false;
+        loop {
+            match self.port.probe(&self.serial)? {
+                Some(mode) if mode == target => return Ok(mode),
+                Some(_) => {}
+                None => seen_gone = true,
+            }
+            if Instant::now() >= deadline {
+                let waited = if seen_gone {
+                    self.timeouts.appear_for(target)
+                } else {
+                    self.timeouts.disappear
+                };
+                return Err(SessionError::Timeout {
+                    serial: self.serial.clone(),
+                    target,
+                    waited,
+                });
+            }
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Scripted port: each probe pops the next mode observation, mirroring
+    /// the device's appearance/disappearance sequence on the bus.
+    struct ScriptedPort {
+        probes: VecDeque<Option<Mode>>,
+        reboot_requests: Vec<(Mode, Mode)>,
+    }
+
+    impl ScriptedPort {
+        fn new(probes: &[Option<Mode>]) -> Self {
+            ScriptedPort {
+                probes: probes.iter().copied().collect(),
+                reboot_requests: Vec::new(),
+            }
+        }
+    }
+
+    impl SessionPort for ScriptedPort {
+        fn probe(&mut self, _serial: &str) -> Result<Option<Mode>, UsbError> {
+            // Once the script runs out the device stays in its last
+            // observed state (absent).
+            Ok(self.probes.pop_front().unwrap_or(None))
+        }
+
+        fn request_reboot(
+            &mut self,
+            _serial: &str,
+            current: Mode,
+            target: Mode,
+        ) -> Result<(), SessionError> {
+            self.reboot_requests.push((current, target));
+            Ok(())
+        }
+    }
+
+    fn session(probes: &[Option<Mode>]) -> DeviceSession<ScriptedPort> {
+        DeviceSession::new(ScriptedPort::new(probes), "BF-1234")
+            .with_poll_interval(Duration::from_millis(1))
+            .with_timeouts(TransitionTimeouts {
+                to_system: Duration::from_millis(50),
+                to_recovery: Duration::from_millis(50),
+                to_bootloader: Duration::from_millis(50),
+                disappear: Duration::from_millis(50),
+            })
+    }
+
+    #[test]
+    fn test_reboot_to_bootloader_waits_out_the_transition() {
+        // Present in system, still present, gone, gone, back as bootloader.
+        let mut session = session(&[
+            Some(Mode::System),
+            Some(Mode::System),
+            None,
+            None,
+            Some(Mode::Bootloader),
+        ]);
+        assert_eq!(session.reboot_to(Mode::Bootloader).unwrap(), Mode::Bootloader);
+        assert_eq!(
+            session.port.reboot_requests,
+            vec![(Mode::System, Mode::Bootloader)]
+        );
+    }
+
+    #[test]
+    fn test_reboot_to_current_mode_is_a_no_op() {
+        let mut session = session(&[Some(Mode::Bootloader)]);
+        assert_eq!(session.reboot_to(Mode::Bootloader).unwrap(), Mode::Bootloader);
+        assert!(session.port.reboot_requests.is_empty());
+    }
+
+    #[test]
+    fn test_passing_through_another_mode_keeps_waiting() {
+        // System -> recovery flashes past bootloader on its way up.
+        let mut session = session(&[
+            Some(Mode::System),
+            None,
+            Some(Mode::Bootloader),
+            None,
+            Some(Mode::Recovery),
+        ]);
+        assert_eq!(session.reboot_to(Mode::Recovery).unwrap(), Mode::Recovery);
+    }
+
+    #[test]
+    fn test_timeout_when_device_never_returns() {
+        let mut session = session(&[Some(Mode::System), None]);
+        match session.reboot_to(Mode::Bootloader).unwrap_err() {
+            SessionError::Timeout { serial, target, .. } => {
+                assert_eq!(serial, "BF-1234");
+                assert_eq!(target, Mode::Bootloader);
+            }
+            other => panic!("expected Timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_absent_device_reports_not_present() {
+        let mut session = session(&[]);
+        assert!(matches!(
+            session.reboot_to(Mode::System).unwrap_err(),
+            SessionError::NotPresent(_)
+        ));
+    }
+}
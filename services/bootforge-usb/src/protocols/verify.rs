@@ -0,0 +1,92 @@
+// BootForge USB - Flash verification policy and CRC support
+// Shared by the DFU and fastboot clients so both report verification the
+// same way.
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * How a flash operation should be verified after the data phase.
+ */
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum VerifyMode {
+    /// Trust the device's OKAY; no readback.
+    #[default]
+    None,
+    /// Read the written data back and compare byte-for-byte.
+    Readback,
+    /// Compare CRC32 values, streaming the readback so neither side
+    /// holds a second copy of the image.
+    Crc,
+}
+
+/**
+ * What a flash operation's report says about verification.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Verification {
+    /// VerifyMode::None, or the device offered no way to read back.
+    Skipped,
+    Passed(VerifyMode),
+}
+
+/**
+ * Streaming CRC-32 (IEEE 802.3 polynomial, reflected), matching what
+ * fastboot's `oem crc` vendor hooks report.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32(0xffff_ffff)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.0;
+        for &byte in data {
+            crc ^= u32::from(byte);
+            for _ in 0..8 {
+                let mask = (crc & 1).wrapping_neg();
+                crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+        self.0 = crc;
+    }
+
+    pub fn finalize(self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One-shot CRC32 of a buffer.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_value() {
+        // The standard CRC-32 check value for "123456789".
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let mut streaming = Crc32::new();
+        streaming.update(b"1234");
+        streaming.update(b"56789");
+        assert_eq!(streaming.finalize(), crc32(b"123456789"));
+        assert_eq!(crc32(b""), 0);
+    }
+}
@@ -0,0 +1,360 @@
+// BootForge USB - Transport re-acquisition
+// DFU detach, fastboot reboot-bootloader, and AOA switches all drop the
+// device off the bus and bring it back at a new address, often under a
+// new product id. Every caller used to hand-roll the close / wait /
+// re-find / reopen / reclaim dance; ReacquiringHandle centralizes it
+// behind the UsbTransport trait so protocol clients get it for free.
+
+use std::time::{Duration, Instant};
+
+use crate::transfer::UsbTransport;
+
+/**
+ * How to locate and reopen the device after a disconnect.
+ *
+ * Implementations match on a stable identity (typically the serial
+ * number) plus the set of product ids the device may come back under,
+ * and re-claim whatever interfaces the protocol had claimed before
+ * returning the fresh transport. `Ok(None)` means the device has not
+ * re-appeared yet; the handle keeps polling until its deadline.
+ */
+pub trait DeviceReopener {
+    type Transport: UsbTransport;
+
+    fn try_reopen(&mut self) -> Result<Option<Self::Transport>, rusb::Error>;
+}
+
+/**
+ * Bounds on the re-acquire dance and on what gets retried afterwards.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReacquirePolicy {
+    /// Total time allowed for the device to come back.
+    pub deadline: Duration,
+    pub poll_interval: Duration,
+    /// Also retry the failed write after a successful reopen. Off by
+    /// default: only reads are assumed idempotent, so a caller enables
+    /// this only when the protocol's writes can be repeated safely.
+    pub retry_writes: bool,
+}
+
+impl Default for ReacquirePolicy {
+    fn default() -> Self {
+        ReacquirePolicy {
+            deadline: Duration::from_secs(15),
+            poll_interval: Duration::from_millis(250),
+            retry_writes: false,
+        }
+    }
+}
+
+impl ReacquirePolicy {
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_retry_writes(mut self, retry_writes: bool) -> Self {
+        self.retry_writes = retry_writes;
+        self
+    }
+}
+
+/**
+ * A transport that survives disconnect/reconnect cycles.
+ *
+ * Any transfer failing with `NoDevice` triggers a bounded wait for the
+ * device to re-enumerate, a reopen through the `DeviceReopener`, and -
+ * when the operation is idempotent (reads always, writes only under
+ * `retry_writes`) - one transparent retry. Non-retried operations still
+ * reacquire, so the next call runs against the fresh handle.
+ *
+ * Implements `UsbTransport`, so existing protocol clients accept it
+ * through their normal constructors.
+ */
+pub struct ReacquiringHandle<R: DeviceReopener> {
+    transport: R::Transport,
+    reopener: R,
+    policy: ReacquirePolicy,
+    reopens: usize,
+}
+
+impl<R: DeviceReopener> ReacquiringHandle<R> {
+    pub fn new(transport: R::Transport, reopener: R) -> Self {
+        ReacquiringHandle {
+            transport,
+            reopener,
+            policy: ReacquirePolicy::default(),
+            reopens: 0,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: ReacquirePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// How many times the device has been reopened so far.
+    pub fn reopens(&self) -> usize {
+        self.reopens
+    }
+
+    /// Poll the reopener until the device is back or the deadline runs
+    /// out; a device that never returns reads as `NoDevice`.
+    fn reacquire(&mut self) -> Result<(), rusb::Error> {
+        let deadline = Instant::now() + self.policy.deadline;
+        loop {
+            if let Some(transport) = self.reopener.try_reopen()? {
+                self.transport = transport;
+                self.reopens += 1;
+                log::debug!("reacquired device after disconnect");
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(rusb::Error::NoDevice);
+            }
+            std::thread::sleep(self.policy.poll_interval);
+        }
+    }
+
+    fn run<T>(
+        &mut self,
+        idempotent: bool,
+        mut op: impl FnMut(&mut R::Transport) -> Result<T, rusb::Error>,
+    ) -> Result<T, rusb::Error> {
+        match op(&mut self.transport) {
+            Err(rusb::Error::NoDevice) => {
+                self.reacquire()?;
+                if idempotent {
+                    op(&mut self.transport)
+                } else {
+                    // The handle is healthy again, but replaying the
+                    // write is the caller's call.
+                    Err(rusb::Error::NoDevice)
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+impl<R: DeviceReopener> UsbTransport for ReacquiringHandle<R> {
+    fn read_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        self.run(true, |t| t.read_bulk(endpoint, buf, timeout))
+    }
+
+    fn write_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        let retry = self.policy.retry_writes;
+        self.run(retry, |t| t.write_bulk(endpoint, buf, timeout))
+    }
+
+    fn read_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        self.run(true, |t| t.read_interrupt(endpoint, buf, timeout))
+    }
+
+    fn write_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        let retry = self.policy.retry_writes;
+        self.run(retry, |t| t.write_interrupt(endpoint, buf, timeout))
+    }
+
+    fn clear_halt(&mut self, endpoint: u8) -> Result<(), rusb::Error> {
+        self.run(true, |t| t.clear_halt(endpoint))
+    }
+
+    fn read_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        self.run(true, |t| {
+            t.read_control(request_type, request, value, index, buf, timeout)
+        })
+    }
+
+    fn write_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        let retry = self.policy.retry_writes;
+        self.run(retry, |t| {
+            t.write_control(request_type, request, value, index, buf, timeout)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    use crate::transfer::mock::MockTransport;
+
+    /// Scripted reopener: pops the next outcome per attempt.
+    #[derive(Default)]
+    struct ScriptedReopener {
+        outcomes: VecDeque<Option<MockTransport>>,
+        attempts: usize,
+    }
+
+    impl DeviceReopener for ScriptedReopener {
+        type Transport = MockTransport;
+
+        fn try_reopen(&mut self) -> Result<Option<MockTransport>, rusb::Error> {
+            self.attempts += 1;
+            Ok(self.outcomes.pop_front().unwrap_or(None))
+        }
+    }
+
+    fn fast_policy() -> ReacquirePolicy {
+        ReacquirePolicy::default()
+            .with_deadline(Duration::from_millis(20))
+            .with_poll_interval(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn test_disconnected_control_read_reopens_and_retries_once() {
+        let mut dead = MockTransport::new();
+        dead.control_read_results
+            .push_back(Err(rusb::Error::NoDevice));
+        // The reconnected device answers exactly one request; more than
+        // one retry would come back Timeout and fail the assertions.
+        let mut alive = MockTransport::new();
+        alive
+            .control_read_results
+            .push_back(Ok(vec![0x12, 0x01]));
+
+        let reopener = ScriptedReopener {
+            outcomes: VecDeque::from([Some(alive)]),
+            ..Default::default()
+        };
+        let mut handle = ReacquiringHandle::new(dead, reopener).with_policy(fast_policy());
+
+        let mut buf = [0u8; 2];
+        let n = handle
+            .read_control(0x80, 0x06, 0x0100, 0, &mut buf, Duration::from_millis(5))
+            .unwrap();
+        assert_eq!((n, buf), (2, [0x12, 0x01]));
+        assert_eq!(handle.reopens(), 1);
+    }
+
+    #[test]
+    fn test_reopen_waits_out_absence() {
+        let mut dead = MockTransport::new();
+        dead.read_results.push_back(Err(rusb::Error::NoDevice));
+        let mut alive = MockTransport::new();
+        alive.read_results.push_back(Ok(vec![0xaa]));
+
+        // Not present for the first two polls.
+        let reopener = ScriptedReopener {
+            outcomes: VecDeque::from([None, None, Some(alive)]),
+            ..Default::default()
+        };
+        let mut handle = ReacquiringHandle::new(dead, reopener).with_policy(fast_policy());
+
+        let mut buf = [0u8; 1];
+        handle
+            .read_bulk(0x81, &mut buf, Duration::from_millis(5))
+            .unwrap();
+        assert_eq!(buf[0], 0xaa);
+        assert_eq!(handle.reopens(), 1);
+    }
+
+    #[test]
+    fn test_write_not_retried_by_default() {
+        let mut dead = MockTransport::new();
+        dead.write_results.push_back(Err(rusb::Error::NoDevice));
+        let mut alive = MockTransport::new();
+        alive.write_results.push_back(Ok(4));
+        alive.read_results.push_back(Ok(vec![0x01]));
+
+        let reopener = ScriptedReopener {
+            outcomes: VecDeque::from([Some(alive)]),
+            ..Default::default()
+        };
+        let mut handle = ReacquiringHandle::new(dead, reopener).with_policy(fast_policy());
+
+        // The write surfaces the disconnect...
+        let err = handle
+            .write_bulk(0x01, b"data", Duration::from_millis(5))
+            .unwrap_err();
+        assert_eq!(err, rusb::Error::NoDevice);
+        // ...but the handle reacquired, so the session continues.
+        assert_eq!(handle.reopens(), 1);
+        let mut buf = [0u8; 1];
+        handle
+            .read_bulk(0x81, &mut buf, Duration::from_millis(5))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_write_retried_when_opted_in() {
+        let mut dead = MockTransport::new();
+        dead.write_results.push_back(Err(rusb::Error::NoDevice));
+        let mut alive = MockTransport::new();
+        alive.write_results.push_back(Ok(4));
+
+        let reopener = ScriptedReopener {
+            outcomes: VecDeque::from([Some(alive)]),
+            ..Default::default()
+        };
+        let mut handle = ReacquiringHandle::new(dead, reopener)
+            .with_policy(fast_policy().with_retry_writes(true));
+
+        let n = handle
+            .write_bulk(0x01, b"data", Duration::from_millis(5))
+            .unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(handle.reopens(), 1);
+    }
+
+    #[test]
+    fn test_device_that_never_returns_reads_as_no_device() {
+        let mut dead = MockTransport::new();
+        dead.read_results.push_back(Err(rusb::Error::NoDevice));
+
+        let reopener = ScriptedReopener::default();
+        let mut handle = ReacquiringHandle::new(dead, reopener).with_policy(fast_policy());
+
+        let mut buf = [0u8; 1];
+        let err = handle
+            .read_bulk(0x81, &mut buf, Duration::from_millis(5))
+            .unwrap_err();
+        assert_eq!(err, rusb::Error::NoDevice);
+        assert!(handle.reopener.attempts >= 2, "should have kept polling");
+        assert_eq!(handle.reopens(), 0);
+    }
+}
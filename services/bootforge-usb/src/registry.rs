@@ -0,0 +1,373 @@
+// BootForge USB - Device registry
+// Tracks known devices across enumeration snapshots and, when tamper
+// detection is enabled, flags descriptor changes that happen without a
+// disconnect in between.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::enumeration::{EnumerationReport, SkipReason, UsbDeviceInfo};
+use crate::events::{diff_descriptors, DeviceEvent, DeviceIdentity};
+
+/**
+ * Fingerprint over the identity-relevant descriptor content of a device.
+ * Volatile fields (bus number, address) are deliberately excluded so
+ * replug-induced renumbering does not trip tamper detection.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorFingerprint(String);
+
+impl DescriptorFingerprint {
+    pub fn of(info: &UsbDeviceInfo) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(info.vendor_id.to_le_bytes());
+        hasher.update(info.product_id.to_le_bytes());
+        hasher.update(info.descriptor.usb_version.0.to_le_bytes());
+        hasher.update(info.descriptor.device_version.0.to_le_bytes());
+        hasher.update([
+            info.descriptor.device_class,
+            info.descriptor.device_subclass,
+            info.descriptor.device_protocol,
+            info.descriptor.max_packet_size_0,
+            info.descriptor.num_configurations,
+        ]);
+        for field in [&info.manufacturer, &info.product, &info.serial_number] {
+            hasher.update([0u8]); // field separator
+            if let Some(s) = field {
+                hasher.update(s.as_bytes());
+            }
+        }
+        DescriptorFingerprint(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn as_hex(&self) -> &str {
+        &self.0
+    }
+}
+
+struct TrackedDevice {
+    info: UsbDeviceInfo,
+    fingerprint: DescriptorFingerprint,
+}
+
+/**
+ * Registry of currently-present devices, fed with enumeration snapshots.
+ */
+#[derive(Default)]
+pub struct DeviceRegistry {
+    devices: HashMap<DeviceIdentity, TrackedDevice>,
+    tamper_detection: bool,
+}
+
+impl DeviceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable descriptor-fingerprint tamper detection.
+    pub fn with_tamper_detection(mut self, enabled: bool) -> Self {
+        self.tamper_detection = enabled;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /**
+     * Reconcile a fresh enumeration snapshot against the tracked state,
+     * returning the events it implies. A descriptor fingerprint change
+     * for an identity that never disconnected is reported as
+     * DescriptorChanged rather than a plain Changed event.
+     */
+    pub fn observe_snapshot(&mut self, snapshot: &[UsbDeviceInfo]) -> Vec<DeviceEvent> {
+        let mut events = Vec::new();
+        let mut seen: Vec<DeviceIdentity> = Vec::with_capacity(snapshot.len());
+
+        for info in snapshot {
+            let identity = DeviceIdentity::of(info);
+            seen.push(identity.clone());
+            let fingerprint = DescriptorFingerprint::of(info);
+
+            match self.devices.get_mut(&identity) {
+                None => {
+                    events.push(DeviceEvent::Connected(info.clone()));
+                    self.devices.insert(
+                        identity,
+                        TrackedDevice {
+                            info: info.clone(),
+                            fingerprint,
+                        },
+                    );
+                }
+                Some(tracked) => {
+                    if self.tamper_detection && tracked.fingerprint != fingerprint {
+                        let diff = diff_descriptors(&tracked.info, info);
+                        events.push(DeviceEvent::DescriptorChanged {
+                            identity: identity.clone(),
+                            before: Box::new(tracked.info.clone()),
+                            after: Box::new(info.clone()),
+                            diff,
+                        });
+                    } else if tracked.info != *info {
+                        events.push(DeviceEvent::Changed {
+                            identity: identity.clone(),
+                            before: Box::new(tracked.info.clone()),
+                            after: Box::new(info.clone()),
+                        });
+                    }
+                    tracked.info = info.clone();
+                    tracked.fingerprint = fingerprint;
+                }
+            }
+        }
+
+        let gone: Vec<DeviceIdentity> = self
+            .devices
+            .keys()
+            .filter(|id| !seen.contains(id))
+            .cloned()
+            .collect();
+        for identity in gone {
+            self.devices.remove(&identity);
+            events.push(DeviceEvent::Disconnected(identity));
+        }
+
+        events
+    }
+}
+
+/**
+ * A (bus, address) slot that keeps appearing in the device list but
+ * vanishes before it can be probed - usually a failing hub port or
+ * cable.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhantomDeviceDiagnostic {
+    pub bus_number: u8,
+    pub address: u8,
+    pub consecutive_failures: u32,
+}
+
+/**
+ * Tracks probe-disconnect skips across enumeration passes and flags
+ * slots that fail `threshold` times in a row.
+ */
+pub struct PhantomDeviceTracker {
+    threshold: u32,
+    counts: HashMap<(u8, u8), u32>,
+}
+
+impl PhantomDeviceTracker {
+    pub fn new(threshold: u32) -> Self {
+        PhantomDeviceTracker {
+            threshold: threshold.max(1),
+            counts: HashMap::new(),
+        }
+    }
+
+    /**
+     * Feed one enumeration report; returns diagnostics for slots that
+     * just crossed the consecutive-failure threshold.
+     */
+    pub fn observe_report(&mut self, report: &EnumerationReport) -> Vec<PhantomDeviceDiagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let skipped_slots: Vec<(u8, u8)> = report
+            .skipped
+            .iter()
+            .filter(|s| s.error == SkipReason::Disconnected)
+            .map(|s| (s.bus_number, s.address))
+            .collect();
+
+        // A successful probe (or the slot simply not appearing) resets it.
+        self.counts.retain(|slot, _| skipped_slots.contains(slot));
+
+        for slot in skipped_slots {
+            let count = self.counts.entry(slot).or_insert(0);
+            *count += 1;
+            if *count == self.threshold {
+                log::warn!(
+                    "phantom device at bus {} address {}: listed but unreachable {} times in a row; inspect the hub/cable",
+                    slot.0,
+                    slot.1,
+                    count
+                );
+                diagnostics.push(PhantomDeviceDiagnostic {
+                    bus_number: slot.0,
+                    address: slot.1,
+                    consecutive_failures: *count,
+                });
+            }
+        }
+
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::version::BcdVersion;
+
+    fn keyboard(serial: &str) -> UsbDeviceInfo {
+        UsbDeviceInfo {
+            bus_number: 1,
+            address: 4,
+            vendor_id: 0x046d,
+            product_id: 0xc31c,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0200),
+                device_version: BcdVersion(0x6401),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 8,
+                num_configurations: 1,
+            },
+            manufacturer: Some("Logitech".to_string()),
+            product: Some("USB Keyboard".to_string()),
+            serial_number: Some(serial.to_string()),
+            port_path: None,
+            speed: None,
+            tags: Vec::new(),
+            active_config: None,
+            usb_ids: None,
+            container_id: None,
+        }
+    }
+
+    #[test]
+    fn test_connect_disconnect_lifecycle() {
+        let mut registry = DeviceRegistry::new();
+        let events = registry.observe_snapshot(&[keyboard("K1")]);
+        assert!(matches!(events.as_slice(), [DeviceEvent::Connected(_)]));
+
+        let events = registry.observe_snapshot(&[]);
+        assert!(matches!(events.as_slice(), [DeviceEvent::Disconnected(_)]));
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_in_place_descriptor_change_detected() {
+        let mut registry = DeviceRegistry::new().with_tamper_detection(true);
+        registry.observe_snapshot(&[keyboard("K1")]);
+
+        // Same identity, but the "keyboard" now reports an extra config
+        // and a different class triple - no disconnect in between.
+        let mut tampered = keyboard("K1");
+        tampered.descriptor.num_configurations = 2;
+        tampered.descriptor.device_class = 0xef;
+
+        let events = registry.observe_snapshot(&[tampered]);
+        match events.as_slice() {
+            [DeviceEvent::DescriptorChanged { diff, .. }] => {
+                let fields: Vec<&str> =
+                    diff.changed_fields.iter().map(|f| f.field.as_str()).collect();
+                assert!(fields.contains(&"num_configurations"));
+                assert!(fields.contains(&"device_class"));
+            }
+            other => panic!("expected DescriptorChanged, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_volatile_fields_do_not_false_positive() {
+        let mut registry = DeviceRegistry::new().with_tamper_detection(true);
+        registry.observe_snapshot(&[keyboard("K1")]);
+
+        // Same device renumbered to a different address: not tampering.
+        let mut moved = keyboard("K1");
+        moved.bus_number = 2;
+        moved.address = 9;
+
+        let events = registry.observe_snapshot(&[moved]);
+        assert!(
+            matches!(events.as_slice(), [DeviceEvent::Changed { .. }]),
+            "expected plain Changed, got {:?}",
+            events
+        );
+    }
+
+    #[test]
+    fn test_tamper_detection_off_reports_plain_change() {
+        let mut registry = DeviceRegistry::new();
+        registry.observe_snapshot(&[keyboard("K1")]);
+
+        let mut tampered = keyboard("K1");
+        tampered.descriptor.num_configurations = 2;
+
+        let events = registry.observe_snapshot(&[tampered]);
+        assert!(matches!(events.as_slice(), [DeviceEvent::Changed { .. }]));
+    }
+
+    fn skip(bus: u8, addr: u8) -> crate::enumeration::SkippedDevice {
+        crate::enumeration::SkippedDevice {
+            bus_number: bus,
+            address: addr,
+            stage: crate::enumeration::ProbeStage::Probe,
+            error: SkipReason::Disconnected,
+        }
+    }
+
+    #[test]
+    fn test_phantom_tracker_fires_at_threshold_once() {
+        let mut tracker = PhantomDeviceTracker::new(3);
+        let report = EnumerationReport {
+            devices: Vec::new(),
+            skipped: vec![skip(1, 7)],
+            ..Default::default()
+        };
+
+        assert!(tracker.observe_report(&report).is_empty());
+        assert!(tracker.observe_report(&report).is_empty());
+        let diags = tracker.observe_report(&report);
+        assert_eq!(
+            diags,
+            vec![PhantomDeviceDiagnostic {
+                bus_number: 1,
+                address: 7,
+                consecutive_failures: 3,
+            }]
+        );
+        // Already reported; do not spam on subsequent passes.
+        assert!(tracker.observe_report(&report).is_empty());
+    }
+
+    #[test]
+    fn test_phantom_tracker_resets_on_clean_pass() {
+        let mut tracker = PhantomDeviceTracker::new(2);
+        let flaky = EnumerationReport {
+            devices: Vec::new(),
+            skipped: vec![skip(1, 7)],
+            ..Default::default()
+        };
+        let clean = EnumerationReport::default();
+
+        assert!(tracker.observe_report(&flaky).is_empty());
+        assert!(tracker.observe_report(&clean).is_empty());
+        // Count restarted; one more flaky pass is below threshold again.
+        assert!(tracker.observe_report(&flaky).is_empty());
+    }
+
+    #[test]
+    fn test_phantom_tracker_ignores_non_disconnect_skips() {
+        let mut tracker = PhantomDeviceTracker::new(1);
+        let report = EnumerationReport {
+            devices: Vec::new(),
+            skipped: vec![crate::enumeration::SkippedDevice {
+                bus_number: 1,
+                address: 7,
+                stage: crate::enumeration::ProbeStage::Probe,
+                error: SkipReason::Other("access denied".to_string()),
+            }],
+            ..Default::default()
+        };
+        assert!(tracker.observe_report(&report).is_empty());
+    }
+}
@@ -0,0 +1,537 @@
+// BootForge USB - Support report envelope
+// The envelope support tooling ships off customer machines: a versioned
+// wrapper around the device and hub lists with capture metadata. Unlike
+// `snapshot` (the schema-checked diffable device list) this format is
+// deliberately tolerant: unknown fields are ignored and fields added in
+// later versions default, so any tool can read any report no older than
+// its own schema knowledge.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::enumeration::{FallbackEnumerator, UsbDeviceRecord};
+use crate::error::UsbError;
+use crate::ports::{enumerate_hubs, UsbHub};
+
+/// Version of the support report format. Bump when a reader upgrade is
+/// required; additive fields with serde defaults do not need one.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+fn default_schema_version() -> u32 {
+    1
+}
+
+/**
+ * A point-in-time support capture: the sysfs device records, the hub
+ * list, and enough metadata to interpret them off-host.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsbSnapshot {
+    /// `REPORT_SCHEMA_VERSION` at capture time; reports written before
+    /// the field existed read as version 1.
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    pub captured_at: DateTime<Utc>,
+    /// `std::env::consts::OS` of the capturing host.
+    #[serde(default)]
+    pub host_os: String,
+    #[serde(default)]
+    pub devices: Vec<UsbDeviceRecord>,
+    #[serde(default)]
+    pub hubs: Vec<UsbHub>,
+}
+
+impl UsbSnapshot {
+    /// Wrap already-enumerated lists under the current version, stamped
+    /// now.
+    pub fn new(devices: Vec<UsbDeviceRecord>, hubs: Vec<UsbHub>) -> Self {
+        UsbSnapshot {
+            schema_version: REPORT_SCHEMA_VERSION,
+            captured_at: Utc::now(),
+            host_os: std::env::consts::OS.to_string(),
+            devices,
+            hubs,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, UsbError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| UsbError::Internal(format!("serializing report: {}", e)))
+    }
+
+    /**
+     * Compare this capture against a later one.
+     *
+     * Devices pair up by the most stable identity available: serial
+     * number first, then port path, then bare VID/PID - each pairing is
+     * one-to-one, so two identical serial-less devices are never
+     * conflated. A pair whose port changed reports as moved rather
+     * than removed-plus-added.
+     */
+    pub fn diff(&self, other: &UsbSnapshot) -> SnapshotDiff {
+        let mut after: Vec<Option<&UsbDeviceRecord>> = other.devices.iter().map(Some).collect();
+        let mut diff = SnapshotDiff::default();
+        let mut pairs: Vec<(&UsbDeviceRecord, &UsbDeviceRecord)> = Vec::new();
+
+        type MatchPass<'p> = &'p dyn Fn(&UsbDeviceRecord, &UsbDeviceRecord) -> bool;
+        // Each pass claims matches the previous one could not, from
+        // strongest identity to weakest.
+        let passes: [MatchPass; 3] = [
+            &|b, a| {
+                same_ids(b, a)
+                    && b.serial_number.is_some()
+                    && b.serial_number == a.serial_number
+            },
+            &|b, a| {
+                same_ids(b, a)
+                    && a.serial_number.is_none()
+                    && record_port(b).is_some()
+                    && record_port(b) == record_port(a)
+            },
+            &|b, a| same_ids(b, a) && a.serial_number.is_none(),
+        ];
+
+        let mut unmatched: Vec<&UsbDeviceRecord> = self.devices.iter().collect();
+        for pass in passes {
+            unmatched.retain(|before| {
+                let slot = after
+                    .iter_mut()
+                    .find(|slot| slot.is_some_and(|a| pass(before, a)));
+                match slot {
+                    Some(slot) => {
+                        pairs.push((before, slot.take().expect("slot checked")));
+                        false
+                    }
+                    None => true,
+                }
+            });
+        }
+
+        diff.removed = unmatched.into_iter().cloned().collect();
+        diff.added = after.into_iter().flatten().cloned().collect();
+
+        for (before, current) in pairs {
+            let from = record_port(before);
+            let to = record_port(current);
+            if from != to {
+                diff.moved.push(MovedDevice {
+                    record: current.clone(),
+                    from_port: from.unwrap_or_default(),
+                    to_port: to.unwrap_or_default(),
+                });
+                continue;
+            }
+            let changes = field_changes(before, current);
+            if !changes.is_empty() {
+                diff.changed.push(ChangedDevice {
+                    before: before.clone(),
+                    after: current.clone(),
+                    changes,
+                });
+            }
+        }
+
+        diff
+    }
+
+    /**
+     * Parse a report, tolerating unknown fields and missing fields from
+     * older versions. A report from a *newer* schema than this build
+     * knows is refused rather than partially decoded.
+     */
+    pub fn from_json(json: &str) -> Result<Self, UsbError> {
+        let snapshot: UsbSnapshot = serde_json::from_str(json)
+            .map_err(|e| UsbError::Parse(format!("bad support report: {}", e)))?;
+        if snapshot.schema_version > REPORT_SCHEMA_VERSION {
+            return Err(UsbError::Unsupported(format!(
+                "report schema version {} is newer than this build's {}",
+                snapshot.schema_version, REPORT_SCHEMA_VERSION
+            )));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// One field whose value differed between captures, both sides
+/// rendered as display strings so the diff serializes flat.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// A device present in both captures with differing fields.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChangedDevice {
+    pub before: UsbDeviceRecord,
+    pub after: UsbDeviceRecord,
+    pub changes: Vec<FieldChange>,
+}
+
+/// A device that re-appeared on a different port.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MovedDevice {
+    /// The record as captured after the move.
+    pub record: UsbDeviceRecord,
+    pub from_port: String,
+    pub to_port: String,
+}
+
+/**
+ * What changed between two captures. Serializable for tooling; the
+ * `Display` form is the human summary support reads first.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SnapshotDiff {
+    pub added: Vec<UsbDeviceRecord>,
+    pub removed: Vec<UsbDeviceRecord>,
+    pub moved: Vec<MovedDevice>,
+    pub changed: Vec<ChangedDevice>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+impl std::fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+        for record in &self.added {
+            write!(f, "Added: {}", describe(record))?;
+            match record_port(record) {
+                Some(port) => writeln!(f, " at port {}", port)?,
+                None => writeln!(f)?,
+            }
+        }
+        for record in &self.removed {
+            write!(f, "Removed: {}", describe(record))?;
+            match record_port(record) {
+                Some(port) => writeln!(f, " from port {}", port)?,
+                None => writeln!(f)?,
+            }
+        }
+        for moved in &self.moved {
+            writeln!(
+                f,
+                "Moved: {} {} -> {}",
+                describe(&moved.record),
+                moved.from_port,
+                moved.to_port
+            )?;
+        }
+        for changed in &self.changed {
+            write!(f, "Changed: {}:", describe(&changed.after))?;
+            for change in &changed.changes {
+                write!(
+                    f,
+                    " {} {:?} -> {:?}",
+                    change.field, change.before, change.after
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn same_ids(a: &UsbDeviceRecord, b: &UsbDeviceRecord) -> bool {
+    a.vendor_id == b.vendor_id && a.product_id == b.product_id
+}
+
+/// The port-path-shaped sysfs directory name, e.g. "2-1.4" (or "usb2"
+/// for a root hub).
+fn record_port(record: &UsbDeviceRecord) -> Option<String> {
+    record
+        .sysfs_path
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// "05ac:12a8 iPhone (S/N abc123)", degrading gracefully when strings
+/// are missing.
+fn describe(record: &UsbDeviceRecord) -> String {
+    let mut out = format!("{:04x}:{:04x}", record.vendor_id, record.product_id);
+    if let Some(product) = &record.product {
+        out.push(' ');
+        out.push_str(product);
+    }
+    if let Some(serial) = &record.serial_number {
+        out.push_str(&format!(" (S/N {})", serial));
+    }
+    out
+}
+
+fn field_changes(before: &UsbDeviceRecord, after: &UsbDeviceRecord) -> Vec<FieldChange> {
+    fn push_opt(out: &mut Vec<FieldChange>, field: &str, a: &Option<String>, b: &Option<String>) {
+        if a != b {
+            out.push(FieldChange {
+                field: field.to_string(),
+                before: a.clone().unwrap_or_default(),
+                after: b.clone().unwrap_or_default(),
+            });
+        }
+    }
+    let mut out = Vec::new();
+    push_opt(&mut out, "manufacturer", &before.manufacturer, &after.manufacturer);
+    push_opt(&mut out, "product", &before.product, &after.product);
+    push_opt(
+        &mut out,
+        "serial_number",
+        &before.serial_number,
+        &after.serial_number,
+    );
+    if before.descriptor.device_version != after.descriptor.device_version {
+        out.push(FieldChange {
+            field: "device_version".to_string(),
+            before: before.descriptor.device_version.to_string(),
+            after: after.descriptor.device_version.to_string(),
+        });
+    }
+    if before.interfaces != after.interfaces {
+        out.push(FieldChange {
+            field: "interfaces".to_string(),
+            before: format!("{} interface(s)", before.interfaces.len()),
+            after: format!("{} interface(s)", after.interfaces.len()),
+        });
+    }
+    out
+}
+
+/**
+ * Capture a support report from this host, best-effort: a list that
+ * cannot be read (permissions, no sysfs, no USB access) comes back
+ * empty rather than failing the whole capture, since a partial report
+ * is still useful to support.
+ */
+pub fn capture_snapshot() -> UsbSnapshot {
+    let devices = FallbackEnumerator::new().enumerate().unwrap_or_default();
+    let hubs = enumerate_hubs().unwrap_or_default();
+    UsbSnapshot::new(devices, hubs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::enumeration::UsbDescriptorSummary;
+    use crate::ports::{PowerSwitching, UsbPort};
+    use crate::version::BcdVersion;
+
+    fn sample() -> UsbSnapshot {
+        UsbSnapshot::new(
+            vec![UsbDeviceRecord {
+                bus_number: 2,
+                device_number: 5,
+                vendor_id: 0x18d1,
+                product_id: 0x4ee7,
+                descriptor: UsbDescriptorSummary {
+                    usb_version: BcdVersion(0x0210),
+                    device_version: BcdVersion(0x0440),
+                    device_class: 0,
+                    device_subclass: 0,
+                    device_protocol: 0,
+                    max_packet_size_0: 64,
+                    num_configurations: 1,
+                },
+                manufacturer: Some("Google Inc.".to_string()),
+                product: Some("Pixel 7".to_string()),
+                serial_number: Some("1A2B3C4D".to_string()),
+                sysfs_path: "/sys/bus/usb/devices/2-1.4".to_string(),
+                speed: None,
+                interfaces: Vec::new(),
+            }],
+            vec![UsbHub {
+                bus_number: 2,
+                address: 2,
+                vendor_id: 0x2109,
+                product_id: 0x0817,
+                num_ports: 4,
+                ports: vec![UsbPort {
+                    number: 1,
+                    path: "2-1.1".to_string(),
+                }],
+                power_switching: Some(PowerSwitching::PerPort),
+                compound: Some(false),
+            }],
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let snapshot = sample();
+        let rebuilt = UsbSnapshot::from_json(&snapshot.to_json().unwrap()).unwrap();
+        assert_eq!(rebuilt, snapshot);
+        assert_eq!(rebuilt.schema_version, REPORT_SCHEMA_VERSION);
+        assert_eq!(rebuilt.host_os, std::env::consts::OS);
+    }
+
+    #[test]
+    fn test_v1_fixture_still_parses() {
+        // A report as the first shipping version wrote it: no host_os,
+        // no hubs, plus a field this build has never heard of. Committed
+        // here verbatim; do not regenerate it.
+        let fixture = r#"{
+            "schema_version": 1,
+            "captured_at": "2026-03-14T09:26:53Z",
+            "support_case": "CS-4471",
+            "devices": [{
+                "bus_number": 1,
+                "device_number": 9,
+                "vendor_id": 1921,
+                "product_id": 21891,
+                "descriptor": {
+                    "usb_version": "2.10",
+                    "device_version": "1.00",
+                    "device_class": 0,
+                    "device_subclass": 0,
+                    "device_protocol": 0,
+                    "max_packet_size_0": 64,
+                    "num_configurations": 1
+                },
+                "manufacturer": "SanDisk",
+                "product": "Ultra Fit",
+                "serial_number": null,
+                "sysfs_path": "/sys/bus/usb/devices/1-2"
+            }]
+        }"#;
+        let snapshot = UsbSnapshot::from_json(fixture).unwrap();
+        assert_eq!(snapshot.schema_version, 1);
+        assert_eq!(snapshot.host_os, "");
+        assert!(snapshot.hubs.is_empty());
+        assert_eq!(snapshot.devices[0].vendor_id, 0x0781);
+        assert_eq!(snapshot.devices[0].interfaces.len(), 0);
+    }
+
+    /// A record with identity fields only; sysfs path derives from the
+    /// port.
+    fn record(
+        vendor_id: u16,
+        product_id: u16,
+        serial: Option<&str>,
+        port: &str,
+        product: &str,
+    ) -> UsbDeviceRecord {
+        UsbDeviceRecord {
+            bus_number: 1,
+            device_number: 2,
+            vendor_id,
+            product_id,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0200),
+                device_version: BcdVersion(0x0100),
+                device_class: 0,
+                device_subclass: 0,
+                device_protocol: 0,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: None,
+            product: Some(product.to_string()),
+            serial_number: serial.map(str::to_string),
+            sysfs_path: format!("/sys/bus/usb/devices/{}", port),
+            speed: None,
+            interfaces: Vec::new(),
+        }
+    }
+
+    fn snapshot_of(devices: Vec<UsbDeviceRecord>) -> UsbSnapshot {
+        UsbSnapshot::new(devices, Vec::new())
+    }
+
+    #[test]
+    fn test_diff_added_removed_and_display() {
+        let before = snapshot_of(vec![record(
+            0x05ac,
+            0x12a8,
+            Some("F2LX"),
+            "1-2.3",
+            "iPhone",
+        )]);
+        let after = snapshot_of(vec![record(0x2109, 0x0817, None, "1-2", "USB2.0 Hub")]);
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed.len(), 1);
+        assert!(diff.moved.is_empty() && diff.changed.is_empty());
+
+        let rendered = diff.to_string();
+        assert!(rendered.contains("Added: 2109:0817 USB2.0 Hub at port 1-2"));
+        assert!(rendered.contains("Removed: 05ac:12a8 iPhone (S/N F2LX) from port 1-2.3"));
+
+        // The diff itself round-trips as JSON for tooling.
+        let json = serde_json::to_string(&diff).unwrap();
+        assert_eq!(serde_json::from_str::<SnapshotDiff>(&json).unwrap(), diff);
+    }
+
+    #[test]
+    fn test_diff_moved_port_and_field_changes() {
+        let before = snapshot_of(vec![
+        record(0x0781, 0x5583, Some("4C53"), "1-2", "Ultra Fit"),
+            record(0x18d1, 0x4ee7, Some("PIXEL"), "1-3", "Pixel 7"),
+        ]);
+        let after = snapshot_of(vec![
+            // Same stick, different port: moved, not removed+added.
+            record(0x0781, 0x5583, Some("4C53"), "1-4", "Ultra Fit"),
+            // Same phone, same port, new product string.
+            record(0x18d1, 0x4ee7, Some("PIXEL"), "1-3", "Pixel 7 (fastboot)"),
+        ]);
+
+        let diff = before.diff(&after);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(diff.moved[0].from_port, "1-2");
+        assert_eq!(diff.moved[0].to_port, "1-4");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].changes.len(), 1);
+        assert_eq!(diff.changed[0].changes[0].field, "product");
+
+        let rendered = diff.to_string();
+        assert!(rendered.contains("Moved: 0781:5583 Ultra Fit (S/N 4C53) 1-2 -> 1-4"));
+        assert!(rendered.contains("product \"Pixel 7\" -> \"Pixel 7 (fastboot)\""));
+    }
+
+    #[test]
+    fn test_diff_does_not_conflate_serial_less_twins() {
+        // Two identical serial-less hubs; one unplugged.
+        let before = snapshot_of(vec![
+            record(0x2109, 0x0817, None, "1-1", "Hub"),
+            record(0x2109, 0x0817, None, "1-2", "Hub"),
+        ]);
+        let after = snapshot_of(vec![record(0x2109, 0x0817, None, "1-2", "Hub")]);
+
+        let diff = before.diff(&after);
+        // Port identity pins the survivor to 1-2; exactly one removal.
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(record_port(&diff.removed[0]).as_deref(), Some("1-1"));
+        assert!(diff.added.is_empty() && diff.moved.is_empty() && diff.changed.is_empty());
+
+        // And both present: a one-to-one pairing, nothing reported.
+        let unchanged = before.diff(&before);
+        assert!(unchanged.is_empty());
+        assert_eq!(unchanged.to_string(), "No changes.\n");
+    }
+
+    #[test]
+    fn test_missing_version_defaults_and_newer_is_refused() {
+        let unversioned = r#"{"captured_at": "2026-03-14T09:26:53Z"}"#;
+        let snapshot = UsbSnapshot::from_json(unversioned).unwrap();
+        assert_eq!(snapshot.schema_version, 1);
+        assert!(snapshot.devices.is_empty());
+
+        let future = r#"{"schema_version": 99, "captured_at": "2026-03-14T09:26:53Z"}"#;
+        assert!(matches!(
+            UsbSnapshot::from_json(future),
+            Err(UsbError::Unsupported(_))
+        ));
+    }
+}
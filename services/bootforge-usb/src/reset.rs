@@ -0,0 +1,217 @@
+// BootForge USB - Port reset and post-reset reopening
+// A device stuck in a reset loop or ignoring transfers often recovers
+// from a USB port reset - but libusb may answer the reset with
+// NOT_FOUND, meaning the device re-enumerated and the old handle is
+// dead. reset_and_reopen folds the whole dance (reset, wait for the
+// device to come back, reopen) into one call; the wait half runs
+// against a `SnapshotSource` so it is testable without hardware.
+
+use std::time::Instant;
+
+use crate::canonical::CanonicalId;
+use crate::enumeration::{enumerate_libusb, UsbDeviceInfo};
+use crate::error::UsbError;
+use crate::open::OpenOptions;
+use crate::reacquire::ReacquirePolicy;
+use crate::watch::SnapshotSource;
+
+/**
+ * Perform a USB port reset on an open handle (libusb_reset_device).
+ *
+ * `Ok(())` means the device kept its address and the handle is still
+ * usable. `Err(Disconnected)` means the reset forced a re-enumeration:
+ * the handle is dead and the caller must relocate and reopen the
+ * device - `reset_and_reopen` does exactly that.
+ */
+pub fn reset_device<C: rusb::UsbContext>(
+    handle: &mut rusb::DeviceHandle<C>,
+) -> Result<(), UsbError> {
+    match handle.reset() {
+        Ok(()) => Ok(()),
+        // libusb reports NOT_FOUND when the device came back under a
+        // new address; NO_DEVICE when it did not come back at all. In
+        // both cases this handle no longer refers to anything.
+        Err(rusb::Error::NotFound) | Err(rusb::Error::NoDevice) => Err(UsbError::Disconnected),
+        Err(rusb::Error::Access) => {
+            Err(UsbError::PermissionDenied("resetting device".to_string()))
+        }
+        Err(e) => Err(UsbError::Libusb(e)),
+    }
+}
+
+/**
+ * Poll `source` until a device matching `info` re-appears or the
+ * policy's deadline runs out (which reads as `NotFound`).
+ *
+ * Matching follows the open path's logic: same VID/PID, disambiguated
+ * by serial number when the original had one, else by port path - a
+ * replugged device keeps its port even when its address changes.
+ */
+pub fn wait_for_return(
+    info: &UsbDeviceInfo,
+    policy: &ReacquirePolicy,
+    source: &mut dyn SnapshotSource,
+) -> Result<UsbDeviceInfo, UsbError> {
+    let deadline = Instant::now() + policy.deadline;
+    loop {
+        if let Some(found) = source
+            .snapshot()?
+            .into_iter()
+            .find(|candidate| matches_returned(info, candidate))
+        {
+            return Ok(found);
+        }
+        if Instant::now() >= deadline {
+            return Err(UsbError::NotFound(CanonicalId::of(info).to_string()));
+        }
+        std::thread::sleep(policy.poll_interval);
+    }
+}
+
+/// Whether `candidate` is plausibly `original` back from a reset.
+fn matches_returned(original: &UsbDeviceInfo, candidate: &UsbDeviceInfo) -> bool {
+    if (candidate.vendor_id, candidate.product_id) != (original.vendor_id, original.product_id) {
+        return false;
+    }
+    match (&original.serial_number, &candidate.serial_number) {
+        (Some(a), Some(b)) => a == b,
+        // The original had a serial; a candidate without one is some
+        // other unit (or not readable yet - the next poll will see it).
+        (Some(_), None) => false,
+        (None, _) => match (&original.port_path, &candidate.port_path) {
+            (Some(a), Some(b)) => a == b,
+            // No discriminator left; same model is the best we can do.
+            _ => true,
+        },
+    }
+}
+
+/**
+ * Reset the device behind `info` and hand back a usable handle: the
+ * original one when the reset succeeded in place, a fresh one after
+ * waiting out a forced re-enumeration. A device that never returns
+ * within the default `ReacquirePolicy` deadline reads as `NotFound`.
+ */
+pub fn reset_and_reopen(info: &UsbDeviceInfo) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+    reset_and_reopen_with(info, &OpenOptions::default(), &ReacquirePolicy::default())
+}
+
+/**
+ * As `reset_and_reopen`, with explicit open options and wait bounds.
+ */
+pub fn reset_and_reopen_with(
+    info: &UsbDeviceInfo,
+    options: &OpenOptions,
+    policy: &ReacquirePolicy,
+) -> Result<rusb::DeviceHandle<rusb::Context>, UsbError> {
+    let mut handle = info.open_with(options)?;
+    match reset_device(&mut handle) {
+        Ok(()) => Ok(handle),
+        Err(UsbError::Disconnected) => {
+            // The handle is dead; close it before polling so the device
+            // does not come back to a claimed-by-a-corpse interface.
+            drop(handle);
+            let returned = wait_for_return(info, policy, &mut LibusbSource)?;
+            returned.open_with(options)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Live enumeration as the snapshot source for the non-test path.
+struct LibusbSource;
+
+impl SnapshotSource for LibusbSource {
+    fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+        enumerate_libusb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    /// Scripted snapshot source: pops the next snapshot per poll, then
+    /// keeps answering empty.
+    #[derive(Default)]
+    struct ScriptedSource {
+        snapshots: VecDeque<Vec<UsbDeviceInfo>>,
+        polls: usize,
+    }
+
+    impl SnapshotSource for ScriptedSource {
+        fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+            self.polls += 1;
+            Ok(self.snapshots.pop_front().unwrap_or_default())
+        }
+    }
+
+    fn fast_policy() -> ReacquirePolicy {
+        ReacquirePolicy::default()
+            .with_deadline(Duration::from_millis(20))
+            .with_poll_interval(Duration::from_millis(1))
+    }
+
+    fn pixel(serial: &str) -> UsbDeviceInfo {
+        UsbDeviceInfo::builder(0x18d1, 0x4ee7).serial(serial).build()
+    }
+
+    #[test]
+    fn test_wait_matches_serial_across_address_change() {
+        let original = pixel("SERIAL1");
+        let mut back = pixel("SERIAL1");
+        back.bus_number = 3;
+        back.address = 9;
+
+        let mut source = ScriptedSource {
+            snapshots: VecDeque::from([vec![], vec![back.clone()]]),
+            ..Default::default()
+        };
+        let found = wait_for_return(&original, &fast_policy(), &mut source).unwrap();
+        assert_eq!(found, back);
+        assert_eq!(source.polls, 2);
+    }
+
+    #[test]
+    fn test_wait_skips_same_model_with_other_serial() {
+        let original = pixel("SERIAL1");
+        let mut source = ScriptedSource {
+            snapshots: VecDeque::from([vec![pixel("SERIAL2"), pixel("SERIAL1")]]),
+            ..Default::default()
+        };
+        let found = wait_for_return(&original, &fast_policy(), &mut source).unwrap();
+        assert_eq!(found.serial_number.as_deref(), Some("SERIAL1"));
+    }
+
+    #[test]
+    fn test_wait_falls_back_to_port_path_without_serial() {
+        let original = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .port_path("3-1.4")
+            .build();
+        let elsewhere = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .port_path("3-1.2")
+            .build();
+        let same_port = UsbDeviceInfo::builder(0x18d1, 0x4ee7)
+            .port_path("3-1.4")
+            .serial("LATE-SERIAL")
+            .build();
+
+        let mut source = ScriptedSource {
+            snapshots: VecDeque::from([vec![elsewhere, same_port.clone()]]),
+            ..Default::default()
+        };
+        let found = wait_for_return(&original, &fast_policy(), &mut source).unwrap();
+        assert_eq!(found, same_port);
+    }
+
+    #[test]
+    fn test_wait_times_out_as_not_found() {
+        let original = pixel("SERIAL1");
+        let mut source = ScriptedSource::default();
+        let err = wait_for_return(&original, &fast_policy(), &mut source).unwrap_err();
+        assert!(matches!(err, UsbError::NotFound(id) if id.contains("SERIAL1")));
+        assert!(source.polls >= 2, "should have kept polling");
+    }
+}
@@ -0,0 +1,300 @@
+// BootForge USB - Snapshot format
+// The on-disk JSON form of an enumerated device list, versioned and
+// described by a committed JSON Schema. Snapshots outlive the process
+// that wrote them, so the shape is a contract: the drift test at the
+// bottom fails whenever the serialized shape and the committed schema
+// disagree, forcing schema updates (and a version bump) to land in the
+// same change as the code that needs them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::enumeration::UsbDeviceInfo;
+
+/// Version of the snapshot wire format. Bump on any change to the
+/// serialized shape of `Snapshot` or the structures it embeds, together
+/// with the `const` in schema/snapshot.schema.json.
+///
+/// v2: added `usb_ids` (names from the usb.ids database) to devices.
+/// v3: added `speed` (negotiated link speed) to devices.
+/// v4: added `container_id` (BOS Container ID) to devices.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 4;
+
+/// The committed schema, embedded so consumers do not need the source
+/// tree at run time.
+const SCHEMA_JSON: &str = include_str!("../schema/snapshot.schema.json");
+
+/**
+ * A point-in-time capture of the device list, suitable for writing to
+ * disk and diffing across runs or hosts.
+ *
+ * Older snapshots deserialize under newer code as long as the schema
+ * version has not been bumped: fields added without a bump carry serde
+ * defaults (see `UsbDeviceInfo::active_config`). A snapshot with a
+ * higher `schema_version` than `SNAPSHOT_SCHEMA_VERSION` should be
+ * treated as unreadable rather than partially decoded.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    /// `SNAPSHOT_SCHEMA_VERSION` at capture time.
+    pub schema_version: u32,
+    pub captured_at: DateTime<Utc>,
+    pub devices: Vec<UsbDeviceInfo>,
+}
+
+impl Snapshot {
+    /// Capture `devices` under the current schema version, stamped now.
+    pub fn new(devices: Vec<UsbDeviceInfo>) -> Self {
+        Snapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            captured_at: Utc::now(),
+            devices,
+        }
+    }
+}
+
+/**
+ * The committed JSON Schema (draft-07) describing the snapshot format,
+ * parsed from schema/snapshot.schema.json.
+ */
+pub fn snapshot_schema() -> Value {
+    // The schema is compiled in; a parse failure is a build defect the
+    // tests below catch, not a runtime condition.
+    serde_json::from_str(SCHEMA_JSON).expect("committed snapshot schema is valid JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    use crate::enumeration::{ConfigInfo, InterfaceInfo, UsbDescriptorSummary};
+    use crate::strings::MALFORMED_STRINGS_TAG;
+    use crate::topology::{EndpointInfo, EndpointKind, Speed};
+    use crate::version::BcdVersion;
+
+    /// Every optional field Some and every list non-empty, so the
+    /// serialized form exercises the full schema surface.
+    fn fully_populated() -> Snapshot {
+        Snapshot::new(vec![UsbDeviceInfo {
+            bus_number: 3,
+            address: 7,
+            vendor_id: 0x18d1,
+            product_id: 0x4ee7,
+            descriptor: UsbDescriptorSummary {
+                usb_version: BcdVersion(0x0210),
+                device_version: BcdVersion(0x0440),
+                device_class: 0x00,
+                device_subclass: 0x00,
+                device_protocol: 0x00,
+                max_packet_size_0: 64,
+                num_configurations: 1,
+            },
+            manufacturer: Some("Google Inc.".to_string()),
+            product: Some("Pixel 7".to_string()),
+            serial_number: Some("1A2B3C4D".to_string()),
+            port_path: Some("3-1.4".to_string()),
+            speed: Some(Speed::High),
+            tags: vec![MALFORMED_STRINGS_TAG.to_string()],
+            active_config: Some(ConfigInfo {
+                configuration_value: 1,
+                max_power_ma: 500,
+                self_powered: false,
+                remote_wakeup: true,
+                description: None,
+                interfaces: vec![InterfaceInfo {
+                    number: 0,
+                    alternate_setting: 0,
+                    class: 0xff,
+                    subclass: 0x42,
+                    protocol: 0x01,
+                    description: None,
+                    endpoints: vec![EndpointInfo {
+                        address: 0x81,
+                        kind: EndpointKind::Bulk,
+                        max_packet_size: 512,
+                        interval: 0,
+                        ss_bytes_per_interval: Some(1024),
+                    }],
+                }],
+            }),
+            usb_ids: Some(crate::usb_ids::UsbIds {
+                vendor_name: Some("Google Inc.".to_string()),
+                product_name: Some("Nexus/Pixel Device (charging + debug)".to_string()),
+                class_name: None,
+            }),
+            container_id: Some("412b748c-9a01-4d02-8a10-5f3e4401227f".to_string()),
+        }])
+    }
+
+    /// Leaf paths of a serialized value, arrays flattened as "[]".
+    fn value_paths(prefix: &str, value: &Value, out: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, sub) in map {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    value_paths(&path, sub, out);
+                }
+            }
+            Value::Array(items) => {
+                let path = format!("{}[]", prefix);
+                for item in items {
+                    value_paths(&path, item, out);
+                }
+            }
+            _ => {
+                out.insert(prefix.to_string());
+            }
+        }
+    }
+
+    /// Leaf paths the schema declares, mirroring `value_paths`.
+    fn schema_paths(prefix: &str, schema: &Value, out: &mut BTreeSet<String>) {
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (key, sub) in properties {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                schema_paths(&path, sub, out);
+            }
+        } else if let Some(items) = schema.get("items") {
+            schema_paths(&format!("{}[]", prefix), items, out);
+        } else {
+            out.insert(prefix.to_string());
+        }
+    }
+
+    /// Does a serialized leaf satisfy the schema node's "type", which is
+    /// either a string or a list of alternatives?
+    fn type_matches(value: &Value, declared: &Value) -> bool {
+        let name = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        match declared {
+            Value::String(t) => t == name,
+            Value::Array(alternatives) => alternatives.iter().any(|t| t == name),
+            _ => false,
+        }
+    }
+
+    /// Minimal structural validation: every serialized field must be
+    /// declared, every required field present, every leaf of a declared
+    /// type. Not a full draft-07 validator; enough to catch the ways
+    /// this format can actually drift.
+    fn assert_conforms(path: &str, value: &Value, schema: &Value) {
+        match value {
+            Value::Object(map) => {
+                let properties = schema
+                    .get("properties")
+                    .and_then(Value::as_object)
+                    .unwrap_or_else(|| panic!("schema has no properties for object at {}", path));
+                for (key, sub) in map {
+                    let sub_schema = properties
+                        .get(key)
+                        .unwrap_or_else(|| panic!("undeclared field {}.{}", path, key));
+                    assert_conforms(&format!("{}.{}", path, key), sub, sub_schema);
+                }
+                if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                    for name in required {
+                        let name = name.as_str().unwrap();
+                        assert!(map.contains_key(name), "missing {}.{}", path, name);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                let item_schema = schema
+                    .get("items")
+                    .unwrap_or_else(|| panic!("schema has no items for array at {}", path));
+                for item in items {
+                    assert_conforms(&format!("{}[]", path), item, item_schema);
+                }
+            }
+            leaf => {
+                let declared = schema
+                    .get("type")
+                    .unwrap_or_else(|| panic!("schema has no type at {}", path));
+                assert!(
+                    type_matches(leaf, declared),
+                    "value at {} does not match declared type {}",
+                    path,
+                    declared
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fully_populated_snapshot_conforms() {
+        let serialized = serde_json::to_value(fully_populated()).unwrap();
+        assert_conforms("$", &serialized, &snapshot_schema());
+    }
+
+    #[test]
+    fn test_schema_drift_requires_version_bump() {
+        let mut generated = BTreeSet::new();
+        value_paths(
+            "",
+            &serde_json::to_value(fully_populated()).unwrap(),
+            &mut generated,
+        );
+        let mut committed = BTreeSet::new();
+        schema_paths("", &snapshot_schema(), &mut committed);
+        assert_eq!(
+            generated, committed,
+            "snapshot shape changed: update schema/snapshot.schema.json \
+             and bump SNAPSHOT_SCHEMA_VERSION"
+        );
+
+        // The version pinned in the schema must track the code constant,
+        // so a schema edit cannot land without touching the version.
+        let pinned = snapshot_schema()["properties"]["schema_version"]["const"]
+            .as_u64()
+            .expect("schema pins schema_version via const");
+        assert_eq!(pinned, u64::from(SNAPSHOT_SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn test_pre_config_snapshot_deserializes() {
+        // A device written before active_config existed.
+        let snapshot: Snapshot = serde_json::from_str(
+            r#"{
+                "schema_version": 1,
+                "captured_at": "2026-08-01T12:00:00Z",
+                "devices": [{
+                    "bus_number": 1,
+                    "address": 4,
+                    "vendor_id": 1003,
+                    "product_id": 24871,
+                    "descriptor": {
+                        "usb_version": "2.00",
+                        "device_version": "1.00",
+                        "device_class": 0,
+                        "device_subclass": 0,
+                        "device_protocol": 0,
+                        "max_packet_size_0": 64,
+                        "num_configurations": 1
+                    },
+                    "manufacturer": null,
+                    "product": null,
+                    "serial_number": null,
+                    "port_path": null
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(snapshot.devices[0].active_config, None);
+    }
+}
@@ -0,0 +1,269 @@
+// BootForge USB - Mass-storage to OS block device correlation
+// Bridges enumerated USB devices to the block devices and mount points
+// users actually think in ("/dev/sdb", "the E: drive").
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::enumeration::UsbDeviceInfo;
+use crate::error::UsbError;
+
+/**
+ * A block device (and its mounts) backed by an enumerated USB device.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlockDeviceInfo {
+    /// OS device node, e.g. "/dev/sdb".
+    pub device_node: String,
+    /// Capacity in bytes, when the platform reports it.
+    pub size_bytes: Option<u64>,
+    pub mount_points: Vec<String>,
+}
+
+/**
+ * Find the block devices and mount points belonging to an enumerated
+ * mass-storage device.
+ */
+pub fn block_devices(info: &UsbDeviceInfo) -> Result<Vec<BlockDeviceInfo>, UsbError> {
+    #[cfg(target_os = "linux")]
+    {
+        LinuxStorageMapper::new().block_devices(info.bus_number, info.address)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = info;
+        Err(UsbError::Unsupported(
+            "storage mapping is only implemented on Linux".to_string(),
+        ))
+    }
+}
+
+/**
+ * Linux implementation: walk sysfs from the USB device directory down to
+ * its `block` children, then match mounts by major:minor.
+ *
+ * Roots are injectable so tests can run against fixture trees.
+ */
+pub struct LinuxStorageMapper {
+    sysfs_root: PathBuf,
+    mountinfo_path: PathBuf,
+}
+
+impl Default for LinuxStorageMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinuxStorageMapper {
+    pub fn new() -> Self {
+        LinuxStorageMapper {
+            sysfs_root: PathBuf::from("/sys/bus/usb/devices"),
+            mountinfo_path: PathBuf::from("/proc/self/mountinfo"),
+        }
+    }
+
+    pub fn with_roots<P: Into<PathBuf>, Q: Into<PathBuf>>(sysfs_root: P, mountinfo: Q) -> Self {
+        LinuxStorageMapper {
+            sysfs_root: sysfs_root.into(),
+            mountinfo_path: mountinfo.into(),
+        }
+    }
+
+    pub fn block_devices(&self, bus: u8, address: u8) -> Result<Vec<BlockDeviceInfo>, UsbError> {
+        let device_dir = self.find_device_dir(bus, address)?;
+        let mountinfo = fs::read_to_string(&self.mountinfo_path).unwrap_or_default();
+
+        let mut result = Vec::new();
+        let mut block_dirs = Vec::new();
+        collect_block_dirs(&device_dir, 0, &mut block_dirs);
+
+        for block_dir in block_dirs {
+            for entry in fs::read_dir(&block_dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let size_bytes = fs::read_to_string(entry.path().join("size"))
+                    .ok()
+                    .and_then(|s| s.trim().parse::<u64>().ok())
+                    .map(|sectors| sectors * 512);
+                let majmin = fs::read_to_string(entry.path().join("dev"))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_default();
+
+                result.push(BlockDeviceInfo {
+                    device_node: format!("/dev/{}", name),
+                    size_bytes,
+                    mount_points: parse_mount_points(&mountinfo, &majmin),
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn find_device_dir(&self, bus: u8, address: u8) -> Result<PathBuf, UsbError> {
+        for entry in fs::read_dir(&self.sysfs_root)? {
+            let path = entry?.path();
+            let busnum = fs::read_to_string(path.join("busnum"))
+                .ok()
+                .and_then(|s| s.trim().parse::<u8>().ok());
+            let devnum = fs::read_to_string(path.join("devnum"))
+                .ok()
+                .and_then(|s| s.trim().parse::<u8>().ok());
+            if busnum == Some(bus) && devnum == Some(address) {
+                return Ok(path);
+            }
+        }
+        Err(UsbError::NotFound(format!(
+            "no sysfs device for bus {} address {}",
+            bus, address
+        )))
+    }
+}
+
+/// Recursively locate `block` directories under a USB device directory
+/// (device -> interface -> host -> target -> LUN -> block).
+fn collect_block_dirs(dir: &Path, depth: usize, out: &mut Vec<PathBuf>) {
+    // SCSI paths are at most a handful of levels below the interface.
+    if depth > 8 {
+        return;
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() || path.is_symlink() {
+            continue;
+        }
+        if entry.file_name() == "block" {
+            out.push(path);
+        } else {
+            collect_block_dirs(&path, depth + 1, out);
+        }
+    }
+}
+
+/**
+ * Extract the mount points whose backing device has the given
+ * major:minor from mountinfo-format text.
+ */
+pub fn parse_mount_points(mountinfo: &str, majmin: &str) -> Vec<String> {
+    if majmin.is_empty() {
+        return Vec::new();
+    }
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            // mount ID, parent ID, major:minor, root, mount point
+            let _id = fields.next()?;
+            let _parent = fields.next()?;
+            let dev = fields.next()?;
+            let _root = fields.next()?;
+            let mount_point = fields.next()?;
+            if dev == majmin {
+                Some(unescape_mountinfo(mount_point))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// mountinfo escapes space, tab, newline and backslash as octal.
+fn unescape_mountinfo(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let digits: String = chars.clone().take(3).collect();
+            if digits.len() == 3 {
+                if let Ok(code) = u8::from_str_radix(&digits, 8) {
+                    out.push(code as char);
+                    chars.nth(2);
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_root(test: &str) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join(test)
+            .join(format!("{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_parse_mount_points_matches_majmin() {
+        let mountinfo = "\
+36 25 8:16 / /mnt/usb rw,relatime shared:1 - ext4 /dev/sdb rw
+37 25 8:17 / /mnt/other rw - ext4 /dev/sdb1 rw
+38 25 8:16 / /mnt/second\\040mount rw - ext4 /dev/sdb rw
+";
+        assert_eq!(
+            parse_mount_points(mountinfo, "8:16"),
+            vec!["/mnt/usb".to_string(), "/mnt/second mount".to_string()]
+        );
+        assert!(parse_mount_points(mountinfo, "8:99").is_empty());
+        assert!(parse_mount_points(mountinfo, "").is_empty());
+    }
+
+    #[test]
+    fn test_linux_mapper_walks_to_block_children() {
+        let root = fixture_root("storage_map");
+        let sysfs = root.join("sysfs");
+        let dev = sysfs.join("2-1");
+        let block = dev
+            .join("2-1:1.0")
+            .join("host0")
+            .join("target0:0:0")
+            .join("0:0:0:0")
+            .join("block")
+            .join("sdb");
+        fs::create_dir_all(&block).unwrap();
+        fs::write(dev.join("busnum"), "2\n").unwrap();
+        fs::write(dev.join("devnum"), "5\n").unwrap();
+        fs::write(block.join("size"), "15728640\n").unwrap(); // 7.5 GiB in sectors
+        fs::write(block.join("dev"), "8:16\n").unwrap();
+
+        let mountinfo_path = root.join("mountinfo");
+        fs::write(
+            &mountinfo_path,
+            "36 25 8:16 / /media/stick rw - vfat /dev/sdb rw\n",
+        )
+        .unwrap();
+
+        let mapper = LinuxStorageMapper::with_roots(&sysfs, &mountinfo_path);
+        let devices = mapper.block_devices(2, 5).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].device_node, "/dev/sdb");
+        assert_eq!(devices[0].size_bytes, Some(15728640 * 512));
+        assert_eq!(devices[0].mount_points, vec!["/media/stick".to_string()]);
+    }
+
+    #[test]
+    fn test_linux_mapper_unknown_device() {
+        let root = fixture_root("storage_map_missing");
+        let sysfs = root.join("sysfs");
+        fs::create_dir_all(&sysfs).unwrap();
+        let mapper = LinuxStorageMapper::with_roots(&sysfs, root.join("mountinfo"));
+        assert!(matches!(
+            mapper.block_devices(1, 1),
+            Err(UsbError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,380 @@
+// BootForge USB - String descriptor cache
+// Polling consumers re-enumerate every few seconds, and without a cache
+// every cycle re-opens every device to read the same manufacturer /
+// product / serial strings - waking devices out of selective suspend
+// and occasionally confusing flaky firmware. The cache remembers the
+// string-probe result per bus position so a warm pass opens nothing.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::context::SharedContext;
+use crate::enumeration::{
+    enumerate_libusb_report_in, EnumerationOptions, EnumerationReport, UsbDeviceInfo,
+};
+use crate::error::UsbError;
+
+/**
+ * Cache key: the device's bus position plus its VID/PID. Keying on the
+ * address means a replug - which changes the address - misses by
+ * construction, so stale strings are never served for a device that
+ * re-enumerated; the stable identity (serial, Container ID) lives in
+ * the cached value for consumers that correlate across replugs.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub bus_number: u8,
+    pub address: u8,
+}
+
+impl CacheKey {
+    pub fn new(vendor_id: u16, product_id: u16, bus_number: u8, address: u8) -> Self {
+        CacheKey {
+            vendor_id,
+            product_id,
+            bus_number,
+            address,
+        }
+    }
+}
+
+/**
+ * The result of one successful string probe, in cacheable form: the
+ * three descriptor strings, the Container ID, and the quality flags
+ * that become tags on the device record.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CachedStrings {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    pub container_id: Option<String>,
+    /// iConfiguration string, when interface-string reads are enabled.
+    pub config_description: Option<String>,
+    /// iInterface strings keyed by (number, alternate_setting).
+    pub interface_descriptions: Vec<((u8, u8), String)>,
+    pub malformed: bool,
+    pub oversized: bool,
+}
+
+struct Entry {
+    strings: CachedStrings,
+    stored_at: Instant,
+}
+
+/**
+ * A thread-safe, bounded, TTL-expiring cache of string-probe results,
+ * consulted by enumeration before a device is opened (see
+ * `EnumerationOptions::with_string_cache`). Opt-in: enumeration without
+ * a cache behaves exactly as before.
+ *
+ * Only successful opens are cached; a device that could not be opened
+ * is retried on the next pass. Entries older than the TTL are dropped
+ * on lookup, so a device whose strings legitimately change (e.g. a
+ * firmware update without a replug) is re-read eventually.
+ */
+pub struct StringDescriptorCache {
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+    capacity: usize,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl StringDescriptorCache {
+    /// A cache holding at most `capacity` entries, each valid for
+    /// `ttl` after insertion.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        StringDescriptorCache {
+            entries: Mutex::new(HashMap::new()),
+            capacity: capacity.max(1),
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The cached strings for `key`, or None on a cold or expired
+    /// entry. Expired entries are removed on the way out.
+    pub fn get(&self, key: &CacheKey) -> Option<CachedStrings> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        match entries.get(key) {
+            Some(entry) if entry.stored_at.elapsed() <= self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry.strings.clone())
+            }
+            Some(_) => {
+                entries.remove(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Store a probe result, evicting expired entries - then the oldest
+    /// live one - when the cache is full.
+    pub fn insert(&self, key: CacheKey, strings: CachedStrings) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if !entries.contains_key(&key) && entries.len() >= self.capacity {
+            entries.retain(|_, entry| entry.stored_at.elapsed() <= self.ttl);
+            if entries.len() >= self.capacity {
+                if let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.stored_at)
+                    .map(|(key, _)| *key)
+                {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                strings,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every entry; the hit/miss counters are left running.
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Lookups served from the cache since construction.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Lookups that missed (cold or expired) since construction.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for StringDescriptorCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StringDescriptorCache")
+            .field("capacity", &self.capacity)
+            .field("ttl", &self.ttl)
+            .field("entries", &self.len())
+            .field("hits", &self.hits())
+            .field("misses", &self.misses())
+            .finish()
+    }
+}
+
+/**
+ * A persistent enumerator owning a shared context and a string cache,
+ * for callers that enumerate on a cadence: the first pass probes every
+ * device, later passes open only devices the cache has not seen at
+ * their current address.
+ */
+pub struct CachedEnumerator {
+    context: SharedContext,
+    options: EnumerationOptions,
+    cache: Arc<StringDescriptorCache>,
+}
+
+impl CachedEnumerator {
+    /// An enumerator with default options and a cache sized for a
+    /// typical host (256 entries, 60 s TTL).
+    pub fn new() -> Self {
+        CachedEnumerator::with_cache(Arc::new(StringDescriptorCache::new(
+            256,
+            Duration::from_secs(60),
+        )))
+    }
+
+    /// An enumerator around a caller-owned cache, so the same cache can
+    /// back several enumerating subsystems.
+    pub fn with_cache(cache: Arc<StringDescriptorCache>) -> Self {
+        CachedEnumerator {
+            context: SharedContext::new(),
+            options: EnumerationOptions::default(),
+            cache,
+        }
+    }
+
+    /// Replace the enumeration options; the cache wiring is reapplied
+    /// on every pass, so a `string_cache` set here is overridden.
+    pub fn with_options(mut self, options: EnumerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Run passes on `context` instead of a private one.
+    pub fn with_context(mut self, context: SharedContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// The cache backing this enumerator, for warm-rate inspection.
+    pub fn cache(&self) -> &Arc<StringDescriptorCache> {
+        &self.cache
+    }
+
+    /// One enumeration pass through the cache.
+    pub fn enumerate_report(&self) -> Result<EnumerationReport, UsbError> {
+        let options = self
+            .options
+            .clone()
+            .with_string_cache(Arc::clone(&self.cache));
+        enumerate_libusb_report_in(&self.context, &options)
+    }
+
+    /// As `enumerate_report`, keeping only the probed devices.
+    pub fn enumerate(&self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+        Ok(self.enumerate_report()?.devices)
+    }
+}
+
+impl Default for CachedEnumerator {
+    fn default() -> Self {
+        CachedEnumerator::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings_for(serial: &str) -> CachedStrings {
+        CachedStrings {
+            manufacturer: Some("Google Inc.".to_string()),
+            product: Some("Pixel 7".to_string()),
+            serial_number: Some(serial.to_string()),
+            container_id: None,
+            config_description: None,
+            interface_descriptions: Vec::new(),
+            malformed: false,
+            oversized: false,
+        }
+    }
+
+    /// The probe flow enumeration runs per device: consult the cache,
+    /// open (counted here) only on a miss, cache the result.
+    fn probe_via(cache: &StringDescriptorCache, key: CacheKey, opens: &mut u32) -> CachedStrings {
+        if let Some(hit) = cache.get(&key) {
+            return hit;
+        }
+        *opens += 1;
+        let strings = strings_for("1A2B3C4D");
+        cache.insert(key, strings.clone());
+        strings
+    }
+
+    #[test]
+    fn test_warm_pass_performs_zero_opens() {
+        let cache = StringDescriptorCache::new(16, Duration::from_secs(60));
+        let devices = [
+            CacheKey::new(0x18d1, 0x4ee7, 3, 7),
+            CacheKey::new(0x05ac, 0x12a8, 3, 8),
+        ];
+
+        let mut opens = 0;
+        for key in devices {
+            probe_via(&cache, key, &mut opens);
+        }
+        assert_eq!(opens, 2);
+
+        // Second pass: same devices at the same addresses, no opens.
+        for key in devices {
+            probe_via(&cache, key, &mut opens);
+        }
+        assert_eq!(opens, 2);
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 2);
+    }
+
+    #[test]
+    fn test_address_change_invalidates() {
+        let cache = StringDescriptorCache::new(16, Duration::from_secs(60));
+        let mut opens = 0;
+        probe_via(&cache, CacheKey::new(0x18d1, 0x4ee7, 3, 7), &mut opens);
+
+        // Replug: same device, new address - must be re-probed.
+        probe_via(&cache, CacheKey::new(0x18d1, 0x4ee7, 3, 9), &mut opens);
+        assert_eq!(opens, 2);
+    }
+
+    #[test]
+    fn test_ttl_expires_entries() {
+        let cache = StringDescriptorCache::new(16, Duration::ZERO);
+        let key = CacheKey::new(0x18d1, 0x4ee7, 3, 7);
+        cache.insert(key, strings_for("1A2B3C4D"));
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(cache.get(&key), None);
+        assert!(cache.is_empty(), "expired entry is removed on lookup");
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let cache = StringDescriptorCache::new(2, Duration::from_secs(60));
+        let first = CacheKey::new(0x18d1, 0x4ee7, 3, 1);
+        let second = CacheKey::new(0x18d1, 0x4ee7, 3, 2);
+        let third = CacheKey::new(0x18d1, 0x4ee7, 3, 3);
+
+        cache.insert(first, strings_for("a"));
+        std::thread::sleep(Duration::from_millis(2));
+        cache.insert(second, strings_for("b"));
+        std::thread::sleep(Duration::from_millis(2));
+        cache.insert(third, strings_for("c"));
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&first), None, "oldest entry was evicted");
+        assert!(cache.get(&second).is_some());
+        assert!(cache.get(&third).is_some());
+    }
+
+    #[test]
+    fn test_rewriting_a_key_does_not_evict() {
+        let cache = StringDescriptorCache::new(2, Duration::from_secs(60));
+        let first = CacheKey::new(0x18d1, 0x4ee7, 3, 1);
+        let second = CacheKey::new(0x18d1, 0x4ee7, 3, 2);
+        cache.insert(first, strings_for("a"));
+        cache.insert(second, strings_for("b"));
+        cache.insert(first, strings_for("a2"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.get(&first).unwrap().serial_number.as_deref(),
+            Some("a2")
+        );
+        assert!(cache.get(&second).is_some());
+    }
+
+    #[test]
+    fn test_clear_keeps_counters() {
+        let cache = StringDescriptorCache::new(16, Duration::from_secs(60));
+        let key = CacheKey::new(0x18d1, 0x4ee7, 3, 7);
+        cache.insert(key, strings_for("a"));
+        assert!(cache.get(&key).is_some());
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&key), None);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    }
+}
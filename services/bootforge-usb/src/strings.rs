@@ -0,0 +1,292 @@
+// BootForge USB - String descriptor decoding
+// Devices routinely return string descriptors that violate the spec:
+// odd lengths, Latin-1 bytes where UTF-16 should be, payloads longer
+// than bLength claims. The decoding policy lives here, in one pure
+// function, so every enumeration path degrades the same way.
+
+use std::time::Duration;
+
+use crate::error::UsbError;
+use crate::transfer::UsbTransport;
+
+/// Tag attached to a device whose string descriptors needed repair;
+/// downstream identity logic should distrust the string fields.
+pub const MALFORMED_STRINGS_TAG: &str = "descriptor:malformed-strings";
+
+const DESCRIPTOR_TYPE_STRING: u8 = 0x03;
+const REQ_GET_DESCRIPTOR: u8 = 0x06;
+const REQUEST_TYPE_STANDARD_IN: u8 = 0x80;
+
+/// Fraction of UTF-16 code units allowed to decode to U+FFFD before the
+/// Latin-1 fallback kicks in, as a (numerator, denominator) ratio.
+const REPLACEMENT_THRESHOLD: (usize, usize) = (1, 4);
+
+/**
+ * Outcome of decoding one raw string descriptor.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedString {
+    /// The recovered text; None when nothing usable survived.
+    pub text: Option<String>,
+    /// The descriptor violated the spec and the text went through a
+    /// repair path - treat it as untrusted.
+    pub malformed: bool,
+}
+
+/**
+ * Decode a raw string descriptor as transferred off the wire.
+ *
+ * Policy, in order:
+ * - the descriptor's own bLength wins over the transfer length; bytes
+ *   beyond bLength are dropped as garbage,
+ * - an odd payload loses its trailing byte,
+ * - the payload is decoded as UTF-16LE; when more than a quarter of the
+ *   code units come out as U+FFFD the payload is re-read as Latin-1
+ *   (one byte per character) instead,
+ * - trailing NULs are stripped.
+ *
+ * Any repair sets `malformed` so callers can tag the device.
+ */
+pub fn decode_string_descriptor(raw: &[u8]) -> DecodedString {
+    if raw.len() < 2 || raw[1] != DESCRIPTOR_TYPE_STRING {
+        return DecodedString {
+            text: None,
+            malformed: true,
+        };
+    }
+
+    let mut malformed = false;
+    let b_length = raw[0] as usize;
+
+    let end = if b_length > raw.len() {
+        // Claims more than the device actually sent.
+        malformed = true;
+        raw.len()
+    } else {
+        if b_length < raw.len() {
+            // Trailing garbage beyond the descriptor's own length.
+            malformed = true;
+        }
+        b_length
+    };
+
+    let mut payload = &raw[2..end.max(2)];
+    if !payload.len().is_multiple_of(2) {
+        malformed = true;
+        payload = &payload[..payload.len() - 1];
+    }
+    if payload.is_empty() {
+        return DecodedString {
+            text: None,
+            malformed,
+        };
+    }
+
+    let units: Vec<u16> = payload
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+
+    let mut replacements = 0usize;
+    let utf16: String = char::decode_utf16(units.iter().copied())
+        .map(|r| {
+            r.unwrap_or_else(|_| {
+                replacements += 1;
+                char::REPLACEMENT_CHARACTER
+            })
+        })
+        .collect();
+
+    let text = if replacements * REPLACEMENT_THRESHOLD.1 > units.len() * REPLACEMENT_THRESHOLD.0 {
+        // Mostly invalid UTF-16; the bytes are likelier Latin-1.
+        malformed = true;
+        payload.iter().map(|&b| b as char).collect()
+    } else {
+        if replacements > 0 {
+            malformed = true;
+        }
+        utf16
+    };
+
+    let text = text.trim_end_matches('\0').to_string();
+    DecodedString {
+        text: (!text.is_empty()).then_some(text),
+        malformed,
+    }
+}
+
+/**
+ * Read string descriptor `index` in `language` and decode it under the
+ * policy above.
+ */
+pub fn get_string_descriptor<T: UsbTransport>(
+    transport: &mut T,
+    index: u8,
+    language: u16,
+    timeout: Duration,
+) -> Result<DecodedString, UsbError> {
+    get_string_descriptor_limited(transport, index, language, timeout, 255)
+}
+
+/**
+ * As `get_string_descriptor`, but bounded: the read buffer never
+ * exceeds `max_len` and a descriptor claiming more is an error rather
+ * than a silent truncation (see `transfer::DescriptorLimits`).
+ */
+pub fn get_string_descriptor_limited<T: UsbTransport>(
+    transport: &mut T,
+    index: u8,
+    language: u16,
+    timeout: Duration,
+    max_len: usize,
+) -> Result<DecodedString, UsbError> {
+    let cap = max_len.clamp(2, 255);
+    let mut buf = [0u8; 255];
+    let n = transport
+        .read_control(
+            REQUEST_TYPE_STANDARD_IN,
+            REQ_GET_DESCRIPTOR,
+            u16::from(DESCRIPTOR_TYPE_STRING) << 8 | u16::from(index),
+            language,
+            &mut buf[..cap],
+            timeout,
+        )
+        .map_err(crate::error::classify_transfer_error)?;
+    if n >= 1 && usize::from(buf[0]) > max_len {
+        return Err(UsbError::Parse(format!(
+            "descriptor exceeds limit: string {} claims {} bytes (limit {})",
+            index, buf[0], max_len
+        )));
+    }
+    Ok(decode_string_descriptor(&buf[..n]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::mock::MockTransport;
+
+    fn utf16le(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn descriptor(payload: &[u8]) -> Vec<u8> {
+        let mut raw = vec![(payload.len() + 2) as u8, 0x03];
+        raw.extend_from_slice(payload);
+        raw
+    }
+
+    #[test]
+    fn test_limited_read_rejects_over_claim() {
+        let mut transport = MockTransport::new();
+        transport
+            .control_read_results
+            .push_back(Ok(vec![0xff, 0x03, 0x41, 0x00]));
+        let err =
+            get_string_descriptor_limited(&mut transport, 1, 0x0409, Duration::from_millis(5), 64)
+                .unwrap_err();
+        assert!(
+            matches!(&err, UsbError::Parse(msg) if msg.starts_with("descriptor exceeds limit")),
+            "got {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_well_formed_descriptor() {
+        let decoded = decode_string_descriptor(&descriptor(&utf16le("Pixel 7")));
+        assert_eq!(decoded.text.as_deref(), Some("Pixel 7"));
+        assert!(!decoded.malformed);
+    }
+
+    #[test]
+    fn test_trailing_garbage_beyond_blength_dropped() {
+        // bLength covers "OK" only; the device kept transmitting anyway.
+        let mut raw = descriptor(&utf16le("OK"));
+        raw.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        raw[0] = 6;
+        let decoded = decode_string_descriptor(&raw);
+        assert_eq!(decoded.text.as_deref(), Some("OK"));
+        assert!(decoded.malformed);
+    }
+
+    #[test]
+    fn test_blength_exceeding_transfer_uses_what_arrived() {
+        let mut raw = descriptor(&utf16le("Short"));
+        raw[0] = 0xff;
+        let decoded = decode_string_descriptor(&raw);
+        assert_eq!(decoded.text.as_deref(), Some("Short"));
+        assert!(decoded.malformed);
+    }
+
+    #[test]
+    fn test_odd_length_loses_trailing_byte() {
+        let mut raw = descriptor(&utf16le("AB"));
+        raw.push(0x41);
+        raw[0] += 1;
+        let decoded = decode_string_descriptor(&raw);
+        assert_eq!(decoded.text.as_deref(), Some("AB"));
+        assert!(decoded.malformed);
+    }
+
+    #[test]
+    fn test_latin1_fallback_on_surrogate_soup() {
+        // Every code unit is an unpaired surrogate; decoded as UTF-16
+        // this is 100% U+FFFD, so the bytes are re-read as Latin-1.
+        let payload = [0x48, 0xd8, 0x69, 0xd8];
+        let decoded = decode_string_descriptor(&descriptor(&payload));
+        assert_eq!(decoded.text.as_deref(), Some("HØiØ"));
+        assert!(decoded.malformed);
+        assert!(!decoded.text.unwrap().contains('\u{fffd}'));
+    }
+
+    #[test]
+    fn test_isolated_replacement_kept_below_threshold() {
+        // One bad unit in a long string: keep the UTF-16 reading, flag it.
+        let mut payload = utf16le("Realtek 802.11n NIC");
+        payload[0] = 0x00;
+        payload[1] = 0xd8; // lone high surrogate
+        let decoded = decode_string_descriptor(&descriptor(&payload));
+        let text = decoded.text.unwrap();
+        assert!(text.starts_with('\u{fffd}'));
+        assert!(text.ends_with("802.11n NIC"));
+        assert!(decoded.malformed);
+    }
+
+    #[test]
+    fn test_trailing_nuls_stripped() {
+        let decoded = decode_string_descriptor(&descriptor(&utf16le("Disk\0\0")));
+        assert_eq!(decoded.text.as_deref(), Some("Disk"));
+        assert!(!decoded.malformed);
+    }
+
+    #[test]
+    fn test_empty_and_junk_headers() {
+        for raw in [&[][..], &[0x02][..], &[0x04, 0x01, 0x41, 0x00][..]] {
+            let decoded = decode_string_descriptor(raw);
+            assert_eq!(decoded.text, None);
+            assert!(decoded.malformed);
+        }
+        // Header-only descriptor: empty but spec-legal shape.
+        let decoded = decode_string_descriptor(&[0x02, 0x03]);
+        assert_eq!(decoded.text, None);
+        assert!(!decoded.malformed);
+    }
+
+    #[test]
+    fn test_get_string_descriptor_request_shape() {
+        let mut transport = MockTransport::new();
+        transport
+            .control_read_results
+            .push_back(Ok(descriptor(&utf16le("Pixel 7"))));
+        let decoded =
+            get_string_descriptor(&mut transport, 2, 0x0409, Duration::from_millis(100)).unwrap();
+        assert_eq!(decoded.text.as_deref(), Some("Pixel 7"));
+
+        let req = &transport.control_requests[0];
+        assert_eq!(req.request_type, 0x80);
+        assert_eq!(req.request, 0x06);
+        assert_eq!(req.value, 0x0302);
+        assert_eq!(req.index, 0x0409);
+    }
+}
@@ -0,0 +1,272 @@
+// BootForge USB - Bus topology model
+// Tree representation of a controller and the devices behind it, carried
+// by analysis passes that need more than a flat device list.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::UsbError;
+use crate::version::BcdVersion;
+
+/**
+ * Negotiated link speed of a device. Ordered slowest to fastest, so
+ * comparisons express "slower than".
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Speed {
+    Low,
+    Full,
+    High,
+    Super,
+    SuperPlus,
+}
+
+impl Speed {
+    /**
+     * Fastest speed a device claiming this bcdUSB could negotiate.
+     * An estimate: a 2.0 device may still be full-speed-only, but a
+     * 3.x descriptor behind a 480 Mbps link is a reliable tell.
+     */
+    pub fn ceiling_for(usb_version: BcdVersion) -> Speed {
+        match usb_version.0 {
+            v if v >= 0x0310 => Speed::SuperPlus,
+            v if v >= 0x0300 => Speed::Super,
+            v if v >= 0x0200 => Speed::High,
+            _ => Speed::Full,
+        }
+    }
+
+    /// Nominal signalling rate of the link, as usually quoted.
+    pub fn bit_rate(self) -> &'static str {
+        match self {
+            Speed::Low => "1.5 Mbps",
+            Speed::Full => "12 Mbps",
+            Speed::High => "480 Mbps",
+            Speed::Super => "5 Gbps",
+            Speed::SuperPlus => "10 Gbps",
+        }
+    }
+
+    /**
+     * Parse the sysfs `speed` attribute, which reports the negotiated
+     * rate in Mbps ("480", "5000"; low speed renders as "1.5"). None
+     * for rates the kernel may grow that this enum does not know,
+     * except 20000, which is still a Gen 2 SuperSpeed+ link.
+     */
+    pub fn from_sysfs(value: &str) -> Option<Speed> {
+        match value.trim() {
+            "1.5" => Some(Speed::Low),
+            "12" => Some(Speed::Full),
+            "480" => Some(Speed::High),
+            "5000" => Some(Speed::Super),
+            "10000" | "20000" => Some(Speed::SuperPlus),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Speed::Low => "Low Speed",
+            Speed::Full => "Full Speed",
+            Speed::High => "High Speed",
+            Speed::Super => "SuperSpeed",
+            Speed::SuperPlus => "SuperSpeed+",
+        };
+        write!(f, "{} {}", self.bit_rate(), name)
+    }
+}
+
+/**
+ * Transfer type of an endpoint (bmAttributes bits 1:0).
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum EndpointKind {
+    Control,
+    Isochronous,
+    Bulk,
+    Interrupt,
+}
+
+/**
+ * The descriptor fields of one endpoint that matter for planning.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EndpointInfo {
+    /// bEndpointAddress, direction bit included.
+    pub address: u8,
+    pub kind: EndpointKind,
+    /// Raw wMaxPacketSize; on high speed, bits 12:11 encode additional
+    /// transactions per microframe.
+    pub max_packet_size: u16,
+    /// Raw bInterval. Frames on low/full speed; a 2^(n-1) microframe
+    /// exponent on high speed and above.
+    pub interval: u8,
+    /// wBytesPerInterval from the SuperSpeed endpoint companion, when the
+    /// descriptor carried one.
+    pub ss_bytes_per_interval: Option<u32>,
+}
+
+/**
+ * Parsed SuperSpeed endpoint companion descriptor (type 0x30), which
+ * follows an endpoint descriptor in SuperSpeed configurations and
+ * carries burst, stream, and periodic-reservation information.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EndpointCompanion {
+    /// bMaxBurst: packets per burst, minus one.
+    pub max_burst: u8,
+    /// bmAttributes: MaxStreams for bulk, Mult for isochronous.
+    pub attributes: u8,
+    /// wBytesPerInterval, for periodic endpoints.
+    pub bytes_per_interval: u16,
+}
+
+impl EndpointCompanion {
+    const DESCRIPTOR_TYPE: u8 = 0x30;
+
+    /// Parse the raw descriptor bytes (bLength, bDescriptorType first).
+    pub fn parse(raw: &[u8]) -> Result<Self, UsbError> {
+        if raw.len() < 6 || usize::from(raw[0]) < 6 {
+            return Err(UsbError::Parse(format!(
+                "SS endpoint companion too short: {} bytes",
+                raw.len()
+            )));
+        }
+        if raw[1] != Self::DESCRIPTOR_TYPE {
+            return Err(UsbError::Parse(format!(
+                "not an SS endpoint companion: descriptor type 0x{:02x}",
+                raw[1]
+            )));
+        }
+        Ok(EndpointCompanion {
+            max_burst: raw[2],
+            attributes: raw[3],
+            bytes_per_interval: u16::from_le_bytes([raw[4], raw[5]]),
+        })
+    }
+
+    /**
+     * Number of streams a bulk endpoint supports: bmAttributes bits 4:0
+     * hold MaxStreams as a power-of-two exponent, zero meaning streams
+     * are not supported. UASP requires a non-zero value; zero here is
+     * why a device fell back to BOT.
+     */
+    pub fn max_streams(&self) -> u32 {
+        let exponent = self.attributes & 0x1f;
+        if exponent == 0 {
+            0
+        } else {
+            1 << exponent.min(16)
+        }
+    }
+
+    /// Isochronous Mult (bmAttributes bits 1:0): bursts per interval,
+    /// minus one.
+    pub fn iso_mult(&self) -> u8 {
+        self.attributes & 0x03
+    }
+}
+
+/**
+ * One node in the bus tree: a controller, hub, or leaf device, with the
+ * devices behind it as children.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TopologyNode {
+    /// Human-readable label, e.g. the product string or "xhci-hcd bus 3".
+    pub name: String,
+    pub speed: Speed,
+    /// bcdUSB from the node's device descriptor, when known; lets
+    /// analyses compare claimed capability against the negotiated link.
+    pub usb_version: Option<BcdVersion>,
+    /// Periodic-capable endpoints of this node's active configuration.
+    pub endpoints: Vec<EndpointInfo>,
+    pub children: Vec<TopologyNode>,
+}
+
+impl TopologyNode {
+    pub fn new(name: impl Into<String>, speed: Speed) -> Self {
+        TopologyNode {
+            name: name.into(),
+            speed,
+            usb_version: None,
+            endpoints: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_usb_version(mut self, usb_version: BcdVersion) -> Self {
+        self.usb_version = Some(usb_version);
+        self
+    }
+
+    pub fn with_endpoints(mut self, endpoints: Vec<EndpointInfo>) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    pub fn with_children(mut self, children: Vec<TopologyNode>) -> Self {
+        self.children = children;
+        self
+    }
+
+    /// Depth-first iteration over this node and everything behind it.
+    pub fn iter(&self) -> impl Iterator<Item = &TopologyNode> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.children.iter());
+            Some(node)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speed_display_and_sysfs_parse() {
+        assert_eq!(Speed::Low.to_string(), "1.5 Mbps Low Speed");
+        assert_eq!(Speed::High.to_string(), "480 Mbps High Speed");
+        assert_eq!(Speed::SuperPlus.to_string(), "10 Gbps SuperSpeed+");
+
+        assert_eq!(Speed::from_sysfs("480\n"), Some(Speed::High));
+        assert_eq!(Speed::from_sysfs("1.5"), Some(Speed::Low));
+        assert_eq!(Speed::from_sysfs("20000"), Some(Speed::SuperPlus));
+        assert_eq!(Speed::from_sysfs("9600"), None);
+    }
+
+    #[test]
+    fn test_companion_parse() {
+        // bLength 6, type 0x30, MaxBurst 15, MaxStreams 2^5 = 32,
+        // wBytesPerInterval 0.
+        let companion = EndpointCompanion::parse(&[0x06, 0x30, 0x0f, 0x05, 0x00, 0x00]).unwrap();
+        assert_eq!(companion.max_burst, 15);
+        assert_eq!(companion.max_streams(), 32);
+        assert_eq!(companion.bytes_per_interval, 0);
+    }
+
+    #[test]
+    fn test_companion_no_streams_means_bot_fallback() {
+        let companion = EndpointCompanion::parse(&[0x06, 0x30, 0x00, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(companion.max_streams(), 0);
+    }
+
+    #[test]
+    fn test_companion_iso_fields() {
+        // Mult 2, 49152 bytes per interval.
+        let companion = EndpointCompanion::parse(&[0x06, 0x30, 0x0f, 0x02, 0x00, 0xc0]).unwrap();
+        assert_eq!(companion.iso_mult(), 2);
+        assert_eq!(companion.bytes_per_interval, 0xc000);
+    }
+
+    #[test]
+    fn test_companion_rejects_short_or_foreign_descriptors() {
+        assert!(EndpointCompanion::parse(&[0x06, 0x30, 0x00]).is_err());
+        // An endpoint descriptor (type 0x05) is not a companion.
+        assert!(EndpointCompanion::parse(&[0x07, 0x05, 0x81, 0x02, 0x00, 0x04, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,1659 @@
+// BootForge USB - Endpoint transfer layer
+// Shared retry, stall recovery and stats for bulk and interrupt endpoints.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{classify_transfer_error, UsbError};
+
+/**
+ * Transport abstraction over a claimed device handle.
+ *
+ * Implemented for rusb::DeviceHandle; tests substitute a mock with
+ * scripted outcomes.
+ */
+pub trait UsbTransport {
+    fn read_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error>;
+    fn write_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error>;
+    fn read_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error>;
+    fn write_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error>;
+    fn clear_halt(&mut self, endpoint: u8) -> Result<(), rusb::Error>;
+    #[allow(clippy::too_many_arguments)]
+    fn read_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error>;
+    #[allow(clippy::too_many_arguments)]
+    fn write_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error>;
+}
+
+impl<C: rusb::UsbContext> UsbTransport for rusb::DeviceHandle<C> {
+    fn read_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        rusb::DeviceHandle::read_bulk(self, endpoint, buf, timeout)
+    }
+
+    fn write_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        rusb::DeviceHandle::write_bulk(self, endpoint, buf, timeout)
+    }
+
+    fn read_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        rusb::DeviceHandle::read_interrupt(self, endpoint, buf, timeout)
+    }
+
+    fn write_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        rusb::DeviceHandle::write_interrupt(self, endpoint, buf, timeout)
+    }
+
+    fn clear_halt(&mut self, endpoint: u8) -> Result<(), rusb::Error> {
+        rusb::DeviceHandle::clear_halt(self, endpoint)
+    }
+
+    fn read_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        rusb::DeviceHandle::read_control(self, request_type, request, value, index, buf, timeout)
+    }
+
+    fn write_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        rusb::DeviceHandle::write_control(self, request_type, request, value, index, buf, timeout)
+    }
+}
+
+/// Forwarding impl so a transport can be borrowed by one transfer
+/// wrapper (e.g. an interrupt poller) without giving up ownership.
+impl<T: UsbTransport + ?Sized> UsbTransport for &mut T {
+    fn read_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        (**self).read_bulk(endpoint, buf, timeout)
+    }
+
+    fn write_bulk(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        (**self).write_bulk(endpoint, buf, timeout)
+    }
+
+    fn read_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        (**self).read_interrupt(endpoint, buf, timeout)
+    }
+
+    fn write_interrupt(
+        &mut self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        (**self).write_interrupt(endpoint, buf, timeout)
+    }
+
+    fn clear_halt(&mut self, endpoint: u8) -> Result<(), rusb::Error> {
+        (**self).clear_halt(endpoint)
+    }
+
+    fn read_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        (**self).read_control(request_type, request, value, index, buf, timeout)
+    }
+
+    fn write_control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, rusb::Error> {
+        (**self).write_control(request_type, request, value, index, buf, timeout)
+    }
+}
+
+/// Tag attached to a device whose descriptors claim sizes beyond the
+/// configured limits; its descriptor-derived data is incomplete.
+pub const OVERSIZED_DESCRIPTOR_TAG: &str = "descriptor:oversized";
+
+/**
+ * Bounds on descriptor reads, so a hostile or broken device claiming
+ * wTotalLength 65535 (or 255 bytes across 255 string indexes) cannot
+ * stall enumeration or balloon memory. Exceeding a limit is an error,
+ * never a silent truncation - truncated descriptors would parse to
+ * plausible nonsense.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DescriptorLimits {
+    /// Largest accepted configuration descriptor (wTotalLength).
+    pub max_config_len: usize,
+    /// Largest accepted BOS descriptor (wTotalLength).
+    pub max_bos_len: usize,
+    /// Largest accepted string descriptor (bLength; the wire format
+    /// caps this at 255 anyway).
+    pub max_string_len: usize,
+    /// Budget across all descriptor reads on one device.
+    pub max_total_per_device: usize,
+}
+
+impl Default for DescriptorLimits {
+    fn default() -> Self {
+        DescriptorLimits {
+            max_config_len: 4096,
+            max_bos_len: 1024,
+            max_string_len: 255,
+            max_total_per_device: 64 * 1024,
+        }
+    }
+}
+
+impl DescriptorLimits {
+    fn exceeded(what: &str, claimed: usize, limit: usize) -> UsbError {
+        UsbError::Parse(format!(
+            "descriptor exceeds limit: {} claims {} bytes (limit {})",
+            what, claimed, limit
+        ))
+    }
+
+    /// Whether `error` is a limit violation from these checks, so
+    /// callers can tag the device rather than drop it.
+    pub fn is_limit_error(error: &UsbError) -> bool {
+        matches!(error, UsbError::Parse(msg) if msg.starts_with("descriptor exceeds limit"))
+    }
+}
+
+const REQ_GET_DESCRIPTOR: u8 = 0x06;
+const REQUEST_TYPE_STANDARD_IN: u8 = 0x80;
+const DESCRIPTOR_TYPE_CONFIG: u8 = 0x02;
+const DESCRIPTOR_TYPE_BOS: u8 = 0x0f;
+
+/**
+ * Control-endpoint descriptor reads with size limits enforced before
+ * any allocation: the header is read first, the claimed total is
+ * checked against `DescriptorLimits`, and only then is the full
+ * descriptor fetched.
+ */
+pub struct ControlTransfer<T: UsbTransport> {
+    transport: T,
+    limits: DescriptorLimits,
+    bytes_read: usize,
+}
+
+impl<T: UsbTransport> ControlTransfer<T> {
+    pub fn new(transport: T) -> Self {
+        ControlTransfer {
+            transport,
+            limits: DescriptorLimits::default(),
+            bytes_read: 0,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: DescriptorLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Bytes fetched so far against the per-device budget.
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
+    /// Full configuration descriptor `index`, bounded by max_config_len.
+    pub fn read_config_descriptor(
+        &mut self,
+        index: u8,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, UsbError> {
+        self.read_sized_descriptor(DESCRIPTOR_TYPE_CONFIG, index, 9, self.limits.max_config_len, "configuration", timeout)
+    }
+
+    /// Full BOS descriptor, bounded by max_bos_len.
+    pub fn read_bos_descriptor(&mut self, timeout: Duration) -> Result<Vec<u8>, UsbError> {
+        self.read_sized_descriptor(DESCRIPTOR_TYPE_BOS, 0, 5, self.limits.max_bos_len, "BOS", timeout)
+    }
+
+    /// Raw string descriptor `index`, bounded by max_string_len.
+    pub fn read_string_descriptor(
+        &mut self,
+        index: u8,
+        language: u16,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, UsbError> {
+        let cap = self.limits.max_string_len.clamp(2, 255);
+        self.charge(cap)?;
+        let mut buf = vec![0u8; cap];
+        let n = self
+            .transport
+            .read_control(
+                REQUEST_TYPE_STANDARD_IN,
+                REQ_GET_DESCRIPTOR,
+                u16::from(0x03u8) << 8 | u16::from(index),
+                language,
+                &mut buf,
+                timeout,
+            )
+            .map_err(classify_transfer_error)?;
+        if let Some(&claimed) = buf.first() {
+            if usize::from(claimed) > self.limits.max_string_len {
+                return Err(DescriptorLimits::exceeded(
+                    "string",
+                    usize::from(claimed),
+                    self.limits.max_string_len,
+                ));
+            }
+        }
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    /// Header-first read of a wTotalLength-bearing descriptor.
+    fn read_sized_descriptor(
+        &mut self,
+        descriptor_type: u8,
+        index: u8,
+        header_len: usize,
+        limit: usize,
+        what: &str,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, UsbError> {
+        let value = u16::from(descriptor_type) << 8 | u16::from(index);
+        self.charge(header_len)?;
+        let mut header = vec![0u8; header_len];
+        let n = self
+            .transport
+            .read_control(
+                REQUEST_TYPE_STANDARD_IN,
+                REQ_GET_DESCRIPTOR,
+                value,
+                0,
+                &mut header,
+                timeout,
+            )
+            .map_err(classify_transfer_error)?;
+        if n < 4 {
+            return Err(UsbError::Parse(format!(
+                "{} descriptor header too short: {} bytes",
+                what, n
+            )));
+        }
+        let total = usize::from(u16::from_le_bytes([header[2], header[3]]));
+        if total > limit {
+            return Err(DescriptorLimits::exceeded(what, total, limit));
+        }
+
+        self.charge(total)?;
+        let mut full = vec![0u8; total.max(header_len)];
+        let n = self
+            .transport
+            .read_control(
+                REQUEST_TYPE_STANDARD_IN,
+                REQ_GET_DESCRIPTOR,
+                value,
+                0,
+                &mut full[..total.max(1)],
+                timeout,
+            )
+            .map_err(classify_transfer_error)?;
+        full.truncate(n);
+        Ok(full)
+    }
+
+    fn charge(&mut self, n: usize) -> Result<(), UsbError> {
+        self.bytes_read = self.bytes_read.saturating_add(n);
+        if self.bytes_read > self.limits.max_total_per_device {
+            return Err(DescriptorLimits::exceeded(
+                "per-device read budget",
+                self.bytes_read,
+                self.limits.max_total_per_device,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Allocate USB3 bulk streams on `endpoints` (UASP command/data/status
+ * pipes). Returns the number of streams the controller actually
+ * granted, which may be lower than requested. Linux-only for now;
+ * other platforms get Unsupported.
+ */
+#[cfg(target_os = "linux")]
+pub fn alloc_streams<C: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<C>,
+    endpoints: &[u8],
+    num_streams: u32,
+) -> Result<u32, UsbError> {
+    let mut endpoints = endpoints.to_vec();
+    let rc = unsafe {
+        rusb::ffi::libusb_alloc_streams(
+            handle.as_raw(),
+            num_streams,
+            endpoints.as_mut_ptr(),
+            endpoints.len() as i32,
+        )
+    };
+    if rc < 0 {
+        Err(stream_error(rc))
+    } else {
+        Ok(rc as u32)
+    }
+}
+
+/**
+ * Release streams previously allocated on `endpoints`.
+ */
+#[cfg(target_os = "linux")]
+pub fn free_streams<C: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<C>,
+    endpoints: &[u8],
+) -> Result<(), UsbError> {
+    let mut endpoints = endpoints.to_vec();
+    let rc = unsafe {
+        rusb::ffi::libusb_free_streams(handle.as_raw(), endpoints.as_mut_ptr(), endpoints.len() as i32)
+    };
+    if rc < 0 {
+        Err(stream_error(rc))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn alloc_streams<C: rusb::UsbContext>(
+    _handle: &rusb::DeviceHandle<C>,
+    _endpoints: &[u8],
+    _num_streams: u32,
+) -> Result<u32, UsbError> {
+    Err(UsbError::Unsupported(
+        "USB3 bulk streams are only wired up on Linux".to_string(),
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn free_streams<C: rusb::UsbContext>(
+    _handle: &rusb::DeviceHandle<C>,
+    _endpoints: &[u8],
+) -> Result<(), UsbError> {
+    Err(UsbError::Unsupported(
+        "USB3 bulk streams are only wired up on Linux".to_string(),
+    ))
+}
+
+/// Map a raw libusb return code from the stream APIs onto UsbError.
+#[cfg(target_os = "linux")]
+fn stream_error(rc: i32) -> UsbError {
+    use rusb::ffi::constants::*;
+    match rc {
+        LIBUSB_ERROR_NOT_SUPPORTED => UsbError::Unsupported(
+            "controller or kernel does not support bulk streams".to_string(),
+        ),
+        LIBUSB_ERROR_NO_DEVICE => UsbError::Disconnected,
+        LIBUSB_ERROR_TIMEOUT => UsbError::Timeout,
+        LIBUSB_ERROR_PIPE => UsbError::Stall { endpoint: None },
+        LIBUSB_ERROR_BUSY => UsbError::Libusb(rusb::Error::Busy),
+        LIBUSB_ERROR_INVALID_PARAM => UsbError::Libusb(rusb::Error::InvalidParam),
+        LIBUSB_ERROR_NO_MEM => UsbError::Libusb(rusb::Error::NoMem),
+        _ => UsbError::Libusb(rusb::Error::Other),
+    }
+}
+
+/**
+ * Retry behaviour for endpoint transfers.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Fail on the first error; no retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            backoff: Duration::ZERO,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_millis(10),
+        }
+    }
+}
+
+/**
+ * Cooperative abort handle for long-running operations. Clones share
+ * one flag, so a GUI thread can hold a clone and `cancel()` while a
+ * worker drives the transfer; the worker observes the flag between
+ * chunks and between status polls and returns `UsbError::Cancelled`.
+ *
+ * An optional deadline bounds the whole operation: once it passes, the
+ * next check returns `UsbError::Timeout` even if every individual
+ * transfer is still succeeding, just slowly.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Bound the whole operation by `deadline`. The deadline is carried
+    /// per clone, so one shared flag can back budgets of different
+    /// lengths.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Flag the operation for abort. Takes effect at the next check;
+    /// an in-flight transfer runs to its own timeout first.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Err when the operation should stop: `Cancelled` for an explicit
+    /// cancel, `Timeout` for an exceeded deadline.
+    pub fn check(&self) -> Result<(), UsbError> {
+        if self.is_cancelled() {
+            return Err(UsbError::Cancelled);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(UsbError::Timeout);
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Counters accumulated across the lifetime of a transfer object.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferStats {
+    pub attempts: u64,
+    pub retries: u64,
+    pub stalls_cleared: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TransferKind {
+    Bulk,
+    Interrupt,
+}
+
+/// Shared retry/stall/stats core; bulk and interrupt differ only in the
+/// libusb call they dispatch to.
+struct EndpointTransfer<T> {
+    transport: T,
+    kind: TransferKind,
+    retry_policy: RetryPolicy,
+    stall_recovery: bool,
+    cancellation: Option<CancellationToken>,
+    stats: TransferStats,
+}
+
+impl<T: UsbTransport> EndpointTransfer<T> {
+    fn new(transport: T, kind: TransferKind) -> Self {
+        EndpointTransfer {
+            transport,
+            kind,
+            retry_policy: RetryPolicy::default(),
+            stall_recovery: true,
+            cancellation: None,
+            stats: TransferStats::default(),
+        }
+    }
+
+    fn read(&mut self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> Result<usize, UsbError> {
+        let mut attempt = 0;
+        loop {
+            self.check_cancelled()?;
+            self.stats.attempts += 1;
+            let result = match self.kind {
+                TransferKind::Bulk => self.transport.read_bulk(endpoint, buf, timeout),
+                TransferKind::Interrupt => self.transport.read_interrupt(endpoint, buf, timeout),
+            };
+            match result {
+                Ok(n) => {
+                    self.stats.bytes_in += n as u64;
+                    return Ok(n);
+                }
+                Err(e) => {
+                    if let Some(err) = self.handle_error(endpoint, e, &mut attempt) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, endpoint: u8, buf: &[u8], timeout: Duration) -> Result<usize, UsbError> {
+        let mut attempt = 0;
+        loop {
+            self.check_cancelled()?;
+            self.stats.attempts += 1;
+            let result = match self.kind {
+                TransferKind::Bulk => self.transport.write_bulk(endpoint, buf, timeout),
+                TransferKind::Interrupt => self.transport.write_interrupt(endpoint, buf, timeout),
+            };
+            match result {
+                Ok(n) => {
+                    self.stats.bytes_out += n as u64;
+                    return Ok(n);
+                }
+                Err(e) => {
+                    if let Some(err) = self.handle_error(endpoint, e, &mut attempt) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), UsbError> {
+        match &self.cancellation {
+            Some(token) => token.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns Some(error) when the caller should give up, None to retry.
+    fn handle_error(&mut self, endpoint: u8, e: rusb::Error, attempt: &mut u32) -> Option<UsbError> {
+        // A stall detected here is attributable to the endpoint being
+        // driven; record that in the error.
+        let classified = match classify_transfer_error(e) {
+            UsbError::Stall { .. } => UsbError::Stall {
+                endpoint: Some(endpoint),
+            },
+            other => other,
+        };
+
+        if matches!(classified, UsbError::Stall { .. })
+            && self.stall_recovery
+            && self.transport.clear_halt(endpoint).is_ok()
+        {
+            self.stats.stalls_cleared += 1;
+        }
+
+        if *attempt >= self.retry_policy.max_retries || !classified.is_retryable() {
+            return Some(classified);
+        }
+
+        *attempt += 1;
+        self.stats.retries += 1;
+        if !self.retry_policy.backoff.is_zero() {
+            thread::sleep(self.retry_policy.backoff);
+        }
+        None
+    }
+}
+
+/**
+ * Bulk endpoint transfers with retry, stall recovery and stats.
+ */
+pub struct BulkTransfer<T: UsbTransport> {
+    inner: EndpointTransfer<T>,
+    zlp: bool,
+    max_packet_size: usize,
+}
+
+/// wMaxPacketSize of a high-speed bulk endpoint, the overwhelmingly
+/// common case; use `with_max_packet_size` with the value from
+/// `endpoints::find_endpoints` when it differs.
+const DEFAULT_BULK_MAX_PACKET: usize = 512;
+
+impl<T: UsbTransport> BulkTransfer<T> {
+    pub fn new(transport: T) -> Self {
+        BulkTransfer {
+            inner: EndpointTransfer::new(transport, TransferKind::Bulk),
+            zlp: false,
+            max_packet_size: DEFAULT_BULK_MAX_PACKET,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.inner.retry_policy = policy;
+        self
+    }
+
+    /// Enable or disable automatic CLEAR_FEATURE(ENDPOINT_HALT) on a
+    /// stall before retrying. On by default; protocols that use stalls
+    /// semantically (e.g. MTP transaction cancel) should disable it.
+    pub fn with_stall_recovery(mut self, enabled: bool) -> Self {
+        self.inner.stall_recovery = enabled;
+        self
+    }
+
+    /// Append a zero-length packet when a write's total length is an
+    /// exact multiple of the endpoint's max packet size. Off by
+    /// default; protocols with length-delimited framing (MTP, some
+    /// fastboot stacks) need it or the peer waits forever for the
+    /// transfer to complete.
+    pub fn with_zlp(mut self, enabled: bool) -> Self {
+        self.zlp = enabled;
+        self
+    }
+
+    /// The endpoint's wMaxPacketSize, used to decide when a ZLP is due
+    /// (default 512, the high-speed bulk size).
+    pub fn with_max_packet_size(mut self, max_packet_size: usize) -> Self {
+        self.max_packet_size = max_packet_size.max(1);
+        self
+    }
+
+    /// Observe `token` between transfers, so another thread can abort
+    /// a long chunked operation.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.inner.cancellation = Some(token);
+        self
+    }
+
+    pub fn read(&mut self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> Result<usize, UsbError> {
+        self.inner.read(endpoint, buf, timeout)
+    }
+
+    /// Read until `buf` is full, checking cancellation between reads.
+    /// A zero-length read before the buffer fills is an error: the
+    /// stream ended short of what the protocol promised.
+    pub fn read_exact(
+        &mut self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<(), UsbError> {
+        let expected = buf.len();
+        let mut filled = 0;
+        while filled < expected {
+            let n = self.inner.read(endpoint, &mut buf[filled..], timeout)?;
+            if n == 0 {
+                return Err(UsbError::TransferIncomplete {
+                    expected,
+                    actual: filled,
+                });
+            }
+            filled += n;
+        }
+        Ok(())
+    }
+
+    pub fn write(&mut self, endpoint: u8, buf: &[u8], timeout: Duration) -> Result<usize, UsbError> {
+        let written = self.inner.write(endpoint, buf, timeout)?;
+        // A short write is not a packet-size multiple of interest; the
+        // caller sees the count and decides what to do.
+        if written == buf.len() {
+            self.write_zlp_if_needed(endpoint, buf.len(), timeout)?;
+        }
+        Ok(written)
+    }
+
+    fn write_zlp_if_needed(
+        &mut self,
+        endpoint: u8,
+        total: usize,
+        timeout: Duration,
+    ) -> Result<(), UsbError> {
+        if self.zlp && total > 0 && total.is_multiple_of(self.max_packet_size) {
+            self.inner.write(endpoint, &[], timeout)?;
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> &TransferStats {
+        &self.inner.stats
+    }
+
+    /// Direct access to the transport, for protocols that multiplex
+    /// bulk and interrupt endpoints over the same device handle.
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.inner.transport
+    }
+
+    /**
+     * Write the whole buffer in chunks, reporting progress after each
+     * one. Returns the byte count on success.
+     */
+    pub fn write_all_with_progress(
+        &mut self,
+        endpoint: u8,
+        data: &[u8],
+        chunk_size: usize,
+        timeout: Duration,
+        mut sink: Option<&mut dyn ProgressSink>,
+    ) -> Result<usize, UsbError> {
+        let tracker = ProgressTracker::new(data.len());
+        let mut done = 0;
+        for chunk in data.chunks(chunk_size.max(1)) {
+            let written = self.inner.write(endpoint, chunk, timeout)?;
+            if written < chunk.len() {
+                // A short write would silently corrupt the stream; the
+                // caller decides whether to restart (it is retryable).
+                return Err(UsbError::TransferIncomplete {
+                    expected: chunk.len(),
+                    actual: written,
+                });
+            }
+            done += chunk.len();
+            tracker.report(done, &mut sink);
+        }
+        // One ZLP for the whole payload, not per chunk: the chunking is
+        // an implementation detail, the transfer boundary is not.
+        self.write_zlp_if_needed(endpoint, done, timeout)?;
+        Ok(done)
+    }
+}
+
+/**
+ * Interrupt endpoint transfers; same retry, stall and stats behaviour
+ * as BulkTransfer.
+ */
+pub struct InterruptTransfer<T: UsbTransport> {
+    inner: EndpointTransfer<T>,
+}
+
+impl<T: UsbTransport> InterruptTransfer<T> {
+    pub fn new(transport: T) -> Self {
+        InterruptTransfer {
+            inner: EndpointTransfer::new(transport, TransferKind::Interrupt),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.inner.retry_policy = policy;
+        self
+    }
+
+    /// Enable or disable automatic CLEAR_FEATURE(ENDPOINT_HALT) on a
+    /// stall before retrying. On by default; protocols that use stalls
+    /// semantically (e.g. MTP transaction cancel) should disable it.
+    pub fn with_stall_recovery(mut self, enabled: bool) -> Self {
+        self.inner.stall_recovery = enabled;
+        self
+    }
+
+    /// Observe `token` between transfers; see
+    /// `BulkTransfer::with_cancellation`.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.inner.cancellation = Some(token);
+        self
+    }
+
+    pub fn read(&mut self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> Result<usize, UsbError> {
+        self.inner.read(endpoint, buf, timeout)
+    }
+
+    pub fn write(&mut self, endpoint: u8, buf: &[u8], timeout: Duration) -> Result<usize, UsbError> {
+        self.inner.write(endpoint, buf, timeout)
+    }
+
+    /**
+     * Non-blocking poll: a single attempt (no retries) with a 1ms timeout.
+     * Returns Ok(None) when no report was ready.
+     */
+    pub fn try_read(&mut self, endpoint: u8, buf: &mut [u8]) -> Result<Option<usize>, UsbError> {
+        let saved = self.inner.retry_policy;
+        self.inner.retry_policy = RetryPolicy::none();
+        let result = self.inner.read(endpoint, buf, Duration::from_millis(1));
+        self.inner.retry_policy = saved;
+        match result {
+            Ok(n) => Ok(Some(n)),
+            Err(UsbError::Timeout) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn stats(&self) -> &TransferStats {
+        &self.inner.stats
+    }
+}
+
+/**
+ * Repeated reads of an interrupt IN endpoint where a timeout means
+ * "nothing yet" rather than an error - the shape of device event pipes
+ * (PTP events, HID reports).
+ */
+pub struct InterruptPoller<T: UsbTransport> {
+    inner: InterruptTransfer<T>,
+    endpoint: u8,
+    read_timeout: Duration,
+}
+
+impl<T: UsbTransport> InterruptPoller<T> {
+    pub fn new(transport: T, endpoint: u8, read_timeout: Duration) -> Self {
+        InterruptPoller {
+            inner: InterruptTransfer::new(transport),
+            endpoint,
+            read_timeout,
+        }
+    }
+
+    /// One read attempt; Ok(None) when the endpoint timed out.
+    pub fn poll_once(&mut self, buf: &mut [u8]) -> Result<Option<usize>, UsbError> {
+        match self.inner.read(self.endpoint, buf, self.read_timeout) {
+            Ok(n) => Ok(Some(n)),
+            Err(UsbError::Timeout) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /**
+     * Read packets into `buf` and hand each to the handler until it
+     * returns false or the endpoint fails. Timeouts keep polling.
+     */
+    pub fn run(
+        &mut self,
+        buf: &mut [u8],
+        mut handler: impl FnMut(&[u8]) -> bool,
+    ) -> Result<(), UsbError> {
+        loop {
+            if let Some(n) = self.poll_once(buf)? {
+                if !handler(&buf[..n]) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/**
+ * A point-in-time snapshot of a long transfer, with the derived
+ * numbers every caller was computing by hand.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferProgress {
+    pub bytes_done: usize,
+    pub bytes_total: usize,
+    pub elapsed: Duration,
+    /// Mean rate since the transfer started; 0.0 until time passes.
+    pub bytes_per_sec: f64,
+    /// Remaining time at the mean rate; None until a rate exists.
+    pub eta: Option<Duration>,
+}
+
+impl TransferProgress {
+    /// Derive rate and ETA from the raw counters.
+    pub fn compute(bytes_done: usize, bytes_total: usize, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let bytes_per_sec = if secs > 0.0 {
+            bytes_done as f64 / secs
+        } else {
+            0.0
+        };
+        let eta = if bytes_per_sec > 0.0 && bytes_total >= bytes_done {
+            Some(Duration::from_secs_f64(
+                (bytes_total - bytes_done) as f64 / bytes_per_sec,
+            ))
+        } else {
+            None
+        };
+        TransferProgress {
+            bytes_done,
+            bytes_total,
+            elapsed,
+            bytes_per_sec,
+            eta,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.bytes_done >= self.bytes_total
+    }
+}
+
+/**
+ * Receiver for transfer progress. Closures taking a &TransferProgress
+ * implement this directly.
+ */
+pub trait ProgressSink {
+    fn on_progress(&mut self, progress: &TransferProgress);
+}
+
+impl<F: FnMut(&TransferProgress)> ProgressSink for F {
+    fn on_progress(&mut self, progress: &TransferProgress) {
+        self(progress)
+    }
+}
+
+/**
+ * Rate-limits another sink: at most one emission per interval, except
+ * the final (complete) update which always goes through.
+ */
+pub struct ThrottledSink<S: ProgressSink> {
+    inner: S,
+    min_interval: Duration,
+    /// `elapsed` of the last forwarded update.
+    last_emit: Option<Duration>,
+}
+
+impl<S: ProgressSink> ThrottledSink<S> {
+    pub fn new(inner: S, min_interval: Duration) -> Self {
+        ThrottledSink {
+            inner,
+            min_interval,
+            last_emit: None,
+        }
+    }
+}
+
+impl<S: ProgressSink> ProgressSink for ThrottledSink<S> {
+    fn on_progress(&mut self, progress: &TransferProgress) {
+        let due = match self.last_emit {
+            None => true,
+            Some(last) => progress.elapsed.saturating_sub(last) >= self.min_interval,
+        };
+        if due || progress.is_complete() {
+            self.last_emit = Some(progress.elapsed);
+            self.inner.on_progress(progress);
+        }
+    }
+}
+
+/**
+ * Produces TransferProgress snapshots against a wall-clock start time.
+ */
+pub struct ProgressTracker {
+    started: std::time::Instant,
+    bytes_total: usize,
+}
+
+impl ProgressTracker {
+    pub fn new(bytes_total: usize) -> Self {
+        ProgressTracker {
+            started: std::time::Instant::now(),
+            bytes_total,
+        }
+    }
+
+    pub fn sample(&self, bytes_done: usize) -> TransferProgress {
+        TransferProgress::compute(bytes_done, self.bytes_total, self.started.elapsed())
+    }
+
+    /// Emit a sample to an optional sink; a no-op without one.
+    pub fn report(&self, bytes_done: usize, sink: &mut Option<&mut dyn ProgressSink>) {
+        if let Some(sink) = sink {
+            sink.on_progress(&self.sample(bytes_done));
+        }
+    }
+}
+
+/// Scripted `UsbTransport` for testing protocol state machines without
+/// hardware; available to dependents under the `test-fixtures` feature.
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod mock {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// One control request observed by the mock, for ordering and
+    /// encoding assertions.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ControlRequest {
+        pub request_type: u8,
+        pub request: u8,
+        pub value: u16,
+        pub index: u16,
+        pub data: Vec<u8>,
+    }
+
+    /// Scripted transport: each read/write pops the next outcome.
+    #[derive(Default)]
+    pub struct MockTransport {
+        pub read_results: VecDeque<Result<Vec<u8>, rusb::Error>>,
+        pub write_results: VecDeque<Result<usize, rusb::Error>>,
+        pub control_read_results: VecDeque<Result<Vec<u8>, rusb::Error>>,
+        pub control_requests: Vec<ControlRequest>,
+        pub clear_halt_calls: usize,
+        /// Lengths handed to write_bulk, in order, for framing (ZLP)
+        /// assertions.
+        pub bulk_writes: Vec<usize>,
+    }
+
+    impl MockTransport {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        fn pop_read(&mut self, buf: &mut [u8]) -> Result<usize, rusb::Error> {
+            match self.read_results.pop_front() {
+                Some(Ok(data)) => {
+                    let n = data.len().min(buf.len());
+                    buf[..n].copy_from_slice(&data[..n]);
+                    Ok(n)
+                }
+                Some(Err(e)) => Err(e),
+                None => Err(rusb::Error::Timeout),
+            }
+        }
+
+        fn pop_write(&mut self) -> Result<usize, rusb::Error> {
+            self.write_results.pop_front().unwrap_or(Err(rusb::Error::Timeout))
+        }
+    }
+
+    impl UsbTransport for MockTransport {
+        fn read_bulk(
+            &mut self,
+            _endpoint: u8,
+            buf: &mut [u8],
+            _timeout: Duration,
+        ) -> Result<usize, rusb::Error> {
+            self.pop_read(buf)
+        }
+
+        fn write_bulk(
+            &mut self,
+            _endpoint: u8,
+            buf: &[u8],
+            _timeout: Duration,
+        ) -> Result<usize, rusb::Error> {
+            self.bulk_writes.push(buf.len());
+            self.pop_write()
+        }
+
+        fn read_interrupt(
+            &mut self,
+            _endpoint: u8,
+            buf: &mut [u8],
+            _timeout: Duration,
+        ) -> Result<usize, rusb::Error> {
+            self.pop_read(buf)
+        }
+
+        fn write_interrupt(
+            &mut self,
+            _endpoint: u8,
+            _buf: &[u8],
+            _timeout: Duration,
+        ) -> Result<usize, rusb::Error> {
+            self.pop_write()
+        }
+
+        fn clear_halt(&mut self, _endpoint: u8) -> Result<(), rusb::Error> {
+            self.clear_halt_calls += 1;
+            Ok(())
+        }
+
+        fn read_control(
+            &mut self,
+            request_type: u8,
+            request: u8,
+            value: u16,
+            index: u16,
+            buf: &mut [u8],
+            _timeout: Duration,
+        ) -> Result<usize, rusb::Error> {
+            self.control_requests.push(ControlRequest {
+                request_type,
+                request,
+                value,
+                index,
+                data: Vec::new(),
+            });
+            match self.control_read_results.pop_front() {
+                Some(Ok(data)) => {
+                    let n = data.len().min(buf.len());
+                    buf[..n].copy_from_slice(&data[..n]);
+                    Ok(n)
+                }
+                Some(Err(e)) => Err(e),
+                None => Err(rusb::Error::Timeout),
+            }
+        }
+
+        fn write_control(
+            &mut self,
+            request_type: u8,
+            request: u8,
+            value: u16,
+            index: u16,
+            buf: &[u8],
+            _timeout: Duration,
+        ) -> Result<usize, rusb::Error> {
+            self.control_requests.push(ControlRequest {
+                request_type,
+                request,
+                value,
+                index,
+                data: buf.to_vec(),
+            });
+            Ok(buf.len())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockTransport;
+    use super::*;
+
+    fn fast_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_progress_rate_and_eta() {
+        let progress = TransferProgress::compute(1000, 3000, Duration::from_secs(2));
+        assert_eq!(progress.bytes_per_sec, 500.0);
+        assert_eq!(progress.eta, Some(Duration::from_secs(4)));
+        assert!(!progress.is_complete());
+
+        // No time elapsed yet: no rate, no ETA.
+        let fresh = TransferProgress::compute(0, 3000, Duration::ZERO);
+        assert_eq!(fresh.bytes_per_sec, 0.0);
+        assert_eq!(fresh.eta, None);
+
+        let done = TransferProgress::compute(3000, 3000, Duration::from_secs(6));
+        assert!(done.is_complete());
+        assert_eq!(done.eta, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_throttled_sink_rate_limits_by_elapsed() {
+        // Drive the sink with hand-built samples so the clock is ours.
+        let mut seen = Vec::new();
+        {
+            let mut sink = ThrottledSink::new(
+                |p: &TransferProgress| seen.push(p.bytes_done),
+                Duration::from_millis(100),
+            );
+            for (done, elapsed_ms) in [(10, 0u64), (20, 50), (30, 80), (40, 200), (50, 250)] {
+                sink.on_progress(&TransferProgress::compute(
+                    done,
+                    100,
+                    Duration::from_millis(elapsed_ms),
+                ));
+            }
+            // The final (complete) update bypasses the throttle.
+            sink.on_progress(&TransferProgress::compute(
+                100,
+                100,
+                Duration::from_millis(260),
+            ));
+        }
+        assert_eq!(seen, vec![10, 40, 100]);
+    }
+
+    #[test]
+    fn test_chunked_write_reports_progress() {
+        let mut transport = MockTransport::new();
+        for _ in 0..3 {
+            transport.write_results.push_back(Ok(4));
+        }
+
+        let mut bulk = BulkTransfer::new(transport);
+        let mut seen = Vec::new();
+        {
+            let mut sink = |p: &TransferProgress| seen.push((p.bytes_done, p.bytes_total));
+            let written = bulk
+                .write_all_with_progress(
+                    0x01,
+                    &[0u8; 10],
+                    4,
+                    Duration::from_millis(5),
+                    Some(&mut sink),
+                )
+                .unwrap();
+            assert_eq!(written, 10);
+        }
+        assert_eq!(seen, vec![(4, 10), (8, 10), (10, 10)]);
+    }
+
+    #[test]
+    fn test_zlp_at_packet_size_multiples() {
+        for payload in [512usize, 1024] {
+            let mut transport = MockTransport::new();
+            transport.write_results.push_back(Ok(payload));
+            transport.write_results.push_back(Ok(0));
+
+            let mut bulk = BulkTransfer::new(transport).with_zlp(true);
+            let n = bulk
+                .write(0x01, &vec![0u8; payload], Duration::from_millis(5))
+                .unwrap();
+            assert_eq!(n, payload);
+            assert_eq!(bulk.transport_mut().bulk_writes, vec![payload, 0]);
+        }
+    }
+
+    #[test]
+    fn test_no_zlp_off_boundary_or_when_disabled() {
+        // 100 bytes is not a packet-size multiple: no ZLP.
+        let mut transport = MockTransport::new();
+        transport.write_results.push_back(Ok(100));
+        let mut bulk = BulkTransfer::new(transport).with_zlp(true);
+        bulk.write(0x01, &[0u8; 100], Duration::from_millis(5)).unwrap();
+        assert_eq!(bulk.transport_mut().bulk_writes, vec![100]);
+
+        // 512 bytes with ZLP left at the default (off): no ZLP either.
+        let mut transport = MockTransport::new();
+        transport.write_results.push_back(Ok(512));
+        let mut bulk = BulkTransfer::new(transport);
+        bulk.write(0x01, &[0u8; 512], Duration::from_millis(5)).unwrap();
+        assert_eq!(bulk.transport_mut().bulk_writes, vec![512]);
+    }
+
+    #[test]
+    fn test_chunked_write_emits_one_trailing_zlp() {
+        let mut transport = MockTransport::new();
+        transport.write_results.push_back(Ok(512));
+        transport.write_results.push_back(Ok(512));
+        transport.write_results.push_back(Ok(0));
+
+        let mut bulk = BulkTransfer::new(transport)
+            .with_zlp(true)
+            .with_max_packet_size(1024);
+        let written = bulk
+            .write_all_with_progress(0x01, &[0u8; 1024], 512, Duration::from_millis(5), None)
+            .unwrap();
+        assert_eq!(written, 1024);
+        // The 512-byte chunks do not each get a ZLP; the 1024-byte
+        // transfer gets exactly one.
+        assert_eq!(bulk.transport_mut().bulk_writes, vec![512, 512, 0]);
+    }
+
+    #[test]
+    fn test_cancel_mid_stream_stops_within_one_chunk() {
+        let mut transport = MockTransport::new();
+        for _ in 0..3 {
+            transport.write_results.push_back(Ok(4));
+        }
+
+        let token = CancellationToken::new();
+        let cancel_handle = token.clone();
+        let mut bulk = BulkTransfer::new(transport).with_cancellation(token);
+        // Stand-in for a GUI thread: cancel as soon as the first chunk
+        // completes.
+        let mut sink = move |_: &TransferProgress| cancel_handle.cancel();
+        let err = bulk
+            .write_all_with_progress(0x01, &[0u8; 12], 4, Duration::from_millis(5), Some(&mut sink))
+            .unwrap_err();
+        assert!(matches!(err, UsbError::Cancelled));
+        assert_eq!(bulk.transport_mut().bulk_writes, vec![4]);
+    }
+
+    #[test]
+    fn test_deadline_bounds_the_whole_operation() {
+        let mut transport = MockTransport::new();
+        transport.write_results.push_back(Ok(4));
+
+        let token = CancellationToken::new()
+            .with_deadline(Instant::now() - Duration::from_millis(1));
+        let mut bulk = BulkTransfer::new(transport).with_cancellation(token);
+        let err = bulk
+            .write(0x01, &[0u8; 4], Duration::from_millis(5))
+            .unwrap_err();
+        assert!(matches!(err, UsbError::Timeout));
+        assert!(bulk.transport_mut().bulk_writes.is_empty());
+    }
+
+    #[test]
+    fn test_read_exact_fills_across_short_reads() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(vec![1, 2]));
+        transport.read_results.push_back(Ok(vec![3, 4, 5]));
+
+        let mut bulk = BulkTransfer::new(transport);
+        let mut buf = [0u8; 5];
+        bulk.read_exact(0x81, &mut buf, Duration::from_millis(5)).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5]);
+
+        // A zero-length read before the buffer fills is an error.
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Ok(vec![1, 2]));
+        transport.read_results.push_back(Ok(vec![]));
+        let mut bulk = BulkTransfer::new(transport);
+        let mut buf = [0u8; 5];
+        let err = bulk
+            .read_exact(0x81, &mut buf, Duration::from_millis(5))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            UsbError::TransferIncomplete {
+                expected: 5,
+                actual: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn test_chunked_write_errors_on_short_write() {
+        let mut transport = MockTransport::new();
+        transport.write_results.push_back(Ok(4));
+        transport.write_results.push_back(Ok(1));
+
+        let mut bulk = BulkTransfer::new(transport);
+        let err = bulk
+            .write_all_with_progress(0x01, &[0u8; 10], 4, Duration::from_millis(5), None)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            UsbError::TransferIncomplete {
+                expected: 4,
+                actual: 1
+            }
+        ));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_bulk_read_retries_transient_errors() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Err(rusb::Error::Timeout));
+        transport.read_results.push_back(Ok(vec![1, 2, 3]));
+
+        let mut bulk = BulkTransfer::new(transport).with_retry_policy(fast_policy(3));
+        let mut buf = [0u8; 8];
+        let n = bulk.read(0x81, &mut buf, Duration::from_millis(5)).unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(bulk.stats().retries, 1);
+        assert_eq!(bulk.stats().bytes_in, 3);
+    }
+
+    #[test]
+    fn test_interrupt_read_retries_like_bulk() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Err(rusb::Error::Timeout));
+        transport.read_results.push_back(Ok(vec![9]));
+
+        let mut intr = InterruptTransfer::new(transport).with_retry_policy(fast_policy(3));
+        let mut buf = [0u8; 8];
+        let n = intr.read(0x83, &mut buf, Duration::from_millis(5)).unwrap();
+        assert_eq!(n, 1);
+        assert_eq!(intr.stats().retries, 1);
+    }
+
+    #[test]
+    fn test_interrupt_stall_cleared_and_retried() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Err(rusb::Error::Pipe));
+        transport.read_results.push_back(Ok(vec![7, 7]));
+
+        let mut intr = InterruptTransfer::new(transport).with_retry_policy(fast_policy(3));
+        let mut buf = [0u8; 8];
+        intr.read(0x83, &mut buf, Duration::from_millis(5)).unwrap();
+        assert_eq!(intr.stats().stalls_cleared, 1);
+    }
+
+    #[test]
+    fn test_bulk_stall_clears_halt_once_then_retries() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Err(rusb::Error::Pipe));
+        transport.read_results.push_back(Ok(vec![1, 2, 3]));
+
+        let mut bulk = BulkTransfer::new(transport).with_retry_policy(fast_policy(3));
+        let mut buf = [0u8; 8];
+        let n = bulk.read(0x81, &mut buf, Duration::from_millis(5)).unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(bulk.stats().stalls_cleared, 1);
+        assert_eq!(bulk.transport_mut().clear_halt_calls, 1);
+    }
+
+    #[test]
+    fn test_stall_recovery_opt_out_skips_clear_halt() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Err(rusb::Error::Pipe));
+        transport.read_results.push_back(Ok(vec![1]));
+
+        let mut bulk = BulkTransfer::new(transport)
+            .with_retry_policy(fast_policy(3))
+            .with_stall_recovery(false);
+        let mut buf = [0u8; 8];
+        // The stall is still retryable; only the halt clear is skipped.
+        bulk.read(0x81, &mut buf, Duration::from_millis(5)).unwrap();
+        assert_eq!(bulk.stats().stalls_cleared, 0);
+        assert_eq!(bulk.transport_mut().clear_halt_calls, 0);
+    }
+
+    #[test]
+    fn test_fatal_error_not_retried() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Err(rusb::Error::NoDevice));
+        transport.read_results.push_back(Ok(vec![1]));
+
+        let mut intr = InterruptTransfer::new(transport).with_retry_policy(fast_policy(3));
+        let mut buf = [0u8; 8];
+        let err = intr.read(0x83, &mut buf, Duration::from_millis(5)).unwrap_err();
+        assert!(matches!(err, UsbError::Disconnected));
+        assert_eq!(intr.stats().retries, 0);
+    }
+
+    #[test]
+    fn test_try_read_maps_timeout_to_none() {
+        let mut transport = MockTransport::new();
+        transport.read_results.push_back(Err(rusb::Error::Timeout));
+
+        let mut intr = InterruptTransfer::new(transport);
+        let mut buf = [0u8; 8];
+        assert_eq!(intr.try_read(0x83, &mut buf).unwrap(), None);
+        // try_read must not consume the configured retry budget
+        assert_eq!(intr.stats().retries, 0);
+    }
+
+    #[test]
+    fn test_config_descriptor_limit_stops_at_the_header() {
+        let mut transport = MockTransport::new();
+        // Config header claiming wTotalLength 0xffff.
+        transport
+            .control_read_results
+            .push_back(Ok(vec![0x09, 0x02, 0xff, 0xff, 0x01, 0x01, 0x00, 0xc0, 0x32]));
+
+        let mut control = ControlTransfer::new(transport);
+        let err = control
+            .read_config_descriptor(0, Duration::from_millis(5))
+            .unwrap_err();
+        assert!(DescriptorLimits::is_limit_error(&err), "got {}", err);
+        // Only the 9-byte header was ever requested; the 64 KiB claim
+        // was never fetched or allocated.
+        assert_eq!(control.transport.control_requests.len(), 1);
+        assert!(control.bytes_read() < 64);
+    }
+
+    #[test]
+    fn test_config_descriptor_within_limit_is_read_fully() {
+        let mut transport = MockTransport::new();
+        let full: Vec<u8> = vec![0x09, 0x02, 0x12, 0x00, 0x01, 0x01, 0x00, 0xc0, 0x32]
+            .into_iter()
+            .chain([0x09, 0x04, 0x00, 0x00, 0x00, 0xfe, 0x01, 0x02, 0x04])
+            .collect();
+        transport.control_read_results.push_back(Ok(full[..9].to_vec()));
+        transport.control_read_results.push_back(Ok(full.clone()));
+
+        let mut control = ControlTransfer::new(transport);
+        let read = control
+            .read_config_descriptor(0, Duration::from_millis(5))
+            .unwrap();
+        assert_eq!(read, full);
+    }
+
+    #[test]
+    fn test_string_descriptor_over_claim_is_an_error() {
+        let mut transport = MockTransport::new();
+        transport
+            .control_read_results
+            .push_back(Ok(vec![0xff, 0x03, 0x41, 0x00]));
+
+        let mut control = ControlTransfer::new(transport).with_limits(DescriptorLimits {
+            max_string_len: 64,
+            ..Default::default()
+        });
+        let err = control
+            .read_string_descriptor(2, 0x0409, Duration::from_millis(5))
+            .unwrap_err();
+        assert!(DescriptorLimits::is_limit_error(&err));
+    }
+
+    #[test]
+    fn test_per_device_budget_is_enforced() {
+        let mut transport = MockTransport::new();
+        for _ in 0..8 {
+            transport
+                .control_read_results
+                .push_back(Ok(vec![0x04, 0x03, 0x41, 0x00]));
+        }
+
+        let mut control = ControlTransfer::new(transport).with_limits(DescriptorLimits {
+            max_string_len: 255,
+            max_total_per_device: 600,
+            ..Default::default()
+        });
+        let mut last = Ok(Vec::new());
+        for _ in 0..8 {
+            last = control.read_string_descriptor(2, 0x0409, Duration::from_millis(5));
+            if last.is_err() {
+                break;
+            }
+        }
+        let err = last.unwrap_err();
+        assert!(DescriptorLimits::is_limit_error(&err), "got {}", err);
+        // The budget tripped on the third 255-byte reservation.
+        assert!(control.transport.control_requests.len() <= 3);
+    }
+
+    // Allocates streams on the first UAS-capable device found. Needs a
+    // real xHCI controller and an attached UASP enclosure:
+    //     cargo test -p bootforge-usb stream -- --ignored --nocapture
+    #[test]
+    #[ignore = "needs an xHCI host and a UASP device"]
+    #[cfg(target_os = "linux")]
+    fn test_live_stream_allocation_round_trip() {
+        let devices = match rusb::devices() {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("skipping: cannot enumerate ({})", e);
+                return;
+            }
+        };
+        for device in devices.iter() {
+            let Ok(descriptor) = device.device_descriptor() else {
+                continue;
+            };
+            if descriptor.class_code() != 0x08 {
+                continue;
+            }
+            let Ok(handle) = device.open() else { continue };
+            // UASP uses four stream-capable bulk pipes; 2 streams is a
+            // conservative probe.
+            match alloc_streams(&handle, &[0x81, 0x02], 2) {
+                Ok(granted) => {
+                    println!("controller granted {} streams", granted);
+                    free_streams(&handle, &[0x81, 0x02]).unwrap();
+                }
+                Err(e) => println!("stream allocation refused: {}", e),
+            }
+            return;
+        }
+        eprintln!("skipping: no mass-storage device attached");
+    }
+
+    #[test]
+    fn test_interrupt_write_counts_bytes() {
+        let mut transport = MockTransport::new();
+        transport.write_results.push_back(Ok(4));
+
+        let mut intr = InterruptTransfer::new(transport);
+        let n = intr
+            .write(0x03, &[0, 1, 2, 3], Duration::from_millis(5))
+            .unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(intr.stats().bytes_out, 4);
+    }
+}
@@ -0,0 +1,373 @@
+// BootForge USB - USB Type-C port control
+// Reads and swaps data/power roles of dual-role (OTG/DRD) ports through
+// /sys/class/typec. The sysfs root is injectable so capability parsing
+// and refusal paths are testable against fixture trees.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::enumeration::enumerate_libusb;
+use crate::error::UsbError;
+use crate::events::DeviceEvent;
+use crate::registry::DeviceRegistry;
+
+/**
+ * Data role of a Type-C port.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataRole {
+    Host,
+    Device,
+}
+
+impl fmt::Display for DataRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DataRole::Host => "host",
+            DataRole::Device => "device",
+        })
+    }
+}
+
+impl FromStr for DataRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "host" => Ok(DataRole::Host),
+            "device" => Ok(DataRole::Device),
+            other => Err(format!("unknown data role {:?}", other)),
+        }
+    }
+}
+
+/**
+ * Power role of a Type-C port.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerRole {
+    Source,
+    Sink,
+}
+
+impl fmt::Display for PowerRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PowerRole::Source => "source",
+            PowerRole::Sink => "sink",
+        })
+    }
+}
+
+impl FromStr for PowerRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "source" => Ok(PowerRole::Source),
+            "sink" => Ok(PowerRole::Sink),
+            other => Err(format!("unknown power role {:?}", other)),
+        }
+    }
+}
+
+/**
+ * Supported and currently-active values of one role attribute.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleCapability<R> {
+    pub supported: Vec<R>,
+    pub active: R,
+}
+
+impl<R: PartialEq + Copy> RoleCapability<R> {
+    /// Whether the port can take `role` at all.
+    pub fn supports(&self, role: R) -> bool {
+        self.supported.contains(&role)
+    }
+
+    /// A port that lists a single value cannot swap.
+    pub fn can_swap(&self) -> bool {
+        self.supported.len() > 1
+    }
+}
+
+/**
+ * Parse a sysfs role attribute: space-separated supported values with
+ * the active one in brackets, e.g. "host [device]" or "[source] sink".
+ */
+pub fn parse_role_attribute<R: FromStr<Err = String> + Copy>(
+    contents: &str,
+) -> Result<RoleCapability<R>, UsbError> {
+    let mut supported = Vec::new();
+    let mut active = None;
+    for token in contents.split_whitespace() {
+        let (value, is_active) = match token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            Some(inner) => (inner, true),
+            None => (token, false),
+        };
+        let role = value.parse().map_err(UsbError::Parse)?;
+        supported.push(role);
+        if is_active {
+            active = Some(role);
+        }
+    }
+    let active = active
+        .ok_or_else(|| UsbError::Parse(format!("no active role in {:?}", contents.trim())))?;
+    Ok(RoleCapability { supported, active })
+}
+
+/**
+ * Access to the Type-C ports exposed under /sys/class/typec.
+ */
+pub struct TypecSysfs {
+    root: PathBuf,
+}
+
+impl Default for TypecSysfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypecSysfs {
+    pub fn new() -> Self {
+        TypecSysfs {
+            root: PathBuf::from("/sys/class/typec"),
+        }
+    }
+
+    /// Use an alternate sysfs root (fixture trees in tests).
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        TypecSysfs { root: root.into() }
+    }
+
+    /// Port names, e.g. ["port0", "port1"]. Partner/cable entries are
+    /// filtered out.
+    pub fn ports(&self) -> Result<Vec<String>, UsbError> {
+        if !self.root.exists() {
+            return Err(UsbError::Unsupported(format!(
+                "typec class not present: {}",
+                self.root.display()
+            )));
+        }
+        let mut ports: Vec<String> = fs::read_dir(&self.root)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("port") && !name.contains('-'))
+            .collect();
+        ports.sort();
+        Ok(ports)
+    }
+
+    fn read_role<R: FromStr<Err = String> + Copy>(
+        &self,
+        port: &str,
+        attr: &str,
+    ) -> Result<RoleCapability<R>, UsbError> {
+        let path = self.root.join(port).join(attr);
+        if !path.exists() {
+            return Err(UsbError::NotFound(format!(
+                "port {} has no {} attribute",
+                port, attr
+            )));
+        }
+        parse_role_attribute(&fs::read_to_string(path)?)
+    }
+
+    pub fn data_role(&self, port: &str) -> Result<RoleCapability<DataRole>, UsbError> {
+        self.read_role(port, "data_role")
+    }
+
+    pub fn power_role(&self, port: &str) -> Result<RoleCapability<PowerRole>, UsbError> {
+        self.read_role(port, "power_role")
+    }
+
+    fn swap_role<R: FromStr<Err = String> + Copy + PartialEq + fmt::Display>(
+        &self,
+        port: &str,
+        attr: &str,
+        role: R,
+    ) -> Result<(), UsbError> {
+        let capability: RoleCapability<R> = self.read_role(port, attr)?;
+        if !capability.can_swap() {
+            return Err(UsbError::Unsupported(format!(
+                "port {} cannot swap {} (single supported value)",
+                port, attr
+            )));
+        }
+        if !capability.supports(role) {
+            return Err(UsbError::Unsupported(format!(
+                "port {} does not support {} {}",
+                port, attr, role
+            )));
+        }
+        // The kernel rejects the write (EOPNOTSUPP) when the attached
+        // partner refuses the swap; that surfaces as Io.
+        fs::write(self.root.join(port).join(attr), role.to_string())?;
+        Ok(())
+    }
+
+    /**
+     * Swap the data role of `port`, checking the attribute's advertised
+     * capabilities first. Unsupported when the port cannot take the
+     * role; Io when the kernel or the attached partner refuses.
+     */
+    pub fn set_data_role(&self, port: &str, role: DataRole) -> Result<(), UsbError> {
+        self.swap_role(port, "data_role", role)
+    }
+
+    /**
+     * Swap the power role of `port`; same error contract as
+     * `set_data_role`.
+     */
+    pub fn set_power_role(&self, port: &str, role: PowerRole) -> Result<(), UsbError> {
+        self.swap_role(port, "power_role", role)
+    }
+}
+
+/**
+ * Re-enumerate after a successful swap and feed the registry, returning
+ * the Changed/Connected/Disconnected events the topology churn caused.
+ */
+pub fn refresh_after_swap(registry: &mut DeviceRegistry) -> Result<Vec<DeviceEvent>, UsbError> {
+    let snapshot = enumerate_libusb()?;
+    Ok(registry.observe_snapshot(&snapshot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture_root(test: &str) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join(test)
+            .join(format!("{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn write_port(root: &Path, name: &str, data_role: &str, power_role: &str) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("data_role"), format!("{}\n", data_role)).unwrap();
+        fs::write(dir.join("power_role"), format!("{}\n", power_role)).unwrap();
+    }
+
+    #[test]
+    fn test_parse_role_attribute() {
+        let cap: RoleCapability<DataRole> = parse_role_attribute("host [device]\n").unwrap();
+        assert_eq!(cap.active, DataRole::Device);
+        assert_eq!(cap.supported, vec![DataRole::Host, DataRole::Device]);
+        assert!(cap.can_swap());
+
+        let cap: RoleCapability<PowerRole> = parse_role_attribute("[sink]\n").unwrap();
+        assert_eq!(cap.active, PowerRole::Sink);
+        assert!(!cap.can_swap());
+
+        assert!(parse_role_attribute::<DataRole>("host device").is_err());
+        assert!(parse_role_attribute::<DataRole>("[gadget]").is_err());
+    }
+
+    #[test]
+    fn test_port_listing_filters_partners() {
+        let root = fixture_root("typec_listing");
+        write_port(&root, "port0", "[host] device", "[source] sink");
+        write_port(&root, "port1", "[device]", "[sink]");
+        fs::create_dir_all(root.join("port0-partner")).unwrap();
+
+        let ports = TypecSysfs::with_root(&root).ports().unwrap();
+        assert_eq!(ports, vec!["port0", "port1"]);
+    }
+
+    #[test]
+    fn test_swap_writes_the_requested_role() {
+        let root = fixture_root("typec_swap");
+        write_port(&root, "port0", "host [device]", "source [sink]");
+
+        let typec = TypecSysfs::with_root(&root);
+        typec.set_data_role("port0", DataRole::Host).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("port0/data_role")).unwrap(),
+            "host"
+        );
+        typec.set_power_role("port0", PowerRole::Source).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("port0/power_role")).unwrap(),
+            "source"
+        );
+    }
+
+    #[test]
+    fn test_swap_refused_on_fixed_role_port() {
+        let root = fixture_root("typec_fixed");
+        // Device-only port (UFP): no swapping either role.
+        write_port(&root, "port0", "[device]", "[sink]");
+
+        let typec = TypecSysfs::with_root(&root);
+        let err = typec.set_data_role("port0", DataRole::Host).unwrap_err();
+        assert!(matches!(err, UsbError::Unsupported(_)), "got {}", err);
+        let err = typec.set_power_role("port0", PowerRole::Source).unwrap_err();
+        assert!(matches!(err, UsbError::Unsupported(_)), "got {}", err);
+        // The attribute must be untouched after a refusal.
+        assert_eq!(
+            fs::read_to_string(root.join("port0/data_role")).unwrap(),
+            "[device]\n"
+        );
+    }
+
+    #[test]
+    fn test_missing_port_and_attribute() {
+        let root = fixture_root("typec_missing");
+        let typec = TypecSysfs::with_root(&root);
+        assert!(matches!(
+            typec.data_role("port9"),
+            Err(UsbError::NotFound(_))
+        ));
+
+        // Port directory without role attributes (older kernels).
+        fs::create_dir_all(root.join("port0")).unwrap();
+        assert!(matches!(
+            typec.power_role("port0"),
+            Err(UsbError::NotFound(_))
+        ));
+    }
+
+    // Flips the first dual-role port back and forth. Needs real Type-C
+    // hardware and root:
+    //     sudo -E cargo test -p bootforge-usb typec -- --ignored
+    #[test]
+    #[ignore = "needs dual-role Type-C hardware and root"]
+    fn test_live_data_role_swap_round_trip() {
+        let typec = TypecSysfs::new();
+        let Ok(ports) = typec.ports() else {
+            eprintln!("skipping: no typec class on this host");
+            return;
+        };
+        let Some(port) = ports.iter().find(|p| {
+            typec
+                .data_role(p)
+                .map(|c| c.can_swap())
+                .unwrap_or(false)
+        }) else {
+            eprintln!("skipping: no dual-role port present");
+            return;
+        };
+
+        let original = typec.data_role(port).unwrap().active;
+        let flipped = match original {
+            DataRole::Host => DataRole::Device,
+            DataRole::Device => DataRole::Host,
+        };
+        typec.set_data_role(port, flipped).unwrap();
+        let mut registry = DeviceRegistry::new();
+        let _ = refresh_after_swap(&mut registry);
+        typec.set_data_role(port, original).unwrap();
+        assert_eq!(typec.data_role(port).unwrap().active, original);
+    }
+}
@@ -0,0 +1,446 @@
+// BootForge USB - usb.ids database
+// Name resolution from the standard usb.ids database, so devices whose
+// string descriptors cannot be read (commonly permissions) still get
+// human-readable vendor and product names. The system copy is parsed
+// once and cached behind a OnceLock.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::error::UsbError;
+
+/// Where distributions install usb.ids, in search order.
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr/share/hwdata/usb.ids",
+    "/usr/share/misc/usb.ids",
+    "/var/lib/usbutils/usb.ids",
+];
+
+/**
+ * Names resolved from the usb.ids database, carried next to the string
+ * descriptors so consumers can tell "the device said" from "the
+ * database says".
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UsbIds {
+    pub vendor_name: Option<String>,
+    pub product_name: Option<String>,
+    /// Name of the device's class triple, most general to most
+    /// specific ("Mass Storage / SCSI / Bulk-Only"), as far as the
+    /// database knows the levels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct VendorEntry {
+    name: String,
+    products: HashMap<u16, String>,
+}
+
+#[derive(Debug, Default)]
+struct SubclassEntry {
+    name: String,
+    protocols: HashMap<u8, String>,
+}
+
+#[derive(Debug, Default)]
+struct ClassEntry {
+    name: String,
+    subclasses: HashMap<u8, SubclassEntry>,
+}
+
+/**
+ * A parsed usb.ids database: vendor/product names plus the class name
+ * section ("C xx").
+ */
+#[derive(Debug, Default)]
+pub struct UsbIdsDb {
+    vendors: HashMap<u16, VendorEntry>,
+    classes: HashMap<u8, ClassEntry>,
+}
+
+/// What the most recent top-level line opened.
+enum Section {
+    Vendor(u16),
+    Class(u8, Option<u8>),
+    Other,
+}
+
+impl UsbIdsDb {
+    /**
+     * Parse usb.ids text. Unknown sections (HID usages, languages,
+     * audio terminal types, ...) and malformed lines are skipped; the
+     * format is maintained by hand upstream and trailing sections vary
+     * between snapshots.
+     */
+    pub fn parse(text: &str) -> Self {
+        let mut db = UsbIdsDb::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            let trimmed = line.trim_end();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some(rest) = trimmed.strip_prefix("\t\t") {
+                if let (Section::Class(class, Some(subclass)), Some((protocol, name))) =
+                    (&section, split_id_u8(rest))
+                {
+                    if let Some(entry) = db
+                        .classes
+                        .get_mut(class)
+                        .and_then(|c| c.subclasses.get_mut(subclass))
+                    {
+                        entry.protocols.insert(protocol, name.to_string());
+                    }
+                }
+            } else if let Some(rest) = trimmed.strip_prefix('\t') {
+                match &mut section {
+                    Section::Vendor(vid) => {
+                        if let Some((pid, name)) = split_id_u16(rest) {
+                            if let Some(vendor) = db.vendors.get_mut(vid) {
+                                vendor.products.insert(pid, name.to_string());
+                            }
+                        }
+                    }
+                    Section::Class(class, subclass) => {
+                        if let Some((id, name)) = split_id_u8(rest) {
+                            if let Some(entry) = db.classes.get_mut(class) {
+                                entry.subclasses.insert(
+                                    id,
+                                    SubclassEntry {
+                                        name: name.to_string(),
+                                        protocols: HashMap::new(),
+                                    },
+                                );
+                            }
+                            *subclass = Some(id);
+                        }
+                    }
+                    Section::Other => {}
+                }
+            } else if let Some(rest) = trimmed.strip_prefix("C ") {
+                section = match split_id_u8(rest) {
+                    Some((class, name)) => {
+                        db.classes.insert(
+                            class,
+                            ClassEntry {
+                                name: name.to_string(),
+                                subclasses: HashMap::new(),
+                            },
+                        );
+                        Section::Class(class, None)
+                    }
+                    None => Section::Other,
+                };
+            } else if let Some((vid, name)) = split_id_u16(trimmed) {
+                db.vendors.insert(
+                    vid,
+                    VendorEntry {
+                        name: name.to_string(),
+                        products: HashMap::new(),
+                    },
+                );
+                section = Section::Vendor(vid);
+            } else {
+                // Some other section (AT, HID, L, ...): skip until the
+                // next one we understand.
+                section = Section::Other;
+            }
+        }
+
+        db
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, UsbError> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    pub fn lookup_vendor(&self, vendor_id: u16) -> Option<&str> {
+        self.vendors.get(&vendor_id).map(|v| v.name.as_str())
+    }
+
+    pub fn lookup_product(&self, vendor_id: u16, product_id: u16) -> Option<&str> {
+        self.vendors
+            .get(&vendor_id)?
+            .products
+            .get(&product_id)
+            .map(String::as_str)
+    }
+
+    pub fn lookup_class(&self, class: u8) -> Option<&str> {
+        self.classes.get(&class).map(|c| c.name.as_str())
+    }
+
+    pub fn lookup_subclass(&self, class: u8, subclass: u8) -> Option<&str> {
+        self.classes
+            .get(&class)?
+            .subclasses
+            .get(&subclass)
+            .map(|s| s.name.as_str())
+    }
+
+    pub fn lookup_protocol(&self, class: u8, subclass: u8, protocol: u8) -> Option<&str> {
+        self.classes
+            .get(&class)?
+            .subclasses
+            .get(&subclass)?
+            .protocols
+            .get(&protocol)
+            .map(String::as_str)
+    }
+
+    /// The `UsbIds` record for a device, or None when the database
+    /// knows neither name.
+    pub fn resolve(&self, vendor_id: u16, product_id: u16) -> Option<UsbIds> {
+        let vendor_name = self.lookup_vendor(vendor_id).map(str::to_string);
+        let product_name = self.lookup_product(vendor_id, product_id).map(str::to_string);
+        if vendor_name.is_none() && product_name.is_none() {
+            return None;
+        }
+        Some(UsbIds {
+            vendor_name,
+            product_name,
+            class_name: None,
+        })
+    }
+
+    /**
+     * Names for a class triple joined " / ", cut off at the first level
+     * the database does not know. None when even the class is unnamed.
+     */
+    pub fn resolve_class_name(&self, class: u8, subclass: u8, protocol: u8) -> Option<String> {
+        let mut name = self.lookup_class(class)?.to_string();
+        if let Some(sub) = self.lookup_subclass(class, subclass) {
+            name.push_str(" / ");
+            name.push_str(sub);
+            if let Some(proto) = self.lookup_protocol(class, subclass, protocol) {
+                name.push_str(" / ");
+                name.push_str(proto);
+            }
+        }
+        Some(name)
+    }
+
+    /**
+     * As `resolve`, with `class_name` filled from the device's class
+     * triple. None when the database knows nothing about the device.
+     */
+    pub fn resolve_device(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+        class: u8,
+        subclass: u8,
+        protocol: u8,
+    ) -> Option<UsbIds> {
+        let class_name = self.resolve_class_name(class, subclass, protocol);
+        match self.resolve(vendor_id, product_id) {
+            Some(ids) => Some(UsbIds { class_name, ..ids }),
+            None => class_name.map(|name| UsbIds {
+                vendor_name: None,
+                product_name: None,
+                class_name: Some(name),
+            }),
+        }
+    }
+}
+
+/// "18d1  Google Inc." -> (0x18d1, "Google Inc.")
+fn split_id_u16(line: &str) -> Option<(u16, &str)> {
+    let (id, name) = line.split_once(' ')?;
+    Some((u16::from_str_radix(id, 16).ok()?, name.trim()))
+        .filter(|(_, name)| !name.is_empty())
+}
+
+fn split_id_u8(line: &str) -> Option<(u8, &str)> {
+    let (id, name) = line.split_once(' ')?;
+    Some((u8::from_str_radix(id, 16).ok()?, name.trim()))
+        .filter(|(_, name)| !name.is_empty())
+}
+
+/**
+ * The name of a device class code from the built-in table - the common
+ * classes every host knows without a usb.ids copy installed. None for
+ * codes outside the table and for 0x00, which defers the class to the
+ * interfaces and names nothing about the device.
+ */
+pub fn class_code_name(class: u8) -> Option<&'static str> {
+    match class {
+        0x01 => Some("Audio"),
+        0x02 => Some("Communications"),
+        0x03 => Some("HID"),
+        0x05 => Some("Physical"),
+        0x06 => Some("Imaging"),
+        0x07 => Some("Printer"),
+        0x08 => Some("Mass Storage"),
+        0x09 => Some("Hub"),
+        0x0a => Some("CDC Data"),
+        0x0b => Some("Smart Card"),
+        0x0d => Some("Content Security"),
+        0x0e => Some("Video"),
+        0x0f => Some("Personal Healthcare"),
+        0x10 => Some("Audio/Video"),
+        0x11 => Some("Billboard"),
+        0x12 => Some("USB Type-C Bridge"),
+        0xdc => Some("Diagnostic"),
+        0xe0 => Some("Wireless"),
+        0xef => Some("Miscellaneous"),
+        0xfe => Some("Application Specific"),
+        0xff => Some("Vendor Specific"),
+        _ => None,
+    }
+}
+
+/**
+ * The system usb.ids database, parsed on first use. The
+ * `BOOTFORGE_USB_IDS` environment variable names an alternate copy and
+ * outranks the distribution paths; None when no copy is found either
+ * way.
+ */
+pub fn system_db() -> Option<&'static UsbIdsDb> {
+    static SYSTEM_DB: OnceLock<Option<UsbIdsDb>> = OnceLock::new();
+    SYSTEM_DB
+        .get_or_init(|| {
+            std::env::var_os("BOOTFORGE_USB_IDS")
+                .and_then(|path| UsbIdsDb::load(path).ok())
+                .or_else(|| {
+                    SYSTEM_PATHS
+                        .iter()
+                        .find_map(|path| UsbIdsDb::load(path).ok())
+                })
+        })
+        .as_ref()
+}
+
+/// The `UsbIds` record for a device from the system database.
+pub fn resolve_usb_ids(vendor_id: u16, product_id: u16) -> Option<UsbIds> {
+    system_db()?.resolve(vendor_id, product_id)
+}
+
+/// Vendor name from the system database.
+pub fn lookup_vendor(vendor_id: u16) -> Option<&'static str> {
+    system_db()?.lookup_vendor(vendor_id)
+}
+
+/// Product name from the system database.
+pub fn lookup_product(vendor_id: u16, product_id: u16) -> Option<&'static str> {
+    system_db()?.lookup_product(vendor_id, product_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+# usb.ids fixture
+#
+18d1  Google Inc.
+\t4ee1  Nexus/Pixel Device (MTP)
+\t4ee7  Nexus/Pixel Device (charging + debug)
+05ac  Apple, Inc.
+\t12a8  iPhone 5/5C/5S/6/SE/7/8/X/XR
+
+# List of known device classes, subclasses and protocols
+
+C 08  Mass Storage
+\t06  SCSI
+\t\t50  Bulk-Only
+\t\t62  UAS
+C 09  Hub
+\t00  Unused
+\t\t01  Single TT
+
+AT 0100  unknown terminal type
+";
+
+    #[test]
+    fn test_vendor_and_product_lookup() {
+        let db = UsbIdsDb::parse(FIXTURE);
+        assert_eq!(db.lookup_vendor(0x18d1), Some("Google Inc."));
+        assert_eq!(
+            db.lookup_product(0x18d1, 0x4ee7),
+            Some("Nexus/Pixel Device (charging + debug)")
+        );
+        assert_eq!(db.lookup_product(0x05ac, 0x12a8), Some("iPhone 5/5C/5S/6/SE/7/8/X/XR"));
+        assert_eq!(db.lookup_vendor(0xffff), None);
+        assert_eq!(db.lookup_product(0x18d1, 0x0000), None);
+    }
+
+    #[test]
+    fn test_class_section() {
+        let db = UsbIdsDb::parse(FIXTURE);
+        assert_eq!(db.lookup_class(0x08), Some("Mass Storage"));
+        assert_eq!(db.lookup_subclass(0x08, 0x06), Some("SCSI"));
+        assert_eq!(db.lookup_protocol(0x08, 0x06, 0x62), Some("UAS"));
+        assert_eq!(db.lookup_protocol(0x09, 0x00, 0x01), Some("Single TT"));
+        assert_eq!(db.lookup_class(0x03), None);
+    }
+
+    #[test]
+    fn test_unknown_trailing_sections_ignored() {
+        // The AT section at the bottom must not bleed into lookups.
+        let db = UsbIdsDb::parse(FIXTURE);
+        assert_eq!(db.lookup_vendor(0x0100), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let db = UsbIdsDb::parse(FIXTURE);
+        let ids = db.resolve(0x18d1, 0x9999).unwrap();
+        assert_eq!(ids.vendor_name.as_deref(), Some("Google Inc."));
+        assert_eq!(ids.product_name, None);
+        assert_eq!(db.resolve(0x1234, 0x5678), None);
+    }
+
+    #[test]
+    fn test_resolve_class_name_cuts_at_first_unknown_level() {
+        let db = UsbIdsDb::parse(FIXTURE);
+        assert_eq!(
+            db.resolve_class_name(0x08, 0x06, 0x50).as_deref(),
+            Some("Mass Storage / SCSI / Bulk-Only")
+        );
+        // Known subclass, unknown protocol.
+        assert_eq!(
+            db.resolve_class_name(0x08, 0x06, 0x99).as_deref(),
+            Some("Mass Storage / SCSI")
+        );
+        // Unknown subclass keeps the class name alone.
+        assert_eq!(
+            db.resolve_class_name(0x08, 0x42, 0x50).as_deref(),
+            Some("Mass Storage")
+        );
+        assert_eq!(db.resolve_class_name(0x03, 0x00, 0x00), None);
+    }
+
+    #[test]
+    fn test_resolve_device_fills_class_name() {
+        let db = UsbIdsDb::parse(FIXTURE);
+        let ids = db.resolve_device(0x05ac, 0x12a8, 0x09, 0x00, 0x01).unwrap();
+        assert_eq!(ids.vendor_name.as_deref(), Some("Apple, Inc."));
+        assert_eq!(ids.product_name.as_deref(), Some("iPhone 5/5C/5S/6/SE/7/8/X/XR"));
+        assert_eq!(ids.class_name.as_deref(), Some("Hub / Unused / Single TT"));
+
+        // A class name alone still makes a record.
+        let ids = db.resolve_device(0x1234, 0x5678, 0x08, 0x06, 0x50).unwrap();
+        assert_eq!(ids.vendor_name, None);
+        assert_eq!(ids.class_name.as_deref(), Some("Mass Storage / SCSI / Bulk-Only"));
+
+        assert_eq!(db.resolve_device(0x1234, 0x5678, 0x03, 0x00, 0x00), None);
+    }
+
+    #[test]
+    fn test_class_code_name_table() {
+        assert_eq!(class_code_name(0x03), Some("HID"));
+        assert_eq!(class_code_name(0x08), Some("Mass Storage"));
+        assert_eq!(class_code_name(0x09), Some("Hub"));
+        assert_eq!(class_code_name(0xff), Some("Vendor Specific"));
+        // 0x00 defers to the interfaces: no device-level name.
+        assert_eq!(class_code_name(0x00), None);
+        assert_eq!(class_code_name(0x04), None);
+    }
+}
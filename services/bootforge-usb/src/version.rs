@@ -0,0 +1,189 @@
+// BootForge USB - Binary-coded-decimal version handling (bcdUSB / bcdDevice)
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/**
+ * USB binary-coded-decimal version as carried in bcdUSB and bcdDevice.
+ *
+ * The raw u16 is kept as the source of truth; the string form is derived
+ * on demand. Display matches what lsusb prints: "{major:x}.{minor:02x}",
+ * so 0x0210 renders "2.10" and 0x0320 renders "3.20".
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BcdVersion(pub u16);
+
+impl BcdVersion {
+    /// Major version, decoded from the high BCD byte (0x0310 -> 3).
+    pub fn major(&self) -> u8 {
+        let hi = (self.0 >> 8) as u8;
+        (hi >> 4) * 10 + (hi & 0x0f)
+    }
+
+    /// Minor version digit (0x0310 -> 1).
+    pub fn minor(&self) -> u8 {
+        ((self.0 >> 4) & 0x0f) as u8
+    }
+
+    /// Sub-minor version digit (0x0201 -> 1).
+    pub fn sub_minor(&self) -> u8 {
+        (self.0 & 0x0f) as u8
+    }
+}
+
+impl fmt::Display for BcdVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}.{:02x}", self.0 >> 8, self.0 & 0xff)
+    }
+}
+
+impl FromStr for BcdVersion {
+    type Err = String;
+
+    /**
+     * Parse both the canonical form ("2.10", "3.20") and the legacy
+     * "{major}.{minor}" strings older snapshots carry ("2.0", "2.1", "3.2").
+     */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (major_part, frac_part) = s
+            .split_once('.')
+            .ok_or_else(|| format!("invalid BCD version: {:?}", s))?;
+
+        let major: u16 = major_part
+            .parse::<u8>()
+            .map_err(|_| format!("invalid BCD major in {:?}", s))? as u16;
+        if major > 99 {
+            return Err(format!("BCD major out of range in {:?}", s));
+        }
+        let major_bcd = ((major / 10) << 4) | (major % 10);
+
+        let digits: Vec<u16> = frac_part
+            .chars()
+            .map(|c| {
+                c.to_digit(10)
+                    .map(|d| d as u16)
+                    .ok_or_else(|| format!("invalid BCD fraction in {:?}", s))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let (minor, sub_minor) = match digits.as_slice() {
+            [minor] => (*minor, 0),
+            [minor, sub] => (*minor, *sub),
+            _ => return Err(format!("invalid BCD fraction in {:?}", s)),
+        };
+
+        Ok(BcdVersion((major_bcd << 8) | (minor << 4) | sub_minor))
+    }
+}
+
+impl From<u16> for BcdVersion {
+    fn from(raw: u16) -> Self {
+        BcdVersion(raw)
+    }
+}
+
+impl From<rusb::Version> for BcdVersion {
+    fn from(v: rusb::Version) -> Self {
+        // rusb::Version keeps the raw high byte plus split minor digits.
+        BcdVersion(
+            ((v.major() as u16) << 8) | ((v.minor() as u16 & 0x0f) << 4) | (v.sub_minor() as u16 & 0x0f),
+        )
+    }
+}
+
+impl Serialize for BcdVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for BcdVersion {
+    /**
+     * Compatibility shim: snapshots written before the canonical rendering
+     * carried either the lossy "{major}.{minor}" string or a raw number.
+     */
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BcdVisitor;
+
+        impl de::Visitor<'_> for BcdVisitor {
+            type Value = BcdVersion;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a BCD version string or raw u16")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(de::Error::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                u16::try_from(v)
+                    .map(BcdVersion)
+                    .map_err(|_| de::Error::custom("BCD version out of u16 range"))
+            }
+        }
+
+        deserializer.deserialize_any(BcdVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_matches_lsusb() {
+        // bcdUSB value -> what lsusb prints
+        let cases = [
+            (0x0110u16, "1.10"),
+            (0x0200, "2.00"),
+            (0x0201, "2.01"),
+            (0x0210, "2.10"),
+            (0x0250, "2.50"),
+            (0x0300, "3.00"),
+            (0x0310, "3.10"),
+            (0x0320, "3.20"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(BcdVersion(raw).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_components() {
+        let v = BcdVersion(0x0321);
+        assert_eq!(v.major(), 3);
+        assert_eq!(v.minor(), 2);
+        assert_eq!(v.sub_minor(), 1);
+    }
+
+    #[test]
+    fn test_parse_canonical_and_legacy() {
+        assert_eq!("2.10".parse::<BcdVersion>().unwrap(), BcdVersion(0x0210));
+        assert_eq!("3.20".parse::<BcdVersion>().unwrap(), BcdVersion(0x0320));
+        // Legacy "{major}.{minor}" strings from old snapshots
+        assert_eq!("2.0".parse::<BcdVersion>().unwrap(), BcdVersion(0x0200));
+        assert_eq!("2.1".parse::<BcdVersion>().unwrap(), BcdVersion(0x0210));
+        assert_eq!("3.2".parse::<BcdVersion>().unwrap(), BcdVersion(0x0320));
+        assert!("garbage".parse::<BcdVersion>().is_err());
+    }
+
+    #[test]
+    fn test_serde_round_trip_and_compat() {
+        let v = BcdVersion(0x0210);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, "\"2.10\"");
+        assert_eq!(serde_json::from_str::<BcdVersion>(&json).unwrap(), v);
+        // Old snapshots: lossy string or raw number
+        assert_eq!(
+            serde_json::from_str::<BcdVersion>("\"2.1\"").unwrap(),
+            BcdVersion(0x0210)
+        );
+        assert_eq!(
+            serde_json::from_str::<BcdVersion>("528").unwrap(),
+            BcdVersion(0x0210)
+        );
+    }
+}
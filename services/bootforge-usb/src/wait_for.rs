@@ -0,0 +1,361 @@
+// BootForge USB - Blocking waits for protocol arrival and removal
+// "Reboot to bootloader, then wait for fastboot" without the
+// hand-rolled poll loop: start a watcher, close the already-present
+// race with one enumeration pass, then block on the event stream until
+// a matching device appears (or the tracked one goes away) or the
+// deadline passes.
+
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+use crate::enumeration::{enumerate_libusb, DeviceFilter, UsbDeviceInfo};
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+use crate::protocols::{classify_device_info_protocols, Protocol};
+use crate::watch::{DeviceWatcher, LibusbHotplugWatcher, SnapshotSource};
+
+/**
+ * Block until a device classifying as `protocol` (and matching
+ * `filter`, when given) is present, up to `timeout`.
+ *
+ * A device already on the bus returns immediately: the watcher starts
+ * first and an enumeration pass runs before the event stream is
+ * consumed, so neither an early nor a late arrival slips through. The
+ * deadline reads as `NotFound`, naming the protocol.
+ */
+pub fn wait_for_protocol(
+    protocol: Protocol,
+    filter: Option<&DeviceFilter>,
+    timeout: Duration,
+) -> Result<UsbDeviceInfo, UsbError> {
+    wait_for_protocol_on(
+        &mut *default_watcher(),
+        &mut LiveSource,
+        protocol,
+        filter,
+        timeout,
+    )
+}
+
+/**
+ * As `wait_for_protocol`, against caller-supplied watcher and snapshot
+ * source - the testable core, and the hook for callers that already
+ * hold a configured watcher.
+ */
+pub fn wait_for_protocol_on(
+    watcher: &mut dyn DeviceWatcher,
+    source: &mut dyn SnapshotSource,
+    protocol: Protocol,
+    filter: Option<&DeviceFilter>,
+    timeout: Duration,
+) -> Result<UsbDeviceInfo, UsbError> {
+    let deadline = Instant::now() + timeout;
+    let events = watcher.start()?;
+
+    // The device may have arrived before the watcher existed; one
+    // snapshot closes that race. Anything arriving during the snapshot
+    // is already queued on the event channel.
+    if let Some(found) = source
+        .snapshot()?
+        .into_iter()
+        .find(|info| matches_protocol(info, protocol, filter))
+    {
+        watcher.stop();
+        return Ok(found);
+    }
+
+    loop {
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            watcher.stop();
+            return Err(not_found(protocol, timeout));
+        };
+        let event = match events.recv_timeout(remaining) {
+            Ok(event) => event,
+            // A watcher that stops early cannot deliver the device
+            // either; both read as the deadline failure.
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                watcher.stop();
+                return Err(not_found(protocol, timeout));
+            }
+        };
+        let candidate = match event {
+            DeviceEvent::Connected(info) => info,
+            DeviceEvent::Changed { after, .. } | DeviceEvent::DescriptorChanged { after, .. } => {
+                *after
+            }
+            DeviceEvent::Disconnected(_) => continue,
+        };
+        if matches_protocol(&candidate, protocol, filter) {
+            watcher.stop();
+            return Ok(candidate);
+        }
+    }
+}
+
+/**
+ * Block until the device with `identity` is gone, up to `timeout`. A
+ * device that is not present to begin with returns immediately; a
+ * deadline reads as `NotFound` naming the identity that stayed.
+ */
+pub fn wait_for_removal(identity: &DeviceIdentity, timeout: Duration) -> Result<(), UsbError> {
+    wait_for_removal_on(&mut *default_watcher(), &mut LiveSource, identity, timeout)
+}
+
+/**
+ * As `wait_for_removal`, against caller-supplied watcher and snapshot
+ * source.
+ */
+pub fn wait_for_removal_on(
+    watcher: &mut dyn DeviceWatcher,
+    source: &mut dyn SnapshotSource,
+    identity: &DeviceIdentity,
+    timeout: Duration,
+) -> Result<(), UsbError> {
+    let deadline = Instant::now() + timeout;
+    let events = watcher.start()?;
+
+    if !source
+        .snapshot()?
+        .iter()
+        .any(|info| DeviceIdentity::of(info) == *identity)
+    {
+        watcher.stop();
+        return Ok(());
+    }
+
+    loop {
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            watcher.stop();
+            return Err(still_present(identity, timeout));
+        };
+        match events.recv_timeout(remaining) {
+            Ok(DeviceEvent::Disconnected(gone)) if gone == *identity => {
+                watcher.stop();
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                watcher.stop();
+                return Err(still_present(identity, timeout));
+            }
+        }
+    }
+}
+
+fn matches_protocol(info: &UsbDeviceInfo, protocol: Protocol, filter: Option<&DeviceFilter>) -> bool {
+    filter.is_none_or(|f| f.matches(info))
+        && classify_device_info_protocols(info).all().contains(protocol)
+}
+
+fn not_found(protocol: Protocol, timeout: Duration) -> UsbError {
+    UsbError::NotFound(format!(
+        "no {} device appeared within {:?}",
+        protocol.name(),
+        timeout
+    ))
+}
+
+fn still_present(identity: &DeviceIdentity, timeout: Duration) -> UsbError {
+    UsbError::NotFound(format!("{} still present after {:?}", identity, timeout))
+}
+
+/// The platform's best watcher: libusb hotplug where it exists, with
+/// its built-in polling fallback; the native notification watchers on
+/// hosts that have one.
+fn default_watcher() -> Box<dyn DeviceWatcher> {
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(crate::watch::MacOSDeviceWatcher::new())
+    }
+    #[cfg(windows)]
+    {
+        Box::new(crate::watch::WindowsDeviceWatcher::new())
+    }
+    #[cfg(not(any(target_os = "macos", windows)))]
+    {
+        Box::new(LibusbHotplugWatcher::new())
+    }
+}
+
+/// Live enumeration as the snapshot source for the non-test path.
+struct LiveSource;
+
+impl SnapshotSource for LiveSource {
+    fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+        enumerate_libusb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread::JoinHandle;
+
+    struct ScriptedWatcher {
+        script: Vec<DeviceEvent>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl ScriptedWatcher {
+        fn new(script: Vec<DeviceEvent>) -> Self {
+            Self {
+                script,
+                thread: None,
+            }
+        }
+    }
+
+    impl DeviceWatcher for ScriptedWatcher {
+        fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+            let (tx, rx) = channel();
+            let script = std::mem::take(&mut self.script);
+            self.thread = Some(std::thread::spawn(move || {
+                for event in script {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }));
+            Ok(rx)
+        }
+
+        fn stop(&mut self) {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct ScriptedSource {
+        snapshots: VecDeque<Vec<UsbDeviceInfo>>,
+    }
+
+    impl SnapshotSource for ScriptedSource {
+        fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+            Ok(self.snapshots.pop_front().unwrap_or_default())
+        }
+    }
+
+    /// Classifies as fastboot through the product-string heuristic.
+    fn fastboot_device() -> UsbDeviceInfo {
+        UsbDeviceInfo::builder(0x18d1, 0x4ee0)
+            .product("Android Fastboot")
+            .serial("29061FDH300EXZ")
+            .build()
+    }
+
+    fn mouse() -> UsbDeviceInfo {
+        UsbDeviceInfo::builder(0x046d, 0xc52b).class(0x03).build()
+    }
+
+    #[test]
+    fn test_already_present_device_returns_before_any_event() {
+        let mut watcher = ScriptedWatcher::new(vec![]);
+        let mut source = ScriptedSource {
+            snapshots: VecDeque::from([vec![mouse(), fastboot_device()]]),
+        };
+        let found = wait_for_protocol_on(
+            &mut watcher,
+            &mut source,
+            Protocol::Fastboot,
+            None,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(found, fastboot_device());
+    }
+
+    #[test]
+    fn test_arrival_through_the_event_stream() {
+        let mut watcher = ScriptedWatcher::new(vec![
+            DeviceEvent::Connected(mouse()),
+            DeviceEvent::Connected(fastboot_device()),
+        ]);
+        let mut source = ScriptedSource::default();
+        let found = wait_for_protocol_on(
+            &mut watcher,
+            &mut source,
+            Protocol::Fastboot,
+            None,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(found, fastboot_device());
+    }
+
+    #[test]
+    fn test_filter_rejects_an_otherwise_matching_device() {
+        let mut watcher = ScriptedWatcher::new(vec![DeviceEvent::Connected(fastboot_device())]);
+        let mut source = ScriptedSource::default();
+        let filter = DeviceFilter::any().with_vendor_id(0x05ac);
+        let err = wait_for_protocol_on(
+            &mut watcher,
+            &mut source,
+            Protocol::Fastboot,
+            Some(&filter),
+            Duration::from_millis(50),
+        )
+        .unwrap_err();
+        assert!(matches!(err, UsbError::NotFound(_)));
+    }
+
+    #[test]
+    fn test_timeout_names_the_protocol() {
+        let mut watcher = ScriptedWatcher::new(vec![]);
+        let mut source = ScriptedSource::default();
+        let err = wait_for_protocol_on(
+            &mut watcher,
+            &mut source,
+            Protocol::Fastboot,
+            None,
+            Duration::from_millis(30),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, UsbError::NotFound(message) if message.contains("fastboot")),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_removal_of_absent_device_is_immediate() {
+        let identity = DeviceIdentity::of(&fastboot_device());
+        let mut watcher = ScriptedWatcher::new(vec![]);
+        let mut source = ScriptedSource::default();
+        wait_for_removal_on(&mut watcher, &mut source, &identity, Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn test_removal_waits_for_the_disconnect_event() {
+        let identity = DeviceIdentity::of(&fastboot_device());
+        let mut watcher = ScriptedWatcher::new(vec![
+            DeviceEvent::Disconnected(DeviceIdentity::of(&mouse())),
+            DeviceEvent::Disconnected(identity.clone()),
+        ]);
+        let mut source = ScriptedSource {
+            snapshots: VecDeque::from([vec![fastboot_device()]]),
+        };
+        wait_for_removal_on(&mut watcher, &mut source, &identity, Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn test_removal_deadline_names_the_identity() {
+        let identity = DeviceIdentity::of(&fastboot_device());
+        let mut watcher = ScriptedWatcher::new(vec![]);
+        let mut source = ScriptedSource {
+            snapshots: VecDeque::from([vec![fastboot_device()]]),
+        };
+        let err = wait_for_removal_on(
+            &mut watcher,
+            &mut source,
+            &identity,
+            Duration::from_millis(30),
+        )
+        .unwrap_err();
+        assert!(matches!(&err, UsbError::NotFound(message) if message.contains(&identity.0)));
+    }
+}
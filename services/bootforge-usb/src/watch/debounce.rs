@@ -0,0 +1,384 @@
+// BootForge USB - Event debouncing
+// A flaky cable or marginal port produces bursts of disconnect/connect
+// pairs within a few hundred milliseconds, and every one costs
+// downstream consumers a reconnect. The wrapper here holds events per
+// device identity for a coalescing window and emits only the net
+// result.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::enumeration::UsbDeviceInfo;
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+
+use super::DeviceWatcher;
+
+/// Default coalescing window; generous enough for cable bounce, short
+/// enough that a real removal is not noticeably delayed.
+pub const DEFAULT_DEBOUNCE_WINDOW: Duration = Duration::from_millis(500);
+
+/**
+ * What a disconnect/reconnect pair inside one window becomes.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplugPolicy {
+    /// Emit a single `Changed` event so consumers can refresh state
+    /// without tearing the session down.
+    #[default]
+    Changed,
+    /// Emit nothing; the device never appeared to leave.
+    Suppress,
+}
+
+/**
+ * Wraps any `DeviceWatcher` and coalesces events for the same device
+ * identity - serial when present, else port chain, else bus slot, per
+ * the canonical identity - within a configurable window. Events are
+ * delayed by at most one window; `Changed` and `DescriptorChanged`
+ * events pass through immediately.
+ */
+pub struct DebouncedWatcher<W: DeviceWatcher> {
+    inner: W,
+    window: Duration,
+    replug: ReplugPolicy,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<W: DeviceWatcher> DebouncedWatcher<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            window: DEFAULT_DEBOUNCE_WINDOW,
+            replug: ReplugPolicy::default(),
+            thread: None,
+        }
+    }
+
+    /// Coalescing window; events for an identity are held this long
+    /// after its first event before the net result is emitted.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_replug_policy(mut self, replug: ReplugPolicy) -> Self {
+        self.replug = replug;
+        self
+    }
+}
+
+impl<W: DeviceWatcher> DeviceWatcher for DebouncedWatcher<W> {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        let inner_rx = self.inner.start()?;
+        let (event_tx, event_rx) = channel();
+        let window = self.window;
+        let replug = self.replug;
+
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-debounce".to_string())
+            .spawn(move || run_debounce_loop(inner_rx, event_tx, window, replug))
+            .map_err(UsbError::Io)?;
+
+        self.thread = Some(thread);
+        Ok(event_rx)
+    }
+
+    fn stop(&mut self) {
+        // Stopping the inner watcher hangs up its channel; the debounce
+        // thread flushes whatever is pending and exits.
+        self.inner.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<W: DeviceWatcher> Drop for DebouncedWatcher<W> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// One identity's events held inside the current window.
+struct Pending {
+    /// Direction of the first event; with the last event this decides
+    /// the net result.
+    first_was_connect: bool,
+    last: DeviceEvent,
+    deadline: Instant,
+}
+
+fn run_debounce_loop(
+    inner: Receiver<DeviceEvent>,
+    sender: Sender<DeviceEvent>,
+    window: Duration,
+    replug: ReplugPolicy,
+) {
+    let mut pending: HashMap<DeviceIdentity, Pending> = HashMap::new();
+    // Last full record seen per identity, for the `before` side of a
+    // coalesced Changed event.
+    let mut last_seen: HashMap<DeviceIdentity, UsbDeviceInfo> = HashMap::new();
+
+    loop {
+        let event = match pending.values().map(|p| p.deadline).min() {
+            None => inner.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(deadline) => {
+                inner.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+        };
+
+        match event {
+            Ok(DeviceEvent::Connected(info)) => {
+                let identity = DeviceIdentity::of(&info);
+                let event = DeviceEvent::Connected(info);
+                pending
+                    .entry(identity)
+                    .and_modify(|p| p.last = event.clone())
+                    .or_insert(Pending {
+                        first_was_connect: true,
+                        last: event,
+                        deadline: Instant::now() + window,
+                    });
+            }
+            Ok(DeviceEvent::Disconnected(identity)) => {
+                let event = DeviceEvent::Disconnected(identity.clone());
+                pending
+                    .entry(identity)
+                    .and_modify(|p| p.last = event.clone())
+                    .or_insert(Pending {
+                        first_was_connect: false,
+                        last: event,
+                        deadline: Instant::now() + window,
+                    });
+            }
+            // In-place changes are not bounce; forward untouched.
+            Ok(event) => {
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                let _ = flush(&mut pending, &mut last_seen, &sender, replug, None);
+                return;
+            }
+        }
+
+        if flush(
+            &mut pending,
+            &mut last_seen,
+            &sender,
+            replug,
+            Some(Instant::now()),
+        )
+        .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Emit the net result for every pending identity whose window has
+/// passed (all of them when `now` is None, on shutdown).
+fn flush(
+    pending: &mut HashMap<DeviceIdentity, Pending>,
+    last_seen: &mut HashMap<DeviceIdentity, UsbDeviceInfo>,
+    sender: &Sender<DeviceEvent>,
+    replug: ReplugPolicy,
+    now: Option<Instant>,
+) -> Result<(), ()> {
+    let due: Vec<DeviceIdentity> = pending
+        .iter()
+        .filter(|(_, p)| now.is_none_or(|now| p.deadline <= now))
+        .map(|(identity, _)| identity.clone())
+        .collect();
+
+    for identity in due {
+        let entry = pending.remove(&identity).expect("key collected above");
+        let net = match (entry.first_was_connect, entry.last) {
+            // Arrived (and possibly bounced) but is present: one connect.
+            (true, DeviceEvent::Connected(info)) => Some(DeviceEvent::Connected(info)),
+            // Arrived and left within the window: nothing happened.
+            (true, DeviceEvent::Disconnected(_)) => None,
+            (false, DeviceEvent::Disconnected(identity)) => {
+                Some(DeviceEvent::Disconnected(identity))
+            }
+            // Left and came back: a replug, per policy.
+            (false, DeviceEvent::Connected(info)) => match replug {
+                ReplugPolicy::Suppress => None,
+                ReplugPolicy::Changed => Some(DeviceEvent::Changed {
+                    identity: identity.clone(),
+                    before: Box::new(last_seen.get(&identity).cloned().unwrap_or_else(|| {
+                        // Inner watcher started mid-bounce; the best
+                        // available "before" is the record we have.
+                        info.clone()
+                    })),
+                    after: Box::new(info),
+                }),
+            },
+            // Only Connected/Disconnected are held in `pending`.
+            (_, event) => Some(event),
+        };
+        if let Some(event) = net {
+            match &event {
+                DeviceEvent::Connected(info) => {
+                    last_seen.insert(identity, info.clone());
+                }
+                DeviceEvent::Changed { after, .. } => {
+                    last_seen.insert(identity, (**after).clone());
+                }
+                _ => {}
+            }
+            sender.send(event).map_err(|_| ())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watch::partial_info;
+
+    /// Inner watcher that replays a scripted sequence with per-event
+    /// delays, then hangs up.
+    struct ScriptedWatcher {
+        script: Vec<(Duration, DeviceEvent)>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl ScriptedWatcher {
+        fn new(script: Vec<(Duration, DeviceEvent)>) -> Self {
+            Self {
+                script,
+                thread: None,
+            }
+        }
+    }
+
+    impl DeviceWatcher for ScriptedWatcher {
+        fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+            let (tx, rx) = channel();
+            let script = std::mem::take(&mut self.script);
+            self.thread = Some(std::thread::spawn(move || {
+                for (delay, event) in script {
+                    std::thread::sleep(delay);
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }));
+            Ok(rx)
+        }
+
+        fn stop(&mut self) {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn info() -> UsbDeviceInfo {
+        let mut info = partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("SERIAL1".to_string()),
+            None,
+            "test".to_string(),
+        );
+        info.bus_number = 1;
+        info.address = 4;
+        info
+    }
+
+    fn identity() -> DeviceIdentity {
+        DeviceIdentity::of(&info())
+    }
+
+    fn collect(watcher: &mut DebouncedWatcher<ScriptedWatcher>) -> Vec<DeviceEvent> {
+        let rx = watcher.start().unwrap();
+        let events = rx.iter().collect();
+        watcher.stop();
+        events
+    }
+
+    #[test]
+    fn test_burst_nets_to_single_connected() {
+        let none = Duration::ZERO;
+        let mut watcher = DebouncedWatcher::new(ScriptedWatcher::new(vec![
+            (none, DeviceEvent::Connected(info())),
+            (none, DeviceEvent::Disconnected(identity())),
+            (none, DeviceEvent::Connected(info())),
+        ]))
+        .with_window(Duration::from_millis(200));
+
+        // All three land in one window: absent before the burst and
+        // present after it nets to a single Connected.
+        let events = collect(&mut watcher);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], DeviceEvent::Connected(_)));
+    }
+
+    #[test]
+    fn test_replug_after_quiet_period_becomes_changed() {
+        let mut watcher = DebouncedWatcher::new(ScriptedWatcher::new(vec![
+            (Duration::ZERO, DeviceEvent::Connected(info())),
+            // Let the connect window expire, then bounce.
+            (Duration::from_millis(120), DeviceEvent::Disconnected(identity())),
+            (Duration::from_millis(10), DeviceEvent::Connected(info())),
+        ]))
+        .with_window(Duration::from_millis(50));
+
+        let events = collect(&mut watcher);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], DeviceEvent::Connected(_)));
+        assert!(matches!(
+            &events[1],
+            DeviceEvent::Changed { identity: id, .. } if *id == identity()
+        ));
+    }
+
+    #[test]
+    fn test_replug_suppressed_by_policy() {
+        let mut watcher = DebouncedWatcher::new(ScriptedWatcher::new(vec![
+            (Duration::ZERO, DeviceEvent::Disconnected(identity())),
+            (Duration::from_millis(10), DeviceEvent::Connected(info())),
+        ]))
+        .with_window(Duration::from_millis(100))
+        .with_replug_policy(ReplugPolicy::Suppress);
+
+        assert!(collect(&mut watcher).is_empty());
+    }
+
+    #[test]
+    fn test_events_beyond_window_pass_through() {
+        let mut watcher = DebouncedWatcher::new(ScriptedWatcher::new(vec![
+            (Duration::ZERO, DeviceEvent::Disconnected(identity())),
+            (Duration::from_millis(120), DeviceEvent::Connected(info())),
+        ]))
+        .with_window(Duration::from_millis(40));
+
+        let events = collect(&mut watcher);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], DeviceEvent::Disconnected(_)));
+        assert!(matches!(events[1], DeviceEvent::Connected(_)));
+    }
+
+    #[test]
+    fn test_transient_arrival_is_suppressed() {
+        let mut watcher = DebouncedWatcher::new(ScriptedWatcher::new(vec![
+            (Duration::ZERO, DeviceEvent::Connected(info())),
+            (Duration::from_millis(10), DeviceEvent::Disconnected(identity())),
+        ]))
+        .with_window(Duration::from_millis(100));
+
+        assert!(collect(&mut watcher).is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+// BootForge USB - Event enrichment
+// Notification-driven watchers emit whatever the platform hands them:
+// empty tags, no driver state, no protocol classification, forcing
+// every consumer to re-enumerate before an event is useful. The wrapper
+// here runs enrichment for just the device an event names - on its own
+// thread, so a slow sysfs read never backs up the platform's
+// notification socket - and forwards the annotated event.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::enumeration::UsbDeviceInfo;
+use crate::error::UsbError;
+use crate::events::DeviceEvent;
+use crate::protocols::classify_device_info_set;
+
+use super::DeviceWatcher;
+
+/// Prefix of the tags `default_enrichment` derives from protocol
+/// classification, e.g. "protocol:fastboot".
+pub const PROTOCOL_TAG_PREFIX: &str = "protocol:";
+
+/**
+ * Annotate one device record in place. The default is
+ * `default_enrichment`; tests and embedders with their own metadata
+ * sources can substitute any function.
+ */
+pub type Enricher = Arc<dyn Fn(&mut UsbDeviceInfo) + Send + Sync>;
+
+/**
+ * Wraps any `DeviceWatcher` and enriches the record carried by each
+ * `Connected`, `Changed`, and `DescriptorChanged` event before
+ * forwarding it. `Disconnected` events pass through untouched - the
+ * device is gone, there is nothing left to look up.
+ */
+pub struct EnrichedWatcher<W: DeviceWatcher> {
+    inner: W,
+    enricher: Enricher,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<W: DeviceWatcher> EnrichedWatcher<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            enricher: Arc::new(default_enrichment),
+            thread: None,
+        }
+    }
+
+    /// Replace the default enrichment with a custom annotator.
+    pub fn with_enricher(mut self, enricher: Enricher) -> Self {
+        self.enricher = enricher;
+        self
+    }
+}
+
+impl<W: DeviceWatcher> DeviceWatcher for EnrichedWatcher<W> {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        let inner_rx = self.inner.start()?;
+        let (event_tx, event_rx) = channel();
+        let enricher = Arc::clone(&self.enricher);
+
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-enrich".to_string())
+            .spawn(move || run_enrich_loop(inner_rx, event_tx, enricher))
+            .map_err(UsbError::Io)?;
+
+        self.thread = Some(thread);
+        Ok(event_rx)
+    }
+
+    fn stop(&mut self) {
+        // Stopping the inner watcher hangs up its channel; the
+        // enrichment thread drains what remains and exits.
+        self.inner.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<W: DeviceWatcher> Drop for EnrichedWatcher<W> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_enrich_loop(inner: Receiver<DeviceEvent>, sender: Sender<DeviceEvent>, enricher: Enricher) {
+    for event in inner {
+        let event = match event {
+            DeviceEvent::Connected(mut info) => {
+                enricher(&mut info);
+                DeviceEvent::Connected(info)
+            }
+            DeviceEvent::Changed {
+                identity,
+                before,
+                mut after,
+            } => {
+                enricher(&mut after);
+                DeviceEvent::Changed {
+                    identity,
+                    before,
+                    after,
+                }
+            }
+            DeviceEvent::DescriptorChanged {
+                identity,
+                before,
+                mut after,
+                diff,
+            } => {
+                enricher(&mut after);
+                DeviceEvent::DescriptorChanged {
+                    identity,
+                    before,
+                    after,
+                    diff,
+                }
+            }
+            removal @ DeviceEvent::Disconnected(_) => removal,
+        };
+        if sender.send(event).is_err() {
+            return;
+        }
+    }
+}
+
+/**
+ * The stock annotator: a `protocol:` tag per classified protocol, and
+ * on Linux the sysfs-derived driver state - `driver:` tags and the
+ * port path - for just this device. Tags already present are not
+ * duplicated, so re-enriching an already-rich record is harmless.
+ */
+pub fn default_enrichment(info: &mut UsbDeviceInfo) {
+    for protocol in classify_device_info_set(info).iter() {
+        push_tag(info, format!("{}{}", PROTOCOL_TAG_PREFIX, protocol.name()));
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Ok(hint) = crate::linux::enrich_linux(info) {
+        use crate::linux::DriverStatus;
+
+        if info.port_path.is_none() {
+            info.port_path = hint.port_path;
+        }
+        match hint.driver {
+            DriverStatus::Unknown | DriverStatus::Missing => {}
+            DriverStatus::Bound(driver) => push_tag(info, format!("driver:{}", driver)),
+            DriverStatus::Multiple(drivers) => {
+                for driver in drivers {
+                    push_tag(info, format!("driver:{}", driver));
+                }
+            }
+            DriverStatus::Blocked { .. } => push_tag(info, "blocked".to_string()),
+        }
+        if hint.authorized == Some(false) {
+            push_tag(info, "linux:deauthorized".to_string());
+        }
+    }
+}
+
+fn push_tag(info: &mut UsbDeviceInfo, tag: String) {
+    if !info.tags.contains(&tag) {
+        info.tags.push(tag);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::DeviceIdentity;
+    use crate::watch::partial_info;
+
+    /// Inner watcher that replays a scripted sequence, then hangs up.
+    struct ScriptedWatcher {
+        script: Vec<DeviceEvent>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl ScriptedWatcher {
+        fn new(script: Vec<DeviceEvent>) -> Self {
+            Self {
+                script,
+                thread: None,
+            }
+        }
+    }
+
+    impl DeviceWatcher for ScriptedWatcher {
+        fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+            let (tx, rx) = channel();
+            let script = std::mem::take(&mut self.script);
+            self.thread = Some(std::thread::spawn(move || {
+                for event in script {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }));
+            Ok(rx)
+        }
+
+        fn stop(&mut self) {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn info() -> UsbDeviceInfo {
+        let mut info = partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("SERIAL1".to_string()),
+            None,
+            "test".to_string(),
+        );
+        info.bus_number = 1;
+        info.address = 4;
+        info
+    }
+
+    fn identity() -> DeviceIdentity {
+        DeviceIdentity::of(&info())
+    }
+
+    fn fake_enricher() -> Enricher {
+        Arc::new(|info: &mut UsbDeviceInfo| info.tags.push("enriched".to_string()))
+    }
+
+    fn collect(watcher: &mut EnrichedWatcher<ScriptedWatcher>) -> Vec<DeviceEvent> {
+        let rx = watcher.start().unwrap();
+        let events = rx.iter().collect();
+        watcher.stop();
+        events
+    }
+
+    #[test]
+    fn test_connected_is_enriched_and_removal_passes_untouched() {
+        let mut watcher = EnrichedWatcher::new(ScriptedWatcher::new(vec![
+            DeviceEvent::Connected(info()),
+            DeviceEvent::Disconnected(identity()),
+        ]))
+        .with_enricher(fake_enricher());
+
+        let events = collect(&mut watcher);
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            DeviceEvent::Connected(info) => {
+                assert!(info.tags.iter().any(|t| t == "enriched"));
+            }
+            other => panic!("expected Connected, got {:?}", other),
+        }
+        assert!(matches!(&events[1], DeviceEvent::Disconnected(id) if *id == identity()));
+    }
+
+    #[test]
+    fn test_changed_enriches_after_not_before() {
+        let before = info();
+        let after = info();
+        let mut watcher = EnrichedWatcher::new(ScriptedWatcher::new(vec![DeviceEvent::Changed {
+            identity: identity(),
+            before: Box::new(before),
+            after: Box::new(after),
+        }]))
+        .with_enricher(fake_enricher());
+
+        let events = collect(&mut watcher);
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            DeviceEvent::Changed { before, after, .. } => {
+                assert!(!before.tags.iter().any(|t| t == "enriched"));
+                assert!(after.tags.iter().any(|t| t == "enriched"));
+            }
+            other => panic!("expected Changed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_double_start_is_rejected() {
+        let mut watcher = EnrichedWatcher::new(ScriptedWatcher::new(vec![]));
+        let _rx = watcher.start().unwrap();
+        assert!(matches!(watcher.start(), Err(UsbError::Internal(_))));
+        watcher.stop();
+    }
+
+    #[test]
+    fn test_default_enrichment_tags_protocols_without_duplicates() {
+        // Google VID with an ADB-looking product string classifies as
+        // adb via the string heuristics; the tag must appear once even
+        // when enrichment runs twice.
+        let mut record = partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("SERIAL1".to_string()),
+            None,
+            "test".to_string(),
+        );
+        record.product = Some("Android ADB Interface".to_string());
+        default_enrichment(&mut record);
+        default_enrichment(&mut record);
+        let adb_tags = record.tags.iter().filter(|t| *t == "protocol:adb").count();
+        assert_eq!(adb_tags, 1);
+    }
+}
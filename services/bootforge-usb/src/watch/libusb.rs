@@ -0,0 +1,284 @@
+// BootForge USB - libusb-backed fallback watcher
+// Cross-platform hotplug for hosts where the native watcher is
+// incomplete: rusb's hotplug callbacks when the running libusb supports
+// them, otherwise a periodic poll-and-diff over enumeration. The diff
+// keys on the (bus, address, vid, pid) slot, so a replug of the same
+// device in the same port changes the address and still produces a
+// Disconnected/Connected pair.
+
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::enumeration::{enumerate_libusb, guard_panics, UsbDeviceInfo};
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+
+use super::{partial_info, DeviceWatcher};
+
+/// Default poll cadence for hosts without libusb hotplug support.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long each handle_events call blocks before rechecking for stop.
+const HOTPLUG_WAKE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A device's slot on the bus; the unit the poll diff keys on.
+type SlotKey = (u8, u8, u16, u16);
+
+/**
+ * Hotplug monitor backed by libusb. `start` registers rusb hotplug
+ * callbacks when the running libusb supports them; if registration
+ * fails for any reason it falls back to polling `enumerate_libusb` and
+ * diffing the results. Hotplug-path events carry only the identity
+ * fields libusb reports from a callback (bus, address, VID, PID);
+ * poll-path events carry full enumeration records.
+ */
+pub struct LibusbHotplugWatcher {
+    poll_interval: Duration,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Default for LibusbHotplugWatcher {
+    fn default() -> Self {
+        Self {
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            stop: None,
+            thread: None,
+        }
+    }
+}
+
+impl LibusbHotplugWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Poll cadence used when libusb hotplug is unavailable.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+}
+
+impl DeviceWatcher for LibusbHotplugWatcher {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        let (event_tx, event_rx) = channel();
+        let (stop_tx, stop_rx) = channel();
+
+        // rusb::has_hotplug() initializes the global context, which the
+        // no-panic guarantee does not allow to unwind through us;
+        // attempting the registration under the guard answers the same
+        // question and covers registration failures too.
+        let registered = guard_panics(
+            "hotplug registration",
+            AssertUnwindSafe(|| {
+                let context = rusb::Context::new()?;
+                let registration = rusb::HotplugBuilder::new().register(
+                    &context,
+                    Box::new(HotplugForwarder {
+                        sender: event_tx.clone(),
+                    }),
+                )?;
+                Ok((context, registration))
+            }),
+        );
+
+        let poll_interval = self.poll_interval;
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-watch".to_string())
+            .spawn(move || match registered {
+                Ok((context, registration)) => {
+                    run_hotplug_loop(&context, stop_rx);
+                    drop(registration);
+                }
+                Err(e) => {
+                    log::debug!(
+                        "libusb hotplug unavailable ({}); polling every {:?}",
+                        e,
+                        poll_interval
+                    );
+                    run_poll_loop(event_tx, stop_rx, poll_interval);
+                }
+            })
+            .map_err(UsbError::Io)?;
+
+        self.stop = Some(stop_tx);
+        self.thread = Some(thread);
+        Ok(event_rx)
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            // Dropping the sender wakes recv_timeout; the explicit send
+            // covers the hotplug path's try_recv.
+            if let Some(stop) = self.stop.take() {
+                let _ = stop.send(());
+            }
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for LibusbHotplugWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Forwards rusb hotplug callbacks as device events. Callbacks only
+/// permit descriptor reads, so records carry slot and VID/PID; strings
+/// and configs come from the next enumeration pass.
+struct HotplugForwarder {
+    sender: Sender<DeviceEvent>,
+}
+
+impl HotplugForwarder {
+    fn info_of(device: &rusb::Device<rusb::Context>) -> Option<UsbDeviceInfo> {
+        let descriptor = device.device_descriptor().ok()?;
+        let mut info = partial_info(
+            descriptor.vendor_id(),
+            descriptor.product_id(),
+            None,
+            None,
+            "libusb-hotplug".to_string(),
+        );
+        info.bus_number = device.bus_number();
+        info.address = device.address();
+        Some(info)
+    }
+}
+
+impl rusb::Hotplug<rusb::Context> for HotplugForwarder {
+    fn device_arrived(&mut self, device: rusb::Device<rusb::Context>) {
+        if let Some(info) = Self::info_of(&device) {
+            let _ = self.sender.send(DeviceEvent::Connected(info));
+        }
+    }
+
+    fn device_left(&mut self, device: rusb::Device<rusb::Context>) {
+        if let Some(info) = Self::info_of(&device) {
+            let _ = self
+                .sender
+                .send(DeviceEvent::Disconnected(DeviceIdentity::of(&info)));
+        }
+    }
+}
+
+fn run_hotplug_loop(context: &rusb::Context, stop: Receiver<()>) {
+    use rusb::UsbContext;
+
+    while matches!(stop.try_recv(), Err(TryRecvError::Empty)) {
+        if let Err(e) = context.handle_events(Some(HOTPLUG_WAKE_INTERVAL)) {
+            log::debug!("handle_events failed: {}; watcher stopping", e);
+            break;
+        }
+    }
+}
+
+fn run_poll_loop(sender: Sender<DeviceEvent>, stop: Receiver<()>, interval: Duration) {
+    // Devices already present at start are not events; enumeration
+    // covers them, as with the armed iterators on the native watchers.
+    let mut known = key_by_slot(enumerate_libusb().unwrap_or_default());
+
+    loop {
+        match stop.recv_timeout(interval) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+        // A failed pass (transient permission or backend trouble) keeps
+        // the previous view rather than reporting everything removed.
+        if let Ok(devices) = enumerate_libusb() {
+            for event in diff_devices(&mut known, devices) {
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn key_by_slot(devices: Vec<UsbDeviceInfo>) -> HashMap<SlotKey, UsbDeviceInfo> {
+    devices
+        .into_iter()
+        .map(|d| ((d.bus_number, d.address, d.vendor_id, d.product_id), d))
+        .collect()
+}
+
+/// Diff one poll pass against the previous view, replacing it. Removals
+/// are reported before arrivals so a replugged device reads as a
+/// Disconnected/Connected pair in order.
+fn diff_devices(
+    known: &mut HashMap<SlotKey, UsbDeviceInfo>,
+    current: Vec<UsbDeviceInfo>,
+) -> Vec<DeviceEvent> {
+    let current = key_by_slot(current);
+    let mut events = Vec::new();
+
+    for (key, info) in known.iter() {
+        if !current.contains_key(key) {
+            events.push(DeviceEvent::Disconnected(DeviceIdentity::of(info)));
+        }
+    }
+    for (key, info) in current.iter() {
+        if !known.contains_key(key) {
+            events.push(DeviceEvent::Connected(info.clone()));
+        }
+    }
+
+    *known = current;
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(bus: u8, address: u8, vid: u16, pid: u16) -> UsbDeviceInfo {
+        let mut info = partial_info(vid, pid, None, None, "test".to_string());
+        info.bus_number = bus;
+        info.address = address;
+        info
+    }
+
+    #[test]
+    fn test_diff_reports_replug_in_same_port() {
+        // Same device, same port: only the address changes on replug.
+        let mut known = key_by_slot(vec![device(1, 4, 0x18d1, 0x4ee7)]);
+        let events = diff_devices(&mut known, vec![device(1, 9, 0x18d1, 0x4ee7)]);
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], DeviceEvent::Disconnected(_)));
+        assert!(matches!(
+            &events[1],
+            DeviceEvent::Connected(info) if info.address == 9
+        ));
+        assert!(known.contains_key(&(1, 9, 0x18d1, 0x4ee7)));
+    }
+
+    #[test]
+    fn test_diff_quiet_when_unchanged() {
+        let mut known = key_by_slot(vec![device(1, 4, 0x18d1, 0x4ee7)]);
+        assert!(diff_devices(&mut known, vec![device(1, 4, 0x18d1, 0x4ee7)]).is_empty());
+        assert_eq!(known.len(), 1);
+    }
+
+    #[test]
+    fn test_start_stop_cycles_without_hardware() {
+        // Whichever backend start() lands on, two full cycles must not
+        // leak a thread or wedge on join.
+        let mut watcher =
+            LibusbHotplugWatcher::new().with_poll_interval(Duration::from_millis(10));
+        for _ in 0..2 {
+            let receiver = watcher.start().expect("watcher should start");
+            watcher.stop();
+            drop(receiver);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// BootForge USB - macOS hotplug watcher
+// IOKit delivers matched/terminated notifications through a run loop
+// source, so the watcher runs a CFRunLoop on a dedicated thread and
+// translates IORegistry entries into device events. As with the
+// Windows watcher the bindings are declared by hand: the surface is
+// small and the crate otherwise has no Apple-framework dependency.
+
+use std::ffi::{c_char, c_void, CString};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+
+use super::{partial_info, DeviceWatcher};
+
+type IoObject = u32;
+type IoIterator = u32;
+type KernReturn = i32;
+type CfRef = *const c_void;
+type CfMutableRef = *mut c_void;
+type CfRunLoopRef = *mut c_void;
+
+const KERN_SUCCESS: KernReturn = 0;
+const K_IO_MASTER_PORT_DEFAULT: u32 = 0;
+const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+const K_CF_NUMBER_SINT32_TYPE: isize = 3;
+
+const IO_USB_DEVICE_CLASS_NAME: &str = "IOUSBDevice";
+const FIRST_MATCH_NOTIFICATION: &str = "IOServiceFirstMatch";
+const TERMINATED_NOTIFICATION: &str = "IOServiceTerminate";
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IONotificationPortCreate(master_port: u32) -> *mut c_void;
+    fn IONotificationPortDestroy(port: *mut c_void);
+    fn IONotificationPortGetRunLoopSource(port: *mut c_void) -> CfRef;
+    fn IOServiceMatching(name: *const c_char) -> CfMutableRef;
+    fn IOServiceAddMatchingNotification(
+        port: *mut c_void,
+        notification_type: *const c_char,
+        matching: CfMutableRef,
+        callback: extern "C" fn(*mut c_void, IoIterator),
+        refcon: *mut c_void,
+        iterator: *mut IoIterator,
+    ) -> KernReturn;
+    fn IOIteratorNext(iterator: IoIterator) -> IoObject;
+    fn IOObjectRelease(object: IoObject) -> KernReturn;
+    fn IORegistryEntryCreateCFProperty(
+        entry: IoObject,
+        key: CfRef,
+        allocator: CfRef,
+        options: u32,
+    ) -> CfRef;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    static kCFRunLoopDefaultMode: CfRef;
+
+    fn CFRunLoopGetCurrent() -> CfRunLoopRef;
+    fn CFRunLoopRun();
+    fn CFRunLoopStop(run_loop: CfRunLoopRef);
+    fn CFRunLoopAddSource(run_loop: CfRunLoopRef, source: CfRef, mode: CfRef);
+    fn CFRelease(object: CfRef);
+    fn CFStringCreateWithCString(
+        allocator: CfRef,
+        string: *const c_char,
+        encoding: u32,
+    ) -> CfRef;
+    fn CFStringGetCString(
+        string: CfRef,
+        buffer: *mut c_char,
+        buffer_size: isize,
+        encoding: u32,
+    ) -> bool;
+    fn CFNumberGetValue(number: CfRef, number_type: isize, value_ptr: *mut c_void) -> bool;
+}
+
+/// Event direction a notification iterator reports for.
+#[derive(Clone, Copy)]
+enum Direction {
+    Added,
+    Removed,
+}
+
+/// Refcon payload for one notification callback: where events go and
+/// which direction this iterator reports.
+struct CallbackState {
+    sender: Sender<DeviceEvent>,
+    direction: Direction,
+}
+
+/**
+ * Hotplug monitor backed by `IOServiceAddMatchingNotification` on
+ * `IOUSBDevice`, with matched and terminated iterators on a dedicated
+ * CFRunLoop thread. Events carry the VID, PID, serial, and locationID
+ * read from the IORegistry entry; locationID lands in `port_path` in
+ * its conventional hex form and the rest of the record is filled in by
+ * the next enumeration pass.
+ */
+#[derive(Default)]
+pub struct MacOSDeviceWatcher {
+    run_loop: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MacOSDeviceWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DeviceWatcher for MacOSDeviceWatcher {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        let (event_tx, event_rx) = channel();
+        // The run loop must exist before start() returns so stop()
+        // always has something to stop.
+        let (ready_tx, ready_rx) = channel::<Result<usize, String>>();
+
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-watch".to_string())
+            .spawn(move || run_notification_loop(event_tx, ready_tx))
+            .map_err(UsbError::Io)?;
+
+        match ready_rx.recv() {
+            Ok(Ok(run_loop)) => {
+                self.run_loop = run_loop;
+                self.thread = Some(thread);
+                Ok(event_rx)
+            }
+            Ok(Err(message)) => {
+                let _ = thread.join();
+                Err(UsbError::Internal(message))
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(UsbError::Internal(
+                    "watcher thread exited before run loop start".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            unsafe { CFRunLoopStop(self.run_loop as CfRunLoopRef) };
+            let _ = thread.join();
+            self.run_loop = 0;
+        }
+    }
+}
+
+impl Drop for MacOSDeviceWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_notification_loop(sender: Sender<DeviceEvent>, ready: Sender<Result<usize, String>>) {
+    unsafe {
+        let port = IONotificationPortCreate(K_IO_MASTER_PORT_DEFAULT);
+        if port.is_null() {
+            let _ = ready.send(Err("IONotificationPortCreate failed".to_string()));
+            return;
+        }
+
+        // Each refcon carries the shared sender plus its direction.
+        let added = Box::into_raw(Box::new(CallbackState {
+            sender: sender.clone(),
+            direction: Direction::Added,
+        }));
+        let removed = Box::into_raw(Box::new(CallbackState {
+            sender,
+            direction: Direction::Removed,
+        }));
+
+        let class_name = CString::new(IO_USB_DEVICE_CLASS_NAME).unwrap();
+        let mut iterators = [0 as IoIterator; 2];
+        for (slot, (kind, refcon)) in iterators.iter_mut().zip([
+            (FIRST_MATCH_NOTIFICATION, added as *mut c_void),
+            (TERMINATED_NOTIFICATION, removed as *mut c_void),
+        ]) {
+            let kind = CString::new(kind).unwrap();
+            // IOServiceAddMatchingNotification consumes one matching
+            // dictionary reference per call.
+            let matching = IOServiceMatching(class_name.as_ptr());
+            let rc = IOServiceAddMatchingNotification(
+                port,
+                kind.as_ptr(),
+                matching,
+                device_notification,
+                refcon,
+                slot,
+            );
+            if rc != KERN_SUCCESS {
+                drop(Box::from_raw(added));
+                drop(Box::from_raw(removed));
+                IONotificationPortDestroy(port);
+                let _ = ready.send(Err(format!(
+                    "IOServiceAddMatchingNotification failed: {:#x}",
+                    rc
+                )));
+                return;
+            }
+            // Draining the iterator arms the notification; these are
+            // the devices already present, which enumeration covers.
+            drain_without_events(*slot);
+        }
+
+        let run_loop = CFRunLoopGetCurrent();
+        CFRunLoopAddSource(
+            run_loop,
+            IONotificationPortGetRunLoopSource(port),
+            kCFRunLoopDefaultMode,
+        );
+        let _ = ready.send(Ok(run_loop as usize));
+
+        CFRunLoopRun();
+
+        for iterator in iterators {
+            IOObjectRelease(iterator);
+        }
+        IONotificationPortDestroy(port);
+        drop(Box::from_raw(added));
+        drop(Box::from_raw(removed));
+    }
+}
+
+extern "C" fn device_notification(refcon: *mut c_void, iterator: IoIterator) {
+    let state = unsafe { &*(refcon as *const CallbackState) };
+    loop {
+        let device = unsafe { IOIteratorNext(iterator) };
+        if device == 0 {
+            break;
+        }
+        if let Some(event) = unsafe { translate(device, state.direction) } {
+            let _ = state.sender.send(event);
+        }
+        unsafe { IOObjectRelease(device) };
+    }
+}
+
+unsafe fn drain_without_events(iterator: IoIterator) {
+    loop {
+        let device = IOIteratorNext(iterator);
+        if device == 0 {
+            break;
+        }
+        IOObjectRelease(device);
+    }
+}
+
+unsafe fn translate(device: IoObject, direction: Direction) -> Option<DeviceEvent> {
+    let vendor_id = prop_u32(device, "idVendor")? as u16;
+    let product_id = prop_u32(device, "idProduct")? as u16;
+    let serial = prop_string(device, "USB Serial Number");
+    let location = prop_u32(device, "locationID");
+
+    let info = partial_info(
+        vendor_id,
+        product_id,
+        serial,
+        location.map(|l| format!("{:#010x}", l)),
+        match location {
+            Some(l) => format!("macos-location:{:#010x}", l),
+            None => "macos-location:unknown".to_string(),
+        },
+    );
+    Some(match direction {
+        Direction::Added => DeviceEvent::Connected(info),
+        Direction::Removed => DeviceEvent::Disconnected(DeviceIdentity::of(&info)),
+    })
+}
+
+unsafe fn prop(device: IoObject, key: &str) -> Option<CfRef> {
+    let key = CString::new(key).ok()?;
+    let key = CFStringCreateWithCString(std::ptr::null(), key.as_ptr(), K_CF_STRING_ENCODING_UTF8);
+    if key.is_null() {
+        return None;
+    }
+    let value = IORegistryEntryCreateCFProperty(device, key, std::ptr::null(), 0);
+    CFRelease(key);
+    (!value.is_null()).then_some(value)
+}
+
+unsafe fn prop_u32(device: IoObject, key: &str) -> Option<u32> {
+    let value = prop(device, key)?;
+    let mut out: i32 = 0;
+    let ok = CFNumberGetValue(
+        value,
+        K_CF_NUMBER_SINT32_TYPE,
+        &mut out as *mut i32 as *mut c_void,
+    );
+    CFRelease(value);
+    ok.then_some(out as u32)
+}
+
+unsafe fn prop_string(device: IoObject, key: &str) -> Option<String> {
+    let value = prop(device, key)?;
+    let mut buf = [0 as c_char; 256];
+    let ok = CFStringGetCString(
+        value,
+        buf.as_mut_ptr(),
+        buf.len() as isize,
+        K_CF_STRING_ENCODING_UTF8,
+    );
+    CFRelease(value);
+    if !ok {
+        return None;
+    }
+    let bytes: Vec<u8> = buf
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8(bytes).ok()
+}
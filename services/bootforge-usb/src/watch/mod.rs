@@ -0,0 +1,166 @@
+// BootForge USB - Hotplug watchers
+// Platform watchers deliver DeviceEvent over a std mpsc channel from a
+// background thread; stop() tears the thread down so start/stop cycles
+// do not leak OS handles. PollingWatcher is the portable fallback for
+// hosts without a native notification source.
+
+use std::sync::mpsc::Receiver;
+
+use crate::enumeration::UsbDeviceInfo;
+use crate::error::UsbError;
+use crate::events::DeviceEvent;
+
+pub mod debounce;
+pub use self::debounce::{DebouncedWatcher, ReplugPolicy};
+pub mod enrich;
+pub use self::enrich::{default_enrichment, EnrichedWatcher, Enricher};
+pub mod libusb;
+pub use self::libusb::LibusbHotplugWatcher;
+pub mod polling;
+pub use self::polling::{PollingWatcher, SnapshotSource};
+pub mod recording;
+pub use self::recording::{RecordedEvent, RecordingWatcher, ReplayWatcher};
+#[cfg(target_os = "macos")]
+pub mod macos;
+#[cfg(target_os = "macos")]
+pub use self::macos::MacOSDeviceWatcher;
+#[cfg(windows)]
+pub mod windows;
+#[cfg(windows)]
+pub use self::windows::WindowsDeviceWatcher;
+
+/**
+ * A running hotplug monitor. Dropping a watcher without `stop()` is
+ * allowed but may leak the background thread until process exit;
+ * implementations stop on Drop where the platform permits.
+ */
+pub trait DeviceWatcher {
+    /// Start monitoring; events arrive on the returned channel until
+    /// `stop` is called or the watcher is dropped.
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError>;
+
+    /// Stop monitoring and release platform resources. Idempotent.
+    fn stop(&mut self);
+}
+
+/**
+ * Parse a Windows device interface path such as
+ * `\\?\USB#VID_046D&PID_C52B#5&2f3a8c0&0&2#{a5dcbf10-...}` into the
+ * vendor id, product id, and instance segment. The instance segment is
+ * the device serial number when the device has one, otherwise a
+ * bus-position string minted by Windows.
+ */
+pub fn parse_interface_path(path: &str) -> Option<(u16, u16, String)> {
+    let trimmed = path.strip_prefix(r"\\?\").unwrap_or(path);
+    let mut parts = trimmed.split('#');
+    if !parts.next()?.eq_ignore_ascii_case("usb") {
+        return None;
+    }
+
+    let mut vendor_id = None;
+    let mut product_id = None;
+    for token in parts.next()?.split('&') {
+        if token.len() == 8 {
+            let (key, hex) = token.split_at(4);
+            if key.eq_ignore_ascii_case("vid_") {
+                vendor_id = u16::from_str_radix(hex, 16).ok();
+            } else if key.eq_ignore_ascii_case("pid_") {
+                product_id = u16::from_str_radix(hex, 16).ok();
+            }
+        }
+    }
+
+    let instance = parts.next()?;
+    if instance.is_empty() {
+        return None;
+    }
+    Some((vendor_id?, product_id?, instance.to_string()))
+}
+
+/**
+ * Build the partial `UsbDeviceInfo` a notification-driven watcher can
+ * report before the device is enumerable: VID/PID and serial from the
+ * interface path, zeroed bus position and descriptor. The full path is
+ * kept as a `windows-instance:` tag so consumers can correlate the
+ * event with a later enumeration pass.
+ *
+ * Windows-minted instance ids (bus positions like `5&2f3a8c0&0&2`)
+ * contain `&`; only instance segments without one are treated as a
+ * device serial number.
+ */
+pub fn info_from_interface_path(path: &str) -> Option<UsbDeviceInfo> {
+    let (vendor_id, product_id, instance) = parse_interface_path(path)?;
+    Some(partial_info(
+        vendor_id,
+        product_id,
+        (!instance.contains('&')).then_some(instance),
+        None,
+        format!("windows-instance:{}", path),
+    ))
+}
+
+/// Shared shape for notification-driven partial records: identity
+/// fields as reported, zeroed bus position and descriptor, one
+/// platform-hint tag.
+pub(crate) fn partial_info(
+    vendor_id: u16,
+    product_id: u16,
+    serial_number: Option<String>,
+    port_path: Option<String>,
+    platform_tag: String,
+) -> UsbDeviceInfo {
+    let mut builder = UsbDeviceInfo::builder(vendor_id, product_id).tag(platform_tag);
+    if let Some(serial) = serial_number {
+        builder = builder.serial(serial);
+    }
+    if let Some(port_path) = port_path {
+        builder = builder.port_path(port_path);
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interface_path() {
+        let (vid, pid, instance) = parse_interface_path(
+            r"\\?\USB#VID_046D&PID_C52B#5&2f3a8c0&0&2#{a5dcbf10-6530-11d2-901f-00c04fb951ed}",
+        )
+        .unwrap();
+        assert_eq!((vid, pid), (0x046d, 0xc52b));
+        assert_eq!(instance, "5&2f3a8c0&0&2");
+    }
+
+    #[test]
+    fn test_parse_serial_instance_and_case() {
+        // Devices with a real serial get it as the instance segment,
+        // and the prefix casing varies between notification sources.
+        let (vid, pid, instance) =
+            parse_interface_path(r"\\?\usb#vid_18d1&pid_4ee7#29061FDH300EXZ#{a5dcbf10}").unwrap();
+        assert_eq!((vid, pid), (0x18d1, 0x4ee7));
+        assert_eq!(instance, "29061FDH300EXZ");
+    }
+
+    #[test]
+    fn test_parse_rejects_foreign_paths() {
+        assert_eq!(parse_interface_path(r"\\?\HID#VID_046D&PID_C52B#2#{gid}"), None);
+        assert_eq!(parse_interface_path(r"\\?\USB#ROOT_HUB30#4&a#{gid}"), None);
+        assert_eq!(parse_interface_path(""), None);
+    }
+
+    #[test]
+    fn test_info_from_interface_path() {
+        let path = r"\\?\USB#VID_18D1&PID_4EE7#29061FDH300EXZ#{a5dcbf10}";
+        let info = info_from_interface_path(path).unwrap();
+        assert_eq!((info.vendor_id, info.product_id), (0x18d1, 0x4ee7));
+        assert_eq!(info.serial_number.as_deref(), Some("29061FDH300EXZ"));
+        assert_eq!(info.tags, vec![format!("windows-instance:{}", path)]);
+
+        // A bus-position instance id is not a serial.
+        let info = info_from_interface_path(r"\\?\USB#VID_046D&PID_C52B#5&2f3a8c0&0&2#{gid}")
+            .unwrap();
+        assert_eq!(info.serial_number, None);
+    }
+}
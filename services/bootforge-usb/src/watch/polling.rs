@@ -0,0 +1,422 @@
+// BootForge USB - Polling watcher
+// The portable fallback watcher: snapshots enumeration on an interval
+// and diffs consecutive snapshots by canonical identity. Used where no
+// native notification source exists (or is still a stub); the snapshot
+// source is a trait object so tests can script device lists.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::enumeration::{DeviceFilter, FallbackEnumerator, UsbDeviceInfo, UsbDeviceRecord};
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+use crate::health::{HealthThresholds, LinkHealthTracker};
+
+use super::DeviceWatcher;
+
+/**
+ * Where a polling pass gets its device list. The production source is
+ * `FallbackEnumerator`; tests inject scripted lists.
+ */
+pub trait SnapshotSource: Send {
+    fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError>;
+}
+
+/// `FallbackEnumerator` records lifted into the info shape events use.
+struct FallbackSource {
+    enumerator: FallbackEnumerator,
+}
+
+impl SnapshotSource for FallbackSource {
+    fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+        Ok(self
+            .enumerator
+            .enumerate()?
+            .iter()
+            .map(info_from_record)
+            .collect())
+    }
+}
+
+/// A sysfs record as the partial info a watcher event carries; the port
+/// path comes from the sysfs directory name.
+fn info_from_record(record: &UsbDeviceRecord) -> UsbDeviceInfo {
+    let mut info = super::partial_info(
+        record.vendor_id,
+        record.product_id,
+        record.serial_number.clone(),
+        record
+            .sysfs_path
+            .rsplit('/')
+            .next()
+            .filter(|name| name.contains('-'))
+            .map(str::to_string),
+        format!("sysfs:{}", record.sysfs_path),
+    );
+    info.bus_number = record.bus_number;
+    info.address = record.device_number;
+    info.descriptor = record.descriptor.clone();
+    info.manufacturer = record.manufacturer.clone();
+    info.product = record.product.clone();
+    info
+}
+
+/**
+ * Cross-platform watcher that polls a snapshot source on an interval
+ * and emits Connected / Disconnected / Changed by diffing consecutive
+ * snapshots under the canonical identity rules. The first pass reports
+ * every present device as Connected so consumers start from a known
+ * state.
+ *
+ * `stop()` wakes the polling thread immediately and joins it, so
+ * teardown never waits out the interval.
+ */
+pub struct PollingWatcher {
+    interval: Duration,
+    filter: Option<DeviceFilter>,
+    source: Arc<Mutex<Box<dyn SnapshotSource>>>,
+    health: Option<Arc<Mutex<LinkHealthTracker>>>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PollingWatcher {
+    pub fn new(interval: Duration, filter: Option<DeviceFilter>) -> Self {
+        Self::with_source(
+            interval,
+            filter,
+            Box::new(FallbackSource {
+                enumerator: FallbackEnumerator::new(),
+            }),
+        )
+    }
+
+    /// Poll an injected source instead of sysfs enumeration.
+    pub fn with_source(
+        interval: Duration,
+        filter: Option<DeviceFilter>,
+        source: Box<dyn SnapshotSource>,
+    ) -> Self {
+        PollingWatcher {
+            interval,
+            filter,
+            source: Arc::new(Mutex::new(source)),
+            health: None,
+            stop: None,
+            thread: None,
+        }
+    }
+
+    /**
+     * Track link health across polls. Emitted records carry the verdict
+     * as a `health:` tag (see `LinkHealth::tag`), and the shared
+     * tracker is available through `health_tracker` so transfer errors
+     * can be fed in from outside.
+     */
+    pub fn with_health(mut self, thresholds: HealthThresholds) -> Self {
+        self.health = Some(Arc::new(Mutex::new(LinkHealthTracker::new(thresholds))));
+        self
+    }
+
+    /// The shared tracker when health tracking is enabled.
+    pub fn health_tracker(&self) -> Option<Arc<Mutex<LinkHealthTracker>>> {
+        self.health.clone()
+    }
+}
+
+impl DeviceWatcher for PollingWatcher {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        let (event_tx, event_rx) = channel();
+        let (stop_tx, stop_rx) = channel::<()>();
+        let source = Arc::clone(&self.source);
+        let filter = self.filter.clone();
+        let health = self.health.clone();
+        let interval = self.interval;
+
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-watch".to_string())
+            .spawn(move || run_polling_loop(source, filter, health, interval, event_tx, stop_rx))
+            .map_err(UsbError::Io)?;
+
+        self.stop = Some(stop_tx);
+        self.thread = Some(thread);
+        Ok(event_rx)
+    }
+
+    fn stop(&mut self) {
+        // Dropping the sender wakes recv_timeout with Disconnected.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for PollingWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_polling_loop(
+    source: Arc<Mutex<Box<dyn SnapshotSource>>>,
+    filter: Option<DeviceFilter>,
+    health: Option<Arc<Mutex<LinkHealthTracker>>>,
+    interval: Duration,
+    events: Sender<DeviceEvent>,
+    stop: Receiver<()>,
+) {
+    let mut known: BTreeMap<String, UsbDeviceInfo> = BTreeMap::new();
+    // Observe the event for health bookkeeping, stamp the verdict onto
+    // the record it carries, and forward it.
+    let emit = |event: DeviceEvent| {
+        let event = match &health {
+            Some(tracker) => match tracker.lock() {
+                Ok(mut tracker) => {
+                    tracker.observe(&event);
+                    stamp_health(event, &tracker)
+                }
+                Err(_) => event,
+            },
+            None => event,
+        };
+        let _ = events.send(event);
+    };
+    loop {
+        // A failed pass (sysfs momentarily unreadable, source racing a
+        // replug) keeps the previous state rather than reporting every
+        // device as removed.
+        let snapshot = source.lock().map(|mut s| s.snapshot()).unwrap_or_else(|e| {
+            Err(UsbError::Internal(format!("snapshot source poisoned: {}", e)))
+        });
+        if let Ok(devices) = snapshot {
+            let current: BTreeMap<String, UsbDeviceInfo> = devices
+                .into_iter()
+                .filter(|d| filter.as_ref().is_none_or(|f| f.matches(d)))
+                .map(|d| (DeviceIdentity::of(&d).0, d))
+                .collect();
+
+            for (identity, before) in &known {
+                match current.get(identity) {
+                    None => {
+                        emit(DeviceEvent::Disconnected(DeviceIdentity(identity.clone())));
+                    }
+                    Some(after) if after != before => {
+                        emit(DeviceEvent::Changed {
+                            identity: DeviceIdentity(identity.clone()),
+                            before: Box::new(before.clone()),
+                            after: Box::new(after.clone()),
+                        });
+                    }
+                    Some(_) => {}
+                }
+            }
+            for (identity, info) in &current {
+                if !known.contains_key(identity) {
+                    emit(DeviceEvent::Connected(info.clone()));
+                }
+            }
+            known = current;
+        }
+
+        match stop.recv_timeout(interval) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// Attach the tracker's verdict to the record an event carries, as a
+/// `health:` tag on Connected / Changed.
+fn stamp_health(event: DeviceEvent, tracker: &LinkHealthTracker) -> DeviceEvent {
+    let stamp = |info: &mut UsbDeviceInfo| {
+        if let Some(tag) = tracker.assess(&DeviceIdentity::of(info)).tag() {
+            if !info.tags.iter().any(|t| t == tag) {
+                info.tags.push(tag.to_string());
+            }
+        }
+    };
+    match event {
+        DeviceEvent::Connected(mut info) => {
+            stamp(&mut info);
+            DeviceEvent::Connected(info)
+        }
+        DeviceEvent::Changed {
+            identity,
+            before,
+            mut after,
+        } => {
+            stamp(&mut after);
+            DeviceEvent::Changed {
+                identity,
+                before,
+                after,
+            }
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    const POLL: Duration = Duration::from_millis(1);
+    const WAIT: Duration = Duration::from_secs(5);
+
+    /// Scripted source: one device list per poll, repeating the last
+    /// list once the script runs out.
+    struct Scripted {
+        lists: VecDeque<Vec<UsbDeviceInfo>>,
+        last: Vec<UsbDeviceInfo>,
+    }
+
+    impl Scripted {
+        fn new(lists: Vec<Vec<UsbDeviceInfo>>) -> Box<Self> {
+            Box::new(Scripted {
+                lists: lists.into(),
+                last: Vec::new(),
+            })
+        }
+    }
+
+    impl SnapshotSource for Scripted {
+        fn snapshot(&mut self) -> Result<Vec<UsbDeviceInfo>, UsbError> {
+            if let Some(list) = self.lists.pop_front() {
+                self.last = list;
+            }
+            Ok(self.last.clone())
+        }
+    }
+
+    fn device(vendor_id: u16, serial: &str) -> UsbDeviceInfo {
+        super::super::partial_info(
+            vendor_id,
+            0x0001,
+            Some(serial.to_string()),
+            None,
+            "test:polling".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_connect_change_disconnect_sequence() {
+        let mut changed = device(0x18d1, "A");
+        changed.tags.push("descriptor:malformed-strings".to_string());
+
+        let mut watcher = PollingWatcher::with_source(
+            POLL,
+            None,
+            Scripted::new(vec![
+                vec![device(0x18d1, "A")],
+                vec![changed],
+                vec![],
+            ]),
+        );
+        let events = watcher.start().unwrap();
+
+        assert!(matches!(
+            events.recv_timeout(WAIT).unwrap(),
+            DeviceEvent::Connected(info) if info.serial_number.as_deref() == Some("A")
+        ));
+        match events.recv_timeout(WAIT).unwrap() {
+            DeviceEvent::Changed { before, after, .. } => {
+                assert!(before.tags.len() < after.tags.len());
+            }
+            other => panic!("expected Changed, got {:?}", other),
+        }
+        assert!(matches!(
+            events.recv_timeout(WAIT).unwrap(),
+            DeviceEvent::Disconnected(_)
+        ));
+
+        watcher.stop();
+    }
+
+    #[test]
+    fn test_filter_limits_events() {
+        let mut watcher = PollingWatcher::with_source(
+            POLL,
+            Some(DeviceFilter::any().with_vendor_id(0x18d1)),
+            Scripted::new(vec![vec![device(0x18d1, "A"), device(0x2109, "HUB")]]),
+        );
+        let events = watcher.start().unwrap();
+
+        match events.recv_timeout(WAIT).unwrap() {
+            DeviceEvent::Connected(info) => assert_eq!(info.vendor_id, 0x18d1),
+            other => panic!("expected Connected, got {:?}", other),
+        }
+        // The filtered-out hub never produces an event.
+        watcher.stop();
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_flapping_device_carries_health_tag() {
+        use crate::health::HealthThresholds;
+
+        // Two quick disconnects trip the Unstable threshold; the third
+        // arrival carries the verdict as a tag.
+        let mut watcher = PollingWatcher::with_source(
+            POLL,
+            None,
+            Scripted::new(vec![
+                vec![device(0x18d1, "A")],
+                vec![],
+                vec![device(0x18d1, "A")],
+                vec![],
+                vec![device(0x18d1, "A")],
+            ]),
+        )
+        .with_health(HealthThresholds::default().with_unstable(2, Duration::from_secs(3600)));
+        let events = watcher.start().unwrap();
+
+        let mut tags_per_connect = Vec::new();
+        for _ in 0..5 {
+            match events.recv_timeout(WAIT).unwrap() {
+                DeviceEvent::Connected(info) => tags_per_connect.push(info.tags),
+                DeviceEvent::Disconnected(_) => {}
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        watcher.stop();
+
+        assert_eq!(tags_per_connect.len(), 3);
+        assert!(!tags_per_connect[0].contains(&"health:unstable".to_string()));
+        assert!(tags_per_connect[2].contains(&"health:unstable".to_string()));
+    }
+
+    #[test]
+    fn test_stop_joins_promptly_and_restarts() {
+        let mut watcher = PollingWatcher::with_source(
+            Duration::from_secs(3600),
+            None,
+            Scripted::new(vec![vec![device(0x18d1, "A")]]),
+        );
+        let events = watcher.start().unwrap();
+        let _ = events.recv_timeout(WAIT).unwrap();
+
+        // The thread is parked in a one-hour wait; stop must not sit it
+        // out.
+        let started = std::time::Instant::now();
+        watcher.stop();
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // A stopped watcher can start again.
+        let events = watcher.start().unwrap();
+        assert!(matches!(
+            events.recv_timeout(WAIT).unwrap(),
+            DeviceEvent::Connected(_)
+        ));
+        watcher.stop();
+    }
+}
@@ -0,0 +1,366 @@
+// BootForge USB - Event recording and replay
+// Reproducing a customer's plug/unplug sequence should not require the
+// physical devices. RecordingWatcher journals the event stream of any
+// watcher to a JSONL file; ReplayWatcher plays such a file back as a
+// watcher of its own, with original timing or as fast as possible.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::UsbError;
+use crate::events::DeviceEvent;
+
+use super::DeviceWatcher;
+
+/**
+ * One line of a recording: the event as serde's tagged-enum form, plus
+ * its offset from the start of the recording in milliseconds. The
+ * offset is monotonic within a file; replay sleeps the deltas.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordedEvent {
+    pub offset_ms: u64,
+    pub event: DeviceEvent,
+}
+
+/**
+ * Wraps any `DeviceWatcher` and appends each event to a JSONL file as
+ * it flows through, then forwards it unchanged. The file is flushed
+ * per line, so a crashed session loses at most the line being written
+ * - which `ReplayWatcher` tolerates.
+ */
+pub struct RecordingWatcher<W: DeviceWatcher> {
+    inner: W,
+    path: PathBuf,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<W: DeviceWatcher> RecordingWatcher<W> {
+    /// Record to `path`, appending if the file already exists.
+    pub fn new(inner: W, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            thread: None,
+        }
+    }
+}
+
+impl<W: DeviceWatcher> DeviceWatcher for RecordingWatcher<W> {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let inner_rx = self.inner.start()?;
+        let (event_tx, event_rx) = channel();
+
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-record".to_string())
+            .spawn(move || run_record_loop(inner_rx, event_tx, file))
+            .map_err(UsbError::Io)?;
+
+        self.thread = Some(thread);
+        Ok(event_rx)
+    }
+
+    fn stop(&mut self) {
+        self.inner.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<W: DeviceWatcher> Drop for RecordingWatcher<W> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_record_loop(inner: Receiver<DeviceEvent>, sender: Sender<DeviceEvent>, file: File) {
+    let started = Instant::now();
+    let mut writer = BufWriter::new(file);
+    for event in inner {
+        let record = RecordedEvent {
+            offset_ms: started.elapsed().as_millis() as u64,
+            event,
+        };
+        // A full disk must not take the live event stream down with
+        // it; the recording just stops growing.
+        if let Ok(line) = serde_json::to_string(&record) {
+            let _ = writeln!(writer, "{}", line);
+            let _ = writer.flush();
+        }
+        if sender.send(record.event).is_err() {
+            return;
+        }
+    }
+}
+
+/**
+ * A `DeviceWatcher` that emits the events of a recorded JSONL file. By
+ * default events come as fast as the consumer takes them;
+ * `with_original_timing` sleeps out the recorded offsets instead.
+ *
+ * Parsing stops at the first malformed line, so the partially-written
+ * tail of a crashed recording session is dropped rather than failing
+ * the whole replay.
+ */
+pub struct ReplayWatcher {
+    path: PathBuf,
+    original_timing: bool,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ReplayWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ReplayWatcher {
+            path: path.into(),
+            original_timing: false,
+            stop: None,
+            thread: None,
+        }
+    }
+
+    /// Sleep out the recorded offsets instead of emitting immediately.
+    pub fn with_original_timing(mut self, original_timing: bool) -> Self {
+        self.original_timing = original_timing;
+        self
+    }
+
+    /// Parse a recording up front, without starting a watcher. Stops
+    /// at the first malformed line.
+    pub fn load(path: impl AsRef<Path>) -> Result<Vec<RecordedEvent>, UsbError> {
+        let reader = BufReader::new(File::open(path.as_ref())?);
+        let mut records = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<RecordedEvent>(&line) {
+                Ok(record) => records.push(record),
+                // Truncated tail of a crashed session; everything up
+                // to here is still good.
+                Err(_) => break,
+            }
+        }
+        Ok(records)
+    }
+}
+
+impl DeviceWatcher for ReplayWatcher {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        // Read the file up front so a missing or unreadable recording
+        // fails start() instead of silently replaying nothing.
+        let records = ReplayWatcher::load(&self.path)?;
+        let (event_tx, event_rx) = channel();
+        let (stop_tx, stop_rx) = channel();
+        let original_timing = self.original_timing;
+
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-replay".to_string())
+            .spawn(move || run_replay_loop(records, event_tx, stop_rx, original_timing))
+            .map_err(UsbError::Io)?;
+
+        self.stop = Some(stop_tx);
+        self.thread = Some(thread);
+        Ok(event_rx)
+    }
+
+    fn stop(&mut self) {
+        // Hanging up the stop channel wakes a sleeping replay thread.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ReplayWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_replay_loop(
+    records: Vec<RecordedEvent>,
+    sender: Sender<DeviceEvent>,
+    stop: Receiver<()>,
+    original_timing: bool,
+) {
+    let mut last_offset = 0u64;
+    for record in records {
+        if original_timing {
+            let delay = Duration::from_millis(record.offset_ms.saturating_sub(last_offset));
+            last_offset = record.offset_ms;
+            if !delay.is_zero() {
+                match stop.recv_timeout(delay) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        }
+        if sender.send(record.event).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::DeviceIdentity;
+    use crate::watch::partial_info;
+    use std::fs;
+
+    struct ScriptedWatcher {
+        script: Vec<DeviceEvent>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl ScriptedWatcher {
+        fn new(script: Vec<DeviceEvent>) -> Self {
+            Self {
+                script,
+                thread: None,
+            }
+        }
+    }
+
+    impl DeviceWatcher for ScriptedWatcher {
+        fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+            let (tx, rx) = channel();
+            let script = std::mem::take(&mut self.script);
+            self.thread = Some(std::thread::spawn(move || {
+                for event in script {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }));
+            Ok(rx)
+        }
+
+        fn stop(&mut self) {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn recording_path(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join("bootforge-usb-tests")
+            .join(test)
+            .join(format!("{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("events.jsonl")
+    }
+
+    fn script() -> Vec<DeviceEvent> {
+        let info = partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("SERIAL1".to_string()),
+            None,
+            "test".to_string(),
+        );
+        let identity = DeviceIdentity::of(&info);
+        vec![
+            DeviceEvent::Connected(info),
+            DeviceEvent::Disconnected(identity),
+        ]
+    }
+
+    #[test]
+    fn test_record_then_replay_round_trips() {
+        let path = recording_path("round_trip");
+
+        let mut recorder = RecordingWatcher::new(ScriptedWatcher::new(script()), &path);
+        let recorded: Vec<DeviceEvent> = recorder.start().unwrap().iter().collect();
+        recorder.stop();
+        // Recording is transparent to the live consumer.
+        assert_eq!(recorded, script());
+
+        let mut replay = ReplayWatcher::new(&path);
+        let replayed: Vec<DeviceEvent> = replay.start().unwrap().iter().collect();
+        replay.stop();
+        assert_eq!(replayed, script());
+    }
+
+    #[test]
+    fn test_replay_tolerates_truncated_last_line() {
+        let path = recording_path("truncated");
+        {
+            let mut recorder = RecordingWatcher::new(ScriptedWatcher::new(script()), &path);
+            let rx = recorder.start().unwrap();
+            let _: Vec<DeviceEvent> = rx.iter().collect();
+            recorder.stop();
+        }
+        // Simulate a crash mid-write: chop the last line in half.
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut lines = contents.lines();
+        let first = lines.next().unwrap();
+        let second = lines.next().unwrap();
+        fs::write(
+            &path,
+            format!("{}\n{}", first, &second[..second.len() / 2]),
+        )
+        .unwrap();
+
+        let records = ReplayWatcher::load(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(matches!(records[0].event, DeviceEvent::Connected(_)));
+    }
+
+    #[test]
+    fn test_replay_missing_file_fails_start() {
+        let path = recording_path("missing").join("nope.jsonl");
+        let mut replay = ReplayWatcher::new(path);
+        assert!(matches!(replay.start(), Err(UsbError::Io(_))));
+    }
+
+    #[test]
+    fn test_replay_honours_recorded_offsets() {
+        let path = recording_path("timing");
+        let mut lines = String::new();
+        for (offset, event) in [(0u64, script().remove(0)), (60, script().remove(1))] {
+            let record = RecordedEvent {
+                offset_ms: offset,
+                event,
+            };
+            lines.push_str(&serde_json::to_string(&record).unwrap());
+            lines.push('\n');
+        }
+        fs::write(&path, lines).unwrap();
+
+        let mut replay = ReplayWatcher::new(&path).with_original_timing(true);
+        let started = Instant::now();
+        let events: Vec<DeviceEvent> = replay.start().unwrap().iter().collect();
+        replay.stop();
+        assert_eq!(events.len(), 2);
+        assert!(
+            started.elapsed() >= Duration::from_millis(60),
+            "replay finished in {:?}",
+            started.elapsed()
+        );
+    }
+}
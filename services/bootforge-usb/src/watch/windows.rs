@@ -0,0 +1,339 @@
+// BootForge USB - Windows hotplug watcher
+// RegisterDeviceNotification needs a window to deliver WM_DEVICECHANGE
+// to, so the watcher runs a hidden message-only window on a background
+// thread. The bindings below are declared by hand rather than pulled
+// from a bindings crate: the surface is a dozen user32 calls and the
+// crate otherwise has no Windows dependency.
+
+use std::ffi::c_void;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+
+use crate::error::UsbError;
+use crate::events::{DeviceEvent, DeviceIdentity};
+
+use super::{info_from_interface_path, DeviceWatcher};
+
+type Hwnd = isize;
+type Hdevnotify = isize;
+
+const WM_CREATE: u32 = 0x0001;
+const WM_DESTROY: u32 = 0x0002;
+const WM_CLOSE: u32 = 0x0010;
+const WM_DEVICECHANGE: u32 = 0x0219;
+
+const DBT_DEVICEARRIVAL: usize = 0x8000;
+const DBT_DEVICEREMOVECOMPLETE: usize = 0x8004;
+const DBT_DEVTYP_DEVICEINTERFACE: u32 = 5;
+const DEVICE_NOTIFY_WINDOW_HANDLE: u32 = 0;
+
+const HWND_MESSAGE: Hwnd = -3;
+const GWLP_USERDATA: i32 = -21;
+
+/// {A5DCBF10-6530-11D2-901F-00C04FB951ED}
+const GUID_DEVINTERFACE_USB_DEVICE: Guid = Guid {
+    data1: 0xa5dcbf10,
+    data2: 0x6530,
+    data3: 0x11d2,
+    data4: [0x90, 0x1f, 0x00, 0xc0, 0x4f, 0xb9, 0x51, 0xed],
+};
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Guid {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+#[repr(C)]
+struct DevBroadcastDeviceInterfaceW {
+    dbcc_size: u32,
+    dbcc_devicetype: u32,
+    dbcc_reserved: u32,
+    dbcc_classguid: Guid,
+    dbcc_name: [u16; 1],
+}
+
+#[repr(C)]
+struct WndClassW {
+    style: u32,
+    lpfn_wnd_proc: unsafe extern "system" fn(Hwnd, u32, usize, isize) -> isize,
+    cb_cls_extra: i32,
+    cb_wnd_extra: i32,
+    h_instance: isize,
+    h_icon: isize,
+    h_cursor: isize,
+    hbr_background: isize,
+    lpsz_menu_name: *const u16,
+    lpsz_class_name: *const u16,
+}
+
+#[repr(C)]
+struct Msg {
+    hwnd: Hwnd,
+    message: u32,
+    w_param: usize,
+    l_param: isize,
+    time: u32,
+    pt: [i32; 2],
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetModuleHandleW(module_name: *const u16) -> isize;
+    fn GetLastError() -> u32;
+}
+
+#[link(name = "user32")]
+extern "system" {
+    fn RegisterClassW(class: *const WndClassW) -> u16;
+    fn CreateWindowExW(
+        ex_style: u32,
+        class_name: *const u16,
+        window_name: *const u16,
+        style: u32,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        parent: Hwnd,
+        menu: isize,
+        instance: isize,
+        param: *mut c_void,
+    ) -> Hwnd;
+    fn DefWindowProcW(hwnd: Hwnd, msg: u32, w_param: usize, l_param: isize) -> isize;
+    fn GetMessageW(msg: *mut Msg, hwnd: Hwnd, filter_min: u32, filter_max: u32) -> i32;
+    fn DispatchMessageW(msg: *const Msg) -> isize;
+    fn PostMessageW(hwnd: Hwnd, msg: u32, w_param: usize, l_param: isize) -> i32;
+    fn PostQuitMessage(exit_code: i32);
+    fn SetWindowLongPtrW(hwnd: Hwnd, index: i32, value: isize) -> isize;
+    fn GetWindowLongPtrW(hwnd: Hwnd, index: i32) -> isize;
+    fn RegisterDeviceNotificationW(
+        recipient: Hwnd,
+        filter: *const c_void,
+        flags: u32,
+    ) -> Hdevnotify;
+    fn UnregisterDeviceNotification(handle: Hdevnotify) -> i32;
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Per-window state, boxed and stowed in GWLP_USERDATA between
+/// WM_CREATE and WM_DESTROY.
+struct WatcherState {
+    sender: Sender<DeviceEvent>,
+    notify: Hdevnotify,
+}
+
+/**
+ * Hotplug monitor backed by `RegisterDeviceNotification` for
+ * `GUID_DEVINTERFACE_USB_DEVICE`. Arrival and removal notifications are
+ * translated into `DeviceEvent::Connected` / `Disconnected` with the
+ * VID, PID, and serial parsed from the device interface path; the rest
+ * of the record is filled in by the next enumeration pass.
+ */
+#[derive(Default)]
+pub struct WindowsDeviceWatcher {
+    hwnd: Hwnd,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WindowsDeviceWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DeviceWatcher for WindowsDeviceWatcher {
+    fn start(&mut self) -> Result<Receiver<DeviceEvent>, UsbError> {
+        if self.thread.is_some() {
+            return Err(UsbError::Internal("watcher already started".to_string()));
+        }
+
+        let (event_tx, event_rx) = channel();
+        // The window must exist before start() returns so stop() always
+        // has somewhere to post WM_CLOSE.
+        let (ready_tx, ready_rx) = channel::<Result<Hwnd, String>>();
+
+        let thread = std::thread::Builder::new()
+            .name("bootforge-usb-watch".to_string())
+            .spawn(move || run_message_loop(event_tx, ready_tx))
+            .map_err(UsbError::Io)?;
+
+        match ready_rx.recv() {
+            Ok(Ok(hwnd)) => {
+                self.hwnd = hwnd;
+                self.thread = Some(thread);
+                Ok(event_rx)
+            }
+            Ok(Err(message)) => {
+                let _ = thread.join();
+                Err(UsbError::Internal(message))
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(UsbError::Internal(
+                    "watcher thread exited before window creation".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            // WM_CLOSE -> DestroyWindow -> WM_DESTROY unregisters the
+            // notification handle and posts the quit message.
+            unsafe { PostMessageW(self.hwnd, WM_CLOSE, 0, 0) };
+            let _ = thread.join();
+            self.hwnd = 0;
+        }
+    }
+}
+
+impl Drop for WindowsDeviceWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_message_loop(sender: Sender<DeviceEvent>, ready: Sender<Result<Hwnd, String>>) {
+    let class_name = wide("BootForgeUsbWatch");
+    let instance = unsafe { GetModuleHandleW(std::ptr::null()) };
+    let class = WndClassW {
+        style: 0,
+        lpfn_wnd_proc: wnd_proc,
+        cb_cls_extra: 0,
+        cb_wnd_extra: 0,
+        h_instance: instance,
+        h_icon: 0,
+        h_cursor: 0,
+        hbr_background: 0,
+        lpsz_menu_name: std::ptr::null(),
+        lpsz_class_name: class_name.as_ptr(),
+    };
+    // Re-registration across start/stop cycles fails with
+    // ERROR_CLASS_ALREADY_EXISTS; the existing class is fine to reuse.
+    unsafe { RegisterClassW(&class) };
+
+    let state = Box::into_raw(Box::new(WatcherState { sender, notify: 0 }));
+    let hwnd = unsafe {
+        CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            std::ptr::null(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            0,
+            instance,
+            state as *mut c_void,
+        )
+    };
+    if hwnd == 0 {
+        let err = unsafe { GetLastError() };
+        drop(unsafe { Box::from_raw(state) });
+        let _ = ready.send(Err(format!("CreateWindowExW failed: error {}", err)));
+        return;
+    }
+    let _ = ready.send(Ok(hwnd));
+
+    let mut msg = Msg {
+        hwnd: 0,
+        message: 0,
+        w_param: 0,
+        l_param: 0,
+        time: 0,
+        pt: [0; 2],
+    };
+    // GetMessageW returns 0 on WM_QUIT and -1 on error; stop on both.
+    while unsafe { GetMessageW(&mut msg, 0, 0, 0) } > 0 {
+        unsafe { DispatchMessageW(&msg) };
+    }
+}
+
+unsafe extern "system" fn wnd_proc(hwnd: Hwnd, msg: u32, w_param: usize, l_param: isize) -> isize {
+    match msg {
+        WM_CREATE => {
+            // lpCreateParams is the first field of CREATESTRUCTW.
+            let state = *(l_param as *const *mut WatcherState);
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, state as isize);
+
+            let filter = DevBroadcastDeviceInterfaceW {
+                dbcc_size: std::mem::size_of::<DevBroadcastDeviceInterfaceW>() as u32,
+                dbcc_devicetype: DBT_DEVTYP_DEVICEINTERFACE,
+                dbcc_reserved: 0,
+                dbcc_classguid: GUID_DEVINTERFACE_USB_DEVICE,
+                dbcc_name: [0],
+            };
+            let notify = RegisterDeviceNotificationW(
+                hwnd,
+                &filter as *const _ as *const c_void,
+                DEVICE_NOTIFY_WINDOW_HANDLE,
+            );
+            if notify == 0 {
+                log::warn!(
+                    "RegisterDeviceNotificationW failed: error {}; hotplug events unavailable",
+                    GetLastError()
+                );
+            }
+            (*state).notify = notify;
+            0
+        }
+        WM_DEVICECHANGE => {
+            if w_param == DBT_DEVICEARRIVAL || w_param == DBT_DEVICEREMOVECOMPLETE {
+                if let Some(path) = interface_path_from_broadcast(l_param) {
+                    let state = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut WatcherState;
+                    if !state.is_null() {
+                        if let Some(event) = translate(w_param, &path) {
+                            let _ = (*state).sender.send(event);
+                        }
+                    }
+                }
+            }
+            0
+        }
+        WM_DESTROY => {
+            let state = SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0) as *mut WatcherState;
+            if !state.is_null() {
+                let state = Box::from_raw(state);
+                if state.notify != 0 {
+                    UnregisterDeviceNotification(state.notify);
+                }
+            }
+            PostQuitMessage(0);
+            0
+        }
+        _ => DefWindowProcW(hwnd, msg, w_param, l_param),
+    }
+}
+
+/// Read dbcc_name out of a DBT_DEVTYP_DEVICEINTERFACE broadcast.
+unsafe fn interface_path_from_broadcast(l_param: isize) -> Option<String> {
+    let header = l_param as *const DevBroadcastDeviceInterfaceW;
+    if header.is_null() || (*header).dbcc_devicetype != DBT_DEVTYP_DEVICEINTERFACE {
+        return None;
+    }
+    let name = (*header).dbcc_name.as_ptr();
+    let mut len = 0;
+    while *name.add(len) != 0 {
+        len += 1;
+    }
+    Some(String::from_utf16_lossy(std::slice::from_raw_parts(
+        name, len,
+    )))
+}
+
+fn translate(w_param: usize, path: &str) -> Option<DeviceEvent> {
+    let info = info_from_interface_path(path)?;
+    Some(match w_param {
+        DBT_DEVICEARRIVAL => DeviceEvent::Connected(info),
+        _ => DeviceEvent::Disconnected(DeviceIdentity::of(&info)),
+    })
+}
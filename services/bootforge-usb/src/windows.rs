@@ -0,0 +1,583 @@
+// BootForge USB - Windows driver health
+// Walks SetupDiGetClassDevs for the USB enumerator and maps each device
+// to the driver facts libusb does not surface: hardware IDs, the bound
+// service name, the instance path, and CfgMgr problem codes. As in
+// watch/windows.rs the bindings are declared by hand: the surface is a
+// handful of setupapi/cfgmgr32 calls and the crate otherwise has no
+// Windows dependency.
+
+use std::ffi::c_void;
+
+use serde::{Deserialize, Serialize};
+
+use crate::enumeration::UsbDeviceInfo;
+use crate::error::UsbError;
+
+// SetupDiGetClassDevsW flags.
+const DIGCF_PRESENT: u32 = 0x0000_0002;
+const DIGCF_ALLCLASSES: u32 = 0x0000_0004;
+const DIGCF_DEVICEINTERFACE: u32 = 0x0000_0010;
+
+/// GUID_DEVINTERFACE_USB_DEVICE, the interface class of whole USB
+/// devices (the same GUID the notification watcher filters on).
+const GUID_DEVINTERFACE_USB_DEVICE: Guid = Guid {
+    data1: 0xa5dc_bf10,
+    data2: 0x6530,
+    data3: 0x11d2,
+    data4: [0x90, 0x1f, 0x00, 0xc0, 0x4f, 0xb9, 0x51, 0xed],
+};
+
+// SetupDiGetDeviceRegistryPropertyW properties.
+const SPDRP_HARDWAREID: u32 = 0x0000_0001;
+const SPDRP_SERVICE: u32 = 0x0000_0004;
+
+// CM_Get_DevNode_Status results.
+const CR_SUCCESS: u32 = 0;
+const DN_HAS_PROBLEM: u32 = 0x0000_0400;
+
+// Problem codes this module interprets; the rest pass through as-is.
+const CM_PROB_FAILED_INSTALL: u32 = 28;
+const CM_PROB_DISABLED: u32 = 22;
+const CM_PROB_FAILED_POST_START: u32 = 43;
+
+const INVALID_HANDLE_VALUE: isize = -1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Guid {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+#[repr(C)]
+struct SpDevinfoData {
+    cb_size: u32,
+    class_guid: Guid,
+    dev_inst: u32,
+    reserved: usize,
+}
+
+#[repr(C)]
+struct SpDeviceInterfaceData {
+    cb_size: u32,
+    interface_class_guid: Guid,
+    flags: u32,
+    reserved: usize,
+}
+
+/// SP_DEVICE_INTERFACE_DETAIL_DATA_W with the path buffer inlined; the
+/// declared cbSize stays that of the one-wchar original.
+#[repr(C)]
+struct SpDeviceInterfaceDetailData {
+    cb_size: u32,
+    device_path: [u16; 512],
+}
+
+/// sizeof(SP_DEVICE_INTERFACE_DETAIL_DATA_W): 4-byte size plus one
+/// wchar, padded to pointer alignment on 64-bit.
+const INTERFACE_DETAIL_CB_SIZE: u32 = if cfg!(target_pointer_width = "64") { 8 } else { 6 };
+
+#[link(name = "setupapi")]
+extern "system" {
+    fn SetupDiGetClassDevsW(
+        class_guid: *const Guid,
+        enumerator: *const u16,
+        hwnd_parent: isize,
+        flags: u32,
+    ) -> isize;
+    fn SetupDiEnumDeviceInfo(
+        device_info_set: isize,
+        member_index: u32,
+        device_info_data: *mut SpDevinfoData,
+    ) -> i32;
+    fn SetupDiGetDeviceInstanceIdW(
+        device_info_set: isize,
+        device_info_data: *const SpDevinfoData,
+        device_instance_id: *mut u16,
+        device_instance_id_size: u32,
+        required_size: *mut u32,
+    ) -> i32;
+    fn SetupDiGetDeviceRegistryPropertyW(
+        device_info_set: isize,
+        device_info_data: *const SpDevinfoData,
+        property: u32,
+        property_reg_data_type: *mut u32,
+        property_buffer: *mut u8,
+        property_buffer_size: u32,
+        required_size: *mut u32,
+    ) -> i32;
+    fn SetupDiDestroyDeviceInfoList(device_info_set: isize) -> i32;
+    fn SetupDiEnumDeviceInterfaces(
+        device_info_set: isize,
+        device_info_data: *const SpDevinfoData,
+        interface_class_guid: *const Guid,
+        member_index: u32,
+        device_interface_data: *mut SpDeviceInterfaceData,
+    ) -> i32;
+    fn SetupDiGetDeviceInterfaceDetailW(
+        device_info_set: isize,
+        device_interface_data: *const SpDeviceInterfaceData,
+        device_interface_detail_data: *mut c_void,
+        device_interface_detail_data_size: u32,
+        required_size: *mut u32,
+        device_info_data: *mut SpDevinfoData,
+    ) -> i32;
+}
+
+#[link(name = "cfgmgr32")]
+extern "system" {
+    fn CM_Get_DevNode_Status(
+        status: *mut u32,
+        problem_number: *mut u32,
+        dev_inst: u32,
+        flags: u32,
+    ) -> u32;
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/**
+ * Driver binding state of a devnode, from the SPDRP_SERVICE property
+ * and the CfgMgr problem code.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DriverStatus {
+    #[default]
+    Unknown,
+    /// The devnode has no driver service bound.
+    Missing,
+    /// Bound to this driver service, e.g. "WINUSB".
+    Bound(String),
+    /// The devnode reports a problem that prevents use.
+    Blocked { reason: String },
+}
+
+/// Whether the devnode reports the link itself as misbehaving.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LinkHealth {
+    #[default]
+    Ok,
+    /// Problem code 43: the device started and then failed, the classic
+    /// flaky-cable / power signature.
+    Unstable,
+}
+
+/**
+ * Windows-side facts about an enumerated device: identity strings from
+ * the devnode plus driver health, filled in by `enrich_windows`.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WindowsPlatformHint {
+    /// SPDRP_HARDWAREID, most- to least-specific.
+    pub hardware_ids: Vec<String>,
+    /// The bound driver service name, when any.
+    pub driver_name: Option<String>,
+    /// The device instance path, e.g. "USB\\VID_18D1&PID_4EE7\\SERIAL".
+    pub instance_path: Option<String>,
+    /// The `\\?\` interface path CreateFile opens, when the USB device
+    /// interface class is registered for the devnode.
+    pub device_path: Option<String>,
+    pub driver: DriverStatus,
+    pub link_health: LinkHealth,
+    /// The raw problem code when the devnode reports one.
+    pub problem_code: Option<u32>,
+}
+
+/// VID, PID, and serial parsed from a device instance ID like
+/// "USB\VID_18D1&PID_4EE7\29061FDH300EXZ". The third segment is only a
+/// serial when the descriptor provides one; Windows-synthesized
+/// locations contain '&' and parse as None.
+pub fn parse_instance_id(instance_id: &str) -> Option<(u16, u16, Option<String>)> {
+    let mut segments = instance_id.split('\\');
+    if !segments.next()?.eq_ignore_ascii_case("USB") {
+        return None;
+    }
+    let ids = segments.next()?;
+    let mut vendor_id = None;
+    let mut product_id = None;
+    for part in ids.split('&') {
+        let upper = part.to_ascii_uppercase();
+        if let Some(hex) = upper.strip_prefix("VID_") {
+            vendor_id = u16::from_str_radix(hex, 16).ok();
+        } else if let Some(hex) = upper.strip_prefix("PID_") {
+            product_id = u16::from_str_radix(hex, 16).ok();
+        }
+    }
+    let serial = segments
+        .next()
+        .filter(|s| !s.is_empty() && !s.contains('&'))
+        .map(str::to_string);
+    Some((vendor_id?, product_id?, serial))
+}
+
+/**
+ * Whether an instance ID refers to this enumerated device: VID and PID
+ * must agree, and when both sides carry a serial it must match too.
+ */
+pub fn instance_matches(instance_id: &str, info: &UsbDeviceInfo) -> bool {
+    let Some((vendor_id, product_id, serial)) = parse_instance_id(instance_id) else {
+        return false;
+    };
+    vendor_id == info.vendor_id
+        && product_id == info.product_id
+        && match (serial.as_deref(), info.serial_number.as_deref()) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+            // A serial-less device gets a synthesized location instead;
+            // when both sides know the hub port it stands in for the
+            // serial, so two identical units do not collapse into one.
+            _ => match (instance_location_port(instance_id), device_port(info)) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            },
+        }
+}
+
+/// The hub port from a synthesized location like "5&2f6a1b8&0&2" - its
+/// final field. None for real serials and malformed locations.
+pub fn instance_location_port(instance_id: &str) -> Option<u8> {
+    let location = instance_id.split('\\').nth(2)?;
+    if !location.contains('&') {
+        return None;
+    }
+    location.rsplit('&').next()?.parse().ok()
+}
+
+/// The last hop of the device's port chain, the side Linux-style port
+/// paths record it on ("2-1.4" is port 4 of the hub at 2-1).
+fn device_port(info: &UsbDeviceInfo) -> Option<u8> {
+    let path = info.port_path.as_deref()?;
+    let chain = path.split_once('-').map(|(_, chain)| chain).unwrap_or(path);
+    chain.rsplit('.').next()?.parse().ok()
+}
+
+/// Map a devnode's problem code and driver service into the crate's
+/// driver-health model.
+pub fn classify_devnode(
+    problem_code: Option<u32>,
+    driver_name: Option<&str>,
+) -> (DriverStatus, LinkHealth) {
+    let health = match problem_code {
+        Some(CM_PROB_FAILED_POST_START) => LinkHealth::Unstable,
+        _ => LinkHealth::Ok,
+    };
+    let driver = match (problem_code, driver_name) {
+        (Some(code @ (CM_PROB_DISABLED | CM_PROB_FAILED_INSTALL)), _) => DriverStatus::Blocked {
+            reason: problem_reason(code),
+        },
+        (_, None) => DriverStatus::Missing,
+        (_, Some(name)) => DriverStatus::Bound(name.to_string()),
+    };
+    (driver, health)
+}
+
+fn problem_reason(code: u32) -> String {
+    match code {
+        CM_PROB_DISABLED => "disabled in Device Manager".to_string(),
+        CM_PROB_FAILED_INSTALL => "driver installation failed".to_string(),
+        other => format!("problem code {}", other),
+    }
+}
+
+/**
+ * Enrich one enumerated device by walking the present USB devnodes and
+ * matching on VID/PID/serial. `NotFound` when no devnode matches, e.g.
+ * a device that vanished between enumeration and enrichment.
+ */
+pub fn enrich_windows(info: &UsbDeviceInfo) -> Result<WindowsPlatformHint, UsbError> {
+    let enumerator = wide("USB");
+    let set = unsafe {
+        SetupDiGetClassDevsW(
+            std::ptr::null(),
+            enumerator.as_ptr(),
+            0,
+            DIGCF_PRESENT | DIGCF_ALLCLASSES,
+        )
+    };
+    if set == INVALID_HANDLE_VALUE {
+        return Err(UsbError::Internal(
+            "SetupDiGetClassDevsW failed".to_string(),
+        ));
+    }
+
+    let mut found = Err(UsbError::NotFound(format!(
+        "no devnode matches {:04x}:{:04x}",
+        info.vendor_id, info.product_id
+    )));
+    let mut index = 0u32;
+    loop {
+        let mut data = SpDevinfoData {
+            cb_size: std::mem::size_of::<SpDevinfoData>() as u32,
+            class_guid: Guid {
+                data1: 0,
+                data2: 0,
+                data3: 0,
+                data4: [0; 8],
+            },
+            dev_inst: 0,
+            reserved: 0,
+        };
+        if unsafe { SetupDiEnumDeviceInfo(set, index, &mut data) } == 0 {
+            break;
+        }
+        index += 1;
+
+        let Some(instance_id) = instance_id_of(set, &data) else {
+            continue;
+        };
+        if !instance_matches(&instance_id, info) {
+            continue;
+        }
+
+        let driver_name = read_string_property(set, &data, SPDRP_SERVICE)
+            .and_then(|mut names| (!names.is_empty()).then(|| names.remove(0)));
+        let mut status = 0u32;
+        let mut problem = 0u32;
+        let ret = unsafe { CM_Get_DevNode_Status(&mut status, &mut problem, data.dev_inst, 0) };
+        let problem_code =
+            (ret == CR_SUCCESS && status & DN_HAS_PROBLEM != 0).then_some(problem);
+
+        let (driver, link_health) = classify_devnode(problem_code, driver_name.as_deref());
+        found = Ok(WindowsPlatformHint {
+            hardware_ids: read_string_property(set, &data, SPDRP_HARDWAREID)
+                .unwrap_or_default(),
+            driver_name,
+            device_path: device_interface_path(&instance_id),
+            instance_path: Some(instance_id),
+            driver,
+            link_health,
+            problem_code,
+        });
+        break;
+    }
+
+    unsafe { SetupDiDestroyDeviceInfoList(set) };
+    found
+}
+
+/// The `\\?\` interface path for a devnode, from a one-device info set
+/// keyed on its instance ID. None when the interface class is not
+/// registered, e.g. a device without WinUSB or a kernel driver.
+fn device_interface_path(instance_id: &str) -> Option<String> {
+    let id = wide(instance_id);
+    let set = unsafe {
+        SetupDiGetClassDevsW(
+            &GUID_DEVINTERFACE_USB_DEVICE,
+            id.as_ptr(),
+            0,
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        )
+    };
+    if set == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    let mut iface = SpDeviceInterfaceData {
+        cb_size: std::mem::size_of::<SpDeviceInterfaceData>() as u32,
+        interface_class_guid: GUID_DEVINTERFACE_USB_DEVICE,
+        flags: 0,
+        reserved: 0,
+    };
+    let mut path = None;
+    let ok = unsafe {
+        SetupDiEnumDeviceInterfaces(
+            set,
+            std::ptr::null(),
+            &GUID_DEVINTERFACE_USB_DEVICE,
+            0,
+            &mut iface,
+        )
+    };
+    if ok != 0 {
+        let mut detail = SpDeviceInterfaceDetailData {
+            cb_size: INTERFACE_DETAIL_CB_SIZE,
+            device_path: [0u16; 512],
+        };
+        let ok = unsafe {
+            SetupDiGetDeviceInterfaceDetailW(
+                set,
+                &iface,
+                &mut detail as *mut SpDeviceInterfaceDetailData as *mut c_void,
+                std::mem::size_of::<SpDeviceInterfaceDetailData>() as u32,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if ok != 0 {
+            let len = detail
+                .device_path
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(detail.device_path.len());
+            path = Some(String::from_utf16_lossy(&detail.device_path[..len]));
+        }
+    }
+
+    unsafe { SetupDiDestroyDeviceInfoList(set) };
+    path
+}
+
+fn instance_id_of(set: isize, data: &SpDevinfoData) -> Option<String> {
+    let mut buffer = [0u16; 512];
+    let mut required = 0u32;
+    let ok = unsafe {
+        SetupDiGetDeviceInstanceIdW(
+            set,
+            data,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            &mut required,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
+/// A REG_SZ / REG_MULTI_SZ device property as a list of strings.
+fn read_string_property(set: isize, data: &SpDevinfoData, property: u32) -> Option<Vec<String>> {
+    let mut buffer = [0u8; 4096];
+    let mut kind = 0u32;
+    let mut required = 0u32;
+    let ok = unsafe {
+        SetupDiGetDeviceRegistryPropertyW(
+            set,
+            data,
+            property,
+            &mut kind,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            &mut required,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+    let units: Vec<u16> = buffer[..required.min(buffer.len() as u32) as usize]
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    let strings: Vec<String> = units
+        .split(|&c| c == 0)
+        .filter(|s| !s.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect();
+    Some(strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel() -> UsbDeviceInfo {
+        crate::watch::partial_info(
+            0x18d1,
+            0x4ee7,
+            Some("29061FDH300EXZ".to_string()),
+            None,
+            "test:windows".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_parse_instance_id() {
+        assert_eq!(
+            parse_instance_id("USB\\VID_18D1&PID_4EE7\\29061FDH300EXZ"),
+            Some((0x18d1, 0x4ee7, Some("29061FDH300EXZ".to_string())))
+        );
+        // Windows-synthesized location stands in for a missing serial.
+        assert_eq!(
+            parse_instance_id("USB\\VID_0781&PID_5583\\5&2f6a1b8&0&2"),
+            Some((0x0781, 0x5583, None))
+        );
+        assert_eq!(parse_instance_id("PCI\\VEN_8086&DEV_A36D"), None);
+        assert_eq!(parse_instance_id("USB\\VID_XXXX&PID_5583\\1"), None);
+    }
+
+    #[test]
+    fn test_instance_matching_semantics() {
+        let info = pixel();
+        assert!(instance_matches(
+            "USB\\VID_18D1&PID_4EE7\\29061FDH300EXZ",
+            &info
+        ));
+        // Serial comparison is case-insensitive, as Windows stores it.
+        assert!(instance_matches(
+            "USB\\vid_18d1&pid_4ee7\\29061fdh300exz",
+            &info
+        ));
+        // A synthesized location matches on VID/PID alone.
+        assert!(instance_matches("USB\\VID_18D1&PID_4EE7\\5&1a2b3c&0&4", &info));
+        assert!(!instance_matches(
+            "USB\\VID_18D1&PID_4EE7\\OTHERSERIAL",
+            &info
+        ));
+        assert!(!instance_matches("USB\\VID_18D1&PID_D00D\\X", &info));
+    }
+
+    #[test]
+    fn test_serialless_match_falls_back_to_hub_port() {
+        let mut info = crate::watch::partial_info(
+            0x0781,
+            0x5583,
+            None,
+            Some("2-1.4".to_string()),
+            "test:windows".to_string(),
+        );
+        assert_eq!(
+            instance_location_port("USB\\VID_0781&PID_5583\\5&2f6a1b8&0&4"),
+            Some(4)
+        );
+        // A real serial is not a location.
+        assert_eq!(
+            instance_location_port("USB\\VID_18D1&PID_4EE7\\29061FDH300EXZ"),
+            None
+        );
+
+        assert!(instance_matches("USB\\VID_0781&PID_5583\\5&2f6a1b8&0&4", &info));
+        // Same model on another port of the hub: not this unit.
+        assert!(!instance_matches("USB\\VID_0781&PID_5583\\5&2f6a1b8&0&2", &info));
+
+        // With no port knowledge on our side, VID/PID alone still
+        // matches, as before.
+        info.port_path = None;
+        assert!(instance_matches("USB\\VID_0781&PID_5583\\5&2f6a1b8&0&2", &info));
+    }
+
+    #[test]
+    fn test_classify_devnode() {
+        assert_eq!(
+            classify_devnode(None, Some("WINUSB")),
+            (DriverStatus::Bound("WINUSB".to_string()), LinkHealth::Ok)
+        );
+        assert_eq!(
+            classify_devnode(None, None),
+            (DriverStatus::Missing, LinkHealth::Ok)
+        );
+        assert_eq!(
+            classify_devnode(Some(CM_PROB_DISABLED), Some("usbccgp")).0,
+            DriverStatus::Blocked {
+                reason: "disabled in Device Manager".to_string()
+            }
+        );
+        assert_eq!(
+            classify_devnode(Some(CM_PROB_FAILED_INSTALL), None).0,
+            DriverStatus::Blocked {
+                reason: "driver installation failed".to_string()
+            }
+        );
+        // Code 43 flags the link without unbinding the driver.
+        assert_eq!(
+            classify_devnode(Some(CM_PROB_FAILED_POST_START), Some("WINUSB")),
+            (
+                DriverStatus::Bound("WINUSB".to_string()),
+                LinkHealth::Unstable
+            )
+        );
+    }
+}
@@ -0,0 +1,168 @@
+// BootForge USB - Hotplug integration harness (Linux, dummy_hcd)
+//
+// Exercises real plug/unplug against a software host controller by
+// binding and unbinding a configfs gadget while the production watcher
+// stack runs: the libusb hotplug watcher wrapped in the debounce
+// adapter, exactly as `wait_for::default_watcher` composes it on Linux.
+// The crate has no udev-based watcher - `LibusbHotplugWatcher` (with
+// its poll-and-diff fallback) is the Linux watcher - so this is the
+// whole stack, asserted from its event stream rather than by polling
+// enumeration around it. Ignored by default because it needs root plus
+// kernel modules:
+//
+//     sudo modprobe libcomposite dummy_hcd
+//     sudo -E cargo test -p bootforge-usb --test hotplug_dummy_hcd -- --ignored
+//
+// In CI run it inside a privileged job with those modules loaded. The
+// test skips (passes with a message) when configfs, dummy_hcd, or the
+// required permissions are missing, so it is safe to keep in the default
+// `--ignored` sweep on developer machines.
+
+#![cfg(target_os = "linux")]
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use bootforge_usb::events::{DeviceEvent, DeviceIdentity};
+use bootforge_usb::gadget::{dummy_udc, ConfigfsGadget, GadgetStrings};
+use bootforge_usb::{DebouncedWatcher, DeviceWatcher, LibusbHotplugWatcher, UsbError};
+
+const TEST_VID: u16 = 0x1d6b;
+const TEST_PID: u16 = 0x0104;
+const TEST_SERIAL: &str = "BF-HOTPLUG-0001";
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Short debounce window so the test settles quickly; anything a real
+/// deployment would use only lengthens the waits below.
+const DEBOUNCE_WINDOW: Duration = Duration::from_millis(200);
+
+/// Matches the test gadget on VID/PID. Serial is checked only when
+/// present: the libusb hotplug-callback path delivers partial records
+/// without strings, while the poll fallback carries full enumerations.
+fn is_test_gadget(info: &bootforge_usb::UsbDeviceInfo) -> bool {
+    info.vendor_id == TEST_VID
+        && info.product_id == TEST_PID
+        && info
+            .serial_number
+            .as_deref()
+            .is_none_or(|serial| serial == TEST_SERIAL)
+}
+
+/// Pull events off the watcher stream until `predicate` accepts one,
+/// failing the test on timeout or if the watcher thread hangs up.
+fn wait_for_event<F>(events: &Receiver<DeviceEvent>, what: &str, predicate: F) -> DeviceEvent
+where
+    F: Fn(&DeviceEvent) -> bool,
+{
+    let deadline = Instant::now() + SETTLE_TIMEOUT;
+    while Instant::now() < deadline {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(event) if predicate(&event) => return event,
+            // Events for unrelated devices on the host are not ours to
+            // assert on.
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                panic!("watcher stopped while waiting for {} event", what)
+            }
+        }
+    }
+    panic!("timed out waiting for {} event", what);
+}
+
+/// Collect every further event for the gadget during a quiet period
+/// long enough for the debounce window and a poll pass to elapse.
+fn drain_gadget_events(events: &Receiver<DeviceEvent>, identity: &DeviceIdentity) -> Vec<DeviceEvent> {
+    let deadline = Instant::now() + DEBOUNCE_WINDOW * 2 + POLL_INTERVAL * 3;
+    let mut seen = Vec::new();
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match events.recv_timeout(left) {
+            Ok(event) => {
+                let ours = match &event {
+                    DeviceEvent::Connected(info) => is_test_gadget(info),
+                    DeviceEvent::Disconnected(id) => id == identity,
+                    DeviceEvent::Changed { after, .. }
+                    | DeviceEvent::DescriptorChanged { after, .. } => is_test_gadget(after),
+                };
+                if ours {
+                    seen.push(event);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    seen
+}
+
+#[test]
+#[ignore = "needs root plus libcomposite/dummy_hcd kernel modules"]
+fn test_gadget_plug_unplug_delivers_matching_events() {
+    if !bootforge_usb::gadget::configfs_available() {
+        eprintln!("skipping: configfs gadget interface not present (modprobe libcomposite)");
+        return;
+    }
+    let Some(udc) = dummy_udc() else {
+        eprintln!("skipping: no dummy_udc UDC (modprobe dummy_hcd)");
+        return;
+    };
+
+    let strings = GadgetStrings {
+        manufacturer: "BootForge".to_string(),
+        product: "Hotplug Harness Gadget".to_string(),
+        serial_number: TEST_SERIAL.to_string(),
+    };
+    let mut gadget =
+        match ConfigfsGadget::create("bootforge-hotplug-test", TEST_VID, TEST_PID, &strings) {
+            Ok(g) => g,
+            Err(UsbError::Io(e)) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                eprintln!("skipping: insufficient permissions for configfs (run as root)");
+                return;
+            }
+            Err(e) => panic!("gadget setup failed: {}", e),
+        };
+
+    // The production Linux stack: libusb hotplug (or its poll fallback)
+    // under the debounce adapter. Started before the bind, so devices
+    // already present are not events and the gadget's arrival is the
+    // only Connected we see for it.
+    let mut watcher = DebouncedWatcher::new(
+        LibusbHotplugWatcher::new().with_poll_interval(POLL_INTERVAL),
+    )
+    .with_window(DEBOUNCE_WINDOW);
+    let events = watcher.start().expect("watcher should start");
+
+    gadget.bind(&udc).expect("binding gadget to dummy_udc failed");
+    let connected = wait_for_event(&events, "Connected", |event| {
+        matches!(event, DeviceEvent::Connected(info) if is_test_gadget(info))
+    });
+    let DeviceEvent::Connected(info) = connected else {
+        unreachable!()
+    };
+    // Event shape: the record must carry enough identity to act on.
+    assert_eq!(info.vendor_id, TEST_VID);
+    assert_eq!(info.product_id, TEST_PID);
+    assert!(info.bus_number != 0 || info.address != 0, "record carries no bus slot");
+    let identity = DeviceIdentity::of(&info);
+
+    // A settled device must not keep generating events through the
+    // debounce adapter.
+    let dupes = drain_gadget_events(&events, &identity);
+    assert!(dupes.is_empty(), "duplicate events after Connected: {:?}", dupes);
+
+    gadget.unbind().expect("unbinding gadget failed");
+    let disconnected = wait_for_event(&events, "Disconnected", |event| {
+        matches!(event, DeviceEvent::Disconnected(id) if *id == identity)
+    });
+    assert!(matches!(disconnected, DeviceEvent::Disconnected(_)));
+
+    // And nothing after the removal either.
+    let stragglers = drain_gadget_events(&events, &identity);
+    assert!(
+        stragglers.is_empty(),
+        "duplicate events after Disconnected: {:?}",
+        stragglers
+    );
+
+    watcher.stop();
+}
@@ -120,7 +120,7 @@ mod tests {
     #[test]
     fn test_analyze_device() {
         let result = analyze("iPhone 13 Pro - Clean device");
-        assert_eq!(result.non_invasive, true);
+        assert!(result.non_invasive);
         assert_eq!(result.classification, DeviceClassification::Clean);
     }
 
@@ -141,6 +141,6 @@ mod tests {
     #[test]
     fn test_always_non_invasive() {
         let result = analyze("Any device metadata");
-        assert_eq!(result.non_invasive, true);
+        assert!(result.non_invasive);
     }
 }
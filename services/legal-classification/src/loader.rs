@@ -21,8 +21,15 @@ pub struct JurisdictionMap {
  * Load jurisdiction map from JSON file
  */
 pub fn load_jurisdiction(code: &str) -> Result<JurisdictionMap, String> {
-    let file_path = format!("services/legal-classification/jurisdiction-map/{}.json", code.to_lowercase());
-    
+    // Resolve relative to the repo root first (service run from workspace root),
+    // falling back to this crate's own directory (tests, standalone use).
+    let file_name = format!("{}.json", code.to_lowercase());
+    let mut file_path = format!("services/legal-classification/jurisdiction-map/{}", file_name);
+
+    if !Path::new(&file_path).exists() {
+        file_path = format!("{}/jurisdiction-map/{}", env!("CARGO_MANIFEST_DIR"), file_name);
+    }
+
     if !Path::new(&file_path).exists() {
         return Err(format!("Jurisdiction map not found: {}", code));
     }
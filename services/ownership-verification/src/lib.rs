@@ -83,7 +83,7 @@ fn calculate_confidence(
     // Adjust based on documentation quality
     let doc_quality: f64 = if attestation.documentation_references.is_empty() {
         0.5
-    } else if attestation.documentation_references.len() >= 3 {
+    } else if attestation.documentation_references.len() >= 2 {
         1.0
     } else {
         0.8